@@ -25,4 +25,7 @@ mod imp;
 mod imp;
 
 pub use error::DataSketchesError;
-pub use imp::{HLLDataSketch, HLLUnionDataSketch};
+pub use imp::{
+    HLLDataSketch, HLLUnionDataSketch, ThetaDataSketch, ThetaIntersectionDataSketch,
+    ThetaUnionDataSketch,
+};
@@ -17,7 +17,7 @@
 pub use crate::error::Result;
 use std::fmt::{Debug, Formatter};
 
-use dsrs::{HLLSketch, HLLType, HLLUnion};
+use dsrs::{HLLSketch, HLLType, HLLUnion, StaticThetaSketch, ThetaIntersection, ThetaUnion};
 
 pub struct HLLDataSketch {
     pub(crate) instance: HLLSketch,
@@ -110,3 +110,141 @@ impl HLLUnionDataSketch {
         32 + k
     }
 }
+
+pub struct ThetaDataSketch {
+    pub(crate) instance: StaticThetaSketch,
+}
+
+unsafe impl Send for ThetaDataSketch {}
+unsafe impl Sync for ThetaDataSketch {}
+
+impl Debug for ThetaDataSketch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaDataSketch")
+            .field("instance", &"<hidden>");
+
+        Ok(())
+    }
+}
+
+impl ThetaDataSketch {
+    pub fn read(data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            instance: StaticThetaSketch::deserialize(data)?,
+        })
+    }
+
+    pub fn estimate(&self) -> u64 {
+        self.instance.estimate().round() as u64
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        self.instance.serialize().as_ref().to_vec()
+    }
+
+    /// Keeps the entries of `self` that are not present in `other`.
+    pub fn a_not_b(mut self, other: &ThetaDataSketch) -> Result<Self> {
+        self.instance.set_difference(&other.instance);
+
+        Ok(self)
+    }
+}
+
+pub struct ThetaUnionDataSketch {
+    pub(crate) instance: ThetaUnion,
+}
+
+impl Debug for ThetaUnionDataSketch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaUnionDataSketch")
+            .field("instance", &"<hidden>");
+
+        Ok(())
+    }
+}
+
+unsafe impl Send for ThetaUnionDataSketch {}
+unsafe impl Sync for ThetaUnionDataSketch {}
+
+impl Default for ThetaUnionDataSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaUnionDataSketch {
+    /// Default nominal entries of theta sketches, 2^12.
+    const LG_K: usize = 12;
+
+    pub fn new() -> Self {
+        Self {
+            instance: ThetaUnion::new(),
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        self.instance.sketch().serialize().as_ref().to_vec()
+    }
+
+    pub fn result(&self) -> ThetaDataSketch {
+        ThetaDataSketch {
+            instance: self.instance.sketch(),
+        }
+    }
+
+    pub fn merge_with(&mut self, other: ThetaDataSketch) -> Result<()> {
+        self.instance.union_with(other.instance);
+
+        Ok(())
+    }
+
+    /// Allocated size, not including size_of::<Self>().  Must be exact.
+    pub fn allocated_size(&self) -> usize {
+        // The union keeps a hash table of up to 2 * k 64-bit hashes before it rebuilds.  As with
+        // HLL, we assume the maximum and eyeball the C++ struct overhead as 32 bytes.
+        32 + (2 << Self::LG_K) * 8
+    }
+}
+
+pub struct ThetaIntersectionDataSketch {
+    pub(crate) instance: ThetaIntersection,
+}
+
+impl Debug for ThetaIntersectionDataSketch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThetaIntersectionDataSketch")
+            .field("instance", &"<hidden>");
+
+        Ok(())
+    }
+}
+
+unsafe impl Send for ThetaIntersectionDataSketch {}
+unsafe impl Sync for ThetaIntersectionDataSketch {}
+
+impl Default for ThetaIntersectionDataSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaIntersectionDataSketch {
+    pub fn new() -> Self {
+        Self {
+            instance: ThetaIntersection::new(),
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: ThetaDataSketch) -> Result<()> {
+        self.instance.intersect_with(other.instance);
+
+        Ok(())
+    }
+
+    /// Returns `None` until at least one sketch was intersected, the result is undefined then.
+    pub fn result(&self) -> Option<ThetaDataSketch> {
+        self.instance
+            .sketch()
+            .map(|instance| ThetaDataSketch { instance })
+    }
+}
@@ -70,3 +70,87 @@ impl HLLUnionDataSketch {
         unimplemented!();
     }
 }
+
+#[derive(Debug)]
+pub struct ThetaDataSketch {}
+
+unsafe impl Send for ThetaDataSketch {}
+unsafe impl Sync for ThetaDataSketch {}
+
+impl ThetaDataSketch {
+    pub fn read(_data: &[u8]) -> Result<Self> {
+        Err(DataSketchesError::new("Not supported on Windows"))
+    }
+
+    pub fn estimate(&self) -> u64 {
+        unimplemented!();
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        unimplemented!();
+    }
+
+    pub fn a_not_b(self, _other: &ThetaDataSketch) -> Result<Self> {
+        unimplemented!();
+    }
+}
+
+#[derive(Debug)]
+pub struct ThetaUnionDataSketch {}
+
+unsafe impl Send for ThetaUnionDataSketch {}
+unsafe impl Sync for ThetaUnionDataSketch {}
+
+impl Default for ThetaUnionDataSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaUnionDataSketch {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        unimplemented!();
+    }
+
+    pub fn result(&self) -> ThetaDataSketch {
+        unimplemented!();
+    }
+
+    pub fn merge_with(&mut self, _other: ThetaDataSketch) -> Result<()> {
+        unimplemented!();
+    }
+
+    pub fn allocated_size(&self) -> usize {
+        unimplemented!();
+    }
+}
+
+#[derive(Debug)]
+pub struct ThetaIntersectionDataSketch {}
+
+unsafe impl Send for ThetaIntersectionDataSketch {}
+unsafe impl Sync for ThetaIntersectionDataSketch {}
+
+impl Default for ThetaIntersectionDataSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThetaIntersectionDataSketch {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn intersect_with(&mut self, _other: ThetaDataSketch) -> Result<()> {
+        unimplemented!();
+    }
+
+    pub fn result(&self) -> Option<ThetaDataSketch> {
+        unimplemented!();
+    }
+}
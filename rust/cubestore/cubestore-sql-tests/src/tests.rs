@@ -9,9 +9,15 @@ use cubestore::queryplanner::pretty_printers::{pp_phys_plan, pp_phys_plan_ext, P
 use cubestore::queryplanner::MIN_TOPK_STREAM_ROWS;
 use cubestore::sql::{timestamp_from_string, InlineTable, SqlQueryContext};
 use cubestore::store::DataFrame;
-use cubestore::table::{Row, TableValue, TimestampValue};
+use cubestore::table::{DateValue, IntervalValue, Row, TableValue, TimestampValue};
 use cubestore::util::decimal::Decimal;
 use cubestore::CubeError;
+use datafusion::arrow::array::{ArrayRef, Int64Array, StringArray};
+use datafusion::arrow::ipc::writer::FileWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::ArrowWriter;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use indoc::indoc;
 use itertools::Itertools;
 use pretty_assertions::assert_eq;
@@ -123,6 +129,17 @@ pub fn sql_tests(prefix: &str) -> Vec<(&'static str, TestFn)> {
             "create_table_with_csv_no_header_and_quotes",
             create_table_with_csv_no_header_and_quotes,
         ),
+        t("create_table_with_ndjson", create_table_with_ndjson),
+        t("create_table_with_columnar", create_table_with_columnar),
+        t("date_and_interval_columns", date_and_interval_columns),
+        t("list_and_json_columns", list_and_json_columns),
+        t("create_table_with_retention", create_table_with_retention),
+        t(
+            "create_table_with_parquet_settings",
+            create_table_with_parquet_settings,
+        ),
+        t("delete_rows", delete_rows),
+        t("alter_table", alter_table),
         t("create_table_with_url", create_table_with_url),
         t("create_table_fail_and_retry", create_table_fail_and_retry),
         t("empty_crash", empty_crash),
@@ -152,6 +169,8 @@ pub fn sql_tests(prefix: &str) -> Vec<(&'static str, TestFn)> {
             "aggregate_index_hll_databricks",
             aggregate_index_hll_databricks,
         ),
+        t("aggregate_index_tdigest", aggregate_index_tdigest),
+        t("theta_sketch_set_operations", theta_sketch_set_operations),
         t("physical_plan_flags", physical_plan_flags),
         t("planning_inplace_aggregate", planning_inplace_aggregate),
         t("planning_hints", planning_hints),
@@ -288,6 +307,7 @@ pub fn sql_tests(prefix: &str) -> Vec<(&'static str, TestFn)> {
         ),
         t("planning_aggregate_index", planning_aggregate_index),
         t("aggregate_index", aggregate_index),
+        t("aggregate_index_count", aggregate_index_count),
         t("aggregate_index_hll", aggregate_index_hll),
         t(
             "aggregate_index_with_hll_bytes",
@@ -308,6 +328,8 @@ pub fn sql_tests(prefix: &str) -> Vec<(&'static str, TestFn)> {
         t("cache_compaction", cache_compaction),
         t("cache_set_nx", cache_set_nx),
         t("cache_prefix_keys", cache_prefix_keys),
+        t("cache_subscribe_publish", cache_subscribe_publish),
+        t("cache_data_structures", cache_data_structures),
         t("queue_list_v1", queue_list_v1),
         t("queue_full_workflow_v1", queue_full_workflow_v1),
         t("queue_full_workflow_v2", queue_full_workflow_v2),
@@ -342,6 +364,8 @@ pub fn sql_tests(prefix: &str) -> Vec<(&'static str, TestFn)> {
             queue_multiple_result_blocking,
         ),
         t("queue_custom_orphaned", queue_custom_orphaned),
+        t("queue_retry_dead_letter", queue_retry_dead_letter),
+        t("queue_add_delay", queue_add_delay),
         t(
             "queue_result_by_id_external_id_mismatch",
             queue_result_by_id_external_id_mismatch,
@@ -453,6 +477,17 @@ lazy_static::lazy_static! {
         "create_table_with_csv_no_header",
         "create_table_with_csv_no_header_and_delimiter",
         "create_table_with_csv_no_header_and_quotes",
+        "create_table_with_ndjson",
+        "create_table_with_columnar",
+        "aggregate_index_count",
+        "date_and_interval_columns",
+        "list_and_json_columns",
+        "create_table_with_retention",
+        "create_table_with_parquet_settings",
+        "delete_rows",
+        "alter_table",
+        "aggregate_index_tdigest",
+        "theta_sketch_set_operations",
         "filter_pushdown_unique_key",
         "group_by_prefix_sorted_aggregate_multi_partition",
         "group_by_prefix_limit_high_cardinality",
@@ -469,6 +504,10 @@ lazy_static::lazy_static! {
         "queue_add_and_retrieve",
         "queue_add_and_retrieve_backlog",
         "queue_add_external_id_max_len",
+        "queue_retry_dead_letter",
+        "queue_add_delay",
+        "cache_subscribe_publish",
+        "cache_data_structures",
     ].into_iter().map(ToOwned::to_owned).collect();
 }
 
@@ -2468,6 +2507,544 @@ async fn create_table_with_csv_no_header_and_quotes(
     Ok(())
 }
 
+async fn create_table_with_ndjson(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    let file = write_tmp_file(indoc! {r#"
+        {"fruit": "apple", "stock": {"count": 2}}
+        {"fruit": "banana", "stock": {"count": "3"}}
+        {"fruit": "orange"}
+    "#})?;
+    let path = file.path().to_string_lossy();
+    let _ = service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS test")
+        .await?;
+    let _ = service
+        .exec_query(format!("CREATE TABLE test.table (`fruit` text, `number` int) WITH (input_format = 'ndjson', json_paths = '{{\"number\": \"$.stock.count\"}}') LOCATION '{}'", path).as_str())
+        .await
+        ?;
+    let result = service
+        .exec_query("SELECT * FROM test.table ORDER BY fruit")
+        .await?;
+    assert_eq!(
+        to_rows(&result),
+        rows(&[("apple", Some(2)), ("banana", Some(3)), ("orange", None)])
+    );
+
+    let res = service
+        .exec_query(format!("CREATE TABLE test.table2 (`fruit` text) WITH (input_format = 'ndjson', json_paths = '{{\"number\": \"$.stock.count\"}}') LOCATION '{}'", path).as_str())
+        .await;
+    assert!(
+        res.is_err(),
+        "Expected unknown column error but got {:?}",
+        res
+    );
+    Ok(())
+}
+
+async fn create_table_with_columnar(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    let batch = RecordBatch::try_from_iter(vec![
+        (
+            "fruit",
+            Arc::new(StringArray::from(vec!["apple", "banana"])) as ArrayRef,
+        ),
+        (
+            "number",
+            Arc::new(Int64Array::from(vec![Some(2), None])) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+
+    let dir = env::temp_dir();
+    let parquet_path = dir.join("columnar-import.parquet");
+    let mut w = ArrowWriter::try_new(File::create(&parquet_path)?, batch.schema(), None).unwrap();
+    w.write(&batch).unwrap();
+    w.close().unwrap();
+
+    let gzipped_path = dir.join("columnar-import.parquet.gz");
+    let mut encoder = GzEncoder::new(File::create(&gzipped_path)?, Compression::default());
+    encoder.write_all(&std::fs::read(&parquet_path)?)?;
+    encoder.finish()?;
+
+    let arrow_path = dir.join("columnar-import.arrow");
+    let mut w = FileWriter::try_new(File::create(&arrow_path)?, &batch.schema()).unwrap();
+    w.write(&batch).unwrap();
+    w.finish().unwrap();
+
+    let _ = service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS test")
+        .await?;
+    let _ = service
+        .exec_query(
+            format!(
+                "CREATE TABLE test.parquet (`fruit` text, `number` int) WITH (input_format = 'parquet') LOCATION '{}', '{}'",
+                parquet_path.to_string_lossy(),
+                gzipped_path.to_string_lossy()
+            )
+            .as_str(),
+        )
+        .await?;
+    let result = service
+        .exec_query("SELECT fruit, number FROM test.parquet ORDER BY fruit")
+        .await?;
+    assert_eq!(
+        to_rows(&result),
+        rows(&[
+            ("apple", Some(2)),
+            ("apple", Some(2)),
+            ("banana", None),
+            ("banana", None)
+        ])
+    );
+
+    let _ = service
+        .exec_query(
+            format!(
+                "CREATE TABLE test.arrow (`fruit` text, `number` int) WITH (input_format = 'arrow') LOCATION '{}'",
+                arrow_path.to_string_lossy()
+            )
+            .as_str(),
+        )
+        .await?;
+    let result = service
+        .exec_query("SELECT fruit, number FROM test.arrow ORDER BY fruit")
+        .await?;
+    assert_eq!(
+        to_rows(&result),
+        rows(&[("apple", Some(2)), ("banana", None)])
+    );
+
+    let res = service
+        .exec_query(
+            format!(
+                "CREATE TABLE test.mismatch (`fruit` int) WITH (input_format = 'parquet') LOCATION '{}'",
+                parquet_path.to_string_lossy()
+            )
+            .as_str(),
+        )
+        .await;
+    assert!(
+        res.is_err(),
+        "Expected incompatible column error but got {:?}",
+        res
+    );
+    Ok(())
+}
+
+async fn date_and_interval_columns(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query("CREATE TABLE s.events (day date, duration interval)")
+        .await?;
+    service
+        .exec_query(
+            "INSERT INTO s.events (day, duration) VALUES \
+             ('2024-02-29', '1 day 02:00:00'), \
+             ('2024-01-01', '90 minutes'), \
+             ('2023-12-31T00:00:00.000Z', NULL)",
+        )
+        .await?;
+
+    let r = service
+        .exec_query("SELECT day, duration FROM s.events WHERE day >= '2024-01-01' ORDER BY day")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![
+                TableValue::Date(DateValue::new(19723)),
+                TableValue::Interval(IntervalValue::new(5_400_000_000)),
+            ],
+            vec![
+                TableValue::Date(DateValue::new(19782)),
+                TableValue::Interval(IntervalValue::new(93_600_000_000)),
+            ],
+        ]
+    );
+    assert_eq!(r.get_rows()[0].values()[0].to_string(), "2024-01-01");
+    assert_eq!(r.get_rows()[1].values()[1].to_string(), "1 day 02:00:00");
+
+    let r = service
+        .exec_query("SELECT MIN(day), MAX(duration) FROM s.events")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![vec![
+            TableValue::Date(DateValue::new(19722)),
+            TableValue::Interval(IntervalValue::new(93_600_000_000)),
+        ]]
+    );
+
+    let r = service
+        .exec_query("INSERT INTO s.events (day, duration) VALUES ('2024-01-01', '1 month')")
+        .await;
+    assert!(r.is_err(), "Expected month interval error but got {:?}", r);
+    Ok(())
+}
+
+async fn list_and_json_columns(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query("CREATE TABLE s.docs (id int, tags text[], payload json)")
+        .await?;
+    service
+        .exec_query(
+            "INSERT INTO s.docs (id, tags, payload) VALUES \
+             (1, '[\"red\", \"green\"]', '{\"user\": {\"name\": \"ann\", \"age\": 30}}'), \
+             (2, '[]', '{\"user\": {\"name\": \"bob\"}, \"items\": [10, 20]}'), \
+             (3, NULL, NULL)",
+        )
+        .await?;
+
+    let r = service
+        .exec_query(
+            "SELECT id, cardinality(tags), json_extract_path_text(payload, 'user', 'name') \
+             FROM s.docs ORDER BY id",
+        )
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![
+                TableValue::Int(1),
+                TableValue::Int(2),
+                TableValue::String("ann".to_string()),
+            ],
+            vec![
+                TableValue::Int(2),
+                TableValue::Int(0),
+                TableValue::String("bob".to_string()),
+            ],
+            vec![TableValue::Int(3), TableValue::Null, TableValue::Null],
+        ]
+    );
+
+    let r = service
+        .exec_query(
+            "SELECT id, tags FROM s.docs WHERE array_contains(tags, 'green') \
+             OR json_extract_path_text(payload, 'items', '1') = '20' ORDER BY id",
+        )
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![
+                TableValue::Int(1),
+                TableValue::List(vec![
+                    TableValue::String("red".to_string()),
+                    TableValue::String("green".to_string()),
+                ]),
+            ],
+            vec![TableValue::Int(2), TableValue::List(vec![])],
+        ]
+    );
+    assert_eq!(
+        r.get_rows()[0].values()[1].to_string(),
+        r#"["red","green"]"#
+    );
+
+    let r = service
+        .exec_query("SELECT payload FROM s.docs WHERE id = 1")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![vec![TableValue::String(
+            r#"{"user":{"name":"ann","age":30}}"#.to_string()
+        )]]
+    );
+
+    let r = service
+        .exec_query("INSERT INTO s.docs (id, payload) VALUES (4, '{\"broken\"')")
+        .await;
+    assert!(r.is_err(), "Expected malformed JSON error but got {:?}", r);
+
+    let r = service
+        .exec_query("SELECT cardinality(id) FROM s.docs")
+        .await;
+    assert!(r.is_err(), "Expected planning error but got {:?}", r);
+    Ok(())
+}
+
+async fn create_table_with_retention(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query(
+            "CREATE TABLE s.events (ts timestamp, name text) \
+             WITH (retention_column = 'ts', retention_period = '7 days')",
+        )
+        .await?;
+    service
+        .exec_query(
+            "INSERT INTO s.events (ts, name) VALUES \
+             ('2020-01-01T00:00:00.000Z', 'a'), ('2020-01-02T00:00:00.000Z', 'b')",
+        )
+        .await?;
+    let r = service
+        .exec_query(
+            "SELECT table_name, retention_column, retention_period_secs FROM system.tables \
+             WHERE table_schema = 's'",
+        )
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[("events", "ts", 7 * 24 * 3600)]));
+
+    let err = service
+        .exec_query(
+            "CREATE TABLE s.by_name (ts timestamp, name text) \
+             INDEX by_name (name, ts) \
+             WITH (retention_column = 'ts', retention_period = '7 days')",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("Retention column 'ts' should be the first column of index 'by_name'"),
+        "{}",
+        err
+    );
+    let err = service
+        .exec_query(
+            "CREATE TABLE s.no_period (ts timestamp, name text) WITH (retention_column = 'ts')",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("retention_column and retention_period should be specified together"),
+        "{}",
+        err
+    );
+    let err = service
+        .exec_query(
+            "CREATE TABLE s.bad_period (ts timestamp, name text) \
+             WITH (retention_column = 'ts', retention_period = '00:00:00.5')",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message.contains("It should be at least one second"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+async fn create_table_with_parquet_settings(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query(
+            "CREATE TABLE s.events (id int, name text, city text) \
+             WITH (compression = 'ZSTD(3)', bloom_filter_columns = 'name, city')",
+        )
+        .await?;
+    service
+        .exec_query(
+            "INSERT INTO s.events (id, name, city) VALUES \
+             (1, 'a', 'London'), (2, 'b', 'Paris'), (3, 'c', 'London')",
+        )
+        .await?;
+    let r = service
+        .exec_query(
+            "SELECT table_name, compression, bloom_filter_columns FROM system.tables \
+             WHERE table_schema = 's'",
+        )
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[("events", "zstd(3)", "name,city")]));
+
+    let r = service
+        .exec_query("SELECT id FROM s.events WHERE city = 'London' AND name IN ('c', 'd')")
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[3]));
+    let r = service
+        .exec_query("SELECT id FROM s.events WHERE name = 'x'")
+        .await?;
+    assert_eq!(to_rows(&r), Vec::<Vec<TableValue>>::new());
+
+    for (query, error) in [
+        (
+            "CREATE TABLE s.bad_codec (id int) WITH (compression = 'lzo')",
+            "Unsupported compression 'lzo'",
+        ),
+        (
+            "CREATE TABLE s.bad_level (id int) WITH (compression = 'zstd(100)')",
+            "Bad compression level in 'zstd(100)'",
+        ),
+        (
+            "CREATE TABLE s.bad_column (id int) WITH (bloom_filter_columns = 'name')",
+            "Bloom filter column name not found",
+        ),
+    ] {
+        let err = service.exec_query(query).await.unwrap_err();
+        assert!(err.message.contains(error), "{}", err);
+    }
+    Ok(())
+}
+
+async fn delete_rows(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query("CREATE TABLE s.data (id int, name text)")
+        .await?;
+    service
+        .exec_query("INSERT INTO s.data (id, name) VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, NULL)")
+        .await?;
+
+    service
+        .exec_query("DELETE FROM s.data WHERE id = 1 OR name IN ('c')")
+        .await?;
+    let r = service
+        .exec_query("SELECT id, name FROM s.data ORDER BY id")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![TableValue::Int(2), TableValue::String("b".to_string())],
+            vec![TableValue::Int(4), TableValue::Null],
+        ]
+    );
+
+    // Markers don't apply to rows inserted after them.
+    service
+        .exec_query("INSERT INTO s.data (id, name) VALUES (1, 'd')")
+        .await?;
+    service
+        .exec_query("DELETE FROM s.data WHERE NOT (2 >= id) AND name IS NULL")
+        .await?;
+    let r = service
+        .exec_query("SELECT id, name FROM s.data ORDER BY id")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![TableValue::Int(1), TableValue::String("d".to_string())],
+            vec![TableValue::Int(2), TableValue::String("b".to_string())],
+        ]
+    );
+
+    // Data is still in chunks, nothing to purge from partitions yet.
+    let r = service
+        .exec_query(
+            "SELECT count(*) FROM system.partitions WHERE active = true AND pending_deletes = true",
+        )
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[0]));
+
+    let err = service.exec_query("DELETE FROM s.data").await.unwrap_err();
+    assert!(err.message.contains("WHERE clause is required"), "{}", err);
+    let err = service
+        .exec_query("DELETE FROM s.data WHERE missing = 1")
+        .await
+        .unwrap_err();
+    assert!(
+        err.message.contains("Column missing is not present"),
+        "{}",
+        err
+    );
+    let err = service
+        .exec_query("DELETE FROM s.data WHERE id + 1 = 2")
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("Comparison of a column with a constant is expected"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+async fn alter_table(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query("CREATE TABLE s.data (id int, name text)")
+        .await?;
+    service
+        .exec_query("CREATE TABLE s.data_new (id int, name text)")
+        .await?;
+    service
+        .exec_query("INSERT INTO s.data (id, name) VALUES (1, 'a'), (2, 'b')")
+        .await?;
+    service
+        .exec_query("INSERT INTO s.data_new (id, name) VALUES (3, 'c')")
+        .await?;
+
+    // Rows written before the column was added read as NULL.
+    service
+        .exec_query("ALTER TABLE s.data ADD COLUMN amount int")
+        .await?;
+    service
+        .exec_query("INSERT INTO s.data (id, name, amount) VALUES (3, 'c', 10)")
+        .await?;
+    let r = service
+        .exec_query("SELECT id, name, amount FROM s.data ORDER BY id")
+        .await?;
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![
+                TableValue::Int(1),
+                TableValue::String("a".to_string()),
+                TableValue::Null
+            ],
+            vec![
+                TableValue::Int(2),
+                TableValue::String("b".to_string()),
+                TableValue::Null
+            ],
+            vec![
+                TableValue::Int(3),
+                TableValue::String("c".to_string()),
+                TableValue::Int(10)
+            ],
+        ]
+    );
+    let r = service.exec_query("SELECT sum(amount) FROM s.data").await?;
+    assert_eq!(to_rows(&r), rows(&[10]));
+
+    let err = service
+        .exec_query("ALTER TABLE s.data DROP COLUMN name")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("used by index"), "{}", err);
+    service
+        .exec_query("ALTER TABLE s.data DROP COLUMN amount")
+        .await?;
+    let r = service
+        .exec_query("SELECT * FROM s.data ORDER BY id")
+        .await?;
+    assert_eq!(r.get_columns().len(), 2);
+    let err = service
+        .exec_query("ALTER TABLE s.data ADD COLUMN amount int")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("can't be added again"), "{}", err);
+
+    service.exec_query("SWAP TABLES s.data, s.data_new").await?;
+    let r = service
+        .exec_query("SELECT id FROM s.data ORDER BY id")
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[3]));
+    let r = service
+        .exec_query("SELECT id FROM s.data_new ORDER BY id")
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[1, 2, 3]));
+
+    let err = service
+        .exec_query("ALTER TABLE s.data_new RENAME TO data")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("already exists"), "{}", err);
+    service
+        .exec_query("ALTER TABLE s.data_new RENAME TO data_old")
+        .await?;
+    let r = service
+        .exec_query("SELECT count(*) FROM s.data_old")
+        .await?;
+    assert_eq!(to_rows(&r), rows(&[3]));
+    assert!(service
+        .exec_query("SELECT count(*) FROM s.data_new")
+        .await
+        .is_err());
+    Ok(())
+}
+
 async fn create_table_with_url(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
     // TODO serve this data ourselves
     let url = "https://data.wprdc.org/dataset/0b584c84-7e35-4f4d-a5a2-b01697470c0f/resource/e95dd941-8e47-4460-9bd8-1e51c194370b/download/bikepghpublic.csv";
@@ -3121,29 +3698,197 @@ async fn aggregate_index_hll_databricks(service: Box<dyn SqlClient>) -> Result<(
         ?;
 
     let res = service
-        .exec_query("SELECT a, b, cardinality(merge(a_hll)) as hll FROM s.Orders GROUP BY 1, 2 ORDER BY 1, 2")
-        .await
-        ?;
+        .exec_query("SELECT a, b, cardinality(merge(a_hll)) as hll FROM s.Orders GROUP BY 1, 2 ORDER BY 1, 2")
+        .await
+        ?;
+    assert_eq!(
+        to_rows(&res),
+        [
+            [TableValue::Int(1), TableValue::Int(10), TableValue::Int(4)],
+            [TableValue::Int(1), TableValue::Int(20), TableValue::Int(4)],
+        ]
+    );
+
+    let res = service
+        .exec_query("SELECT a, cardinality(merge(a_hll)) as hll FROM s.Orders WHERE b = 20 GROUP BY 1 ORDER BY 1")
+        .await
+        ?;
+    assert_eq!(to_rows(&res), [[TableValue::Int(1), TableValue::Int(4)],]);
+
+    let res = service
+        .exec_query(
+            "SELECT a, cardinality(merge(a_hll)) as hll FROM s.Orders GROUP BY 1 ORDER BY 1",
+        )
+        .await?;
+    assert_eq!(to_rows(&res), [[TableValue::Int(1), TableValue::Int(4)],]);
+    Ok(())
+}
+
+async fn aggregate_index_tdigest(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query(
+            "CREATE TABLE s.Latency(a int, b int, l tdigest)
+                     AGGREGATIONS(merge_tdigest(l))
+                     AGGREGATE INDEX aggr_index (a)
+                     ",
+        )
+        .await?;
+    // Sketches of (1, 2, 3), (4, 5) and (10).
+    service
+        .exec_query(
+            "INSERT INTO s.Latency (a, b, l) VALUES \
+                    (1, 10, X'01000000000000594003000000000000000000F03F0000000000000840000000000000F03F000000000000F03F0000000000000040000000000000F03F0000000000000840000000000000F03F'), \
+                    (1, 20, X'01000000000000594002000000000000000000104000000000000014400000000000001040000000000000F03F0000000000001440000000000000F03F'), \
+                    (2, 10, X'01000000000000594001000000000000000000244000000000000024400000000000002440000000000000F03F'), \
+                    (2, 20, NULL)
+           ",
+        )
+        .await?;
+
+    let res = service
+        .exec_query("SELECT b, quantile(l, 0.5) FROM s.Latency WHERE a = 1 ORDER BY 1")
+        .await?;
+    assert_eq!(
+        to_rows(&res),
+        [
+            [TableValue::Int(10), TableValue::Float(2.0.into())],
+            [TableValue::Int(20), TableValue::Float(4.5.into())],
+        ]
+    );
+
+    let res = service
+        .exec_query(
+            "SELECT a, quantile(merge_tdigest(l), 0), quantile(merge_tdigest(l), 0.5), quantile(merge_tdigest(l), 1) \
+             FROM s.Latency GROUP BY 1 ORDER BY 1",
+        )
+        .await?;
+    assert_eq!(
+        to_rows(&res),
+        [
+            [
+                TableValue::Int(1),
+                TableValue::Float(1.0.into()),
+                TableValue::Float(3.0.into()),
+                TableValue::Float(5.0.into())
+            ],
+            [
+                TableValue::Int(2),
+                TableValue::Float(10.0.into()),
+                TableValue::Float(10.0.into()),
+                TableValue::Float(10.0.into())
+            ],
+        ]
+    );
+
+    let res = service
+        .exec_query("SELECT quantile(merge_tdigest(l), 0.5) FROM s.Latency WHERE a = 3")
+        .await?;
+    assert_eq!(to_rows(&res), [[TableValue::Null]]);
+
+    let err = service
+        .exec_query("SELECT quantile(l, 1.5) FROM s.Latency")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("quantile must be between 0 and 1"));
+
+    let err = service
+        .exec_query("INSERT INTO s.Latency (a, b, l) VALUES (3, 10, X'0100')")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("invalid serialized t-digest sketch"));
+
+    let err = service
+        .exec_query("CREATE TABLE s.Errors(a int, b text) AGGREGATIONS(merge_tdigest(b)) AGGREGATE INDEX aggr_index (a)")
+        .await
+        .unwrap_err();
+    assert!(err
+        .message
+        .contains("Aggregate function MERGE_TDIGEST not allowed for column type text"));
+    Ok(())
+}
+
+async fn theta_sketch_set_operations(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query(
+            "CREATE TABLE s.Cohorts(cohort int, day int, users theta_sketch)
+                     AGGREGATIONS(merge_theta(users))
+                     AGGREGATE INDEX by_cohort (cohort)
+                     ",
+        )
+        .await?;
+    // Exact mode sketches with hashes (1, 2, 3), (5, 6) and (2, 3, 4).
+    let a = "X'02030300001ACC930300000000000000010000000000000002000000000000000300000000000000'";
+    let c = "X'02030300001ACC93020000000000000005000000000000000600000000000000'";
+    let b = "X'02030300001ACC930300000000000000020000000000000003000000000000000400000000000000'";
+    service
+        .exec_query(&format!(
+            "INSERT INTO s.Cohorts (cohort, day, users) VALUES (1, 1, {a}), (1, 2, {c}), (2, 1, {b}), (2, 2, NULL)",
+            a = a,
+            b = b,
+            c = c
+        ))
+        .await?;
+
+    let res = service
+        .exec_query(
+            "SELECT cohort, theta_estimate(merge_theta(users)) FROM s.Cohorts GROUP BY 1 ORDER BY 1",
+        )
+        .await?;
+    assert_eq!(to_rows(&res), rows(&[(1, 5), (2, 3)]));
+
+    let res = service
+        .exec_query(&format!(
+            "SELECT day, \
+                    theta_estimate(theta_union(users, {b})), \
+                    theta_estimate(theta_intersect(users, {b})), \
+                    theta_estimate(theta_a_not_b(users, {b})) \
+             FROM s.Cohorts WHERE cohort = 1 ORDER BY 1",
+            b = b
+        ))
+        .await?;
+    assert_eq!(to_rows(&res), rows(&[(1, 4, 2, 1), (2, 5, 0, 2)]));
+
+    let res = service
+        .exec_query(&format!(
+            "SELECT theta_estimate(theta_intersect(merge_theta(users), {b})), \
+                    theta_estimate(theta_union(merge_theta(users), {b}, {c})) \
+             FROM s.Cohorts WHERE cohort = 1",
+            b = b,
+            c = c
+        ))
+        .await?;
+    assert_eq!(to_rows(&res), rows(&[(2, 6)]));
+
+    let res = service
+        .exec_query(&format!(
+            "SELECT day, theta_estimate(users), theta_intersect(users, {b}) IS NULL \
+             FROM s.Cohorts WHERE cohort = 2 ORDER BY 1",
+            b = b
+        ))
+        .await?;
     assert_eq!(
         to_rows(&res),
-        [
-            [TableValue::Int(1), TableValue::Int(10), TableValue::Int(4)],
-            [TableValue::Int(1), TableValue::Int(20), TableValue::Int(4)],
+        vec![
+            vec![
+                TableValue::Int(1),
+                TableValue::Int(3),
+                TableValue::Boolean(false)
+            ],
+            vec![
+                TableValue::Int(2),
+                TableValue::Null,
+                TableValue::Boolean(true)
+            ],
         ]
     );
 
-    let res = service
-        .exec_query("SELECT a, cardinality(merge(a_hll)) as hll FROM s.Orders WHERE b = 20 GROUP BY 1 ORDER BY 1")
+    let err = service
+        .exec_query("INSERT INTO s.Cohorts (cohort, day, users) VALUES (3, 1, X'00')")
         .await
-        ?;
-    assert_eq!(to_rows(&res), [[TableValue::Int(1), TableValue::Int(4)],]);
-
-    let res = service
-        .exec_query(
-            "SELECT a, cardinality(merge(a_hll)) as hll FROM s.Orders GROUP BY 1 ORDER BY 1",
-        )
-        .await?;
-    assert_eq!(to_rows(&res), [[TableValue::Int(1), TableValue::Int(4)],]);
+        .unwrap_err();
+    assert!(err.message.contains("DataSketches"), "{}", err.message);
     Ok(())
 }
 
@@ -8693,6 +9438,48 @@ async fn aggregate_index(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
     Ok(())
 }
 
+async fn aggregate_index_count(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service.exec_query("CREATE SCHEMA s").await?;
+    service
+        .exec_query(
+            "CREATE TABLE s.Orders(a int, b int, cnt int)
+                     AGGREGATIONS(count(cnt))
+                     AGGREGATE INDEX aggr_index (a)
+                     ",
+        )
+        .await?;
+    // Values don't matter, only non-null ones are counted.
+    service
+        .exec_query(
+            "INSERT INTO s.Orders (a, b, cnt) VALUES (1, 1, 10), (1, 2, NULL), (1, 3, 3), (2, 1, 7)",
+        )
+        .await?;
+    service
+        .exec_query("INSERT INTO s.Orders (a, b, cnt) VALUES (1, 4, 5)")
+        .await?;
+
+    let res = service
+        .exec_query("SELECT a, sum(cnt) FROM s.Orders GROUP BY 1 ORDER BY 1")
+        .await?;
+    assert_eq!(to_rows(&res), rows(&[(1, 3), (2, 1)]));
+
+    // The default index stores the same counts, so it gives the same totals.
+    let res = service
+        .exec_query("SELECT a, sum(cnt) FROM s.Orders WHERE b >= 1 GROUP BY 1 ORDER BY 1")
+        .await?;
+    assert_eq!(to_rows(&res), rows(&[(1, 3), (2, 1)]));
+
+    // LAST_VALUE follows insert order and can't be combined with the sequence column.
+    let err = service
+        .exec_query(
+            "CREATE TABLE s.Versions(a int, v int) UNIQUE KEY (a) AGGREGATIONS(last_value(v))",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("LAST_VALUE(v)"), "{}", err);
+    Ok(())
+}
+
 async fn aggregate_index_with_hll_bytes(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
     service.exec_query("CREATE SCHEMA s").await?;
     service
@@ -8845,6 +9632,24 @@ async fn aggregate_index_errors(service: Box<dyn SqlClient>) -> Result<(), CubeE
         )
         .await
         .expect_err("Aggregate function MERGE not allowed for column type integer");
+
+    service
+        .exec_query(
+            "CREATE TABLE s.Orders(a int, b string, a_hll hyperloglog)
+                    AGGREGATIONS (count(b))
+                     ",
+        )
+        .await
+        .expect_err("Aggregate function COUNT not allowed for column type text");
+
+    service
+        .exec_query(
+            "CREATE TABLE s.Orders(a int, b string, a_hll hyperloglog)
+                    AGGREGATIONS (merge_theta(a_hll))
+                     ",
+        )
+        .await
+        .expect_err("Aggregate function MERGE_THETA not allowed for column type hyperloglog");
     Ok(())
 }
 
@@ -9553,6 +10358,156 @@ async fn cache_prefix_keys(service: Box<dyn SqlClient>) -> Result<(), CubeError>
     Ok(())
 }
 
+async fn cache_subscribe_publish(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    let r = service
+        .exec_query("CACHE PUBLISH 'refresh:all' 'message';")
+        .await?;
+    assert_eq!(r.len(), 0);
+
+    // Notifications are streamed only over the WebSocket protocol
+    let r = service.exec_query("CACHE SUBSCRIBE 'refresh:';").await;
+    assert!(r.is_err(), "expected error, got: {:?}", r);
+
+    Ok(())
+}
+
+async fn cache_data_structures(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    // Compare-and-set
+    let r = service
+        .exec_query("CACHE SET 'lock:1' 'owner1' IF VERSION = 0")
+        .await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(true)])]
+    );
+
+    let r = service
+        .exec_query("CACHE GET WITH_VERSION 'lock:1'")
+        .await?;
+    assert_eq!(
+        r.get_columns(),
+        &vec![
+            Column::new("value".to_string(), ColumnType::String, 0),
+            Column::new("version".to_string(), ColumnType::Int, 1),
+        ]
+    );
+    assert_eq!(
+        r.get_rows()[0].values()[0],
+        TableValue::String("owner1".to_string())
+    );
+    let version = match &r.get_rows()[0].values()[1] {
+        TableValue::Int(version) => *version,
+        v => panic!("unexpected version: {:?}", v),
+    };
+    assert!(version > 0);
+
+    let r = service
+        .exec_query("CACHE SET 'lock:1' 'owner2' IF VERSION = 0")
+        .await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(false)])]
+    );
+
+    let r = service
+        .exec_query(&format!(
+            "CACHE SET 'lock:1' 'owner2' IF VERSION = {}",
+            version
+        ))
+        .await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(true)])]
+    );
+
+    // Hashes
+    let r = service
+        .exec_query("CACHE HSET 'query:1' 'status' 'running'")
+        .await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(true)])]
+    );
+
+    service
+        .exec_query("CACHE HSET TTL 60 'query:1' 'status' 'done'")
+        .await?;
+    service
+        .exec_query("CACHE HSET 'query:1' 'rows' '42'")
+        .await?;
+
+    let r = service.exec_query("CACHE HGET 'query:1' 'status'").await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::String("done".to_string())])]
+    );
+
+    let r = service.exec_query("CACHE HGETALL 'query:1'").await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![
+            Row::new(vec![
+                TableValue::String("rows".to_string()),
+                TableValue::String("42".to_string())
+            ]),
+            Row::new(vec![
+                TableValue::String("status".to_string()),
+                TableValue::String("done".to_string())
+            ]),
+        ]
+    );
+
+    // Plain GET doesn't read hashes
+    let r = service.exec_query("CACHE GET 'query:1'").await;
+    assert!(r.is_err(), "expected error, got: {:?}", r);
+
+    let r = service.exec_query("CACHE HDEL 'query:1' 'rows'").await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(true)])]
+    );
+
+    // Sorted sets
+    service
+        .exec_query("CACHE ZADD 'slow' 2.5 'query:1'")
+        .await?;
+    service.exec_query("CACHE ZADD 'slow' 10 'query:2'").await?;
+    service.exec_query("CACHE ZADD 'slow' -1 'query:3'").await?;
+
+    let r = service.exec_query("CACHE ZRANGE REV 'slow' 0 1").await?;
+    assert_eq!(
+        r.get_columns(),
+        &vec![
+            Column::new("member".to_string(), ColumnType::String, 0),
+            Column::new("score".to_string(), ColumnType::Float, 1),
+        ]
+    );
+    assert_eq!(
+        r.get_rows(),
+        &vec![
+            Row::new(vec![
+                TableValue::String("query:2".to_string()),
+                TableValue::Float(10.0.into())
+            ]),
+            Row::new(vec![
+                TableValue::String("query:1".to_string()),
+                TableValue::Float(2.5.into())
+            ]),
+        ]
+    );
+
+    let r = service.exec_query("CACHE ZREM 'slow' 'query:2'").await?;
+    assert_eq!(
+        r.get_rows(),
+        &vec![Row::new(vec![TableValue::Boolean(true)])]
+    );
+
+    let r = service.exec_query("CACHE ZRANGE 'slow' 0 -1").await?;
+    assert_eq!(r.get_rows().len(), 2);
+
+    Ok(())
+}
+
 async fn limit_pushdown_group(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
     service.exec_query("CREATE SCHEMA foo").await?;
     service
@@ -12704,6 +13659,114 @@ async fn queue_custom_orphaned(service: Box<dyn SqlClient>) -> Result<(), CubeEr
     Ok(())
 }
 
+async fn queue_retry_dead_letter(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service
+        .exec_query(r#"QUEUE ADD MAX_ATTEMPTS 1 "STANDALONE#queue:queue_key_1" "payload1";"#)
+        .await?;
+
+    service
+        .exec_query(r#"QUEUE ADD MAX_ATTEMPTS 3 "STANDALONE#queue:queue_key_2" "payload2";"#)
+        .await?;
+
+    service
+        .exec_query(r#"QUEUE RETRIEVE CONCURRENCY 2 "STANDALONE#queue:queue_key_1""#)
+        .await?;
+
+    service
+        .exec_query(r#"QUEUE RETRIEVE CONCURRENCY 2 "STANDALONE#queue:queue_key_2""#)
+        .await?;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Both are stalled, but left to the retry loop of the cachestore instead of being reported
+    let res = service
+        .exec_query(r#"QUEUE TO_CANCEL 100 100 "STANDALONE#queue""#)
+        .await?;
+    assert_eq!(res.len(), 0);
+
+    let res = service
+        .exec_query(
+            r#"SELECT id, status, attempts, max_attempts FROM system.queue WHERE prefix = 'STANDALONE#queue' ORDER BY id"#,
+        )
+        .await?;
+    assert_eq!(
+        res.get_rows(),
+        &vec![
+            Row::new(vec![
+                TableValue::String("queue_key_1".to_string()),
+                TableValue::String("Active".to_string()),
+                TableValue::Int(0),
+                TableValue::Int(1),
+            ]),
+            Row::new(vec![
+                TableValue::String("queue_key_2".to_string()),
+                TableValue::String("Active".to_string()),
+                TableValue::Int(0),
+                TableValue::Int(3),
+            ]),
+        ]
+    );
+
+    Ok(())
+}
+
+async fn queue_add_delay(service: Box<dyn SqlClient>) -> Result<(), CubeError> {
+    service
+        .exec_query(r#"QUEUE ADD PRIORITY 1 DELAY 3600 "STANDALONE#queue:queue_key_1" "payload1";"#)
+        .await?;
+
+    service
+        .exec_query(
+            r#"QUEUE ADD AT '2000-01-01T00:00:00Z' "STANDALONE#queue:queue_key_2" "payload2";"#,
+        )
+        .await?;
+
+    let res = service
+        .exec_query(r#"QUEUE PENDING "STANDALONE#queue""#)
+        .await?;
+    assert_eq!(
+        res.get_rows()
+            .iter()
+            .map(|row| row.values()[0].clone())
+            .collect::<Vec<_>>(),
+        vec![
+            TableValue::String("queue_key_2".to_string()),
+            TableValue::String("queue_key_1".to_string()),
+        ]
+    );
+
+    let res = service
+        .exec_query(r#"QUEUE RETRIEVE CONCURRENCY 2 "STANDALONE#queue:queue_key_1""#)
+        .await?;
+    assert_eq!(res.len(), 0);
+
+    let res = service
+        .exec_query(r#"QUEUE RETRIEVE CONCURRENCY 2 "STANDALONE#queue:queue_key_2""#)
+        .await?;
+    assert_eq!(res.len(), 1);
+
+    let res = service
+        .exec_query(
+            r#"SELECT id, visible_at > created FROM system.queue WHERE prefix = 'STANDALONE#queue' ORDER BY id"#,
+        )
+        .await?;
+    assert_eq!(
+        res.get_rows(),
+        &vec![
+            Row::new(vec![
+                TableValue::String("queue_key_1".to_string()),
+                TableValue::Boolean(true),
+            ]),
+            Row::new(vec![
+                TableValue::String("queue_key_2".to_string()),
+                TableValue::Boolean(false),
+            ]),
+        ]
+    );
+
+    Ok(())
+}
+
 async fn queue_full_workflow_v2_with_external_id(
     service: Box<dyn SqlClient>,
 ) -> Result<(), CubeError> {
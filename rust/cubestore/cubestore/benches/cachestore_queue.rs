@@ -64,6 +64,8 @@ async fn do_insert(
             process_id: None,
             exclusive: false,
             external_id: None,
+            max_attempts: None,
+            visible_at: None,
         });
 
         let res = fut.await;
@@ -80,6 +80,8 @@ async fn populate_metastore(
                     None,   // trace_obj
                     false,  // drop_if_exists
                     None,   // extension
+                    None,   // retention
+                    None,   // parquet_settings
                 )
                 .await?;
 
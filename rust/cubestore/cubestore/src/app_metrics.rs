@@ -76,6 +76,8 @@ pub static CACHE_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.cac
 /// Incoming queue queries.
 pub static QUEUE_QUERIES: Counter = metrics::counter("cs.sql.query.queue");
 pub static QUEUE_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.queue.ms");
+/// Statements denied by the user grants, tagged by the required permission.
+pub static SQL_ACCESS_DENIED: Counter = metrics::counter("cs.sql.access_denied");
 pub static STREAMING_ROWS_READ: Counter = metrics::counter("cs.streaming.rows");
 pub static STREAMING_CHUNKS_READ: Counter = metrics::counter("cs.streaming.chunks");
 pub static STREAMING_LASTOFFSET: Gauge = metrics::gauge("cs.streaming.lastoffset");
@@ -142,6 +144,11 @@ pub static JOBS_REPARTITION_CHUNK_FAILURES: Counter =
 /// (version skew across `latest`/`release` channels).
 pub static JOBS_UNKNOWN_DELETED: Counter = metrics::counter("cs.jobs.unknown.deleted");
 
+/// Rate limiter budgets and waiting tasks, tagged with task type and tenant.
+pub static RATE_LIMITER_BUDGET: Gauge = metrics::gauge("cs.rate_limiter.budget");
+pub static RATE_LIMITER_PENDING: Gauge = metrics::gauge("cs.rate_limiter.pending");
+pub static RATE_LIMITER_REJECTED: Counter = metrics::counter("cs.rate_limiter.rejected");
+
 /// RemoteFs metrics
 pub static REMOTE_FS_OPERATION_CORE: Counter = metrics::counter("cs.remote_fs.operations.core");
 pub static REMOTE_FS_FILES_TO_REMOVE: Gauge = metrics::gauge("cs.remote_fs.files_to_remove.count");
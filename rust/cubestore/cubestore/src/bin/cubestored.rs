@@ -1,5 +1,9 @@
+use cubestore::cluster::{register_select_worker_setup, register_select_worker_teardown};
 use cubestore::config::{validate_config, Config, CubeServices};
 use cubestore::http::status::serve_status_probes;
+use cubestore::remotefs::fsck::{fsck, FsckOptions};
+use cubestore::remotefs::RemoteFs;
+use cubestore::telemetry::tracing::{init_otlp_tracing, shutdown_otlp_tracing};
 use cubestore::telemetry::{init_agent_sender, track_event};
 use cubestore::util::logger::init_cube_logger;
 use cubestore::util::metrics::init_metrics;
@@ -9,12 +13,14 @@ use datafusion::cube_ext;
 use log::debug;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Builder;
 
 const PACKAGE_JSON: &str = std::include_str!("../../../package.json");
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
     let package_json: Value = serde_json::from_str(PACKAGE_JSON).unwrap();
     let version = package_json
         .get("version")
@@ -25,8 +31,9 @@ fn main() {
     let metrics_format = match std::env::var("CUBESTORE_METRICS_FORMAT") {
         Ok(s) if s == "statsd" => metrics::Compatibility::StatsD,
         Ok(s) if s == "dogstatsd" => metrics::Compatibility::DogStatsD,
+        Ok(s) if s == "prometheus" => metrics::Compatibility::Prometheus,
         Ok(s) => panic!(
-            "CUBESTORE_METRICS_FORMAT must be 'statsd' or 'dogstatsd', got '{}'",
+            "CUBESTORE_METRICS_FORMAT must be 'statsd', 'dogstatsd' or 'prometheus', got '{}'",
             s
         ),
         Err(_) => metrics::Compatibility::StatsD,
@@ -70,6 +77,14 @@ fn main() {
     debug!("New process started");
     app_metrics::STARTUPS.increment();
 
+    register_select_worker_setup(|runtime| {
+        let _guard = runtime.enter();
+        if let Err(e) = init_otlp_tracing() {
+            log::error!("Select worker tracing: {}", e);
+        }
+    });
+    register_select_worker_teardown(shutdown_otlp_tracing);
+
     #[cfg(not(target_os = "windows"))]
     cubestore::util::respawn::init();
 
@@ -85,11 +100,26 @@ fn main() {
     let runtime = tokio_builder.build().unwrap();
     runtime.block_on(async move {
         init_agent_sender().await;
+        if let Err(e) = init_otlp_tracing() {
+            log::error!("{}", e);
+        }
 
         validate_config(config.config_obj().as_ref()).report_and_abort_on_errors();
 
         config.configure_injector().await;
 
+        if args.get(1).map(|a| a.as_str()) == Some("fsck") {
+            let code = match run_fsck(&config, &args[2..]).await {
+                Ok(true) => 0,
+                Ok(false) => 1,
+                Err(e) => {
+                    log::error!("fsck failed: {}", e);
+                    2
+                }
+            };
+            std::process::exit(code);
+        }
+
         serve_status_probes(&config);
 
         let services = config.cube_services().await;
@@ -101,6 +131,58 @@ fn main() {
         stop_on_ctrl_c(&services).await;
         services.wait_processing_loops().await.unwrap();
     });
+    // Exporter runs on the runtime, so spans are flushed outside of `block_on`
+    shutdown_otlp_tracing();
+}
+
+/// `cubestored fsck [--verify] [--repair]` checks the metastore against the remote fs without
+/// starting processing loops, the router must be stopped. Returns `false` if issues were found.
+async fn run_fsck(config: &Config, args: &[String]) -> Result<bool, CubeError> {
+    let mut options = FsckOptions::default();
+    for arg in args {
+        match arg.as_str() {
+            "--verify" => options.verify = true,
+            "--repair" => options.repair = true,
+            _ => {
+                return Err(CubeError::user(format!(
+                    "Unknown fsck option '{}', expected: --verify, --repair",
+                    arg
+                )))
+            }
+        }
+    }
+
+    let services = config.cube_services().await;
+    // Queue remote fs works only with running processing loops
+    let remote_fs: Arc<dyn RemoteFs> = services.injector.get_service("original_remote_fs").await;
+    let issues = fsck(
+        services.meta_store.clone(),
+        remote_fs,
+        services.cluster.clone(),
+        options,
+    )
+    .await?;
+
+    println!("kind\tobject\tid\tfile\tdetails\taction");
+    for issue in issues.iter() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            issue.kind.as_str(),
+            issue.object,
+            issue.id.map(|id| id.to_string()).unwrap_or_default(),
+            issue.file.as_deref().unwrap_or_default(),
+            issue.details,
+            issue.action.as_deref().unwrap_or_default()
+        );
+    }
+
+    if options.repair {
+        if let Some(rocks_meta_store) = services.rocks_meta_store.as_ref() {
+            rocks_meta_store.run_upload().await?;
+        }
+    }
+
+    Ok(issues.is_empty())
 }
 
 async fn stop_on_ctrl_c(s: &CubeServices) {
@@ -1,12 +1,14 @@
 use crate::cachestore::cache_item::{
-    CacheItemRocksIndex, CacheItemRocksTable, CACHE_ITEM_SIZE_WITHOUT_VALUE,
+    CacheItemRocksIndex, CacheItemRocksTable, CacheNotificationEvent, CacheNotificationKind,
+    CACHE_ITEM_SIZE_WITHOUT_VALUE,
 };
 use crate::cachestore::CacheItem;
 use crate::config::ConfigObj;
 use crate::metastore::{
-    BaseRocksSecondaryIndex, IdRow, PackedDateTime, RocksSecondaryIndexValueTTLExtended,
-    RocksSecondaryIndexValueVersionDecoder, RocksSecondaryIndexValueVersionEncoder, RocksStore,
-    RocksTable, SecondaryIndexValueScanIterItem,
+    BaseRocksSecondaryIndex, IdRow, MetaStoreEvent, PackedDateTime,
+    RocksSecondaryIndexValueTTLExtended, RocksSecondaryIndexValueVersionDecoder,
+    RocksSecondaryIndexValueVersionEncoder, RocksStore, RocksTable,
+    SecondaryIndexValueScanIterItem,
 };
 use crate::util::aborting_join_handle::AbortingJoinHandle;
 use crate::util::lock::acquire_lock;
@@ -307,6 +309,8 @@ impl CacheEvictionManager {
                                 lfu_decay_counter(cache_data.lfu, elapsed_minutes, lfu_decay_time);
                             cache_data.lfu = lfu_log_increment(decayed, lfu_log_factor, &mut rng);
                             cache_data.lru = now.encode_value_as_u32().unwrap();
+                            // Hashes and sorted sets change their size in place
+                            cache_data.raw_size = raw_size;
                         } else {
                             if ttl_buffer.len() >= ttl_buffer_max_size {
                                 continue;
@@ -403,7 +407,9 @@ impl CacheEvictionManager {
                     let current_batch =
                         std::mem::replace(&mut batch, Vec::with_capacity(self.eviction_batch_size));
 
-                    let batch_result = self.delete_batch(current_batch, &store).await?;
+                    let batch_result = self
+                        .delete_batch(current_batch, &store, keys_are_expired)
+                        .await?;
 
                     total_size_removed += batch_result.deleted_size;
                     total_keys_removed += batch_result.deleted_count;
@@ -417,7 +423,9 @@ impl CacheEvictionManager {
         };
 
         if last_batch.len() > 0 {
-            let batch_result = self.delete_batch(last_batch, &store).await?;
+            let batch_result = self
+                .delete_batch(last_batch, &store, keys_are_expired)
+                .await?;
 
             total_size_removed += batch_result.deleted_size;
             total_keys_removed += batch_result.deleted_count;
@@ -445,7 +453,14 @@ impl CacheEvictionManager {
         &self,
         batch: Vec<(u64, u32)>,
         store: &Arc<RocksStore>,
+        keys_are_expired: bool,
     ) -> Result<DeleteBatchResult, CubeError> {
+        let notification_kind = if keys_are_expired {
+            CacheNotificationKind::Expire
+        } else {
+            CacheNotificationKind::Evict
+        };
+
         let (deleted_count, deleted_size, skipped) = store
             .write_operation("delete_batch", move |db_ref, pipe| {
                 let cache_schema = CacheItemRocksTable::new(db_ref.clone());
@@ -455,7 +470,15 @@ impl CacheEvictionManager {
                 let mut skipped: u32 = 0;
 
                 for (id, raw_size) in batch {
-                    if let Some(_) = cache_schema.try_delete(id, pipe)? {
+                    if let Some(row) = cache_schema.try_delete(id, pipe)? {
+                        pipe.add_event(MetaStoreEvent::CacheNotification(
+                            CacheNotificationEvent::new(
+                                notification_kind,
+                                row.get_row().get_path(),
+                                None,
+                            ),
+                        ));
+
                         deleted_count += 1;
                         deleted_size += raw_size as u64;
                     } else {
@@ -1090,6 +1113,20 @@ impl CacheEvictionManager {
         Ok(())
     }
 
+    /// In-place update of an item, which changes its size (hashes and sorted sets grow and shrink).
+    pub fn notify_update(&self, id_row: &IdRow<CacheItem>, old_size: u64) -> Result<(), CubeError> {
+        let new_size = id_row.get_row().get_value().len() as u64;
+        if new_size > old_size {
+            self.stats_total_raw_size
+                .fetch_add(new_size - old_size, Ordering::Relaxed);
+        } else {
+            self.stats_total_raw_size
+                .fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+
+        self.notify_lookup(id_row)
+    }
+
     pub fn notify_delete(&self, row_id: u64, row_size: u64) -> Result<(), CubeError> {
         self.stats_total_keys.fetch_sub(1, Ordering::Relaxed);
         self.stats_total_raw_size
@@ -6,6 +6,32 @@ use chrono::serde::ts_seconds_option;
 use chrono::{DateTime, Duration, Utc};
 use cuberockstore::rocksdb::WriteBatch;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq, Hash)]
+pub enum CacheItemValueType {
+    #[default]
+    String,
+    /// Value is a JSON object of field -> value
+    Hash,
+    /// Value is a JSON object of member -> score
+    SortedSet,
+}
+
+impl CacheItemValueType {
+    pub fn is_string(&self) -> bool {
+        matches!(self, CacheItemValueType::String)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheItemValueType::String => "string",
+            CacheItemValueType::Hash => "hash",
+            CacheItemValueType::SortedSet => "sorted_set",
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct CacheItem {
@@ -14,6 +40,12 @@ pub struct CacheItem {
     pub(crate) value: String,
     #[serde(with = "ts_seconds_option")]
     pub(crate) expire: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "CacheItemValueType::is_string")]
+    pub(crate) value_type: CacheItemValueType,
+    // Assigned by the store on every write, items written before versioning start from the
+    // initial version
+    #[serde(default = "CacheItem::initial_version")]
+    pub(crate) version: u64,
 }
 
 // Every RowKey uses 15 bytes
@@ -55,9 +87,32 @@ impl CacheItem {
             key,
             value,
             expire: ttl.map(|ttl| Utc::now() + Duration::seconds(ttl as i64)),
+            value_type: CacheItemValueType::String,
+            version: Self::initial_version(),
         }
     }
 
+    pub fn new_hash(path: String, ttl: Option<u32>) -> CacheItem {
+        let mut item = Self::new(path, ttl, "{}".to_string());
+        item.value_type = CacheItemValueType::Hash;
+        item
+    }
+
+    pub fn new_sorted_set(path: String, ttl: Option<u32>) -> CacheItem {
+        let mut item = Self::new(path, ttl, "{}".to_string());
+        item.value_type = CacheItemValueType::SortedSet;
+        item
+    }
+
+    pub fn initial_version() -> u64 {
+        1
+    }
+
+    pub fn with_version(mut self, version: u64) -> CacheItem {
+        self.version = version;
+        self
+    }
+
     pub fn get_path(&self) -> String {
         if let Some(prefix) = &self.prefix {
             format!("{}:{}", prefix, self.key)
@@ -81,6 +136,155 @@ impl CacheItem {
     pub fn get_value(&self) -> &String {
         &self.value
     }
+
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.expire = Some(Utc::now() + Duration::seconds(ttl as i64));
+    }
+
+    /// Hash or sorted set without entries, such items are removed instead of being stored.
+    pub fn is_empty_collection(&self) -> bool {
+        !self.value_type.is_string() && self.value == "{}"
+    }
+
+    pub fn get_value_type(&self) -> &CacheItemValueType {
+        &self.value_type
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn ensure_value_type(&self, value_type: CacheItemValueType) -> Result<(), CubeError> {
+        if self.value_type != value_type {
+            return Err(CubeError::user(format!(
+                "Cache item '{}' holds a {} value, but {} was expected",
+                self.get_path(),
+                self.value_type.as_str(),
+                value_type.as_str()
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn hash_fields(&self) -> Result<BTreeMap<String, String>, CubeError> {
+        self.ensure_value_type(CacheItemValueType::Hash)?;
+
+        Ok(serde_json::from_str(&self.value)?)
+    }
+
+    pub fn set_hash_fields(&mut self, fields: &BTreeMap<String, String>) -> Result<(), CubeError> {
+        self.ensure_value_type(CacheItemValueType::Hash)?;
+        self.value = serde_json::to_string(fields)?;
+
+        Ok(())
+    }
+
+    pub fn sorted_set_members(&self) -> Result<BTreeMap<String, f64>, CubeError> {
+        self.ensure_value_type(CacheItemValueType::SortedSet)?;
+
+        Ok(serde_json::from_str(&self.value)?)
+    }
+
+    pub fn set_sorted_set_members(
+        &mut self,
+        members: &BTreeMap<String, f64>,
+    ) -> Result<(), CubeError> {
+        self.ensure_value_type(CacheItemValueType::SortedSet)?;
+        self.value = serde_json::to_string(members)?;
+
+        Ok(())
+    }
+
+    /// Members ordered by score (ties by member), `start` and `stop` are inclusive ranks,
+    /// negative ranks count from the end.
+    pub fn sorted_set_range(
+        &self,
+        start: i64,
+        stop: i64,
+        rev: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError> {
+        let mut members = self.sorted_set_members()?.into_iter().collect::<Vec<_>>();
+        members.sort_by(|(a_member, a_score), (b_member, b_score)| {
+            a_score
+                .total_cmp(b_score)
+                .then_with(|| a_member.cmp(b_member))
+        });
+        if rev {
+            members.reverse();
+        }
+
+        let len = members.len() as i64;
+        let start = if start < 0 { len + start } else { start }.max(0);
+        let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+        if start > stop {
+            return Ok(vec![]);
+        }
+
+        Ok(members
+            .into_iter()
+            .skip(start as usize)
+            .take((stop - start + 1) as usize)
+            .collect())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum CacheNotificationKind {
+    Set,
+    Remove,
+    Expire,
+    Evict,
+    /// Message sent by `CACHE PUBLISH`, the key is the channel name
+    Message,
+    /// All keys were removed at once (`CACHE CLEAR`, truncate or wipe) without per-key
+    /// notifications, subscribers of any prefix should resync. The key is empty
+    Clear,
+}
+
+impl CacheNotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheNotificationKind::Set => "set",
+            CacheNotificationKind::Remove => "remove",
+            CacheNotificationKind::Expire => "expire",
+            CacheNotificationKind::Evict => "evict",
+            CacheNotificationKind::Message => "message",
+            CacheNotificationKind::Clear => "clear",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CacheNotificationEvent {
+    pub kind: CacheNotificationKind,
+    pub key: String,
+    /// Only messages have a value. Cache values aren't sent over the event channel shared with
+    /// queue listeners, subscribers read changed keys with `CACHE GET`.
+    // Arc is used to protect cloning on receiving from broadcast::channel
+    pub value: Option<Arc<String>>,
+}
+
+impl CacheNotificationEvent {
+    pub fn new(kind: CacheNotificationKind, key: String, value: Option<String>) -> Self {
+        Self {
+            kind,
+            key,
+            value: value.map(Arc::new),
+        }
+    }
+
+    pub fn clear() -> Self {
+        Self::new(CacheNotificationKind::Clear, "".to_string(), None)
+    }
+
+    /// Prefix uses the same syntax as `CACHE KEYS`, a trailing `*` is optional.
+    pub fn matches(&self, prefix: &str) -> bool {
+        self.kind == CacheNotificationKind::Clear
+            || self
+                .key
+                .starts_with(prefix.strip_suffix('*').unwrap_or(prefix))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -207,4 +411,59 @@ mod tests {
         assert_eq!(row.key, "1".to_string());
         assert_eq!(row.get_path(), "lock:1".to_string());
     }
+
+    #[test]
+    fn test_notification_matches() {
+        let event = CacheNotificationEvent::new(
+            CacheNotificationKind::Set,
+            "refresh:orders".to_string(),
+            Some("1".to_string()),
+        );
+        assert!(event.matches("refresh"));
+        assert!(event.matches("refresh:"));
+        assert!(event.matches("refresh:*"));
+        assert!(event.matches("refresh:orders"));
+        assert!(event.matches(""));
+        assert!(!event.matches("lock:*"));
+        assert!(!event.matches("refresh:orders:"));
+        assert!(CacheNotificationEvent::clear().matches("refresh:*"));
+    }
+
+    #[test]
+    fn test_sorted_set_range() -> Result<(), CubeError> {
+        let mut row = CacheItem::new_sorted_set("leaderboard".to_string(), None);
+        assert!(row.is_empty_collection());
+        assert_eq!(row.get_version(), 1);
+
+        row.set_sorted_set_members(&BTreeMap::from([
+            ("c".to_string(), 3.0),
+            ("a".to_string(), 1.0),
+            ("b".to_string(), 1.0),
+            ("d".to_string(), -2.5),
+        ]))?;
+        assert!(!row.is_empty_collection());
+
+        let members = |range: Vec<(String, f64)>| {
+            range
+                .into_iter()
+                .map(|(member, _)| member)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            members(row.sorted_set_range(0, -1, false)?),
+            vec!["d", "a", "b", "c"]
+        );
+        assert_eq!(members(row.sorted_set_range(1, 2, false)?), vec!["a", "b"]);
+        assert_eq!(members(row.sorted_set_range(0, 1, true)?), vec!["c", "b"]);
+        assert_eq!(
+            members(row.sorted_set_range(-2, 10, false)?),
+            vec!["b", "c"]
+        );
+        assert!(row.sorted_set_range(3, 1, false)?.is_empty());
+
+        assert!(row.hash_fields().is_err());
+
+        Ok(())
+    }
 }
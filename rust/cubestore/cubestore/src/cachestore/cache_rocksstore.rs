@@ -1,6 +1,6 @@
 use crate::cachestore::cache_item::{
-    CacheItem, CacheItemIndexKey, CacheItemRocksIndex, CacheItemRocksTable,
-    CACHE_ITEM_SIZE_WITHOUT_VALUE,
+    CacheItem, CacheItemIndexKey, CacheItemRocksIndex, CacheItemRocksTable, CacheItemValueType,
+    CacheNotificationEvent, CacheNotificationKind, CACHE_ITEM_SIZE_WITHOUT_VALUE,
 };
 use crate::cachestore::queue_item::{
     active_keys_to_value, QueueItem, QueueItemIndexKey, QueueItemRocksIndex, QueueItemRocksTable,
@@ -13,12 +13,13 @@ use crate::config::{Config, ConfigObj};
 use std::collections::HashMap;
 use std::env;
 
+use crate::metastore::backup::BackupStoreSnapshot;
 use crate::metastore::{
     BaseRocksStoreFs, BatchPipe, DbTableRef, IdRow, MetaStoreEvent, MetaStoreFs, RocksPropertyRow,
     RocksStore, RocksStoreDetails, RocksStoreRWLoop, RocksTable, RocksTableStats,
 };
 use crate::remotefs::LocalDirRemoteFs;
-use crate::util::WorkerLoop;
+use crate::util::{IntervalLoop, WorkerLoop};
 use crate::{app_metrics, CubeError};
 use async_trait::async_trait;
 
@@ -184,6 +185,7 @@ pub struct RocksCacheStore {
     cache_eviction_manager: CacheEvictionManager,
     upload_loop: Arc<WorkerLoop>,
     metrics_loop: Arc<WorkerLoop>,
+    queue_retry_loop: Arc<IntervalLoop>,
     rw_loop_queue_cf: RocksStoreRWLoop,
 }
 
@@ -223,6 +225,10 @@ impl RocksCacheStore {
             cache_eviction_manager,
             upload_loop: Arc::new(WorkerLoop::new("Cachestore upload")),
             metrics_loop: Arc::new(WorkerLoop::new("Cachestore metrics")),
+            queue_retry_loop: Arc::new(IntervalLoop::new(
+                "Cachestore queue retry",
+                Duration::from_secs(store.config.cachestore_queue_retry_loop_interval()),
+            )),
             rw_loop_queue_cf: RocksStoreRWLoop::new("cachestore", "queue"),
         }))
     }
@@ -352,6 +358,29 @@ impl RocksCacheStore {
             log::info!("Not running cachestore eviction loop");
         }
 
+        if self.store.config.cachestore_queue_retry_loop_interval() > 0 {
+            let heartbeat_timeout =
+                (self.store.config.cachestore_queue_retry_heartbeat_timeout() * 1000) as u32;
+            let cachestore = self.clone();
+            loops.push(cube_ext::spawn(async move {
+                cachestore
+                    .queue_retry_loop
+                    .process(cachestore.clone(), async move |m| {
+                        let failed = m.queue_retry_failed(heartbeat_timeout).await?;
+                        if failed > 0 {
+                            log::debug!("Registered failed attempts for {} queue items", failed);
+                        }
+
+                        Ok(())
+                    })
+                    .await;
+
+                Ok(())
+            }))
+        } else {
+            log::info!("Not running cachestore queue retry loop");
+        }
+
         loops
     }
 
@@ -389,6 +418,7 @@ impl RocksCacheStore {
         self.cache_eviction_manager.stop_processing_loops();
         self.upload_loop.stop();
         self.metrics_loop.stop();
+        self.queue_retry_loop.stop();
     }
 
     pub async fn add_listener(&self, listener: Sender<MetaStoreEvent>) {
@@ -504,6 +534,142 @@ impl RocksCacheStore {
         Ok(())
     }
 
+    fn check_cache_entry_size(item: &CacheItem, max_entry_size: usize) -> Result<(), CubeError> {
+        if item.get_value().len() >= max_entry_size {
+            return Err(CubeError::user(format!(
+                "Unable to SET cache with '{}' key, exceeds maximum allowed size for payload: {}, max allowed: {}",
+                item.key,
+                humansize::format_size(item.get_value().len(), humansize::DECIMAL),
+                humansize::format_size(max_entry_size, humansize::DECIMAL),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Versions of cache items come from the RocksDB sequence number, so they grow across all
+    /// keys. A key removed and created again doesn't repeat a version a client may still hold
+    /// for compare-and-set. Writes are serialized, and the sequence grows with every write batch.
+    fn next_cache_version(db_ref: &DbTableRef) -> u64 {
+        db_ref.db.latest_sequence_number() + 1
+    }
+
+    pub(crate) async fn notify_cache_cleared(&self) {
+        for listener in self.store.listeners.read().await.iter() {
+            // Error means that there are no receivers, nobody to notify
+            let _ = listener.send(MetaStoreEvent::CacheNotification(
+                CacheNotificationEvent::clear(),
+            ));
+        }
+    }
+
+    fn notify_cache_item_change(&self, change: CacheItemChange) -> Result<(), CubeError> {
+        match change {
+            CacheItemChange::None => Ok(()),
+            CacheItemChange::Inserted(raw_size) => {
+                self.cache_eviction_manager.notify_insert(raw_size)
+            }
+            CacheItemChange::Updated(id_row, old_raw_size) => self
+                .cache_eviction_manager
+                .notify_update(&id_row, old_raw_size),
+            CacheItemChange::Deleted(row_id, raw_size) => {
+                self.cache_eviction_manager.notify_delete(row_id, raw_size)
+            }
+        }
+    }
+
+    /// Read-modify-write of a hash or a sorted set. `update` returns the result of the command
+    /// and whether the item has been changed, an item left without entries is removed. Missing
+    /// items are created from `empty`, or the command is a no-op when it's not provided.
+    async fn cache_update_collection<R, F>(
+        &self,
+        op_name: &'static str,
+        path: String,
+        empty: Option<CacheItem>,
+        ttl: Option<u32>,
+        update: F,
+    ) -> Result<R, CubeError>
+    where
+        F: FnOnce(&mut CacheItem) -> Result<(R, bool), CubeError> + Send + Sync + 'static,
+        R: Default + Send + Sync + 'static,
+    {
+        let max_entry_size = self.store.config.cachestore_cache_max_entry_size();
+
+        let (result, change) = self
+            .store
+            .write_operation(op_name, move |db_ref, batch_pipe| {
+                let cache_schema = CacheItemRocksTable::new(db_ref.clone());
+                let index_key = CacheItemIndexKey::ByPath(path);
+                let id_row_opt = cache_schema
+                    .get_single_opt_row_by_index(&index_key, &CacheItemRocksIndex::ByPath)?;
+
+                let mut item = match (&id_row_opt, empty) {
+                    (Some(id_row), _) => id_row.get_row().clone(),
+                    (None, Some(empty)) => empty,
+                    (None, None) => return Ok((R::default(), CacheItemChange::None)),
+                };
+
+                let (result, changed) = update(&mut item)?;
+                if !changed {
+                    return Ok((result, CacheItemChange::None));
+                }
+
+                if let Some(ttl) = ttl {
+                    item.set_ttl(ttl);
+                }
+
+                if item.is_empty_collection() {
+                    let change = if let Some(id_row) = id_row_opt {
+                        batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                            CacheNotificationEvent::new(
+                                CacheNotificationKind::Remove,
+                                item.get_path(),
+                                None,
+                            ),
+                        ));
+
+                        let raw_size = id_row.get_row().get_value().len() as u64;
+                        let row_id = id_row.get_id();
+                        cache_schema.delete_row(id_row, batch_pipe)?;
+
+                        CacheItemChange::Deleted(row_id, raw_size)
+                    } else {
+                        CacheItemChange::None
+                    };
+
+                    return Ok((result, change));
+                }
+
+                Self::check_cache_entry_size(&item, max_entry_size)?;
+
+                batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                    CacheNotificationEvent::new(CacheNotificationKind::Set, item.get_path(), None),
+                ));
+
+                let item = item.with_version(Self::next_cache_version(&db_ref));
+                let change = if let Some(id_row) = id_row_opt {
+                    let old_raw_size = id_row.get_row().get_value().len() as u64;
+
+                    CacheItemChange::Updated(
+                        cache_schema.update(id_row.get_id(), item, id_row.get_row(), batch_pipe)?,
+                        old_raw_size,
+                    )
+                } else {
+                    let raw_size = item.get_value().len() as u64;
+                    cache_schema.insert(item, batch_pipe)?;
+
+                    CacheItemChange::Inserted(raw_size)
+                };
+
+                Ok((result, change))
+            })
+            .await?;
+
+        self.notify_cache_item_change(change)?;
+
+        Ok(result)
+    }
+
     /// Number of strong references to the underlying RocksDB handle.
     pub fn db_strong_count(&self) -> usize {
         Arc::strong_count(&self.store.db)
@@ -682,7 +848,12 @@ impl RocksCacheStore {
                             return if orphaned < &now { true } else { false };
                         }
 
-                        let elapsed = now - item.get_row().get_created().clone();
+                        // A delayed item waits for a claim since it has become visible
+                        let since = item
+                            .get_row()
+                            .get_visible_at()
+                            .unwrap_or(item.get_row().get_created().clone());
+                        let elapsed = now - since;
                         if elapsed.num_milliseconds() > orphaned_timeout as i64 {
                             true
                         } else {
@@ -711,6 +882,95 @@ impl RocksCacheStore {
             .collect()
     }
 
+    /// Registers a failed attempt for items with a retry policy: pending items past their
+    /// orphaned deadline and active items without a heartbeat for `heartbeat_timeout` ms.
+    /// Returns the number of such items.
+    pub async fn queue_retry_failed(&self, heartbeat_timeout: u32) -> Result<usize, CubeError> {
+        self.write_operation_queue("queue_retry_failed", move |db_ref, batch_pipe| {
+            let queue_schema = QueueItemRocksTable::new(db_ref.clone());
+            let queue_payload_schema = QueueItemPayloadRocksTable::new(db_ref.clone());
+
+            let now = db_ref.start_time.clone();
+            let items = queue_schema
+                .scan_rows(None)?
+                .into_iter()
+                .filter(|item| {
+                    let row = item.get_row();
+                    if row.get_max_attempts().is_none() {
+                        return false;
+                    }
+
+                    match row.get_status() {
+                        QueueItemStatus::Pending => {
+                            matches!(row.get_orphaned(), Some(orphaned) if orphaned < &now)
+                        }
+                        QueueItemStatus::Active => {
+                            let since = row.get_heartbeat().unwrap_or(*row.get_created());
+                            (now - since).num_milliseconds() > heartbeat_timeout as i64
+                        }
+                        _ => false,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for item in items.iter() {
+                let mut new = item.get_row().clone();
+                new.fail_attempt(now);
+
+                if new.get_expire() != item.get_row().get_expire() {
+                    if let Some(payload_row) = queue_payload_schema.get_row(item.get_id())? {
+                        let mut new_payload = payload_row.get_row().clone();
+                        new_payload.set_expire(new.get_expire().clone());
+
+                        queue_payload_schema.update(
+                            item.get_id(),
+                            new_payload,
+                            payload_row.get_row(),
+                            batch_pipe,
+                        )?;
+                    }
+                }
+
+                queue_schema.update(item.get_id(), new, item.get_row(), batch_pipe)?;
+            }
+
+            Ok(items.len())
+        })
+        .await
+    }
+
+    /// A dead item is kept for diagnostics only, adding an item with the same path revives it
+    /// in place (the path is unique, so the row cannot be deleted and inserted in one batch).
+    fn insert_or_revive_queue_item(
+        queue_schema: &QueueItemRocksTable,
+        queue_payload_schema: &QueueItemPayloadRocksTable,
+        batch_pipe: &mut BatchPipe,
+        dead_row: Option<IdRow<QueueItem>>,
+        item: QueueItem,
+        value: String,
+    ) -> Result<(IdRow<QueueItem>, IdRow<QueueItemPayload>), CubeError> {
+        let payload =
+            QueueItemPayload::new(value, item.get_created().clone(), item.get_expire().clone());
+
+        if let Some(dead_row) = dead_row {
+            let id = dead_row.get_id();
+            let item_row = queue_schema.update(id, item, dead_row.get_row(), batch_pipe)?;
+            let payload_row = if let Some(old_payload) = queue_payload_schema.get_row(id)? {
+                queue_payload_schema.update(id, payload, old_payload.get_row(), batch_pipe)?
+            } else {
+                queue_payload_schema.insert_with_pk(id, payload, batch_pipe)?
+            };
+
+            Ok((item_row, payload_row))
+        } else {
+            let item_row = queue_schema.insert(item, batch_pipe)?;
+            let payload_row =
+                queue_payload_schema.insert_with_pk(item_row.get_id(), payload, batch_pipe)?;
+
+            Ok((item_row, payload_row))
+        }
+    }
+
     /// The budget is prefix scoped, exclusivity and priority blind. Shared by
     /// `QUEUE RETRIEVE` and `QUEUE ADD_AND_RETRIEVE` so that they cannot drift apart.
     fn queue_prefix_counters(
@@ -748,7 +1008,9 @@ impl RocksCacheStore {
         pending: u64,
         mut active: Vec<String>,
     ) -> Result<QueueRetrieveResponse, CubeError> {
-        if id_row.get_row().get_status() != &QueueItemStatus::Pending {
+        if id_row.get_row().get_status() != &QueueItemStatus::Pending
+            || id_row.get_row().is_delayed(&Utc::now())
+        {
             return Ok(QueueRetrieveResponse::LockFailed { pending, active });
         }
 
@@ -854,6 +1116,10 @@ pub struct QueueAddPayload {
     pub process_id: Option<String>,
     pub exclusive: bool,
     pub external_id: Option<String>,
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    #[serde(default)]
+    pub visible_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -865,6 +1131,10 @@ pub struct QueueAddAndRetrievePayload {
     pub process_id: Option<String>,
     pub exclusive: bool,
     pub external_id: Option<String>,
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    #[serde(default)]
+    pub visible_at: Option<DateTime<Utc>>,
     /// The same budget as `QUEUE RETRIEVE CONCURRENCY` uses
     pub concurrency: u32,
 }
@@ -1041,6 +1311,14 @@ pub struct QueueAllItem {
     pub payload: Option<String>,
 }
 
+/// Outcome of a cache write, used to keep the eviction manager stats in sync.
+enum CacheItemChange {
+    None,
+    Inserted(u64),
+    Updated(IdRow<CacheItem>, u64),
+    Deleted(u64, u64),
+}
+
 #[cuberpc::service]
 pub trait CacheStore: DIService + Send + Sync {
     // cache
@@ -1057,6 +1335,33 @@ pub trait CacheStore: DIService + Send + Sync {
     async fn cache_get(&self, key: String) -> Result<Option<IdRow<CacheItem>>, CubeError>;
     async fn cache_keys(&self, prefix: String) -> Result<Vec<IdRow<CacheItem>>, CubeError>;
     async fn cache_incr(&self, key: String) -> Result<IdRow<CacheItem>, CubeError>;
+    /// Compare-and-set, `version` 0 expects the key to be missing.
+    async fn cache_set_if_version(&self, item: CacheItem, version: u64) -> Result<bool, CubeError>;
+    async fn cache_hset(
+        &self,
+        key: String,
+        field: String,
+        value: String,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError>;
+    async fn cache_hget(&self, key: String, field: String) -> Result<Option<String>, CubeError>;
+    async fn cache_hgetall(&self, key: String) -> Result<Vec<(String, String)>, CubeError>;
+    async fn cache_hdel(&self, key: String, field: String) -> Result<bool, CubeError>;
+    async fn cache_zadd(
+        &self,
+        key: String,
+        member: String,
+        score: f64,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError>;
+    async fn cache_zrange(
+        &self,
+        key: String,
+        start: i64,
+        stop: i64,
+        rev: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError>;
+    async fn cache_zrem(&self, key: String, member: String) -> Result<bool, CubeError>;
 
     // queue
     async fn queue_all(&self, limit: Option<usize>) -> Result<Vec<QueueAllItem>, CubeError>;
@@ -1071,6 +1376,9 @@ pub trait CacheStore: DIService + Send + Sync {
         payload: QueueAddAndRetrievePayload,
     ) -> Result<QueueAddAndRetrieveResponse, CubeError>;
     async fn queue_clear(&self) -> Result<(), CubeError>;
+    /// Returns orphaned and stalled items of the prefix which should be cancelled by the caller.
+    /// Items with a retry policy (`max_attempts`) are never returned, they are re-queued or moved
+    /// to the dead status by the cachestore itself, see `RocksCacheStore::queue_retry_failed`.
     async fn queue_to_cancel(
         &self,
         prefix: String,
@@ -1122,6 +1430,13 @@ pub trait CacheStore: DIService + Send + Sync {
     // Wipe all cachestore state (cache + queue) and persist a fresh snapshot, updating the
     // remote cachestore-current pointer so a poisoned snapshot is not re-hydrated on reload
     async fn wipe(&self) -> Result<(), CubeError>;
+    // Upload a snapshot and copy it into `location`, see `SYS BACKUP`
+    async fn backup_snapshot(&self, location: String) -> Result<BackupStoreSnapshot, CubeError>;
+    async fn restore_snapshot(
+        &self,
+        location: String,
+        snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError>;
 }
 
 #[async_trait]
@@ -1139,14 +1454,7 @@ impl CacheStore for RocksCacheStore {
         item: CacheItem,
         update_if_not_exists: bool,
     ) -> Result<bool, CubeError> {
-        if item.get_value().len() >= self.store.config.cachestore_cache_max_entry_size() {
-            return Err(CubeError::user(format!(
-                "Unable to SET cache with '{}' key, exceeds maximum allowed size for payload: {}, max allowed: {}",
-                item.key,
-                humansize::format_size(item.get_value().len(), humansize::DECIMAL),
-                humansize::format_size(self.store.config.cachestore_cache_max_entry_size(), humansize::DECIMAL),
-            )));
-        }
+        Self::check_cache_entry_size(&item, self.store.config.cachestore_cache_max_entry_size())?;
 
         self.cache_eviction_manager
             .before_insert(item.get_value().len() as u64)
@@ -1160,11 +1468,16 @@ impl CacheStore for RocksCacheStore {
                 let id_row_opt = cache_schema
                     .get_single_opt_row_by_index(&index_key, &CacheItemRocksIndex::ByPath)?;
 
-                if let Some(id_row) = id_row_opt {
-                    if update_if_not_exists {
-                        return Ok((false, None));
-                    };
+                if id_row_opt.is_some() && update_if_not_exists {
+                    return Ok((false, None));
+                };
+
+                batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                    CacheNotificationEvent::new(CacheNotificationKind::Set, item.get_path(), None),
+                ));
 
+                let item = item.with_version(Self::next_cache_version(&db_ref));
+                if let Some(id_row) = id_row_opt {
                     cache_schema.update(id_row.id, item, &id_row.row, batch_pipe)?;
                     Ok((true, None))
                 } else {
@@ -1191,6 +1504,9 @@ impl CacheStore for RocksCacheStore {
             .write_operation("cache_clear", move |db_ref, batch_pipe| {
                 let cache_schema = CacheItemRocksTable::new(db_ref);
                 cache_schema.truncate(batch_pipe)?;
+                batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                    CacheNotificationEvent::clear(),
+                ));
 
                 Ok(())
             })
@@ -1218,6 +1534,11 @@ impl CacheStore for RocksCacheStore {
         self.cache_eviction_manager.notify_truncate_end().await?;
         drop(block);
 
+        if result.is_ok() {
+            // The range delete doesn't go through the tables, so no per-key events were emitted
+            self.notify_cache_cleared().await;
+        }
+
         result
     }
 
@@ -1234,6 +1555,13 @@ impl CacheStore for RocksCacheStore {
                     let row_id = row.id;
                     let raw_size = row.get_row().get_value().len();
 
+                    batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                        CacheNotificationEvent::new(
+                            CacheNotificationKind::Remove,
+                            row.get_row().get_path(),
+                            None,
+                        ),
+                    ));
                     cache_schema.delete_row(row, batch_pipe)?;
 
                     Ok(Some((row_id, raw_size)))
@@ -1295,17 +1623,30 @@ impl CacheStore for RocksCacheStore {
                     .get_single_opt_row_by_index(&index_key, &CacheItemRocksIndex::ByPath)?;
 
                 // TODO: Merge operator?
-                if let Some(id_row) = id_row_opt {
+                let item = if let Some(id_row) = id_row_opt {
+                    id_row.row.ensure_value_type(CacheItemValueType::String)?;
                     let mut new = id_row.row.clone();
 
                     let last_val = id_row.row.value.parse::<i64>()?;
                     new.value = (last_val + 1).to_string();
+                    new.version = Self::next_cache_version(&db_ref);
 
-                    cache_schema.update(id_row.id, new, &id_row.row, batch_pipe)
+                    cache_schema.update(id_row.id, new, &id_row.row, batch_pipe)?
                 } else {
-                    let item = CacheItem::new(path, None, "1".to_string());
-                    cache_schema.insert(item, batch_pipe)
-                }
+                    let item = CacheItem::new(path, None, "1".to_string())
+                        .with_version(Self::next_cache_version(&db_ref));
+                    cache_schema.insert(item, batch_pipe)?
+                };
+
+                batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                    CacheNotificationEvent::new(
+                        CacheNotificationKind::Set,
+                        item.get_row().get_path(),
+                        None,
+                    ),
+                ));
+
+                Ok(item)
             })
             .await?;
 
@@ -1314,6 +1655,158 @@ impl CacheStore for RocksCacheStore {
         Ok(item)
     }
 
+    async fn cache_set_if_version(&self, item: CacheItem, version: u64) -> Result<bool, CubeError> {
+        Self::check_cache_entry_size(&item, self.store.config.cachestore_cache_max_entry_size())?;
+
+        self.cache_eviction_manager
+            .before_insert(item.get_value().len() as u64)
+            .await?;
+
+        let (result, change) = self
+            .store
+            .write_operation("cache_set_if_version", move |db_ref, batch_pipe| {
+                let cache_schema = CacheItemRocksTable::new(db_ref.clone());
+                let index_key = CacheItemIndexKey::ByPath(item.get_path());
+                let id_row_opt = cache_schema
+                    .get_single_opt_row_by_index(&index_key, &CacheItemRocksIndex::ByPath)?;
+
+                let current_version = id_row_opt
+                    .as_ref()
+                    .map(|id_row| id_row.get_row().get_version())
+                    .unwrap_or(0);
+                if current_version != version {
+                    return Ok((false, CacheItemChange::None));
+                }
+
+                batch_pipe.add_event(MetaStoreEvent::CacheNotification(
+                    CacheNotificationEvent::new(CacheNotificationKind::Set, item.get_path(), None),
+                ));
+
+                let item = item.with_version(Self::next_cache_version(&db_ref));
+                if let Some(id_row) = id_row_opt {
+                    let old_raw_size = id_row.get_row().get_value().len() as u64;
+
+                    Ok((
+                        true,
+                        CacheItemChange::Updated(
+                            cache_schema.update(id_row.id, item, &id_row.row, batch_pipe)?,
+                            old_raw_size,
+                        ),
+                    ))
+                } else {
+                    let raw_size = item.get_value().len() as u64;
+                    cache_schema.insert(item, batch_pipe)?;
+
+                    Ok((true, CacheItemChange::Inserted(raw_size)))
+                }
+            })
+            .await?;
+
+        self.notify_cache_item_change(change)?;
+
+        Ok(result)
+    }
+
+    async fn cache_hset(
+        &self,
+        key: String,
+        field: String,
+        value: String,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        self.cache_eviction_manager
+            .before_insert((field.len() + value.len()) as u64)
+            .await?;
+
+        let empty = CacheItem::new_hash(key.clone(), None);
+        self.cache_update_collection("cache_hset", key, Some(empty), ttl, move |item| {
+            let mut fields = item.hash_fields()?;
+            let created = fields.insert(field, value).is_none();
+            item.set_hash_fields(&fields)?;
+
+            Ok((created, true))
+        })
+        .await
+    }
+
+    async fn cache_hget(&self, key: String, field: String) -> Result<Option<String>, CubeError> {
+        if let Some(row) = self.cache_get(key).await? {
+            Ok(row.get_row().hash_fields()?.remove(&field))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn cache_hgetall(&self, key: String) -> Result<Vec<(String, String)>, CubeError> {
+        if let Some(row) = self.cache_get(key).await? {
+            Ok(row.get_row().hash_fields()?.into_iter().collect())
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    async fn cache_hdel(&self, key: String, field: String) -> Result<bool, CubeError> {
+        self.cache_update_collection("cache_hdel", key, None, None, move |item| {
+            let mut fields = item.hash_fields()?;
+            let removed = fields.remove(&field).is_some();
+            if removed {
+                item.set_hash_fields(&fields)?;
+            }
+
+            Ok((removed, removed))
+        })
+        .await
+    }
+
+    async fn cache_zadd(
+        &self,
+        key: String,
+        member: String,
+        score: f64,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        self.cache_eviction_manager
+            .before_insert(member.len() as u64)
+            .await?;
+
+        let empty = CacheItem::new_sorted_set(key.clone(), None);
+        self.cache_update_collection("cache_zadd", key, Some(empty), ttl, move |item| {
+            let mut members = item.sorted_set_members()?;
+            let created = members.insert(member, score).is_none();
+            item.set_sorted_set_members(&members)?;
+
+            Ok((created, true))
+        })
+        .await
+    }
+
+    async fn cache_zrange(
+        &self,
+        key: String,
+        start: i64,
+        stop: i64,
+        rev: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError> {
+        if let Some(row) = self.cache_get(key).await? {
+            row.get_row().sorted_set_range(start, stop, rev)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    async fn cache_zrem(&self, key: String, member: String) -> Result<bool, CubeError> {
+        self.cache_update_collection("cache_zrem", key, None, None, move |item| {
+            let mut members = item.sorted_set_members()?;
+            let removed = members.remove(&member).is_some();
+            if removed {
+                item.set_sorted_set_members(&members)?;
+            }
+
+            Ok((removed, removed))
+        })
+        .await
+    }
+
     async fn queue_all(&self, limit: Option<usize>) -> Result<Vec<QueueAllItem>, CubeError> {
         self.store
             .read_operation("queue_all", move |db_ref| {
@@ -1378,33 +1871,36 @@ impl CacheStore for RocksCacheStore {
             )?;
 
             let index_key = QueueItemIndexKey::ByPath(payload.path.clone());
-            let id_row_opt = queue_schema
+            let mut id_row_opt = queue_schema
                 .get_single_opt_row_by_index(&index_key, &QueueItemRocksIndex::ByPath)?;
+            let dead_row =
+                id_row_opt.take_if(|row| row.get_row().get_status() == &QueueItemStatus::Dead);
 
             let (id, added) = if let Some(row) = id_row_opt {
                 (row.id, false)
             } else {
-                let queue_item_row = queue_schema.insert(
-                    QueueItem::new(
-                        payload.path,
-                        QueueItem::status_default(),
-                        payload.priority,
-                        payload.orphaned.clone(),
-                        payload.process_id,
-                        payload.exclusive,
-                        payload.external_id,
-                    ),
-                    batch_pipe,
-                )?;
+                let mut item = QueueItem::new(
+                    payload.path,
+                    QueueItem::status_default(),
+                    payload.priority,
+                    payload.orphaned.clone(),
+                    payload.process_id,
+                    payload.exclusive,
+                    payload.external_id,
+                );
+                item.max_attempts = payload.max_attempts;
+                if let Some(visible_at) = payload.visible_at {
+                    item.set_visible_at(visible_at);
+                }
+
                 let queue_payload_schema = QueueItemPayloadRocksTable::new(db_ref.clone());
-                queue_payload_schema.insert_with_pk(
-                    queue_item_row.id,
-                    QueueItemPayload::new(
-                        payload.value,
-                        queue_item_row.row.get_created().clone(),
-                        queue_item_row.row.get_expire().clone(),
-                    ),
+                let (queue_item_row, _) = Self::insert_or_revive_queue_item(
+                    &queue_schema,
+                    &queue_payload_schema,
                     batch_pipe,
+                    dead_row,
+                    item,
+                    payload.value,
                 )?;
 
                 (queue_item_row.id, true)
@@ -1434,8 +1930,10 @@ impl CacheStore for RocksCacheStore {
             let (pending, mut active) = Self::queue_prefix_counters(&queue_schema, &payload.path)?;
 
             let index_key = QueueItemIndexKey::ByPath(payload.path.clone());
-            let id_row_opt = queue_schema
+            let mut id_row_opt = queue_schema
                 .get_single_opt_row_by_index(&index_key, &QueueItemRocksIndex::ByPath)?;
+            let dead_row =
+                id_row_opt.take_if(|row| row.get_row().get_status() == &QueueItemStatus::Dead);
 
             // An item which is already pending is not a part of its own backlog
             let backlog = match &id_row_opt {
@@ -1471,6 +1969,10 @@ impl CacheStore for RocksCacheStore {
                 return Ok(QueueAddAndRetrieveResponse::from_claim(id, claim_result));
             }
 
+            // A delayed item is never claimed on insert, it's left to QUEUE RETRIEVE
+            let claim =
+                claim && !matches!(&payload.visible_at, Some(at) if at > &db_ref.start_time);
+
             // Inserting a claimed item as active saves an update of the just written row
             // (and its secondary indexes) inside the same batch
             let mut item = QueueItem::new(
@@ -1486,23 +1988,24 @@ impl CacheStore for RocksCacheStore {
                 payload.exclusive,
                 payload.external_id,
             );
+            item.max_attempts = payload.max_attempts;
+            if let Some(visible_at) = payload.visible_at {
+                item.set_visible_at(visible_at);
+            }
             if claim {
                 // It's important to insert heartbeat, because
                 // without that created datetime will be used for orphaned filtering
                 item.update_heartbeat();
             }
 
-            let queue_item_row = queue_schema.insert(item, batch_pipe)?;
-
             let queue_payload_schema = QueueItemPayloadRocksTable::new(db_ref.clone());
-            let queue_payload_row = queue_payload_schema.insert_with_pk(
-                queue_item_row.id,
-                QueueItemPayload::new(
-                    payload.value,
-                    queue_item_row.row.get_created().clone(),
-                    queue_item_row.row.get_expire().clone(),
-                ),
+            let (queue_item_row, queue_payload_row) = Self::insert_or_revive_queue_item(
+                &queue_schema,
+                &queue_payload_schema,
                 batch_pipe,
+                dead_row,
+                item,
+                payload.value,
             )?;
 
             // The value can be huge, take it back from the inserted row instead of cloning
@@ -1557,6 +2060,12 @@ impl CacheStore for RocksCacheStore {
             let items =
                 queue_schema.get_rows_by_index(&index_key, &QueueItemRocksIndex::ByPrefix)?;
 
+            // Items with a retry policy are handled by the retry loop, see `queue_retry_failed`
+            let items = items
+                .into_iter()
+                .filter(|item| item.get_row().get_max_attempts().is_none())
+                .collect();
+
             Ok(Self::filter_to_cancel(
                 db_ref.start_time.clone(),
                 items,
@@ -1593,9 +2102,16 @@ impl CacheStore for RocksCacheStore {
                 .collect();
 
             let items = if priority_sort {
+                // Delayed items go after the claimable ones, they can't be retrieved yet
+                let now = db_ref.start_time.clone();
                 items
                     .into_iter()
-                    .sorted_by(|a, b| b.row.cmp(&a.row))
+                    .sorted_by(|a, b| {
+                        a.row
+                            .is_delayed(&now)
+                            .cmp(&b.row.is_delayed(&now))
+                            .then_with(|| b.row.cmp(&a.row))
+                    })
                     .collect()
             } else {
                 items
@@ -1796,50 +2312,50 @@ impl CacheStore for RocksCacheStore {
         key: QueueKey,
         timeout: u64,
     ) -> Result<Option<QueueResultResponse>, CubeError> {
-        // It's important to open listener at the beginning to protect race condition
-        // it will fix the position (subscribe) of a broadcast channel
-        let listener = self.get_listener().await;
-
-        let store_in_result = self.lookup_queue_result_by_key(key.clone(), None).await?;
-        if store_in_result.is_some() {
-            return Ok(store_in_result);
-        }
-
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout);
         let query_key_is_path = key.is_path();
-        let fut = tokio::time::timeout(
-            Duration::from_millis(timeout),
-            listener.wait_for_queue_ack_by_key(key),
-        );
+        let ack_event = loop {
+            // It's important to open listener at the beginning to protect race condition
+            // it will fix the position (subscribe) of a broadcast channel
+            let listener = self.get_listener().await;
+
+            let store_in_result = self.lookup_queue_result_by_key(key.clone(), None).await?;
+            if store_in_result.is_some() {
+                return Ok(store_in_result);
+            }
 
-        if let Ok(res) = fut.await {
-            match res {
-                Ok(Some(ack_event)) => match ack_event.result {
-                    QueueResultAckEventResult::Empty => Ok(Some(QueueResultResponse::Success {
-                        value: None,
-                        id: ack_event.id,
-                        external_id: None,
-                    })),
-                    QueueResultAckEventResult::WithResult { result } => {
-                        if query_key_is_path {
-                            // Queue v1 behavior
-                            self.queue_result_delete_by_id(ack_event.id).await?;
-                        } else {
-                            // Queue v2 behavior
-                            self.queue_result_ready_to_delete(ack_event.id).await?;
-                        }
+            let fut =
+                tokio::time::timeout_at(deadline, listener.wait_for_queue_ack_by_key(key.clone()));
+            match fut.await {
+                Ok(Ok(Some(ack_event))) => break ack_event,
+                // The listener lagged behind and might have skipped the ack, check the store again
+                Ok(Ok(None)) => continue,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => return Ok(None),
+            }
+        };
 
-                        Ok(Some(QueueResultResponse::Success {
-                            value: Some(result.to_string()),
-                            id: ack_event.id,
-                            external_id: None,
-                        }))
-                    }
-                },
-                Ok(None) => Ok(None),
-                Err(e) => Err(e),
+        match ack_event.result {
+            QueueResultAckEventResult::Empty => Ok(Some(QueueResultResponse::Success {
+                value: None,
+                id: ack_event.id,
+                external_id: None,
+            })),
+            QueueResultAckEventResult::WithResult { result } => {
+                if query_key_is_path {
+                    // Queue v1 behavior
+                    self.queue_result_delete_by_id(ack_event.id).await?;
+                } else {
+                    // Queue v2 behavior
+                    self.queue_result_ready_to_delete(ack_event.id).await?;
+                }
+
+                Ok(Some(QueueResultResponse::Success {
+                    value: Some(result.to_string()),
+                    id: ack_event.id,
+                    external_id: None,
+                }))
             }
-        } else {
-            Ok(None)
         }
     }
 
@@ -1932,9 +2448,23 @@ impl CacheStore for RocksCacheStore {
             "cachestore wipe is only supported through LazyRocksCacheStore".to_string(),
         ))
     }
-}
 
-crate::di_service!(RocksCacheStore, [CacheStore]);
+    async fn backup_snapshot(&self, location: String) -> Result<BackupStoreSnapshot, CubeError> {
+        let (snapshot, _) = self.store.backup_to(&location, |_| Ok(())).await?;
+
+        Ok(snapshot)
+    }
+
+    async fn restore_snapshot(
+        &self,
+        location: String,
+        snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        self.store.restore_from(&location, &snapshot).await
+    }
+}
+
+crate::di_service!(RocksCacheStore, [CacheStore]);
 crate::di_service!(CacheStoreRpcClient, [CacheStore]);
 
 pub struct ClusterCacheStoreClient {}
@@ -1977,6 +2507,56 @@ impl CacheStore for ClusterCacheStoreClient {
         panic!("CacheStore cannot be used on the worker node! cache_incr was used.")
     }
 
+    async fn cache_set_if_version(&self, _: CacheItem, _: u64) -> Result<bool, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_set_if_version was used.")
+    }
+
+    async fn cache_hset(
+        &self,
+        _: String,
+        _: String,
+        _: String,
+        _: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_hset was used.")
+    }
+
+    async fn cache_hget(&self, _: String, _: String) -> Result<Option<String>, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_hget was used.")
+    }
+
+    async fn cache_hgetall(&self, _: String) -> Result<Vec<(String, String)>, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_hgetall was used.")
+    }
+
+    async fn cache_hdel(&self, _: String, _: String) -> Result<bool, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_hdel was used.")
+    }
+
+    async fn cache_zadd(
+        &self,
+        _: String,
+        _: String,
+        _: f64,
+        _: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_zadd was used.")
+    }
+
+    async fn cache_zrange(
+        &self,
+        _: String,
+        _: i64,
+        _: i64,
+        _: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_zrange was used.")
+    }
+
+    async fn cache_zrem(&self, _: String, _: String) -> Result<bool, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! cache_zrem was used.")
+    }
+
     async fn queue_all(&self, _limit: Option<usize>) -> Result<Vec<QueueAllItem>, CubeError> {
         panic!("CacheStore cannot be used on the worker node! queue_all was used.")
     }
@@ -2099,6 +2679,14 @@ impl CacheStore for ClusterCacheStoreClient {
     async fn wipe(&self) -> Result<(), CubeError> {
         panic!("CacheStore cannot be used on the worker node! wipe was used.")
     }
+
+    async fn backup_snapshot(&self, _: String) -> Result<BackupStoreSnapshot, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! backup_snapshot was used.")
+    }
+
+    async fn restore_snapshot(&self, _: String, _: BackupStoreSnapshot) -> Result<u128, CubeError> {
+        panic!("CacheStore cannot be used on the worker node! restore_snapshot was used.")
+    }
 }
 
 crate::di_service!(ClusterCacheStoreClient, [CacheStore]);
@@ -2106,7 +2694,7 @@ crate::di_service!(ClusterCacheStoreClient, [CacheStore]);
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cachestore::{CacheEvictionPolicy, EvictionFinishedResult};
+    use crate::cachestore::{CacheEvictionPolicy, CacheStoreNotifier, EvictionFinishedResult};
     use crate::config::{init_test_logger, ConfigObjImpl, CubeServices};
     use crate::CubeError;
 
@@ -2189,6 +2777,267 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_cache_set_if_version() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let (_, cachestore) = RocksCacheStore::prepare_test_cachestore(
+            "cache_set_if_version",
+            Config::test("cache_set_if_version"),
+        );
+
+        let path = "prefix:cas".to_string();
+        let item = |value: &str| CacheItem::new(path.clone(), None, value.to_string());
+
+        let version = |cachestore: Arc<RocksCacheStore>, path: String| async move {
+            cachestore
+                .cache_get(path)
+                .await
+                .unwrap()
+                .expect("must return row")
+                .into_row()
+                .get_version()
+        };
+
+        assert_eq!(cachestore.cache_set_if_version(item("v1"), 1).await?, false);
+        assert_eq!(cachestore.cache_set_if_version(item("v1"), 0).await?, true);
+        assert_eq!(cachestore.cache_set_if_version(item("v2"), 0).await?, false);
+        let v1 = version(cachestore.clone(), path.clone()).await;
+        assert_eq!(cachestore.cache_set_if_version(item("v2"), v1).await?, true);
+        let v2 = version(cachestore.clone(), path.clone()).await;
+        assert!(v2 > v1);
+
+        cachestore.cache_set(item("v3"), false).await?;
+        let v3 = version(cachestore.clone(), path.clone()).await;
+        assert!(v3 > v2);
+
+        assert_eq!(
+            cachestore.cache_set_if_version(item("v4"), v2).await?,
+            false
+        );
+        assert_eq!(cachestore.cache_set_if_version(item("v4"), v3).await?, true);
+        let v4 = version(cachestore.clone(), path.clone()).await;
+
+        // Versions don't restart when a key is created again, a stale version can't win
+        cachestore.cache_delete(path.clone()).await?;
+        assert_eq!(cachestore.cache_set_if_version(item("v5"), 0).await?, true);
+        cachestore.cache_delete(path.clone()).await?;
+        cachestore.cache_set(item("v6"), false).await?;
+        assert!(version(cachestore.clone(), path.clone()).await > v4);
+        assert_eq!(
+            cachestore.cache_set_if_version(item("v7"), v1).await?,
+            false
+        );
+        assert_eq!(
+            cachestore.cache_set_if_version(item("v7"), v4).await?,
+            false
+        );
+
+        RocksCacheStore::cleanup_test_cachestore("cache_set_if_version");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_hash() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let (_, cachestore) =
+            RocksCacheStore::prepare_test_cachestore("cache_hash", Config::test("cache_hash"));
+
+        let key = "prefix:hash".to_string();
+        let hset = |field: &str, value: &str| {
+            cachestore.cache_hset(key.clone(), field.to_string(), value.to_string(), None)
+        };
+
+        assert_eq!(hset("a", "1").await?, true);
+        assert_eq!(hset("b", "2").await?, true);
+        assert_eq!(hset("a", "3").await?, false);
+
+        assert_eq!(
+            cachestore.cache_hget(key.clone(), "a".to_string()).await?,
+            Some("3".to_string())
+        );
+        assert_eq!(
+            cachestore.cache_hget(key.clone(), "c".to_string()).await?,
+            None
+        );
+        assert_eq!(
+            cachestore.cache_hgetall(key.clone()).await?,
+            vec![
+                ("a".to_string(), "3".to_string()),
+                ("b".to_string(), "2".to_string())
+            ]
+        );
+
+        // Plain string commands must not be applied to a hash
+        assert!(cachestore.cache_incr(key.clone()).await.is_err());
+        assert!(cachestore
+            .cache_zadd(key.clone(), "a".to_string(), 1.0, None)
+            .await
+            .is_err());
+
+        assert_eq!(
+            cachestore.cache_hdel(key.clone(), "c".to_string()).await?,
+            false
+        );
+        assert_eq!(
+            cachestore.cache_hdel(key.clone(), "a".to_string()).await?,
+            true
+        );
+        assert_eq!(
+            cachestore.cache_hdel(key.clone(), "b".to_string()).await?,
+            true
+        );
+
+        // Hash without fields is removed
+        assert!(cachestore.cache_get(key.clone()).await?.is_none());
+        assert_eq!(
+            cachestore.cache_hdel(key.clone(), "b".to_string()).await?,
+            false
+        );
+
+        RocksCacheStore::cleanup_test_cachestore("cache_hash");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_sorted_set() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let (_, cachestore) = RocksCacheStore::prepare_test_cachestore(
+            "cache_sorted_set",
+            Config::test("cache_sorted_set"),
+        );
+
+        let key = "prefix:zset".to_string();
+        let zadd = |member: &str, score: f64| {
+            cachestore.cache_zadd(key.clone(), member.to_string(), score, Some(60))
+        };
+
+        assert_eq!(zadd("a", 3.0).await?, true);
+        assert_eq!(zadd("b", 1.0).await?, true);
+        assert_eq!(zadd("c", 2.0).await?, true);
+        assert_eq!(zadd("a", 0.5).await?, false);
+
+        assert_eq!(
+            cachestore.cache_zrange(key.clone(), 0, -1, false).await?,
+            vec![
+                ("a".to_string(), 0.5),
+                ("b".to_string(), 1.0),
+                ("c".to_string(), 2.0)
+            ]
+        );
+        assert_eq!(
+            cachestore.cache_zrange(key.clone(), 0, 0, true).await?,
+            vec![("c".to_string(), 2.0)]
+        );
+        assert!(cachestore
+            .cache_zrange("prefix:missing".to_string(), 0, -1, false)
+            .await?
+            .is_empty());
+
+        let row = cachestore
+            .cache_get(key.clone())
+            .await?
+            .expect("must return row")
+            .into_row();
+        assert_eq!(row.get_value_type(), &CacheItemValueType::SortedSet);
+        assert!(row.get_version() > CacheItem::initial_version());
+        assert_eq!(row.expire.is_some(), true);
+
+        assert_eq!(
+            cachestore.cache_zrem(key.clone(), "a".to_string()).await?,
+            true
+        );
+        assert_eq!(
+            cachestore.cache_zrem(key.clone(), "a".to_string()).await?,
+            false
+        );
+        assert_eq!(
+            cachestore.cache_zrem(key.clone(), "b".to_string()).await?,
+            true
+        );
+        assert_eq!(
+            cachestore.cache_zrem(key.clone(), "c".to_string()).await?,
+            true
+        );
+        assert!(cachestore.cache_get(key).await?.is_none());
+
+        RocksCacheStore::cleanup_test_cachestore("cache_sorted_set");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_notifications() -> Result<(), CubeError> {
+        use futures::StreamExt;
+
+        init_test_logger().await;
+
+        let (_, cachestore) = RocksCacheStore::prepare_test_cachestore(
+            "cache_notifications",
+            Config::test("cache_notifications"),
+        );
+        let (sender, _) = tokio::sync::broadcast::channel(64);
+        cachestore.add_listener(sender.clone()).await;
+
+        let notifier = CacheStoreNotifier::new(sender);
+        let mut notifications = notifier.subscribe("refresh:".to_string());
+
+        cachestore
+            .cache_set(
+                CacheItem::new("refresh:orders".to_string(), None, "v1".to_string()),
+                false,
+            )
+            .await?;
+        // NX doesn't overwrite the key, nothing to notify about
+        cachestore
+            .cache_set(
+                CacheItem::new("refresh:orders".to_string(), None, "v2".to_string()),
+                true,
+            )
+            .await?;
+        cachestore
+            .cache_set(
+                CacheItem::new("lock:orders".to_string(), None, "1".to_string()),
+                false,
+            )
+            .await?;
+        cachestore.cache_incr("refresh:counter".to_string()).await?;
+        cachestore
+            .cache_delete("refresh:orders".to_string())
+            .await?;
+        cachestore
+            .cache_delete("refresh:unknown".to_string())
+            .await?;
+        notifier.publish("refresh:all".to_string(), "hello".to_string());
+        // Keys removed in bulk are reported by a single event matching every prefix
+        cachestore.cache_clear().await?;
+        cachestore.truncate().await?;
+
+        let expected = vec![
+            (CacheNotificationKind::Set, "refresh:orders", None),
+            (CacheNotificationKind::Set, "refresh:counter", None),
+            (CacheNotificationKind::Remove, "refresh:orders", None),
+            (CacheNotificationKind::Message, "refresh:all", Some("hello")),
+            (CacheNotificationKind::Clear, "", None),
+            (CacheNotificationKind::Clear, "", None),
+        ];
+
+        for (kind, key, value) in expected {
+            let event = notifications.next().await.unwrap()?;
+            assert_eq!(event.kind, kind);
+            assert_eq!(event.key, key);
+            assert_eq!(event.value.as_ref().map(|v| v.as_str()), value);
+        }
+
+        RocksCacheStore::cleanup_test_cachestore("cache_notifications");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_truncate() -> Result<(), CubeError> {
         init_test_logger().await;
@@ -2220,6 +3069,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
 
@@ -2674,6 +3525,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: Some("ext-dup".to_string()),
+                max_attempts: None,
+                visible_at: None,
             })
             .await;
         assert!(res.is_ok(), "First insert with external_id should succeed");
@@ -2689,6 +3542,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: Some("ext-dup".to_string()),
+                max_attempts: None,
+                visible_at: None,
             })
             .await;
         assert!(
@@ -2707,6 +3562,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: Some("ext-dup".to_string()),
+                max_attempts: None,
+                visible_at: None,
             })
             .await;
         assert!(res.is_ok(), "Duplicate path should return added: false");
@@ -2723,6 +3580,8 @@ mod tests {
                     process_id: None,
                     exclusive: false,
                     external_id: None,
+                    max_attempts: None,
+                    visible_at: None,
                 })
                 .await;
             assert!(
@@ -2740,6 +3599,8 @@ mod tests {
                     process_id: None,
                     exclusive: false,
                     external_id: None,
+                    max_attempts: None,
+                    visible_at: None,
                 })
                 .await;
             assert!(
@@ -2791,6 +3652,8 @@ mod tests {
             process_id: None,
             exclusive: false,
             external_id: None,
+            max_attempts: None,
+            visible_at: None,
             concurrency,
         }
     }
@@ -2866,6 +3729,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
         assert!(res.added);
@@ -2897,6 +3762,8 @@ mod tests {
                     process_id: None,
                     exclusive: false,
                     external_id: None,
+                    max_attempts: None,
+                    visible_at: None,
                 })
                 .await?;
         }
@@ -3019,6 +3886,217 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_queue_retry_dead_letter() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let (_, cachestore) = RocksCacheStore::prepare_test_cachestore(
+            "test_queue_retry_dead_letter",
+            Config::test("test_queue_retry_dead_letter"),
+        );
+
+        for (path, max_attempts) in [
+            ("prefix:retry", Some(2)),
+            ("prefix:once", Some(1)),
+            ("prefix:legacy", None),
+        ] {
+            cachestore
+                .queue_add_and_retrieve(QueueAddAndRetrievePayload {
+                    max_attempts,
+                    ..queue_add_and_retrieve_payload(path, "v", 3)
+                })
+                .await?;
+        }
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // Only the item without a retry policy is reported for cancellation
+        let to_cancel = cachestore
+            .queue_to_cancel("prefix".to_string(), None, Some(1))
+            .await?;
+        assert_eq!(
+            to_cancel
+                .iter()
+                .map(|item| item.get_row().get_key().clone())
+                .collect::<Vec<_>>(),
+            vec!["legacy".to_string()]
+        );
+
+        // Reporting doesn't touch the items, the retry pass does
+        assert_queue_item_status(&cachestore, "retry", QueueItemStatus::Active, true).await?;
+        assert_eq!(cachestore.queue_retry_failed(1).await?, 2);
+
+        assert_queue_item_status(&cachestore, "retry", QueueItemStatus::Pending, false).await?;
+        assert_queue_item_status(&cachestore, "once", QueueItemStatus::Dead, false).await?;
+
+        // A re-queued item cannot be claimed during its backoff
+        let res = cachestore
+            .queue_retrieve_by_path("prefix:retry".to_string(), 3, None)
+            .await?;
+        assert!(
+            matches!(res, QueueRetrieveResponse::LockFailed { .. }),
+            "actual: {:?}",
+            res
+        );
+
+        let dead = cachestore
+            .queue_list(
+                "prefix".to_string(),
+                Some(QueueItemStatus::Dead),
+                true,
+                true,
+                None,
+            )
+            .await?;
+        assert_eq!(dead.len(), 1);
+        match &dead[0] {
+            QueueListItem::WithPayload(item, payload) => {
+                assert_eq!(item.get_row().get_key(), "once");
+                assert_eq!(item.get_row().get_attempts(), 1);
+                assert_eq!(payload, "v");
+            }
+            other => panic!("Expected QueueListItem::WithPayload, actual: {:?}", other),
+        }
+
+        // Adding a dead item again revives it from scratch
+        let res = cachestore
+            .queue_add(QueueAddPayload {
+                path: "prefix:once".to_string(),
+                value: "v2".to_string(),
+                priority: 0,
+                orphaned: None,
+                process_id: None,
+                exclusive: false,
+                external_id: None,
+                max_attempts: Some(1),
+            })
+            .await?;
+        assert!(res.added);
+        assert_queue_item_status(&cachestore, "once", QueueItemStatus::Pending, false).await?;
+
+        let res = cachestore
+            .queue_retrieve_by_path("prefix:once".to_string(), 3, None)
+            .await?;
+        match res {
+            QueueRetrieveResponse::Success { item, payload, .. } => {
+                assert_eq!(item.get_attempts(), 0);
+                assert_eq!(payload, "v2");
+            }
+            other => panic!(
+                "Expected QueueRetrieveResponse::Success, actual: {:?}",
+                other
+            ),
+        }
+
+        RocksCacheStore::cleanup_test_cachestore("test_queue_retry_dead_letter");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_add_delayed() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let (_, cachestore) = RocksCacheStore::prepare_test_cachestore(
+            "test_queue_add_delayed",
+            Config::test("test_queue_add_delayed"),
+        );
+
+        let visible_at = Some(Utc::now() + chrono::Duration::hours(1));
+
+        let res = cachestore
+            .queue_add(QueueAddPayload {
+                path: "prefix:delayed".to_string(),
+                value: "v1".to_string(),
+                priority: 10,
+                orphaned: None,
+                process_id: None,
+                exclusive: false,
+                external_id: None,
+                max_attempts: None,
+                visible_at,
+            })
+            .await?;
+        assert!(res.added);
+
+        let res = cachestore
+            .queue_add_and_retrieve(QueueAddAndRetrievePayload {
+                visible_at,
+                ..queue_add_and_retrieve_payload("prefix:delayed_claim", "v2", 5)
+            })
+            .await?;
+        assert!(res.added);
+        assert_eq!(res.payload, None);
+        assert_queue_item_status(
+            &cachestore,
+            "delayed_claim",
+            QueueItemStatus::Pending,
+            false,
+        )
+        .await?;
+
+        cachestore
+            .queue_add(QueueAddPayload {
+                path: "prefix:visible".to_string(),
+                value: "v3".to_string(),
+                priority: 0,
+                orphaned: None,
+                process_id: None,
+                exclusive: false,
+                external_id: None,
+                max_attempts: None,
+                visible_at: None,
+            })
+            .await?;
+
+        // Claimable items go first regardless of the priority
+        let pending = cachestore
+            .queue_list(
+                "prefix".to_string(),
+                Some(QueueItemStatus::Pending),
+                true,
+                false,
+                None,
+            )
+            .await?;
+        assert_eq!(
+            pending
+                .into_iter()
+                .map(|item| match item {
+                    QueueListItem::ItemOnly(item) => item.into_row().key,
+                    QueueListItem::WithPayload(item, _) => item.into_row().key,
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                "visible".to_string(),
+                "delayed".to_string(),
+                "delayed_claim".to_string()
+            ]
+        );
+
+        let res = cachestore
+            .queue_retrieve_by_path("prefix:delayed".to_string(), 5, None)
+            .await?;
+        assert!(
+            matches!(res, QueueRetrieveResponse::LockFailed { .. }),
+            "actual: {:?}",
+            res
+        );
+
+        let res = cachestore
+            .queue_retrieve_by_path("prefix:visible".to_string(), 5, None)
+            .await?;
+        assert!(
+            matches!(res, QueueRetrieveResponse::Success { .. }),
+            "actual: {:?}",
+            res
+        );
+
+        RocksCacheStore::cleanup_test_cachestore("test_queue_add_delayed");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_queue_add_none_external_id_after_rebuild() -> Result<(), CubeError> {
         init_test_logger().await;
@@ -3038,6 +4116,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
 
@@ -3050,6 +4130,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
 
@@ -3063,6 +4145,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: Some("ext-real".to_string()),
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
 
@@ -3091,6 +4175,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await;
         assert!(
@@ -3109,6 +4195,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: Some("ext-real".to_string()),
+                max_attempts: None,
+                visible_at: None,
             })
             .await;
         assert!(
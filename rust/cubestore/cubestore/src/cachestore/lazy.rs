@@ -5,10 +5,11 @@ use crate::cachestore::cache_rocksstore::{
 };
 use crate::cachestore::queue_item::QueueRetrieveResponse;
 use crate::cachestore::{
-    CacheItem, CacheStore, QueueCancelResponse, QueueItem, QueueItemStatus, QueueKey, QueueResult,
-    QueueResultResponse, RocksCacheStore,
+    CacheItem, CacheNotificationEvent, CacheStore, QueueCancelResponse, QueueItem, QueueItemStatus,
+    QueueKey, QueueResult, QueueResultResponse, RocksCacheStore,
 };
 use crate::config::ConfigObj;
+use crate::metastore::backup::BackupStoreSnapshot;
 use crate::metastore::{IdRow, MetaStoreEvent, MetaStoreFs, RocksPropertyRow};
 use crate::CubeError;
 use async_trait::async_trait;
@@ -275,6 +276,56 @@ impl CacheStore for LazyRocksCacheStore {
         self.init().await?.cache_incr(path).await
     }
 
+    async fn cache_set_if_version(&self, item: CacheItem, version: u64) -> Result<bool, CubeError> {
+        self.init().await?.cache_set_if_version(item, version).await
+    }
+
+    async fn cache_hset(
+        &self,
+        key: String,
+        field: String,
+        value: String,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        self.init().await?.cache_hset(key, field, value, ttl).await
+    }
+
+    async fn cache_hget(&self, key: String, field: String) -> Result<Option<String>, CubeError> {
+        self.init().await?.cache_hget(key, field).await
+    }
+
+    async fn cache_hgetall(&self, key: String) -> Result<Vec<(String, String)>, CubeError> {
+        self.init().await?.cache_hgetall(key).await
+    }
+
+    async fn cache_hdel(&self, key: String, field: String) -> Result<bool, CubeError> {
+        self.init().await?.cache_hdel(key, field).await
+    }
+
+    async fn cache_zadd(
+        &self,
+        key: String,
+        member: String,
+        score: f64,
+        ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        self.init().await?.cache_zadd(key, member, score, ttl).await
+    }
+
+    async fn cache_zrange(
+        &self,
+        key: String,
+        start: i64,
+        stop: i64,
+        rev: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError> {
+        self.init().await?.cache_zrange(key, start, stop, rev).await
+    }
+
+    async fn cache_zrem(&self, key: String, member: String) -> Result<bool, CubeError> {
+        self.init().await?.cache_zrem(key, member).await
+    }
+
     async fn queue_all(&self, limit: Option<usize>) -> Result<Vec<QueueAllItem>, CubeError> {
         self.init().await?.queue_all(limit).await
     }
@@ -409,6 +460,21 @@ impl CacheStore for LazyRocksCacheStore {
         self.init().await?.rocksdb_properties().await
     }
 
+    async fn backup_snapshot(&self, location: String) -> Result<BackupStoreSnapshot, CubeError> {
+        self.init().await?.backup_snapshot(location).await
+    }
+
+    async fn restore_snapshot(
+        &self,
+        location: String,
+        snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        self.init()
+            .await?
+            .restore_snapshot(location, snapshot)
+            .await
+    }
+
     async fn wipe(&self) -> Result<(), CubeError> {
         // Make sure the store is initialized. init() fires init_signal so run_processing_loops
         // spawns the initial loops; the returned Arc is dropped immediately so it does not
@@ -447,6 +513,13 @@ impl CacheStore for LazyRocksCacheStore {
                         return Err(err);
                     }
 
+                    for listener in &self.listeners {
+                        // Subscribers of the wiped keys should resync, error means no receivers
+                        let _ = listener.send(MetaStoreEvent::CacheNotification(
+                            CacheNotificationEvent::clear(),
+                        ));
+                    }
+
                     Ok(())
                 }
                 LazyRocksCacheStoreState::Wiping {} => {
@@ -672,6 +745,8 @@ mod tests {
                 process_id: None,
                 exclusive: false,
                 external_id: None,
+                max_attempts: None,
+                visible_at: None,
             })
             .await?;
 
@@ -1,7 +1,13 @@
-use crate::cachestore::{QueueKey, QueueResultAckEvent};
+use crate::cachestore::{
+    CacheNotificationEvent, CacheNotificationKind, QueueKey, QueueResultAckEvent,
+};
 use crate::metastore::MetaStoreEvent;
 use crate::CubeError;
-use tokio::sync::broadcast::Receiver;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{Receiver, Sender};
 
 pub struct RocksCacheStoreListener {
     receiver: Receiver<MetaStoreEvent>,
@@ -22,31 +28,195 @@ impl RocksCacheStoreListener {
         }
     }
 
+    /// Returns `None` once the receiver lags behind, the ack might be among the skipped events
+    /// and the caller should look up the result in the store.
     pub async fn wait_for_queue_ack_by_id(
         mut self,
         id: u64,
     ) -> Result<Option<QueueResultAckEvent>, CubeError> {
         loop {
-            let event = self.receiver.recv().await?;
-            if let MetaStoreEvent::AckQueueItem(ack_event) = event {
-                if ack_event.id == id {
+            match self.receiver.recv().await {
+                Ok(MetaStoreEvent::AckQueueItem(ack_event)) if ack_event.id == id => {
                     return Ok(Some(ack_event));
                 }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
             }
         }
     }
 
+    /// Returns `None` once the receiver lags behind, see `wait_for_queue_ack_by_id`.
     pub async fn wait_for_queue_ack_by_path(
         mut self,
         path: String,
     ) -> Result<Option<QueueResultAckEvent>, CubeError> {
         loop {
-            let event = self.receiver.recv().await?;
-            if let MetaStoreEvent::AckQueueItem(ack_event) = event {
-                if ack_event.path == path {
+            match self.receiver.recv().await {
+                Ok(MetaStoreEvent::AckQueueItem(ack_event)) if ack_event.path == path => {
                     return Ok(Some(ack_event));
                 }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
             }
         }
     }
+
+    /// Streams cache notifications for keys (or channels) starting with `prefix`. The stream
+    /// fails once the receiver lags behind, because the subscriber has missed notifications.
+    pub fn into_cache_notifications(
+        self,
+        prefix: String,
+    ) -> BoxStream<'static, Result<CacheNotificationEvent, CubeError>> {
+        futures::stream::unfold(Some(self.receiver), move |receiver| {
+            let prefix = prefix.clone();
+
+            async move {
+                let mut receiver = receiver?;
+
+                loop {
+                    match receiver.recv().await {
+                        Ok(MetaStoreEvent::CacheNotification(event)) => {
+                            if event.matches(&prefix) {
+                                return Some((Ok(event), Some(receiver)));
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(skipped)) => {
+                            return Some((
+                                Err(CubeError::user(format!(
+                                    "Subscription is too slow to keep up, {} notifications were skipped",
+                                    skipped
+                                ))),
+                                None,
+                            ));
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+/// Gives access to the cachestore events without going through `CacheStore`, which is a
+/// cuberpc::service and can't return streams.
+pub struct CacheStoreNotifier {
+    sender: Sender<MetaStoreEvent>,
+}
+
+crate::di_service!(CacheStoreNotifier, []);
+
+impl CacheStoreNotifier {
+    pub fn new(sender: Sender<MetaStoreEvent>) -> Arc<Self> {
+        Arc::new(Self { sender })
+    }
+
+    pub fn subscribe(
+        &self,
+        prefix: String,
+    ) -> BoxStream<'static, Result<CacheNotificationEvent, CubeError>> {
+        RocksCacheStoreListener::new(self.sender.subscribe()).into_cache_notifications(prefix)
+    }
+
+    pub fn publish(&self, channel: String, message: String) {
+        // Error means that there are no receivers at all, nobody to deliver the message to
+        let _ = self.sender.send(MetaStoreEvent::CacheNotification(
+            CacheNotificationEvent::new(CacheNotificationKind::Message, channel, Some(message)),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cachestore::queue_item::QueueResultAckEventResult;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn test_cache_notifications_by_prefix() -> Result<(), CubeError> {
+        let (sender, _) = broadcast::channel(16);
+        let notifier = CacheStoreNotifier::new(sender.clone());
+        let mut notifications = notifier.subscribe("refresh:".to_string());
+
+        sender.send(MetaStoreEvent::CacheNotification(
+            CacheNotificationEvent::new(
+                CacheNotificationKind::Set,
+                "lock:orders".to_string(),
+                Some("1".to_string()),
+            ),
+        ))?;
+        sender.send(MetaStoreEvent::Insert(
+            crate::metastore::TableId::CacheItems,
+            1,
+        ))?;
+        sender.send(MetaStoreEvent::CacheNotification(
+            CacheNotificationEvent::new(
+                CacheNotificationKind::Remove,
+                "refresh:orders".to_string(),
+                None,
+            ),
+        ))?;
+        notifier.publish("refresh:all".to_string(), "hello".to_string());
+
+        let event = notifications.next().await.unwrap()?;
+        assert_eq!(event.kind, CacheNotificationKind::Remove);
+        assert_eq!(event.key, "refresh:orders");
+        assert_eq!(event.value, None);
+
+        let event = notifications.next().await.unwrap()?;
+        assert_eq!(event.kind, CacheNotificationKind::Message);
+        assert_eq!(event.key, "refresh:all");
+        assert_eq!(event.value, Some(Arc::new("hello".to_string())));
+
+        drop(notifier);
+        drop(sender);
+        assert!(notifications.next().await.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_ack_lagged() -> Result<(), CubeError> {
+        let (sender, _) = broadcast::channel(2);
+        let ack = |id: u64| {
+            MetaStoreEvent::AckQueueItem(QueueResultAckEvent {
+                id,
+                path: format!("q:{}", id),
+                result: QueueResultAckEventResult::Empty,
+            })
+        };
+
+        let listener = RocksCacheStoreListener::new(sender.subscribe());
+        sender.send(ack(1))?;
+        sender.send(ack(2))?;
+        let event = listener.wait_for_queue_ack_by_id(2).await?.unwrap();
+        assert_eq!(event.path, "q:2");
+
+        // The ack was skipped, the caller has to look it up in the store
+        let listener = RocksCacheStoreListener::new(sender.subscribe());
+        for id in 1..=3 {
+            sender.send(ack(id))?;
+        }
+        assert!(listener.wait_for_queue_ack_by_id(1).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_notifications_lagged() -> Result<(), CubeError> {
+        let (sender, _) = broadcast::channel(1);
+        let notifier = CacheStoreNotifier::new(sender);
+        let mut notifications = notifier.subscribe("".to_string());
+
+        notifier.publish("a".to_string(), "1".to_string());
+        notifier.publish("b".to_string(), "2".to_string());
+
+        assert!(notifications.next().await.unwrap().is_err());
+        assert!(notifications.next().await.is_none());
+
+        Ok(())
+    }
 }
@@ -12,7 +12,9 @@ mod scheduler;
 pub use cache_eviction_manager::{
     CacheEvictionManager, CacheEvictionPolicy, EvictionFinishedResult, EvictionResult, LFU_INIT_VAL,
 };
-pub use cache_item::CacheItem;
+pub use cache_item::{
+    CacheItem, CacheItemValueType, CacheNotificationEvent, CacheNotificationKind,
+};
 pub use cache_rocksstore::{
     CacheStore, CacheStoreRpcClient, CachestoreInfo, ClusterCacheStoreClient,
     QueueAddAndRetrievePayload, QueueAddAndRetrieveResponse, QueueAddPayload, QueueAddResponse,
@@ -20,6 +22,7 @@ pub use cache_rocksstore::{
     QueueResultResponse, RocksCacheStore,
 };
 pub use lazy::LazyRocksCacheStore;
+pub use listener::CacheStoreNotifier;
 pub use queue_item::{
     QueueItem, QueueItemStatus, QueueResultAckEvent, QueueRetrieveResponse,
     QUEUE_ITEM_EXTERNAL_ID_MAX_LEN, QUEUE_ITEM_PROCESS_ID_MAX_LEN,
@@ -17,6 +17,10 @@ use serde::{Deserialize, Deserializer, Serialize};
 pub const QUEUE_ITEM_PROCESS_ID_MAX_LEN: usize = 64;
 // We use ${uuidv4()}, it's 36, let's limit to 48
 pub const QUEUE_ITEM_EXTERNAL_ID_MAX_LEN: usize = 48;
+// Retry backoff doubles with each failed attempt, 1s, 2s, 4s... up to 5 minutes
+const QUEUE_ITEM_RETRY_BACKOFF_SECS: i64 = 1;
+const QUEUE_ITEM_RETRY_BACKOFF_MAX_SECS: i64 = 300;
+const QUEUE_ITEM_DEAD_TTL_HOURS: i64 = 24;
 
 fn merge(a: serde_json::Value, b: serde_json::Value) -> Option<serde_json::Value> {
     match (a, b) {
@@ -62,6 +66,8 @@ pub enum QueueItemStatus {
     Pending = 0,
     Active = 1,
     Finished = 2,
+    /// Ran out of attempts, kept for diagnostics until it expires or is cancelled
+    Dead = 3,
 }
 
 impl ToString for QueueItemStatus {
@@ -70,6 +76,7 @@ impl ToString for QueueItemStatus {
             QueueItemStatus::Pending => "pending".to_string(),
             QueueItemStatus::Active => "active".to_string(),
             QueueItemStatus::Finished => "finished".to_string(),
+            QueueItemStatus::Dead => "dead".to_string(),
         }
     }
 }
@@ -97,6 +104,15 @@ pub struct QueueItem {
     pub(crate) exclusive: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) external_id: Option<String>,
+    /// Count of failed attempts, an attempt fails when the item is orphaned or stalled
+    #[serde(default)]
+    pub(crate) attempts: u32,
+    /// Enables the retry policy, without it failed items are reported by `QUEUE TO_CANCEL`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_attempts: Option<u32>,
+    /// The item cannot be claimed before this moment, set by `QUEUE ADD DELAY/AT` and retries
+    #[serde(default, with = "ts_seconds_option")]
+    pub(crate) visible_at: Option<DateTime<Utc>>,
 }
 
 impl RocksEntity for QueueItem {
@@ -173,6 +189,9 @@ impl QueueItem {
             process_id,
             exclusive,
             external_id,
+            attempts: 0,
+            max_attempts: None,
+            visible_at: None,
         }
     }
 
@@ -242,6 +261,65 @@ impl QueueItem {
         &self.external_id
     }
 
+    pub fn get_attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn get_max_attempts(&self) -> &Option<u32> {
+        &self.max_attempts
+    }
+
+    pub fn get_visible_at(&self) -> &Option<DateTime<Utc>> {
+        &self.visible_at
+    }
+
+    pub fn is_delayed(&self, now: &DateTime<Utc>) -> bool {
+        matches!(&self.visible_at, Some(visible_at) if visible_at > now)
+    }
+
+    /// Hides the item from claims until the given moment. The orphaned deadline and the
+    /// expiration keep their distance from the moment the item becomes claimable.
+    pub fn set_visible_at(&mut self, visible_at: DateTime<Utc>) {
+        let shift = visible_at - self.visible_at.unwrap_or(self.created);
+        if shift > Duration::zero() {
+            self.orphaned = self.orphaned.map(|orphaned| orphaned + shift);
+            self.expire = self.expire + shift;
+        }
+
+        self.visible_at = Some(visible_at);
+    }
+
+    /// Registers a failed attempt of an item with the retry policy. Moves it back to the
+    /// pending status with an exponential backoff, or to the dead status when it's out of
+    /// attempts. Returns `false` when the item has no retry policy.
+    pub fn fail_attempt(&mut self, now: DateTime<Utc>) -> bool {
+        let max_attempts = if let Some(max_attempts) = self.max_attempts {
+            max_attempts
+        } else {
+            return false;
+        };
+
+        self.attempts += 1;
+        self.heartbeat = None;
+
+        if self.attempts >= max_attempts {
+            self.status = QueueItemStatus::Dead;
+            self.visible_at = None;
+            self.expire = now + Duration::hours(QUEUE_ITEM_DEAD_TTL_HOURS);
+        } else {
+            self.status = QueueItemStatus::Pending;
+            self.set_visible_at(now + Self::retry_backoff(self.attempts));
+        }
+
+        true
+    }
+
+    fn retry_backoff(attempts: u32) -> Duration {
+        let secs = QUEUE_ITEM_RETRY_BACKOFF_SECS << attempts.saturating_sub(1).min(16);
+
+        Duration::seconds(secs.min(QUEUE_ITEM_RETRY_BACKOFF_MAX_SECS))
+    }
+
     /// Returns whether this item should be visible to the given caller process.
     /// Exclusive items with a process_id are only visible to the owning process.
     pub fn is_visible_for(&self, caller_process_id: &Option<String>) -> bool {
@@ -460,6 +538,7 @@ impl RocksSecondaryIndex<QueueItem, QueueItemIndexKey> for QueueItemRocksIndex {
                     QueueItemStatus::Pending => r.push(0_u8),
                     QueueItemStatus::Active => r.push(1_u8),
                     QueueItemStatus::Finished => r.push(2_u8),
+                    QueueItemStatus::Dead => r.push(3_u8),
                 }
 
                 r
@@ -658,4 +737,86 @@ mod tests {
         assert!(!exclusive.is_visible_for(&Some("pid-other".to_string())));
         assert!(!exclusive.is_visible_for(&None));
     }
+
+    #[test]
+    fn test_fail_attempt() {
+        let mut item = QueueItem::new(
+            "prefix:key".to_string(),
+            QueueItemStatus::Active,
+            0,
+            Some(60),
+            None,
+            false,
+            None,
+        );
+        let now = item.created + Duration::seconds(10);
+
+        // Without a retry policy the item is left to the caller
+        assert!(!item.clone().fail_attempt(now));
+
+        item.max_attempts = Some(3);
+        item.update_heartbeat();
+
+        assert!(item.fail_attempt(now));
+        assert_eq!(item.status, QueueItemStatus::Pending);
+        assert_eq!(item.attempts, 1);
+        assert_eq!(item.heartbeat, None);
+        assert_eq!(item.visible_at, Some(now + Duration::seconds(1)));
+        assert_eq!(item.orphaned, Some(now + Duration::seconds(61)));
+        assert!(item.is_delayed(&now));
+        assert!(!item.is_delayed(&(now + Duration::seconds(1))));
+
+        let now = now + Duration::seconds(30);
+        assert!(item.fail_attempt(now));
+        assert_eq!(item.attempts, 2);
+        assert_eq!(item.visible_at, Some(now + Duration::seconds(2)));
+        assert_eq!(item.orphaned, Some(now + Duration::seconds(62)));
+
+        assert!(item.fail_attempt(now));
+        assert_eq!(item.status, QueueItemStatus::Dead);
+        assert_eq!(item.attempts, 3);
+        assert_eq!(item.visible_at, None);
+        assert_eq!(
+            item.expire,
+            now + Duration::hours(QUEUE_ITEM_DEAD_TTL_HOURS)
+        );
+    }
+
+    #[test]
+    fn test_set_visible_at() {
+        let mut item = QueueItem::new(
+            "prefix:key".to_string(),
+            QueueItemStatus::Pending,
+            0,
+            Some(60),
+            None,
+            false,
+            None,
+        );
+        let created = item.created;
+        let expire = item.expire;
+
+        item.set_visible_at(created + Duration::hours(1));
+        assert!(item.is_delayed(&created));
+        assert_eq!(item.orphaned, Some(created + Duration::seconds(3660)));
+        assert_eq!(item.expire, expire + Duration::hours(1));
+
+        // A moment in the past makes the item claimable right away, nothing is shortened
+        let mut item = QueueItem::new(
+            "prefix:key".to_string(),
+            QueueItemStatus::Pending,
+            0,
+            Some(60),
+            None,
+            false,
+            None,
+        );
+        let created = item.created;
+        let expire = item.expire;
+
+        item.set_visible_at(created - Duration::hours(1));
+        assert!(!item.is_delayed(&created));
+        assert_eq!(item.orphaned, Some(created + Duration::seconds(60)));
+        assert_eq!(item.expire, expire);
+    }
 }
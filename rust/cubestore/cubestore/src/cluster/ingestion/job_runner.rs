@@ -10,6 +10,7 @@ use crate::store::compaction::CompactionService;
 use crate::store::ChunkDataStore;
 use crate::util::aborting_join_handle::AbortingJoinHandle;
 use crate::CubeError;
+use chrono::Utc;
 use core::mem;
 use datafusion::cube_ext;
 use futures_timer::Delay;
@@ -21,6 +22,7 @@ use tokio::sync::{oneshot, Notify};
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 pub struct JobRunner {
     pub config_obj: Arc<dyn ConfigObj>,
@@ -60,7 +62,12 @@ impl JobRunner {
             .start_processing_job(self.server_name.to_string(), self.pool)
             .await?;
         if let Some(to_process) = job {
-            self.run_local(to_process).await?;
+            let span = tracing::info_span!(
+                "job",
+                id = to_process.get_id(),
+                job_type = ?to_process.get_row().job_type()
+            );
+            self.run_local(to_process).instrument(span).await?;
             // In case of job queue is in place jump to the next job immediately
             self.notify.notify_one();
         }
@@ -203,18 +210,12 @@ impl JobRunner {
                     let job_processor = self.job_processor.clone();
                     let job_to_move = job.clone();
                     Ok(cube_ext::spawn(async move {
-                        let wait_ms = process_rate_limiter
-                            .wait_for_allow(TaskType::Job, timeout)
-                            .await?; //TODO config, may be same ad orphaned timeout
-
                         let (_, _, table, _) =
                             metastore.get_partition_for_compaction(partition_id).await?;
-                        let table_id = table.get_id();
-                        let trace_obj = metastore.get_trace_obj_by_table_id(table_id).await?;
-                        let trace_index = TraceIndex {
-                            table_id: Some(table_id),
-                            trace_obj,
-                        };
+                        let trace_index = Self::table_trace_index(&metastore, &table).await?;
+                        let wait_ms = process_rate_limiter
+                            .wait_for_allow(TaskType::Job, &trace_index, timeout)
+                            .await?; //TODO config, may be same ad orphaned timeout
 
                         match job_processor.process_job(job_to_move).await {
                             Ok(job_res) => {
@@ -315,17 +316,13 @@ impl JobRunner {
                             Some(DataLoadedSize::new())
                         };
                         if !is_streaming {
+                            let table = metastore.get_table_by_id(table_id).await?;
+                            let trace_index = Self::table_trace_index(&metastore, &table).await?;
                             let wait_ms = process_rate_limiter
-                                .wait_for_allow(TaskType::Job, timeout)
+                                .wait_for_allow(TaskType::Job, &trace_index, timeout)
                                 .await?; //TODO config, may be same ad orphaned timeout
                             match job_processor.process_job(job_to_move).await {
                                 Ok(job_res) => {
-                                    let trace_obj =
-                                        metastore.get_trace_obj_by_table_id(table_id).await?;
-                                    let trace_index = TraceIndex {
-                                        table_id: Some(table_id),
-                                        trace_obj,
-                                    };
                                     process_rate_limiter
                                         .commit_task_usage(
                                             TaskType::Job,
@@ -359,20 +356,15 @@ impl JobRunner {
                     let job_to_move = job.clone();
                     let job_processor = self.job_processor.clone();
                     Ok(cube_ext::spawn(async move {
+                        let chunk = metastore.get_chunk(chunk_id).await?;
+                        let (_, _, table, _) = metastore
+                            .get_partition_for_compaction(chunk.get_row().get_partition_id())
+                            .await?;
+                        let trace_index = Self::table_trace_index(&metastore, &table).await?;
                         let wait_ms = process_rate_limiter
-                            .wait_for_allow(TaskType::Job, timeout)
+                            .wait_for_allow(TaskType::Job, &trace_index, timeout)
                             .await?; //TODO config, may be same ad orphaned timeout
-                        let chunk = metastore.get_chunk(chunk_id).await?;
                         if !chunk.get_row().in_memory() {
-                            let (_, _, table, _) = metastore
-                                .get_partition_for_compaction(chunk.get_row().get_partition_id())
-                                .await?;
-                            let table_id = table.get_id();
-                            let trace_obj = metastore.get_trace_obj_by_table_id(table_id).await?;
-                            let trace_index = TraceIndex {
-                                table_id: Some(table_id),
-                                trace_obj,
-                            };
                             match job_processor.process_job(job_to_move).await {
                                 Ok(job_res) => {
                                     process_rate_limiter
@@ -406,19 +398,14 @@ impl JobRunner {
                     let job_to_move = job.clone();
                     let job_processor = self.job_processor.clone();
                     Ok(cube_ext::spawn(async move {
-                        let wait_ms = process_rate_limiter
-                            .wait_for_allow(TaskType::Job, timeout)
-                            .await?;
                         let chunk = metastore.get_chunk(start_chunk_id).await?;
                         let (_, _, table, _) = metastore
                             .get_partition_for_compaction(chunk.get_row().get_partition_id())
                             .await?;
-                        let table_id = table.get_id();
-                        let trace_obj = metastore.get_trace_obj_by_table_id(table_id).await?;
-                        let trace_index = TraceIndex {
-                            table_id: Some(table_id),
-                            trace_obj,
-                        };
+                        let trace_index = Self::table_trace_index(&metastore, &table).await?;
+                        let wait_ms = process_rate_limiter
+                            .wait_for_allow(TaskType::Job, &trace_index, timeout)
+                            .await?;
                         match job_processor.process_job(job_to_move).await {
                             Ok(job_res) => {
                                 process_rate_limiter
@@ -438,6 +425,27 @@ impl JobRunner {
                     Self::fail_job_row_key(job)
                 }
             }
+            JobType::TableRetention => {
+                if let RowKey::Table(TableId::Tables, table_id) = job.row_reference() {
+                    let table_id = *table_id;
+                    let metastore = self.meta_store.clone();
+                    Ok(cube_ext::spawn(async move {
+                        let table = metastore.get_table_by_id(table_id).await?;
+                        if let Some(retention) = table.get_row().retention() {
+                            let cutoff = retention.expiration_cutoff(Utc::now());
+                            let (partitions, chunks) =
+                                metastore.drop_expired_table_data(table_id, cutoff).await?;
+                            info!(
+                                "Dropped {} expired partitions and {} expired chunks of table {}",
+                                partitions, chunks, table_id
+                            );
+                        }
+                        Ok(())
+                    }))
+                } else {
+                    Self::fail_job_row_key(job)
+                }
+            }
             // Defense-in-depth: start_processing_job never selects an Unknown job, so
             // this arm is not a live path — it just guarantees we never panic on one.
             JobType::Unknown => Err(CubeError::internal(format!(
@@ -447,6 +455,21 @@ impl JobRunner {
         }
     }
 
+    /// Jobs are budgeted for the schema of the table they process.
+    async fn table_trace_index(
+        metastore: &Arc<dyn MetaStore>,
+        table: &IdRow<Table>,
+    ) -> Result<TraceIndex, CubeError> {
+        let schema = metastore
+            .get_schema_by_id(table.get_row().get_schema_id())
+            .await?;
+        Ok(TraceIndex {
+            table_id: Some(table.get_id()),
+            trace_obj: metastore.get_trace_obj_by_table_id(table.get_id()).await?,
+            tenants: vec![schema.get_row().get_name().clone()],
+        })
+    }
+
     fn fail_job_row_key(job: &Job) -> Result<JoinHandle<Result<(), CubeError>>, CubeError> {
         Err(CubeError::internal(format!(
             "Incorrect row key for {:?}: {:?}",
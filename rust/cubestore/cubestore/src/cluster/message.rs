@@ -14,10 +14,14 @@ use crate::cluster::WorkerPlanningParams;
 #[derive(Serialize, Deserialize, Debug)]
 pub enum NetworkMessage {
     /// Route subqueries to other nodes and collect results.
-    RouterSelect(SerializedPlan),
+    RouterSelect(SerializedPlan, /*traceparent*/ Option<String>),
 
     /// Partial select on the worker.
-    Select(SerializedPlan, WorkerPlanningParams),
+    Select(
+        SerializedPlan,
+        WorkerPlanningParams,
+        /*traceparent*/ Option<String>,
+    ),
     SelectResult(Result<(SchemaRef, Vec<SerializedRecordBatchStream>), CubeError>),
 
     //Perform explain analyze of worker query part and return it pretty printed physical plan
@@ -70,7 +74,7 @@ pub enum NetworkMessage {
 
 const MAGIC: u32 = 94107;
 
-const NETWORK_MESSAGE_VERSION: u32 = 1;
+const NETWORK_MESSAGE_VERSION: u32 = 2;
 
 impl NetworkMessage {
     pub fn is_streaming_request(&self) -> bool {
@@ -41,7 +41,9 @@ use crate::queryplanner::query_executor::{QueryExecutor, SerializedRecordBatchSt
 use crate::queryplanner::serialized_plan::SerializedPlan;
 use crate::remotefs::RemoteFs;
 use crate::store::ChunkDataStore;
-use crate::telemetry::tracing::{TraceIdAndSpanId, TracingHelper};
+use crate::telemetry::tracing::{
+    format_traceparent, parse_traceparent, with_remote_parent, TraceIdAndSpanId, TracingHelper,
+};
 use crate::trace::{MainTrace, OpKind, SubprocessTrace, WorkerTrace};
 use crate::CubeError;
 use async_trait::async_trait;
@@ -60,10 +62,6 @@ use ingestion::job_runner::JobRunner;
 use itertools::Itertools;
 use log::{debug, error, info, warn};
 use mockall::automock;
-#[cfg(not(target_os = "windows"))]
-use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId};
-#[cfg(not(target_os = "windows"))]
-use opentelemetry::Context as OtelContext;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
@@ -81,8 +79,6 @@ use tokio::sync::{oneshot, watch, Notify, RwLock};
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
 use tracing::{instrument, Instrument};
-#[cfg(not(target_os = "windows"))]
-use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 #[automock]
 #[async_trait]
@@ -410,22 +406,8 @@ impl WorkerProcessing for WorkerProcessor {
                     Ok::<_, CubeError>((schema, records, data_loaded_size))
                 };
 
-                let span = trace_id_and_span_id.map(|(t, s)| {
-                    let trace_id = TraceId::from(t);
-                    let span_id = SpanId::from(s);
-                    let span_context = SpanContext::new(
-                        trace_id,
-                        span_id,
-                        TraceFlags::SAMPLED,
-                        true,
-                        Default::default(),
-                    );
-
-                    let context = OtelContext::new().with_remote_span_context(span_context);
-                    let span = tracing::info_span!("Process on select worker");
-
-                    span.set_parent(context);
-                    span
+                let span = trace_id_and_span_id.map(|ids| {
+                    with_remote_parent(tracing::info_span!("Process on select worker"), Some(ids))
                 });
 
                 let run = async move {
@@ -554,7 +536,10 @@ impl Cluster for ClusterImpl {
         plan: SerializedPlan,
     ) -> Result<(SchemaRef, Vec<SerializedRecordBatchStream>), CubeError> {
         let response = self
-            .send_or_process_locally(&node_name, NetworkMessage::RouterSelect(plan))
+            .send_or_process_locally(
+                &node_name,
+                NetworkMessage::RouterSelect(plan, self.traceparent()),
+            )
             .await?;
         match response {
             NetworkMessage::SelectResult(r) => r,
@@ -572,7 +557,7 @@ impl Cluster for ClusterImpl {
         let response = self
             .send_or_process_locally(
                 node_name,
-                NetworkMessage::Select(plan_node, worker_planning_params),
+                NetworkMessage::Select(plan_node, worker_planning_params, self.traceparent()),
             )
             .await?;
         match response {
@@ -802,10 +787,15 @@ impl Cluster for ClusterImpl {
     #[instrument(level = "trace", skip(self, m))]
     async fn process_message_on_worker(&self, m: NetworkMessage) -> NetworkMessage {
         match m {
-            NetworkMessage::RouterSelect(plan) => {
+            NetworkMessage::RouterSelect(plan, traceparent) => {
+                let span = with_remote_parent(
+                    tracing::info_span!("router_execution"),
+                    traceparent.as_deref().and_then(parse_traceparent),
+                );
                 let res = self
                     .query_executor
                     .execute_router_plan(plan, self.this.upgrade().unwrap())
+                    .instrument(span)
                     .await
                     .and_then(|(schema, records)| {
                         let records = SerializedRecordBatchStream::write(&schema, records)?;
@@ -813,8 +803,15 @@ impl Cluster for ClusterImpl {
                     });
                 NetworkMessage::SelectResult(res)
             }
-            NetworkMessage::Select(plan, planning_params) => {
-                let res = self.run_local_select_worker(plan, planning_params).await;
+            NetworkMessage::Select(plan, planning_params, traceparent) => {
+                let span = with_remote_parent(
+                    tracing::info_span!("worker_execution"),
+                    traceparent.as_deref().and_then(parse_traceparent),
+                );
+                let res = self
+                    .run_local_select_worker(plan, planning_params)
+                    .instrument(span)
+                    .await;
                 NetworkMessage::SelectResult(res)
             }
             NetworkMessage::ExplainAnalyze(plan, planning_params) => {
@@ -1570,17 +1567,27 @@ impl ClusterImpl {
         plan_node: SerializedPlan,
         worker_planning_params: WorkerPlanningParams,
     ) -> Result<(SchemaRef, Vec<SerializedRecordBatchStream>), CubeError> {
+        // Each schema of the query is charged, so joins don't escape limits of their schemas.
+        let tenants = plan_node
+            .index_snapshots()
+            .iter()
+            .map(|s| s.table_path.schema.get_row().get_name().clone())
+            .sorted()
+            .dedup()
+            .collect();
+        let trace_index = TraceIndex {
+            table_id: None,
+            trace_obj: plan_node.trace_obj(),
+            tenants,
+        };
         let wait_ms = self
             .process_rate_limiter
             .wait_for_allow(
                 TaskType::Select,
+                &trace_index,
                 Some(Duration::from_secs(self.config_obj.query_timeout())),
             )
             .await?;
-        let trace_index = TraceIndex {
-            table_id: None,
-            trace_obj: plan_node.trace_obj(),
-        };
         let res = self
             .run_local_select_worker_impl(plan_node, worker_planning_params, false)
             .await;
@@ -1914,6 +1921,13 @@ impl ClusterImpl {
         Ok(())
     }
 
+    /// W3C `traceparent` of the current span to continue the trace on the receiving node.
+    fn traceparent(&self) -> Option<String> {
+        self.tracing_helper
+            .trace_and_span_id()
+            .map(format_traceparent)
+    }
+
     #[instrument(level = "trace", skip(self, m))]
     async fn send_or_process_locally(
         &self,
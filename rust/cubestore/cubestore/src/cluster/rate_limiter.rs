@@ -1,13 +1,18 @@
+use crate::app_metrics;
 use crate::config::injection::DIService;
+use crate::util::metrics;
 use crate::CubeError;
 use async_trait::async_trait;
+use datafusion::cube_ext;
 use std::cmp::PartialEq;
+use std::collections::HashMap;
 use std::hash::Hash;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub enum TaskType {
     Select,
     Job,
@@ -30,6 +35,23 @@ impl TaskType {
 pub struct TraceIndex {
     pub table_id: Option<u64>,
     pub trace_obj: Option<String>,
+    /// Tenants the task is budgeted for, e.g. schema names of the accessed tables. A task has to
+    /// wait for the budget of every tenant and its usage is charged to each of them, so accessing
+    /// several schemas doesn't escape their limits. Tasks without tenants share one budget.
+    pub tenants: Vec<String>,
+}
+
+impl TraceIndex {
+    fn bucket_keys(&self, task_type: &TaskType) -> Vec<BucketKey> {
+        if self.tenants.is_empty() {
+            vec![(task_type.clone(), None)]
+        } else {
+            self.tenants
+                .iter()
+                .map(|t| (task_type.clone(), Some(t.clone())))
+                .collect()
+        }
+    }
 }
 
 #[async_trait]
@@ -50,6 +72,7 @@ pub trait ProcessRateLimiter: DIService + Send + Sync {
     async fn wait_for_allow(
         &self,
         task_type: TaskType,
+        trace_index: &TraceIndex,
         timeout: Option<Duration>,
     ) -> Result<u64, CubeError>;
 
@@ -92,6 +115,7 @@ impl ProcessRateLimiter for BasicProcessRateLimiter {
     async fn wait_for_allow(
         &self,
         _task_type: TaskType,
+        _trace_index: &TraceIndex,
         _timeout: Option<Duration>,
     ) -> Result<u64, CubeError> {
         Ok(0)
@@ -107,3 +131,366 @@ impl ProcessRateLimiter for BasicProcessRateLimiter {
 
     fn stop_processing_loops(&self) {}
 }
+
+/// Budget of a task type: it's restored at `rate` units per second and accumulates up to
+/// `rate * burst_secs`. Units are sizes passed to `commit_task_usage`, e.g. bytes of data loaded
+/// by selects and jobs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskRateLimit {
+    pub rate: u64,
+    pub burst_secs: u64,
+}
+
+impl TaskRateLimit {
+    fn capacity(&self) -> f64 {
+        (self.rate * self.burst_secs.max(1)) as f64
+    }
+}
+
+type BucketKey = (TaskType, Option<String>);
+
+struct Bucket {
+    budget: f64,
+    updated: Instant,
+    pending: usize,
+}
+
+impl Bucket {
+    fn new(limit: &TaskRateLimit) -> Self {
+        Self {
+            budget: limit.capacity(),
+            updated: Instant::now(),
+            pending: 0,
+        }
+    }
+
+    fn refill(&mut self, limit: &TaskRateLimit) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.budget = (self.budget + elapsed * limit.rate as f64).min(limit.capacity());
+        self.updated = now;
+    }
+}
+
+/// Token bucket limiter keeping a separate budget per task type and tenant, so one tenant
+/// exhausting its budget doesn't delay tasks of others. Task size is only known after it's
+/// done, so a task is allowed to start while the budget is positive and its usage is charged
+/// afterwards. Tasks of a tenant in debt wait until the budget is restored.
+pub struct TokenBucketProcessRateLimiter {
+    limits: HashMap<TaskType, TaskRateLimit>,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    stop_token: CancellationToken,
+}
+
+crate::di_service!(TokenBucketProcessRateLimiter, [ProcessRateLimiter]);
+
+/// Upper bound of a single sleep so waiters notice budget changes made by other tasks.
+const MAX_WAIT_STEP: Duration = Duration::from_millis(500);
+const METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+impl TokenBucketProcessRateLimiter {
+    pub fn new(limits: HashMap<TaskType, TaskRateLimit>) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+            stop_token: CancellationToken::new(),
+        })
+    }
+
+    fn with_bucket<R>(
+        &self,
+        key: &BucketKey,
+        limit: &TaskRateLimit,
+        f: impl FnOnce(&mut Bucket) -> R,
+    ) -> R {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(key.clone())
+            .or_insert_with(|| Bucket::new(limit));
+        bucket.refill(limit);
+        f(bucket)
+    }
+
+    fn report_metrics(&self) {
+        let mut buckets = self.buckets.lock().unwrap();
+        for ((task_type, tenant), bucket) in buckets.iter_mut() {
+            if let Some(limit) = self.limits.get(task_type) {
+                bucket.refill(limit);
+            }
+            let tags = metric_tags(task_type, tenant);
+            app_metrics::RATE_LIMITER_BUDGET.report_with_tags(bucket.budget as i64, Some(&tags));
+            app_metrics::RATE_LIMITER_PENDING.report_with_tags(bucket.pending as i64, Some(&tags));
+        }
+        // Buckets of idle tenants are restored to the full budget and can be recreated on demand.
+        buckets.retain(|(task_type, _), b| {
+            b.pending > 0
+                || self
+                    .limits
+                    .get(task_type)
+                    .map(|l| b.budget < l.capacity())
+                    .unwrap_or(false)
+        });
+    }
+}
+
+fn metric_tags(task_type: &TaskType, tenant: &Option<String>) -> Vec<String> {
+    vec![
+        metrics::format_tag("task_type", &task_type.name()),
+        metrics::format_tag("tenant", tenant.as_deref().unwrap_or("")),
+    ]
+}
+
+/// Decrements the pending counter of a bucket once the waiting task is allowed, rejected or
+/// cancelled.
+struct PendingGuard<'a> {
+    limiter: &'a TokenBucketProcessRateLimiter,
+    key: BucketKey,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        let mut buckets = self.limiter.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(&self.key) {
+            bucket.pending -= 1;
+        }
+    }
+}
+
+#[async_trait]
+impl ProcessRateLimiter for TokenBucketProcessRateLimiter {
+    async fn commit_task_usage(
+        &self,
+        task_type: TaskType,
+        size: i64,
+        _wait_ms: u64,
+        trace_index: TraceIndex,
+    ) {
+        let limit = match self.limits.get(&task_type) {
+            Some(limit) => limit,
+            None => return,
+        };
+        for key in trace_index.bucket_keys(&task_type) {
+            self.with_bucket(&key, limit, |b| b.budget -= size as f64);
+        }
+    }
+
+    async fn current_budget(&self, task_type: TaskType) -> Option<i64> {
+        self.current_budget_f64(task_type)
+            .await
+            .map(|b| b.floor() as i64)
+    }
+
+    /// The lowest budget among tenants.
+    async fn current_budget_f64(&self, task_type: TaskType) -> Option<f64> {
+        let limit = self.limits.get(&task_type)?;
+        let mut buckets = self.buckets.lock().unwrap();
+        Some(
+            buckets
+                .iter_mut()
+                .filter(|((t, _), _)| t == &task_type)
+                .map(|(_, b)| {
+                    b.refill(limit);
+                    b.budget
+                })
+                .fold(limit.capacity(), f64::min),
+        )
+    }
+
+    async fn wait_for_allow(
+        &self,
+        task_type: TaskType,
+        trace_index: &TraceIndex,
+        timeout: Option<Duration>,
+    ) -> Result<u64, CubeError> {
+        let limit = match self.limits.get(&task_type) {
+            Some(limit) => limit,
+            None => return Ok(0),
+        };
+        let start = Instant::now();
+        // Buckets are checked one by one: the task waits for the first tenant in debt.
+        for key in trace_index.bucket_keys(&task_type) {
+            let mut pending_guard = None;
+            loop {
+                // Debt is paid off at the refill rate, so the wait time is known upfront.
+                let wait = self.with_bucket(&key, limit, |b| {
+                    if b.budget > 0.0 {
+                        None
+                    } else {
+                        if pending_guard.is_none() {
+                            b.pending += 1;
+                        }
+                        Some(Duration::from_secs_f64(
+                            (1.0 - b.budget) / limit.rate.max(1) as f64,
+                        ))
+                    }
+                });
+                let wait = match wait {
+                    None => break,
+                    Some(wait) => wait,
+                };
+                if pending_guard.is_none() {
+                    pending_guard = Some(PendingGuard {
+                        limiter: self,
+                        key: key.clone(),
+                    });
+                }
+                if let Some(timeout) = timeout {
+                    if start.elapsed() + wait > timeout {
+                        app_metrics::RATE_LIMITER_REJECTED
+                            .add_with_tags(1, Some(&metric_tags(&key.0, &key.1)));
+                        return Err(CubeError::user(format!(
+                            "Rate limit exceeded: {} budget{} is not restored within {:?} timeout",
+                            key.0.name(),
+                            key.1
+                                .as_ref()
+                                .map(|t| format!(" of '{}'", t))
+                                .unwrap_or_default(),
+                            timeout
+                        )));
+                    }
+                }
+                tokio::time::sleep(wait.min(MAX_WAIT_STEP)).await;
+            }
+        }
+        Ok(start.elapsed().as_millis() as u64)
+    }
+
+    async fn spawn_processing_loop(self: Arc<Self>) -> Vec<JoinHandle<()>> {
+        let limiter = self.clone();
+        vec![cube_ext::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = limiter.stop_token.cancelled() => return,
+                    _ = tokio::time::sleep(METRICS_INTERVAL) => {}
+                }
+                limiter.report_metrics();
+            }
+        })]
+    }
+
+    async fn pending_size(&self, task_type: TaskType) -> Option<usize> {
+        self.limits.get(&task_type)?;
+        let buckets = self.buckets.lock().unwrap();
+        Some(
+            buckets
+                .iter()
+                .filter(|((t, _), _)| t == &task_type)
+                .map(|(_, b)| b.pending)
+                .sum(),
+        )
+    }
+
+    fn stop_processing_loops(&self) {
+        self.stop_token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace_index(tenant: &str) -> TraceIndex {
+        TraceIndex {
+            tenants: vec![tenant.to_string()],
+            ..TraceIndex::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn token_bucket_per_tenant() -> Result<(), CubeError> {
+        let limiter = TokenBucketProcessRateLimiter::new(
+            vec![(
+                TaskType::Select,
+                TaskRateLimit {
+                    rate: 100,
+                    burst_secs: 1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let timeout = Some(Duration::from_millis(100));
+
+        limiter
+            .wait_for_allow(TaskType::Select, &trace_index("a"), timeout)
+            .await?;
+        limiter
+            .commit_task_usage(TaskType::Select, 1000, 0, trace_index("a"))
+            .await;
+        assert!(limiter.current_budget(TaskType::Select).await.unwrap() < 0);
+
+        // Restoring 900 units at 100 per second takes longer than the timeout.
+        let err = limiter
+            .wait_for_allow(TaskType::Select, &trace_index("a"), timeout)
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("Rate limit exceeded"), "{}", err);
+        assert_eq!(limiter.pending_size(TaskType::Select).await, Some(0));
+
+        // Other tenants and task types aren't affected.
+        limiter
+            .wait_for_allow(TaskType::Select, &trace_index("b"), timeout)
+            .await?;
+        limiter
+            .wait_for_allow(TaskType::Job, &trace_index("a"), timeout)
+            .await?;
+        assert_eq!(limiter.current_budget(TaskType::Job).await, None);
+
+        // Small debts are paid off by waiting.
+        limiter
+            .commit_task_usage(TaskType::Select, 105, 0, trace_index("b"))
+            .await;
+        let wait_ms = limiter
+            .wait_for_allow(
+                TaskType::Select,
+                &trace_index("b"),
+                Some(Duration::from_secs(5)),
+            )
+            .await?;
+        assert!(wait_ms > 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_bucket_several_tenants() -> Result<(), CubeError> {
+        let limiter = TokenBucketProcessRateLimiter::new(
+            vec![(
+                TaskType::Select,
+                TaskRateLimit {
+                    rate: 100,
+                    burst_secs: 1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let timeout = Some(Duration::from_millis(100));
+        let joined = TraceIndex {
+            tenants: vec!["a".to_string(), "b".to_string()],
+            ..TraceIndex::default()
+        };
+
+        // A task touching a tenant in debt waits, even if other tenants have budget.
+        limiter
+            .commit_task_usage(TaskType::Select, 1000, 0, trace_index("a"))
+            .await;
+        let err = limiter
+            .wait_for_allow(TaskType::Select, &joined, timeout)
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("of 'a'"), "{}", err);
+
+        // Usage is charged to every tenant.
+        limiter
+            .wait_for_allow(TaskType::Select, &trace_index("b"), timeout)
+            .await?;
+        limiter
+            .commit_task_usage(TaskType::Select, 1000, 0, joined)
+            .await;
+        limiter
+            .wait_for_allow(TaskType::Select, &trace_index("b"), timeout)
+            .await
+            .unwrap_err();
+        Ok(())
+    }
+}
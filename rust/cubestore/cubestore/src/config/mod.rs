@@ -3,11 +3,14 @@ pub mod injection;
 pub mod processing_loop;
 
 use crate::cachestore::{
-    CacheEvictionPolicy, CacheStore, CacheStoreSchedulerImpl, ClusterCacheStoreClient,
-    LazyRocksCacheStore,
+    CacheEvictionPolicy, CacheStore, CacheStoreNotifier, CacheStoreSchedulerImpl,
+    ClusterCacheStoreClient, LazyRocksCacheStore,
 };
 use crate::cluster::ingestion::job_processor::{JobProcessor, JobProcessorImpl};
-use crate::cluster::rate_limiter::{BasicProcessRateLimiter, ProcessRateLimiter};
+use crate::cluster::rate_limiter::{
+    BasicProcessRateLimiter, ProcessRateLimiter, TaskRateLimit, TaskType,
+    TokenBucketProcessRateLimiter,
+};
 use crate::cluster::transport::{
     ClusterTransport, ClusterTransportImpl, MetaStoreTransport, MetaStoreTransportImpl,
 };
@@ -17,15 +20,20 @@ use crate::config::processing_loop::ProcessingLoop;
 use crate::http::HttpServer;
 use crate::import::limits::ConcurrencyLimits;
 use crate::import::{ImportService, ImportServiceImpl, LocationsValidator, LocationsValidatorImpl};
+use crate::metastore::backup::complete_pending_restores;
 use crate::metastore::{
     BaseRocksStoreFs, MetaStore, MetaStoreRpcClient, RocksMetaStore, RocksStoreConfig,
     TracedMetaStore,
 };
-use crate::mysql::{MySqlServer, SqlAuthDefaultImpl, SqlAuthService};
+use crate::mysql::{
+    AuthUser, MySqlServer, SqlAuthConfiguredImpl, SqlAuthDefaultImpl, SqlAuthService,
+};
 use crate::queryplanner::metadata_cache::BasicMetadataCacheFactory;
 use crate::queryplanner::query_executor::{QueryExecutor, QueryExecutorImpl};
 use crate::queryplanner::{QueryPlanner, QueryPlannerImpl};
+use crate::remotefs::azure::AzureBlobRemoteFs;
 use crate::remotefs::cleanup::RemoteFsCleanup;
+use crate::remotefs::encrypted::{EncryptedRemoteFs, EncryptionKeyring};
 use crate::remotefs::gcs::GCSRemoteFs;
 use crate::remotefs::minio::MINIORemoteFs;
 use crate::remotefs::queue::QueueRemoteFs;
@@ -38,6 +46,7 @@ use crate::sql::{TableExtensionService, TableExtensionServiceImpl};
 use crate::store::compaction::{CompactionService, CompactionServiceImpl};
 use crate::store::{ChunkDataStore, ChunkStore, WALDataStore, WALStore};
 use crate::streaming::kafka::{KafkaClientService, KafkaClientServiceImpl};
+use crate::streaming::schema_registry::{SchemaRegistryClient, SchemaRegistryClientImpl};
 use crate::streaming::{KsqlClient, KsqlClientImpl, StreamingService, StreamingServiceImpl};
 use crate::table::parquet::{
     CubestoreMetadataCacheFactory, CubestoreMetadataCacheFactoryImpl,
@@ -56,6 +65,7 @@ use log::Level;
 use log::{debug, error};
 use mockall::automock;
 use simple_logger::SimpleLogger;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::future::Future;
 use std::path::{Path, PathBuf};
@@ -145,6 +155,11 @@ impl CubeServices {
             }));
             started_rx.await?;
 
+            let remote_fs = self.injector.get_service_typed::<QueueRemoteFs>().await;
+            if let Err(e) = complete_pending_restores(remote_fs).await {
+                error!("Error while checking data files of restored backup: {}", e);
+            }
+
             if self.injector.has_service_typed::<SchedulerImpl>().await {
                 let scheduler = self.injector.get_service_typed::<SchedulerImpl>().await;
                 futures.extend(scheduler.spawn_processing_loops());
@@ -316,6 +331,7 @@ pub fn validate_config(c: &dyn ConfigObj) -> ValidationMessages {
         "CUBESTORE_MINIO_BUCKET",
         "CUBESTORE_S3_BUCKET",
         "CUBESTORE_GCS_BUCKET",
+        "CUBESTORE_AZURE_CONTAINER",
         "CUBESTORE_REMOTE_DIR",
     ];
     remote_vars.retain(|v| env::var(v).is_ok());
@@ -327,6 +343,22 @@ pub fn validate_config(c: &dyn ConfigObj) -> ValidationMessages {
         ));
     }
 
+    if let Some(key_id) = c.encryption_key_id() {
+        if remote_vars.is_empty() {
+            errors.push(
+                "CUBESTORE_ENCRYPTION_KEY_ID requires a remote storage to encrypt files for"
+                    .to_string(),
+            );
+        }
+        if let Err(e) = EncryptionKeyring::from_env(key_id.to_string()) {
+            errors.push(e.message);
+        }
+    }
+
+    if let Err(e) = env_auth_users() {
+        errors.push(e.message);
+    }
+
     ValidationMessages { errors, warnings }
 }
 
@@ -349,6 +381,10 @@ pub enum FileStoreProvider {
         bucket_name: String,
         sub_path: Option<String>,
     },
+    Azure {
+        container_name: String,
+        sub_path: Option<String>,
+    },
 }
 
 #[derive(Clone)]
@@ -441,6 +477,9 @@ pub trait ConfigObj: DIService {
 
     fn http_bind_address(&self) -> &Option<String>;
 
+    /// Users allowed to connect, anyone is allowed to run any statement if empty.
+    fn auth_users(&self) -> &Vec<AuthUser>;
+
     fn query_timeout(&self) -> u64;
 
     fn not_used_timeout(&self) -> u64;
@@ -515,6 +554,10 @@ pub trait ConfigObj: DIService {
 
     fn cachestore_queue_results_expire(&self) -> u64;
 
+    fn cachestore_queue_retry_loop_interval(&self) -> u64;
+
+    fn cachestore_queue_retry_heartbeat_timeout(&self) -> u64;
+
     fn cachestore_metrics_interval(&self) -> u64;
 
     fn download_concurrency(&self) -> u64;
@@ -633,6 +676,16 @@ pub trait ConfigObj: DIService {
 
     fn dump_dir(&self) -> &Option<PathBuf>;
 
+    /// Id of the key from `CUBESTORE_ENCRYPTION_KEYS` used to encrypt files uploaded to the
+    /// remote filesystem. Encryption is disabled when not set.
+    fn encryption_key_id(&self) -> &Option<String>;
+
+    /// Allows reading remote files uploaded before encryption was enabled.
+    fn encryption_allow_unencrypted(&self) -> bool;
+
+    /// Per-tenant rate limits of task types. Task types without a limit aren't throttled.
+    fn rate_limits(&self) -> &HashMap<TaskType, TaskRateLimit>;
+
     fn snapshots_deletion_batch_size(&self) -> u64;
 
     fn minimum_metastore_snapshots_count(&self) -> u64;
@@ -697,6 +750,9 @@ pub struct ConfigObjImpl {
     pub data_dir: PathBuf,
     pub dump_dir: Option<PathBuf>,
     pub store_provider: FileStoreProvider,
+    pub encryption_key_id: Option<String>,
+    pub encryption_allow_unencrypted: bool,
+    pub rate_limits: HashMap<TaskType, TaskRateLimit>,
     pub select_worker_pool_size: usize,
     pub select_worker_idle_timeout: u64,
     pub job_runners_count: usize,
@@ -705,6 +761,7 @@ pub struct ConfigObjImpl {
     pub bind_address: Option<String>,
     pub status_bind_address: Option<String>,
     pub http_bind_address: Option<String>,
+    pub auth_users: Vec<AuthUser>,
     pub query_timeout: u64,
     /// Must be set to 2*query_timeout in prod, only for overrides in tests.
     pub not_used_timeout: u64,
@@ -732,6 +789,8 @@ pub struct ConfigObjImpl {
     pub cachestore_cache_compaction_trigger_size: u64,
     pub cachestore_cache_threshold_to_force_eviction: u8,
     pub cachestore_queue_results_expire: u64,
+    pub cachestore_queue_retry_loop_interval: u64,
+    pub cachestore_queue_retry_heartbeat_timeout: u64,
     pub cachestore_metrics_interval: u64,
     pub cachestore_cache_max_keys: u32,
     pub cachestore_cache_policy: CacheEvictionPolicy,
@@ -908,6 +967,10 @@ impl ConfigObj for ConfigObjImpl {
         &self.http_bind_address
     }
 
+    fn auth_users(&self) -> &Vec<AuthUser> {
+        &self.auth_users
+    }
+
     fn query_timeout(&self) -> u64 {
         self.query_timeout
     }
@@ -1048,6 +1111,14 @@ impl ConfigObj for ConfigObjImpl {
         self.cachestore_queue_results_expire
     }
 
+    fn cachestore_queue_retry_loop_interval(&self) -> u64 {
+        self.cachestore_queue_retry_loop_interval
+    }
+
+    fn cachestore_queue_retry_heartbeat_timeout(&self) -> u64 {
+        self.cachestore_queue_retry_heartbeat_timeout
+    }
+
     fn cachestore_metrics_interval(&self) -> u64 {
         self.cachestore_metrics_interval
     }
@@ -1186,6 +1257,18 @@ impl ConfigObj for ConfigObjImpl {
         &self.dump_dir
     }
 
+    fn encryption_key_id(&self) -> &Option<String> {
+        &self.encryption_key_id
+    }
+
+    fn encryption_allow_unencrypted(&self) -> bool {
+        self.encryption_allow_unencrypted
+    }
+
+    fn rate_limits(&self) -> &HashMap<TaskType, TaskRateLimit> {
+        &self.rate_limits
+    }
+
     fn snapshots_deletion_batch_size(&self) -> u64 {
         self.snapshots_deletion_batch_size
     }
@@ -1534,6 +1617,43 @@ where
     })
 }
 
+/// Reads `CUBESTORE_RATE_LIMIT_<TASK TYPE>` budgets restored per second, e.g.
+/// `CUBESTORE_RATE_LIMIT_SELECT` for bytes loaded by selects, and `CUBESTORE_RATE_LIMIT_BURST_SECS`
+/// seconds of budget a tenant can accumulate while idle.
+fn env_rate_limits() -> HashMap<TaskType, TaskRateLimit> {
+    let burst_secs = env_parse("CUBESTORE_RATE_LIMIT_BURST_SECS", 10);
+    [
+        TaskType::Select,
+        TaskType::Job,
+        TaskType::Cache,
+        TaskType::Queue,
+    ]
+    .into_iter()
+    .filter_map(|task_type| {
+        let name = format!(
+            "CUBESTORE_RATE_LIMIT_{}",
+            task_type.name().to_ascii_uppercase()
+        );
+        env_optparse::<u64>(&name)
+            .filter(|rate| *rate > 0)
+            .map(|rate| (task_type, TaskRateLimit { rate, burst_secs }))
+    })
+    .collect()
+}
+
+/// Users set by `CUBESTORE_AUTH_USERS`, empty if it isn't set.
+fn env_auth_users() -> Result<Vec<AuthUser>, CubeError> {
+    match env::var("CUBESTORE_AUTH_USERS") {
+        Ok(users) => serde_json::from_str(&users).map_err(|e| {
+            CubeError::user(format!(
+                "Can't parse CUBESTORE_AUTH_USERS env variable: {}",
+                e
+            ))
+        }),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
 // Unlike env_optparse, an unparseable value is not fatal: it logs a warning and falls
 // back to per_chunk, so a typo in the strategy env never takes the process down.
 fn env_repartition_strategy() -> RepartitionStrategy {
@@ -1697,6 +1817,11 @@ impl Config {
                             bucket_name,
                             sub_path: env::var("CUBESTORE_GCS_SUB_PATH").ok(),
                         }
+                    } else if let Ok(container_name) = env::var("CUBESTORE_AZURE_CONTAINER") {
+                        FileStoreProvider::Azure {
+                            container_name,
+                            sub_path: env::var("CUBESTORE_AZURE_SUB_PATH").ok(),
+                        }
                     } else if let Ok(remote_dir) = env::var("CUBESTORE_REMOTE_DIR") {
                         FileStoreProvider::Filesystem {
                             remote_dir: Some(PathBuf::from(remote_dir)),
@@ -1705,6 +1830,12 @@ impl Config {
                         FileStoreProvider::Filesystem { remote_dir: None }
                     }
                 },
+                encryption_key_id: env::var("CUBESTORE_ENCRYPTION_KEY_ID").ok(),
+                encryption_allow_unencrypted: env_bool(
+                    "CUBESTORE_ENCRYPTION_ALLOW_UNENCRYPTED",
+                    false,
+                ),
+                rate_limits: env_rate_limits(),
                 select_worker_pool_size: env_parse("CUBESTORE_SELECT_WORKERS", 4),
                 select_worker_idle_timeout: env_parse_duration(
                     "CUBESTORE_SELECT_WORKERS_IDLE_TIMEOUT",
@@ -1723,6 +1854,8 @@ impl Config {
                 http_bind_address: Some(env::var("CUBESTORE_HTTP_BIND_ADDR").ok().unwrap_or(
                     format!("0.0.0.0:{}", env_parse("CUBESTORE_HTTP_PORT", 3030)),
                 )),
+                // Invalid value is reported by `validate_config` on startup
+                auth_users: env_auth_users().unwrap_or_default(),
                 query_timeout,
                 not_used_timeout: 2 * query_timeout,
                 in_memory_not_used_timeout: 30,
@@ -1792,6 +1925,19 @@ impl Config {
                     Some(60 * 5),
                     Some(1),
                 ),
+                cachestore_queue_retry_loop_interval: env_parse_duration(
+                    "CUBESTORE_QUEUE_RETRY_LOOP",
+                    5,
+                    Some(60 * 10),
+                    // 0 to disable
+                    Some(0),
+                ),
+                cachestore_queue_retry_heartbeat_timeout: env_parse_duration(
+                    "CUBESTORE_QUEUE_RETRY_HEARTBEAT_TIMEOUT",
+                    60,
+                    Some(60 * 60),
+                    Some(1),
+                ),
                 cachestore_metrics_interval: env_parse_duration(
                     "CUBESTORE_CACHESTORE_METRICS_LOOP",
                     15,
@@ -2090,6 +2236,9 @@ impl Config {
                 store_provider: FileStoreProvider::Filesystem {
                     remote_dir: Some(Self::test_remote_dir_path(directory, name)),
                 },
+                encryption_key_id: None,
+                encryption_allow_unencrypted: false,
+                rate_limits: HashMap::new(),
                 select_worker_pool_size: 0,
                 select_worker_idle_timeout: 600,
                 job_runners_count: 4,
@@ -2098,6 +2247,7 @@ impl Config {
                 bind_address: None,
                 status_bind_address: None,
                 http_bind_address: None,
+                auth_users: Vec::new(),
                 query_timeout,
                 not_used_timeout: 2 * query_timeout,
                 in_memory_not_used_timeout: 30,
@@ -2120,6 +2270,8 @@ impl Config {
                 cachestore_cache_compaction_trigger_size: 4096 * 2 << 20,
                 cachestore_cache_threshold_to_force_eviction: 25,
                 cachestore_queue_results_expire: 90,
+                cachestore_queue_retry_loop_interval: 5,
+                cachestore_queue_retry_heartbeat_timeout: 60,
                 cachestore_metrics_interval: 15,
                 cachestore_cache_max_keys: 100_000,
                 cachestore_cache_policy: CacheEvictionPolicy::SampledLru,
@@ -2436,12 +2588,24 @@ impl Config {
             .register_typed::<dyn ConfigObj, _, _, _>(async move |_| config_obj_to_register)
             .await;
 
+        // With encryption enabled, the storage backend keeps encrypted files in transit in its
+        // own directory and is wrapped by `EncryptedRemoteFs`.
+        let encryption_key_id = self.config_obj.encryption_key_id().clone();
+        let (backend_service_name, backend_data_dir) = if encryption_key_id.is_some() {
+            (
+                "unencrypted_remote_fs",
+                self.config_obj.data_dir.join("encrypted"),
+            )
+        } else {
+            ("original_remote_fs", self.config_obj.data_dir.clone())
+        };
+
         match &self.config_obj.store_provider {
             FileStoreProvider::Filesystem { remote_dir } => {
                 let remote_dir = remote_dir.clone();
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = backend_data_dir.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(backend_service_name, async move |_| {
                         let arc: Arc<dyn DIService> = LocalDirRemoteFs::new(remote_dir, data_dir);
                         arc
                     })
@@ -2452,12 +2616,12 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = backend_data_dir.clone();
                 let region = region.to_string();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(backend_service_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             S3RemoteFs::new(data_dir, region, bucket_name, sub_path).unwrap();
                         arc
@@ -2468,11 +2632,11 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = backend_data_dir.clone();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(backend_service_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             GCSRemoteFs::new(data_dir, bucket_name, sub_path).unwrap();
                         arc
@@ -2483,24 +2647,57 @@ impl Config {
                 bucket_name,
                 sub_path,
             } => {
-                let data_dir = self.config_obj.data_dir.clone();
+                let data_dir = backend_data_dir.clone();
                 let bucket_name = bucket_name.to_string();
                 let sub_path = sub_path.clone();
                 self.injector
-                    .register("original_remote_fs", async move |_| {
+                    .register(backend_service_name, async move |_| {
                         let arc: Arc<dyn DIService> =
                             MINIORemoteFs::new(data_dir, bucket_name, sub_path).unwrap();
                         arc
                     })
                     .await;
             }
+            FileStoreProvider::Azure {
+                container_name,
+                sub_path,
+            } => {
+                let data_dir = backend_data_dir.clone();
+                let container_name = container_name.to_string();
+                let sub_path = sub_path.clone();
+                self.injector
+                    .register(backend_service_name, async move |_| {
+                        let arc: Arc<dyn DIService> =
+                            AzureBlobRemoteFs::new(data_dir, container_name, sub_path).unwrap();
+                        arc
+                    })
+                    .await;
+            }
             FileStoreProvider::Local => unimplemented!(), // TODO
         };
+
+        if let Some(key_id) = encryption_key_id {
+            let data_dir = self.config_obj.data_dir.clone();
+            let allow_unencrypted = self.config_obj.encryption_allow_unencrypted();
+            self.injector
+                .register("original_remote_fs", async move |i| {
+                    let keyring = EncryptionKeyring::from_env(key_id).unwrap();
+                    let arc: Arc<dyn DIService> = EncryptedRemoteFs::new(
+                        data_dir,
+                        i.get_service(backend_service_name).await,
+                        keyring,
+                        allow_unencrypted,
+                    );
+                    arc
+                })
+                .await;
+        }
     }
 
     pub async fn configure_cache_store(&self) {
         let (cachestore_event_sender, _) = broadcast::channel(2048); // TODO config
         let cachestore_event_sender_to_move = cachestore_event_sender.clone();
+        let cachestore_notifier_sender = cachestore_event_sender.clone();
 
         if uses_remote_metastore(&self.injector).await {
             self.injector
@@ -2554,6 +2751,12 @@ impl Config {
                 .await;
         }
 
+        self.injector
+            .register_typed::<CacheStoreNotifier, _, _, _>(async move |_| {
+                CacheStoreNotifier::new(cachestore_notifier_sender)
+            })
+            .await;
+
         self.injector
             .register_typed::<CacheStoreSchedulerImpl, _, _, _>(async move |i| {
                 Arc::new(CacheStoreSchedulerImpl::new(
@@ -2736,6 +2939,8 @@ impl Config {
                         .await
                         .cache_factory()
                         .clone(),
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
                 )
             })
             .await;
@@ -2751,11 +2956,26 @@ impl Config {
             .await;
 
         self.injector
-            .register_typed::<dyn ProcessRateLimiter, _, _, _>(async move |_| {
-                BasicProcessRateLimiter::new()
+            .register_typed::<dyn SchemaRegistryClient, _, _, _>(async move |_| {
+                SchemaRegistryClientImpl::new()
             })
             .await;
 
+        let rate_limits = self.config_obj.rate_limits().clone();
+        if rate_limits.is_empty() {
+            self.injector
+                .register_typed::<dyn ProcessRateLimiter, _, _, _>(async move |_| {
+                    BasicProcessRateLimiter::new()
+                })
+                .await;
+        } else {
+            self.injector
+                .register_typed::<dyn ProcessRateLimiter, _, _, _>(async move |_| {
+                    TokenBucketProcessRateLimiter::new(rate_limits.clone())
+                })
+                .await;
+        }
+
         let cluster_meta_store_sender = metastore_event_sender_to_move.clone();
 
         self.injector
@@ -2900,6 +3120,7 @@ impl Config {
                     i.get_service_typed().await,
                     i.get_service_typed().await,
                     i.get_service_typed().await,
+                    i.get_service_typed().await,
                     c.wal_split_threshold() as usize,
                     Duration::from_secs(c.query_timeout()),
                     Duration::from_secs(c.import_job_timeout() * 2),
@@ -2922,11 +3143,20 @@ impl Config {
             .await;
 
         if self.config_obj.bind_address().is_some() {
-            self.injector
-                .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
-                    Arc::new(SqlAuthDefaultImpl)
-                })
-                .await;
+            let auth_users = self.config_obj.auth_users().clone();
+            if auth_users.is_empty() {
+                self.injector
+                    .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
+                        Arc::new(SqlAuthDefaultImpl)
+                    })
+                    .await;
+            } else {
+                self.injector
+                    .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
+                        SqlAuthConfiguredImpl::new(auth_users)
+                    })
+                    .await;
+            }
 
             self.injector
                 .register_typed::<MySqlServer, _, _, _>(async move |i| {
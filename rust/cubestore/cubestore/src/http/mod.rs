@@ -7,11 +7,14 @@ use warp::{Filter, Rejection, Reply};
 use crate::cachestore::QUEUE_ITEM_PROCESS_ID_MAX_LEN;
 use crate::metastore::{Column, ColumnType, ImportFormat};
 use crate::mysql::SqlAuthService;
+use crate::sql::access_control::UserGrants;
 use crate::sql::{
-    InlineTable, InlineTables, QueryParameter, QueryParameters, SqlQueryContext, SqlService,
+    InlineTable, InlineTables, QueryParameter, QueryParameters, QueryResult, SqlQueryContext,
+    SqlService,
 };
 use crate::store::DataFrame;
 use crate::table::{Row, TableValue};
+use crate::telemetry::tracing::{query_span, traceparent_from_trace_obj};
 use crate::util::WorkerLoop;
 use crate::{app_metrics, CubeError};
 use cubeshared::codegen::{
@@ -23,6 +26,7 @@ use cubeshared::codegen::{
 };
 use cubeshared::flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector, WIPOffset};
 use datafusion::cube_ext;
+use futures::stream::BoxStream;
 use futures::{SinkExt, Stream, StreamExt};
 use futures_timer::Delay;
 use hex::ToHex;
@@ -41,6 +45,7 @@ use tokio::io::{AsyncWriteExt, BufReader};
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 use warp::filters::ws::{Message, Ws};
 use warp::http::StatusCode;
 use warp::reject::Reject;
@@ -145,12 +150,13 @@ impl HttpServer {
 
                         let res = HttpServer::authorize(auth_service, auth_header).await;
                         match res {
-                            Ok(user) => Ok(SqlQueryContext {
+                            Ok((user, grants)) => Ok(SqlQueryContext {
                                 user,
                                 inline_tables: InlineTables::new(),
                                 parameters: None,
                                 trace_obj: None,
                                 process_id,
+                                grants,
                             }),
                             Err(_) => Err(warp::reject::custom(CubeRejection::NotAuthorized)),
                         }
@@ -307,6 +313,7 @@ impl HttpServer {
                 if connection_id.is_some() {
                     cube_ext::spawn(async move {
                         let key = (connection_id.clone(), message_id);
+                        let subscriber = HttpSubscriber::new(message_id, connection_id.clone(), sender.clone());
                         {
                             let mut messages = messages_state.lock().await;
                             let state = messages.get_mut(&key);
@@ -332,6 +339,7 @@ impl HttpServer {
                             sql_service.clone(),
                             sql_query_context,
                             command.clone(),
+                            Some(subscriber),
                         )
                             .await;
                         let message = Arc::new(match res {
@@ -441,6 +449,7 @@ impl HttpServer {
                             sql_service.clone(),
                             sql_query_context,
                             command,
+                            Some(HttpSubscriber::new(message_id, connection_id.clone(), sender.clone())),
                         )
                             .await;
                         let message = Arc::new(match res {
@@ -613,6 +622,7 @@ impl HttpServer {
         sql_service: Arc<dyn SqlService>,
         sql_query_context: SqlQueryContext,
         command: HttpCommand,
+        subscriber: Option<HttpSubscriber>,
     ) -> Result<HttpCommand, CubeError> {
         match command {
             HttpCommand::Query {
@@ -622,6 +632,7 @@ impl HttpServer {
                 parameters,
                 response_format,
             } => {
+                let trace_parent = trace_obj.as_deref().and_then(traceparent_from_trace_obj);
                 let query_result = sql_service
                     .exec_query_with_context(
                         sql_query_context
@@ -630,55 +641,113 @@ impl HttpServer {
                             .with_parameters(&parameters),
                         &query,
                     )
+                    .instrument(query_span(trace_parent))
                     .await?;
-                match response_format {
-                    QueryResultFormat::Legacy => Ok(HttpCommand::ResultSet {
-                        data_frame: query_result.collect().await?,
-                    }),
-                    QueryResultFormat::Arrow => {
-                        // Commands that complete without a result set (CREATE
-                        // TABLE/INSERT, queue/cache writes) carry zero columns.
-                        // There's no Arrow stream to build for them, so signal
-                        // completion with a dedicated result instead.
-                        if query_result.schema().fields().is_empty() {
-                            Ok(HttpCommand::QueryResultCompleted)
-                        } else {
-                            let data = query_result.to_arrow_ipc_stream().await?;
-                            Ok(HttpCommand::QueryResultArrow { data })
-                        }
+                match query_result {
+                    QueryResult::Subscription { columns, rows } => {
+                        let subscriber = subscriber.ok_or_else(|| {
+                            CubeError::user(
+                                "Subscriptions are not supported for this connection".to_string(),
+                            )
+                        })?;
+
+                        HttpServer::process_subscription(subscriber, columns, rows, response_format)
+                            .await
+                    }
+                    query_result => {
+                        HttpServer::encode_query_result(query_result, response_format).await
                     }
-                    other => Err(CubeError::user(format!(
-                        "Unsupported response_format: {:?}",
-                        other
-                    ))),
                 }
             }
             x => Err(CubeError::user(format!("Unexpected command: {:?}", x))),
         }
     }
 
+    async fn encode_query_result(
+        query_result: QueryResult,
+        response_format: QueryResultFormat,
+    ) -> Result<HttpCommand, CubeError> {
+        match response_format {
+            QueryResultFormat::Legacy => Ok(HttpCommand::ResultSet {
+                data_frame: query_result.collect().await?,
+            }),
+            QueryResultFormat::Arrow => {
+                // Commands that complete without a result set (CREATE
+                // TABLE/INSERT, queue/cache writes) carry zero columns.
+                // There's no Arrow stream to build for them, so signal
+                // completion with a dedicated result instead.
+                if query_result.schema().fields().is_empty() {
+                    Ok(HttpCommand::QueryResultCompleted)
+                } else {
+                    let data = query_result.to_arrow_ipc_stream().await?;
+                    Ok(HttpCommand::QueryResultArrow { data })
+                }
+            }
+            other => Err(CubeError::user(format!(
+                "Unsupported response_format: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Sends an empty result set with the subscription columns first, then a result set per
+    /// notification, all with the id of the subscribing message. The returned command (an empty
+    /// result) is sent once the subscription is over: the stream has ended or the client is gone.
+    async fn process_subscription(
+        subscriber: HttpSubscriber,
+        columns: Vec<Column>,
+        mut rows: BoxStream<'static, Result<Row, CubeError>>,
+        response_format: QueryResultFormat,
+    ) -> Result<HttpCommand, CubeError> {
+        let header = HttpServer::encode_query_result(
+            DataFrame::new(columns.clone(), vec![]).into(),
+            response_format,
+        )
+        .await?;
+        subscriber.send(header).await;
+
+        loop {
+            let row = tokio::select! {
+                _ = subscriber.sender.closed() => break,
+                row = rows.next() => match row {
+                    Some(row) => row?,
+                    None => break,
+                },
+            };
+
+            let command = HttpServer::encode_query_result(
+                DataFrame::new(columns.clone(), vec![row]).into(),
+                response_format,
+            )
+            .await?;
+            if !subscriber.send(command).await {
+                break;
+            }
+        }
+
+        HttpServer::encode_query_result(DataFrame::new(vec![], vec![]).into(), response_format)
+            .await
+    }
+
+    /// Returns the authenticated user with the user grants.
     pub async fn authorize(
         auth: Arc<dyn SqlAuthService>,
         auth_header: Option<String>,
-    ) -> Result<Option<String>, CubeError> {
+    ) -> Result<(Option<String>, Option<UserGrants>), CubeError> {
         let credentials = auth_header
             .map(|auth_header| Credentials::from_header(auth_header))
             .transpose()
             .map_err(|e| CubeError::from_error(e))?;
-        if let Some(password) = auth
-            .authenticate(credentials.as_ref().map(|c| c.user_id.to_string()))
-            .await?
-        {
+        let user = credentials.as_ref().map(|c| c.user_id.to_string());
+        if let Some(password) = auth.authenticate(user.clone()).await? {
             if Some(password) != credentials.as_ref().map(|c| c.password.to_string()) {
-                Err(CubeError::user(
+                return Err(CubeError::user(
                     "User or password doesn't match".to_string(),
-                ))
-            } else {
-                Ok(credentials.as_ref().map(|c| c.user_id.to_string()))
+                ));
             }
-        } else {
-            Ok(credentials.as_ref().map(|c| c.user_id.to_string()))
         }
+        let grants = auth.user_grants(user.clone()).await?;
+        Ok((user, grants))
     }
 
     pub async fn stop_processing(&self) {
@@ -688,6 +757,41 @@ impl HttpServer {
     }
 }
 
+/// Receives the intermediate messages of long-lived commands, like `CACHE SUBSCRIBE`, which are
+/// delivered to the web socket ahead of the final response.
+#[derive(Clone)]
+pub struct HttpSubscriber {
+    message_id: u32,
+    connection_id: Option<String>,
+    sender: Sender<Arc<HttpMessage>>,
+}
+
+impl HttpSubscriber {
+    pub fn new(
+        message_id: u32,
+        connection_id: Option<String>,
+        sender: Sender<Arc<HttpMessage>>,
+    ) -> Self {
+        Self {
+            message_id,
+            connection_id,
+            sender,
+        }
+    }
+
+    /// Returns false when the web socket has been closed.
+    async fn send(&self, command: HttpCommand) -> bool {
+        self.sender
+            .send(Arc::new(HttpMessage {
+                message_id: self.message_id,
+                connection_id: self.connection_id.clone(),
+                command,
+            }))
+            .await
+            .is_ok()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct HttpMessage {
     message_id: u32,
@@ -934,6 +1038,18 @@ impl HttpMessage {
                         let string_value = Some(builder.create_string(&v.to_string()));
                         HttpColumnValue::create(builder, &HttpColumnValueArgs { string_value })
                     }
+                    TableValue::Date(v) => {
+                        let string_value = Some(builder.create_string(&v.to_string()));
+                        HttpColumnValue::create(builder, &HttpColumnValueArgs { string_value })
+                    }
+                    TableValue::Interval(v) => {
+                        let string_value = Some(builder.create_string(&v.to_string()));
+                        HttpColumnValue::create(builder, &HttpColumnValueArgs { string_value })
+                    }
+                    TableValue::List(_) => {
+                        let string_value = Some(builder.create_string(&value.to_string()));
+                        HttpColumnValue::create(builder, &HttpColumnValueArgs { string_value })
+                    }
                 };
                 value_offsets.push(value);
             }
@@ -1450,6 +1566,7 @@ mod tests {
                 parameters: None,
                 response_format: QueryResultFormat::Arrow,
             },
+            None,
         )
         .await?;
         let arrow_bytes = match resp {
@@ -1514,6 +1631,7 @@ mod tests {
                 parameters: None,
                 response_format: QueryResultFormat::Arrow,
             },
+            None,
         )
         .await?;
         assert!(
@@ -1610,6 +1728,7 @@ mod tests {
         };
         let mut auth = MockSqlAuthService::new();
         auth.expect_authenticate().return_const(Ok(None));
+        auth.expect_user_grants().return_const(Ok(None));
 
         let config = Config::test("ws_test").config_obj();
 
@@ -1811,6 +1930,7 @@ mod tests {
 
         let mut auth = MockSqlAuthService::new();
         auth.expect_authenticate().return_const(Ok(None));
+        auth.expect_user_grants().return_const(Ok(None));
 
         let config = Config::test("ws_process_id_header_test").config_obj();
 
@@ -1861,4 +1981,119 @@ mod tests {
         http_server.stop_processing().await;
         Ok(())
     }
+
+    /// Replies with a subscription that yields the given rows and then ends.
+    struct SubscriptionStubService(Vec<Row>);
+    crate::di_service!(SubscriptionStubService, [SqlService]);
+    #[async_trait]
+    impl SqlService for SubscriptionStubService {
+        async fn exec_query(&self, _q: &str) -> Result<QueryResult, CubeError> {
+            unimplemented!("Mock")
+        }
+        async fn exec_query_with_context(
+            &self,
+            _ctx: SqlQueryContext,
+            _q: &str,
+        ) -> Result<QueryResult, CubeError> {
+            Ok(QueryResult::Subscription {
+                columns: vec![
+                    Column::new("event".to_string(), ColumnType::String, 0),
+                    Column::new("key".to_string(), ColumnType::String, 1),
+                ],
+                rows: futures::stream::iter(self.0.clone().into_iter().map(Ok)).boxed(),
+            })
+        }
+        async fn plan_query(&self, _q: &str) -> Result<QueryPlans, CubeError> {
+            unimplemented!("Mock")
+        }
+        async fn plan_query_with_context(
+            &self,
+            _ctx: SqlQueryContext,
+            _q: &str,
+        ) -> Result<QueryPlans, CubeError> {
+            unimplemented!("Mock")
+        }
+        async fn upload_temp_file(
+            &self,
+            _ctx: SqlQueryContext,
+            _name: String,
+            _path: &Path,
+        ) -> Result<(), CubeError> {
+            unimplemented!("Mock")
+        }
+        async fn temp_uploads_dir(&self, _ctx: SqlQueryContext) -> Result<String, CubeError> {
+            unimplemented!("Mock")
+        }
+    }
+
+    #[tokio::test]
+    async fn process_command_subscription() -> Result<(), CubeError> {
+        let rows = vec![
+            Row::new(vec![
+                TableValue::String("set".to_string()),
+                TableValue::String("refresh:orders".to_string()),
+            ]),
+            Row::new(vec![
+                TableValue::String("remove".to_string()),
+                TableValue::String("refresh:orders".to_string()),
+            ]),
+        ];
+        let svc = Arc::new(SubscriptionStubService(rows.clone()));
+        let command = HttpCommand::Query {
+            query: "CACHE SUBSCRIBE 'refresh:'".to_string(),
+            inline_tables: vec![],
+            trace_obj: None,
+            parameters: None,
+            response_format: QueryResultFormat::Legacy,
+        };
+
+        let res = HttpServer::process_command(
+            svc.clone(),
+            SqlQueryContext::default(),
+            command.clone(),
+            None,
+        )
+        .await;
+        assert!(res.is_err(), "expected error, got: {:?}", res);
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+        let resp = HttpServer::process_command(
+            svc,
+            SqlQueryContext::default(),
+            command,
+            Some(HttpSubscriber::new(3, Some("conn".to_string()), sender)),
+        )
+        .await?;
+        // The subscription is over once the stream has ended
+        assert_eq!(
+            resp,
+            HttpCommand::ResultSet {
+                data_frame: Arc::new(DataFrame::new(vec![], vec![]))
+            }
+        );
+
+        let columns = vec![
+            Column::new("event".to_string(), ColumnType::String, 0),
+            Column::new("key".to_string(), ColumnType::String, 1),
+        ];
+        let mut expected = vec![DataFrame::new(columns.clone(), vec![])];
+        expected.extend(
+            rows.into_iter()
+                .map(|row| DataFrame::new(columns.clone(), vec![row])),
+        );
+        for data_frame in expected {
+            let message = receiver.recv().await.unwrap();
+            assert_eq!(message.message_id, 3);
+            assert_eq!(message.connection_id, Some("conn".to_string()));
+            assert_eq!(
+                message.command,
+                HttpCommand::ResultSet {
+                    data_frame: Arc::new(data_frame)
+                }
+            );
+        }
+        assert!(receiver.try_recv().is_err());
+
+        Ok(())
+    }
 }
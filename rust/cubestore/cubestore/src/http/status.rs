@@ -2,12 +2,15 @@ use crate::config::injection::Injector;
 use crate::config::{is_router, uses_remote_metastore, Config};
 use crate::metastore::MetaStore;
 use crate::sql::SqlService;
+use crate::util::metrics::registry::{registry, PROMETHEUS_CONTENT_TYPE};
 use crate::CubeError;
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use warp::http::header::CONTENT_TYPE;
 use warp::http::StatusCode;
-use warp::Filter;
+use warp::reply::Response;
+use warp::{Filter, Reply};
 
 pub fn serve_status_probes(c: &Config) {
     let addr = match c.config_obj().status_bind_address() {
@@ -15,23 +18,32 @@ pub fn serve_status_probes(c: &Config) {
         None => return,
     };
 
-    let p = match RouterProbes::try_new(c) {
-        Some(p) => p,
+    // Every node serves its own metrics, probes are only served by the router.
+    let m = warp::path!("metrics").map(metrics_reply);
+    let routes = match RouterProbes::try_new(c) {
+        Some(p) => {
+            let pc = p.clone();
+            let l = warp::path!("livez").and_then(move || {
+                let pc = pc.clone();
+                async move { status_probe_reply("liveness", pc.is_live().await) }
+            });
+            let r = warp::path!("readyz").and_then(move || {
+                let p = p.clone();
+                async move { status_probe_reply("readiness", p.is_ready().await) }
+            });
+            l.or(r)
+                .unify()
+                .map(|s: StatusCode| s.into_response())
+                .or(m)
+                .unify()
+                .boxed()
+        }
+        None if registry().is_some() => m.boxed(),
         None => return,
     };
 
-    let pc = p.clone();
-    let l = warp::path!("livez").and_then(move || {
-        let pc = pc.clone();
-        async move { status_probe_reply("liveness", pc.is_live().await) }
-    });
-    let r = warp::path!("readyz").and_then(move || {
-        let p = p.clone();
-        async move { status_probe_reply("readiness", p.is_ready().await) }
-    });
-
     let addr: SocketAddr = addr.parse().expect("cannot parse status probe address");
-    match warp::serve(l.or(r)).try_bind_ephemeral(addr) {
+    match warp::serve(routes).try_bind_ephemeral(addr) {
         Ok((addr, f)) => {
             log::info!("Serving status probes at {}", addr);
             tokio::spawn(f);
@@ -42,6 +54,14 @@ pub fn serve_status_probes(c: &Config) {
     }
 }
 
+fn metrics_reply() -> Response {
+    match registry() {
+        Some(r) => warp::reply::with_header(r.render(), CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 pub fn status_probe_reply(probe: &str, r: Result<(), CubeError>) -> Result<StatusCode, Infallible> {
     match r {
         Ok(()) => Ok(StatusCode::OK),
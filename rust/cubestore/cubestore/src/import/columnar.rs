@@ -0,0 +1,439 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, AsArray, BinaryBuilder, StringBuilder};
+use datafusion::arrow::compute::{cast, cast_with_options, concat, CastOptions};
+use datafusion::arrow::datatypes::{
+    DataType, Field, IntervalUnit, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType,
+    TimestampNanosecondType, TimestampSecondType,
+};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::reader::{FileReader, StreamReader};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use itertools::Itertools;
+
+use crate::metastore::{Column, ColumnType, HllFlavour, ImportFormat};
+use crate::CubeError;
+
+const ARROW_FILE_MAGIC: &[u8; 6] = b"ARROW1";
+
+type RecordBatchIter = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>> + Send>;
+
+/// Reads a Parquet or Arrow IPC file and yields its content as table columns, at most
+/// `batch_size` rows per item. File columns are matched to table columns by name; extra file
+/// columns are ignored. Blocking, so it's expected to be driven from `spawn_blocking`.
+pub fn read_columns(
+    format: ImportFormat,
+    file: File,
+    columns: Vec<Column>,
+    batch_size: usize,
+) -> Result<impl Iterator<Item = Result<Vec<ArrayRef>, CubeError>> + Send, CubeError> {
+    let batches = open_batches(format, file, batch_size)?;
+    Ok(batches
+        .map(move |batch| -> Result<Vec<RecordBatch>, CubeError> {
+            let batch = batch?;
+            // IPC files don't let us choose the batch size, so large batches are sliced to keep
+            // data frames within the ingestion split threshold.
+            Ok((0..batch.num_rows())
+                .step_by(batch_size.max(1))
+                .map(|offset| batch.slice(offset, batch_size.min(batch.num_rows() - offset)))
+                .collect())
+        })
+        .flatten_ok()
+        .map(move |batch| batch_to_columns(&batch?, &columns)))
+}
+
+fn open_batches(
+    format: ImportFormat,
+    mut file: File,
+    batch_size: usize,
+) -> Result<RecordBatchIter, CubeError> {
+    match format {
+        ImportFormat::Parquet => Ok(Box::new(
+            ParquetRecordBatchReaderBuilder::try_new(file)?
+                .with_batch_size(batch_size)
+                .build()?,
+        )),
+        ImportFormat::ArrowIPC => {
+            // Both the IPC file format (with footer) and the streaming format are accepted.
+            let mut magic = [0u8; 6];
+            let is_file_format = file.read_exact(&mut magic).is_ok() && &magic == ARROW_FILE_MAGIC;
+            file.seek(SeekFrom::Start(0))?;
+            if is_file_format {
+                Ok(Box::new(FileReader::try_new_buffered(file, None)?))
+            } else {
+                Ok(Box::new(StreamReader::try_new_buffered(file, None)?))
+            }
+        }
+        f => Err(CubeError::internal(format!(
+            "{:?} is not a columnar import format",
+            f
+        ))),
+    }
+}
+
+/// Projects a record batch onto table columns, converting each array to the column's storage type.
+pub fn batch_to_columns(
+    batch: &RecordBatch,
+    columns: &[Column],
+) -> Result<Vec<ArrayRef>, CubeError> {
+    let schema = batch.schema();
+    columns
+        .iter()
+        .map(|column| {
+            let index = schema.index_of(column.get_name()).map_err(|_| {
+                CubeError::user(format!(
+                    "Column '{}' is not found during import in {:?}",
+                    column.get_name(),
+                    schema.fields().iter().map(|f| f.name()).collect_vec()
+                ))
+            })?;
+            convert_array(batch.column(index), column)
+        })
+        .collect()
+}
+
+/// Concatenates data frames produced by `read_columns` column by column.
+pub fn concat_columns(frames: Vec<Vec<ArrayRef>>) -> Result<Vec<ArrayRef>, CubeError> {
+    if frames.len() == 1 {
+        return Ok(frames.into_iter().next().unwrap());
+    }
+    let num_columns = frames.first().map_or(0, |f| f.len());
+    (0..num_columns)
+        .map(|i| {
+            let arrays = frames.iter().map(|f| f[i].as_ref()).collect_vec();
+            Ok(concat(&arrays)?)
+        })
+        .collect()
+}
+
+fn convert_array(array: &ArrayRef, column: &Column) -> Result<ArrayRef, CubeError> {
+    let column_type = column.get_column_type();
+    if !is_compatible(array.data_type(), column_type) {
+        return Err(CubeError::user(format!(
+            "Can't import values of type {} into '{}' column of type {}",
+            array.data_type(),
+            column.get_name(),
+            column_type
+        )));
+    }
+    let conversion_error = |e: CubeError| {
+        CubeError::user(format!(
+            "Can't convert values of type {} for '{}' column: {}",
+            array.data_type(),
+            column.get_name(),
+            e
+        ))
+    };
+    match column_type {
+        ColumnType::HyperLogLog(f) => {
+            convert_binary_array(array, |v| ImportFormat::parse_hll_bytes(*f, v))
+                .map_err(conversion_error)
+        }
+        ColumnType::TDigest => {
+            convert_binary_array(array, ImportFormat::parse_tdigest_bytes).map_err(conversion_error)
+        }
+        ColumnType::ThetaSketch => {
+            convert_binary_array(array, ImportFormat::parse_theta_sketch_bytes)
+                .map_err(conversion_error)
+        }
+        ColumnType::Json => convert_json_array(array).map_err(conversion_error),
+        _ => {
+            let field: Field = column.into();
+            let options = CastOptions {
+                safe: false,
+                ..Default::default()
+            };
+            cast_with_options(&strip_timezone(array), field.data_type(), &options)
+                .map_err(|e| conversion_error(e.into()))
+        }
+    }
+}
+
+fn is_compatible(source: &DataType, column_type: &ColumnType) -> bool {
+    let source = match source {
+        DataType::Dictionary(_, value) => value.as_ref(),
+        s => s,
+    };
+    let is_string = matches!(
+        source,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    );
+    let is_binary = matches!(
+        source,
+        DataType::Binary
+            | DataType::LargeBinary
+            | DataType::BinaryView
+            | DataType::FixedSizeBinary(_)
+    );
+    match column_type {
+        ColumnType::String => is_string,
+        ColumnType::Int => source.is_integer(),
+        ColumnType::Int96 | ColumnType::Decimal { .. } | ColumnType::Decimal96 { .. } => {
+            source.is_integer()
+                || matches!(source, DataType::Decimal128(..) | DataType::Decimal256(..))
+        }
+        ColumnType::Float => source.is_numeric(),
+        ColumnType::Boolean => matches!(source, DataType::Boolean),
+        ColumnType::Timestamp => matches!(
+            source,
+            DataType::Timestamp(..) | DataType::Date32 | DataType::Date64
+        ),
+        ColumnType::Bytes => is_binary,
+        ColumnType::HyperLogLog(HllFlavour::Snowflake) => is_string,
+        ColumnType::HyperLogLog(_) | ColumnType::TDigest | ColumnType::ThetaSketch => is_binary,
+        ColumnType::Date => matches!(
+            source,
+            DataType::Date32 | DataType::Date64 | DataType::Timestamp(..)
+        ),
+        ColumnType::Interval => matches!(
+            source,
+            DataType::Duration(_) | DataType::Interval(IntervalUnit::MonthDayNano)
+        ),
+        ColumnType::Json => is_string,
+        ColumnType::List(element) => match source {
+            DataType::List(f) | DataType::LargeList(f) => is_compatible(f.data_type(), element),
+            _ => false,
+        },
+    }
+}
+
+/// Timestamps are stored as UTC without a timezone. Arrow keeps UTC values for zoned timestamps
+/// as well, so dropping the timezone is enough and avoids local time conversion on cast.
+fn strip_timezone(array: &ArrayRef) -> ArrayRef {
+    macro_rules! strip {
+        ($t: ty) => {
+            Arc::new(
+                array
+                    .as_primitive::<$t>()
+                    .clone()
+                    .with_timezone_opt(None::<String>),
+            )
+        };
+    }
+    match array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, Some(_)) => strip!(TimestampSecondType),
+        DataType::Timestamp(TimeUnit::Millisecond, Some(_)) => strip!(TimestampMillisecondType),
+        DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => strip!(TimestampMicrosecondType),
+        DataType::Timestamp(TimeUnit::Nanosecond, Some(_)) => strip!(TimestampNanosecondType),
+        _ => array.clone(),
+    }
+}
+
+fn convert_binary_array(
+    array: &ArrayRef,
+    parse: impl Fn(Vec<u8>) -> Result<Vec<u8>, CubeError>,
+) -> Result<ArrayRef, CubeError> {
+    let binary = cast(array, &DataType::Binary)?;
+    let binary = binary.as_binary::<i32>();
+    let mut builder = BinaryBuilder::with_capacity(binary.len(), binary.value_data().len());
+    for value in binary.iter() {
+        match value {
+            None => builder.append_null(),
+            Some(value) => builder.append_value(parse(value.to_vec())?),
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+/// JSON documents are validated and stored in compact form, same as for text formats.
+fn convert_json_array(array: &ArrayRef) -> Result<ArrayRef, CubeError> {
+    let strings = cast(array, &DataType::Utf8)?;
+    let strings = strings.as_string::<i32>();
+    let mut builder = StringBuilder::with_capacity(strings.len(), strings.value_data().len());
+    for value in strings.iter() {
+        match value {
+            None => builder.append_null(),
+            Some(value) => builder.append_value(json::parse(value)?.dump()),
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_columns;
+    use crate::table::data::rows_to_columns;
+    use crate::table::{Row, TableValue, TimestampValue};
+    use crate::util::decimal::Decimal;
+    use datafusion::arrow::array::{
+        Date32Array, Decimal128Array, Float32Array, Int32Array, Int64Array, StringArray,
+        TimestampMillisecondArray, UInt64Array,
+    };
+    use datafusion::arrow::datatypes::Schema;
+    use datafusion::arrow::ipc::writer::{FileWriter, StreamWriter};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use tempfile::NamedTempFile;
+
+    fn source_batch() -> RecordBatch {
+        RecordBatch::try_from_iter(vec![
+            (
+                "number",
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef,
+            ),
+            (
+                "fruit",
+                Arc::new(StringArray::from(vec!["apple", "banana", "orange"])) as ArrayRef,
+            ),
+            (
+                "price",
+                Arc::new(
+                    Decimal128Array::from(vec![Some(150), Some(2), None])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ) as ArrayRef,
+            ),
+            (
+                "ratio",
+                Arc::new(Float32Array::from(vec![0.5, 1.5, 2.5])) as ArrayRef,
+            ),
+            (
+                "ts",
+                Arc::new(
+                    TimestampMillisecondArray::from(vec![1577836800000, 1577836800001, 0])
+                        .with_timezone("America/Los_Angeles"),
+                ) as ArrayRef,
+            ),
+            (
+                "day",
+                Arc::new(Date32Array::from(vec![18262, 18263, 18264])) as ArrayRef,
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn table_columns() -> Vec<Column> {
+        vec![
+            Column::new("fruit".to_string(), ColumnType::String, 0),
+            Column::new("number".to_string(), ColumnType::Int, 1),
+            Column::new(
+                "price".to_string(),
+                ColumnType::Decimal {
+                    scale: 5,
+                    precision: 18,
+                },
+                2,
+            ),
+            Column::new("ratio".to_string(), ColumnType::Float, 3),
+            Column::new("ts".to_string(), ColumnType::Timestamp, 4),
+            Column::new("day".to_string(), ColumnType::Timestamp, 5),
+        ]
+    }
+
+    fn expected_columns() -> Vec<ArrayRef> {
+        let ts = |v: i64| TableValue::Timestamp(TimestampValue::new(v));
+        rows_to_columns(
+            &table_columns(),
+            &[
+                Row::new(vec![
+                    TableValue::String("apple".to_string()),
+                    TableValue::Int(1),
+                    TableValue::Decimal(Decimal::new(150000)),
+                    TableValue::Float(0.5.into()),
+                    ts(1577836800000000000),
+                    ts(1577836800000000000),
+                ]),
+                Row::new(vec![
+                    TableValue::String("banana".to_string()),
+                    TableValue::Null,
+                    TableValue::Decimal(Decimal::new(2000)),
+                    TableValue::Float(1.5.into()),
+                    ts(1577836800001000000),
+                    ts(1577923200000000000),
+                ]),
+                Row::new(vec![
+                    TableValue::String("orange".to_string()),
+                    TableValue::Int(3),
+                    TableValue::Null,
+                    TableValue::Float(2.5.into()),
+                    ts(0),
+                    ts(1578009600000000000),
+                ]),
+            ],
+        )
+    }
+
+    fn collect(format: ImportFormat, file: &NamedTempFile, batch_size: usize) -> Vec<ArrayRef> {
+        let frames = read_columns(format, file.reopen().unwrap(), table_columns(), batch_size)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        concat_columns(frames).unwrap()
+    }
+
+    #[test]
+    fn read_parquet() {
+        let batch = source_batch();
+        let file = NamedTempFile::new().unwrap();
+        let mut w = ArrowWriter::try_new(file.reopen().unwrap(), batch.schema(), None).unwrap();
+        w.write(&batch).unwrap();
+        w.close().unwrap();
+
+        assert_eq_columns!(
+            &collect(ImportFormat::Parquet, &file, 2),
+            &expected_columns()
+        );
+    }
+
+    #[test]
+    fn read_arrow_file_and_stream() {
+        let batch = source_batch();
+
+        let file = NamedTempFile::new().unwrap();
+        let mut w = FileWriter::try_new(file.reopen().unwrap(), &batch.schema()).unwrap();
+        w.write(&batch).unwrap();
+        w.finish().unwrap();
+        assert_eq_columns!(
+            &collect(ImportFormat::ArrowIPC, &file, 1),
+            &expected_columns()
+        );
+
+        let file = NamedTempFile::new().unwrap();
+        let mut w = StreamWriter::try_new(file.reopen().unwrap(), &batch.schema()).unwrap();
+        w.write(&batch).unwrap();
+        w.finish().unwrap();
+        assert_eq_columns!(
+            &collect(ImportFormat::ArrowIPC, &file, 2),
+            &expected_columns()
+        );
+    }
+
+    #[test]
+    fn missing_and_incompatible_columns_are_rejected() {
+        let batch = source_batch();
+
+        let columns = vec![Column::new("color".to_string(), ColumnType::String, 0)];
+        let err = batch_to_columns(&batch, &columns).unwrap_err().to_string();
+        assert!(
+            err.contains("'color' is not found"),
+            "unexpected error: {}",
+            err
+        );
+
+        let columns = vec![Column::new("fruit".to_string(), ColumnType::Int, 0)];
+        let err = batch_to_columns(&batch, &columns).unwrap_err().to_string();
+        assert!(err.contains("Can't import"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("n", DataType::UInt64, true)])),
+            vec![Arc::new(UInt64Array::from(vec![u64::MAX]))],
+        )
+        .unwrap();
+        let columns = vec![Column::new("n".to_string(), ColumnType::Int, 0)];
+        let err = batch_to_columns(&batch, &columns).unwrap_err().to_string();
+        assert!(err.contains("Can't convert"), "unexpected error: {}", err);
+
+        let batch = RecordBatch::try_from_iter(vec![(
+            "n",
+            Arc::new(Int64Array::from(vec![i64::MAX])) as ArrayRef,
+        )])
+        .unwrap();
+        assert!(batch_to_columns(&batch, &columns).is_ok());
+    }
+}
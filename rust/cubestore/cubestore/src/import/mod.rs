@@ -29,12 +29,14 @@ use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::cube_ext::ordfloat::OrdF64;
 use crate::import::limits::ConcurrencyLimits;
+use crate::import::ndjson::{parse_json_list, NdJsonImportParser};
 use crate::metastore::table::Table;
 use crate::metastore::{is_valid_plain_binary_hll, HllFlavour, IdRow};
 use crate::metastore::{Column, ColumnType, ImportFormat, MetaStore};
+use crate::queryplanner::tdigest::TDigest;
 use crate::queryplanner::trace_data_loaded::DataLoadedSize;
 use crate::remotefs::RemoteFs;
-use crate::sql::timestamp_from_string;
+use crate::sql::{date_from_string, interval_from_string, timestamp_from_string};
 use crate::store::ChunkDataStore;
 use crate::streaming::StreamingService;
 use crate::table::data::{append_value, create_array_builders};
@@ -44,10 +46,12 @@ use crate::util::decimal::{Decimal, Decimal96};
 use crate::util::int96::Int96;
 use crate::util::maybe_owned::MaybeOwnedStr;
 use crate::CubeError;
-use cubedatasketches::HLLDataSketch;
+use cubedatasketches::{HLLDataSketch, ThetaDataSketch};
 use tokio::time::{sleep, Duration};
 
+pub mod columnar;
 pub mod limits;
+pub mod ndjson;
 
 impl ImportFormat {
     fn open_reader(file: File, location: &str) -> Pin<Box<dyn AsyncBufRead + Send>> {
@@ -64,6 +68,25 @@ impl ImportFormat {
         columns: Vec<Column>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Option<Row>, CubeError>> + Send + 'a>>, CubeError>
     {
+        if let ImportFormat::NDJSON { .. } = self {
+            let num_columns = columns.len();
+            let mut parser = NdJsonImportParser::new(self, columns)?;
+            let lines_stream = CsvLineStream::new(reader, true);
+            let rows = lines_stream.map(move |line| -> Result<Option<Row>, CubeError> {
+                let line = line?;
+                let mut row = vec![TableValue::Null; num_columns];
+                let is_data_row = parser.visit_line(line.as_str(), |insert_pos, _, value| {
+                    row[insert_pos] = value;
+                    Ok(())
+                })?;
+                Ok(if is_data_row {
+                    Some(Row::new(row))
+                } else {
+                    None
+                })
+            });
+            return Ok(rows.boxed());
+        }
         let mut parser = CsvImportParser::new(self, columns)?;
         let disable_quoting = parser.disable_quoting;
         let num_columns = parser.columns.len();
@@ -92,10 +115,15 @@ impl ImportFormat {
     /// closely enough to drive the split; exactness is unnecessary for a threshold.
     fn estimate_arrow_value_size(column_type: &ColumnType, value: Option<&str>) -> usize {
         match column_type {
-            ColumnType::String | ColumnType::Bytes | ColumnType::HyperLogLog(_) => {
-                value.map_or(0, |v| v.len()) + 4
-            }
-            ColumnType::Int | ColumnType::Timestamp | ColumnType::Float => 8,
+            ColumnType::String
+            | ColumnType::Bytes
+            | ColumnType::HyperLogLog(_)
+            | ColumnType::TDigest
+            | ColumnType::ThetaSketch
+            | ColumnType::Json
+            | ColumnType::List(_) => value.map_or(0, |v| v.len()) + 4,
+            ColumnType::Int | ColumnType::Timestamp | ColumnType::Float | ColumnType::Interval => 8,
+            ColumnType::Date => 4,
             ColumnType::Int96 | ColumnType::Decimal { .. } | ColumnType::Decimal96 { .. } => 16,
             ColumnType::Boolean => 1,
         }
@@ -125,28 +153,65 @@ impl ImportFormat {
                 let hll = HllSketch::read_snowflake(value)?;
                 TableValue::Bytes(hll.write())
             }
-            ColumnType::HyperLogLog(HllFlavour::Postgres) => {
-                let data = parse_binary_data(value)?;
-                let hll = HllSketch::read_hll_storage_spec(&data)?;
-                TableValue::Bytes(hll.write())
-            }
-            ColumnType::HyperLogLog(f @ (HllFlavour::Airlift | HllFlavour::ZetaSketch)) => {
-                let data = parse_binary_data(value)?;
-                is_valid_plain_binary_hll(&data, *f)?;
-                TableValue::Bytes(data)
-            }
-            ColumnType::HyperLogLog(HllFlavour::DataSketches) => {
-                let data = parse_binary_data(value)?;
-                let hll = HLLDataSketch::read(&data)?;
-                TableValue::Bytes(hll.write())
-            }
+            ColumnType::HyperLogLog(f) => TableValue::Bytes(ImportFormat::parse_hll_bytes(
+                *f,
+                parse_binary_data(value)?,
+            )?),
+            ColumnType::TDigest => TableValue::Bytes(ImportFormat::parse_tdigest_bytes(
+                parse_binary_data(value)?,
+            )?),
+            ColumnType::ThetaSketch => TableValue::Bytes(ImportFormat::parse_theta_sketch_bytes(
+                parse_binary_data(value)?,
+            )?),
             ColumnType::Timestamp => TableValue::Timestamp(timestamp_from_string(value)?),
             ColumnType::Float => TableValue::Float(OrdF64(value.parse::<f64>()?)),
             ColumnType::Boolean => {
                 TableValue::Boolean(value.to_lowercase() == "true" || value.to_lowercase() == "t")
             }
+            ColumnType::Date => TableValue::Date(date_from_string(value)?),
+            ColumnType::Interval => TableValue::Interval(interval_from_string(value)?),
+            ColumnType::Json => TableValue::String(parse_json_text(value)?.dump()),
+            ColumnType::List(element) => parse_json_list(element, &parse_json_text(value)?)?,
+        })
+    }
+
+    /// Validates a binary HLL sketch and converts it into the representation stored in
+    /// partitions. Snowflake sketches are JSON documents and are expected as UTF-8 bytes.
+    pub(crate) fn parse_hll_bytes(
+        flavour: HllFlavour,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, CubeError> {
+        Ok(match flavour {
+            HllFlavour::Snowflake => HllSketch::read_snowflake(&String::from_utf8(data)?)?.write(),
+            HllFlavour::Postgres => HllSketch::read_hll_storage_spec(&data)?.write(),
+            HllFlavour::Airlift | HllFlavour::ZetaSketch => {
+                is_valid_plain_binary_hll(&data, flavour)?;
+                data
+            }
+            HllFlavour::DataSketches => HLLDataSketch::read(&data)?.write(),
         })
     }
+
+    /// Validates a serialized t-digest sketch, sketches are stored as is.
+    pub(crate) fn parse_tdigest_bytes(data: Vec<u8>) -> Result<Vec<u8>, CubeError> {
+        TDigest::read(&data)?;
+        Ok(data)
+    }
+
+    /// Validates a serialized theta sketch, sketches are stored as is.
+    pub(crate) fn parse_theta_sketch_bytes(data: Vec<u8>) -> Result<Vec<u8>, CubeError> {
+        ThetaDataSketch::read(&data)?;
+        Ok(data)
+    }
+
+    /// Columnar formats carry typed data and are read batch by batch instead of line by line.
+    pub fn is_columnar(&self) -> bool {
+        matches!(self, ImportFormat::Parquet | ImportFormat::ArrowIPC)
+    }
+}
+
+fn parse_json_text(value: &str) -> Result<json::JsonValue, CubeError> {
+    json::parse(value).map_err(|e| CubeError::user(format!("Malformed JSON '{}': {}", value, e)))
 }
 
 pub(crate) fn parse_decimal(value: &str, scale: u8) -> Result<Decimal, CubeError> {
@@ -431,6 +496,12 @@ impl CsvImportParser {
             ImportFormat::CSVOptions {
                 delimiter, quote, ..
             } => (delimiter.unwrap_or(','), quote.is_none()),
+            ImportFormat::Parquet | ImportFormat::ArrowIPC | ImportFormat::NDJSON { .. } => {
+                return Err(CubeError::internal(format!(
+                    "{:?} is not a CSV import format",
+                    format
+                )))
+            }
         };
         if delimiter as u16 > 255 {
             return Err(CubeError::user(format!(
@@ -549,6 +620,49 @@ fn append_csv_value(
     Ok(())
 }
 
+/// Parses lines of a row-based import format straight into the column builders, see
+/// `ImportServiceImpl::import_lines`.
+trait LineImportParser: Send {
+    /// Appends values of every table column to `builders`. Returns `None` for lines without a
+    /// data row, otherwise the estimated size of appended values in bytes if `estimate_size` is
+    /// set and zero if it isn't.
+    fn append_line(
+        &mut self,
+        line: &str,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        estimate_size: bool,
+    ) -> Result<Option<usize>, CubeError>;
+}
+
+impl LineImportParser for CsvImportParser {
+    fn append_line(
+        &mut self,
+        line: &str,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        estimate_size: bool,
+    ) -> Result<Option<usize>, CubeError> {
+        let mut estimated_bytes = 0;
+        let is_data_row = self.visit_line(line, |insert_pos, column, value| {
+            let builder = builders[insert_pos].as_mut();
+            if estimate_size {
+                estimated_bytes +=
+                    ImportFormat::estimate_arrow_value_size(column.get_column_type(), value);
+            }
+            match value {
+                None => {
+                    append_value(builder, column.get_column_type(), &TableValue::Null);
+                }
+                Some(value) => {
+                    append_csv_value(builder, column, value)
+                        .map_err(|e| parse_value_error(value, column, e))?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(is_data_row.then_some(estimated_bytes))
+    }
+}
+
 #[async_trait]
 pub trait LocationsValidator: DIService + Send + Sync {
     async fn validate(&self, locations: &Vec<String>) -> Result<(), CubeError>;
@@ -734,6 +848,34 @@ impl ImportServiceImpl {
         Ok((file, size, path))
     }
 
+    /// Parquet and Arrow IPC readers need random access to the file, so gzipped columnar files
+    /// are unpacked into the temp dir before reading.
+    async fn decompress_to_temp_file(
+        &self,
+        file: File,
+        table_id: u64,
+        temp_dir: &Path,
+    ) -> Result<(File, TempPath), CubeError> {
+        let (out, path) = tempfile::Builder::new()
+            .prefix(&table_id.to_string())
+            .tempfile_in(temp_dir)
+            .map_err(|e| {
+                CubeError::internal(format!(
+                    "Open tempfile in {}: {}",
+                    temp_dir.to_str().unwrap_or("<invalid>"),
+                    e
+                ))
+            })?
+            .into_parts();
+        let mut out = File::from_std(out);
+
+        let mut decoder = GzipDecoder::new(BufReader::new(file));
+        tokio::io::copy(&mut decoder, &mut out).await?;
+        out.seek(SeekFrom::Start(0)).await?;
+
+        Ok((out, path))
+    }
+
     async fn download_temp_file(&self, location: &str) -> Result<File, CubeError> {
         let to_download = LocationHelper::temp_uploads_path(location);
         // TODO check file size
@@ -774,9 +916,64 @@ impl ImportServiceImpl {
         let (file, tmp_path) = self
             .resolve_location(location, table.get_id(), &temp_dir)
             .await?;
+        if format.is_columnar() {
+            let (file, tmp_path) = if location.contains(".gz") {
+                let (file, path) = self
+                    .decompress_to_temp_file(file, table.get_id(), &temp_dir)
+                    .await?;
+                mem::drop(tmp_path);
+                (file, Some(path))
+            } else {
+                (file, tmp_path)
+            };
+            return self
+                .do_columnar_import(table, format, file, tmp_path, data_loaded_size)
+                .await;
+        }
         let reader = ImportFormat::open_reader(file, location);
-        let mut parser = CsvImportParser::new(&format, table.get_row().get_columns().clone())?;
+        if let ImportFormat::NDJSON { .. } = format {
+            return self
+                .do_ndjson_import(table, &format, reader, tmp_path, data_loaded_size)
+                .await;
+        }
+        let parser = CsvImportParser::new(&format, table.get_row().get_columns().clone())?;
         let disable_quoting = parser.disable_quoting;
+        self.import_lines(
+            table,
+            parser,
+            reader,
+            disable_quoting,
+            tmp_path,
+            data_loaded_size,
+        )
+        .await
+    }
+
+    async fn do_ndjson_import(
+        &self,
+        table: &IdRow<Table>,
+        format: &ImportFormat,
+        reader: Pin<Box<dyn AsyncBufRead + Send>>,
+        tmp_path: Option<TempPath>,
+        data_loaded_size: Option<Arc<DataLoadedSize>>,
+    ) -> Result<(), CubeError> {
+        let parser = NdJsonImportParser::new(format, table.get_row().get_columns().clone())?;
+        // JSON strings can't contain raw newlines, so lines are split without quote tracking.
+        self.import_lines(table, parser, reader, true, tmp_path, data_loaded_size)
+            .await
+    }
+
+    /// Splits the input into lines, parses them into column builders and ingests the rows in
+    /// batches of the WAL split thresholds.
+    async fn import_lines(
+        &self,
+        table: &IdRow<Table>,
+        mut parser: impl LineImportParser,
+        reader: Pin<Box<dyn AsyncBufRead + Send>>,
+        disable_quoting: bool,
+        tmp_path: Option<TempPath>,
+        data_loaded_size: Option<Arc<DataLoadedSize>>,
+    ) -> Result<(), CubeError> {
         let mut lines = Box::pin(CsvLineStream::new(reader, disable_quoting));
 
         let mut ingestion = Ingestion::new(
@@ -784,6 +981,7 @@ impl ImportServiceImpl {
             self.chunk_store.clone(),
             self.limits.clone(),
             table.clone(),
+            self.config_obj.encryption_key_id().clone(),
         );
 
         let finish = |builders: Vec<Box<dyn ArrayBuilder>>| {
@@ -801,25 +999,9 @@ impl ImportServiceImpl {
         let mut estimated_bytes = 0;
         while let Some(line) = lines.next().await {
             let line = line?;
-            let is_data_row = parser.visit_line(line.as_str(), |insert_pos, column, value| {
-                let builder = builders[insert_pos].as_mut();
-                if size_threshold.is_some() {
-                    estimated_bytes +=
-                        ImportFormat::estimate_arrow_value_size(column.get_column_type(), value);
-                }
-                match value {
-                    None => {
-                        append_value(builder, column.get_column_type(), &TableValue::Null);
-                    }
-                    Some(value) => {
-                        append_csv_value(builder, column, value)
-                            .map_err(|e| parse_value_error(value, column, e))?;
-                    }
-                }
-                Ok(())
-            })?;
-            if !is_data_row {
-                continue;
+            match parser.append_line(line.as_str(), &mut builders, size_threshold.is_some())? {
+                None => continue,
+                Some(bytes) => estimated_bytes += bytes,
             }
             num_rows += 1;
 
@@ -846,6 +1028,79 @@ impl ImportServiceImpl {
         ingestion.wait_completion().await
     }
 
+    /// Parquet and Arrow IPC files are decoded on a blocking thread and handed over as typed
+    /// columns, so neither line splitting nor per-value string parsing is involved.
+    async fn do_columnar_import(
+        &self,
+        table: &IdRow<Table>,
+        format: ImportFormat,
+        file: File,
+        tmp_path: Option<TempPath>,
+        data_loaded_size: Option<Arc<DataLoadedSize>>,
+    ) -> Result<(), CubeError> {
+        let mut ingestion = Ingestion::new(
+            self.meta_store.clone(),
+            self.chunk_store.clone(),
+            self.limits.clone(),
+            table.clone(),
+            self.config_obj.encryption_key_id().clone(),
+        );
+
+        let table_cols = table.get_row().get_columns().clone();
+        let row_threshold = self.config_obj.wal_split_threshold() as usize;
+        let size_threshold = self
+            .config_obj
+            .wal_split_size_threshold_bytes()
+            .map(|v| v as usize);
+
+        let file = file.into_std().await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(2);
+        let read_job = cube_ext::spawn_blocking(move || -> Result<(), CubeError> {
+            for columns in columnar::read_columns(format, file, table_cols, row_threshold)? {
+                let columns = columns?;
+                if tx.blocking_send(columns).is_err() {
+                    // Ingestion has failed and dropped the receiver.
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        let mut pending = Vec::new();
+        let mut num_rows = 0;
+        let mut estimated_bytes = 0;
+        while let Some(columns) = rx.recv().await {
+            num_rows += columns.first().map_or(0, |c: &ArrayRef| c.len());
+            estimated_bytes += columns_vec_buffer_size(&columns);
+            pending.push(columns);
+
+            let over_size_threshold = size_threshold.map_or(false, |t| estimated_bytes >= t);
+            if num_rows >= row_threshold || over_size_threshold {
+                let builded_rows = columnar::concat_columns(mem::take(&mut pending))?;
+                num_rows = 0;
+                estimated_bytes = 0;
+
+                if let Some(data_loaded_size) = &data_loaded_size {
+                    data_loaded_size.add(columns_vec_buffer_size(&builded_rows));
+                }
+
+                ingestion.queue_data_frame(builded_rows).await?;
+            }
+        }
+        read_job.await??;
+
+        mem::drop(tmp_path);
+
+        if !pending.is_empty() {
+            let builded_rows = columnar::concat_columns(pending)?;
+            if let Some(data_loaded_size) = &data_loaded_size {
+                data_loaded_size.add(columns_vec_buffer_size(&builded_rows));
+            }
+            ingestion.queue_data_frame(builded_rows).await?;
+        }
+        ingestion.wait_completion().await
+    }
+
     fn estimate_rows(location: &str, size: Option<u64>) -> u64 {
         if let Some(size) = size {
             let uncompressed_size = if location.contains(".gz") {
@@ -881,7 +1136,8 @@ impl ImportService for ImportServiceImpl {
                 table
             )))?;
         for location in locations.iter() {
-            self.do_import(&table, *format, location, None).await?;
+            self.do_import(&table, format.clone(), location, None)
+                .await?;
         }
 
         for location in locations.iter() {
@@ -923,7 +1179,7 @@ impl ImportService for ImportServiceImpl {
         if Table::is_stream_location(location) {
             self.streaming_service.stream_table(table, location).await?;
         } else {
-            self.do_import(&table, *format, location, data_loaded_size.clone())
+            self.do_import(&table, format.clone(), location, data_loaded_size.clone())
                 .await?;
             self.drop_temp_uploads(&location).await?;
         }
@@ -1019,6 +1275,8 @@ pub struct Ingestion {
     chunk_store: Arc<dyn ChunkDataStore>,
     limits: Arc<ConcurrencyLimits>,
     table: IdRow<Table>,
+    /// Key the remote fs encrypts uploaded chunks with.
+    encryption_key_id: Option<String>,
 
     partition_jobs: Vec<JoinHandle<Result<(), CubeError>>>,
 }
@@ -1029,12 +1287,14 @@ impl Ingestion {
         chunk_store: Arc<dyn ChunkDataStore>,
         limits: Arc<ConcurrencyLimits>,
         table: IdRow<Table>,
+        encryption_key_id: Option<String>,
     ) -> Ingestion {
         Ingestion {
             meta_store,
             chunk_store,
             limits,
             table,
+            encryption_key_id,
             partition_jobs: Vec::new(),
         }
     }
@@ -1048,6 +1308,7 @@ impl Ingestion {
         let table_id = self.table.get_id();
         // TODO In fact it should be only for inserts. Batch imports should still go straight to disk.
         let in_memory = self.table.get_row().in_memory_ingest();
+        let encryption_key_id = self.encryption_key_id.clone();
         self.partition_jobs.push(cube_ext::spawn(async move {
             let new_chunks = chunk_store
                 .partition_data(table_id, rows, &columns, in_memory)
@@ -1065,7 +1326,7 @@ impl Ingestion {
                 })
                 .collect();
             meta_store
-                .activate_chunks(table_id, new_chunk_ids?, None)
+                .activate_chunks(table_id, new_chunk_ids?, None, encryption_key_id)
                 .await
         }));
 
@@ -1089,7 +1350,7 @@ mod tests {
     };
     use crate::metastore::{Column, ColumnType, HllFlavour, ImportFormat};
     use crate::table::data::{append_value, create_array_builders, rows_to_columns};
-    use crate::table::{Row, TableValue, TimestampValue};
+    use crate::table::{DateValue, IntervalValue, Row, TableValue, TimestampValue};
     use crate::util::decimal::{Decimal, Decimal96};
     use crate::util::int96::Int96;
     use cubehll::HllSketch;
@@ -1367,6 +1628,118 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn parse_dates_and_intervals() {
+        let data = indoc! {"
+            2020-01-02,1 day 02:03:04.5
+            1969-12-31T00:00:00.000Z,-3 days -00:00:01
+            ,PT1H30M
+        "};
+        let columns = vec![
+            Column::new("A".to_string(), ColumnType::Date, 0),
+            Column::new("B".to_string(), ColumnType::Interval, 1),
+        ];
+        let rows = collect_rows(
+            ImportFormat::CSVNoHeader,
+            Box::pin(BufReader::new(data.as_bytes())),
+            columns.clone(),
+        )
+        .await;
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    TableValue::Date(DateValue::new(18263)),
+                    TableValue::Interval(IntervalValue::new(93_784_500_000)),
+                ]),
+                Row::new(vec![
+                    TableValue::Date(DateValue::new(-1)),
+                    TableValue::Interval(IntervalValue::new(-259_201_000_000)),
+                ]),
+                Row::new(vec![
+                    TableValue::Null,
+                    TableValue::Interval(IntervalValue::new(5_400_000_000)),
+                ]),
+            ]
+        );
+        assert_eq!(rows[0].values()[0].to_string(), "2020-01-02");
+        assert_eq!(rows[0].values()[1].to_string(), "1 day 02:03:04.5");
+        assert_eq!(rows[1].values()[1].to_string(), "-3 days -00:00:01");
+        assert_eq!(rows[2].values()[1].to_string(), "01:30:00");
+
+        for bad in ["1 month", "P1Y", "2 fortnights", "01:60:00", "yesterday"] {
+            let column = &columns[1];
+            assert!(
+                ImportFormat::parse_column_value_str(column, bad).is_err(),
+                "expected error for {:?}",
+                bad
+            );
+        }
+        assert!(ImportFormat::parse_column_value_str(&columns[0], "2020-13-01").is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_lists_and_json() {
+        let data = indoc! {r#"
+            "[""a"", ""b""]","[1, null, 3]","{ ""a"": { ""b"": [1, 2] } }"
+            [],,"""text"""
+        "#};
+        let columns = vec![
+            Column::new(
+                "A".to_string(),
+                ColumnType::List(Box::new(ColumnType::String)),
+                0,
+            ),
+            Column::new(
+                "B".to_string(),
+                ColumnType::List(Box::new(ColumnType::Int)),
+                1,
+            ),
+            Column::new("C".to_string(), ColumnType::Json, 2),
+        ];
+        let rows = collect_rows(
+            ImportFormat::CSVNoHeader,
+            Box::pin(BufReader::new(data.as_bytes())),
+            columns.clone(),
+        )
+        .await;
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    TableValue::List(vec![
+                        TableValue::String("a".to_string()),
+                        TableValue::String("b".to_string()),
+                    ]),
+                    TableValue::List(vec![
+                        TableValue::Int(1),
+                        TableValue::Null,
+                        TableValue::Int(3),
+                    ]),
+                    TableValue::String(r#"{"a":{"b":[1,2]}}"#.to_string()),
+                ]),
+                Row::new(vec![
+                    TableValue::List(vec![]),
+                    TableValue::Null,
+                    TableValue::String(r#""text""#.to_string()),
+                ]),
+            ]
+        );
+        assert_eq!(rows[0].values()[1].to_string(), "[1,null,3]");
+
+        for (column, bad) in [
+            (&columns[1], "[1, \"x\"]"),
+            (&columns[1], "1"),
+            (&columns[2], "{"),
+        ] {
+            assert!(
+                ImportFormat::parse_column_value_str(column, bad).is_err(),
+                "expected error for {:?}",
+                bad
+            );
+        }
+    }
+
     fn quoted_columns() -> Vec<Column> {
         vec![
             Column::new("A".to_string(), ColumnType::String, 0),
@@ -0,0 +1,384 @@
+use datafusion::arrow::array::ArrayBuilder;
+use json::JsonValue;
+
+use crate::import::LineImportParser;
+use crate::metastore::{Column, ColumnType, HllFlavour, ImportFormat};
+use crate::streaming::parse_json_value;
+use crate::table::data::append_value;
+use crate::table::TableValue;
+use crate::CubeError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses a column path like `$.user.addresses[0].country`. The leading `$.` is optional.
+pub fn parse_json_path(path: &str) -> Result<Vec<JsonPathSegment>, CubeError> {
+    let bad_path = || CubeError::user(format!("Bad JSON path '{}'", path));
+    let trimmed = path.strip_prefix('$').unwrap_or(path);
+    let trimmed = trimmed.strip_prefix('.').unwrap_or(trimmed);
+    if trimmed.is_empty() {
+        return Err(bad_path());
+    }
+    let mut segments = Vec::new();
+    for part in trimmed.split('.') {
+        let (key, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !key.is_empty() {
+            segments.push(JsonPathSegment::Key(key.to_string()));
+        } else if indexes.is_empty() {
+            return Err(bad_path());
+        }
+        while !indexes.is_empty() {
+            let close = indexes.find(']').ok_or_else(bad_path)?;
+            if !indexes.starts_with('[') {
+                return Err(bad_path());
+            }
+            let index = indexes[1..close].parse::<usize>().map_err(|_| bad_path())?;
+            segments.push(JsonPathSegment::Index(index));
+            indexes = &indexes[close + 1..];
+        }
+    }
+    Ok(segments)
+}
+
+/// Validates the `json_paths` of an NDJSON import against the table columns and returns the path
+/// of every column. Columns without a path are read from their top-level key.
+pub fn resolve_column_paths(
+    column_paths: &[(String, String)],
+    column_names: &[&str],
+) -> Result<Vec<Vec<JsonPathSegment>>, CubeError> {
+    for (name, _) in column_paths.iter() {
+        if !column_names.contains(&name.as_str()) {
+            return Err(CubeError::user(format!(
+                "JSON path is specified for unknown column '{}'",
+                name
+            )));
+        }
+    }
+    column_names
+        .iter()
+        .map(|c| match column_paths.iter().find(|(name, _)| name == c) {
+            Some((_, path)) => parse_json_path(path),
+            None => Ok(vec![JsonPathSegment::Key(c.to_string())]),
+        })
+        .collect()
+}
+
+fn lookup<'a>(value: &'a JsonValue, path: &[JsonPathSegment]) -> &'a JsonValue {
+    // `json` indexing yields `Null` for missing keys, out of range indexes and non-containers.
+    path.iter().fold(value, |v, segment| match segment {
+        JsonPathSegment::Key(key) => &v[key.as_str()],
+        JsonPathSegment::Index(index) => &v[*index],
+    })
+}
+
+/// Maps NDJSON lines onto table columns. Every line must be a JSON object; columns are read from
+/// their top-level key unless the import format gives them a path. Missing values are nulls.
+pub struct NdJsonImportParser {
+    columns: Vec<Column>,
+    paths: Vec<Vec<JsonPathSegment>>,
+    line_number: usize,
+}
+
+impl NdJsonImportParser {
+    pub fn new(format: &ImportFormat, columns: Vec<Column>) -> Result<Self, CubeError> {
+        let column_paths = match format {
+            ImportFormat::NDJSON { column_paths } => column_paths,
+            f => {
+                return Err(CubeError::internal(format!(
+                    "{:?} is not a JSON import format",
+                    f
+                )))
+            }
+        };
+        let column_names = columns
+            .iter()
+            .map(|c| c.get_name().as_str())
+            .collect::<Vec<_>>();
+        let paths = resolve_column_paths(column_paths, &column_names)?;
+        Ok(Self {
+            columns,
+            paths,
+            line_number: 0,
+        })
+    }
+
+    /// Returns `false` for blank lines, `true` for a data row. The visitor is called once per
+    /// table column in table order.
+    pub fn visit_line<F>(&mut self, line: &str, mut visit: F) -> Result<bool, CubeError>
+    where
+        F: FnMut(usize, &Column, TableValue) -> Result<(), CubeError>,
+    {
+        self.line_number += 1;
+        if line.trim().is_empty() {
+            return Ok(false);
+        }
+        let object = json::parse(line).map_err(|e| {
+            CubeError::user(format!(
+                "Malformed JSON at line {}: {}",
+                self.line_number, e
+            ))
+        })?;
+        if !object.is_object() {
+            return Err(CubeError::user(format!(
+                "JSON object expected at line {} but found: {}",
+                self.line_number,
+                object.dump()
+            )));
+        }
+        for (i, (column, path)) in self.columns.iter().zip(self.paths.iter()).enumerate() {
+            let value = lookup(&object, path);
+            let value = parse_ndjson_value(column, value).map_err(|e| {
+                CubeError::user(format!(
+                    "Can't parse '{}' column value for '{}' column at line {}: {}",
+                    value.dump(),
+                    column.get_name(),
+                    self.line_number,
+                    e
+                ))
+            })?;
+            visit(i, column, value)?;
+        }
+        Ok(true)
+    }
+}
+
+impl LineImportParser for NdJsonImportParser {
+    fn append_line(
+        &mut self,
+        line: &str,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        estimate_size: bool,
+    ) -> Result<Option<usize>, CubeError> {
+        let mut estimated_bytes = 0;
+        let is_data_row = self.visit_line(line, |insert_pos, column, value| {
+            if estimate_size {
+                estimated_bytes += match &value {
+                    TableValue::String(s) => s.len() + 4,
+                    TableValue::Bytes(b) => b.len() + 4,
+                    _ => ImportFormat::estimate_arrow_value_size(column.get_column_type(), None),
+                };
+            }
+            append_value(
+                builders[insert_pos].as_mut(),
+                column.get_column_type(),
+                &value,
+            );
+            Ok(())
+        })?;
+        Ok(is_data_row.then_some(estimated_bytes))
+    }
+}
+
+fn parse_ndjson_value(column: &Column, value: &JsonValue) -> Result<TableValue, CubeError> {
+    match (column.get_column_type(), value) {
+        (_, JsonValue::Null) => Ok(TableValue::Null),
+        (ColumnType::Json, value) => Ok(TableValue::String(value.dump())),
+        (ColumnType::List(element), JsonValue::Array(_)) => parse_json_list(element, value),
+        // Quoted numbers and types `parse_json_value` doesn't cover are accepted in the same
+        // text representation as in CSV.
+        (
+            ColumnType::Int
+            | ColumnType::Int96
+            | ColumnType::Decimal { .. }
+            | ColumnType::Decimal96 { .. }
+            | ColumnType::Float
+            | ColumnType::Bytes
+            | ColumnType::HyperLogLog(_)
+            | ColumnType::TDigest
+            | ColumnType::ThetaSketch
+            | ColumnType::List(_),
+            JsonValue::Short(_) | JsonValue::String(_),
+        ) => ImportFormat::parse_column_value_str(column, value.as_str().unwrap()),
+        (ColumnType::Int96 | ColumnType::Decimal96 { .. }, JsonValue::Number(_)) => {
+            ImportFormat::parse_column_value_str(column, &value.dump())
+        }
+        (ColumnType::HyperLogLog(HllFlavour::Snowflake), JsonValue::Object(_)) => {
+            ImportFormat::parse_column_value_str(column, &value.dump())
+        }
+        _ => parse_json_value(column, value),
+    }
+}
+
+/// Converts a JSON array into a list value. Elements are accepted in the same forms as NDJSON
+/// column values.
+pub fn parse_json_list(element: &ColumnType, value: &JsonValue) -> Result<TableValue, CubeError> {
+    let items = match value {
+        JsonValue::Array(items) => items,
+        v => {
+            return Err(CubeError::user(format!(
+                "JSON array expected but found: {}",
+                v.dump()
+            )))
+        }
+    };
+    let element = Column::new("element".to_string(), element.clone(), 0);
+    Ok(TableValue::List(
+        items
+            .iter()
+            .map(|v| parse_ndjson_value(&element, v))
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::Row;
+    use crate::util::decimal::{Decimal, Decimal96};
+    use indoc::indoc;
+    use tokio::io::BufReader;
+    use tokio_stream::StreamExt;
+
+    async fn collect_rows(
+        format: ImportFormat,
+        data: &'static str,
+        columns: Vec<Column>,
+    ) -> Result<Vec<Row>, CubeError> {
+        let mut row_stream = format
+            .row_stream_from_reader(Box::pin(BufReader::new(data.as_bytes())), columns)
+            .unwrap();
+        let mut rows = vec![];
+        while let Some(row) = row_stream.next().await {
+            if let Some(row) = row? {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+
+    #[test]
+    fn json_paths() {
+        assert_eq!(
+            parse_json_path("$.user.addresses[0][1].country").unwrap(),
+            vec![
+                JsonPathSegment::Key("user".to_string()),
+                JsonPathSegment::Key("addresses".to_string()),
+                JsonPathSegment::Index(0),
+                JsonPathSegment::Index(1),
+                JsonPathSegment::Key("country".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_json_path("id").unwrap(),
+            vec![JsonPathSegment::Key("id".to_string())]
+        );
+        for bad in ["", "$", "a..b", "a[x]", "a[1", "a[1]b"] {
+            assert!(
+                parse_json_path(bad).is_err(),
+                "expected error for {:?}",
+                bad
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn read_nested_values() {
+        let data = indoc! {r#"
+            {"id": 1, "user": {"name": "alice", "tags": ["a", "b"]}, "amount": 1.5, "big": "123456789012345"}
+
+            {"id": 2, "user": {"name": null}, "amount": "2.25", "big": 42}
+            {"id": 3}
+        "#};
+        let columns = vec![
+            Column::new("id".to_string(), ColumnType::Int, 0),
+            Column::new("name".to_string(), ColumnType::String, 1),
+            Column::new("first_tag".to_string(), ColumnType::String, 2),
+            Column::new(
+                "amount".to_string(),
+                ColumnType::Decimal {
+                    scale: 5,
+                    precision: 18,
+                },
+                3,
+            ),
+            Column::new(
+                "big".to_string(),
+                ColumnType::Decimal96 {
+                    scale: 2,
+                    precision: 27,
+                },
+                4,
+            ),
+        ];
+        let format = ImportFormat::NDJSON {
+            column_paths: vec![
+                ("name".to_string(), "$.user.name".to_string()),
+                ("first_tag".to_string(), "user.tags[0]".to_string()),
+            ],
+        };
+        let rows = collect_rows(format, data, columns).await.unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    TableValue::Int(1),
+                    TableValue::String("alice".to_string()),
+                    TableValue::String("a".to_string()),
+                    TableValue::Decimal(Decimal::new(150000)),
+                    TableValue::Decimal96(Decimal96::new(12345678901234500)),
+                ]),
+                Row::new(vec![
+                    TableValue::Int(2),
+                    TableValue::Null,
+                    TableValue::Null,
+                    TableValue::Decimal(Decimal::new(225000)),
+                    TableValue::Decimal96(Decimal96::new(4200)),
+                ]),
+                Row::new(vec![
+                    TableValue::Int(3),
+                    TableValue::Null,
+                    TableValue::Null,
+                    TableValue::Null,
+                    TableValue::Null,
+                ]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_have_line_numbers() {
+        let columns = vec![Column::new("ts".to_string(), ColumnType::Timestamp, 0)];
+        let format = ImportFormat::NDJSON {
+            column_paths: vec![],
+        };
+
+        let data = "{\"ts\": \"2020-01-01T00:00:00.000Z\"}\n\n{\"ts\": \"yesterday\"}\n";
+        let err = collect_rows(format.clone(), data, columns.clone())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("'ts' column at line 3"),
+            "unexpected error: {}",
+            err
+        );
+
+        let err = collect_rows(format.clone(), "{\"ts\": \n", columns.clone())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Malformed JSON at line 1"),
+            "unexpected error: {}",
+            err
+        );
+
+        let err = collect_rows(format, "[1, 2]\n", columns.clone())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("JSON object expected at line 1"),
+            "unexpected error: {}",
+            err
+        );
+
+        let format = ImportFormat::NDJSON {
+            column_paths: vec![("unknown".to_string(), "a.b".to_string())],
+        };
+        assert!(NdJsonImportParser::new(&format, columns).is_err());
+    }
+}
@@ -0,0 +1,614 @@
+use crate::cachestore::CacheStore;
+use crate::metastore::MetaStore;
+use crate::remotefs::encrypted::stored_file_size;
+use crate::remotefs::RemoteFs;
+use crate::CubeError;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::fs;
+
+pub const BACKUP_MANIFEST_VERSION: u32 = 1;
+
+const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+
+/// Restored snapshots are loaded after the restart, until then GC of the running metastore can
+/// remove data files they reference. An empty file `<prefix><location>` marks such a restore, its
+/// data files are checked again at startup, see `complete_pending_restores`.
+const RESTORE_PENDING_PREFIX: &str = "backup-restore-pending/";
+
+/// Files of a RocksDB check point, which were copied into a backup bundle.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackupStoreSnapshot {
+    pub snapshot_id: u128,
+    pub files: Vec<String>,
+}
+
+/// Partition or chunk file referenced by the metastore snapshot of a backup.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackupDataFile {
+    pub path: String,
+    /// Plaintext size, as stored in the metastore
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub encryption_key_id: Option<String>,
+}
+
+/// Self-describing content of a backup bundle, stored as `<location>/manifest.json`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub metastore: BackupStoreSnapshot,
+    pub cachestore: BackupStoreSnapshot,
+    /// Data files are copied into the bundle, see `BackupManifest::data_file_location`. GC
+    /// removes files of compacted partitions and chunks, restore puts such files back.
+    pub data_files: Vec<BackupDataFile>,
+}
+
+impl BackupManifest {
+    pub fn manifest_path(location: &str) -> String {
+        format!("{}/{}", location, BACKUP_MANIFEST_FILE)
+    }
+
+    pub fn store_location(location: &str, store_name: &str) -> String {
+        format!("{}/{}", location, store_name)
+    }
+
+    /// Copies don't end with `.parquet`, otherwise the remote fs cleanup would remove them as
+    /// files unknown to the metastore.
+    pub fn data_file_location(location: &str, path: &str) -> String {
+        format!("{}/data/{}.bak", location, path)
+    }
+}
+
+/// Backup location is a path on the remote fs, which must not overlap with snapshots.
+pub fn normalize_backup_location(location: &str) -> Result<String, CubeError> {
+    let location = location.trim().trim_end_matches('/');
+    if location.is_empty() {
+        return Err(CubeError::user(
+            "Backup location must not be empty".to_string(),
+        ));
+    }
+
+    if location.starts_with('/')
+        || location.contains("://")
+        || location
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        return Err(CubeError::user(format!(
+            "Backup location must be a relative path on the remote fs, actual: '{}'",
+            location
+        )));
+    }
+
+    if location.starts_with("metastore-") || location.starts_with("cachestore-") {
+        return Err(CubeError::user(format!(
+            "Backup location '{}' conflicts with metastore and cachestore snapshots",
+            location
+        )));
+    }
+
+    if format!("{}/", location).starts_with(RESTORE_PENDING_PREFIX) {
+        return Err(CubeError::user(format!(
+            "Backup location '{}' conflicts with markers of pending restores",
+            location
+        )));
+    }
+
+    Ok(location.to_string())
+}
+
+/// Snapshots of the metastore and the cachestore are taken one after another, not at a single
+/// point in time: changes of the cachestore in between are only in its snapshot.
+pub async fn create_backup(
+    meta_store: Arc<dyn MetaStore>,
+    cache_store: Arc<dyn CacheStore>,
+    remote_fs: Arc<dyn RemoteFs>,
+    location: String,
+) -> Result<BackupManifest, CubeError> {
+    let location = normalize_backup_location(&location)?;
+    let manifest_path = BackupManifest::manifest_path(&location);
+    if !remote_fs.list(format!("{}/", location)).await?.is_empty() {
+        return Err(CubeError::user(format!(
+            "Backup location '{}' is not empty",
+            location
+        )));
+    }
+
+    let (metastore, data_files) = meta_store
+        .backup_snapshot(BackupManifest::store_location(&location, "metastore"))
+        .await?;
+    let cachestore = cache_store
+        .backup_snapshot(BackupManifest::store_location(&location, "cachestore"))
+        .await?;
+
+    for_each_data_file(&data_files, |file| {
+        let remote_fs = remote_fs.clone();
+        let backup_path = BackupManifest::data_file_location(&location, &file.path);
+        async move { copy_remote_file(remote_fs, file.path, file.size, backup_path).await }
+    })
+    .await?;
+
+    let manifest = BackupManifest {
+        version: BACKUP_MANIFEST_VERSION,
+        created_at: Utc::now(),
+        metastore,
+        cachestore,
+        data_files,
+    };
+
+    // Manifest is written last, a bundle without it is incomplete and can't be restored
+    let temp_path = remote_fs.temp_upload_path(manifest_path.clone()).await?;
+    fs::write(&temp_path, serde_json::to_vec_pretty(&manifest)?).await?;
+    remote_fs.upload_file(temp_path, manifest_path).await?;
+
+    Ok(manifest)
+}
+
+pub async fn read_backup_manifest(
+    remote_fs: Arc<dyn RemoteFs>,
+    location: &str,
+) -> Result<BackupManifest, CubeError> {
+    let manifest_path = BackupManifest::manifest_path(location);
+    if remote_fs.list(manifest_path.clone()).await?.is_empty() {
+        return Err(CubeError::user(format!(
+            "Backup manifest is not found in '{}'",
+            location
+        )));
+    }
+
+    let local_path = remote_fs.download_file(manifest_path, None).await?;
+    let manifest: BackupManifest =
+        serde_json::from_slice(&fs::read(&local_path).await?).map_err(|e| {
+            CubeError::user(format!(
+                "Unable to read backup manifest in '{}': {}",
+                location, e
+            ))
+        })?;
+    if manifest.version != BACKUP_MANIFEST_VERSION {
+        return Err(CubeError::user(format!(
+            "Unsupported backup manifest version {} in '{}', expected: {}",
+            manifest.version, location, BACKUP_MANIFEST_VERSION
+        )));
+    }
+
+    Ok(manifest)
+}
+
+async fn for_each_data_file<F, Fut>(data_files: &[BackupDataFile], f: F) -> Result<(), CubeError>
+where
+    F: Fn(BackupDataFile) -> Fut,
+    Fut: std::future::Future<Output = Result<(), CubeError>>,
+{
+    futures::stream::iter(data_files.iter().cloned())
+        .map(f)
+        .buffer_unordered(16)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(())
+}
+
+async fn copy_remote_file(
+    remote_fs: Arc<dyn RemoteFs>,
+    from: String,
+    expected_size: Option<u64>,
+    to: String,
+) -> Result<(), CubeError> {
+    let local_path = remote_fs.download_file(from, expected_size).await?;
+    let temp_path = remote_fs.temp_upload_path(to.clone()).await?;
+    fs::copy(&local_path, &temp_path).await?;
+    remote_fs.upload_file(temp_path, to).await?;
+
+    Ok(())
+}
+
+/// Puts back data files of the backup which were removed (or replaced) since it was created.
+async fn restore_data_files(
+    remote_fs: Arc<dyn RemoteFs>,
+    location: &str,
+    manifest: &BackupManifest,
+) -> Result<(), CubeError> {
+    for_each_data_file(&manifest.data_files, |file| {
+        let remote_fs = remote_fs.clone();
+        let backup_path = BackupManifest::data_file_location(location, &file.path);
+        async move {
+            // Listings report sizes of stored objects, which are bigger for encrypted files
+            let stored_size = file
+                .size
+                .map(|size| stored_file_size(size, &file.encryption_key_id));
+            let files = remote_fs.list_with_metadata(file.path.clone()).await?;
+            let exists = files.iter().any(|f| {
+                f.remote_path == file.path && stored_size.map_or(true, |size| f.file_size == size)
+            });
+            if exists {
+                return Ok(());
+            }
+
+            copy_remote_file(remote_fs, backup_path, file.size, file.path).await
+        }
+    })
+    .await
+}
+
+/// Checks that all files of the bundle, including copies of data files, exist on the remote fs.
+/// Returns the list of missing files.
+pub async fn find_missing_backup_files(
+    remote_fs: Arc<dyn RemoteFs>,
+    location: &str,
+    manifest: &BackupManifest,
+) -> Result<Vec<String>, CubeError> {
+    let mut expected = Vec::new();
+    for (store_name, snapshot) in [
+        ("metastore", &manifest.metastore),
+        ("cachestore", &manifest.cachestore),
+    ] {
+        let store_location = BackupManifest::store_location(location, store_name);
+        for file in snapshot.files.iter() {
+            expected.push(format!("{}/{}", store_location, file));
+        }
+    }
+    // Sizes of copies are checked on download, they may be encrypted with a different key
+    for file in manifest.data_files.iter() {
+        expected.push(BackupManifest::data_file_location(location, &file.path));
+    }
+
+    let missing = futures::stream::iter(expected)
+        .map(|path| {
+            let remote_fs = remote_fs.clone();
+            async move {
+                let files = remote_fs.list(path.clone()).await?;
+                let exists = files.iter().any(|f| f == &path);
+
+                Ok::<_, CubeError>(if exists { None } else { Some(path) })
+            }
+        })
+        .buffer_unordered(16)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .filter_map(|r| r.transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(missing)
+}
+
+/// Switches the metastore and cachestore to snapshots from the backup. As with
+/// `SYS METASTORE SET_CURRENT`, the restored state is loaded after the restart.
+pub async fn restore_backup(
+    meta_store: Arc<dyn MetaStore>,
+    cache_store: Arc<dyn CacheStore>,
+    remote_fs: Arc<dyn RemoteFs>,
+    location: String,
+) -> Result<(u128, u128), CubeError> {
+    let location = normalize_backup_location(&location)?;
+    let manifest = read_backup_manifest(remote_fs.clone(), &location).await?;
+
+    let mut missing = find_missing_backup_files(remote_fs.clone(), &location, &manifest).await?;
+    if !missing.is_empty() {
+        missing.sort();
+        let total = missing.len();
+        missing.truncate(10);
+
+        return Err(CubeError::user(format!(
+            "Unable to restore backup from '{}', {} referenced files are missing: {}{}",
+            location,
+            total,
+            missing.join(", "),
+            if total > missing.len() { ", ..." } else { "" }
+        )));
+    }
+
+    // The marker goes first, so data files are checked at startup even if this call fails midway
+    let marker_path = format!("{}{}", RESTORE_PENDING_PREFIX, location);
+    let temp_path = remote_fs.temp_upload_path(marker_path.clone()).await?;
+    fs::write(&temp_path, b"").await?;
+    remote_fs.upload_file(temp_path, marker_path).await?;
+
+    restore_data_files(remote_fs, &location, &manifest).await?;
+
+    // Cachestore goes first, so its failure leaves the metastore snapshot untouched
+    let cachestore_snapshot = cache_store
+        .restore_snapshot(
+            BackupManifest::store_location(&location, "cachestore"),
+            manifest.cachestore,
+        )
+        .await?;
+    let metastore_snapshot = meta_store
+        .restore_snapshot(
+            BackupManifest::store_location(&location, "metastore"),
+            manifest.metastore,
+        )
+        .await?;
+
+    Ok((metastore_snapshot, cachestore_snapshot))
+}
+
+/// Puts back data files of restored backups removed between the restore and the restart. Called at
+/// startup before the scheduler is started.
+pub async fn complete_pending_restores(remote_fs: Arc<dyn RemoteFs>) -> Result<(), CubeError> {
+    for marker_path in remote_fs.list(RESTORE_PENDING_PREFIX.to_string()).await? {
+        let location = &marker_path[RESTORE_PENDING_PREFIX.len()..];
+        let manifest = read_backup_manifest(remote_fs.clone(), location).await?;
+        restore_data_files(remote_fs.clone(), location, &manifest).await?;
+        remote_fs.delete_file(marker_path.clone()).await?;
+        log::info!(
+            "Data files of the backup restored from '{}' are checked",
+            location
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cachestore::CacheItem;
+    use crate::config::{init_test_logger, Config};
+    use crate::remotefs::queue::QueueRemoteFs;
+    use futures_timer::Delay;
+    use std::time::Duration;
+
+    #[test]
+    fn test_normalize_backup_location() {
+        assert_eq!(
+            normalize_backup_location("backups/2024-01-01/").unwrap(),
+            "backups/2024-01-01"
+        );
+        assert_eq!(normalize_backup_location(" daily ").unwrap(), "daily");
+
+        for location in [
+            "",
+            "/",
+            "/backups",
+            "../backups",
+            "backups/../metastore-1",
+            "backups//daily",
+            "s3://bucket/backups",
+            "metastore-1",
+            "cachestore-current",
+            "backup-restore-pending",
+        ] {
+            assert!(
+                normalize_backup_location(location).is_err(),
+                "expected error for '{}'",
+                location
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore() -> Result<(), CubeError> {
+        init_test_logger().await;
+
+        let config = Config::test("backup_and_restore");
+        let _ = std::fs::remove_dir_all(config.local_dir());
+        let _ = std::fs::remove_dir_all(config.remote_dir());
+
+        let (metastore_snapshot_id, cachestore_snapshot_id) = {
+            let services = config.configure().await;
+            services.start_processing_loops().await?;
+
+            let meta_store = services.meta_store.clone();
+            let cache_store: Arc<dyn CacheStore> = services.rocks_cache_store.clone().unwrap();
+            let remote_fs: Arc<dyn RemoteFs> =
+                services.injector.get_service_typed::<QueueRemoteFs>().await;
+
+            meta_store
+                .create_schema("before_backup".to_string(), false)
+                .await?;
+            cache_store
+                .cache_set(
+                    CacheItem::new("backup:key".to_string(), None, "value".to_string()),
+                    false,
+                )
+                .await?;
+
+            let manifest = create_backup(
+                meta_store.clone(),
+                cache_store.clone(),
+                remote_fs.clone(),
+                "backups/first/".to_string(),
+            )
+            .await?;
+            assert!(!manifest.metastore.files.is_empty());
+            assert!(!manifest.cachestore.files.is_empty());
+            assert_eq!(
+                read_backup_manifest(remote_fs.clone(), "backups/first").await?,
+                manifest
+            );
+
+            let res = create_backup(
+                meta_store.clone(),
+                cache_store.clone(),
+                remote_fs.clone(),
+                "backups/first".to_string(),
+            )
+            .await;
+            assert_eq!(
+                res.unwrap_err().to_string(),
+                "Backup location 'backups/first' is not empty"
+            );
+
+            let mut broken = manifest.clone();
+            broken.data_files.push(BackupDataFile {
+                path: "1-missing.parquet".to_string(),
+                size: None,
+                encryption_key_id: None,
+            });
+            assert_eq!(
+                find_missing_backup_files(remote_fs.clone(), "backups/first", &broken).await?,
+                vec!["backups/first/data/1-missing.parquet.bak".to_string()]
+            );
+
+            meta_store
+                .create_schema("after_backup".to_string(), false)
+                .await?;
+
+            let restored = restore_backup(
+                meta_store.clone(),
+                cache_store.clone(),
+                remote_fs.clone(),
+                "backups/first".to_string(),
+            )
+            .await?;
+
+            let snapshots = meta_store.get_snapshots_list().await?;
+            let current = snapshots.iter().find(|s| s.current).unwrap();
+            assert_eq!(current.id, restored.0);
+
+            services.stop_processing_loops().await?;
+
+            Delay::new(Duration::from_millis(2000)).await; // TODO logger init conflict
+            std::fs::remove_dir_all(config.local_dir())?;
+
+            restored
+        };
+
+        {
+            let services = config.configure().await;
+
+            let snapshots = services.meta_store.get_snapshots_list().await?;
+            let current = snapshots.iter().find(|s| s.current).unwrap();
+            assert_eq!(current.id, metastore_snapshot_id);
+
+            services
+                .meta_store
+                .get_schema("before_backup".to_string())
+                .await?;
+            assert!(services
+                .meta_store
+                .get_schema("after_backup".to_string())
+                .await
+                .is_err());
+
+            let cache_store = services.rocks_cache_store.clone().unwrap();
+            let item = cache_store
+                .cache_get("backup:key".to_string())
+                .await?
+                .expect("must return row");
+            assert_eq!(item.get_row().get_value(), "value");
+            assert!(cachestore_snapshot_id > 0);
+
+            Delay::new(Duration::from_millis(2000)).await; // TODO logger init conflict
+            let _ = std::fs::remove_dir_all(config.local_dir());
+            let _ = std::fs::remove_dir_all(config.remote_dir());
+        }
+
+        Ok(())
+    }
+
+    async fn originals_exist(
+        remote_fs: Arc<dyn RemoteFs>,
+        files: Vec<BackupDataFile>,
+    ) -> Result<Vec<bool>, CubeError> {
+        let mut exist = Vec::new();
+        for file in files {
+            exist.push(
+                remote_fs
+                    .list(file.path.clone())
+                    .await?
+                    .contains(&file.path),
+            );
+        }
+
+        Ok(exist)
+    }
+
+    #[tokio::test]
+    async fn backup_survives_compaction_gc() -> Result<(), CubeError> {
+        Config::test("backup_survives_compaction_gc")
+            .update_config(|mut c| {
+                c.compaction_chunks_count_threshold = 0;
+                c.not_used_timeout = 0;
+                c.meta_store_log_upload_interval = 1;
+                c.meta_store_snapshot_interval = 1;
+                c.gc_loop_interval = 1;
+                c
+            })
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                let remote_fs: Arc<dyn RemoteFs> =
+                    services.injector.get_service_typed::<QueueRemoteFs>().await;
+                let cache_store: Arc<dyn CacheStore> = services.rocks_cache_store.clone().unwrap();
+
+                service.exec_query("CREATE SCHEMA foo").await?;
+                service
+                    .exec_query("CREATE TABLE foo.numbers (num int)")
+                    .await?;
+                service
+                    .exec_query("INSERT INTO foo.numbers (num) VALUES (1), (2)")
+                    .await?;
+                // Let the compaction of the first chunk finish
+                Delay::new(Duration::from_millis(1000)).await;
+
+                let manifest = create_backup(
+                    services.meta_store.clone(),
+                    cache_store.clone(),
+                    remote_fs.clone(),
+                    "backups/gc".to_string(),
+                )
+                .await?;
+                assert!(!manifest.data_files.is_empty());
+
+                // New data is compacted into new partition files, GC removes the old ones
+                for i in 3..10 {
+                    service
+                        .exec_query(&format!("INSERT INTO foo.numbers (num) VALUES ({})", i))
+                        .await?;
+                }
+                Delay::new(Duration::from_millis(4000)).await;
+                assert!(
+                    originals_exist(remote_fs.clone(), manifest.data_files.clone())
+                        .await?
+                        .contains(&false)
+                );
+
+                assert!(
+                    find_missing_backup_files(remote_fs.clone(), "backups/gc", &manifest)
+                        .await?
+                        .is_empty()
+                );
+                restore_backup(
+                    services.meta_store.clone(),
+                    cache_store.clone(),
+                    remote_fs.clone(),
+                    "backups/gc".to_string(),
+                )
+                .await?;
+                assert!(
+                    originals_exist(remote_fs.clone(), manifest.data_files.clone())
+                        .await?
+                        .iter()
+                        .all(|e| *e)
+                );
+
+                // GC of the running metastore may remove files before the restart
+                remote_fs
+                    .delete_file(manifest.data_files[0].path.clone())
+                    .await?;
+                complete_pending_restores(remote_fs.clone()).await?;
+                assert!(
+                    originals_exist(remote_fs.clone(), manifest.data_files.clone())
+                        .await?
+                        .iter()
+                        .all(|e| *e)
+                );
+                assert!(remote_fs
+                    .list(RESTORE_PENDING_PREFIX.to_string())
+                    .await?
+                    .is_empty());
+
+                Ok(())
+            })
+            .await;
+
+        Ok(())
+    }
+}
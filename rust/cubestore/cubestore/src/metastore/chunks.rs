@@ -38,6 +38,8 @@ impl Chunk {
             replay_handle_id: None,
             min,
             max,
+            encryption_key_id: None,
+            deletes_applied: 0,
         }
     }
 
@@ -87,6 +89,26 @@ impl Chunk {
         Ok(c)
     }
 
+    pub fn encryption_key_id(&self) -> &Option<String> {
+        &self.encryption_key_id
+    }
+
+    pub fn set_encryption_key_id(&self, encryption_key_id: Option<String>) -> Self {
+        let mut c = self.clone();
+        c.encryption_key_id = encryption_key_id;
+        c
+    }
+
+    pub fn deletes_applied(&self) -> u64 {
+        self.deletes_applied
+    }
+
+    pub fn set_deletes_applied(&self, deletes_applied: u64) -> Self {
+        let mut c = self.clone();
+        c.deletes_applied = deletes_applied;
+        c
+    }
+
     pub fn deactivate(&self) -> Chunk {
         let mut to_update = self.clone();
         to_update.active = false;
@@ -66,6 +66,27 @@ impl Index {
         self.multi_index_id
     }
 
+    /// Appends a column after the sort key and other columns.
+    pub fn add_column(&self, column: &Column) -> Index {
+        let mut index = self.clone();
+        let column_index = index.columns.len();
+        index.columns.push(column.replace_index(column_index));
+        index
+    }
+
+    /// Removes a column which is not a part of the sort key.
+    pub fn drop_column(&self, name: &str) -> Index {
+        let mut index = self.clone();
+        index.columns = self
+            .columns
+            .iter()
+            .filter(|c| c.get_name() != name)
+            .enumerate()
+            .map(|(i, c)| c.replace_index(i))
+            .collect();
+        index
+    }
+
     pub fn index_type_default() -> IndexType {
         IndexType::Regular
     }
@@ -27,6 +27,8 @@ pub enum JobType {
     // that extends the trailing range dedups on the start instead of spawning a
     // second job for the same start.
     RepartitionRange(/*end_chunk_id*/ u64),
+    /// Drops partitions and chunks of a table which are expired according to its retention.
+    TableRetention,
     /// Fallback for job types written by a newer binary that this binary does
     /// not know about. Lets the read path decode such rows instead of failing
     /// the whole job scan; the worker ignores `Unknown` jobs.
@@ -48,6 +50,7 @@ fn get_job_type_index(j: &JobType) -> u32 {
         JobType::NodeInMemoryChunksCompaction(_) => 10,
         JobType::Unknown => 11,
         JobType::RepartitionRange(_) => 12,
+        JobType::TableRetention => 13,
     }
 }
 
@@ -63,6 +66,7 @@ fn get_job_type_priority(j: &JobType) -> u32 {
         JobType::FinishMultiSplit => 1000,
         JobType::RepartitionChunk => 1000,
         JobType::RepartitionRange(_) => 1000,
+        JobType::TableRetention => 1000,
         JobType::InMemoryChunksCompaction => 10000,
         JobType::NodeInMemoryChunksCompaction(_) => 10000,
         JobType::Unknown => 0,
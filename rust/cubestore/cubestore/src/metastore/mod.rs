@@ -1,3 +1,4 @@
+pub mod backup;
 pub mod chunks;
 pub mod index;
 pub mod job;
@@ -48,7 +49,10 @@ use crate::metastore::replay_handle::{
 use crate::metastore::source::{
     Source, SourceCredentials, SourceIndexKey, SourceRocksIndex, SourceRocksTable,
 };
-use crate::metastore::table::{AggregateColumnIndex, StreamOffset, TableIndexKey, TablePath};
+use crate::metastore::table::{
+    AggregateColumnIndex, DeletePredicate, StreamOffset, TableIndexKey, TableParquetSettings,
+    TablePath, TableRetention,
+};
 use crate::metastore::trace_object::{
     TraceObject, TraceObjectIndexKey, TraceObjectRocksIndex, TraceObjectRocksTable,
 };
@@ -90,9 +94,11 @@ use std::path::Path;
 use std::str::FromStr;
 
 use crate::cachestore::{
-    CacheItem, QueueItem, QueueItemPayload, QueueItemStatus, QueueResult, QueueResultAckEvent,
+    CacheItem, CacheNotificationEvent, QueueItem, QueueItemPayload, QueueItemStatus, QueueResult,
+    QueueResultAckEvent,
 };
 use crate::remotefs::LocalDirRemoteFs;
+use backup::{BackupDataFile, BackupStoreSnapshot};
 use cubedatasketches::HLLDataSketch;
 use deepsize::DeepSizeOf;
 use snapshot_info::SnapshotInfo;
@@ -241,6 +247,12 @@ impl DataFrameValue<String> for Option<String> {
     }
 }
 
+impl DataFrameValue<String> for Vec<String> {
+    fn value(v: &Self) -> String {
+        format!("{:?}", v)
+    }
+}
+
 impl DataFrameValue<String> for Option<Vec<String>> {
     fn value(v: &Self) -> String {
         v.as_ref()
@@ -334,6 +346,9 @@ impl DataFrameValue<String> for Option<Row> {
                             TableValue::Decimal(v) => format!("{}", v.raw_value()),
                             TableValue::Decimal96(v) => format!("{}", v.raw_value()),
                             TableValue::Float(v) => format!("{}", v),
+                            TableValue::Date(v) => format!("{:?}", v),
+                            TableValue::Interval(v) => format!("{:?}", v),
+                            TableValue::List(v) => format!("{:?}", v),
                         })
                         .join(", ")
                 )
@@ -388,10 +403,26 @@ pub enum ColumnType {
     Bytes,
     HyperLogLog(HllFlavour), // HLL Sketches, compatible with presto.
     Timestamp,
-    Decimal { scale: i32, precision: i32 },
-    Decimal96 { scale: i32, precision: i32 },
+    Decimal {
+        scale: i32,
+        precision: i32,
+    },
+    Decimal96 {
+        scale: i32,
+        precision: i32,
+    },
     Float,
     Boolean,
+    Date,
+    Interval,
+    /// List of primitive values, see `is_list_element`.
+    List(Box<ColumnType>),
+    /// JSON document stored as text.
+    Json,
+    /// Quantile sketch, see `queryplanner::tdigest::TDigest`.
+    TDigest,
+    /// Theta sketch from Apache DataSketches, supports set operations.
+    ThetaSketch,
 }
 
 impl Display for ColumnType {
@@ -399,6 +430,7 @@ impl Display for ColumnType {
         let s = match self {
             ColumnType::Decimal { scale, .. } => return write!(f, "decimal({})", scale),
             ColumnType::Decimal96 { scale, .. } => return write!(f, "decimal96({})", scale),
+            ColumnType::List(element) => return write!(f, "list<{}>", element),
             ColumnType::String => "text",
             ColumnType::Int => "int",
             ColumnType::Int96 => "int96",
@@ -411,6 +443,11 @@ impl Display for ColumnType {
             ColumnType::Timestamp => "timestamp",
             ColumnType::Float => "float",
             ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::Interval => "interval",
+            ColumnType::Json => "json",
+            ColumnType::TDigest => "tdigest",
+            ColumnType::ThetaSketch => "theta_sketch",
         };
         f.write_str(s)
     }
@@ -422,7 +459,9 @@ impl ColumnType {
             static ref DECIMAL_RE: Regex = Regex::new(r"decimal\((?P<scale>\d+)\)").unwrap();
             static ref DECIMAL_96_RE: Regex = Regex::new(r"decimal96\((?P<scale>\d+)\)").unwrap();
         }
-        if let Some(captures) = DECIMAL_96_RE.captures(s) {
+        if let Some(element) = s.strip_prefix("list<").and_then(|s| s.strip_suffix('>')) {
+            ColumnType::list_of(ColumnType::from_string(element)?)
+        } else if let Some(captures) = DECIMAL_96_RE.captures(s) {
             let scale = captures
                 .name("scale")
                 .ok_or(CubeError::internal("missing scale capture".to_string()))?
@@ -457,6 +496,11 @@ impl ColumnType {
                 "timestamp" => Ok(ColumnType::Timestamp),
                 "float" => Ok(ColumnType::Float),
                 "boolean" => Ok(ColumnType::Boolean),
+                "date" => Ok(ColumnType::Date),
+                "interval" => Ok(ColumnType::Interval),
+                "json" => Ok(ColumnType::Json),
+                "tdigest" => Ok(ColumnType::TDigest),
+                "theta_sketch" => Ok(ColumnType::ThetaSketch),
                 _ => {
                     return Err(CubeError::user(format!(
                         "Column type '{}' is not supported",
@@ -467,6 +511,38 @@ impl ColumnType {
         }
     }
 
+    /// Lists hold primitive values only: no nested lists, sketches or decimals.
+    pub fn is_list_element(&self) -> bool {
+        match self {
+            ColumnType::String
+            | ColumnType::Int
+            | ColumnType::Float
+            | ColumnType::Boolean
+            | ColumnType::Timestamp
+            | ColumnType::Date => true,
+            ColumnType::Int96
+            | ColumnType::Bytes
+            | ColumnType::HyperLogLog(_)
+            | ColumnType::Decimal { .. }
+            | ColumnType::Decimal96 { .. }
+            | ColumnType::Interval
+            | ColumnType::List(_)
+            | ColumnType::Json
+            | ColumnType::TDigest
+            | ColumnType::ThetaSketch => false,
+        }
+    }
+
+    pub fn list_of(element: ColumnType) -> Result<ColumnType, CubeError> {
+        if !element.is_list_element() {
+            return Err(CubeError::user(format!(
+                "Lists of '{}' are not supported",
+                element
+            )));
+        }
+        Ok(ColumnType::List(Box::new(element)))
+    }
+
     pub fn target_scale(&self) -> i32 {
         match self {
             ColumnType::Decimal { scale, .. } => *scale,
@@ -515,7 +591,10 @@ impl From<&Column> for types::Type {
                     .build()
                     .unwrap()
             }
-            ColumnType::Bytes | ColumnType::HyperLogLog(_) => {
+            ColumnType::Bytes
+            | ColumnType::HyperLogLog(_)
+            | ColumnType::TDigest
+            | ColumnType::ThetaSketch => {
                 types::Type::primitive_type_builder(&column.get_name(), Type::BYTE_ARRAY)
                     .with_converted_type(ConvertedType::NONE)
                     .with_repetition(Repetition::OPTIONAL)
@@ -542,6 +621,41 @@ impl From<&Column> for types::Type {
                     .build()
                     .unwrap()
             }
+            ColumnType::Date => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::INT32)
+                    .with_converted_type(ConvertedType::DATE)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::Interval => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::INT64)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::Json => {
+                types::Type::primitive_type_builder(&column.get_name(), Type::BYTE_ARRAY)
+                    .with_converted_type(ConvertedType::JSON)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()
+                    .unwrap()
+            }
+            ColumnType::List(element) => {
+                let element: types::Type =
+                    (&Column::new("element".to_string(), element.as_ref().clone(), 0)).into();
+                let list = types::Type::group_type_builder("list")
+                    .with_repetition(Repetition::REPEATED)
+                    .with_fields(vec![Arc::new(element)])
+                    .build()
+                    .unwrap();
+                types::Type::group_type_builder(&column.get_name())
+                    .with_converted_type(ConvertedType::LIST)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .with_fields(vec![Arc::new(list)])
+                    .build()
+                    .unwrap()
+            }
         }
     }
 }
@@ -577,16 +691,24 @@ impl<'a> Into<Field> for &'a Column {
                 }
                 ColumnType::Bytes => DataType::Binary,
                 ColumnType::HyperLogLog(_) => DataType::Binary,
+                ColumnType::TDigest => DataType::Binary,
+                ColumnType::ThetaSketch => DataType::Binary,
                 ColumnType::Float => DataType::Float64,
+                ColumnType::Date => DataType::Date32,
+                ColumnType::Interval => DataType::Duration(Microsecond),
+                ColumnType::Json => DataType::Utf8,
+                ColumnType::List(ref element) => DataType::List(Arc::new(
+                    (&Column::new("item".to_string(), element.as_ref().clone(), 0)).into(),
+                )),
             },
             true,
         )
     }
 }
 
-impl fmt::Display for Column {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let column_type = match &self.column_type {
+impl Column {
+    fn type_name(column_type: &ColumnType) -> String {
+        match column_type {
             ColumnType::String => "STRING".to_string(),
             ColumnType::Int => "INT".to_string(),
             ColumnType::Int96 => "INT96".to_string(),
@@ -605,12 +727,27 @@ impl fmt::Display for Column {
             ColumnType::HyperLogLog(HllFlavour::Snowflake) => "HLL_SNOWFLAKE".to_string(),
             ColumnType::HyperLogLog(HllFlavour::DataSketches) => "HLL_DATASKETCHES".to_string(),
             ColumnType::Float => "FLOAT".to_string(),
-        };
-        f.write_fmt(format_args!("{} {}", self.name, column_type))
+            ColumnType::Date => "DATE".to_string(),
+            ColumnType::Interval => "INTERVAL".to_string(),
+            ColumnType::Json => "JSON".to_string(),
+            ColumnType::TDigest => "TDIGEST".to_string(),
+            ColumnType::ThetaSketch => "THETA_SKETCH".to_string(),
+            ColumnType::List(element) => format!("{}[]", Column::type_name(element)),
+        }
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "{} {}",
+            self.name,
+            Column::type_name(&self.column_type)
+        ))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
 pub enum ImportFormat {
     CSV,
     CSVNoHeader,
@@ -620,6 +757,12 @@ pub enum ImportFormat {
         quote: Option<char>,
         has_header: bool,
     },
+    Parquet,
+    ArrowIPC,
+    NDJSON {
+        /// `(column name, JSON path)` pairs for columns that aren't top-level keys.
+        column_paths: Vec<(String, String)>,
+    },
 }
 
 data_frame_from! {
@@ -655,12 +798,24 @@ pub struct Index {
 
 impl RocksEntity for Index {}
 
+#[allow(non_camel_case_types)]
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
 pub enum AggregateFunction {
     SUM = 1,
     MAX = 2,
     MIN = 3,
     MERGE = 4,
+    /// Number of non-null values. Ingestion replaces every value with 1 (0 for nulls) and
+    /// merges add the counts up.
+    COUNT = 5,
+    ANY_VALUE = 6,
+    /// The value of the most recently ingested row. Not allowed for tables with a `seq_column`,
+    /// as insert order is used instead of it.
+    LAST_VALUE = 7,
+    /// Merge of DataSketches theta sketches.
+    MERGE_THETA = 8,
+    /// Merge of t-digest quantile sketches.
+    MERGE_TDIGEST = 9,
 }
 
 impl FromStr for AggregateFunction {
@@ -672,6 +827,11 @@ impl FromStr for AggregateFunction {
             "MAX" => Ok(AggregateFunction::MAX),
             "MIN" => Ok(AggregateFunction::MIN),
             "MERGE" => Ok(AggregateFunction::MERGE),
+            "COUNT" => Ok(AggregateFunction::COUNT),
+            "ANY_VALUE" => Ok(AggregateFunction::ANY_VALUE),
+            "LAST_VALUE" => Ok(AggregateFunction::LAST_VALUE),
+            "MERGE_THETA" => Ok(AggregateFunction::MERGE_THETA),
+            "MERGE_TDIGEST" => Ok(AggregateFunction::MERGE_TDIGEST),
             _ => Err(CubeError::user(format!(
                 "Function {} can't be used in aggregate index",
                 s
@@ -687,6 +847,11 @@ impl fmt::Display for AggregateFunction {
             Self::MAX => "MAX",
             Self::MIN => "MIN",
             Self::MERGE => "MERGE",
+            Self::COUNT => "COUNT",
+            Self::ANY_VALUE => "ANY_VALUE",
+            Self::LAST_VALUE => "LAST_VALUE",
+            Self::MERGE_THETA => "MERGE_THETA",
+            Self::MERGE_TDIGEST => "MERGE_TDIGEST",
         };
 
         f.write_fmt(format_args!("{}", res))
@@ -697,7 +862,11 @@ impl AggregateFunction {
     pub fn allowed_for_type(&self, col_type: &ColumnType) -> bool {
         match self {
             Self::MAX | Self::MIN => match col_type {
-                ColumnType::HyperLogLog(_) => false,
+                ColumnType::HyperLogLog(_)
+                | ColumnType::List(_)
+                | ColumnType::Json
+                | ColumnType::TDigest
+                | ColumnType::ThetaSketch => false,
                 _ => true,
             },
             Self::SUM => match col_type {
@@ -712,6 +881,20 @@ impl AggregateFunction {
                 ColumnType::Bytes => true,
                 _ => false,
             },
+            Self::COUNT => match col_type {
+                ColumnType::Int => true,
+                _ => false,
+            },
+            Self::ANY_VALUE | Self::LAST_VALUE => true,
+            Self::MERGE_THETA => match col_type {
+                ColumnType::ThetaSketch => true,
+                ColumnType::Bytes => true,
+                _ => false,
+            },
+            Self::MERGE_TDIGEST => match col_type {
+                ColumnType::TDigest => true,
+                _ => false,
+            },
         }
     }
 }
@@ -747,7 +930,13 @@ pub struct Partition {
     #[serde(default)]
     min: Option<Row>,
     #[serde(default)]
-    max: Option<Row>
+    max: Option<Row>,
+    /// Key the uploaded file is encrypted with, see `EncryptedRemoteFs`.
+    #[serde(default)]
+    encryption_key_id: Option<String>,
+    /// Id of the last table `DELETE` marker applied to the data.
+    #[serde(default)]
+    deletes_applied: u64
 }
 }
 
@@ -779,7 +968,13 @@ pub struct Chunk {
     replay_handle_id: Option<u64>,
     min: Option<Row>,
     #[serde(default)]
-    max: Option<Row>
+    max: Option<Row>,
+    /// Key the uploaded file is encrypted with, see `EncryptedRemoteFs`.
+    #[serde(default)]
+    encryption_key_id: Option<String>,
+    /// Id of the last table `DELETE` marker applied to the data.
+    #[serde(default)]
+    deletes_applied: u64
 }
 }
 
@@ -856,6 +1051,8 @@ pub trait MetaStore: DIService + Send + Sync {
         trace_obj: Option<String>,
         drop_if_exists: bool,
         extension: Option<String>,
+        retention: Option<(String, u64)>,
+        parquet_settings: Option<TableParquetSettings>,
     ) -> Result<IdRow<Table>, CubeError>;
     async fn table_ready(&self, id: u64, is_ready: bool) -> Result<IdRow<Table>, CubeError>;
     async fn seal_table(&self, id: u64) -> Result<IdRow<Table>, CubeError>;
@@ -882,6 +1079,29 @@ pub trait MetaStore: DIService + Send + Sync {
         created_seconds_ago: i64,
     ) -> Result<Vec<IdRow<Table>>, CubeError>;
     async fn drop_table(&self, table_id: u64) -> Result<IdRow<Table>, CubeError>;
+    /// Adds a column to the table and its regular indexes. Existing data is read with nulls
+    /// in place of the new column.
+    async fn add_table_column(
+        &self,
+        table_id: u64,
+        column_name: String,
+        column_type: ColumnType,
+    ) -> Result<IdRow<Table>, CubeError>;
+    /// Drops a column which isn't a part of index sort keys, the unique key, aggregations or
+    /// retention. Existing data files keep the column until they're rewritten by compaction.
+    async fn drop_table_column(
+        &self,
+        table_id: u64,
+        column_name: String,
+    ) -> Result<IdRow<Table>, CubeError>;
+    async fn rename_table(
+        &self,
+        table_id: u64,
+        schema_name: String,
+        table_name: String,
+    ) -> Result<IdRow<Table>, CubeError>;
+    /// Exchanges schemas and names of two tables in one transaction.
+    async fn swap_tables(&self, left_table_id: u64, right_table_id: u64) -> Result<(), CubeError>;
 
     fn partition_table(&self) -> PartitionMetaStoreTable;
     async fn create_partition(&self, partition: Partition) -> Result<IdRow<Partition>, CubeError>;
@@ -910,18 +1130,21 @@ pub trait MetaStore: DIService + Send + Sync {
     /// Swaps chunks inside the same partition.
     /// The operation will not be commited if partition_id is inside a multi-partition that started
     /// a multi-split. Returns true iff the operation is committed.
+    /// `encryption_key_id` is the key the writer encrypted the new files with.
     async fn swap_compacted_chunks(
         &self,
         partition_id: u64,
         old_chunk_ids: Vec<u64>,
         new_chunk: u64,
         new_chunk_file_size: u64,
+        encryption_key_id: Option<String>,
     ) -> Result<bool, CubeError>;
     async fn swap_active_partitions(
         &self,
         current_active: Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
         new_active: Vec<(IdRow<Partition>, u64)>,
         new_active_min_max: Vec<(u64, (Option<Row>, Option<Row>), (Option<Row>, Option<Row>))>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError>;
     async fn delete_partition(&self, partition_id: u64) -> Result<IdRow<Partition>, CubeError>;
     async fn mark_partition_warmed_up(&self, partition_id: u64) -> Result<(), CubeError>;
@@ -1029,6 +1252,7 @@ pub trait MetaStore: DIService + Send + Sync {
         new_partitions: Vec<(IdRow<Partition>, u64)>,
         new_partition_rows: Vec<u64>,
         initial_split: bool,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError>;
     async fn find_unsplit_partitions(&self, multi_partition_id: u64)
         -> Result<Vec<u64>, CubeError>;
@@ -1095,12 +1319,14 @@ pub trait MetaStore: DIService + Send + Sync {
         deactivate_ids: Vec<u64>,
         uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         new_replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError>;
     async fn swap_chunks_without_check(
         &self,
         deactivate_ids: Vec<u64>,
         uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         new_replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError>;
     async fn deactivate_chunks_without_check(
         &self,
@@ -1111,11 +1337,42 @@ pub trait MetaStore: DIService + Send + Sync {
         table_id: u64,
         uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
         replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError>;
     async fn delete_chunk(&self, chunk_id: u64) -> Result<IdRow<Chunk>, CubeError>;
     async fn delete_chunks_without_checks(&self, chunk_ids: Vec<u64>) -> Result<(), CubeError>;
     async fn all_inactive_chunks(&self) -> Result<Vec<IdRow<Chunk>>, CubeError>;
     async fn all_inactive_not_uploaded_chunks(&self) -> Result<Vec<IdRow<Chunk>>, CubeError>;
+    /// Active partitions with a data file and active chunks of the table which contain only rows
+    /// older than `cutoff` (timestamp in nanoseconds) according to the table retention.
+    async fn get_expired_table_data(
+        &self,
+        table_id: u64,
+        cutoff: i64,
+    ) -> Result<(Vec<IdRow<Partition>>, Vec<IdRow<Chunk>>), CubeError>;
+    /// Replaces expired partitions with empty ones covering the same key range and deactivates
+    /// expired chunks. Returns the number of dropped partitions and chunks.
+    async fn drop_expired_table_data(
+        &self,
+        table_id: u64,
+        cutoff: i64,
+    ) -> Result<(u64, u64), CubeError>;
+    /// Adds a `DELETE` marker to the table. Rows matching `predicate` among data written before
+    /// the marker are skipped by queries and purged by compaction.
+    async fn add_delete_marker(
+        &self,
+        table_id: u64,
+        predicate: DeletePredicate,
+    ) -> Result<IdRow<Table>, CubeError>;
+    /// Active partitions of the table with a data file that haven't applied all of its `DELETE`
+    /// markers yet.
+    async fn get_partitions_with_pending_deletes(
+        &self,
+        table_id: u64,
+    ) -> Result<Vec<IdRow<Partition>>, CubeError>;
+    /// Removes `DELETE` markers already applied by all partitions and chunks of the table.
+    /// Returns the number of removed markers.
+    async fn remove_applied_delete_markers(&self, table_id: u64) -> Result<u64, CubeError>;
 
     async fn create_wal(&self, table_id: u64, row_count: usize) -> Result<IdRow<WAL>, CubeError>;
     async fn get_wal(&self, wal_id: u64) -> Result<IdRow<WAL>, CubeError>;
@@ -1207,6 +1464,16 @@ pub trait MetaStore: DIService + Send + Sync {
 
     async fn get_snapshots_list(&self) -> Result<Vec<SnapshotInfo>, CubeError>;
     async fn set_current_snapshot(&self, snapshot_id: u128) -> Result<(), CubeError>;
+    /// Uploads a snapshot and copies it into `location`, returns it with the data files it references.
+    async fn backup_snapshot(
+        &self,
+        location: String,
+    ) -> Result<(BackupStoreSnapshot, Vec<BackupDataFile>), CubeError>;
+    async fn restore_snapshot(
+        &self,
+        location: String,
+        snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError>;
 }
 
 crate::di_service!(RocksMetaStore, [MetaStore]);
@@ -1250,6 +1517,7 @@ pub enum MetaStoreEvent {
     // TODO: Split to CacheStoreEvent
     UpdateCacheItem(IdRow<CacheItem>, IdRow<CacheItem>),
     DeleteCacheItem(IdRow<CacheItem>),
+    CacheNotification(CacheNotificationEvent),
 
     UpdateQueueItem(IdRow<QueueItem>, IdRow<QueueItem>),
     DeleteQueueItem(IdRow<QueueItem>),
@@ -1797,6 +2065,17 @@ impl RocksMetaStore {
         multi_partitions: &[IdRow<MultiPartition>],
         index_def: IndexDef,
     ) -> Result<IdRow<Index>, CubeError> {
+        // Expired data is dropped by partition and chunk min/max rows, which only works
+        // when the retention column comes first in the sort key.
+        if let Some(retention_column) = table_id.get_row().retention_column() {
+            if index_def.columns.first() != Some(retention_column.get_name()) {
+                return Err(CubeError::user(format!(
+                    "Retention column '{}' should be the first column of index '{}'",
+                    retention_column.get_name(),
+                    index_def.name
+                )));
+            }
+        }
         match index_def.index_type {
             IndexType::Regular => Self::add_regular_index(
                 batch_pipe,
@@ -2086,20 +2365,34 @@ impl RocksMetaStore {
         batch_pipe: &mut BatchPipe<'_, RocksMetaStore>,
         uploaded_chunk_ids: &[(u64, Option<u64>)],
         replay_handle_id: Option<u64>,
+        encryption_key_id: &Option<String>,
     ) -> Result<(u64, HashMap</*partition_id*/ u64, /*rows*/ u64>), CubeError> {
         let table = ChunkRocksTable::new(db_ref.clone());
         let mut activated_row_count = 0;
         let mut partitions = HashMap::new();
+        let mut deletes_applied = HashMap::new();
         for (id, file_size) in uploaded_chunk_ids {
             let chunk = table.get_row_or_not_found(*id)?.into_row();
             *partitions.entry(chunk.get_partition_id()).or_default() += chunk.get_row_count();
             activated_row_count += chunk.get_row_count();
+            let chunk_deletes_applied = match deletes_applied.get(&chunk.get_partition_id()) {
+                Some(id) => *id,
+                None => {
+                    let id = last_delete_marker_id_impl(db_ref.clone(), chunk.get_partition_id())?;
+                    deletes_applied.insert(chunk.get_partition_id(), id);
+                    id
+                }
+            };
             table.update_with_res_fn(
                 *id,
                 |row| {
-                    let mut chunk = row.set_uploaded(true);
+                    let mut chunk = row
+                        .set_uploaded(true)
+                        .set_deletes_applied(chunk_deletes_applied);
                     if let Some(file_size) = file_size {
-                        chunk = chunk.set_file_size(*file_size)?;
+                        chunk = chunk
+                            .set_file_size(*file_size)?
+                            .set_encryption_key_id(encryption_key_id.clone());
                     }
                     Ok(chunk.set_replay_handle_id(replay_handle_id.clone()))
                 },
@@ -2341,6 +2634,8 @@ impl MetaStore for RocksMetaStore {
         trace_obj: Option<String>,
         drop_if_exists: bool,
         extension: Option<String>,
+        retention: Option<(String, u64)>,
+        parquet_settings: Option<TableParquetSettings>,
     ) -> Result<IdRow<Table>, CubeError> {
         self.write_operation("create_table", move |db_ref, batch_pipe| {
             batch_pipe.set_post_commit_callback(|metastore| {
@@ -2415,6 +2710,13 @@ impl MetaStore for RocksMetaStore {
                         }
                         let function = aggr.0.parse::<AggregateFunction>()?;
 
+                        if function == AggregateFunction::LAST_VALUE && seq_column_index.is_some() {
+                            return Err(CubeError::user(format!(
+                                "Aggregate function LAST_VALUE({}) is not supported for tables with unique key: it follows insert order and ignores the sequence column",
+                                aggr_column
+                            )));
+                        }
+
                         if !function.allowed_for_type(&column.column_type) {
                             return Err(CubeError::user(
                                     format!(
@@ -2431,6 +2733,45 @@ impl MetaStore for RocksMetaStore {
             } else {
                 vec![]
             };
+            let retention = if let Some((column_name, period_secs)) = retention {
+                let column = columns
+                    .iter()
+                    .find(|c| c.name == column_name)
+                    .ok_or_else(|| {
+                        CubeError::user(format!(
+                            "Retention column {} not found among column definitions {:?}",
+                            column_name, columns
+                        ))
+                    })?;
+                if column.column_type != ColumnType::Timestamp {
+                    return Err(CubeError::user(format!(
+                        "Retention column {} should be of timestamp type but {} found",
+                        column_name, column.column_type
+                    )));
+                }
+                Some(TableRetention::new(column.column_index as u64, period_secs))
+            } else {
+                None
+            };
+            if let Some(settings) = &parquet_settings {
+                for column_name in settings.bloom_filter_columns() {
+                    let column = columns
+                        .iter()
+                        .find(|c| &c.name == column_name)
+                        .ok_or_else(|| {
+                            CubeError::user(format!(
+                                "Bloom filter column {} not found among column definitions {:?}",
+                                column_name, columns
+                            ))
+                        })?;
+                    if let ColumnType::List(_) = column.column_type {
+                        return Err(CubeError::user(format!(
+                            "Bloom filter column {} can't be of {} type",
+                            column_name, column.column_type
+                        )));
+                    }
+                }
+            }
             let table = Table::new(
                 table_name,
                 schema_id.get_id(),
@@ -2448,6 +2789,8 @@ impl MetaStore for RocksMetaStore {
                 seq_column_index,
                 partition_split_threshold,
                 extension,
+                retention,
+                parquet_settings,
             );
             let table_id = rocks_table.insert(table, batch_pipe)?;
 
@@ -2505,6 +2848,8 @@ impl MetaStore for RocksMetaStore {
                 .filter_map(|c| match c.get_column_type() {
                     ColumnType::Bytes => None,
                     ColumnType::HyperLogLog(_) => None,
+                    ColumnType::TDigest => None,
+                    ColumnType::ThetaSketch => None,
                     _ => {
                         if !aggr_column_names.contains(&c.get_name())
                             && seq_column_index.is_none()
@@ -2758,6 +3103,202 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn add_table_column(
+        &self,
+        table_id: u64,
+        column_name: String,
+        column_type: ColumnType,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation("add_table_column", move |db_ref, batch_pipe| {
+            batch_pipe.set_post_commit_callback(|metastore| {
+                metastore.cached_tables.reset();
+            });
+            let tables_table = TableRocksTable::new(db_ref.clone());
+            let indexes_table = IndexRocksTable::new(db_ref.clone());
+            let table = tables_table.get_row_or_not_found(table_id)?;
+            let indexes = indexes_table
+                .get_rows_by_index(&IndexIndexKey::TableId(table_id), &IndexRocksIndex::TableID)?;
+            check_table_columns_can_be_altered(&table, &indexes)?;
+            let table_name = table.get_row().get_table_name();
+            if table
+                .get_row()
+                .get_columns()
+                .iter()
+                .any(|c| c.get_name() == &column_name)
+            {
+                return Err(CubeError::user(format!(
+                    "Column '{}' already exists in table '{}'",
+                    column_name, table_name
+                )));
+            }
+            // Data files written before the drop still have values of the old column.
+            if table.get_row().dropped_columns().contains(&column_name) {
+                return Err(CubeError::user(format!(
+                    "Column '{}' was dropped from table '{}' and can't be added again",
+                    column_name, table_name
+                )));
+            }
+
+            let table = tables_table.update_with_fn(
+                table_id,
+                |t| t.add_column(column_name, column_type),
+                batch_pipe,
+            )?;
+            let column = table.get_row().get_columns().last().unwrap().clone();
+            for index in indexes.iter() {
+                if let IndexType::Regular = index.get_row().get_type() {
+                    indexes_table.update_with_fn(
+                        index.get_id(),
+                        |i| i.add_column(&column),
+                        batch_pipe,
+                    )?;
+                }
+            }
+            Ok(table)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn drop_table_column(
+        &self,
+        table_id: u64,
+        column_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation("drop_table_column", move |db_ref, batch_pipe| {
+            batch_pipe.set_post_commit_callback(|metastore| {
+                metastore.cached_tables.reset();
+            });
+            let tables_table = TableRocksTable::new(db_ref.clone());
+            let indexes_table = IndexRocksTable::new(db_ref.clone());
+            let table = tables_table.get_row_or_not_found(table_id)?;
+            let indexes = indexes_table
+                .get_rows_by_index(&IndexIndexKey::TableId(table_id), &IndexRocksIndex::TableID)?;
+            check_table_columns_can_be_altered(&table, &indexes)?;
+            let t = table.get_row();
+            let is_column = |c: &Column| c.get_name() == &column_name;
+            let used_by = if t
+                .unique_key_columns()
+                .unwrap_or_default()
+                .into_iter()
+                .any(is_column)
+                || t.seq_column().map(is_column).unwrap_or(false)
+            {
+                Some("the unique key".to_string())
+            } else if t.aggregate_columns().iter().any(|a| is_column(a.column())) {
+                Some("an aggregation".to_string())
+            } else if t.retention_column().map(is_column).unwrap_or(false) {
+                Some("the retention".to_string())
+            } else if t
+                .delete_markers()
+                .iter()
+                .any(|m| m.predicate().columns().contains(&&column_name))
+            {
+                Some("pending deletes".to_string())
+            } else {
+                indexes.iter().find_map(|i| {
+                    let key_columns = match i.get_row().get_type() {
+                        IndexType::Regular => {
+                            &i.get_row().get_columns()[..i.get_row().sort_key_size() as usize]
+                        }
+                        IndexType::Aggregate => &i.get_row().get_columns()[..],
+                    };
+                    if key_columns.iter().any(is_column) {
+                        Some(format!("index '{}'", i.get_row().get_name()))
+                    } else {
+                        None
+                    }
+                })
+            };
+            if let Some(used_by) = used_by {
+                return Err(CubeError::user(format!(
+                    "Column '{}' of table '{}' can't be dropped as it's used by {}",
+                    column_name,
+                    t.get_table_name(),
+                    used_by
+                )));
+            }
+
+            let table = tables_table.update_with_res_fn(
+                table_id,
+                |t| t.drop_column(&column_name),
+                batch_pipe,
+            )?;
+            for index in indexes.iter() {
+                indexes_table.update_with_fn(
+                    index.get_id(),
+                    |i| i.drop_column(&column_name),
+                    batch_pipe,
+                )?;
+            }
+            Ok(table)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn rename_table(
+        &self,
+        table_id: u64,
+        schema_name: String,
+        table_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation("rename_table", move |db_ref, batch_pipe| {
+            batch_pipe.set_post_commit_callback(|metastore| {
+                metastore.cached_tables.reset();
+            });
+            let tables_table = TableRocksTable::new(db_ref.clone());
+            let schema_id = SchemaRocksTable::new(db_ref.clone())
+                .get_single_row_by_index(&schema_name, &SchemaRocksIndex::Name)?
+                .get_id();
+            let existing = tables_table.get_rows_by_index(
+                &TableIndexKey::ByName(schema_id, table_name.clone()),
+                &TableRocksIndex::Name,
+            )?;
+            if !existing.is_empty() {
+                return Err(CubeError::user(format!(
+                    "Table {}.{} already exists",
+                    schema_name, table_name
+                )));
+            }
+            tables_table.update_with_fn(table_id, |t| t.rename(schema_id, table_name), batch_pipe)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn swap_tables(&self, left_table_id: u64, right_table_id: u64) -> Result<(), CubeError> {
+        self.write_operation("swap_tables", move |db_ref, batch_pipe| {
+            batch_pipe.set_post_commit_callback(|metastore| {
+                metastore.cached_tables.reset();
+            });
+            if left_table_id == right_table_id {
+                return Err(CubeError::user(
+                    "Table can't be swapped with itself".to_string(),
+                ));
+            }
+            let tables_table = TableRocksTable::new(db_ref.clone());
+            let left = tables_table.get_row_or_not_found(left_table_id)?;
+            let right = tables_table.get_row_or_not_found(right_table_id)?;
+            let (left, right) = (left.get_row(), right.get_row());
+            tables_table.update(
+                left_table_id,
+                left.rename(right.get_schema_id(), right.get_table_name().clone()),
+                left,
+                batch_pipe,
+            )?;
+            tables_table.update(
+                right_table_id,
+                right.rename(left.get_schema_id(), left.get_table_name().clone()),
+                right,
+                batch_pipe,
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
     fn partition_table(&self) -> PartitionMetaStoreTable {
         PartitionMetaStoreTable {
             rocks_meta_store: self.store.clone(),
@@ -2962,6 +3503,7 @@ impl MetaStore for RocksMetaStore {
         old_chunk_ids: Vec<u64>,
         new_chunk: u64,
         new_chunk_file_size: u64,
+        encryption_key_id: Option<String>,
     ) -> Result<bool, CubeError> {
         self.write_operation("swap_compacted_chunks", move |db, pipe| {
             let p = PartitionRocksTable::new(db.clone()).get_row_or_not_found(partition_id)?;
@@ -2978,6 +3520,7 @@ impl MetaStore for RocksMetaStore {
                 pipe,
                 false,
                 None,
+                encryption_key_id,
             )?;
             let index =
                 IndexRocksTable::new(db).get_row_or_not_found(p.get_row().get_index_id())?;
@@ -2995,6 +3538,7 @@ impl MetaStore for RocksMetaStore {
         current_active: Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
         new_active: Vec<(IdRow<Partition>, u64)>,
         mut new_active_min_max: Vec<(u64, (Option<Row>, Option<Row>), (Option<Row>, Option<Row>))>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         trace!(
             "Swapping partitions: deactivating ({}), deactivating chunks ({}), activating ({})",
@@ -3012,6 +3556,7 @@ impl MetaStore for RocksMetaStore {
                 pipe,
                 &current_active,
                 &new_active,
+                &encryption_key_id,
                 move |i, p| {
                     let (rows, (min_val, max_val), (min, max)) = take(&mut new_active_min_max[i]);
                     p.update_min_max_and_row_count(min_val, max_val, rows, min, max)
@@ -4035,6 +4580,7 @@ impl MetaStore for RocksMetaStore {
         table_id: u64,
         uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
         replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         trace!(
             "Activating chunks ({})",
@@ -4051,6 +4597,7 @@ impl MetaStore for RocksMetaStore {
                 pipe,
                 &uploaded_chunk_ids,
                 replay_handle_id,
+                &encryption_key_id,
             )?;
             let partition = PartitionRocksTable::new(db.clone());
             let mut mpartition_rows = HashMap::new();
@@ -4075,6 +4622,7 @@ impl MetaStore for RocksMetaStore {
         deactivate_ids: Vec<u64>,
         uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         new_replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         if uploaded_ids_and_sizes.is_empty() {
             return Err(CubeError::internal(format!(
@@ -4090,6 +4638,7 @@ impl MetaStore for RocksMetaStore {
                 batch_pipe,
                 true,
                 new_replay_handle_id,
+                encryption_key_id,
             )
         })
         .await
@@ -4101,6 +4650,7 @@ impl MetaStore for RocksMetaStore {
         deactivate_ids: Vec<u64>,
         uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         new_replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         if uploaded_ids_and_sizes.is_empty() {
             return Err(CubeError::internal(format!(
@@ -4116,6 +4666,7 @@ impl MetaStore for RocksMetaStore {
                 batch_pipe,
                 false,
                 new_replay_handle_id,
+                encryption_key_id,
             )
         })
         .await
@@ -4136,6 +4687,7 @@ impl MetaStore for RocksMetaStore {
                     batch_pipe,
                     false,
                     None,
+                    None,
                 )
             },
         )
@@ -4207,35 +4759,170 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
-    fn chunks_table(&self) -> ChunkMetaStoreTable {
-        ChunkMetaStoreTable {
-            rocks_meta_store: self.store.clone(),
-        }
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn get_expired_table_data(
+        &self,
+        table_id: u64,
+        cutoff: i64,
+    ) -> Result<(Vec<IdRow<Partition>>, Vec<IdRow<Chunk>>), CubeError> {
+        self.read_operation_out_of_queue("get_expired_table_data", move |db_ref| {
+            expired_table_data_impl(db_ref, table_id, cutoff)
+        })
+        .await
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn create_wal(&self, table_id: u64, row_count: usize) -> Result<IdRow<WAL>, CubeError> {
-        self.write_operation("create_wal", move |db_ref, batch_pipe| {
-            let rocks_wal = WALRocksTable::new(db_ref.clone());
-            TableRocksTable::new(db_ref.clone()).update_with_fn(
+    async fn drop_expired_table_data(
+        &self,
+        table_id: u64,
+        cutoff: i64,
+    ) -> Result<(u64, u64), CubeError> {
+        self.write_operation("drop_expired_table_data", move |db_ref, batch_pipe| {
+            let partition_table = PartitionRocksTable::new(db_ref.clone());
+            let chunk_table = ChunkRocksTable::new(db_ref.clone());
+            let (partitions, chunks) = expired_table_data_impl(db_ref, table_id, cutoff)?;
+
+            let expired_chunk_ids = chunks.iter().map(|c| c.get_id()).collect::<HashSet<_>>();
+            for chunk in chunks.iter() {
+                chunk_table.update_with_fn(chunk.get_id(), |c| c.deactivate(), batch_pipe)?;
+            }
+            for partition in partitions.iter() {
+                partition_table.update(
+                    partition.get_id(),
+                    partition.get_row().to_active(false),
+                    partition.get_row(),
+                    batch_pipe,
+                )?;
+                let empty = Partition::new_child(partition, None)
+                    .update_min_max_and_row_count(
+                        partition.get_row().get_min_val().clone(),
+                        partition.get_row().get_max_val().clone(),
+                        0,
+                        None,
+                        None,
+                    )
+                    .to_active(true);
+                let empty = partition_table.insert(empty, batch_pipe)?;
+                // Chunks which are not expired yet are kept with the same key range.
+                for chunk in chunk_table.get_rows_by_index(
+                    &ChunkIndexKey::ByPartitionId(partition.get_id()),
+                    &ChunkRocksIndex::PartitionId,
+                )? {
+                    if !expired_chunk_ids.contains(&chunk.get_id()) {
+                        chunk_table.update_with_fn(
+                            chunk.get_id(),
+                            |c| c.set_partition_id(empty.get_id()),
+                            batch_pipe,
+                        )?;
+                    }
+                }
+            }
+
+            Ok((partitions.len() as u64, chunks.len() as u64))
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn add_delete_marker(
+        &self,
+        table_id: u64,
+        predicate: DeletePredicate,
+    ) -> Result<IdRow<Table>, CubeError> {
+        self.write_operation("add_delete_marker", move |db_ref, batch_pipe| {
+            let entry = TableRocksTable::new(db_ref).update_with_fn(
                 table_id,
-                |t| t.update_has_data(true),
+                |t| t.add_delete_marker(predicate),
                 batch_pipe,
             )?;
 
-            let wal = WAL::new(table_id, row_count);
-            let id_row = rocks_wal.insert(wal, batch_pipe)?;
+            let table_to_move = entry.get_row().clone();
+            batch_pipe.set_post_commit_callback(move |metastore| {
+                metastore
+                    .cached_tables
+                    .update_table_by_id_or_reset(table_id, |tp| {
+                        tp.table = IdRow::new(tp.table.get_id(), table_to_move);
+                    });
+            });
 
-            Ok(id_row)
+            Ok(entry)
         })
         .await
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn get_wal(&self, wal_id: u64) -> Result<IdRow<WAL>, CubeError> {
-        self.read_operation("get_wal", move |db_ref| {
-            WALRocksTable::new(db_ref).get_row_or_not_found(wal_id)
-        })
+    async fn get_partitions_with_pending_deletes(
+        &self,
+        table_id: u64,
+    ) -> Result<Vec<IdRow<Partition>>, CubeError> {
+        self.read_operation_out_of_queue("get_partitions_with_pending_deletes", move |db_ref| {
+            let table = TableRocksTable::new(db_ref.clone()).get_row_or_not_found(table_id)?;
+            let (partitions, _) = pending_deletes_impl(db_ref, &table)?;
+            Ok(partitions)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn remove_applied_delete_markers(&self, table_id: u64) -> Result<u64, CubeError> {
+        self.write_operation(
+            "remove_applied_delete_markers",
+            move |db_ref, batch_pipe| {
+                let tables = TableRocksTable::new(db_ref.clone());
+                let table = tables.get_row_or_not_found(table_id)?;
+                let (_, min_applied) = pending_deletes_impl(db_ref, &table)?;
+                let updated = table.get_row().remove_applied_delete_markers(min_applied);
+                let removed =
+                    table.get_row().delete_markers().len() - updated.delete_markers().len();
+                if removed == 0 {
+                    return Ok(0);
+                }
+                let entry = tables.update(table_id, updated, table.get_row(), batch_pipe)?;
+
+                let table_to_move = entry.get_row().clone();
+                batch_pipe.set_post_commit_callback(move |metastore| {
+                    metastore
+                        .cached_tables
+                        .update_table_by_id_or_reset(table_id, |tp| {
+                            tp.table = IdRow::new(tp.table.get_id(), table_to_move);
+                        });
+                });
+
+                Ok(removed as u64)
+            },
+        )
+        .await
+    }
+
+    fn chunks_table(&self) -> ChunkMetaStoreTable {
+        ChunkMetaStoreTable {
+            rocks_meta_store: self.store.clone(),
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn create_wal(&self, table_id: u64, row_count: usize) -> Result<IdRow<WAL>, CubeError> {
+        self.write_operation("create_wal", move |db_ref, batch_pipe| {
+            let rocks_wal = WALRocksTable::new(db_ref.clone());
+            TableRocksTable::new(db_ref.clone()).update_with_fn(
+                table_id,
+                |t| t.update_has_data(true),
+                batch_pipe,
+            )?;
+
+            let wal = WAL::new(table_id, row_count);
+            let id_row = rocks_wal.insert(wal, batch_pipe)?;
+
+            Ok(id_row)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn get_wal(&self, wal_id: u64) -> Result<IdRow<WAL>, CubeError> {
+        self.read_operation("get_wal", move |db_ref| {
+            WALRocksTable::new(db_ref).get_row_or_not_found(wal_id)
+        })
         .await
     }
 
@@ -4981,6 +5668,7 @@ impl MetaStore for RocksMetaStore {
         new_partitions: Vec<(IdRow<Partition>, u64)>,
         new_partition_rows: Vec<u64>,
         initial_split: bool,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         assert_eq!(new_multi_partitions.len(), new_multi_partition_rows.len());
         assert_eq!(new_partition_rows.len(), new_partitions.len());
@@ -5000,6 +5688,7 @@ impl MetaStore for RocksMetaStore {
                 pipe,
                 &old_partitions,
                 &new_partitions,
+                &encryption_key_id,
                 |i, p| p.update_row_count(new_partition_rows[i]),
                 |_| Ok(()), // Concurrent 'DROP TABLE' might remove some partitions. That's ok.
                 |new_i| {
@@ -5153,6 +5842,48 @@ impl MetaStore for RocksMetaStore {
     async fn set_current_snapshot(&self, snapshot_id: u128) -> Result<(), CubeError> {
         self.store.set_current_snapshot(snapshot_id).await
     }
+
+    async fn backup_snapshot(
+        &self,
+        location: String,
+    ) -> Result<(BackupStoreSnapshot, Vec<BackupDataFile>), CubeError> {
+        self.store
+            .backup_to(&location, |db| {
+                let mut data_files = Vec::new();
+                for p in PartitionRocksTable::new(db.clone()).table_scan(db.snapshot)? {
+                    let p = p?;
+                    if p.row.has_main_table_file() {
+                        data_files.push(BackupDataFile {
+                            path: partition_file_name(p.id, p.row.suffix()),
+                            size: p.row.file_size(),
+                            encryption_key_id: p.row.encryption_key_id().clone(),
+                        });
+                    }
+                }
+
+                for c in ChunkRocksTable::new(db.clone()).table_scan(db.snapshot)? {
+                    let c = c?;
+                    if c.row.active() && c.row.uploaded() && !c.row.in_memory() {
+                        data_files.push(BackupDataFile {
+                            path: c.row.get_full_name(c.id),
+                            size: c.row.file_size(),
+                            encryption_key_id: c.row.encryption_key_id().clone(),
+                        });
+                    }
+                }
+
+                Ok(data_files)
+            })
+            .await
+    }
+
+    async fn restore_snapshot(
+        &self,
+        location: String,
+        snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        self.store.restore_from(&location, &snapshot).await
+    }
 }
 
 pub async fn deactivate_table_on_corrupt_data<'a, T: 'static>(
@@ -5244,6 +5975,30 @@ fn get_table_impl(
     Ok(table)
 }
 
+fn check_table_columns_can_be_altered(
+    table: &IdRow<Table>,
+    indexes: &[IdRow<Index>],
+) -> Result<(), CubeError> {
+    let table_name = table.get_row().get_table_name();
+    if table.get_row().select_statement().is_some() {
+        return Err(CubeError::user(format!(
+            "Columns of table '{}' are defined by its select statement and can't be altered",
+            table_name
+        )));
+    }
+    if let Some(index) = indexes
+        .iter()
+        .find(|i| i.get_row().multi_index_id().is_some())
+    {
+        return Err(CubeError::user(format!(
+            "Columns of table '{}' can't be altered as it's in partitioned index '{}'",
+            table_name,
+            index.get_row().get_name()
+        )));
+    }
+    Ok(())
+}
+
 fn get_default_index_impl(db_ref: DbTableRef, table_id: u64) -> Result<IdRow<Index>, CubeError> {
     let index = IndexRocksTable::new(db_ref);
     let indexes = index.get_rows_by_index(
@@ -5259,6 +6014,117 @@ fn get_default_index_impl(db_ref: DbTableRef, table_id: u64) -> Result<IdRow<Ind
         )))
 }
 
+/// Partitions of multi-indexes are skipped as their row counts are tracked by multi-partitions.
+fn expired_table_data_impl(
+    db_ref: DbTableRef,
+    table_id: u64,
+    cutoff: i64,
+) -> Result<(Vec<IdRow<Partition>>, Vec<IdRow<Chunk>>), CubeError> {
+    let table = TableRocksTable::new(db_ref.clone()).get_row_or_not_found(table_id)?;
+    let retention = match table.get_row().retention() {
+        Some(retention) => retention,
+        None => return Ok((Vec::new(), Vec::new())),
+    };
+    let index_table = IndexRocksTable::new(db_ref.clone());
+    let partition_table = PartitionRocksTable::new(db_ref.clone());
+    let chunk_table = ChunkRocksTable::new(db_ref);
+
+    let is_expired = |max: &Option<Row>| {
+        max.as_ref()
+            .map(|max| retention.is_expired(max, cutoff))
+            .unwrap_or(false)
+    };
+    let mut partitions = Vec::new();
+    let mut chunks = Vec::new();
+    let indexes = index_table
+        .get_rows_by_index(&IndexIndexKey::TableId(table_id), &IndexRocksIndex::TableID)?;
+    for index in indexes {
+        let index_partitions = partition_table.get_rows_by_index(
+            &PartitionIndexKey::ByIndexId(index.get_id()),
+            &PartitionRocksIndex::IndexId,
+        )?;
+        for partition in index_partitions {
+            let row = partition.get_row();
+            if !row.is_active() || row.multi_partition_id().is_some() {
+                continue;
+            }
+            for chunk in chunk_table.get_rows_by_index(
+                &ChunkIndexKey::ByPartitionId(partition.get_id()),
+                &ChunkRocksIndex::PartitionId,
+            )? {
+                if chunk.get_row().active() && is_expired(chunk.get_row().max()) {
+                    chunks.push(chunk);
+                }
+            }
+            if row.has_main_table_file() && is_expired(row.get_max()) {
+                partitions.push(partition);
+            }
+        }
+    }
+    Ok((partitions, chunks))
+}
+
+/// Returns active partitions with a data file which haven't applied all `DELETE` markers of the
+/// table, and the latest marker applied by all partitions and chunks of the table.
+fn pending_deletes_impl(
+    db_ref: DbTableRef,
+    table: &IdRow<Table>,
+) -> Result<(Vec<IdRow<Partition>>, u64), CubeError> {
+    let last_marker_id = table.get_row().last_delete_marker_id();
+    let index_table = IndexRocksTable::new(db_ref.clone());
+    let partition_table = PartitionRocksTable::new(db_ref.clone());
+    let chunk_table = ChunkRocksTable::new(db_ref);
+
+    let mut pending = Vec::new();
+    let mut min_applied = last_marker_id;
+    let indexes = index_table.get_rows_by_index(
+        &IndexIndexKey::TableId(table.get_id()),
+        &IndexRocksIndex::TableID,
+    )?;
+    for index in indexes {
+        let index_partitions = partition_table.get_rows_by_index(
+            &PartitionIndexKey::ByIndexId(index.get_id()),
+            &PartitionRocksIndex::IndexId,
+        )?;
+        for partition in index_partitions {
+            // Chunks of inactive partitions are still waiting for repartition.
+            for chunk in chunk_table.get_rows_by_index(
+                &ChunkIndexKey::ByPartitionId(partition.get_id()),
+                &ChunkRocksIndex::PartitionId,
+            )? {
+                if chunk.get_row().active() {
+                    min_applied = min_applied.min(chunk.get_row().deletes_applied());
+                }
+            }
+            let row = partition.get_row();
+            if row.has_main_table_file() {
+                min_applied = min_applied.min(row.deletes_applied());
+                if row.deletes_applied() < last_marker_id {
+                    pending.push(partition);
+                }
+            }
+        }
+    }
+    Ok((pending, min_applied))
+}
+
+/// Id of the last `DELETE` marker of the table the partition belongs to. Data written from now
+/// on isn't affected by existing markers.
+fn last_delete_marker_id_impl(db_ref: DbTableRef, partition_id: u64) -> Result<u64, CubeError> {
+    let Some(partition) = PartitionRocksTable::new(db_ref.clone()).get_row(partition_id)? else {
+        return Ok(0);
+    };
+    let Some(index) =
+        IndexRocksTable::new(db_ref.clone()).get_row(partition.get_row().get_index_id())?
+    else {
+        return Ok(0);
+    };
+    Ok(TableRocksTable::new(db_ref)
+        .get_row(index.get_row().table_id())?
+        .map(|t| t.get_row().last_delete_marker_id())
+        .unwrap_or(0))
+}
+
 /// Note that [current_active] and [new_active] are snapshots at some older point in time. The
 /// relevant partitions might be dropped or changed by the time this function runs. Implementation
 /// must take great care to avoid inconsistencies caused by this.
@@ -5267,6 +6133,7 @@ fn swap_active_partitions_impl(
     batch_pipe: &mut BatchPipe<'_, RocksMetaStore>,
     current_active: &[(IdRow<Partition>, Vec<IdRow<Chunk>>)],
     new_active: &[(IdRow<Partition>, u64)],
+    encryption_key_id: &Option<String>,
     mut update_new_partition_stats: impl FnMut(/*index*/ usize, &Partition) -> Partition,
     mut on_dropped_current_partition: impl FnMut(/*index*/ usize) -> Result<(), CubeError>,
     mut on_dropped_new_partition: impl FnMut(/*index*/ usize) -> Result<(), CubeError>,
@@ -5276,7 +6143,8 @@ fn swap_active_partitions_impl(
     let table = PartitionRocksTable::new(db_ref.clone());
     let chunk_table = ChunkRocksTable::new(db_ref.clone());
 
-    // Rows are compacted using unique key columns or aggregating index and totals don't match
+    // Rows are compacted using unique key columns or aggregating index, or purged by `DELETE`
+    // markers, and totals don't match
     let skip_row_count_sanity_check = if let Some(current) = current_active.first() {
         let current_partition = table
             .get_row(current.0.get_id())?
@@ -5288,6 +6156,7 @@ fn swap_active_partitions_impl(
         let table = table_table.get_row_or_not_found(index.get_row().table_id())?;
         index.get_row().get_type() == IndexType::Aggregate
             || table.get_row().unique_key_columns().is_some()
+            || !table.get_row().delete_markers().is_empty()
     } else {
         false
     };
@@ -5361,9 +6230,15 @@ fn swap_active_partitions_impl(
                 new_partition.get_row()
             )));
         }
-        let updated = update_new_partition_stats(i, new_partition.get_row())
-            .to_active(true)
-            .set_file_size(*new_file_size)?;
+        let updated = update_new_partition_stats(i, new_partition.get_row()).to_active(true);
+        // Partitions emptied by `DELETE` have no data file.
+        let updated = if updated.main_table_row_count == 0 {
+            updated
+        } else {
+            updated
+                .set_file_size(*new_file_size)?
+                .set_encryption_key_id(encryption_key_id.clone())
+        };
         activated_row_count += updated.main_table_row_count;
         table.update(
             new_partition.get_id(),
@@ -5417,10 +6292,11 @@ fn swap_active_partitions_impl(
 
 #[cfg(test)]
 mod tests {
-    use super::table::AggregateColumn;
+    use super::table::{AggregateColumn, DeleteComparison};
     use super::*;
     use crate::config::{init_test_logger, Config};
     use crate::remotefs::{LocalDirRemoteFs, RemoteFs};
+    use crate::table::TimestampValue;
     use cuberockstore::rocksdb::IteratorMode;
     use futures_timer::Delay;
     use std::thread::sleep;
@@ -5731,6 +6607,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
 
@@ -5754,6 +6632,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
 
@@ -5857,6 +6737,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let reg_partition = meta_store.get_partition(1).await?;
@@ -5891,6 +6773,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let stream_partition = meta_store.get_partition(2).await?;
@@ -5943,6 +6827,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
         let table2 = meta_store
@@ -5965,6 +6851,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
 
@@ -6026,6 +6914,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
         let index = meta_store.get_default_index(table.get_id()).await?;
@@ -6111,6 +7001,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let table1_id = table1.id;
@@ -6136,6 +7028,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .is_err());
@@ -6220,6 +7114,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let table1_id = table1.id;
@@ -6305,6 +7201,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
 
@@ -6377,6 +7275,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .is_err());
@@ -6401,6 +7301,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .is_err());
@@ -6428,6 +7330,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .is_err());
@@ -6489,6 +7393,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
 
@@ -6953,6 +7859,8 @@ mod tests {
                         None,
                         false,
                         None,
+                        None,
+                        None,
                     )
                     .await?;
                 services
@@ -7143,6 +8051,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let partition = meta_store.get_partition(1).await?;
@@ -7177,6 +8087,7 @@ mod tests {
                     source_ids.clone(),
                     vec![(dest_chunk.get_id(), Some(26))],
                     None,
+                    Some("writer".to_string()),
                 )
                 .await?;
 
@@ -7187,12 +8098,17 @@ mod tests {
 
             let ch = meta_store.get_chunk(dest_chunk.get_id()).await?;
             assert_eq!(ch.get_row().active(), true);
+            assert_eq!(
+                ch.get_row().encryption_key_id(),
+                &Some("writer".to_string())
+            );
 
             meta_store
                 .swap_chunks(
                     source_ids.clone(),
                     vec![(dest_chunk2.get_id(), Some(26))],
                     None,
+                    None,
                 )
                 .await
                 .expect_err("Source chunk 1 is not active when swapping of (1, 2) to (3) chunks");
@@ -7216,6 +8132,7 @@ mod tests {
                     source_ids.clone(),
                     vec![(dest_chunk.get_id(), Some(26))],
                     None,
+                    None,
                 )
                 .await
                 .expect_err(
@@ -7350,6 +8267,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await?;
             let partition = meta_store.get_partition(1).await?;
@@ -7376,6 +8295,7 @@ mod tests {
                     vec![(partition.clone(), source_chunks.clone())],
                     vec![(dest_partition.clone(), 10)],
                     vec![(26, (None, None), (None, None))],
+                    None,
                 )
                 .await?;
             assert_eq!(
@@ -7421,6 +8341,7 @@ mod tests {
                     vec![(partition, source_chunks.clone())],
                     vec![(dest_partition.clone(), 10)],
                     vec![(26, (None, None), (None, None))],
+                    None,
                 )
                 .await
             {
@@ -7461,6 +8382,7 @@ mod tests {
                     vec![(partition, source_chunks.clone())],
                     vec![(dest_partition.clone(), 10)],
                     vec![(dest_row_count, (None, None), (None, None))],
+                    None,
                 )
                 .await
             {
@@ -7498,6 +8420,7 @@ mod tests {
                     vec![(partition.clone(), source_chunks.clone())],
                     vec![(partition.clone(), 10)],
                     vec![(dest_row_count, (None, None), (None, None))],
+                    None,
                 )
                 .await
             {
@@ -7515,6 +8438,428 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn drop_expired_table_data() -> Result<(), CubeError> {
+        let config = Config::test("drop_expired_table_data");
+        let store_path = env::current_dir()?.join("drop_expired_table_data_test-local");
+        let remote_store_path = env::current_dir()?.join("drop_expired_table_data_test-remote");
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+        let remote_fs = LocalDirRemoteFs::new(Some(remote_store_path.clone()), store_path.clone());
+        {
+            let meta_store = RocksMetaStore::new(
+                store_path.join("metastore").as_path(),
+                BaseRocksStoreFs::new_for_metastore(remote_fs.clone(), config.config_obj()),
+                config.config_obj(),
+            )?;
+            meta_store.create_schema("foo".to_string(), false).await?;
+            let cols = vec![
+                Column::new("ts".to_string(), ColumnType::Timestamp, 0),
+                Column::new("name".to_string(), ColumnType::String, 1),
+            ];
+            let create_table = |name: &str, retention_column: &str, indexes: Vec<IndexDef>| {
+                meta_store.create_table(
+                    "foo".to_string(),
+                    name.to_string(),
+                    cols.clone(),
+                    None,
+                    None,
+                    indexes,
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    Some((retention_column.to_string(), 3600)),
+                    None,
+                )
+            };
+            let err = create_table("by_name", "name", vec![]).await.unwrap_err();
+            assert!(err.message.contains("should be of timestamp type"));
+            let err = create_table(
+                "by_name_index",
+                "ts",
+                vec![IndexDef {
+                    name: "by_name".to_string(),
+                    columns: vec!["name".to_string(), "ts".to_string()],
+                    multi_index: None,
+                    index_type: IndexType::Regular,
+                }],
+            )
+            .await
+            .unwrap_err();
+            assert!(err
+                .message
+                .contains("Retention column 'ts' should be the first column of index 'by_name'"));
+
+            let table = create_table("bar", "ts", vec![]).await?;
+            let table_id = table.get_id();
+            let index = meta_store.get_default_index(table_id).await?;
+            let partition = meta_store
+                .get_active_partitions_by_index_id(index.get_id())
+                .await?
+                .remove(0);
+
+            let now = Utc::now();
+            let row = |ts: DateTime<Utc>| {
+                Some(Row::new(vec![
+                    TableValue::Timestamp(TimestampValue::new(ts.timestamp_nanos_opt().unwrap())),
+                    TableValue::String("a".to_string()),
+                ]))
+            };
+            let expired = now - chrono::Duration::hours(2);
+            let cutoff = table
+                .get_row()
+                .retention()
+                .as_ref()
+                .unwrap()
+                .expiration_cutoff(now);
+
+            let create_uploaded_chunk = |partition_id: u64, rows: usize, max: DateTime<Utc>| {
+                let meta_store = &meta_store;
+                async move {
+                    let chunk = meta_store
+                        .create_chunk(partition_id, rows, row(expired), row(max), false)
+                        .await?;
+                    meta_store.chunk_uploaded(chunk.get_id()).await
+                }
+            };
+            let compacted = create_uploaded_chunk(partition.get_id(), 10, expired).await?;
+            let fresh = create_uploaded_chunk(partition.get_id(), 5, now).await?;
+            let dest_partition = meta_store
+                .create_partition(Partition::new_child(&partition, None))
+                .await?;
+            meta_store
+                .swap_active_partitions(
+                    vec![(partition.clone(), vec![compacted.clone()])],
+                    vec![(dest_partition.clone(), 10)],
+                    vec![(10, (None, None), (row(expired), row(expired)))],
+                    None,
+                )
+                .await?;
+            let expired_chunk = create_uploaded_chunk(dest_partition.get_id(), 3, expired).await?;
+
+            let (partitions, chunks) = meta_store.get_expired_table_data(table_id, cutoff).await?;
+            assert_eq!(
+                partitions.iter().map(|p| p.get_id()).collect_vec(),
+                vec![dest_partition.get_id()]
+            );
+            assert_eq!(
+                chunks.iter().map(|c| c.get_id()).collect_vec(),
+                vec![expired_chunk.get_id()]
+            );
+
+            assert_eq!(
+                meta_store.drop_expired_table_data(table_id, cutoff).await?,
+                (1, 1)
+            );
+            assert!(!meta_store
+                .get_partition(dest_partition.get_id())
+                .await?
+                .get_row()
+                .is_active());
+            let active = meta_store
+                .get_active_partitions_by_index_id(index.get_id())
+                .await?;
+            assert_eq!(active.len(), 1);
+            assert_eq!(
+                active[0].get_row().parent_partition_id(),
+                &Some(dest_partition.get_id())
+            );
+            assert_eq!(active[0].get_row().main_table_row_count(), 0);
+            assert_eq!(active[0].get_row().get_min_val(), &None);
+            assert_eq!(active[0].get_row().get_max_val(), &None);
+            let fresh = meta_store.get_chunk(fresh.get_id()).await?;
+            assert!(fresh.get_row().active());
+            assert_eq!(fresh.get_row().get_partition_id(), active[0].get_id());
+            assert!(!meta_store
+                .get_chunk(expired_chunk.get_id())
+                .await?
+                .get_row()
+                .active());
+
+            let (partitions, chunks) = meta_store.get_expired_table_data(table_id, cutoff).await?;
+            assert!(partitions.is_empty());
+            assert!(chunks.is_empty());
+        }
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_markers() -> Result<(), CubeError> {
+        let config = Config::test("delete_markers");
+        let store_path = env::current_dir()?.join("delete_markers_test-local");
+        let remote_store_path = env::current_dir()?.join("delete_markers_test-remote");
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+        let remote_fs = LocalDirRemoteFs::new(Some(remote_store_path.clone()), store_path.clone());
+        {
+            let meta_store = RocksMetaStore::new(
+                store_path.join("metastore").as_path(),
+                BaseRocksStoreFs::new_for_metastore(remote_fs.clone(), config.config_obj()),
+                config.config_obj(),
+            )?;
+            meta_store.create_schema("foo".to_string(), false).await?;
+            let cols = vec![
+                Column::new("id".to_string(), ColumnType::Int, 0),
+                Column::new("name".to_string(), ColumnType::String, 1),
+            ];
+            let table = meta_store
+                .create_table(
+                    "foo".to_string(),
+                    "bar".to_string(),
+                    cols,
+                    None,
+                    None,
+                    vec![],
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+            let table_id = table.get_id();
+            let index = meta_store.get_default_index(table_id).await?;
+            let partition = meta_store
+                .get_active_partitions_by_index_id(index.get_id())
+                .await?
+                .remove(0);
+            let predicate = |id: i64| DeletePredicate::Compare {
+                column: "id".to_string(),
+                op: DeleteComparison::Eq,
+                value: TableValue::Int(id),
+            };
+            let create_active_chunk = |rows: usize| {
+                let meta_store = &meta_store;
+                let partition_id = partition.get_id();
+                async move {
+                    let chunk = meta_store
+                        .create_chunk(partition_id, rows, None, None, false)
+                        .await?;
+                    meta_store
+                        .activate_chunks(table_id, vec![(chunk.get_id(), None)], None, None)
+                        .await?;
+                    meta_store.get_chunk(chunk.get_id()).await
+                }
+            };
+
+            let old_chunk = create_active_chunk(10).await?;
+            assert_eq!(old_chunk.get_row().deletes_applied(), 0);
+            let table = meta_store.add_delete_marker(table_id, predicate(1)).await?;
+            assert_eq!(table.get_row().last_delete_marker_id(), 1);
+            assert_eq!(table.get_row().pending_delete_markers(0).len(), 1);
+            let new_chunk = create_active_chunk(5).await?;
+            assert_eq!(new_chunk.get_row().deletes_applied(), 1);
+            assert!(meta_store
+                .get_partitions_with_pending_deletes(table_id)
+                .await?
+                .is_empty());
+
+            // Chunks with different markers applied hold rows of different generations.
+            let merged = meta_store
+                .create_chunk(partition.get_id(), 15, None, None, false)
+                .await?;
+            let err = meta_store
+                .swap_chunks(
+                    vec![old_chunk.get_id(), new_chunk.get_id()],
+                    vec![(merged.get_id(), None)],
+                    None,
+                    None,
+                )
+                .await
+                .unwrap_err();
+            assert!(err.message.contains("applied different delete markers"));
+
+            let dest_partition = meta_store
+                .create_partition(Partition::new_child(&partition, None).set_deletes_applied(1))
+                .await?;
+            meta_store
+                .swap_active_partitions(
+                    vec![(partition.clone(), vec![old_chunk.clone()])],
+                    vec![(dest_partition.clone(), 8)],
+                    vec![(8, (None, None), (None, None))],
+                    None,
+                )
+                .await?;
+            // Nothing to purge until a marker newer than the partition data is added.
+            assert!(meta_store
+                .get_partitions_with_pending_deletes(table_id)
+                .await?
+                .is_empty());
+            assert_eq!(meta_store.remove_applied_delete_markers(table_id).await?, 1);
+            assert!(meta_store
+                .get_table_by_id(table_id)
+                .await?
+                .get_row()
+                .delete_markers()
+                .is_empty());
+
+            meta_store.add_delete_marker(table_id, predicate(2)).await?;
+            let pending = meta_store
+                .get_partitions_with_pending_deletes(table_id)
+                .await?;
+            assert_eq!(
+                pending.iter().map(|p| p.get_id()).collect_vec(),
+                vec![dest_partition.get_id()]
+            );
+            assert_eq!(meta_store.remove_applied_delete_markers(table_id).await?, 0);
+            let table = meta_store.get_table_by_id(table_id).await?;
+            assert_eq!(table.get_row().last_delete_marker_id(), 2);
+            assert_eq!(table.get_row().delete_markers().len(), 1);
+        }
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn alter_table() -> Result<(), CubeError> {
+        let config = Config::test("alter_table");
+        let store_path = env::current_dir()?.join("alter_table_test-local");
+        let remote_store_path = env::current_dir()?.join("alter_table_test-remote");
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+        let remote_fs = LocalDirRemoteFs::new(Some(remote_store_path.clone()), store_path.clone());
+        {
+            let meta_store = RocksMetaStore::new(
+                store_path.join("metastore").as_path(),
+                BaseRocksStoreFs::new_for_metastore(remote_fs.clone(), config.config_obj()),
+                config.config_obj(),
+            )?;
+            meta_store.create_schema("foo".to_string(), false).await?;
+            meta_store.create_schema("bar".to_string(), false).await?;
+            let mut tables = Vec::new();
+            for name in ["t1", "t2"] {
+                let table = meta_store
+                    .create_table(
+                        "foo".to_string(),
+                        name.to_string(),
+                        vec![
+                            Column::new("id".to_string(), ColumnType::Int, 0),
+                            Column::new("name".to_string(), ColumnType::String, 1),
+                        ],
+                        None,
+                        None,
+                        vec![],
+                        true,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        false,
+                        None,
+                        None,
+                        None,
+                    )
+                    .await?;
+                tables.push(table.get_id());
+            }
+            let table_id = tables[0];
+
+            let table = meta_store
+                .add_table_column(table_id, "amount".to_string(), ColumnType::Float)
+                .await?;
+            assert_eq!(table.get_row().get_columns()[2].get_name(), "amount");
+            let index = meta_store.get_default_index(table_id).await?;
+            assert_eq!(index.get_row().get_columns().len(), 3);
+            assert_eq!(index.get_row().sort_key_size(), 2);
+            let err = meta_store
+                .add_table_column(table_id, "amount".to_string(), ColumnType::Int)
+                .await
+                .unwrap_err();
+            assert!(err.message.contains("already exists"));
+
+            // Sort key columns define the order of existing data.
+            let err = meta_store
+                .drop_table_column(table_id, "name".to_string())
+                .await
+                .unwrap_err();
+            assert!(err.message.contains("used by index"));
+            let table = meta_store
+                .drop_table_column(table_id, "amount".to_string())
+                .await?;
+            assert_eq!(table.get_row().get_columns().len(), 2);
+            assert_eq!(
+                table.get_row().dropped_columns(),
+                &vec!["amount".to_string()]
+            );
+            let index = meta_store.get_default_index(table_id).await?;
+            assert_eq!(index.get_row().get_columns().len(), 2);
+            let err = meta_store
+                .add_table_column(table_id, "amount".to_string(), ColumnType::Float)
+                .await
+                .unwrap_err();
+            assert!(err.message.contains("can't be added again"));
+
+            let err = meta_store
+                .rename_table(table_id, "foo".to_string(), "t2".to_string())
+                .await
+                .unwrap_err();
+            assert!(err.message.contains("already exists"));
+            meta_store
+                .rename_table(table_id, "bar".to_string(), "t3".to_string())
+                .await?;
+            assert_eq!(
+                meta_store
+                    .get_table("bar".to_string(), "t3".to_string())
+                    .await?
+                    .get_id(),
+                table_id
+            );
+            assert!(meta_store
+                .get_table("foo".to_string(), "t1".to_string())
+                .await
+                .is_err());
+
+            meta_store.swap_tables(tables[0], tables[1]).await?;
+            assert_eq!(
+                meta_store
+                    .get_table("foo".to_string(), "t2".to_string())
+                    .await?
+                    .get_id(),
+                tables[0]
+            );
+            assert_eq!(
+                meta_store
+                    .get_table("bar".to_string(), "t3".to_string())
+                    .await?
+                    .get_id(),
+                tables[1]
+            );
+            assert!(meta_store.swap_tables(tables[0], tables[0]).await.is_err());
+        }
+        let _ = fs::remove_dir_all(store_path.clone());
+        let _ = fs::remove_dir_all(remote_store_path.clone());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn job_priority_test() -> Result<(), CubeError> {
         let config = Config::test("job_priority_test");
@@ -7961,6 +9306,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await?;
 
@@ -8001,6 +9348,7 @@ impl RocksMetaStore {
         batch_pipe: &mut BatchPipe<'_, RocksMetaStore>,
         check_rows: bool,
         new_replay_handle_id: Option<u64>,
+        encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         trace!(
             "Swapping chunks: deactivating ({}), activating ({})",
@@ -8011,6 +9359,8 @@ impl RocksMetaStore {
         let mut partition_to_row_diffs = HashMap::<u64, i64>::new();
         let mut deactivated_row_count = 0;
         let mut activated_row_count = 0;
+        // New chunks hold the same rows, so they applied the same `DELETE` markers.
+        let mut deletes_applied = None;
         for id in deactivate_ids.iter() {
             let chunk = chunks.get_row_or_not_found(*id)?;
             if !chunk.get_row().active() {
@@ -8021,6 +9371,17 @@ impl RocksMetaStore {
                     uploaded_ids_and_sizes.iter().map(|(id, _)| id).join(", ")
                 )));
             }
+            match deletes_applied {
+                None => deletes_applied = Some(chunk.get_row().deletes_applied()),
+                Some(d) if d != chunk.get_row().deletes_applied() => {
+                    return Err(CubeError::internal(format!(
+                        "Source chunks applied different delete markers when swapping of ({}) to ({}) chunks",
+                        deactivate_ids.iter().join(", "),
+                        uploaded_ids_and_sizes.iter().map(|(id, _)| id).join(", ")
+                    )));
+                }
+                Some(_) => {}
+            }
             deactivated_row_count += chunk.get_row().row_count;
             *partition_to_row_diffs
                 .entry(chunk.get_row().partition_id)
@@ -8041,12 +9402,20 @@ impl RocksMetaStore {
             *partition_to_row_diffs
                 .entry(chunk.get_row().partition_id)
                 .or_default() += chunk.get_row().row_count as i64;
+            let chunk_deletes_applied = match deletes_applied {
+                Some(d) => d,
+                None => last_delete_marker_id_impl(db_ref.clone(), chunk.get_row().partition_id)?,
+            };
             chunks.update_with_res_fn(
                 *id,
                 |row| {
-                    let mut updated = row.set_uploaded(true);
+                    let mut updated = row
+                        .set_uploaded(true)
+                        .set_deletes_applied(chunk_deletes_applied);
                     if let Some(file_size) = file_size {
-                        updated = updated.set_file_size(*file_size)?;
+                        updated = updated
+                            .set_file_size(*file_size)?
+                            .set_encryption_key_id(encryption_key_id.clone());
                     }
                     updated = updated.set_replay_handle_id(new_replay_handle_id);
                     Ok(updated)
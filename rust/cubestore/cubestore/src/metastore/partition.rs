@@ -35,6 +35,8 @@ impl Partition {
             file_size: None,
             min: None,
             max: None,
+            encryption_key_id: None,
+            deletes_applied: 0,
         }
     }
 
@@ -57,6 +59,8 @@ impl Partition {
             file_size: None,
             min: None,
             max: None,
+            encryption_key_id: None,
+            deletes_applied: 0,
         }
     }
     pub fn get_min_val(&self) -> &Option<Row> {
@@ -144,6 +148,26 @@ impl Partition {
         Ok(p)
     }
 
+    pub fn encryption_key_id(&self) -> &Option<String> {
+        &self.encryption_key_id
+    }
+
+    pub fn set_encryption_key_id(&self, encryption_key_id: Option<String>) -> Self {
+        let mut p = self.clone();
+        p.encryption_key_id = encryption_key_id;
+        p
+    }
+
+    pub fn deletes_applied(&self) -> u64 {
+        self.deletes_applied
+    }
+
+    pub fn set_deletes_applied(&self, deletes_applied: u64) -> Self {
+        let mut p = self.clone();
+        p.deletes_applied = deletes_applied;
+        p
+    }
+
     pub fn get_index_id(&self) -> u64 {
         self.index_id
     }
@@ -410,6 +410,8 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
             ),
         )
     }
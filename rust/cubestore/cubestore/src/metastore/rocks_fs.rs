@@ -48,6 +48,18 @@ pub trait MetaStoreFs: Send + Sync {
     ) -> Result<(), CubeError>;
     async fn get_snapshots_list(&self) -> Result<Vec<SnapshotInfo>, CubeError>;
     async fn write_metastore_current(&self, remote_path: &str) -> Result<(), CubeError>;
+    /// Copies files of the snapshot into `location`, returns names of the copied files.
+    async fn copy_snapshot_files(
+        &self,
+        snapshot: u128,
+        location: &str,
+    ) -> Result<Vec<String>, CubeError>;
+    /// Creates a new snapshot from files in `location` and makes it current.
+    async fn restore_snapshot_files(
+        &self,
+        location: &str,
+        files: &[String],
+    ) -> Result<u128, CubeError>;
 }
 
 #[derive(Clone)]
@@ -396,6 +408,14 @@ impl BaseRocksStoreFs {
         Ok(last_metastore_snapshot)
     }
 
+    async fn copy_remote_file(&self, from: String, to: String) -> Result<u64, CubeError> {
+        let local = self.remote_fs.download_file(from, None).await?;
+        let temp_upload_path = self.remote_fs.temp_upload_path(to.clone()).await?;
+        fs::copy(&local, &temp_upload_path).await?;
+
+        self.remote_fs.upload_file(temp_upload_path, to).await
+    }
+
     pub async fn files_to_load(&self, snapshot: u128) -> Result<Vec<(String, u64)>, CubeError> {
         let res = self
             .remote_fs
@@ -589,6 +609,63 @@ impl MetaStoreFs for BaseRocksStoreFs {
             .await?;
         Ok(())
     }
+
+    async fn copy_snapshot_files(
+        &self,
+        snapshot: u128,
+        location: &str,
+    ) -> Result<Vec<String>, CubeError> {
+        let snapshot_prefix = format!("{}-{}/", self.name, snapshot);
+        let to_copy = self.files_to_load(snapshot).await?;
+        if to_copy.is_empty() {
+            return Err(CubeError::internal(format!(
+                "Files of {} snapshot {} are not found",
+                self.name, snapshot
+            )));
+        }
+
+        let mut files = Vec::with_capacity(to_copy.len());
+        for (remote_path, _) in to_copy {
+            let file_name = remote_path
+                .strip_prefix(&snapshot_prefix)
+                .ok_or_else(|| {
+                    CubeError::internal(format!(
+                        "Unexpected {} snapshot file: {}",
+                        self.name, remote_path
+                    ))
+                })?
+                .to_string();
+
+            self.copy_remote_file(remote_path, format!("{}/{}", location, file_name))
+                .await?;
+            files.push(file_name);
+        }
+
+        Ok(files)
+    }
+
+    async fn restore_snapshot_files(
+        &self,
+        location: &str,
+        files: &[String],
+    ) -> Result<u128, CubeError> {
+        let snapshot = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let remote_path = format!("{}-{}", self.name, snapshot);
+
+        for file in files {
+            self.copy_remote_file(
+                format!("{}/{}", location, file),
+                format!("{}/{}", remote_path, file),
+            )
+            .await?;
+        }
+
+        self.write_metastore_current(&remote_path).await?;
+
+        Ok(snapshot)
+    }
 }
 
 crate::di_service!(BaseRocksStoreFs, [MetaStoreFs]);
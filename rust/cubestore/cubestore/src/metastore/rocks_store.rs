@@ -19,6 +19,7 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::io::{Cursor, Read, Write};
 
+use crate::metastore::backup::BackupStoreSnapshot;
 use crate::metastore::snapshot_info::SnapshotInfo;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use cuberockstore::rocksdb;
@@ -1332,30 +1333,126 @@ impl RocksStore {
                 self.prepare_checkpoint(&check_point_time).await?
             };
 
-            let details = self.details.clone();
-            let config = self.config.clone();
-            let path_to_move = checkpoint_path.clone();
-            let checkpoint_last_seq =
-                cube_ext::spawn_blocking(move || -> Result<u64, CubeError> {
-                    let snap_db = details.open_readonly_db(&path_to_move, &config)?;
-                    Ok(snap_db.latest_sequence_number())
-                })
-                .await??;
-
-            self.metastore_fs
-                .upload_checkpoint(remote_path, checkpoint_path)
+            self.finish_check_point_upload(remote_path, checkpoint_path)
                 .await?;
-            let mut snapshot_uploaded = self.snapshot_uploaded.write().await;
-            *snapshot_uploaded = true;
-            let mut last_uploaded_check_seq = self.last_check_seq.write().await;
-            *last_uploaded_check_seq = checkpoint_last_seq;
-            let mut last_uploaded_seq = self.last_upload_seq.write().await;
-            *last_uploaded_seq = checkpoint_last_seq;
-            self.write_completed_notify.notify_waiters();
         }
         Ok(())
     }
 
+    /// Uploads a check point of exactly the state that `f` has read, returns the snapshot id
+    /// together with the result of `f`. Unlike `upload_check_point` it fails when snapshot
+    /// uploads were stopped by switching the current snapshot.
+    pub async fn upload_consistent_check_point<F, R>(&self, f: F) -> Result<(u128, R), CubeError>
+    where
+        F: for<'a> FnOnce(DbTableRef<'a>) -> Result<R, CubeError> + Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        info!(
+            "Uploading {} consistent check point",
+            self.details.get_name()
+        );
+        let upload_stopped = self.snapshots_upload_stopped.lock().await;
+        if *upload_stopped {
+            return Err(CubeError::user(format!(
+                "Uploading of {} snapshots is stopped after switching the current snapshot, restart is required",
+                self.details.get_name()
+            )));
+        }
+
+        let mut check_point_time = self.last_checkpoint_time.write().await;
+        *check_point_time = SystemTime::now();
+
+        let snapshot_id = check_point_time
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let remote_path = self.get_store_path(&check_point_time);
+        let checkpoint_path = self.db.path().join("..").join(remote_path.clone());
+
+        let path_to_move = checkpoint_path.clone();
+        // Read operations are serialized with writes, nothing can change the state between `f`
+        // and the check point
+        let result = self
+            .read_operation("upload_consistent_check_point", move |db_ref| {
+                let result = f(db_ref.clone())?;
+
+                let checkpoint = Checkpoint::new(db_ref.db)?;
+                checkpoint.create_checkpoint(path_to_move.as_path())?;
+
+                Ok(result)
+            })
+            .await?;
+
+        self.finish_check_point_upload(remote_path, checkpoint_path)
+            .await?;
+
+        Ok((snapshot_id, result))
+    }
+
+    async fn finish_check_point_upload(
+        &self,
+        remote_path: String,
+        checkpoint_path: PathBuf,
+    ) -> Result<(), CubeError> {
+        let details = self.details.clone();
+        let config = self.config.clone();
+        let path_to_move = checkpoint_path.clone();
+        let checkpoint_last_seq = cube_ext::spawn_blocking(move || -> Result<u64, CubeError> {
+            let snap_db = details.open_readonly_db(&path_to_move, &config)?;
+            Ok(snap_db.latest_sequence_number())
+        })
+        .await??;
+
+        self.metastore_fs
+            .upload_checkpoint(remote_path, checkpoint_path)
+            .await?;
+        let mut snapshot_uploaded = self.snapshot_uploaded.write().await;
+        *snapshot_uploaded = true;
+        let mut last_uploaded_check_seq = self.last_check_seq.write().await;
+        *last_uploaded_check_seq = checkpoint_last_seq;
+        let mut last_uploaded_seq = self.last_upload_seq.write().await;
+        *last_uploaded_seq = checkpoint_last_seq;
+        self.write_completed_notify.notify_waiters();
+
+        Ok(())
+    }
+
+    /// Uploads a consistent check point and copies its files to `location` on the remote fs.
+    pub async fn backup_to<F, R>(
+        &self,
+        location: &str,
+        f: F,
+    ) -> Result<(BackupStoreSnapshot, R), CubeError>
+    where
+        F: for<'a> FnOnce(DbTableRef<'a>) -> Result<R, CubeError> + Send + Sync + 'static,
+        R: Send + Sync + 'static,
+    {
+        let (snapshot_id, result) = self.upload_consistent_check_point(f).await?;
+        let files = self
+            .metastore_fs
+            .copy_snapshot_files(snapshot_id, location)
+            .await?;
+
+        Ok((BackupStoreSnapshot { snapshot_id, files }, result))
+    }
+
+    /// Makes a new snapshot from the backup files current, it's loaded after the restart, see
+    /// `set_current_snapshot`.
+    pub async fn restore_from(
+        &self,
+        location: &str,
+        snapshot: &BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        let mut upload_stopped = self.snapshots_upload_stopped.lock().await;
+
+        let snapshot_id = self
+            .metastore_fs
+            .restore_snapshot_files(location, &snapshot.files)
+            .await?;
+
+        *upload_stopped = true;
+        Ok(snapshot_id)
+    }
+
     async fn last_upload_seq(&self) -> u64 {
         *self.last_upload_seq.read().await
     }
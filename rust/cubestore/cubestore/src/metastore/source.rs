@@ -2,10 +2,12 @@ use super::{IndexId, RocksSecondaryIndex, TableId};
 use crate::base_rocks_secondary_index;
 use crate::metastore::{DataFrameValue, RocksEntity};
 use crate::rocks_table_impl;
+use crate::CubeError;
 use byteorder::{BigEndian, WriteBytesExt};
 
 use serde::{Deserialize, Deserializer, Serialize};
 use std::io::{Cursor, Write};
+use std::str::FromStr;
 
 #[derive(Clone, Serialize, Deserialize, Debug, Hash)]
 pub enum SourceCredentials {
@@ -19,7 +21,78 @@ pub enum SourceCredentials {
         password: Option<String>,
         host: String,
         use_ssl: bool,
+        #[serde(default)]
+        format: KafkaPayloadFormat,
+        /// Required for the `avro` and `protobuf` formats.
+        #[serde(default)]
+        schema_registry: Option<SchemaRegistryConfig>,
     },
+    /// Server-Sent Events or NDJSON streamed over a long-lived HTTP response.
+    Http {
+        user: Option<String>,
+        password: Option<String>,
+        url: String,
+        format: HttpStreamFormat,
+    },
+    /// Tails files uploaded under the prefix of the remote fs.
+    Files {
+        prefix: String,
+        poll_interval_secs: u64,
+    },
+}
+
+/// Encoding of kafka message keys and payloads. Avro and protobuf messages are expected in the
+/// Confluent wire format, i.e. prefixed with the id of the schema in the registry.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Hash, PartialEq, Eq, Default)]
+pub enum KafkaPayloadFormat {
+    #[default]
+    Json,
+    Avro,
+    Protobuf,
+}
+
+impl FromStr for KafkaPayloadFormat {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(KafkaPayloadFormat::Json),
+            "avro" => Ok(KafkaPayloadFormat::Avro),
+            "protobuf" => Ok(KafkaPayloadFormat::Protobuf),
+            _ => Err(CubeError::user(format!(
+                "Unsupported kafka format '{}', expected: json, avro, protobuf",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Hash, PartialEq, Eq)]
+pub enum HttpStreamFormat {
+    Sse,
+    Ndjson,
+}
+
+impl FromStr for HttpStreamFormat {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sse" => Ok(HttpStreamFormat::Sse),
+            "ndjson" => Ok(HttpStreamFormat::Ndjson),
+            _ => Err(CubeError::user(format!(
+                "Unsupported http stream format '{}', expected: sse, ndjson",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Hash, PartialEq, Eq)]
+pub struct SchemaRegistryConfig {
+    pub url: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
 }
 
 impl DataFrameValue<String> for SourceCredentials {
@@ -6,6 +6,7 @@ use crate::metastore::{IdRow, ImportFormat, RocksEntity, Schema};
 use crate::queryplanner::udfs::aggregate_udf_by_kind;
 use crate::queryplanner::udfs::CubeAggregateUDFKind;
 use crate::rocks_table_impl;
+use crate::table::{Row, TableValue};
 use crate::{base_rocks_secondary_index, CubeError};
 use byteorder::{BigEndian, WriteBytesExt};
 use chrono::DateTime;
@@ -86,6 +87,25 @@ impl AggregateColumn {
                 "MERGE",
                 aggregate_udf_by_kind(CubeAggregateUDFKind::MergeHll),
             ),
+            // Ingestion stores 1 for every counted value, see `ChunkStore::build_index_chunks`,
+            // so merges only add the counts up.
+            AggregateFunction::COUNT => ("COUNT", AggregateUDF::new_from_impl(Sum::new())),
+            AggregateFunction::ANY_VALUE => (
+                "ANY_VALUE",
+                aggregate_udf_by_kind(CubeAggregateUDFKind::AnyValue),
+            ),
+            AggregateFunction::LAST_VALUE => (
+                "LAST_VALUE",
+                aggregate_udf_by_kind(CubeAggregateUDFKind::LastValue),
+            ),
+            AggregateFunction::MERGE_THETA => (
+                "MERGE_THETA",
+                aggregate_udf_by_kind(CubeAggregateUDFKind::MergeTheta),
+            ),
+            AggregateFunction::MERGE_TDIGEST => (
+                "MERGE_TDIGEST",
+                aggregate_udf_by_kind(CubeAggregateUDFKind::MergeTDigest),
+            ),
         };
 
         // TODO upgrade DF: Understand what effect the choice of alias value has.
@@ -128,6 +148,197 @@ impl DataFrameValue<String> for Option<StreamOffset> {
     }
 }
 
+/// Rows are kept while the timestamp in the retention column is not older than the retention
+/// period. Retention column leads every index of a table, so partitions and chunks which are
+/// expired as a whole can be found by their min/max rows.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
+pub struct TableRetention {
+    column_index: u64,
+    period_secs: u64,
+}
+
+impl TableRetention {
+    pub fn new(column_index: u64, period_secs: u64) -> Self {
+        Self {
+            column_index,
+            period_secs,
+        }
+    }
+
+    pub fn column_index(&self) -> u64 {
+        self.column_index
+    }
+
+    pub fn period_secs(&self) -> u64 {
+        self.period_secs
+    }
+
+    /// Rows with retention column values below the returned timestamp (in nanoseconds) are
+    /// expired.
+    pub fn expiration_cutoff(&self, now: DateTime<Utc>) -> i64 {
+        let period_nanos = i64::try_from(self.period_secs)
+            .unwrap_or(i64::MAX)
+            .saturating_mul(1_000_000_000);
+        now.timestamp_nanos_opt()
+            .unwrap_or(i64::MAX)
+            .saturating_sub(period_nanos)
+    }
+
+    /// Whether all rows up to `max`, a sort key row with the retention column first, are
+    /// expired. Rows with NULL in the retention column never expire.
+    pub fn is_expired(&self, max: &Row, cutoff: i64) -> bool {
+        match max.values().first() {
+            Some(TableValue::Timestamp(t)) => t.get_time_stamp() < cutoff,
+            _ => false,
+        }
+    }
+}
+
+impl DataFrameValue<String> for Option<TableRetention> {
+    fn value(v: &Self) -> String {
+        v.as_ref()
+            .map(|r| format!("{:?}", r))
+            .unwrap_or("NULL".to_string())
+    }
+}
+
+/// Settings of parquet files written for the table partitions and chunks.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd, Default)]
+pub struct TableParquetSettings {
+    /// Codec with an optional level, e.g. `zstd(3)`. See `table::parquet::parse_compression`.
+    #[serde(default)]
+    compression: Option<String>,
+    /// Columns with bloom filters written for every row group.
+    #[serde(default)]
+    bloom_filter_columns: Vec<String>,
+}
+
+impl TableParquetSettings {
+    pub fn new(compression: Option<String>, bloom_filter_columns: Vec<String>) -> Self {
+        Self {
+            compression,
+            bloom_filter_columns,
+        }
+    }
+
+    pub fn compression(&self) -> &Option<String> {
+        &self.compression
+    }
+
+    pub fn bloom_filter_columns(&self) -> &Vec<String> {
+        &self.bloom_filter_columns
+    }
+
+    pub fn has_bloom_filter(&self, column: &str) -> bool {
+        self.bloom_filter_columns.iter().any(|c| c == column)
+    }
+}
+
+impl DataFrameValue<String> for Option<TableParquetSettings> {
+    fn value(v: &Self) -> String {
+        v.as_ref()
+            .map(|s| format!("{:?}", s))
+            .unwrap_or("NULL".to_string())
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
+pub enum DeleteComparison {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl DeleteComparison {
+    /// Same comparison with swapped operands.
+    pub fn flip(self) -> Self {
+        match self {
+            DeleteComparison::Eq => DeleteComparison::Eq,
+            DeleteComparison::NotEq => DeleteComparison::NotEq,
+            DeleteComparison::Lt => DeleteComparison::Gt,
+            DeleteComparison::LtEq => DeleteComparison::GtEq,
+            DeleteComparison::Gt => DeleteComparison::Lt,
+            DeleteComparison::GtEq => DeleteComparison::LtEq,
+        }
+    }
+}
+
+/// `WHERE` clause of a `DELETE` statement. Values are typed after columns they're compared to.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
+pub enum DeletePredicate {
+    And(Box<DeletePredicate>, Box<DeletePredicate>),
+    Or(Box<DeletePredicate>, Box<DeletePredicate>),
+    Not(Box<DeletePredicate>),
+    Compare {
+        column: String,
+        op: DeleteComparison,
+        value: TableValue,
+    },
+    InList {
+        column: String,
+        values: Vec<TableValue>,
+        negated: bool,
+    },
+    IsNull {
+        column: String,
+        negated: bool,
+    },
+}
+
+impl DeletePredicate {
+    pub fn columns(&self) -> Vec<&String> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns.into_iter().unique().collect()
+    }
+
+    fn collect_columns<'a>(&'a self, out: &mut Vec<&'a String>) {
+        match self {
+            DeletePredicate::And(l, r) | DeletePredicate::Or(l, r) => {
+                l.collect_columns(out);
+                r.collect_columns(out);
+            }
+            DeletePredicate::Not(p) => p.collect_columns(out),
+            DeletePredicate::Compare { column, .. }
+            | DeletePredicate::InList { column, .. }
+            | DeletePredicate::IsNull { column, .. } => out.push(column),
+        }
+    }
+}
+
+/// Rows matching the predicate are deleted from all partitions and chunks which haven't applied
+/// this marker yet, i.e. from data that existed by the time of `DELETE`. Marker ids are
+/// sequential within a table.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
+pub struct DeleteMarker {
+    id: u64,
+    predicate: DeletePredicate,
+    created_at: DateTime<Utc>,
+}
+
+impl DeleteMarker {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn predicate(&self) -> &DeletePredicate {
+        &self.predicate
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        &self.created_at
+    }
+}
+
+impl DataFrameValue<String> for Vec<DeleteMarker> {
+    fn value(v: &Self) -> String {
+        v.iter().map(|m| format!("{:?}", m)).join(", ")
+    }
+}
+
 data_frame_from! {
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, PartialOrd)]
 pub struct Table {
@@ -166,6 +377,17 @@ pub struct Table {
     #[serde(default)]
     partition_split_threshold: Option<u64>,
     #[serde(default)]
+    retention: Option<TableRetention>,
+    #[serde(default)]
+    parquet_settings: Option<TableParquetSettings>,
+    #[serde(default)]
+    delete_markers: Vec<DeleteMarker>,
+    #[serde(default)]
+    last_delete_marker_id: u64,
+    /// Names of columns removed by `ALTER TABLE DROP COLUMN`. Old data files may still have them.
+    #[serde(default)]
+    dropped_columns: Vec<String>,
+    #[serde(default)]
     extension: Option<String>  // TODO: Make this an Option<serde_json::Value> or Option<json::JsonValue>?  We have some problems implementing Hash.
 }
 }
@@ -217,6 +439,8 @@ impl Table {
         seq_column_index: Option<u64>,
         partition_split_threshold: Option<u64>,
         extension: Option<String>,
+        retention: Option<TableRetention>,
+        parquet_settings: Option<TableParquetSettings>,
     ) -> Table {
         let location_download_sizes = locations.as_ref().map(|locations| vec![0; locations.len()]);
         Table {
@@ -239,6 +463,11 @@ impl Table {
             seq_column_index,
             location_download_sizes,
             partition_split_threshold,
+            retention,
+            parquet_settings,
+            delete_markers: Vec::new(),
+            last_delete_marker_id: 0,
+            dropped_columns: Vec::new(),
             extension,
         }
     }
@@ -371,6 +600,14 @@ impl Table {
         &self.aggregate_column_indices
     }
 
+    /// `LAST_VALUE` aggregations need chunks to be merged in the order they were inserted. They
+    /// are rejected for tables with a `seq_column`, so insert order is the only ordering.
+    pub fn aggregates_depend_on_insert_order(&self) -> bool {
+        self.aggregate_column_indices
+            .iter()
+            .any(|v| v.function == AggregateFunction::LAST_VALUE)
+    }
+
     pub fn seq_column(&self) -> Option<&Column> {
         self.seq_column_index
             .as_ref()
@@ -421,6 +658,120 @@ impl Table {
     pub fn stream_offset(&self) -> &Option<StreamOffset> {
         &self.stream_offset
     }
+
+    pub fn retention(&self) -> &Option<TableRetention> {
+        &self.retention
+    }
+
+    pub fn retention_column(&self) -> Option<&Column> {
+        self.retention
+            .as_ref()
+            .map(|r| &self.columns[r.column_index as usize])
+    }
+
+    pub fn parquet_settings(&self) -> &Option<TableParquetSettings> {
+        &self.parquet_settings
+    }
+
+    pub fn delete_markers(&self) -> &Vec<DeleteMarker> {
+        &self.delete_markers
+    }
+
+    /// Id of the latest `DELETE` marker. Data written after it is unaffected by existing markers.
+    pub fn last_delete_marker_id(&self) -> u64 {
+        self.last_delete_marker_id
+    }
+
+    /// Markers which still have to be applied to data that applied markers up to
+    /// `deletes_applied`.
+    pub fn pending_delete_markers(&self, deletes_applied: u64) -> Vec<&DeleteMarker> {
+        self.delete_markers
+            .iter()
+            .filter(|m| m.id > deletes_applied)
+            .collect()
+    }
+
+    pub fn add_delete_marker(&self, predicate: DeletePredicate) -> Self {
+        let mut table = self.clone();
+        table.last_delete_marker_id += 1;
+        table.delete_markers.push(DeleteMarker {
+            id: table.last_delete_marker_id,
+            predicate,
+            created_at: Utc::now(),
+        });
+        table
+    }
+
+    /// Drops markers that all data of the table has applied already.
+    pub fn remove_applied_delete_markers(&self, deletes_applied: u64) -> Self {
+        let mut table = self.clone();
+        table.delete_markers.retain(|m| m.id > deletes_applied);
+        table
+    }
+
+    pub fn dropped_columns(&self) -> &Vec<String> {
+        &self.dropped_columns
+    }
+
+    pub fn rename(&self, schema_id: u64, table_name: String) -> Self {
+        let mut table = self.clone();
+        table.schema_id = schema_id;
+        table.table_name = table_name;
+        table
+    }
+
+    /// Appends a column. Data written before has no such column and is read with nulls in its place.
+    pub fn add_column(&self, name: String, column_type: ColumnType) -> Self {
+        let mut table = self.clone();
+        let column_index = table.columns.len();
+        table
+            .columns
+            .push(Column::new(name, column_type, column_index));
+        table
+    }
+
+    /// Removes a column and shifts positions of the following ones. The column must not be
+    /// referenced by the unique key, aggregations or retention. Its bloom filter is dropped.
+    pub fn drop_column(&self, name: &str) -> Result<Self, CubeError> {
+        let dropped = self
+            .columns
+            .iter()
+            .position(|c| c.get_name() == name)
+            .ok_or_else(|| {
+                CubeError::user(format!(
+                    "Column '{}' is not found in table '{}'",
+                    name, self.table_name
+                ))
+            })? as u64;
+        let shift = |i: u64| if i > dropped { i - 1 } else { i };
+        let mut table = self.clone();
+        table.columns = self
+            .columns
+            .iter()
+            .filter(|c| c.get_name() != name)
+            .enumerate()
+            .map(|(i, c)| c.replace_index(i))
+            .collect();
+        table.unique_key_column_indices = self
+            .unique_key_column_indices
+            .as_ref()
+            .map(|indices| indices.iter().map(|i| shift(*i)).collect());
+        table.aggregate_column_indices = self
+            .aggregate_column_indices
+            .iter()
+            .map(|a| AggregateColumnIndex::new(shift(a.index), a.function.clone()))
+            .collect();
+        table.seq_column_index = self.seq_column_index.map(shift);
+        table.retention = self
+            .retention
+            .as_ref()
+            .map(|r| TableRetention::new(shift(r.column_index), r.period_secs));
+        if let Some(settings) = table.parquet_settings.as_mut() {
+            settings.bloom_filter_columns.retain(|c| c != name);
+        }
+        table.dropped_columns.push(name.to_string());
+        Ok(table)
+    }
 }
 
 impl Column {
@@ -1,6 +1,8 @@
 use crate::config::processing_loop::ProcessingLoop;
+use crate::sql::access_control::UserGrants;
 use crate::sql::{InlineTables, SqlQueryContext, SqlService};
 use crate::table::TableValue;
+use crate::telemetry::tracing::{query_span, traceparent_from_sql_comment};
 use crate::util::time_span::warn_long;
 use crate::{app_metrics, metastore, CubeError};
 use async_trait::async_trait;
@@ -9,18 +11,23 @@ use hex::ToHex;
 use log::{error, info, warn};
 use mockall::automock;
 use msql_srv::*;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt;
 use std::io;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::SystemTime;
 use tokio::net::TcpListener;
 use tokio::sync::{watch, RwLock};
+use tracing::Instrument;
 
 struct Backend {
     sql_service: Arc<dyn SqlService>,
     auth: Arc<dyn SqlAuthService>,
     user: Option<String>,
+    grants: Option<UserGrants>,
 }
 
 #[async_trait]
@@ -65,9 +72,11 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
                     trace_obj: None,
                     process_id: None,
                     parameters: None,
+                    grants: self.grants.clone(),
                 },
                 query,
             )
+            .instrument(query_span(traceparent_from_sql_comment(query)))
             .await;
         let res = match res {
             Ok(qr) => qr.collect().await,
@@ -104,6 +113,12 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
                     metastore::ColumnType::Bytes => ColumnType::MYSQL_TYPE_STRING,
                     metastore::ColumnType::HyperLogLog(_) => ColumnType::MYSQL_TYPE_STRING,
                     metastore::ColumnType::Float => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::Date => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::Interval => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::List(_) => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::Json => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::TDigest => ColumnType::MYSQL_TYPE_STRING,
+                    metastore::ColumnType::ThetaSketch => ColumnType::MYSQL_TYPE_STRING,
                 },
                 colflags: ColumnFlags::empty(),
             })
@@ -136,6 +151,9 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
                     TableValue::Bytes(b) => {
                         rw.write_col(format!("0x{}", b.encode_hex_upper::<String>()))?
                     }
+                    TableValue::Date(v) => rw.write_col(v.to_string())?,
+                    TableValue::Interval(v) => rw.write_col(v.to_string())?,
+                    TableValue::List(_) => rw.write_col(value.to_string())?,
                     TableValue::Null => rw.write_col(Option::<String>::None)?,
                 }
             }
@@ -175,11 +193,18 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
         } else {
             None
         };
-        self.auth
+        let to_io_error = |e: CubeError| io::Error::new(io::ErrorKind::Other, e.to_string());
+        let password = self
+            .auth
             .authenticate(self.user.clone())
             .await
-            .map(|p| p.map(|p| p.as_bytes().to_vec()))
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            .map_err(to_io_error)?;
+        self.grants = self
+            .auth
+            .user_grants(self.user.clone())
+            .await
+            .map_err(to_io_error)?;
+        Ok(password.map(|p| p.as_bytes().to_vec()))
     }
 }
 
@@ -229,6 +254,7 @@ impl ProcessingLoop for MySqlServer {
                         sql_service,
                         auth,
                         user: None,
+                        grants: None,
                     },
                     socket,
                 )
@@ -267,6 +293,11 @@ impl MySqlServer {
 #[async_trait]
 pub trait SqlAuthService: Send + Sync {
     async fn authenticate(&self, user: Option<String>) -> Result<Option<String>, CubeError>;
+
+    /// Statements the authenticated user is allowed to run, `None` allows all statements.
+    async fn user_grants(&self, _user: Option<String>) -> Result<Option<UserGrants>, CubeError> {
+        Ok(None)
+    }
 }
 
 pub struct SqlAuthDefaultImpl;
@@ -279,3 +310,54 @@ impl SqlAuthService for SqlAuthDefaultImpl {
         Ok(None)
     }
 }
+
+/// User of [SqlAuthConfiguredImpl], set by `CUBESTORE_AUTH_USERS` as a json array of
+/// `{"user": "...", "password": "...", "schemas": ["..."], "permissions": ["read"]}`.
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct AuthUser {
+    pub user: String,
+    pub password: String,
+    #[serde(flatten)]
+    pub grants: UserGrants,
+}
+
+impl fmt::Debug for AuthUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthUser")
+            .field("user", &self.user)
+            .field("grants", &self.grants)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Authenticates only the configured users and restricts them to their grants.
+pub struct SqlAuthConfiguredImpl {
+    users: HashMap<String, AuthUser>,
+}
+
+crate::di_service!(SqlAuthConfiguredImpl, [SqlAuthService]);
+
+impl SqlAuthConfiguredImpl {
+    pub fn new(users: Vec<AuthUser>) -> Arc<Self> {
+        Arc::new(Self {
+            users: users.into_iter().map(|u| (u.user.clone(), u)).collect(),
+        })
+    }
+
+    fn user(&self, user: Option<String>) -> Result<&AuthUser, CubeError> {
+        user.as_ref()
+            .and_then(|u| self.users.get(u))
+            .ok_or_else(|| CubeError::user(format!("Unknown user '{}'", user.unwrap_or_default())))
+    }
+}
+
+#[async_trait]
+impl SqlAuthService for SqlAuthConfiguredImpl {
+    async fn authenticate(&self, user: Option<String>) -> Result<Option<String>, CubeError> {
+        Ok(Some(self.user(user)?.password.clone()))
+    }
+
+    async fn user_grants(&self, user: Option<String>) -> Result<Option<UserGrants>, CubeError> {
+        Ok(Some(self.user(user)?.grants.clone()))
+    }
+}
@@ -0,0 +1,110 @@
+use crate::metastore::table::{DeleteComparison, DeleteMarker, DeletePredicate, Table};
+use crate::metastore::Column;
+use crate::table::data::{append_value, create_array_builder};
+use crate::table::TableValue;
+use crate::CubeError;
+use datafusion::common::{Column as DFColumn, ScalarValue, ToDFSchema};
+use datafusion::logical_expr::execution_props::ExecutionProps;
+use datafusion::logical_expr::{lit, Expr};
+use datafusion::physical_expr::create_physical_expr;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::ExecutionPlan;
+use std::sync::Arc;
+
+/// Skips rows of `input` deleted by markers of `table` which are newer than `deletes_applied`.
+/// Input columns are expected to be named after table columns and include all columns
+/// referenced by the markers.
+pub fn filter_deleted_rows(
+    input: Arc<dyn ExecutionPlan>,
+    table: &Table,
+    deletes_applied: u64,
+) -> Result<Arc<dyn ExecutionPlan>, CubeError> {
+    let markers = table.pending_delete_markers(deletes_applied);
+    let filter = match delete_markers_filter(&markers, table.get_columns())? {
+        Some(filter) => filter,
+        None => return Ok(input),
+    };
+    let schema = input.schema().as_ref().clone().to_dfschema()?;
+    let predicate = create_physical_expr(&filter, &schema, &ExecutionProps::new())?;
+    Ok(Arc::new(FilterExec::try_new(predicate, input)?))
+}
+
+/// Expression keeping rows not matched by any of `markers`. Same as the `WHERE` clause of
+/// `DELETE`, predicates evaluating to NULL don't match.
+pub fn delete_markers_filter(
+    markers: &[&DeleteMarker],
+    columns: &[Column],
+) -> Result<Option<Expr>, CubeError> {
+    let mut deleted: Option<Expr> = None;
+    for marker in markers {
+        let predicate = predicate_to_expr(marker.predicate(), columns)?;
+        deleted = Some(match deleted {
+            None => predicate,
+            Some(d) => d.or(predicate),
+        });
+    }
+    Ok(deleted.map(|d| Expr::IsNotTrue(Box::new(d))))
+}
+
+fn predicate_to_expr(predicate: &DeletePredicate, columns: &[Column]) -> Result<Expr, CubeError> {
+    Ok(match predicate {
+        DeletePredicate::And(l, r) => {
+            predicate_to_expr(l, columns)?.and(predicate_to_expr(r, columns)?)
+        }
+        DeletePredicate::Or(l, r) => {
+            predicate_to_expr(l, columns)?.or(predicate_to_expr(r, columns)?)
+        }
+        DeletePredicate::Not(p) => Expr::Not(Box::new(predicate_to_expr(p, columns)?)),
+        DeletePredicate::Compare { column, op, value } => {
+            let (column, c) = column_expr(column, columns)?;
+            let v = lit(scalar_value(value, column)?);
+            match op {
+                DeleteComparison::Eq => c.eq(v),
+                DeleteComparison::NotEq => c.not_eq(v),
+                DeleteComparison::Lt => c.lt(v),
+                DeleteComparison::LtEq => c.lt_eq(v),
+                DeleteComparison::Gt => c.gt(v),
+                DeleteComparison::GtEq => c.gt_eq(v),
+            }
+        }
+        DeletePredicate::InList {
+            column,
+            values,
+            negated,
+        } => {
+            let (column, c) = column_expr(column, columns)?;
+            let values = values
+                .iter()
+                .map(|v| Ok(lit(scalar_value(v, column)?)))
+                .collect::<Result<Vec<_>, CubeError>>()?;
+            c.in_list(values, *negated)
+        }
+        DeletePredicate::IsNull { column, negated } => {
+            let (_, c) = column_expr(column, columns)?;
+            if *negated {
+                c.is_not_null()
+            } else {
+                c.is_null()
+            }
+        }
+    })
+}
+
+fn column_expr<'a>(name: &str, columns: &'a [Column]) -> Result<(&'a Column, Expr), CubeError> {
+    let column = columns
+        .iter()
+        .find(|c| c.get_name() == name)
+        .ok_or_else(|| {
+            CubeError::internal(format!("Column '{}' of delete marker is not found", name))
+        })?;
+    Ok((
+        column,
+        Expr::Column(DFColumn::from_name(column.get_name().clone())),
+    ))
+}
+
+fn scalar_value(value: &TableValue, column: &Column) -> Result<ScalarValue, CubeError> {
+    let mut builder = create_array_builder(column.get_column_type());
+    append_value(builder.as_mut(), column.get_column_type(), value);
+    Ok(ScalarValue::try_from_array(&builder.finish(), 0)?)
+}
@@ -52,6 +52,7 @@ impl InfoSchemaTableDef for SystemChunksTableDef {
             Field::new("file_size", DataType::UInt64, true),
             Field::new("min_row", DataType::Utf8, true),
             Field::new("max_row", DataType::Utf8, true),
+            Field::new("encryption_key_id", DataType::Utf8, true),
         ]
     }
 
@@ -71,6 +72,7 @@ impl InfoSchemaTableDef for SystemChunksTableDef {
         let mut file_size_builder = UInt64Builder::with_capacity(num_rows);
         let mut min_builder = StringBuilder::with_capacity(num_rows, num_rows * 64);
         let mut max_builder = StringBuilder::with_capacity(num_rows, num_rows * 64);
+        let mut encryption_key_id_builder = StringBuilder::with_capacity(num_rows, num_rows * 16);
 
         for row in rows.into_iter() {
             let id = row.get_id();
@@ -100,6 +102,7 @@ impl InfoSchemaTableDef for SystemChunksTableDef {
             file_size_builder.append_option(chunk.file_size());
             min_builder.append_option(chunk.min().as_ref().map(|v| format!("{:?}", v)));
             max_builder.append_option(chunk.max().as_ref().map(|v| format!("{:?}", v)));
+            encryption_key_id_builder.append_option(chunk.encryption_key_id().as_ref());
         }
 
         vec![
@@ -117,6 +120,7 @@ impl InfoSchemaTableDef for SystemChunksTableDef {
             Arc::new(file_size_builder.finish()),
             Arc::new(min_builder.finish()),
             Arc::new(max_builder.finish()),
+            Arc::new(encryption_key_id_builder.finish()),
         ]
     }
 }
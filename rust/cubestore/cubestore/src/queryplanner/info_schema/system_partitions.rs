@@ -5,20 +5,58 @@ use crate::CubeError;
 use async_trait::async_trait;
 use datafusion::arrow::array::{ArrayRef, BooleanBuilder, StringBuilder, UInt64Builder};
 use datafusion::arrow::datatypes::{DataType, Field};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub struct SystemPartitionsTableDef;
 
 #[async_trait]
 impl InfoSchemaTableDef for SystemPartitionsTableDef {
-    type T = IdRow<Partition>;
+    /// Partitions along with whether they have rows matching pending `DELETE` markers.
+    type T = (IdRow<Partition>, bool);
 
     async fn rows(
         &self,
         ctx: InfoSchemaTableDefContext,
         _limit: Option<usize>,
     ) -> Result<Vec<Self::T>, CubeError> {
-        Ok(ctx.meta_store.partition_table().all_rows().await?)
+        let last_marker_by_table = ctx
+            .meta_store
+            .get_tables_with_path(true)
+            .await?
+            .iter()
+            .map(|t| (t.table.get_id(), t.table.get_row().last_delete_marker_id()))
+            .collect::<HashMap<_, _>>();
+        let last_marker_by_index = ctx
+            .meta_store
+            .index_table()
+            .all_rows()
+            .await?
+            .into_iter()
+            .map(|i| {
+                let last_marker = last_marker_by_table
+                    .get(&i.get_row().table_id())
+                    .cloned()
+                    .unwrap_or(0);
+                (i.get_id(), last_marker)
+            })
+            .collect::<HashMap<_, _>>();
+        Ok(ctx
+            .meta_store
+            .partition_table()
+            .all_rows()
+            .await?
+            .into_iter()
+            .map(|p| {
+                let last_marker = last_marker_by_index
+                    .get(&p.get_row().get_index_id())
+                    .cloned()
+                    .unwrap_or(0);
+                let pending_deletes = p.get_row().has_main_table_file()
+                    && p.get_row().deletes_applied() < last_marker;
+                (p, pending_deletes)
+            })
+            .collect())
     }
 
     fn schema(&self) -> Vec<Field> {
@@ -36,6 +74,9 @@ impl InfoSchemaTableDef for SystemPartitionsTableDef {
             Field::new("warmed_up", DataType::Boolean, true),
             Field::new("main_table_row_count", DataType::UInt64, true),
             Field::new("file_size", DataType::UInt64, true),
+            Field::new("encryption_key_id", DataType::Utf8, true),
+            Field::new("deletes_applied", DataType::UInt64, false),
+            Field::new("pending_deletes", DataType::Boolean, false),
         ]
     }
 
@@ -54,8 +95,11 @@ impl InfoSchemaTableDef for SystemPartitionsTableDef {
         let mut warmed_builder = BooleanBuilder::with_capacity(num_rows);
         let mut row_count_builder = UInt64Builder::with_capacity(num_rows);
         let mut file_size_builder = UInt64Builder::with_capacity(num_rows);
+        let mut encryption_key_id_builder = StringBuilder::with_capacity(num_rows, num_rows * 16);
+        let mut deletes_applied_builder = UInt64Builder::with_capacity(num_rows);
+        let mut pending_deletes_builder = BooleanBuilder::with_capacity(num_rows);
 
-        for row in rows.into_iter() {
+        for (row, pending_deletes) in rows.into_iter() {
             let id = row.get_id();
             let part = row.get_row();
             id_builder.append_value(id);
@@ -71,6 +115,9 @@ impl InfoSchemaTableDef for SystemPartitionsTableDef {
             warmed_builder.append_value(part.is_warmed_up());
             row_count_builder.append_value(part.main_table_row_count());
             file_size_builder.append_option(part.file_size());
+            encryption_key_id_builder.append_option(part.encryption_key_id().as_ref());
+            deletes_applied_builder.append_value(part.deletes_applied());
+            pending_deletes_builder.append_value(pending_deletes);
         }
 
         vec![
@@ -87,6 +134,9 @@ impl InfoSchemaTableDef for SystemPartitionsTableDef {
             Arc::new(warmed_builder.finish()),
             Arc::new(row_count_builder.finish()),
             Arc::new(file_size_builder.finish()),
+            Arc::new(encryption_key_id_builder.finish()),
+            Arc::new(deletes_applied_builder.finish()),
+            Arc::new(pending_deletes_builder.finish()),
         ]
     }
 }
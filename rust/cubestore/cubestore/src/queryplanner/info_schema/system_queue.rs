@@ -49,6 +49,13 @@ impl InfoSchemaTableDef for SystemQueueTableDef {
             Field::new("process_id", DataType::Utf8, true),
             Field::new("exclusive", DataType::Boolean, false),
             Field::new("external_id", DataType::Utf8, true),
+            Field::new("attempts", DataType::Int64, false),
+            Field::new("max_attempts", DataType::Int64, true),
+            Field::new(
+                "visible_at",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
         ]
     }
 
@@ -66,6 +73,9 @@ impl InfoSchemaTableDef for SystemQueueTableDef {
         let mut process_id_builder = StringBuilder::with_capacity(num_rows, num_rows * 64);
         let mut exclusive_builder = BooleanBuilder::with_capacity(num_rows);
         let mut external_id_builder = StringBuilder::with_capacity(num_rows, num_rows * 64);
+        let mut attempts_builder = Int64Builder::with_capacity(num_rows);
+        let mut max_attempts_builder = Int64Builder::with_capacity(num_rows);
+        let mut visible_at_builder = TimestampNanosecondBuilder::with_capacity(num_rows);
 
         for row in rows.into_iter() {
             let item = row.item.get_row();
@@ -83,6 +93,10 @@ impl InfoSchemaTableDef for SystemQueueTableDef {
             process_id_builder.append_option(item.get_process_id().as_deref());
             exclusive_builder.append_value(item.get_exclusive());
             external_id_builder.append_option(item.get_external_id().as_deref());
+            attempts_builder.append_value(item.get_attempts() as i64);
+            max_attempts_builder.append_option(item.get_max_attempts().map(|v| v as i64));
+            visible_at_builder
+                .append_option(item.get_visible_at().as_ref().map(timestamp_nanos_or_panic));
         }
 
         vec![
@@ -98,6 +112,9 @@ impl InfoSchemaTableDef for SystemQueueTableDef {
             Arc::new(process_id_builder.finish()),
             Arc::new(exclusive_builder.finish()),
             Arc::new(external_id_builder.finish()),
+            Arc::new(attempts_builder.finish()),
+            Arc::new(max_attempts_builder.finish()),
+            Arc::new(visible_at_builder.finish()),
         ]
     }
 }
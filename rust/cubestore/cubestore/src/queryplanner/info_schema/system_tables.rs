@@ -58,6 +58,10 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
             Field::new("sealed", DataType::Boolean, false),
             Field::new("select_statement", DataType::Utf8, true),
             Field::new("extension", DataType::Utf8, true),
+            Field::new("retention_column", DataType::Utf8, true),
+            Field::new("retention_period_secs", DataType::UInt64, true),
+            Field::new("compression", DataType::Utf8, true),
+            Field::new("bloom_filter_columns", DataType::Utf8, true),
         ]
     }
 
@@ -82,6 +86,10 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
         let mut sealed_builder = BooleanBuilder::with_capacity(num_rows);
         let mut select_builder = StringBuilder::with_capacity(num_rows, num_rows * 128);
         let mut extension_builder = StringBuilder::with_capacity(num_rows, num_rows * 32);
+        let mut retention_column_builder = StringBuilder::with_capacity(num_rows, num_rows * 32);
+        let mut retention_period_builder = UInt64Builder::with_capacity(num_rows);
+        let mut compression_builder = StringBuilder::with_capacity(num_rows, num_rows * 16);
+        let mut bloom_filter_builder = StringBuilder::with_capacity(num_rows, num_rows * 32);
 
         for row in rows.into_iter() {
             id_builder.append_value(row.table.get_id());
@@ -116,6 +124,18 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
             sealed_builder.append_value(table.sealed());
             select_builder.append_option(table.select_statement().as_ref().map(|s| s.as_str()));
             extension_builder.append_option(table.extension().as_ref().map(|e| e.as_str()));
+            retention_column_builder
+                .append_option(table.retention_column().map(|c| c.get_name().as_str()));
+            retention_period_builder
+                .append_option(table.retention().as_ref().map(|r| r.period_secs()));
+            let parquet_settings = table.parquet_settings().as_ref();
+            compression_builder
+                .append_option(parquet_settings.and_then(|s| s.compression().as_ref()));
+            bloom_filter_builder.append_option(
+                parquet_settings
+                    .filter(|s| !s.bloom_filter_columns().is_empty())
+                    .map(|s| s.bloom_filter_columns().join(",")),
+            );
         }
 
         vec![
@@ -138,6 +158,10 @@ impl InfoSchemaTableDef for SystemTablesTableDef {
             Arc::new(sealed_builder.finish()),
             Arc::new(select_builder.finish()),
             Arc::new(extension_builder.finish()),
+            Arc::new(retention_column_builder.finish()),
+            Arc::new(retention_period_builder.finish()),
+            Arc::new(compression_builder.finish()),
+            Arc::new(bloom_filter_builder.finish()),
         ]
     }
 }
@@ -10,6 +10,7 @@ use datafusion_datasource::memory::MemorySourceConfig;
 use datafusion_datasource::source::DataSourceExec;
 pub use planning::PlanningMeta;
 mod check_memory;
+pub mod delete_markers;
 mod group_by_limit_aggregate;
 pub mod physical_plan_flags;
 pub mod pretty_printers;
@@ -17,6 +18,7 @@ mod projection_above_limit;
 pub mod query_executor;
 pub mod serialized_plan;
 mod tail_limit;
+pub mod tdigest;
 mod topk;
 pub mod trace_data_loaded;
 use serialized_plan::PreSerializedPlan;
@@ -104,6 +106,7 @@ use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::SystemTime;
+use tracing::Instrument;
 
 #[automock]
 #[async_trait]
@@ -233,6 +236,7 @@ impl QueryPlanner for QueryPlannerImpl {
                 &self.meta_store.as_ref(),
                 self.config.enable_topk(),
             )
+            .instrument(tracing::info_span!("choose_index"))
             .await?;
             let workers = compute_workers(
                 self.config.as_ref(),
@@ -478,6 +482,8 @@ impl ContextProvider for MetaStoreSchemaProvider {
                     None,
                     None,
                     None,
+                    None,
+                    None,
                 ),
             ),
         );
@@ -1,4 +1,5 @@
-use crate::table::{cmp_same_types, TableValue};
+use crate::sql::date_from_string;
+use crate::table::{cmp_same_types, DateValue, TableValue};
 use crate::util::decimal::Decimal;
 use datafusion::arrow::datatypes::{DataType, Schema};
 use datafusion::common::Column;
@@ -48,6 +49,66 @@ impl PartitionFilter {
     }
 }
 
+/// Columns compared for equality (`=` or `IN`) with literals by any row matching `filters`.
+/// Min/max conditions only prune on the sort key, row groups of these columns can additionally
+/// be skipped by parquet bloom filters.
+pub fn equality_filter_columns(filters: &[Expr]) -> Vec<String> {
+    let mut r = Vec::new();
+    for f in filters {
+        for c in equality_columns(f) {
+            if !r.contains(&c) {
+                r.push(c);
+            }
+        }
+    }
+    r
+}
+
+fn equality_columns(e: &Expr) -> Vec<String> {
+    match e {
+        Expr::BinaryExpr(BinaryExpr {
+            left: box Expr::Column(c),
+            op: Operator::Eq,
+            right: box Expr::Literal(_),
+        })
+        | Expr::BinaryExpr(BinaryExpr {
+            left: box Expr::Literal(_),
+            op: Operator::Eq,
+            right: box Expr::Column(c),
+        }) => vec![c.name.clone()],
+        Expr::InList(InList {
+            expr: box Expr::Column(c),
+            list,
+            negated: false,
+        }) if list.iter().all(|v| matches!(v, Expr::Literal(_))) => vec![c.name.clone()],
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        }) => {
+            let mut r = equality_columns(left);
+            r.extend(
+                equality_columns(right)
+                    .into_iter()
+                    .filter(|c| !r.contains(c)),
+            );
+            r
+        }
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Or,
+            right,
+        }) => {
+            let right = equality_columns(right);
+            equality_columns(left)
+                .into_iter()
+                .filter(|c| right.contains(c))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct MinMaxCondition {
     min: Vec<Option<TableValue>>, // 'None' means no limit.
@@ -393,6 +454,9 @@ impl Builder<'_> {
     fn try_minus_one(mut v: TableValue) -> TableValue {
         match &mut v {
             TableValue::Int(i) if *i != i64::min_value() => *i -= 1,
+            TableValue::Date(d) if d.get_days() != i32::MIN => {
+                *d = DateValue::new(d.get_days() - 1)
+            }
             _ => (),
         }
         v
@@ -401,6 +465,9 @@ impl Builder<'_> {
     fn try_plus_one(mut v: TableValue) -> TableValue {
         match &mut v {
             TableValue::Int(i) if *i != i64::max_value() => *i += 1,
+            TableValue::Date(d) if d.get_days() != i32::MAX => {
+                *d = DateValue::new(d.get_days() + 1)
+            }
             _ => (),
         }
         v
@@ -415,6 +482,7 @@ impl Builder<'_> {
             DataType::Decimal128(_precision, scale) => Self::extract_decimal(v, *scale),
             DataType::Boolean => Self::extract_bool(v),
             DataType::Utf8 => Self::extract_string(v),
+            DataType::Date32 => Self::extract_date(v),
             _ => None,
             // TODO: more data types
         }
@@ -442,6 +510,16 @@ impl Builder<'_> {
         }
     }
 
+    fn extract_date(v: &ScalarValue) -> Option<TableValue> {
+        match v {
+            ScalarValue::Date32(v) => v.map(|d| TableValue::Date(DateValue::new(d))),
+            ScalarValue::Utf8(s) | ScalarValue::LargeUtf8(s) => {
+                Some(TableValue::Date(date_from_string(s.as_ref()?).ok()?))
+            }
+            _ => None,
+        }
+    }
+
     fn extract_string(v: &ScalarValue) -> Option<TableValue> {
         let s = match v {
             ScalarValue::Utf8(v) => v.as_ref().map(|s| s.clone()),
@@ -735,6 +813,35 @@ mod tests {
         assert_eq!(extract("NOT a").min_max, false_cond);
     }
 
+    #[test]
+    fn test_dates() {
+        let s = schema(&[("a", DataType::Date32)]);
+        let extract = |sql| PartitionFilter::extract(&s, &[parse(sql, &s)]);
+        let date = |days| Some(TableValue::Date(DateValue::new(days)));
+
+        assert_eq!(
+            extract("a = '2020-01-02'").min_max,
+            vec![MinMaxCondition {
+                min: vec![date(18263)],
+                max: vec![date(18263)],
+            }]
+        );
+        assert_eq!(
+            extract("a < '2020-01-02'").min_max,
+            vec![MinMaxCondition {
+                min: vec![None],
+                max: vec![date(18262)],
+            }]
+        );
+        assert_eq!(
+            extract("a > '2020-01-02'").min_max,
+            vec![MinMaxCondition {
+                min: vec![date(18264)],
+                max: vec![None],
+            }]
+        );
+    }
+
     #[test]
     fn test_arithmetic_corner_cases() {
         let s = schema(&[("a", DataType::Int64)]);
@@ -1446,6 +1553,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_equality_filter_columns() {
+        let s = schema(&[
+            ("a", DataType::Int64),
+            ("b", DataType::Utf8),
+            ("c", DataType::Int64),
+        ]);
+        let columns = |sql| equality_filter_columns(&[parse(sql, &s)]);
+
+        assert_eq!(columns("a = 1"), vec!["a"]);
+        assert_eq!(columns("'x' = b"), vec!["b"]);
+        assert_eq!(columns("b IN ('x', 'y') AND c > 1"), vec!["b"]);
+        assert_eq!(columns("a = 1 AND b = 'x' AND a = 2"), vec!["a", "b"]);
+        assert_eq!(columns("(a = 1 AND b = 'x') OR a = 2"), vec!["a"]);
+        assert_eq!(columns("a = 1 OR b = 'x'"), Vec::<String>::new());
+        assert_eq!(columns("b NOT IN ('x')"), Vec::<String>::new());
+        assert_eq!(columns("a = c"), Vec::<String>::new());
+        assert_eq!(columns("a != 1"), Vec::<String>::new());
+    }
+
     fn schema(s: &[(&str, DataType)]) -> Schema {
         Schema::new(
             s.iter()
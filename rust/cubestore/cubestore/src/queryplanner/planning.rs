@@ -1309,23 +1309,24 @@ fn check_aggregates_expr(table: &IdRow<Table>, aggregates: &Vec<Expr>) -> bool {
                     return false;
                 }
 
-                let aggr_fun = match func.name().to_lowercase().as_str() {
-                    "sum" => Some(AggregateFunction::SUM),
-                    "max" => Some(AggregateFunction::MAX),
-                    "min" => Some(AggregateFunction::MIN),
-                    "merge" => Some(AggregateFunction::MERGE),
-                    _ => None,
+                // Counts are stored as numbers of rows, so they are rolled up with a sum.
+                let aggr_funs: &[AggregateFunction] = match func.name().to_lowercase().as_str() {
+                    "sum" => &[AggregateFunction::SUM, AggregateFunction::COUNT],
+                    "max" => &[AggregateFunction::MAX],
+                    "min" => &[AggregateFunction::MIN],
+                    "merge" => &[AggregateFunction::MERGE],
+                    "merge_theta" => &[AggregateFunction::MERGE_THETA],
+                    "merge_tdigest" => &[AggregateFunction::MERGE_TDIGEST],
+                    _ => &[],
                 };
 
-                if aggr_fun.is_none() {
+                if aggr_funs.is_empty() {
                     return false;
                 }
 
-                let aggr_fun = aggr_fun.unwrap();
-
                 let col_match = match &args[0] {
                     Expr::Column(col) => table_aggregates.iter().any(|ta| {
-                        ta.function() == &aggr_fun && ta.column().get_name() == &col.name
+                        aggr_funs.contains(ta.function()) && ta.column().get_name() == &col.name
                     }),
                     _ => false,
                 };
@@ -2948,6 +2949,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            None,
         ));
         i.indices.push(
             Index::try_new(
@@ -3000,6 +3003,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            None,
         ));
 
         i.indices.push(
@@ -3058,6 +3063,8 @@ pub mod tests {
             None,
             None,
             None,
+            None,
+            None,
         ));
 
         i
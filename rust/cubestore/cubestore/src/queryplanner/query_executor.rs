@@ -6,10 +6,12 @@ use crate::config::ConfigObj;
 use crate::metastore::multi_index::MultiPartition;
 use crate::metastore::table::Table;
 use crate::metastore::{Column, ColumnType, IdRow, Index, Partition};
+use crate::queryplanner::delete_markers::filter_deleted_rows;
 use crate::queryplanner::filter_by_key_range::FilterByKeyRangeExec;
 use crate::queryplanner::merge_sort::LastRowByUniqueKeyExec;
 use crate::queryplanner::metadata_cache::{MetadataCacheFactory, NoopParquetMetadataCache};
 use crate::queryplanner::optimizations::{CubeQueryPlanner, PreOptimizeRule};
+use crate::queryplanner::partition_filter::equality_filter_columns;
 use crate::queryplanner::physical_plan_flags::PhysicalPlanFlags;
 use crate::queryplanner::planning::{get_worker_plan, Snapshot, Snapshots};
 use crate::queryplanner::pretty_printers::{pp_phys_plan, pp_phys_plan_ext, pp_plan, PPOptions};
@@ -18,16 +20,17 @@ use crate::queryplanner::trace_data_loaded::DataLoadedSize;
 use crate::store::DataFrame;
 use crate::table::data::rows_to_columns;
 use crate::table::parquet::CubestoreParquetMetadataCache;
-use crate::table::{Row, TableValue, TimestampValue};
+use crate::table::{DateValue, IntervalValue, Row, TableValue, TimestampValue};
 use crate::telemetry::suboptimal_query_plan_event;
 use crate::util::memory::MemoryHandler;
 use crate::{app_metrics, CubeError};
 use async_trait::async_trait;
 use core::fmt;
 use datafusion::arrow::array::{
-    make_array, Array, ArrayRef, BinaryArray, BooleanArray, Decimal128Array, Float32Array,
-    Float64Array, Int16Array, Int32Array, Int64Array, MutableArrayData, NullArray, StringArray,
-    TimestampMicrosecondArray, TimestampNanosecondArray, UInt16Array, UInt32Array, UInt64Array,
+    make_array, Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Decimal128Array,
+    DurationMicrosecondArray, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array,
+    MutableArrayData, NullArray, StringArray, TimestampMicrosecondArray, TimestampNanosecondArray,
+    UInt16Array, UInt32Array, UInt64Array,
 };
 use datafusion::arrow::compute::{filter_record_batch, SortOptions};
 use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
@@ -797,6 +800,26 @@ impl CubeTable {
                     .unwrap();
                 partition_projection.push(part_col_i);
             }
+            // Pending DELETE markers are evaluated on scanned rows, so their columns are read
+            // too and dropped by the final projection.
+            let table = self.index_snapshot.table_path.table.get_row();
+            for marker in table.delete_markers() {
+                for name in marker.predicate().columns() {
+                    let (part_col_i, _) = index_cols
+                        .iter()
+                        .find_position(|c| c.get_name() == name)
+                        .ok_or_else(|| {
+                            CubeError::internal(format!(
+                                "Column '{}' of delete marker is not found in index {:?}",
+                                name,
+                                self.index_snapshot.index()
+                            ))
+                        })?;
+                    if !partition_projection.contains(&part_col_i) {
+                        partition_projection.push(part_col_i);
+                    }
+                }
+            }
             // Parquet does not rearrange columns on projection. This looks like a bug, but until
             // this is fixed, we have to handle this ourselves.
             partition_projection.sort();
@@ -857,6 +880,21 @@ impl CubeTable {
         } else {
             combine_filters(filters)
         };
+        // Reading bloom filters costs an extra read per row group, so they are only used for
+        // equality filters on columns the table writes them for.
+        let bloom_filter_on_read = match (
+            self.index_snapshot
+                .table_path
+                .table
+                .get_row()
+                .parquet_settings(),
+            &predicate,
+        ) {
+            (Some(settings), Some(predicate)) => equality_filter_columns(&[predicate.clone()])
+                .iter()
+                .any(|c| settings.has_bloom_filter(c)),
+            _ => false,
+        };
         let physical_predicate = if let Some(pred) = &predicate {
             Some(state.create_physical_expr(
                 pred.clone(),
@@ -885,6 +923,7 @@ impl CubeTable {
 
                 let mut options = TableParquetOptions::new();
                 options.global = state.config_options().execution.parquet.clone();
+                options.global.bloom_filter_on_read = bloom_filter_on_read;
 
                 let parquet_source =
                     ParquetSource::new(options, get_reader_options_customizer(state.config()))
@@ -921,6 +960,11 @@ impl CubeTable {
                 let data_source_exec = DataSourceExec::new(Arc::new(file_scan));
 
                 let arc: Arc<dyn ExecutionPlan> = Arc::new(data_source_exec);
+                let arc = filter_deleted_rows(
+                    arc,
+                    self.index_snapshot.table_path.table.get_row(),
+                    partition.get_row().deletes_applied(),
+                )?;
                 let arc = FilterByKeyRangeExec::issue_filters(arc, filter.clone(), key_len);
                 partition_execs.push(arc);
             }
@@ -966,6 +1010,7 @@ impl CubeTable {
 
                     let mut options = TableParquetOptions::new();
                     options.global = state.config_options().execution.parquet.clone();
+                    options.global.bloom_filter_on_read = bloom_filter_on_read;
                     let parquet_source =
                         ParquetSource::new(options, get_reader_options_customizer(state.config()))
                             .with_parquet_file_reader_factory(self.parquet_metadata_cache.clone());
@@ -990,6 +1035,11 @@ impl CubeTable {
                     Arc::new(data_source_exec)
                 };
 
+                let node = filter_deleted_rows(
+                    node,
+                    self.index_snapshot.table_path.table.get_row(),
+                    chunk.get_row().deletes_applied(),
+                )?;
                 let node = FilterByKeyRangeExec::issue_filters(node, filter.clone(), key_len);
                 partition_execs.push(node);
             }
@@ -1012,10 +1062,11 @@ impl CubeTable {
                 .0;
             final_reorder.push(batch_col_i);
         }
-        if !final_reorder
-            .iter()
-            .cloned()
-            .eq(0..table_projection_with_seq_column.len())
+        if index_projection.len() != final_reorder.len()
+            || !final_reorder
+                .iter()
+                .cloned()
+                .eq(0..table_projection_with_seq_column.len())
         {
             for p in &mut partition_execs {
                 let s = p.schema();
@@ -2265,6 +2316,34 @@ pub fn batches_to_dataframe(batches: Vec<RecordBatch>) -> Result<DataFrame, Cube
                         });
                     }
                 }
+                DataType::Date32 => {
+                    let a = array.as_any().downcast_ref::<Date32Array>().unwrap();
+                    for i in 0..num_rows {
+                        rows[i].push(if a.is_null(i) {
+                            TableValue::Null
+                        } else {
+                            TableValue::Date(DateValue::new(a.value(i)))
+                        });
+                    }
+                }
+                DataType::Duration(TimeUnit::Microsecond) => {
+                    let a = array
+                        .as_any()
+                        .downcast_ref::<DurationMicrosecondArray>()
+                        .unwrap();
+                    for i in 0..num_rows {
+                        rows[i].push(if a.is_null(i) {
+                            TableValue::Null
+                        } else {
+                            TableValue::Interval(IntervalValue::new(a.value(i)))
+                        });
+                    }
+                }
+                DataType::List(_) => {
+                    for i in 0..num_rows {
+                        rows[i].push(TableValue::from_array(array.as_ref(), i));
+                    }
+                }
                 DataType::Null => {
                     // Force the cast, just because.
                     let _ = array.as_any().downcast_ref::<NullArray>().unwrap();
@@ -2300,6 +2379,11 @@ pub fn arrow_to_column_type(arrow_type: DataType) -> Result<ColumnType, CubeErro
             precision: precision as i32,
         }),
         DataType::Boolean => Ok(ColumnType::Boolean),
+        DataType::Date32 => Ok(ColumnType::Date),
+        DataType::Duration(TimeUnit::Microsecond) => Ok(ColumnType::Interval),
+        DataType::List(f) => Ok(ColumnType::List(Box::new(arrow_to_column_type(
+            f.data_type().clone(),
+        )?))),
         DataType::Int8
         | DataType::Int16
         | DataType::Int32
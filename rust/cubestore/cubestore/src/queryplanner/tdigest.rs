@@ -0,0 +1,305 @@
+use crate::CubeError;
+
+/// Serialization format version, the first byte of every serialized sketch.
+const TDIGEST_FORMAT_V1: u8 = 1;
+/// Version + compression + count of centroids.
+const TDIGEST_HEADER_SIZE: usize = 1 + 8 + 4;
+/// Min and max values, only present for non-empty sketches.
+const TDIGEST_BOUNDS_SIZE: usize = 2 * 8;
+/// Mean and weight of a single centroid.
+const TDIGEST_CENTROID_SIZE: usize = 2 * 8;
+
+pub const DEFAULT_TDIGEST_COMPRESSION: f64 = 100.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest used for approximate quantiles. Sketches are stored in `tdigest` columns
+/// and can be merged freely, so quantiles can be served from rollups.
+///
+/// Serialized as (all numbers are little-endian):
+///   - u8: format version, always 1,
+///   - f64: compression,
+///   - u32: number of centroids,
+///   - f64, f64: min and max values, only written when there is at least one centroid,
+///   - (f64, f64) for each centroid: mean and weight, ordered by mean.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> TDigest {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn from_values(compression: f64, values: impl IntoIterator<Item = f64>) -> TDigest {
+        let mut d = TDigest::new(compression);
+        for v in values {
+            if v.is_nan() {
+                continue;
+            }
+            d.min = d.min.min(v);
+            d.max = d.max.max(v);
+            d.centroids.push(Centroid {
+                mean: v,
+                weight: 1.,
+            });
+        }
+        d.compress();
+        d
+    }
+
+    pub fn read(data: &[u8]) -> Result<TDigest, CubeError> {
+        let invalid = |reason: &str| {
+            CubeError::user(format!("invalid serialized t-digest sketch: {}", reason))
+        };
+        if data.len() < TDIGEST_HEADER_SIZE {
+            return Err(invalid("not enough data"));
+        }
+        if data[0] != TDIGEST_FORMAT_V1 {
+            return Err(invalid(&format!("unsupported version {}", data[0])));
+        }
+        let compression = read_f64(data, 1);
+        if !(compression.is_finite() && compression > 0.) {
+            return Err(invalid("compression must be positive"));
+        }
+        let num_centroids = u32::from_le_bytes(data[9..13].try_into().unwrap()) as usize;
+        let mut d = TDigest::new(compression);
+        if num_centroids == 0 {
+            if data.len() != TDIGEST_HEADER_SIZE {
+                return Err(invalid("unexpected trailing data"));
+            }
+            return Ok(d);
+        }
+        let expected_len = num_centroids
+            .checked_mul(TDIGEST_CENTROID_SIZE)
+            .and_then(|n| n.checked_add(TDIGEST_HEADER_SIZE + TDIGEST_BOUNDS_SIZE));
+        if expected_len != Some(data.len()) {
+            return Err(invalid("size does not match the number of centroids"));
+        }
+
+        d.min = read_f64(data, TDIGEST_HEADER_SIZE);
+        d.max = read_f64(data, TDIGEST_HEADER_SIZE + 8);
+        if !(d.min.is_finite() && d.max.is_finite() && d.min <= d.max) {
+            return Err(invalid("bad min and max values"));
+        }
+        d.centroids.reserve_exact(num_centroids);
+        let mut pos = TDIGEST_HEADER_SIZE + TDIGEST_BOUNDS_SIZE;
+        for _ in 0..num_centroids {
+            let c = Centroid {
+                mean: read_f64(data, pos),
+                weight: read_f64(data, pos + 8),
+            };
+            pos += TDIGEST_CENTROID_SIZE;
+            if !(d.min..=d.max).contains(&c.mean) {
+                return Err(invalid("centroid mean is out of bounds"));
+            }
+            if !(c.weight.is_finite() && c.weight > 0.) {
+                return Err(invalid("centroid weight must be positive"));
+            }
+            if let Some(prev) = d.centroids.last() {
+                if prev.mean > c.mean {
+                    return Err(invalid("centroids are not sorted"));
+                }
+            }
+            d.centroids.push(c);
+        }
+        Ok(d)
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut r = Vec::with_capacity(
+            TDIGEST_HEADER_SIZE
+                + TDIGEST_BOUNDS_SIZE
+                + self.centroids.len() * TDIGEST_CENTROID_SIZE,
+        );
+        r.push(TDIGEST_FORMAT_V1);
+        r.extend_from_slice(&self.compression.to_le_bytes());
+        r.extend_from_slice(&(self.centroids.len() as u32).to_le_bytes());
+        if self.centroids.is_empty() {
+            return r;
+        }
+        r.extend_from_slice(&self.min.to_le_bytes());
+        r.extend_from_slice(&self.max.to_le_bytes());
+        for c in &self.centroids {
+            r.extend_from_slice(&c.mean.to_le_bytes());
+            r.extend_from_slice(&c.weight.to_le_bytes());
+        }
+        r
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centroids.is_empty()
+    }
+
+    pub fn count(&self) -> f64 {
+        self.centroids.iter().map(|c| c.weight).sum()
+    }
+
+    /// Sketches with different compression can be merged, the result keeps the compression of
+    /// `self`.
+    pub fn merge_with(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.centroids.extend_from_slice(&other.centroids);
+        self.compress();
+    }
+
+    /// Estimates the value at quantile `q`, which must be in the range `[0, 1]`. Returns `None`
+    /// for empty sketches.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        debug_assert!((0. ..=1.).contains(&q));
+        if self.centroids.is_empty() {
+            return None;
+        }
+        if self.centroids.len() == 1 {
+            return Some(self.centroids[0].mean);
+        }
+
+        // Each centroid is placed at the middle of the range of ranks it covers, min and max are
+        // placed at the very ends. We interpolate linearly between these points.
+        let total = self.count();
+        let rank = q * total;
+        let mut prev_rank = 0.;
+        let mut prev_value = self.min;
+        let mut seen = 0.;
+        for c in &self.centroids {
+            let c_rank = seen + c.weight / 2.;
+            if rank < c_rank {
+                return Some(interpolate(prev_rank, prev_value, c_rank, c.mean, rank));
+            }
+            seen += c.weight;
+            prev_rank = c_rank;
+            prev_value = c.mean;
+        }
+        Some(interpolate(prev_rank, prev_value, total, self.max, rank))
+    }
+
+    /// The size of allocated memory used (not including `sizeof::<Self>()`). Must be exact.
+    pub fn allocated_size(&self) -> usize {
+        self.centroids.capacity() * size_of::<Centroid>()
+    }
+
+    /// Merges adjacent centroids while they fit into the size limit. The limit is smaller near
+    /// the ends of the distribution, which keeps the tails accurate.
+    fn compress(&mut self) {
+        if self.centroids.len() <= 1 {
+            return;
+        }
+        // Stable sort keeps the result deterministic for equal means.
+        self.centroids.sort_by(|l, r| l.mean.total_cmp(&r.mean));
+
+        let total = self.count();
+        let mut merged: Vec<Centroid> = Vec::with_capacity(self.centroids.len());
+        let mut seen = 0.;
+        let mut current = self.centroids[0];
+        for c in &self.centroids[1..] {
+            let proposed = current.weight + c.weight;
+            let q = (seen + proposed / 2.) / total;
+            let limit = 4. * total * q * (1. - q) / self.compression;
+            if proposed <= limit {
+                current.mean += (c.mean - current.mean) * c.weight / proposed;
+                current.weight = proposed;
+            } else {
+                seen += current.weight;
+                merged.push(current);
+                current = *c;
+            }
+        }
+        merged.push(current);
+        merged.shrink_to_fit();
+        self.centroids = merged;
+    }
+}
+
+fn read_f64(data: &[u8], pos: usize) -> f64 {
+    f64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())
+}
+
+fn interpolate(x0: f64, y0: f64, x1: f64, y1: f64, x: f64) -> f64 {
+    if x1 <= x0 {
+        return y1;
+    }
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64, eps: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() <= eps,
+            "expected {} to be within {} of {}",
+            actual,
+            eps,
+            expected
+        );
+    }
+
+    #[test]
+    fn small_sketches_are_exact() {
+        let d = TDigest::from_values(DEFAULT_TDIGEST_COMPRESSION, [3., 1., 2.]);
+        assert_eq!(d.quantile(0.), Some(1.));
+        assert_eq!(d.quantile(0.5), Some(2.));
+        assert_eq!(d.quantile(1.), Some(3.));
+        assert_eq!(d.count(), 3.);
+
+        let single = TDigest::from_values(DEFAULT_TDIGEST_COMPRESSION, [42.]);
+        assert_eq!(single.quantile(0.99), Some(42.));
+
+        let empty = TDigest::new(DEFAULT_TDIGEST_COMPRESSION);
+        assert_eq!(empty.quantile(0.5), None);
+    }
+
+    #[test]
+    fn merged_quantiles() {
+        let mut d = TDigest::new(DEFAULT_TDIGEST_COMPRESSION);
+        for chunk in 0..10 {
+            let values = (chunk * 1000..(chunk + 1) * 1000).map(|v| v as f64);
+            d.merge_with(&TDigest::from_values(DEFAULT_TDIGEST_COMPRESSION, values));
+        }
+        assert_eq!(d.count(), 10000.);
+        assert!(d.centroids.len() < 500, "{} centroids", d.centroids.len());
+        assert_eq!(d.quantile(0.), Some(0.));
+        assert_eq!(d.quantile(1.), Some(9999.));
+        assert_close(d.quantile(0.5), 5000., 50.);
+        assert_close(d.quantile(0.95), 9500., 20.);
+        assert_close(d.quantile(0.99), 9900., 5.);
+    }
+
+    #[test]
+    fn serialization_roundtrip() -> Result<(), CubeError> {
+        let d = TDigest::from_values(50., (0..1000).map(|v| (v % 37) as f64));
+        let restored = TDigest::read(&d.write())?;
+        assert_eq!(restored, d);
+
+        let empty = TDigest::new(DEFAULT_TDIGEST_COMPRESSION);
+        assert_eq!(empty.write().len(), TDIGEST_HEADER_SIZE);
+        assert_eq!(TDigest::read(&empty.write())?, empty);
+
+        let data = d.write();
+        assert!(TDigest::read(&[]).is_err());
+        assert!(TDigest::read(&data[..data.len() - 1]).is_err());
+        let mut bad_version = data.clone();
+        bad_version[0] = 2;
+        assert!(TDigest::read(&bad_version).is_err());
+        Ok(())
+    }
+}
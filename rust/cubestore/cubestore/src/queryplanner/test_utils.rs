@@ -3,16 +3,19 @@ use crate::cachestore::{
     QueueItem, QueueItemStatus, QueueKey, QueueListItem, QueueResult, QueueResultResponse,
     QueueRetrieveResponse,
 };
+use crate::metastore::backup::{BackupDataFile, BackupStoreSnapshot};
 use crate::metastore::job::{Job, JobRunnerPool, JobStatus, JobType};
 use crate::metastore::multi_index::{MultiIndex, MultiPartition};
 use crate::metastore::replay_handle::{ReplayHandle, SeqPointer};
 use crate::metastore::snapshot_info::SnapshotInfo;
 use crate::metastore::source::{Source, SourceCredentials};
-use crate::metastore::table::{StreamOffset, Table, TablePath};
+use crate::metastore::table::{
+    DeletePredicate, StreamOffset, Table, TableParquetSettings, TablePath,
+};
 use crate::metastore::{
-    Chunk, ChunkMetaStoreTable, Column, IdRow, ImportFormat, Index, IndexDef, IndexMetaStoreTable,
-    MetaStore, Partition, PartitionData, PartitionMetaStoreTable, RocksPropertyRow, RowKey, Schema,
-    SchemaMetaStoreTable, TableMetaStoreTable, WAL,
+    Chunk, ChunkMetaStoreTable, Column, ColumnType, IdRow, ImportFormat, Index, IndexDef,
+    IndexMetaStoreTable, MetaStore, Partition, PartitionData, PartitionMetaStoreTable,
+    RocksPropertyRow, RowKey, Schema, SchemaMetaStoreTable, TableMetaStoreTable, WAL,
 };
 use crate::table::Row;
 use crate::CubeError;
@@ -106,6 +109,8 @@ impl MetaStore for MetaStoreMock {
         _trace_obj: Option<String>,
         _drop_if_exists: bool,
         _extension: Option<String>,
+        _retention: Option<(String, u64)>,
+        _parquet_settings: Option<TableParquetSettings>,
     ) -> Result<IdRow<Table>, CubeError> {
         panic!("MetaStore mock!")
     }
@@ -165,6 +170,40 @@ impl MetaStore for MetaStoreMock {
         panic!("MetaStore mock!")
     }
 
+    async fn add_table_column(
+        &self,
+        _table_id: u64,
+        _column_name: String,
+        _column_type: ColumnType,
+    ) -> Result<IdRow<Table>, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn drop_table_column(
+        &self,
+        _table_id: u64,
+        _column_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn rename_table(
+        &self,
+        _table_id: u64,
+        _schema_name: String,
+        _table_name: String,
+    ) -> Result<IdRow<Table>, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn swap_tables(
+        &self,
+        _left_table_id: u64,
+        _right_table_id: u64,
+    ) -> Result<(), CubeError> {
+        panic!("MetaStore mock!")
+    }
+
     fn partition_table(&self) -> PartitionMetaStoreTable {
         panic!("MetaStore mock!")
     }
@@ -216,6 +255,7 @@ impl MetaStore for MetaStoreMock {
         _old_chunk_ids: Vec<u64>,
         _new_chunk: u64,
         _new_chunk_file_size: u64,
+        _encryption_key_id: Option<String>,
     ) -> Result<bool, CubeError> {
         panic!("MetaStore mock!")
     }
@@ -225,6 +265,7 @@ impl MetaStore for MetaStoreMock {
         _current_active: Vec<(IdRow<Partition>, Vec<IdRow<Chunk>>)>,
         _new_active: Vec<(IdRow<Partition>, u64)>,
         _new_active_min_max: Vec<(u64, (Option<Row>, Option<Row>), (Option<Row>, Option<Row>))>,
+        _encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
@@ -414,6 +455,7 @@ impl MetaStore for MetaStoreMock {
         _new_partitions: Vec<(IdRow<Partition>, u64)>,
         _new_partition_rows: Vec<u64>,
         _initial_split: bool,
+        _encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
@@ -541,6 +583,7 @@ impl MetaStore for MetaStoreMock {
         _deactivate_ids: Vec<u64>,
         _uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         _new_replay_handle_id: Option<u64>,
+        _encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
@@ -550,6 +593,7 @@ impl MetaStore for MetaStoreMock {
         _deactivate_ids: Vec<u64>,
         _uploaded_ids_and_sizes: Vec<(u64, Option<u64>)>,
         _new_replay_handle_id: Option<u64>,
+        _encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
@@ -566,6 +610,7 @@ impl MetaStore for MetaStoreMock {
         _table_id: u64,
         _uploaded_chunk_ids: Vec<(u64, Option<u64>)>,
         _replay_handle_id: Option<u64>,
+        _encryption_key_id: Option<String>,
     ) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
@@ -586,6 +631,41 @@ impl MetaStore for MetaStoreMock {
         panic!("MetaStore mock!")
     }
 
+    async fn get_expired_table_data(
+        &self,
+        _table_id: u64,
+        _cutoff: i64,
+    ) -> Result<(Vec<IdRow<Partition>>, Vec<IdRow<Chunk>>), CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn drop_expired_table_data(
+        &self,
+        _table_id: u64,
+        _cutoff: i64,
+    ) -> Result<(u64, u64), CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn add_delete_marker(
+        &self,
+        _table_id: u64,
+        _predicate: DeletePredicate,
+    ) -> Result<IdRow<Table>, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn get_partitions_with_pending_deletes(
+        &self,
+        _table_id: u64,
+    ) -> Result<Vec<IdRow<Partition>>, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn remove_applied_delete_markers(&self, _table_id: u64) -> Result<u64, CubeError> {
+        panic!("MetaStore mock!")
+    }
+
     async fn create_wal(&self, _table_id: u64, _row_count: usize) -> Result<IdRow<WAL>, CubeError> {
         panic!("MetaStore mock!")
     }
@@ -772,6 +852,21 @@ impl MetaStore for MetaStoreMock {
     async fn set_current_snapshot(&self, _snapshot_id: u128) -> Result<(), CubeError> {
         panic!("MetaStore mock!")
     }
+
+    async fn backup_snapshot(
+        &self,
+        _location: String,
+    ) -> Result<(BackupStoreSnapshot, Vec<BackupDataFile>), CubeError> {
+        panic!("MetaStore mock!")
+    }
+
+    async fn restore_snapshot(
+        &self,
+        _location: String,
+        _snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        panic!("MetaStore mock!")
+    }
 }
 
 crate::di_service!(MetaStoreMock, [MetaStore]);
@@ -816,6 +911,60 @@ impl CacheStore for CacheStoreMock {
         panic!("CacheStore mock!")
     }
 
+    async fn cache_set_if_version(
+        &self,
+        _item: CacheItem,
+        _version: u64,
+    ) -> Result<bool, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_hset(
+        &self,
+        _key: String,
+        _field: String,
+        _value: String,
+        _ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_hget(&self, _key: String, _field: String) -> Result<Option<String>, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_hgetall(&self, _key: String) -> Result<Vec<(String, String)>, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_hdel(&self, _key: String, _field: String) -> Result<bool, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_zadd(
+        &self,
+        _key: String,
+        _member: String,
+        _score: f64,
+        _ttl: Option<u32>,
+    ) -> Result<bool, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_zrange(
+        &self,
+        _key: String,
+        _start: i64,
+        _stop: i64,
+        _rev: bool,
+    ) -> Result<Vec<(String, f64)>, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn cache_zrem(&self, _key: String, _member: String) -> Result<bool, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
     async fn queue_all(&self, _limit: Option<usize>) -> Result<Vec<QueueAllItem>, CubeError> {
         panic!("CacheStore mock!")
     }
@@ -944,6 +1093,18 @@ impl CacheStore for CacheStoreMock {
     async fn wipe(&self) -> Result<(), CubeError> {
         panic!("CacheStore mock!")
     }
+
+    async fn backup_snapshot(&self, _location: String) -> Result<BackupStoreSnapshot, CubeError> {
+        panic!("CacheStore mock!")
+    }
+
+    async fn restore_snapshot(
+        &self,
+        _location: String,
+        _snapshot: BackupStoreSnapshot,
+    ) -> Result<u128, CubeError> {
+        panic!("CacheStore mock!")
+    }
 }
 
 crate::di_service!(CacheStoreMock, [CacheStore]);
@@ -1,13 +1,19 @@
+use crate::metastore::{Column, ColumnType};
 use crate::queryplanner::hll::{Hll, HllUnion};
 use crate::queryplanner::info_schema::timestamp_nanos_or_panic;
+use crate::queryplanner::tdigest::TDigest;
 use crate::queryplanner::udf_xirr::{XirrUDF, XIRR_UDAF_NAME};
 use crate::CubeError;
 use chrono::{DateTime, Datelike, Duration, Months, NaiveDateTime};
+use cubedatasketches::{ThetaDataSketch, ThetaIntersectionDataSketch, ThetaUnionDataSketch};
 use datafusion::arrow::array::{
-    Array, ArrayRef, BinaryArray, StringArray, TimestampNanosecondArray, UInt64Builder,
+    new_null_array, Array, ArrayRef, AsArray, BinaryArray, BinaryBuilder, BooleanBuilder,
+    Float64Builder, Scalar, StringArray, StringBuilder, TimestampNanosecondArray, UInt64Builder,
 };
 use datafusion::arrow::buffer::ScalarBuffer;
-use datafusion::arrow::datatypes::{DataType, IntervalUnit, TimeUnit};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::compute::kernels::cmp::eq;
+use datafusion::arrow::datatypes::{DataType, Field, Float64Type, IntervalUnit, TimeUnit};
 use datafusion::error::DataFusionError;
 use datafusion::logical_expr::function::AccumulatorArgs;
 use datafusion::logical_expr::simplify::{ExprSimplifyResult, SimplifyInfo};
@@ -17,6 +23,7 @@ use datafusion::logical_expr::{
 };
 use datafusion::physical_plan::{Accumulator, ColumnarValue};
 use datafusion::scalar::ScalarValue;
+use itertools::Itertools;
 use serde_derive::{Deserialize, Serialize};
 use std::any::Any;
 use std::sync::Arc;
@@ -25,6 +32,13 @@ use std::time::SystemTime;
 pub fn registerable_scalar_udfs_iter() -> impl Iterator<Item = ScalarUDF> {
     [
         ScalarUDF::new_from_impl(HllCardinality::new()),
+        ScalarUDF::new_from_impl(Quantile::new()),
+        ScalarUDF::new_from_impl(ThetaSetOperation::new(ThetaSetOperationKind::Union)),
+        ScalarUDF::new_from_impl(ThetaSetOperation::new(ThetaSetOperationKind::Intersect)),
+        ScalarUDF::new_from_impl(ThetaSetOperation::new(ThetaSetOperationKind::ANotB)),
+        ScalarUDF::new_from_impl(ThetaEstimate::new()),
+        ScalarUDF::new_from_impl(ArrayContains::new()),
+        ScalarUDF::new_from_impl(JsonExtractPathText::new()),
         ScalarUDF::new_from_impl(DateBin::new()),
         ScalarUDF::new_from_impl(DateAddSub::new_add()),
         ScalarUDF::new_from_impl(DateAddSub::new_sub()),
@@ -39,12 +53,18 @@ pub fn registerable_scalar_udfs_iter() -> impl Iterator<Item = ScalarUDF> {
 pub enum CubeAggregateUDFKind {
     MergeHll, // merge(), accepting the HyperLogLog sketches.
     Xirr,
+    MergeTheta,   // merge_theta(), accepting the DataSketches theta sketches.
+    AnyValue,     // Only used by aggregate indexes.
+    LastValue,    // Only used by aggregate indexes.
+    MergeTDigest, // merge_tdigest(), accepting the t-digest quantile sketches.
 }
 
 pub fn registerable_aggregate_udfs_iter() -> impl Iterator<Item = AggregateUDF> {
     [
         AggregateUDF::new_from_impl(HllMergeUDF::new()),
+        AggregateUDF::new_from_impl(TDigestMergeUDF::new()),
         AggregateUDF::new_from_impl(XirrUDF::new()),
+        AggregateUDF::new_from_impl(ThetaMergeUDF::new()),
     ]
     .into_iter()
 }
@@ -53,6 +73,10 @@ pub fn aggregate_udf_by_kind(k: CubeAggregateUDFKind) -> AggregateUDF {
     match k {
         CubeAggregateUDFKind::MergeHll => AggregateUDF::new_from_impl(HllMergeUDF::new()),
         CubeAggregateUDFKind::Xirr => AggregateUDF::new_from_impl(XirrUDF::new()),
+        CubeAggregateUDFKind::MergeTheta => AggregateUDF::new_from_impl(ThetaMergeUDF::new()),
+        CubeAggregateUDFKind::AnyValue => AggregateUDF::new_from_impl(PickValueUDF::new(false)),
+        CubeAggregateUDFKind::LastValue => AggregateUDF::new_from_impl(PickValueUDF::new(true)),
+        CubeAggregateUDFKind::MergeTDigest => AggregateUDF::new_from_impl(TDigestMergeUDF::new()),
     }
 }
 
@@ -64,6 +88,12 @@ pub fn aggregate_kind_by_name(n: &str) -> Option<CubeAggregateUDFKind> {
     if n == XIRR_UDAF_NAME {
         return Some(CubeAggregateUDFKind::Xirr);
     }
+    if n == "merge_theta" {
+        return Some(CubeAggregateUDFKind::MergeTheta);
+    }
+    if n == "merge_tdigest" {
+        return Some(CubeAggregateUDFKind::MergeTDigest);
+    }
     return None;
 }
 
@@ -620,16 +650,41 @@ impl ScalarUDFImpl for DateAddSub {
     }
 }
 
+/// Arrow types of list columns for every supported element type, so list functions can have
+/// typed signatures.
+fn list_data_types() -> Vec<DataType> {
+    [
+        ColumnType::String,
+        ColumnType::Int,
+        ColumnType::Float,
+        ColumnType::Boolean,
+        ColumnType::Timestamp,
+        ColumnType::Date,
+    ]
+    .into_iter()
+    .map(|element| {
+        let column = Column::new("list".to_string(), ColumnType::List(Box::new(element)), 0);
+        let field: Field = (&column).into();
+        field.data_type().clone()
+    })
+    .collect()
+}
+
+/// `cardinality(x)` returns the estimated number of distinct values for HyperLogLog sketches and
+/// the number of elements for lists.
 #[derive(Debug)]
 pub(crate) struct HllCardinality {
     signature: Signature,
 }
 impl HllCardinality {
     pub fn new() -> HllCardinality {
-        let signature = Signature::new(
-            TypeSignature::Exact(vec![DataType::Binary]),
-            Volatility::Immutable,
+        let mut signatures = vec![TypeSignature::Exact(vec![DataType::Binary])];
+        signatures.extend(
+            list_data_types()
+                .into_iter()
+                .map(|t| TypeSignature::Exact(vec![t])),
         );
+        let signature = Signature::one_of(signatures, Volatility::Immutable);
 
         HllCardinality { signature }
     }
@@ -660,21 +715,34 @@ impl ScalarUDFImpl for HllCardinality {
     }
     fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
         assert_eq!(args.len(), 1);
-        let sketches = args[0].clone().into_array(1)?;
-        let sketches = sketches
-            .as_any()
-            .downcast_ref::<BinaryArray>()
-            .expect("expected binary data");
-
-        let mut r = UInt64Builder::with_capacity(sketches.len());
-        for s in sketches {
-            match s {
-                None => r.append_null(),
-                Some(d) => {
-                    if d.len() == 0 {
-                        r.append_value(0)
+        let values = args[0].clone().into_array(1)?;
+        let mut r = UInt64Builder::with_capacity(values.len());
+        match values.data_type() {
+            DataType::List(_) => {
+                let lists = values.as_list::<i32>();
+                for i in 0..lists.len() {
+                    if lists.is_null(i) {
+                        r.append_null()
                     } else {
-                        r.append_value(read_sketch(d)?.cardinality())
+                        r.append_value(lists.value_length(i) as u64)
+                    }
+                }
+            }
+            _ => {
+                let sketches = values
+                    .as_any()
+                    .downcast_ref::<BinaryArray>()
+                    .expect("expected binary data");
+                for s in sketches {
+                    match s {
+                        None => r.append_null(),
+                        Some(d) => {
+                            if d.len() == 0 {
+                                r.append_value(0)
+                            } else {
+                                r.append_value(read_sketch(d)?.cardinality())
+                            }
+                        }
                     }
                 }
             }
@@ -686,6 +754,347 @@ impl ScalarUDFImpl for HllCardinality {
     }
 }
 
+/// `quantile(sketch, q)` estimates the value at quantile `q` (between 0 and 1) of the t-digest
+/// sketch. Returns NULL for empty sketches.
+#[derive(Debug)]
+struct Quantile {
+    signature: Signature,
+}
+
+impl Quantile {
+    fn new() -> Quantile {
+        Quantile {
+            signature: Signature::exact(
+                vec![DataType::Binary, DataType::Float64],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl ScalarUDFImpl for Quantile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "quantile"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(DataType::Float64)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        assert_eq!(args.len(), 2);
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let sketches = arrays[0].as_binary::<i32>();
+        let quantiles = arrays[1].as_primitive::<Float64Type>();
+        let mut r = Float64Builder::with_capacity(sketches.len());
+        for (s, q) in sketches.iter().zip(quantiles.iter()) {
+            let (Some(s), Some(q)) = (s, q) else {
+                r.append_null();
+                continue;
+            };
+            if !(0. ..=1.).contains(&q) {
+                return Err(DataFusionError::Execution(format!(
+                    "quantile must be between 0 and 1, got {}",
+                    q
+                )));
+            }
+            // Empty data means an empty sketch.
+            if s.is_empty() {
+                r.append_null();
+                continue;
+            }
+            r.append_option(read_tdigest(s)?.quantile(q));
+        }
+        Ok(ColumnarValue::Array(Arc::new(r.finish())))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ThetaSetOperationKind {
+    Union,
+    Intersect,
+    ANotB,
+}
+
+/// `theta_union(s1, s2, ...)`, `theta_intersect(s1, s2, ...)` and `theta_a_not_b(a, b)` combine
+/// theta sketches row by row. Use `merge_theta()` to union sketches across rows. Empty data is
+/// treated as an empty sketch, same as in `merge_theta()`, and NULL in any argument gives NULL.
+#[derive(Debug)]
+struct ThetaSetOperation {
+    signature: Signature,
+    kind: ThetaSetOperationKind,
+}
+
+impl ThetaSetOperation {
+    fn new(kind: ThetaSetOperationKind) -> ThetaSetOperation {
+        let signature = match kind {
+            ThetaSetOperationKind::Union | ThetaSetOperationKind::Intersect => {
+                Signature::variadic(vec![DataType::Binary], Volatility::Immutable)
+            }
+            ThetaSetOperationKind::ANotB => Signature::exact(
+                vec![DataType::Binary, DataType::Binary],
+                Volatility::Immutable,
+            ),
+        };
+        ThetaSetOperation { signature, kind }
+    }
+
+    fn apply(&self, sketches: &[&[u8]]) -> Result<Vec<u8>, DataFusionError> {
+        let result = match self.kind {
+            ThetaSetOperationKind::Union => {
+                let mut union = ThetaUnionDataSketch::new();
+                for s in sketches.iter().filter(|s| !s.is_empty()) {
+                    union
+                        .merge_with(read_theta_sketch(s)?)
+                        .map_err(|e| DataFusionError::Execution(e.message))?;
+                }
+                union.result()
+            }
+            ThetaSetOperationKind::Intersect => {
+                if sketches.iter().any(|s| s.is_empty()) {
+                    return Ok(Vec::new());
+                }
+                let mut intersection = ThetaIntersectionDataSketch::new();
+                for s in sketches {
+                    intersection
+                        .intersect_with(read_theta_sketch(s)?)
+                        .map_err(|e| DataFusionError::Execution(e.message))?;
+                }
+                intersection
+                    .result()
+                    .expect("theta_intersect has at least one argument")
+            }
+            ThetaSetOperationKind::ANotB => {
+                let (a, b) = (sketches[0], sketches[1]);
+                if a.is_empty() || b.is_empty() {
+                    return Ok(a.to_vec());
+                }
+                read_theta_sketch(a)?
+                    .a_not_b(&read_theta_sketch(b)?)
+                    .map_err(|e| DataFusionError::Execution(e.message))?
+            }
+        };
+        Ok(result.write())
+    }
+}
+
+impl ScalarUDFImpl for ThetaSetOperation {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        match self.kind {
+            ThetaSetOperationKind::Union => "theta_union",
+            ThetaSetOperationKind::Intersect => "theta_intersect",
+            ThetaSetOperationKind::ANotB => "theta_a_not_b",
+        }
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(DataType::Binary)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let columns = arrays.iter().map(|a| a.as_binary::<i32>()).collect_vec();
+        let len = arrays[0].len();
+        let mut r = BinaryBuilder::with_capacity(len, 0);
+        let mut sketches = Vec::with_capacity(columns.len());
+        for i in 0..len {
+            if columns.iter().any(|c| c.is_null(i)) {
+                r.append_null();
+                continue;
+            }
+            sketches.clear();
+            sketches.extend(columns.iter().map(|c| c.value(i)));
+            r.append_value(self.apply(&sketches)?);
+        }
+        Ok(ColumnarValue::Array(Arc::new(r.finish())))
+    }
+}
+
+/// `theta_estimate(sketch)` returns the estimated number of distinct values in the theta sketch.
+#[derive(Debug)]
+struct ThetaEstimate {
+    signature: Signature,
+}
+
+impl ThetaEstimate {
+    fn new() -> ThetaEstimate {
+        ThetaEstimate {
+            signature: Signature::exact(vec![DataType::Binary], Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ThetaEstimate {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "theta_estimate"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(DataType::UInt64)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        assert_eq!(args.len(), 1);
+        let values = args[0].clone().into_array(1)?;
+        let sketches = values.as_binary::<i32>();
+        let mut r = UInt64Builder::with_capacity(sketches.len());
+        for s in sketches {
+            match s {
+                None => r.append_null(),
+                Some(d) if d.is_empty() => r.append_value(0),
+                Some(d) => r.append_value(read_theta_sketch(d)?.estimate()),
+            }
+        }
+        Ok(ColumnarValue::Array(Arc::new(r.finish())))
+    }
+}
+
+/// `array_contains(list, value)` checks whether the list has an element equal to `value`.
+#[derive(Debug)]
+struct ArrayContains {
+    signature: Signature,
+}
+
+impl ArrayContains {
+    fn new() -> ArrayContains {
+        ArrayContains {
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for ArrayContains {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "array_contains"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        match &arg_types[0] {
+            DataType::List(_) | DataType::Null => Ok(DataType::Boolean),
+            t => Err(DataFusionError::Plan(format!(
+                "array_contains expects list as the first argument, got {}",
+                t
+            ))),
+        }
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        assert_eq!(args.len(), 2);
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        if arrays[0].data_type() == &DataType::Null {
+            return Ok(ColumnarValue::Array(new_null_array(
+                &DataType::Boolean,
+                arrays[0].len(),
+            )));
+        }
+        let lists = arrays[0].as_list::<i32>();
+        let values = cast(&arrays[1], &lists.value_type())?;
+        let mut r = BooleanBuilder::with_capacity(lists.len());
+        for i in 0..lists.len() {
+            if lists.is_null(i) || values.is_null(i) {
+                r.append_null();
+                continue;
+            }
+            let found = eq(&lists.value(i), &Scalar::new(values.slice(i, 1)))?;
+            r.append_value(found.true_count() != 0);
+        }
+        Ok(ColumnarValue::Array(Arc::new(r.finish())))
+    }
+}
+
+/// `json_extract_path_text(json, key, ...)` follows the path of object keys and array indexes
+/// and returns the value found as text, or NULL if there is no such path. Same as in Postgres.
+#[derive(Debug)]
+struct JsonExtractPathText {
+    signature: Signature,
+}
+
+impl JsonExtractPathText {
+    fn new() -> JsonExtractPathText {
+        JsonExtractPathText {
+            signature: Signature::variadic_any(Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for JsonExtractPathText {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn name(&self) -> &str {
+        "json_extract_path_text"
+    }
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        if arg_types.len() < 2 {
+            return Err(DataFusionError::Plan(
+                "json_extract_path_text expects json and at least one path element".to_string(),
+            ));
+        }
+        Ok(DataType::Utf8)
+    }
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        let arrays = ColumnarValue::values_to_arrays(args)?
+            .iter()
+            .map(|a| cast(a, &DataType::Utf8))
+            .collect::<Result<Vec<_>, _>>()?;
+        let arrays = arrays.iter().map(|a| a.as_string::<i32>()).collect_vec();
+        let (docs, path) = (arrays[0], &arrays[1..]);
+        let mut r = StringBuilder::with_capacity(docs.len(), 0);
+        for i in 0..docs.len() {
+            if docs.is_null(i) || path.iter().any(|p| p.is_null(i)) {
+                r.append_null();
+                continue;
+            }
+            let doc = json::parse(docs.value(i)).map_err(|e| {
+                DataFusionError::Execution(format!(
+                    "json_extract_path_text: malformed JSON '{}': {}",
+                    docs.value(i),
+                    e
+                ))
+            })?;
+            let mut current = Some(&doc);
+            for p in path {
+                let key = p.value(i);
+                current = match current {
+                    Some(json::JsonValue::Object(o)) => o.get(key),
+                    Some(json::JsonValue::Array(a)) => {
+                        key.parse::<usize>().ok().and_then(|index| a.get(index))
+                    }
+                    _ => None,
+                };
+            }
+            match current {
+                None => r.append_null(),
+                Some(v) if v.is_null() => r.append_null(),
+                Some(v) => match v.as_str() {
+                    Some(s) => r.append_value(s),
+                    None => r.append_value(v.dump()),
+                },
+            }
+        }
+        Ok(ColumnarValue::Array(Arc::new(r.finish())))
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct HllMergeUDF {
     signature: Signature,
@@ -835,6 +1244,342 @@ impl HllMergeAccumulator {
     }
 }
 
+#[derive(Debug)]
+pub(crate) struct ThetaMergeUDF {
+    signature: Signature,
+}
+impl ThetaMergeUDF {
+    fn new() -> ThetaMergeUDF {
+        ThetaMergeUDF {
+            signature: Signature::exact(vec![DataType::Binary], Volatility::Stable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for ThetaMergeUDF {
+    fn name(&self) -> &str {
+        return "merge_theta";
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::common::Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn accumulator(
+        &self,
+        _acc_args: AccumulatorArgs,
+    ) -> datafusion::common::Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ThetaMergeAccumulator { acc: None }))
+    }
+}
+
+#[derive(Debug)]
+struct ThetaMergeAccumulator {
+    acc: Option<ThetaUnionDataSketch>,
+}
+
+impl ThetaMergeAccumulator {
+    fn merge_sketches(&mut self, values: &ArrayRef) -> Result<(), DataFusionError> {
+        let value_rows = values
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .ok_or_else(|| {
+                CubeError::internal(
+                    "invalid array type passed to merge_theta, expecting theta sketches"
+                        .to_string(),
+                )
+            })?;
+        for data in value_rows.iter().flatten() {
+            // Empty data means an empty sketch.
+            if !data.is_empty() {
+                self.acc
+                    .get_or_insert_with(ThetaUnionDataSketch::new)
+                    .merge_with(read_theta_sketch(data)?)
+                    .map_err(|e| DataFusionError::Execution(e.message))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for ThetaMergeAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<(), DataFusionError> {
+        assert_eq!(values.len(), 1);
+        self.merge_sketches(&values[0])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue, DataFusionError> {
+        self.peek_evaluate()
+    }
+
+    // Cube ext:
+    fn peek_evaluate(&self) -> Result<ScalarValue, DataFusionError> {
+        let v = match &self.acc {
+            None => Vec::new(),
+            Some(s) => s.write(),
+        };
+        Ok(ScalarValue::Binary(Some(v)))
+    }
+
+    fn size(&self) -> usize {
+        size_of::<Self>() + self.acc.as_ref().map_or(0, |u| u.allocated_size())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<(), DataFusionError> {
+        assert_eq!(states.len(), 1);
+        self.merge_sketches(&states[0])
+    }
+
+    fn reset(&mut self) -> Result<(), DataFusionError> {
+        self.acc = None;
+        Ok(())
+    }
+    fn peek_state(&self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.peek_evaluate()?])
+    }
+    fn supports_cube_ext(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TDigestMergeUDF {
+    signature: Signature,
+}
+impl TDigestMergeUDF {
+    fn new() -> TDigestMergeUDF {
+        TDigestMergeUDF {
+            signature: Signature::exact(vec![DataType::Binary], Volatility::Stable),
+        }
+    }
+}
+
+impl AggregateUDFImpl for TDigestMergeUDF {
+    fn name(&self) -> &str {
+        return "merge_tdigest";
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::common::Result<DataType> {
+        Ok(DataType::Binary)
+    }
+
+    fn accumulator(
+        &self,
+        _acc_args: AccumulatorArgs,
+    ) -> datafusion::common::Result<Box<dyn Accumulator>> {
+        Ok(Box::new(TDigestMergeAccumulator { acc: None }))
+    }
+}
+
+#[derive(Debug)]
+struct TDigestMergeAccumulator {
+    acc: Option<TDigest>,
+}
+
+impl TDigestMergeAccumulator {
+    fn merge_sketches(&mut self, values: &ArrayRef) -> Result<(), DataFusionError> {
+        let value_rows = values
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .ok_or_else(|| {
+                CubeError::internal(
+                    "invalid array type passed to merge_tdigest, expecting t-digest sketches"
+                        .to_string(),
+                )
+            })?;
+        for data in value_rows.iter().flatten() {
+            // Empty data means an empty sketch.
+            if !data.is_empty() {
+                let s = read_tdigest(data)?;
+                match &mut self.acc {
+                    None => self.acc = Some(s),
+                    Some(acc) => acc.merge_with(&s),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for TDigestMergeAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<(), DataFusionError> {
+        assert_eq!(values.len(), 1);
+        self.merge_sketches(&values[0])
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue, DataFusionError> {
+        self.peek_evaluate()
+    }
+
+    // Cube ext:
+    fn peek_evaluate(&self) -> Result<ScalarValue, DataFusionError> {
+        let v = match &self.acc {
+            None => Vec::new(),
+            Some(s) => s.write(),
+        };
+        Ok(ScalarValue::Binary(Some(v)))
+    }
+
+    fn size(&self) -> usize {
+        size_of::<Self>() + self.acc.as_ref().map_or(0, |d| d.allocated_size())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<(), DataFusionError> {
+        assert_eq!(states.len(), 1);
+        self.merge_sketches(&states[0])
+    }
+
+    fn reset(&mut self) -> Result<(), DataFusionError> {
+        self.acc = None;
+        Ok(())
+    }
+    fn peek_state(&self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.peek_evaluate()?])
+    }
+    fn supports_cube_ext(&self) -> bool {
+        true
+    }
+}
+
+pub fn read_tdigest(data: &[u8]) -> Result<TDigest, DataFusionError> {
+    TDigest::read(data).map_err(|e| DataFusionError::Execution(e.message))
+}
+
+pub fn read_theta_sketch(data: &[u8]) -> Result<ThetaDataSketch, DataFusionError> {
+    ThetaDataSketch::read(data).map_err(|e| DataFusionError::Execution(e.message))
+}
+
+/// Keeps a single non-null value of the column: `ANY_VALUE` takes the first one it sees and
+/// `LAST_VALUE` the last one. Only used by aggregate indexes, where rows are fed in the order they
+/// were ingested, so `LAST_VALUE` ends up with the most recently ingested value. `LAST_VALUE` is
+/// rejected for tables with a `seq_column`, rows replayed out of order keep the value ingested last.
+#[derive(Debug)]
+pub(crate) struct PickValueUDF {
+    signature: Signature,
+    last: bool,
+}
+
+impl PickValueUDF {
+    fn new(last: bool) -> PickValueUDF {
+        PickValueUDF {
+            signature: Signature::any(1, Volatility::Immutable),
+            last,
+        }
+    }
+}
+
+impl AggregateUDFImpl for PickValueUDF {
+    fn name(&self) -> &str {
+        if self.last {
+            "last_value"
+        } else {
+            "any_value"
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> datafusion::common::Result<DataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn accumulator(
+        &self,
+        acc_args: AccumulatorArgs,
+    ) -> datafusion::common::Result<Box<dyn Accumulator>> {
+        Ok(Box::new(PickValueAccumulator {
+            last: self.last,
+            value: ScalarValue::try_from(acc_args.return_type)?,
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct PickValueAccumulator {
+    last: bool,
+    value: ScalarValue,
+}
+
+impl Accumulator for PickValueAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<(), DataFusionError> {
+        assert_eq!(values.len(), 1);
+        let values = &values[0];
+        let index = if self.last {
+            (0..values.len()).rev().find(|i| values.is_valid(*i))
+        } else if self.value.is_null() {
+            (0..values.len()).find(|i| values.is_valid(*i))
+        } else {
+            None
+        };
+        if let Some(i) = index {
+            self.value = ScalarValue::try_from_array(values, i)?;
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue, DataFusionError> {
+        self.peek_evaluate()
+    }
+
+    // Cube ext:
+    fn peek_evaluate(&self) -> Result<ScalarValue, DataFusionError> {
+        Ok(self.value.clone())
+    }
+
+    fn size(&self) -> usize {
+        size_of_val(self) - size_of_val(&self.value) + self.value.size()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.evaluate()?])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<(), DataFusionError> {
+        self.update_batch(states)
+    }
+
+    fn reset(&mut self) -> Result<(), DataFusionError> {
+        self.value = ScalarValue::try_from(&self.value.data_type())?;
+        Ok(())
+    }
+    fn peek_state(&self) -> Result<Vec<ScalarValue>, DataFusionError> {
+        Ok(vec![self.peek_evaluate()?])
+    }
+    fn supports_cube_ext(&self) -> bool {
+        true
+    }
+}
+
 pub fn read_sketch(data: &[u8]) -> Result<Hll, DataFusionError> {
     return Hll::read(&data).map_err(|e| DataFusionError::Execution(e.message));
 }
@@ -0,0 +1,844 @@
+use crate::app_metrics;
+use crate::di_service;
+use crate::remotefs::ExtendedRemoteFs;
+use crate::remotefs::{CommonRemoteFsUtils, LocalDirRemoteFs, RemoteFile, RemoteFs};
+use crate::util::lock::acquire_lock;
+use crate::CubeError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::cube_ext;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use log::{debug, info};
+use regex::{NoExpand, Regex};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tempfile::{NamedTempFile, PathPersistError};
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+use tokio_util::codec::{BytesCodec, FramedRead};
+use url::Url;
+
+/// Version of the Blob service REST API used for all requests.
+const AZURE_STORAGE_API_VERSION: &str = "2021-08-06";
+/// Maximum number of blobs returned by a single List Blobs call.
+const LIST_PAGE_SIZE: usize = 5000;
+
+#[derive(Clone)]
+enum AzureCredentials {
+    /// Requests are signed with the storage account key (Shared Key authorization).
+    SharedKey { account_key: Vec<u8> },
+    /// Shared access signature appended to the query string of every request.
+    SasToken(String),
+}
+
+/// Everything needed to issue requests against a single container. Kept separate from the
+/// filesystem itself, so paginated listings can outlive the call that started them.
+struct AzureBlobClient {
+    http: reqwest::Client,
+    endpoint: Url,
+    account: String,
+    credentials: AzureCredentials,
+    container: String,
+}
+
+/// Stores files as block blobs in a single Azure Blob Storage container.
+///
+/// The storage account is configured with `CUBESTORE_AZURE_ACCOUNT_NAME` and either
+/// `CUBESTORE_AZURE_ACCOUNT_KEY` or `CUBESTORE_AZURE_SAS_TOKEN`. `CUBESTORE_AZURE_ENDPOINT`
+/// overrides the default `https://<account>.blob.core.windows.net` endpoint, e.g. to run
+/// against the Azurite emulator: `http://127.0.0.1:10000/devstoreaccount1`.
+pub struct AzureBlobRemoteFs {
+    dir: PathBuf,
+    client: Arc<AzureBlobClient>,
+    sub_path: Option<String>,
+    delete_mut: Mutex<()>,
+}
+
+impl fmt::Debug for AzureBlobRemoteFs {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Do not expose Azure credentials.
+        f.debug_struct("AzureBlobRemoteFs")
+            .field("dir", &self.dir)
+            .field("sub_path", &self.sub_path)
+            .field("account", &self.client.account)
+            .field("container", &self.client.container)
+            .field("endpoint", &self.client.endpoint.as_str())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AzureBlobRemoteFs {
+    pub fn new(
+        dir: PathBuf,
+        container_name: String,
+        sub_path: Option<String>,
+    ) -> Result<Arc<Self>, CubeError> {
+        let account = env::var("CUBESTORE_AZURE_ACCOUNT_NAME").map_err(|_| {
+            CubeError::user(
+                "CUBESTORE_AZURE_ACCOUNT_NAME required when CUBESTORE_AZURE_CONTAINER is set"
+                    .to_string(),
+            )
+        })?;
+        let credentials = credentials_from_env()?;
+        let endpoint = match env::var("CUBESTORE_AZURE_ENDPOINT") {
+            Ok(endpoint) => Url::parse(&endpoint)?,
+            Err(_) => Url::parse(&format!("https://{}.blob.core.windows.net", account))?,
+        };
+        if endpoint.cannot_be_a_base() {
+            return Err(CubeError::user(format!(
+                "Invalid CUBESTORE_AZURE_ENDPOINT value '{}'",
+                endpoint
+            )));
+        }
+
+        Ok(Arc::new(Self {
+            dir,
+            client: Arc::new(AzureBlobClient {
+                http: reqwest::Client::new(),
+                endpoint,
+                account,
+                credentials,
+                container: container_name,
+            }),
+            sub_path,
+            delete_mut: Mutex::new(()),
+        }))
+    }
+
+    /// Creates the container unless it already exists. CubeStore expects the container to be
+    /// provisioned beforehand, this is used to prepare fresh emulator instances in tests.
+    pub async fn create_container_if_not_exists(&self) -> Result<(), CubeError> {
+        let mut url = self.client.container_url();
+        url.query_pairs_mut().append_pair("restype", "container");
+        let response = self
+            .client
+            .send(Method::PUT, url, HeaderMap::new(), None)
+            .await?;
+        if response.status() == StatusCode::CONFLICT {
+            return Ok(());
+        }
+        AzureBlobClient::check_response(response, "create container").await?;
+        Ok(())
+    }
+}
+
+fn credentials_from_env() -> Result<AzureCredentials, CubeError> {
+    if let Ok(account_key) = env::var("CUBESTORE_AZURE_ACCOUNT_KEY") {
+        let account_key = base64::decode(account_key.trim()).map_err(|e| {
+            CubeError::user(format!("Invalid CUBESTORE_AZURE_ACCOUNT_KEY value: {}", e))
+        })?;
+        Ok(AzureCredentials::SharedKey { account_key })
+    } else if let Ok(token) = env::var("CUBESTORE_AZURE_SAS_TOKEN") {
+        Ok(AzureCredentials::SasToken(
+            token.trim().trim_start_matches('?').to_string(),
+        ))
+    } else {
+        Err(CubeError::user(
+            "Either CUBESTORE_AZURE_ACCOUNT_KEY or CUBESTORE_AZURE_SAS_TOKEN required when CUBESTORE_AZURE_CONTAINER is set"
+                .to_string(),
+        ))
+    }
+}
+
+di_service!(AzureBlobRemoteFs, [RemoteFs, ExtendedRemoteFs]);
+
+#[async_trait]
+impl RemoteFs for AzureBlobRemoteFs {
+    async fn temp_upload_path(&self, remote_path: String) -> Result<String, CubeError> {
+        CommonRemoteFsUtils::temp_upload_path(self, remote_path).await
+    }
+
+    async fn uploads_dir(&self) -> Result<String, CubeError> {
+        CommonRemoteFsUtils::uploads_dir(self).await
+    }
+
+    async fn check_upload_file(
+        &self,
+        remote_path: String,
+        expected_size: u64,
+    ) -> Result<(), CubeError> {
+        CommonRemoteFsUtils::check_upload_file(self, remote_path, expected_size).await
+    }
+
+    async fn upload_file(
+        &self,
+        temp_upload_path: String,
+        remote_path: String,
+    ) -> Result<u64, CubeError> {
+        app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+            1,
+            Some(&vec![
+                "operation:upload_file".to_string(),
+                "driver:azure".to_string(),
+            ]),
+        );
+        let time = SystemTime::now();
+        debug!("Uploading {}", remote_path);
+        let size = fs::metadata(temp_upload_path.clone()).await?.len();
+        let file = File::open(temp_upload_path.clone()).await?;
+        let body = reqwest::Body::wrap_stream(FramedRead::new(file, BytesCodec::new()));
+
+        // Put Blob accepts up to 5000 MiB in a single request, which is well above the size of
+        // files CubeStore produces.
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(size));
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        headers.insert("x-ms-blob-type", HeaderValue::from_static("BlockBlob"));
+        let url = self.client.blob_url(&self.azure_path(&remote_path));
+        let response = self
+            .client
+            .send(Method::PUT, url, headers, Some(body))
+            .await?;
+        AzureBlobClient::check_response(response, "upload").await?;
+
+        self.check_upload_file(remote_path.clone(), size).await?;
+
+        let local_path = self.dir.as_path().join(&remote_path);
+        if Path::new(&temp_upload_path) != local_path {
+            fs::create_dir_all(local_path.parent().unwrap())
+                .await
+                .map_err(|e| {
+                    CubeError::internal(format!(
+                        "Create dir {}: {}",
+                        local_path.parent().as_ref().unwrap().to_string_lossy(),
+                        e
+                    ))
+                })?;
+            fs::rename(&temp_upload_path, local_path.clone()).await?;
+        }
+        info!("Uploaded {} ({:?})", remote_path, time.elapsed()?);
+        Ok(fs::metadata(local_path).await?.len())
+    }
+
+    async fn download_file(
+        &self,
+        remote_path: String,
+        _expected_file_size: Option<u64>,
+    ) -> Result<String, CubeError> {
+        let mut local_file = self.dir.as_path().join(&remote_path);
+        let local_dir = local_file.parent().unwrap();
+        let downloads_dirs = local_dir.join("downloads");
+
+        fs::create_dir_all(&downloads_dirs).await?;
+        if !local_file.exists() {
+            app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+                1,
+                Some(&vec![
+                    "operation:download_file".to_string(),
+                    "driver:azure".to_string(),
+                ]),
+            );
+            let time = SystemTime::now();
+            debug!("Downloading {}", remote_path);
+            let (temp_file, temp_path) =
+                cube_ext::spawn_blocking(move || NamedTempFile::new_in(downloads_dirs))
+                    .await??
+                    .into_parts();
+            let mut writer = BufWriter::new(tokio::fs::File::from_std(temp_file));
+
+            let url = self.client.blob_url(&self.azure_path(&remote_path));
+            let response = self
+                .client
+                .send(Method::GET, url, HeaderMap::new(), None)
+                .await?;
+            let response = AzureBlobClient::check_response(response, "download").await?;
+            let mut stream = response.bytes_stream();
+            let mut size = 0;
+            while let Some(bytes) = stream.next().await {
+                let bytes = bytes.map_err(http_error)?;
+                writer.write_all(&bytes).await?;
+                size += bytes.len();
+            }
+            writer.flush().await?;
+
+            local_file = cube_ext::spawn_blocking(move || -> Result<PathBuf, PathPersistError> {
+                temp_path.persist(&local_file)?;
+                Ok(local_file)
+            })
+            .await??;
+
+            info!(
+                "Downloaded {} ({:?}) ({} bytes)",
+                remote_path,
+                time.elapsed()?,
+                size
+            );
+        }
+        Ok(local_file.into_os_string().into_string().unwrap())
+    }
+
+    async fn delete_file(&self, remote_path: String) -> Result<(), CubeError> {
+        app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+            1,
+            Some(&vec![
+                "operation:delete_file".to_string(),
+                "driver:azure".to_string(),
+            ]),
+        );
+        let time = SystemTime::now();
+        debug!("Deleting {}", remote_path);
+        let url = self.client.blob_url(&self.azure_path(&remote_path));
+        let response = self
+            .client
+            .send(Method::DELETE, url, HeaderMap::new(), None)
+            .await?;
+        // Same as S3, deleting a missing file is not an error.
+        if response.status() != StatusCode::NOT_FOUND {
+            AzureBlobClient::check_response(response, "delete").await?;
+        }
+        info!("Deleting {} ({:?})", remote_path, time.elapsed()?);
+
+        let _guard = acquire_lock("delete file", self.delete_mut.lock()).await?;
+        let local = self.dir.as_path().join(remote_path);
+        if fs::metadata(local.clone()).await.is_ok() {
+            fs::remove_file(local.clone()).await?;
+            LocalDirRemoteFs::remove_empty_paths(self.dir.as_path().to_path_buf(), local.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, remote_prefix: String) -> Result<Vec<String>, CubeError> {
+        let leading_subpath = self.leading_subpath_regex();
+        self.list_blobs_and_map(remote_prefix, |blob: ListBlobItem| {
+            Ok(Self::blob_name_to_remote_path(&leading_subpath, &blob.name))
+        })
+        .await
+    }
+
+    async fn list_with_metadata(
+        &self,
+        remote_prefix: String,
+    ) -> Result<Vec<RemoteFile>, CubeError> {
+        let leading_subpath = self.leading_subpath_regex();
+        self.list_blobs_and_map(remote_prefix, |blob: ListBlobItem| {
+            Ok(RemoteFile {
+                remote_path: Self::blob_name_to_remote_path(&leading_subpath, &blob.name),
+                updated: blob.properties.last_modified()?,
+                file_size: blob.properties.content_length,
+            })
+        })
+        .await
+    }
+
+    async fn local_path(&self) -> Result<String, CubeError> {
+        Ok(self.dir.to_str().unwrap().to_owned())
+    }
+
+    async fn local_file(&self, remote_path: String) -> Result<String, CubeError> {
+        let buf = self.dir.join(remote_path);
+        fs::create_dir_all(buf.parent().unwrap()).await?;
+        Ok(buf.to_str().unwrap().to_string())
+    }
+}
+
+#[async_trait]
+impl ExtendedRemoteFs for AzureBlobRemoteFs {
+    async fn list_by_page(
+        &self,
+        remote_prefix: String,
+    ) -> Result<BoxStream<'static, Result<Vec<String>, CubeError>>, CubeError> {
+        let prefix = self.azure_path(&remote_prefix);
+        let client = self.client.clone();
+        let leading_subpath = self.leading_subpath_regex();
+
+        let stream = async_stream::stream! {
+            let mut marker = None;
+            let mut pages_count: i64 = 0;
+
+            loop {
+                let result = client.list_page(&prefix, marker).await?;
+
+                pages_count += 1;
+
+                let page: Vec<String> = result.blobs.blobs.into_iter().map(|blob| Self::blob_name_to_remote_path(&leading_subpath, &blob.name)).collect();
+                marker = result.next_marker.filter(|m| !m.is_empty());
+
+                yield Ok(page);
+
+                if marker.is_none() {
+                    break;
+                }
+            }
+
+            Self::pages_count_app_metrics_and_logging(pages_count, "streaming");
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
+
+struct LeadingSubpath(Regex);
+
+impl AzureBlobRemoteFs {
+    fn leading_subpath_regex(&self) -> LeadingSubpath {
+        LeadingSubpath(
+            Regex::new(format!("^{}", regex::escape(&self.azure_path(""))).as_str()).unwrap(),
+        )
+    }
+
+    fn blob_name_to_remote_path(leading_subpath: &LeadingSubpath, blob_name: &str) -> String {
+        leading_subpath
+            .0
+            .replace(blob_name, NoExpand(""))
+            .to_string()
+    }
+
+    async fn list_blobs_and_map<T, F>(
+        &self,
+        remote_prefix: String,
+        mut f: F,
+    ) -> Result<Vec<T>, CubeError>
+    where
+        F: FnMut(ListBlobItem) -> Result<T, CubeError> + Copy,
+    {
+        let prefix = self.azure_path(&remote_prefix);
+        let mut mapped_results = Vec::new();
+        let mut marker = None;
+        let mut pages_count: i64 = 0;
+
+        loop {
+            let result = self.client.list_page(&prefix, marker).await?;
+
+            pages_count += 1;
+
+            for blob in result.blobs.blobs.into_iter() {
+                mapped_results.push(f(blob)?);
+            }
+
+            marker = result.next_marker.filter(|m| !m.is_empty());
+            if marker.is_none() {
+                break;
+            }
+        }
+
+        Self::pages_count_app_metrics_and_logging(pages_count, "non-streaming");
+
+        Ok(mapped_results)
+    }
+
+    fn pages_count_app_metrics_and_logging(pages_count: i64, log_op: &str) {
+        app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+            pages_count,
+            Some(&vec![
+                "operation:list".to_string(),
+                "driver:azure".to_string(),
+            ]),
+        );
+        if pages_count > 100 {
+            log::warn!(
+                "Azure list ({}) returned more than 100 pages: {}",
+                log_op,
+                pages_count
+            );
+        }
+    }
+
+    fn azure_path(&self, remote_path: &str) -> String {
+        format!(
+            "{}{}",
+            self.sub_path
+                .as_ref()
+                .map(|p| format!("{}/", p))
+                .unwrap_or_else(|| "".to_string()),
+            remote_path
+        )
+    }
+}
+
+impl AzureBlobClient {
+    fn container_url(&self) -> Url {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .push(&self.container);
+        url
+    }
+
+    fn blob_url(&self, blob_name: &str) -> Url {
+        let mut url = self.container_url();
+        url.path_segments_mut()
+            .unwrap()
+            .extend(blob_name.split('/'));
+        url
+    }
+
+    async fn list_page(
+        &self,
+        prefix: &str,
+        marker: Option<String>,
+    ) -> Result<ListBlobsResponse, CubeError> {
+        let mut url = self.container_url();
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("restype", "container")
+                .append_pair("comp", "list")
+                .append_pair("maxresults", &LIST_PAGE_SIZE.to_string());
+            if !prefix.is_empty() {
+                query.append_pair("prefix", prefix);
+            }
+            if let Some(marker) = &marker {
+                query.append_pair("marker", marker);
+            }
+        }
+        let response = self.send(Method::GET, url, HeaderMap::new(), None).await?;
+        let response = Self::check_response(response, "list").await?;
+        parse_list_blobs_response(&response.text().await.map_err(http_error)?)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        mut url: Url,
+        headers: HeaderMap,
+        body: Option<reqwest::Body>,
+    ) -> Result<reqwest::Response, CubeError> {
+        if let AzureCredentials::SasToken(token) = &self.credentials {
+            let query = match url.query() {
+                Some(query) => format!("{}&{}", query, token),
+                None => token.clone(),
+            };
+            url.set_query(Some(&query));
+        }
+        let mut builder = self
+            .http
+            .request(method, url)
+            .headers(headers)
+            .header("x-ms-date", format_http_date(Utc::now()))
+            .header("x-ms-version", AZURE_STORAGE_API_VERSION);
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        let mut request = builder.build().map_err(http_error)?;
+        if let AzureCredentials::SharedKey { account_key } = &self.credentials {
+            let authorization = shared_key_authorization(
+                &self.account,
+                account_key,
+                &string_to_sign(
+                    &self.account,
+                    request.method(),
+                    request.url(),
+                    request.headers(),
+                ),
+            )?;
+            request.headers_mut().insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&authorization).map_err(|e| {
+                    CubeError::internal(format!("Invalid Azure authorization header: {}", e))
+                })?,
+            );
+        }
+        self.http.execute(request).await.map_err(http_error)
+    }
+
+    async fn check_response(
+        response: reqwest::Response,
+        operation: &str,
+    ) -> Result<reqwest::Response, CubeError> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        // Only the path is reported: the query string can carry a SAS token.
+        let path = response.url().path().to_string();
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(CubeError::internal(format!(
+            "Azure Blob Storage {} of {} failed with {}: {}",
+            operation, path, status, body
+        )))
+    }
+}
+
+/// reqwest errors include the request url, which can carry a SAS token.
+fn http_error(e: reqwest::Error) -> CubeError {
+    CubeError::from(e.without_url())
+}
+
+fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Builds the string signed by Shared Key authorization, see
+/// https://learn.microsoft.com/en-us/rest/api/storageservices/authorize-with-shared-key
+fn string_to_sign(account: &str, method: &Method, url: &Url, headers: &HeaderMap) -> String {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    };
+
+    let mut result = format!("{}\n", method.as_str());
+    for name in [
+        "content-encoding",
+        "content-language",
+        "content-length",
+        "content-md5",
+        "content-type",
+        "date",
+        "if-modified-since",
+        "if-match",
+        "if-none-match",
+        "if-unmodified-since",
+        "range",
+    ] {
+        let value = header(name);
+        // Zero length is signed as an empty string since API version 2015-02-21.
+        if !(name == "content-length" && value == "0") {
+            result.push_str(value);
+        }
+        result.push('\n');
+    }
+
+    let mut ms_headers = headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-ms-"))
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("").trim()))
+        .collect::<Vec<_>>();
+    ms_headers.sort();
+    for (name, value) in ms_headers {
+        result.push_str(&format!("{}:{}\n", name, value));
+    }
+
+    result.push_str(&format!("/{}{}", account, url.path()));
+    let mut params = BTreeMap::<String, Vec<String>>::new();
+    for (name, value) in url.query_pairs() {
+        params
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(value.to_string());
+    }
+    for (name, mut values) in params {
+        values.sort();
+        result.push_str(&format!("\n{}:{}", name, values.join(",")));
+    }
+    result
+}
+
+fn shared_key_authorization(
+    account: &str,
+    account_key: &[u8],
+    string_to_sign: &str,
+) -> Result<String, CubeError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(account_key)
+        .map_err(|e| CubeError::internal(format!("Invalid Azure account key: {}", e)))?;
+    mac.update(string_to_sign.as_bytes());
+    Ok(format!(
+        "SharedKey {}:{}",
+        account,
+        base64::encode(mac.finalize().into_bytes())
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListBlobsResponse {
+    #[serde(rename = "Blobs", default)]
+    blobs: ListBlobs,
+    #[serde(rename = "NextMarker", default)]
+    next_marker: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListBlobs {
+    #[serde(rename = "Blob", default)]
+    blobs: Vec<ListBlobItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListBlobItem {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Properties")]
+    properties: ListBlobProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListBlobProperties {
+    #[serde(rename = "Last-Modified")]
+    last_modified: String,
+    #[serde(rename = "Content-Length")]
+    content_length: u64,
+}
+
+impl ListBlobProperties {
+    fn last_modified(&self) -> Result<DateTime<Utc>, CubeError> {
+        Ok(DateTime::parse_from_rfc2822(&self.last_modified)
+            .map_err(|e| {
+                CubeError::internal(format!(
+                    "Can't parse Azure blob modification time '{}': {}",
+                    self.last_modified, e
+                ))
+            })?
+            .with_timezone(&Utc))
+    }
+}
+
+fn parse_list_blobs_response(body: &str) -> Result<ListBlobsResponse, CubeError> {
+    // Azure prepends a byte order mark to XML responses.
+    serde_xml_rs::from_str(body.trim_start_matches('\u{feff}'))
+        .map_err(|e| CubeError::internal(format!("Can't parse Azure List Blobs response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AZURITE_ACCOUNT: &str = "devstoreaccount1";
+    const AZURITE_KEY: &str =
+        "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+    fn test_client(endpoint: &str) -> AzureBlobClient {
+        AzureBlobClient {
+            http: reqwest::Client::new(),
+            endpoint: Url::parse(endpoint).unwrap(),
+            account: AZURITE_ACCOUNT.to_string(),
+            credentials: AzureCredentials::SharedKey {
+                account_key: base64::decode(AZURITE_KEY).unwrap(),
+            },
+            container: "test-container".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn http_errors_hide_sas_token() {
+        let mut client = test_client("http://127.0.0.1:1");
+        client.credentials = AzureCredentials::SasToken("sv=2021-08-06&sig=secret".to_string());
+        let err = client
+            .send(
+                Method::GET,
+                client.blob_url("a.parquet"),
+                HeaderMap::new(),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(!err.to_string().contains("sig="), "{}", err);
+        assert!(!err.to_string().contains("secret"), "{}", err);
+    }
+
+    fn ms_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-ms-date",
+            HeaderValue::from_static("Sat, 17 Oct 2026 12:00:00 GMT"),
+        );
+        headers.insert(
+            "x-ms-version",
+            HeaderValue::from_static(AZURE_STORAGE_API_VERSION),
+        );
+        headers
+    }
+
+    #[test]
+    fn blob_urls() {
+        let client = test_client("https://devstoreaccount1.blob.core.windows.net");
+        assert_eq!(
+            client.blob_url("sub/a b.parquet").as_str(),
+            "https://devstoreaccount1.blob.core.windows.net/test-container/sub/a%20b.parquet"
+        );
+
+        let client = test_client("http://127.0.0.1:10000/devstoreaccount1/");
+        assert_eq!(
+            client.container_url().as_str(),
+            "http://127.0.0.1:10000/devstoreaccount1/test-container"
+        );
+    }
+
+    #[test]
+    fn shared_key_signature() {
+        let client = test_client("http://127.0.0.1:10000/devstoreaccount1");
+        let url = Url::parse(
+            "http://127.0.0.1:10000/devstoreaccount1/test-container?restype=container&comp=list&maxresults=5000&prefix=dir%2Fa",
+        )
+        .unwrap();
+        let to_sign = string_to_sign(AZURITE_ACCOUNT, &Method::GET, &url, &ms_headers());
+        assert_eq!(
+            to_sign,
+            "GET\n\n\n\n\n\n\n\n\n\n\n\n\
+             x-ms-date:Sat, 17 Oct 2026 12:00:00 GMT\n\
+             x-ms-version:2021-08-06\n\
+             /devstoreaccount1/devstoreaccount1/test-container\n\
+             comp:list\n\
+             maxresults:5000\n\
+             prefix:dir/a\n\
+             restype:container"
+        );
+        let AzureCredentials::SharedKey { account_key } = &client.credentials else {
+            panic!("expected shared key credentials");
+        };
+        assert_eq!(
+            shared_key_authorization(AZURITE_ACCOUNT, account_key, &to_sign).unwrap(),
+            "SharedKey devstoreaccount1:RIEuRSsLUu+Qd7T8b5ovskwTdUBGBRqE6typYThCtsY="
+        );
+
+        let mut headers = ms_headers();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(4));
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        headers.insert("x-ms-blob-type", HeaderValue::from_static("BlockBlob"));
+        let to_sign = string_to_sign(
+            AZURITE_ACCOUNT,
+            &Method::PUT,
+            &client.blob_url("sub/a b.parquet"),
+            &headers,
+        );
+        assert_eq!(
+            shared_key_authorization(AZURITE_ACCOUNT, account_key, &to_sign).unwrap(),
+            "SharedKey devstoreaccount1:00gDTqJED3bL0Kjyu4qBBAGpGEw1PEW+jEq0gskAokQ="
+        );
+    }
+
+    #[test]
+    fn list_blobs_response() {
+        let page = parse_list_blobs_response(
+            "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <EnumerationResults ServiceEndpoint=\"http://127.0.0.1:10000/devstoreaccount1\" ContainerName=\"test-container\">\
+             <Prefix>sub/</Prefix><MaxResults>2</MaxResults>\
+             <Blobs>\
+             <Blob><Name>sub/1.parquet</Name><Properties><Last-Modified>Sat, 17 Oct 2026 12:00:00 GMT</Last-Modified><Etag>0x1</Etag><Content-Length>4</Content-Length><BlobType>BlockBlob</BlobType></Properties></Blob>\
+             <Blob><Name>sub/2.parquet</Name><Properties><Last-Modified>Sun, 18 Oct 2026 08:30:00 GMT</Last-Modified><Etag>0x2</Etag><Content-Length>1024</Content-Length><BlobType>BlockBlob</BlobType></Properties></Blob>\
+             </Blobs>\
+             <NextMarker>marker-1</NextMarker>\
+             </EnumerationResults>",
+        )
+        .unwrap();
+        assert_eq!(page.next_marker.as_deref(), Some("marker-1"));
+        assert_eq!(page.blobs.blobs.len(), 2);
+        assert_eq!(page.blobs.blobs[1].name, "sub/2.parquet");
+        assert_eq!(page.blobs.blobs[1].properties.content_length, 1024);
+        assert_eq!(
+            page.blobs.blobs[1].properties.last_modified().unwrap(),
+            DateTime::parse_from_rfc3339("2026-10-18T08:30:00Z").unwrap()
+        );
+
+        let last_page = parse_list_blobs_response(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <EnumerationResults ContainerName=\"test-container\">\
+             <Blobs /><NextMarker /></EnumerationResults>",
+        )
+        .unwrap();
+        assert!(last_page.blobs.blobs.is_empty());
+        assert_eq!(last_page.next_marker.filter(|m| !m.is_empty()), None);
+    }
+}
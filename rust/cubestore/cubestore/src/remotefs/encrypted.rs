@@ -0,0 +1,732 @@
+use crate::app_metrics;
+use crate::di_service;
+use crate::remotefs::{
+    CommonRemoteFsUtils, ExtendedRemoteFs, LocalDirRemoteFs, RemoteFile, RemoteFs,
+};
+use crate::util::lock::acquire_lock;
+use crate::CubeError;
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use async_trait::async_trait;
+use datafusion::cube_ext;
+use futures::stream::BoxStream;
+use log::{debug, info};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tempfile::NamedTempFile;
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// First bytes of every encrypted file.
+const MAGIC: [u8; 6] = *b"CSTENC";
+const FORMAT_V1: u8 = 1;
+/// Key ids are stored in a fixed-size field, so the size of an encrypted file only depends on
+/// the size of the original one.
+pub const MAX_KEY_ID_LEN: usize = 64;
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+/// Data key encrypted with the key encryption key: nonce + ciphertext + tag.
+const WRAPPED_KEY_SIZE: usize = NONCE_SIZE + KEY_SIZE + TAG_SIZE;
+/// Magic + version + key id length + key id + wrapped data key + segment size.
+const HEADER_SIZE: usize = 6 + 1 + 1 + MAX_KEY_ID_LEN + WRAPPED_KEY_SIZE + 4;
+const WRAPPED_KEY_OFFSET: usize = 8 + MAX_KEY_ID_LEN;
+/// Files are encrypted in segments of this size, so they never have to fit into memory.
+const SEGMENT_SIZE: usize = 1024 * 1024;
+
+/// Keys used to encrypt data keys of remote files. Configured with `CUBESTORE_ENCRYPTION_KEYS`
+/// as a comma-separated list of `<key id>:<base64-encoded 256-bit key>` pairs. New files are
+/// encrypted with the active key, the rest are kept to read files written before key rotation.
+pub struct EncryptionKeyring {
+    active_key_id: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl fmt::Debug for EncryptionKeyring {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Do not expose the keys.
+        let mut key_ids = self.keys.keys().collect::<Vec<_>>();
+        key_ids.sort();
+        f.debug_struct("EncryptionKeyring")
+            .field("active_key_id", &self.active_key_id)
+            .field("key_ids", &key_ids)
+            .finish()
+    }
+}
+
+impl EncryptionKeyring {
+    pub fn from_env(active_key_id: String) -> Result<Self, CubeError> {
+        let keys = env::var("CUBESTORE_ENCRYPTION_KEYS").map_err(|_| {
+            CubeError::user(
+                "CUBESTORE_ENCRYPTION_KEYS required when CUBESTORE_ENCRYPTION_KEY_ID is set"
+                    .to_string(),
+            )
+        })?;
+        Self::parse(&keys, active_key_id)
+    }
+
+    pub fn parse(keys: &str, active_key_id: String) -> Result<Self, CubeError> {
+        let mut parsed = HashMap::new();
+        for entry in keys.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            // Entries are never printed, they contain keys.
+            let (key_id, key) = entry.split_once(':').ok_or_else(|| {
+                CubeError::user(
+                    "Invalid CUBESTORE_ENCRYPTION_KEYS entry, expected '<key id>:<base64 key>'"
+                        .to_string(),
+                )
+            })?;
+            if key_id.is_empty() || key_id.len() > MAX_KEY_ID_LEN {
+                return Err(CubeError::user(format!(
+                    "Encryption key id must be from 1 to {} bytes long: '{}'",
+                    MAX_KEY_ID_LEN, key_id
+                )));
+            }
+            let key = base64::decode(key).map_err(|e| {
+                CubeError::user(format!("Invalid encryption key '{}': {}", key_id, e))
+            })?;
+            if key.len() != KEY_SIZE {
+                return Err(CubeError::user(format!(
+                    "Encryption key '{}' must be {} bytes long, got {}",
+                    key_id,
+                    KEY_SIZE,
+                    key.len()
+                )));
+            }
+            let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+            if parsed.insert(key_id.to_string(), cipher).is_some() {
+                return Err(CubeError::user(format!(
+                    "Duplicate encryption key id '{}'",
+                    key_id
+                )));
+            }
+        }
+        if !parsed.contains_key(&active_key_id) {
+            return Err(CubeError::user(format!(
+                "Active encryption key '{}' is missing in CUBESTORE_ENCRYPTION_KEYS",
+                active_key_id
+            )));
+        }
+        Ok(Self {
+            active_key_id,
+            keys: parsed,
+        })
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+}
+
+/// Size of the file produced by [encrypt] for a file of `plaintext_size` bytes.
+pub fn encrypted_size(plaintext_size: u64) -> u64 {
+    let segments = ((plaintext_size + SEGMENT_SIZE as u64 - 1) / SEGMENT_SIZE as u64).max(1);
+    HEADER_SIZE as u64 + plaintext_size + segments * TAG_SIZE as u64
+}
+
+/// Size of the stored object for a partition or chunk file. The metastore keeps plaintext sizes,
+/// which are used by `upload_file`, `check_upload_file` and `download_file`, while listings report
+/// sizes of stored objects. Files written without encryption have no `encryption_key_id`.
+pub fn stored_file_size(plaintext_size: u64, encryption_key_id: &Option<String>) -> u64 {
+    if encryption_key_id.is_some() {
+        encrypted_size(plaintext_size)
+    } else {
+        plaintext_size
+    }
+}
+
+/// Envelope encryption: every file gets a random data key, which is stored in the file header
+/// encrypted with the active key of `keyring`. Data is split into segments sealed with
+/// AES-256-GCM, each nonce carries the segment number and a flag marking the last segment, so
+/// reordered or truncated files fail to decrypt. Returns the number of plaintext bytes.
+pub fn encrypt(
+    keyring: &EncryptionKeyring,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> Result<u64, CubeError> {
+    let key_id = keyring.active_key_id.as_bytes();
+    let key_cipher = &keyring.keys[&keyring.active_key_id];
+
+    let mut data_key = [0u8; KEY_SIZE];
+    OsRng.fill_bytes(&mut data_key);
+    let mut key_nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut key_nonce);
+    let wrapped_key = key_cipher
+        .encrypt(
+            Nonce::from_slice(&key_nonce),
+            Payload {
+                msg: &data_key,
+                aad: key_id,
+            },
+        )
+        .map_err(|_| CubeError::internal("Can't encrypt data key".to_string()))?;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(&MAGIC);
+    header.push(FORMAT_V1);
+    header.push(key_id.len() as u8);
+    header.extend_from_slice(key_id);
+    header.resize(WRAPPED_KEY_OFFSET, 0);
+    header.extend_from_slice(&key_nonce);
+    header.extend_from_slice(&wrapped_key);
+    header.extend_from_slice(&(SEGMENT_SIZE as u32).to_le_bytes());
+    debug_assert_eq!(header.len(), HEADER_SIZE);
+    writer.write_all(&header)?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+    let mut plaintext_size = 0;
+    let mut index = 0;
+    let mut segment = read_up_to(reader, SEGMENT_SIZE)?;
+    loop {
+        // Full segment might be followed by an empty one, we need to look ahead to know.
+        let next = if segment.len() == SEGMENT_SIZE {
+            read_up_to(reader, SEGMENT_SIZE)?
+        } else {
+            Vec::new()
+        };
+        let last = next.is_empty();
+        let sealed = cipher
+            .encrypt(
+                &segment_nonce(index, last),
+                Payload {
+                    msg: &segment,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| CubeError::internal("Can't encrypt file segment".to_string()))?;
+        writer.write_all(&sealed)?;
+        plaintext_size += segment.len() as u64;
+        if last {
+            break;
+        }
+        segment = next;
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(plaintext_size)
+}
+
+/// Reverses [encrypt]. Input without the encryption header is copied as is when
+/// `allow_unencrypted` is set, which allows reading files uploaded before encryption was enabled.
+pub fn decrypt(
+    keyring: &EncryptionKeyring,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    allow_unencrypted: bool,
+) -> Result<u64, CubeError> {
+    let header = read_up_to(reader, HEADER_SIZE)?;
+    if !header.starts_with(&MAGIC) {
+        if !allow_unencrypted {
+            return Err(CubeError::corrupt_data(
+                "File is not encrypted. Set CUBESTORE_ENCRYPTION_ALLOW_UNENCRYPTED to read files uploaded before encryption was enabled".to_string(),
+            ));
+        }
+        writer.write_all(&header)?;
+        let rest = std::io::copy(reader, writer)?;
+        writer.flush()?;
+        return Ok(header.len() as u64 + rest);
+    }
+    if header.len() != HEADER_SIZE {
+        return Err(CubeError::corrupt_data(
+            "Encrypted file header is truncated".to_string(),
+        ));
+    }
+    if header[6] != FORMAT_V1 {
+        return Err(CubeError::corrupt_data(format!(
+            "Unsupported encrypted file version {}",
+            header[6]
+        )));
+    }
+    let key_id_len = header[7] as usize;
+    if key_id_len == 0 || key_id_len > MAX_KEY_ID_LEN {
+        return Err(CubeError::corrupt_data(
+            "Invalid key id in encrypted file header".to_string(),
+        ));
+    }
+    let key_id = &header[8..8 + key_id_len];
+    let key_cipher = std::str::from_utf8(key_id)
+        .ok()
+        .and_then(|id| keyring.keys.get(id))
+        .ok_or_else(|| {
+            CubeError::user(format!(
+                "File is encrypted with key '{}' which is missing in CUBESTORE_ENCRYPTION_KEYS",
+                String::from_utf8_lossy(key_id)
+            ))
+        })?;
+    let key_nonce = &header[WRAPPED_KEY_OFFSET..WRAPPED_KEY_OFFSET + NONCE_SIZE];
+    let wrapped_key =
+        &header[WRAPPED_KEY_OFFSET + NONCE_SIZE..WRAPPED_KEY_OFFSET + WRAPPED_KEY_SIZE];
+    let data_key = key_cipher
+        .decrypt(
+            Nonce::from_slice(key_nonce),
+            Payload {
+                msg: wrapped_key,
+                aad: key_id,
+            },
+        )
+        .map_err(|_| {
+            CubeError::corrupt_data("Can't decrypt data key of encrypted file".to_string())
+        })?;
+    let segment_size = u32::from_le_bytes(header[HEADER_SIZE - 4..].try_into().unwrap()) as usize;
+    if segment_size == 0 {
+        return Err(CubeError::corrupt_data(
+            "Invalid segment size in encrypted file header".to_string(),
+        ));
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+    let sealed_size = segment_size + TAG_SIZE;
+    let mut plaintext_size = 0;
+    let mut index = 0;
+    let mut sealed = read_up_to(reader, sealed_size)?;
+    loop {
+        let next = if sealed.len() == sealed_size {
+            read_up_to(reader, sealed_size)?
+        } else {
+            Vec::new()
+        };
+        let last = next.is_empty();
+        let segment = cipher
+            .decrypt(
+                &segment_nonce(index, last),
+                Payload {
+                    msg: &sealed,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| {
+                CubeError::corrupt_data(format!(
+                    "Can't decrypt segment {} of encrypted file, the file is corrupted or truncated",
+                    index
+                ))
+            })?;
+        writer.write_all(&segment)?;
+        plaintext_size += segment.len() as u64;
+        if last {
+            break;
+        }
+        sealed = next;
+        index += 1;
+    }
+    writer.flush()?;
+    Ok(plaintext_size)
+}
+
+fn segment_nonce(index: u64, last: bool) -> Nonce<U12> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = last as u8;
+    *Nonce::from_slice(&nonce)
+}
+
+fn read_up_to(reader: &mut impl Read, size: usize) -> Result<Vec<u8>, CubeError> {
+    let mut buf = Vec::with_capacity(size);
+    reader.by_ref().take(size as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Encrypts files before they are uploaded to the wrapped remote filesystem and decrypts them
+/// after download, so only ciphertext leaves the node. Local copies of files are kept in plain
+/// form, the wrapped filesystem uses its own local directory for encrypted files in transit.
+///
+/// Listings are passed through, so `list_with_metadata` reports sizes of encrypted files.
+pub struct EncryptedRemoteFs {
+    dir: PathBuf,
+    remote_fs: Arc<dyn ExtendedRemoteFs>,
+    keyring: Arc<EncryptionKeyring>,
+    allow_unencrypted: bool,
+    delete_mut: Mutex<()>,
+}
+
+impl fmt::Debug for EncryptedRemoteFs {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("EncryptedRemoteFs")
+            .field("dir", &self.dir)
+            .field("remote_fs", &self.remote_fs)
+            .field("keyring", &self.keyring)
+            .field("allow_unencrypted", &self.allow_unencrypted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EncryptedRemoteFs {
+    pub fn new(
+        dir: PathBuf,
+        remote_fs: Arc<dyn ExtendedRemoteFs>,
+        keyring: EncryptionKeyring,
+        allow_unencrypted: bool,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            dir,
+            remote_fs,
+            keyring: Arc::new(keyring),
+            allow_unencrypted,
+            delete_mut: Mutex::new(()),
+        })
+    }
+
+    /// Removes the copy of an encrypted file kept by the wrapped filesystem.
+    async fn remove_encrypted_local_file(&self, remote_path: &str) -> Result<(), CubeError> {
+        let encrypted_dir = PathBuf::from(self.remote_fs.local_path().await?);
+        let local = encrypted_dir.join(remote_path);
+        if fs::metadata(&local).await.is_ok() {
+            fs::remove_file(&local).await?;
+            LocalDirRemoteFs::remove_empty_paths(encrypted_dir, local).await?;
+        }
+        Ok(())
+    }
+}
+
+di_service!(EncryptedRemoteFs, [RemoteFs, ExtendedRemoteFs]);
+
+#[async_trait]
+impl RemoteFs for EncryptedRemoteFs {
+    async fn temp_upload_path(&self, remote_path: String) -> Result<String, CubeError> {
+        CommonRemoteFsUtils::temp_upload_path(self, remote_path).await
+    }
+
+    async fn uploads_dir(&self) -> Result<String, CubeError> {
+        CommonRemoteFsUtils::uploads_dir(self).await
+    }
+
+    async fn check_upload_file(
+        &self,
+        remote_path: String,
+        expected_size: u64,
+    ) -> Result<(), CubeError> {
+        self.remote_fs
+            .check_upload_file(remote_path, encrypted_size(expected_size))
+            .await
+    }
+
+    async fn upload_file(
+        &self,
+        temp_upload_path: String,
+        remote_path: String,
+    ) -> Result<u64, CubeError> {
+        app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+            1,
+            Some(&vec![
+                "operation:encrypt_file".to_string(),
+                "driver:encrypted".to_string(),
+            ]),
+        );
+        let time = SystemTime::now();
+        debug!("Encrypting {}", remote_path);
+        let encrypted_path = self.remote_fs.temp_upload_path(remote_path.clone()).await?;
+        let keyring = self.keyring.clone();
+        let plain_path = temp_upload_path.clone();
+        let encrypted_path_to_write = encrypted_path.clone();
+        let size = cube_ext::spawn_blocking(move || -> Result<u64, CubeError> {
+            let mut reader = BufReader::new(std::fs::File::open(plain_path)?);
+            let mut writer = BufWriter::new(std::fs::File::create(encrypted_path_to_write)?);
+            encrypt(&keyring, &mut reader, &mut writer)
+        })
+        .await??;
+        debug!("Encrypted {} ({:?})", remote_path, time.elapsed()?);
+
+        self.remote_fs
+            .upload_file(encrypted_path, remote_path.clone())
+            .await?;
+        self.remove_encrypted_local_file(&remote_path).await?;
+
+        let local_path = self.dir.as_path().join(&remote_path);
+        if Path::new(&temp_upload_path) != local_path {
+            fs::create_dir_all(local_path.parent().unwrap())
+                .await
+                .map_err(|e| {
+                    CubeError::internal(format!(
+                        "Create dir {}: {}",
+                        local_path.parent().as_ref().unwrap().to_string_lossy(),
+                        e
+                    ))
+                })?;
+            fs::rename(&temp_upload_path, local_path.clone()).await?;
+        }
+        info!(
+            "Uploaded encrypted {} ({:?}) ({} bytes)",
+            remote_path,
+            time.elapsed()?,
+            size
+        );
+        Ok(fs::metadata(local_path).await?.len())
+    }
+
+    async fn download_file(
+        &self,
+        remote_path: String,
+        expected_file_size: Option<u64>,
+    ) -> Result<String, CubeError> {
+        let local_file = self.dir.as_path().join(&remote_path);
+        if local_file.exists() {
+            return Ok(local_file.into_os_string().into_string().unwrap());
+        }
+        let downloads_dir = local_file.parent().unwrap().join("downloads");
+        fs::create_dir_all(&downloads_dir).await?;
+
+        // The expected size is the plaintext one, so it's checked after decryption. Files uploaded
+        // before encryption was enabled don't have an encrypted size to check against.
+        let encrypted_path = self
+            .remote_fs
+            .download_file(remote_path.clone(), None)
+            .await?;
+
+        app_metrics::REMOTE_FS_OPERATION_CORE.add_with_tags(
+            1,
+            Some(&vec![
+                "operation:decrypt_file".to_string(),
+                "driver:encrypted".to_string(),
+            ]),
+        );
+        let time = SystemTime::now();
+        debug!("Decrypting {}", remote_path);
+        let keyring = self.keyring.clone();
+        let allow_unencrypted = self.allow_unencrypted;
+        let local_file_to_write = local_file.clone();
+        let decrypt_path = remote_path.clone();
+        cube_ext::spawn_blocking(move || -> Result<(), CubeError> {
+            let temp_file = NamedTempFile::new_in(downloads_dir)?;
+            let mut reader = BufReader::new(std::fs::File::open(encrypted_path)?);
+            let mut writer = BufWriter::new(temp_file.as_file());
+            let size =
+                decrypt(&keyring, &mut reader, &mut writer, allow_unencrypted).map_err(|e| {
+                    CubeError::corrupt_data(format!(
+                        "Can't decrypt {}: {}",
+                        decrypt_path, e.message
+                    ))
+                })?;
+            drop(writer);
+            if let Some(expected_file_size) = expected_file_size {
+                if size != expected_file_size {
+                    return Err(CubeError::corrupt_data(format!(
+                        "Expected file size for '{}' is {} but {} received",
+                        decrypt_path, expected_file_size, size
+                    )));
+                }
+            }
+            temp_file
+                .persist(&local_file_to_write)
+                .map_err(|e| CubeError::internal(e.to_string()))?;
+            Ok(())
+        })
+        .await??;
+        self.remove_encrypted_local_file(&remote_path).await?;
+        info!("Decrypted {} ({:?})", remote_path, time.elapsed()?);
+
+        Ok(local_file.into_os_string().into_string().unwrap())
+    }
+
+    async fn delete_file(&self, remote_path: String) -> Result<(), CubeError> {
+        self.remote_fs.delete_file(remote_path.clone()).await?;
+
+        let _guard = acquire_lock("delete file", self.delete_mut.lock()).await?;
+        let local = self.dir.as_path().join(remote_path);
+        if fs::metadata(local.clone()).await.is_ok() {
+            fs::remove_file(local.clone()).await?;
+            LocalDirRemoteFs::remove_empty_paths(self.dir.as_path().to_path_buf(), local.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, remote_prefix: String) -> Result<Vec<String>, CubeError> {
+        self.remote_fs.list(remote_prefix).await
+    }
+
+    /// Sizes of listed files are the sizes of stored, i.e. encrypted, objects. Use
+    /// [stored_file_size] to compare them with file sizes kept in the metastore.
+    async fn list_with_metadata(
+        &self,
+        remote_prefix: String,
+    ) -> Result<Vec<RemoteFile>, CubeError> {
+        self.remote_fs.list_with_metadata(remote_prefix).await
+    }
+
+    async fn local_path(&self) -> Result<String, CubeError> {
+        Ok(self.dir.to_str().unwrap().to_owned())
+    }
+
+    async fn local_file(&self, remote_path: String) -> Result<String, CubeError> {
+        let buf = self.dir.join(remote_path);
+        fs::create_dir_all(buf.parent().unwrap()).await?;
+        Ok(buf.to_str().unwrap().to_string())
+    }
+}
+
+#[async_trait]
+impl ExtendedRemoteFs for EncryptedRemoteFs {
+    async fn list_by_page(
+        &self,
+        remote_prefix: String,
+    ) -> Result<BoxStream<'static, Result<Vec<String>, CubeError>>, CubeError> {
+        self.remote_fs.list_by_page(remote_prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn keyring(active_key_id: &str) -> EncryptionKeyring {
+        EncryptionKeyring::parse(
+            &format!(
+                "k1:{}, k2:{}",
+                base64::encode([1u8; KEY_SIZE]),
+                base64::encode([2u8; KEY_SIZE])
+            ),
+            active_key_id.to_string(),
+        )
+        .unwrap()
+    }
+
+    fn encrypt_bytes(keyring: &EncryptionKeyring, data: &[u8]) -> Vec<u8> {
+        let mut encrypted = Vec::new();
+        let size = encrypt(keyring, &mut Cursor::new(data), &mut encrypted).unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(encrypted.len() as u64, encrypted_size(data.len() as u64));
+        encrypted
+    }
+
+    fn decrypt_bytes(
+        keyring: &EncryptionKeyring,
+        data: &[u8],
+        allow_unencrypted: bool,
+    ) -> Result<Vec<u8>, CubeError> {
+        let mut decrypted = Vec::new();
+        decrypt(
+            keyring,
+            &mut Cursor::new(data),
+            &mut decrypted,
+            allow_unencrypted,
+        )?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keyring = keyring("k1");
+        for size in [
+            0,
+            1,
+            SEGMENT_SIZE - 1,
+            SEGMENT_SIZE,
+            SEGMENT_SIZE + 1,
+            3 * SEGMENT_SIZE,
+        ] {
+            let data = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let encrypted = encrypt_bytes(&keyring, &data);
+            assert!(encrypted.starts_with(&MAGIC));
+            assert_eq!(decrypt_bytes(&keyring, &encrypted, false).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn key_rotation() {
+        let data = b"rotated".to_vec();
+        let encrypted = encrypt_bytes(&keyring("k1"), &data);
+        assert_eq!(
+            decrypt_bytes(&keyring("k2"), &encrypted, false).unwrap(),
+            data
+        );
+
+        let only_k2 = EncryptionKeyring::parse(
+            &format!("k2:{}", base64::encode([2u8; KEY_SIZE])),
+            "k2".to_string(),
+        )
+        .unwrap();
+        let err = decrypt_bytes(&only_k2, &encrypted, false).unwrap_err();
+        assert!(err.message.contains("'k1'"), "{}", err.message);
+    }
+
+    #[test]
+    fn tampered_and_truncated_files() {
+        let keyring = keyring("k1");
+        let data = (0..2 * SEGMENT_SIZE).map(|i| i as u8).collect::<Vec<_>>();
+        let encrypted = encrypt_bytes(&keyring, &data);
+
+        let mut tampered = encrypted.clone();
+        tampered[HEADER_SIZE + 10] ^= 1;
+        assert!(decrypt_bytes(&keyring, &tampered, false).is_err());
+
+        // Dropping the last segment leaves a file that is valid segment by segment.
+        let truncated = &encrypted[..HEADER_SIZE + SEGMENT_SIZE + TAG_SIZE];
+        assert!(decrypt_bytes(&keyring, truncated, false).is_err());
+        assert!(decrypt_bytes(&keyring, &encrypted[..HEADER_SIZE - 1], false).is_err());
+    }
+
+    #[test]
+    fn unencrypted_files() {
+        let keyring = keyring("k1");
+        let data = b"PAR1 plain parquet".to_vec();
+        assert!(decrypt_bytes(&keyring, &data, false).is_err());
+        assert_eq!(decrypt_bytes(&keyring, &data, true).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn metastore_and_listing_sizes() {
+        let root = env::temp_dir().join("encrypted-remote-fs-sizes");
+        let _ = std::fs::remove_dir_all(&root);
+        let backend = LocalDirRemoteFs::new(Some(root.join("remote")), root.join("backend"));
+        let fs = EncryptedRemoteFs::new(root.join("local"), backend, keyring("k1"), false);
+
+        let data = vec![7u8; 1000];
+        let temp_path = fs.temp_upload_path("1.parquet".to_string()).await.unwrap();
+        std::fs::write(&temp_path, &data).unwrap();
+        let size = fs
+            .upload_file(temp_path, "1.parquet".to_string())
+            .await
+            .unwrap();
+        assert_eq!(size, data.len() as u64);
+        fs.check_upload_file("1.parquet".to_string(), size)
+            .await
+            .unwrap();
+
+        let listed = fs
+            .list_with_metadata("1.parquet".to_string())
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(
+            listed[0].file_size,
+            stored_file_size(size, &Some("k1".to_string()))
+        );
+
+        let local = root.join("local").join("1.parquet");
+        std::fs::remove_file(&local).unwrap();
+        let err = fs
+            .download_file("1.parquet".to_string(), Some(size + 1))
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("Expected file size"), "{}", err);
+        assert!(!local.exists());
+        fs.download_file("1.parquet".to_string(), Some(size))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&local).unwrap(), data);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn keyring_config() {
+        let key = base64::encode([1u8; KEY_SIZE]);
+        assert!(EncryptionKeyring::parse(&format!("k1:{}", key), "k2".to_string()).is_err());
+        assert!(EncryptionKeyring::parse("k1:AAAA", "k1".to_string()).is_err());
+        assert!(EncryptionKeyring::parse(&key, "k1".to_string()).is_err());
+        assert!(
+            EncryptionKeyring::parse(&format!("k1:{},k1:{}", key, key), "k1".to_string()).is_err()
+        );
+        let keyring = EncryptionKeyring::parse(&format!("k1:{}", key), "k1".to_string()).unwrap();
+        assert!(!format!("{:?}", keyring).contains(&key));
+    }
+}
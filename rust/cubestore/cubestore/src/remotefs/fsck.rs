@@ -0,0 +1,463 @@
+use crate::cluster::Cluster;
+use crate::metastore::chunks::Chunk;
+use crate::metastore::partition::Partition;
+use crate::metastore::{IdRow, MetaStore};
+use crate::remotefs::encrypted::stored_file_size;
+use crate::remotefs::{RemoteFile, RemoteFs};
+use crate::CubeError;
+use datafusion::cube_ext;
+use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum FsckIssueKind {
+    MissingFile,
+    SizeMismatch,
+    CorruptFile,
+    RowCountMismatch,
+    OrphanedFile,
+    MissingReplayHandle,
+    ChunksOfInactivePartition,
+}
+
+impl FsckIssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FsckIssueKind::MissingFile => "missing_file",
+            FsckIssueKind::SizeMismatch => "size_mismatch",
+            FsckIssueKind::CorruptFile => "corrupt_file",
+            FsckIssueKind::RowCountMismatch => "row_count_mismatch",
+            FsckIssueKind::OrphanedFile => "orphaned_file",
+            FsckIssueKind::MissingReplayHandle => "missing_replay_handle",
+            FsckIssueKind::ChunksOfInactivePartition => "chunks_of_inactive_partition",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FsckIssue {
+    pub kind: FsckIssueKind,
+    /// `partition`, `chunk` or `file` for files unknown to the metastore.
+    pub object: &'static str,
+    pub id: Option<u64>,
+    pub file: Option<String>,
+    pub details: String,
+    /// Repair action applied to the issue, if any.
+    pub action: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FsckOptions {
+    /// Download files and check parquet footers and row counts.
+    pub verify: bool,
+    /// Deactivate broken chunks and schedule repartition of inactive partitions with chunks.
+    /// Chunks with a size mismatch are only deactivated when `verify` fails to read them.
+    pub repair: bool,
+}
+
+/// Cross-checks partitions and chunks of the metastore with files on the remote fs.
+/// Orphaned files are only reported, `RemoteFsCleanup` takes care of them.
+pub async fn fsck(
+    meta_store: Arc<dyn MetaStore>,
+    remote_fs: Arc<dyn RemoteFs>,
+    cluster: Arc<dyn Cluster>,
+    options: FsckOptions,
+) -> Result<Vec<FsckIssue>, CubeError> {
+    let (partitions, chunks) = meta_store
+        .get_all_partitions_and_chunks_out_of_queue()
+        .await?;
+    let replay_handles = meta_store
+        .all_replay_handles()
+        .await?
+        .into_iter()
+        .map(|h| h.get_id())
+        .collect::<HashSet<_>>();
+    let remote_files = remote_fs
+        .list_with_metadata("".to_string())
+        .await?
+        .into_iter()
+        .filter(|f| f.remote_path().ends_with(".parquet"))
+        .map(|f| (f.remote_path.clone(), f))
+        .collect::<HashMap<_, _>>();
+
+    let mut issues = Vec::new();
+
+    // Files of inactive partitions are removed by GC
+    for p in partitions.iter().filter(|p| p.get_row().is_active()) {
+        if let Some(file) = p.get_row().get_full_name(p.get_id()) {
+            let found = check_file(
+                remote_fs.as_ref(),
+                &remote_files,
+                "partition",
+                p.get_id(),
+                file,
+                p.get_row().file_size(),
+                p.get_row().encryption_key_id(),
+                p.get_row().main_table_row_count(),
+                options.verify,
+            )
+            .await?;
+            // Partition data can't be restored from the metastore, so such issues are report only
+            issues.extend(found);
+        }
+    }
+
+    let active_partitions = partitions
+        .iter()
+        .filter(|p| p.get_row().is_active())
+        .map(|p| p.get_id())
+        .collect::<HashSet<_>>();
+    let mut inactive_partitions_with_chunks = HashSet::new();
+
+    for c in chunks.iter() {
+        let row = c.get_row();
+        if !row.active() {
+            continue;
+        }
+
+        if let Some(replay_handle_id) = row.replay_handle_id() {
+            if !replay_handles.contains(replay_handle_id) {
+                issues.push(FsckIssue {
+                    kind: FsckIssueKind::MissingReplayHandle,
+                    object: "chunk",
+                    id: Some(c.get_id()),
+                    file: None,
+                    details: format!("Replay handle {} is not found", replay_handle_id),
+                    action: None,
+                });
+            }
+        }
+
+        if !active_partitions.contains(&row.get_partition_id()) {
+            inactive_partitions_with_chunks.insert(row.get_partition_id());
+        }
+
+        if row.in_memory() || !row.uploaded() {
+            continue;
+        }
+
+        let mut found = check_file(
+            remote_fs.as_ref(),
+            &remote_files,
+            "chunk",
+            c.get_id(),
+            row.get_full_name(c.get_id()),
+            row.file_size(),
+            row.encryption_key_id(),
+            row.get_row_count(),
+            options.verify,
+        )
+        .await?;
+        let broken = found.iter().any(|i| i.kind != FsckIssueKind::SizeMismatch);
+        if options.repair && broken {
+            repair_chunk(meta_store.as_ref(), c, &mut found).await?;
+        }
+        issues.extend(found);
+    }
+
+    for p in partitions.iter() {
+        if !inactive_partitions_with_chunks.contains(&p.get_id()) {
+            continue;
+        }
+
+        let mut issue = FsckIssue {
+            kind: FsckIssueKind::ChunksOfInactivePartition,
+            object: "partition",
+            id: Some(p.get_id()),
+            file: None,
+            details: "Inactive partition has active chunks".to_string(),
+            action: None,
+        };
+        if options.repair {
+            repair_partition(cluster.as_ref(), p, &mut issue).await?;
+        }
+        issues.push(issue);
+    }
+
+    let known_files = meta_store
+        .get_all_filenames()
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let mut orphaned = remote_files
+        .values()
+        .filter(|f| !known_files.contains(f.remote_path()))
+        .collect::<Vec<_>>();
+    orphaned.sort_by(|a, b| a.remote_path.cmp(&b.remote_path));
+    for f in orphaned {
+        issues.push(FsckIssue {
+            kind: FsckIssueKind::OrphanedFile,
+            object: "file",
+            id: None,
+            file: Some(f.remote_path.clone()),
+            details: format!(
+                "File is not referenced by the metastore, size: {}, updated: {}",
+                f.file_size, f.updated
+            ),
+            action: None,
+        });
+    }
+
+    Ok(issues)
+}
+
+async fn check_file(
+    remote_fs: &dyn RemoteFs,
+    remote_files: &HashMap<String, RemoteFile>,
+    object: &'static str,
+    id: u64,
+    file: String,
+    expected_size: Option<u64>,
+    encryption_key_id: &Option<String>,
+    expected_rows: u64,
+    verify: bool,
+) -> Result<Vec<FsckIssue>, CubeError> {
+    let issue = |kind: FsckIssueKind, details: String| FsckIssue {
+        kind,
+        object,
+        id: Some(id),
+        file: Some(file.clone()),
+        details,
+        action: None,
+    };
+
+    let remote_file = match remote_files.get(&file) {
+        Some(f) => f,
+        None => {
+            return Ok(vec![issue(
+                FsckIssueKind::MissingFile,
+                "File is not found on the remote fs".to_string(),
+            )])
+        }
+    };
+
+    let mut issues = Vec::new();
+    // The metastore keeps plaintext sizes, listings report sizes of stored objects
+    let mut download_size = expected_size;
+    if let Some(expected_size) = expected_size {
+        let expected_stored_size = stored_file_size(expected_size, encryption_key_id);
+        if remote_file.file_size != expected_stored_size {
+            issues.push(issue(
+                FsckIssueKind::SizeMismatch,
+                format!(
+                    "Expected size {}, actual: {}",
+                    expected_stored_size, remote_file.file_size
+                ),
+            ));
+            // The download would reject the file, read it as is to tell if it's broken
+            download_size = None;
+        }
+    }
+
+    if verify {
+        match read_parquet_row_count(remote_fs, &file, download_size).await? {
+            Ok(rows) if rows != expected_rows => issues.push(issue(
+                FsckIssueKind::RowCountMismatch,
+                format!("Expected {} rows, actual: {}", expected_rows, rows),
+            )),
+            Ok(_) => {}
+            Err(e) => issues.push(issue(FsckIssueKind::CorruptFile, e)),
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Download errors are returned as is, so a flaky remote fs never marks files as corrupt.
+async fn read_parquet_row_count(
+    remote_fs: &dyn RemoteFs,
+    file: &str,
+    expected_size: Option<u64>,
+) -> Result<Result<u64, String>, CubeError> {
+    let local_path = remote_fs
+        .download_file(file.to_string(), expected_size)
+        .await?;
+    let local_file = File::open(&local_path)?;
+    let res = cube_ext::spawn_blocking(move || match SerializedFileReader::new(local_file) {
+        Ok(reader) => Ok(reader.metadata().file_metadata().num_rows() as u64),
+        Err(e) => Err(format!("Unable to read parquet footer: {}", e)),
+    })
+    .await?;
+    Ok(res)
+}
+
+async fn repair_chunk(
+    meta_store: &dyn MetaStore,
+    chunk: &IdRow<Chunk>,
+    issues: &mut Vec<FsckIssue>,
+) -> Result<(), CubeError> {
+    meta_store.deactivate_chunk(chunk.get_id()).await?;
+    for issue in issues.iter_mut() {
+        issue.action = Some("chunk deactivated".to_string());
+    }
+    Ok(())
+}
+
+async fn repair_partition(
+    cluster: &dyn Cluster,
+    partition: &IdRow<Partition>,
+    issue: &mut FsckIssue,
+) -> Result<(), CubeError> {
+    cluster.schedule_repartition(partition).await?;
+    issue.action = Some("repartition scheduled".to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn fsck_chunk_issues() {
+        Config::test("fsck_chunk_issues")
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                service
+                    .exec_query("CREATE SCHEMA foo")
+                    .await?
+                    .collect()
+                    .await?;
+                service
+                    .exec_query("CREATE TABLE foo.ints (value int)")
+                    .await?
+                    .collect()
+                    .await?;
+                service
+                    .exec_query("INSERT INTO foo.ints (value) VALUES (1), (2), (3)")
+                    .await?
+                    .collect()
+                    .await?;
+
+                let meta_store = services.meta_store.clone();
+                let remote_fs = services.injector.get_service_typed::<dyn RemoteFs>().await;
+                let cluster = services.cluster.clone();
+
+                let issues = fsck(
+                    meta_store.clone(),
+                    remote_fs.clone(),
+                    cluster.clone(),
+                    FsckOptions {
+                        verify: true,
+                        repair: false,
+                    },
+                )
+                .await?;
+                assert_eq!(issues, vec![]);
+
+                let chunk = meta_store.get_chunk(1).await?;
+                let chunk_file = chunk.get_row().get_full_name(chunk.get_id());
+                for remote_path in [chunk_file.clone(), "999-orphan.parquet".to_string()] {
+                    let temp_path = remote_fs.temp_upload_path(remote_path.clone()).await?;
+                    File::create(&temp_path)?.write_all(b"Malformed parquet")?;
+                    remote_fs.upload_file(temp_path, remote_path).await?;
+                }
+
+                // Without verification a size mismatch alone doesn't deactivate the chunk
+                let issues = fsck(
+                    meta_store.clone(),
+                    remote_fs.clone(),
+                    cluster.clone(),
+                    FsckOptions {
+                        verify: false,
+                        repair: true,
+                    },
+                )
+                .await?;
+                assert_eq!(issues[0].kind, FsckIssueKind::SizeMismatch);
+                assert_eq!(issues[0].action, None);
+                assert!(meta_store.get_chunk(1).await?.get_row().active());
+
+                let issues = fsck(
+                    meta_store.clone(),
+                    remote_fs.clone(),
+                    cluster.clone(),
+                    FsckOptions {
+                        verify: true,
+                        repair: true,
+                    },
+                )
+                .await?;
+                let kinds = issues
+                    .iter()
+                    .map(|i| (i.kind, i.file.clone().unwrap(), i.action.clone()))
+                    .collect::<Vec<_>>();
+                let deactivated = Some("chunk deactivated".to_string());
+                assert_eq!(
+                    kinds,
+                    vec![
+                        (
+                            FsckIssueKind::SizeMismatch,
+                            chunk_file.clone(),
+                            deactivated.clone()
+                        ),
+                        (FsckIssueKind::CorruptFile, chunk_file, deactivated),
+                        (
+                            FsckIssueKind::OrphanedFile,
+                            "999-orphan.parquet".to_string(),
+                            None
+                        ),
+                    ]
+                );
+                assert!(!meta_store.get_chunk(1).await?.get_row().active());
+
+                Ok(())
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn fsck_encrypted_files() {
+        std::env::set_var(
+            "CUBESTORE_ENCRYPTION_KEYS",
+            format!("fsck:{}", base64::encode([7u8; 32])),
+        );
+        Config::test("fsck_encrypted_files")
+            .update_config(|mut c| {
+                c.encryption_key_id = Some("fsck".to_string());
+                c
+            })
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                service
+                    .exec_query("CREATE SCHEMA foo")
+                    .await?
+                    .collect()
+                    .await?;
+                service
+                    .exec_query("CREATE TABLE foo.ints (value int)")
+                    .await?
+                    .collect()
+                    .await?;
+                service
+                    .exec_query("INSERT INTO foo.ints (value) VALUES (1), (2), (3)")
+                    .await?
+                    .collect()
+                    .await?;
+
+                let meta_store = services.meta_store.clone();
+                let chunk = meta_store.get_chunk(1).await?;
+                assert!(chunk.get_row().encryption_key_id().is_some());
+
+                // Listings report sizes of encrypted files, which must not be taken for a mismatch
+                let issues = fsck(
+                    meta_store.clone(),
+                    services.injector.get_service_typed::<dyn RemoteFs>().await,
+                    services.cluster.clone(),
+                    FsckOptions {
+                        verify: true,
+                        repair: true,
+                    },
+                )
+                .await?;
+                assert_eq!(issues, vec![]);
+                assert!(meta_store.get_chunk(1).await?.get_row().active());
+
+                Ok(())
+            })
+            .await;
+    }
+}
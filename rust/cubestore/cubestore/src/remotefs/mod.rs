@@ -1,4 +1,7 @@
+pub mod azure;
 pub mod cleanup;
+pub mod encrypted;
+pub mod fsck;
 pub mod gcs;
 pub mod minio;
 pub mod queue;
@@ -486,11 +489,13 @@ impl LocalDirRemoteFs {
 
 #[cfg(test)]
 mod tests {
+    use super::azure::AzureBlobRemoteFs;
     use super::gcs::GCSRemoteFs;
     use super::minio::MINIORemoteFs;
     use super::s3::S3RemoteFs;
     use super::*;
     use crate::config::init_test_logger;
+    use futures::TryStreamExt;
     use std::io::prelude::*;
     use std::path::{Path, PathBuf};
     use std::sync::Arc;
@@ -771,4 +776,67 @@ mod tests {
         clear_test_dir("gcp_remote_fs");
         Ok(())
     }
+
+    /// Runs against the Azurite emulator with:
+    ///   CUBESTORE_AZURE_ACCOUNT_NAME=devstoreaccount1
+    ///   CUBESTORE_AZURE_ACCOUNT_KEY=Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==
+    ///   CUBESTORE_AZURE_ENDPOINT=http://127.0.0.1:10000/devstoreaccount1
+    #[tokio::test]
+    async fn azure_remote_fs() -> Result<(), CubeError> {
+        if env::var("CUBESTORE_AZURE_ACCOUNT_NAME").is_err() {
+            return Ok(());
+        }
+
+        init_test_logger().await;
+        let container_name = "cube-store-ci-test".to_string();
+
+        clear_test_dir("azure_remote_fs");
+        let local_path = get_test_local_dir("azure_remote_fs");
+
+        let remote_fs = AzureBlobRemoteFs::new(local_path.clone(), container_name.clone(), None)?;
+        remote_fs.create_container_if_not_exists().await?;
+
+        let name_maker = NameMaker::new(Uuid::new_v4().to_string());
+        test_remote_filesystem(
+            remote_fs.clone(),
+            local_path.as_ref(),
+            name_maker.clone(),
+            true,
+        )
+        .await;
+
+        let files = vec![
+            name_maker.name("paged-1.txt"),
+            name_maker.name("paged-2.txt"),
+        ];
+        for filename in files.iter() {
+            let remote_fs: Arc<dyn RemoteFs> = remote_fs.clone();
+            create_and_upload_file(&remote_fs, filename).await?;
+        }
+        let mut paged_list = remote_fs
+            .list_by_page(name_maker.name("paged-"))
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+        paged_list.sort();
+        assert_eq!(paged_list, files);
+        for filename in files.iter() {
+            remote_fs.delete_file(filename.clone()).await?;
+        }
+
+        clear_test_dir("azure_remote_fs");
+
+        let remote_fs = AzureBlobRemoteFs::new(
+            local_path.clone(),
+            container_name.clone(),
+            Some("remotefs_test_subpathdir".to_string()),
+        )
+        .unwrap();
+
+        test_remote_filesystem(remote_fs, local_path.as_ref(), name_maker.clone(), true).await;
+
+        clear_test_dir("azure_remote_fs");
+        Ok(())
+    }
 }
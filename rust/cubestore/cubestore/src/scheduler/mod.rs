@@ -404,6 +404,26 @@ impl SchedulerImpl {
             error!("Error scheduling repartition: {}", e);
         }
 
+        if let Err(e) = warn_long_fut(
+            "Scheduling table retention",
+            Duration::from_millis(5000),
+            self.schedule_all_expired_table_data(),
+        )
+        .await
+        {
+            error!("Error scheduling table retention: {}", e);
+        }
+
+        if let Err(e) = warn_long_fut(
+            "Scheduling pending deletes",
+            Duration::from_millis(5000),
+            self.schedule_all_pending_deletes(),
+        )
+        .await
+        {
+            error!("Error scheduling pending deletes: {}", e);
+        }
+
         if let Err(e) = warn_long_fut(
             "Delete orphaned partitions",
             Duration::from_millis(5000),
@@ -449,6 +469,45 @@ impl SchedulerImpl {
         Ok(())
     }
 
+    async fn schedule_all_expired_table_data(&self) -> Result<(), CubeError> {
+        let now = Utc::now();
+        let tables = self.meta_store.get_tables_with_path(false).await?;
+        for table in tables.iter() {
+            if let Some(retention) = table.table.get_row().retention() {
+                let table_id = table.table.get_id();
+                let (partitions, chunks) = self
+                    .meta_store
+                    .get_expired_table_data(table_id, retention.expiration_cutoff(now))
+                    .await?;
+                if !partitions.is_empty() || !chunks.is_empty() {
+                    self.schedule_table_retention(table_id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn schedule_all_pending_deletes(&self) -> Result<(), CubeError> {
+        let tables = self.meta_store.get_tables_with_path(false).await?;
+        for table in tables.iter() {
+            if table.table.get_row().delete_markers().is_empty() {
+                continue;
+            }
+            let table_id = table.table.get_id();
+            self.meta_store
+                .remove_applied_delete_markers(table_id)
+                .await?;
+            let partitions = self
+                .meta_store
+                .get_partitions_with_pending_deletes(table_id)
+                .await?;
+            for p in partitions.iter() {
+                self.schedule_partition_to_compact(p).await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn delete_created_but_not_written_partitions(&self) -> Result<(), CubeError> {
         let all_inactive_partitions = self.meta_store.all_just_created_partitions().await?;
 
@@ -1355,6 +1414,23 @@ impl SchedulerImpl {
         Ok(())
     }
 
+    async fn schedule_table_retention(&self, table_id: u64) -> Result<(), CubeError> {
+        let node = pick_worker_by_ids(self.config.as_ref(), [table_id]).to_string();
+        let job = self
+            .meta_store
+            .add_job(Job::new(
+                RowKey::Table(TableId::Tables, table_id),
+                JobType::TableRetention,
+                node.clone(),
+            ))
+            .await?;
+        if job.is_some() {
+            // TODO queue failover
+            self.cluster.notify_job_runner(node).await?;
+        }
+        Ok(())
+    }
+
     async fn schedule_multi_partition_split(
         &self,
         multi_partition_id: u64,
@@ -1628,6 +1704,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await
             .unwrap();
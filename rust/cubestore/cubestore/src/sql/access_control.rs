@@ -0,0 +1,440 @@
+use crate::app_metrics;
+use crate::metastore::table::Table;
+use crate::sql::normalize_for_schema_table_or_index_name;
+use crate::sql::parser::{AlterTableCommand, Statement as CubeStoreStatement};
+use crate::util::metrics;
+use crate::CubeError;
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlparser::ast::{
+    visit_relations, CreateIndex, CreateTable, Ident, Insert, ObjectName, ObjectType, Statement,
+    TableObject,
+};
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+/// Schema with system tables, its tables are readable only by admins.
+const SYSTEM_SCHEMA: &str = "system";
+/// Metadata of tables is readable without a grant for the schema.
+const INFORMATION_SCHEMA: &str = "information_schema";
+
+/// Classes of statements a user can be allowed to run.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// `SELECT`, `EXPLAIN` and `SHOW`.
+    Read,
+    /// DDL and ingestion: `CREATE`, `ALTER`, `DROP`, `INSERT`, `DELETE` of tables.
+    #[serde(alias = "ingest")]
+    Write,
+    /// `CACHE` and `QUEUE` commands.
+    #[serde(alias = "queue")]
+    Cache,
+    /// `SYS` commands, sources, system tables. Admins are allowed to run everything.
+    Admin,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Cache => "cache",
+            Permission::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = CubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(Permission::Read),
+            "write" | "ingest" => Ok(Permission::Write),
+            "cache" | "queue" => Ok(Permission::Cache),
+            "admin" => Ok(Permission::Admin),
+            _ => Err(CubeError::user(format!(
+                "Unknown permission '{}', expected: read, write, cache, admin",
+                s
+            ))),
+        }
+    }
+}
+
+/// Statements the user is allowed to run. Users without grants are allowed to run everything.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UserGrants {
+    /// Schemas the user has access to, `None` grants access to all schemas. Names are normalized
+    /// the same way as schema names in statements.
+    #[serde(default, deserialize_with = "deserialize_schemas")]
+    pub schemas: Option<Vec<String>>,
+    pub permissions: Vec<Permission>,
+}
+
+fn deserialize_schemas<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    let schemas = Option::<Vec<String>>::deserialize(deserializer)?;
+    Ok(schemas.map(|schemas| {
+        schemas
+            .iter()
+            .map(|s| normalize_for_schema_table_or_index_name(&Ident::new(s.as_str())))
+            .collect()
+    }))
+}
+
+impl UserGrants {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions.contains(&Permission::Admin) || self.permissions.contains(&permission)
+    }
+
+    pub fn has_schema(&self, schema: &str) -> bool {
+        match &self.schemas {
+            None => true,
+            Some(schemas) => schemas.iter().any(|s| s == schema),
+        }
+    }
+
+    /// Checks the statement against the grants, denied attempts are reported to metrics.
+    pub fn check(&self, user: Option<&str>, access: &StatementAccess) -> Result<(), CubeError> {
+        let denied = if !self.has_permission(access.permission) {
+            Some(format!("'{}' permission is required", access.permission))
+        } else {
+            access
+                .schemas
+                .iter()
+                .find(|s| !self.has_schema(s))
+                .map(|s| format!("schema '{}' isn't granted", s))
+        };
+        match denied {
+            None => Ok(()),
+            Some(reason) => {
+                app_metrics::SQL_ACCESS_DENIED.add_with_tags(
+                    1,
+                    Some(&vec![metrics::format_tag(
+                        "permission",
+                        access.permission.as_str(),
+                    )]),
+                );
+                Err(CubeError::user(format!(
+                    "Access denied for user '{}': {}",
+                    user.unwrap_or_default(),
+                    reason
+                )))
+            }
+        }
+    }
+}
+
+/// Permission and schemas required to run a statement.
+#[derive(Debug, PartialEq)]
+pub struct StatementAccess {
+    pub permission: Permission,
+    pub schemas: Vec<String>,
+}
+
+impl StatementAccess {
+    fn new(permission: Permission) -> Self {
+        Self {
+            permission,
+            schemas: Vec::new(),
+        }
+    }
+
+    fn with_name(mut self, name: &ObjectName) -> Self {
+        self.add_name(name);
+        self
+    }
+
+    fn add_name(&mut self, name: &ObjectName) {
+        // Single part names are inline tables and common table expressions
+        if name.0.len() >= 2 {
+            self.add_schema(&name.0[name.0.len() - 2]);
+        }
+    }
+
+    fn add_schema(&mut self, ident: &Ident) {
+        let schema = normalize_for_schema_table_or_index_name(ident);
+        if schema.eq_ignore_ascii_case(INFORMATION_SCHEMA) {
+            return;
+        }
+        if schema.eq_ignore_ascii_case(SYSTEM_SCHEMA) {
+            self.permission = Permission::Admin;
+        }
+        if !self.schemas.contains(&schema) {
+            self.schemas.push(schema);
+        }
+    }
+
+    /// Tables referenced by the statement, including the ones in subqueries.
+    fn with_relations(mut self, statement: &Statement) -> Self {
+        let _ = visit_relations(statement, |name| {
+            self.add_name(name);
+            ControlFlow::<()>::Continue(())
+        });
+        self
+    }
+}
+
+pub fn statement_access(statement: &CubeStoreStatement) -> StatementAccess {
+    match statement {
+        CubeStoreStatement::Statement(statement) => sql_statement_access(statement),
+        CubeStoreStatement::CreateTable {
+            create_table,
+            partitioned_index,
+            locations,
+            ..
+        } => {
+            let mut access = sql_statement_access(create_table);
+            if let Some(index) = partitioned_index {
+                access.add_name(&index.name);
+            }
+            // Imports read server files and fetch http urls
+            let is_uploaded_or_stream =
+                |l: &String| l.starts_with("temp://") || Table::is_stream_location(l);
+            if !locations.iter().flatten().all(is_uploaded_or_stream) {
+                access.permission = Permission::Admin;
+            }
+            access
+        }
+        CubeStoreStatement::CreateSchema { schema_name, .. } => {
+            let mut access = StatementAccess::new(Permission::Write);
+            if let Some(schema) = schema_name.0.first() {
+                access.add_schema(schema);
+            }
+            access
+        }
+        CubeStoreStatement::AlterTable { name, command } => {
+            let mut access = StatementAccess::new(Permission::Write).with_name(name);
+            if let AlterTableCommand::RenameTo(new_name) = command {
+                access.add_name(new_name);
+            }
+            access
+        }
+        CubeStoreStatement::SwapTables { left, right } => StatementAccess::new(Permission::Write)
+            .with_name(left)
+            .with_name(right),
+        CubeStoreStatement::Cache(_) | CubeStoreStatement::Queue(_) => {
+            StatementAccess::new(Permission::Cache)
+        }
+        CubeStoreStatement::ExplainAnalyzeDetailed(query) => {
+            StatementAccess::new(Permission::Read).with_relations(&Statement::Query(query.clone()))
+        }
+        // Dump writes query inputs to the remote fs
+        CubeStoreStatement::Dump(_)
+        | CubeStoreStatement::CreateSource { .. }
+        | CubeStoreStatement::System(_) => StatementAccess::new(Permission::Admin),
+    }
+}
+
+fn sql_statement_access(statement: &Statement) -> StatementAccess {
+    match statement {
+        Statement::Query(_)
+        | Statement::Explain { .. }
+        | Statement::ShowVariable { .. }
+        | Statement::SetVariable { .. } => {
+            StatementAccess::new(Permission::Read).with_relations(statement)
+        }
+        Statement::Drop {
+            object_type: ObjectType::Schema,
+            names,
+            ..
+        } => {
+            let mut access = StatementAccess::new(Permission::Write);
+            for name in names {
+                if let Some(schema) = name.0.last() {
+                    access.add_schema(schema);
+                }
+            }
+            access
+        }
+        Statement::Drop { names, .. } => {
+            let mut access = StatementAccess::new(Permission::Write);
+            for name in names {
+                access.add_name(name);
+            }
+            access
+        }
+        Statement::CreateTable(CreateTable { name, .. })
+        | Statement::CreateIndex(CreateIndex {
+            table_name: name, ..
+        })
+        | Statement::CreatePartitionedIndex { name, .. }
+        | Statement::Insert(Insert {
+            table: TableObject::TableName(name),
+            ..
+        }) => StatementAccess::new(Permission::Write)
+            .with_name(name)
+            .with_relations(statement),
+        Statement::Insert(_) | Statement::Delete(_) => {
+            StatementAccess::new(Permission::Write).with_relations(statement)
+        }
+        _ => StatementAccess::new(Permission::Admin).with_relations(statement),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::parser::CubeStoreParser;
+
+    fn access(query: &str) -> StatementAccess {
+        let statement = CubeStoreParser::new(query, None)
+            .unwrap()
+            .parse_statement()
+            .unwrap();
+        statement_access(&statement)
+    }
+
+    fn expected(permission: Permission, schemas: &[&str]) -> StatementAccess {
+        StatementAccess {
+            permission,
+            schemas: schemas.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn statements_access() {
+        assert_eq!(
+            access(
+                "WITH t AS (SELECT * FROM s1.a) SELECT * FROM t JOIN s2.b ON t.id = b.id \
+                 WHERE t.id IN (SELECT id FROM s1.c)"
+            ),
+            expected(Permission::Read, &["s1", "s2"])
+        );
+        assert_eq!(
+            access("SELECT * FROM information_schema.tables"),
+            expected(Permission::Read, &[])
+        );
+        assert_eq!(
+            access("SELECT * FROM system.tables"),
+            expected(Permission::Admin, &["system"])
+        );
+        assert_eq!(
+            access("EXPLAIN SELECT * FROM s1.a"),
+            expected(Permission::Read, &["s1"])
+        );
+        assert_eq!(
+            access("CREATE TABLE s1.a (id int) LOCATION 'temp://a.csv'"),
+            expected(Permission::Write, &["s1"])
+        );
+        assert_eq!(
+            access("CREATE TABLE s1.a (id int) LOCATION 'stream://kafka/events'"),
+            expected(Permission::Write, &["s1"])
+        );
+        for location in ["/etc/passwd", "http://169.254.169.254/latest/meta-data"] {
+            assert_eq!(
+                access(&format!(
+                    "CREATE TABLE s1.a (id int) LOCATION 'temp://a.csv', '{}'",
+                    location
+                )),
+                expected(Permission::Admin, &["s1"])
+            );
+        }
+        assert_eq!(
+            access("CREATE SCHEMA IF NOT EXISTS s1"),
+            expected(Permission::Write, &["s1"])
+        );
+        assert_eq!(
+            access("INSERT INTO s1.a (id) VALUES (1)"),
+            expected(Permission::Write, &["s1"])
+        );
+        assert_eq!(
+            access("DROP TABLE s2.a"),
+            expected(Permission::Write, &["s2"])
+        );
+        assert_eq!(
+            access("DROP SCHEMA s2"),
+            expected(Permission::Write, &["s2"])
+        );
+        assert_eq!(
+            access("ALTER TABLE s1.a RENAME TO s2.b"),
+            expected(Permission::Write, &["s1", "s2"])
+        );
+        assert_eq!(
+            access("QUEUE ADD PRIORITY 1 \"k\" \"v\""),
+            expected(Permission::Cache, &[])
+        );
+        assert_eq!(access("CACHE GET 'k'"), expected(Permission::Cache, &[]));
+        assert_eq!(access("SYS DROP CACHE"), expected(Permission::Admin, &[]));
+    }
+
+    #[test]
+    fn grants_check() {
+        let grants = UserGrants {
+            schemas: Some(vec!["s1".to_string()]),
+            permissions: vec![Permission::Read, Permission::Cache],
+        };
+        assert!(grants
+            .check(Some("u"), &access("SELECT * FROM s1.a"))
+            .is_ok());
+        assert!(grants.check(Some("u"), &access("CACHE GET 'k'")).is_ok());
+        assert_eq!(
+            grants
+                .check(
+                    Some("u"),
+                    &access("SELECT * FROM s1.a JOIN s2.b ON a.id = b.id")
+                )
+                .unwrap_err()
+                .to_string(),
+            "Access denied for user 'u': schema 's2' isn't granted"
+        );
+        assert_eq!(
+            grants
+                .check(Some("u"), &access("DROP TABLE s1.a"))
+                .unwrap_err()
+                .to_string(),
+            "Access denied for user 'u': 'write' permission is required"
+        );
+
+        let admin = UserGrants {
+            schemas: None,
+            permissions: vec![Permission::Admin],
+        };
+        assert!(admin.check(None, &access("SYS DROP CACHE")).is_ok());
+        assert!(admin.check(None, &access("DROP TABLE s2.a")).is_ok());
+
+        let grants: UserGrants =
+            serde_json::from_str(r#"{"permissions": ["read", "ingest", "queue"]}"#).unwrap();
+        assert_eq!(
+            grants,
+            UserGrants {
+                schemas: None,
+                permissions: vec![Permission::Read, Permission::Write, Permission::Cache],
+            }
+        );
+    }
+
+    #[test]
+    fn grants_mixed_case_schema() {
+        let grants: UserGrants =
+            serde_json::from_str(r#"{"schemas": ["MySchema"], "permissions": ["read"]}"#).unwrap();
+        assert_eq!(
+            grants.schemas,
+            Some(vec![normalize_for_schema_table_or_index_name(&Ident::new(
+                "MySchema"
+            ))])
+        );
+        assert!(grants
+            .check(Some("u"), &access("SELECT * FROM MySchema.a"))
+            .is_ok());
+        assert!(grants
+            .check(Some("u"), &access("SELECT * FROM \"MySchema\".a"))
+            .is_ok());
+        // Schema names are case sensitive, same as in the metastore.
+        assert_eq!(
+            grants
+                .check(Some("u"), &access("SELECT * FROM myschema.a"))
+                .unwrap_err()
+                .to_string(),
+            "Access denied for user 'u': schema 'myschema' isn't granted"
+        );
+    }
+}
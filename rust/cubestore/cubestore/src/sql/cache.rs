@@ -20,6 +20,8 @@ pub struct SqlResultCacheKey {
     inline_tables: InlineTables,
     partition_ids: Vec<u64>,
     chunk_ids: Vec<u64>,
+    /// `DELETE` changes results without changing partitions and chunks of the table.
+    delete_marker_ids: Vec<(u64, u64)>,
 }
 
 impl SqlResultCacheKey {
@@ -30,7 +32,10 @@ impl SqlResultCacheKey {
     pub fn from_plan(query: &str, inline_tables: &InlineTables, plan: &SerializedPlan) -> Self {
         let mut partition_ids = HashSet::new();
         let mut chunk_ids = HashSet::new();
+        let mut delete_marker_ids = HashSet::new();
         for index in plan.index_snapshots().iter() {
+            let table = &index.table_path.table;
+            delete_marker_ids.insert((table.get_id(), table.get_row().last_delete_marker_id()));
             for p in index.partitions.iter() {
                 partition_ids.insert(p.partition.get_id());
                 for c in p.chunks.iter() {
@@ -42,11 +47,14 @@ impl SqlResultCacheKey {
         partition_ids.sort();
         let mut chunk_ids = chunk_ids.into_iter().collect::<Vec<_>>();
         chunk_ids.sort();
+        let mut delete_marker_ids = delete_marker_ids.into_iter().collect::<Vec<_>>();
+        delete_marker_ids.sort();
         Self {
             query: query.to_string(),
             inline_tables: (*inline_tables).clone(),
             partition_ids,
             chunk_ids,
+            delete_marker_ids,
         }
     }
 }
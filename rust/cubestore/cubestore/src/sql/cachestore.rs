@@ -1,5 +1,6 @@
 use crate::cachestore::{
-    CacheItem, CacheStore, EvictionResult, QueueAddAndRetrievePayload, QueueAddPayload, QueueItem,
+    CacheItem, CacheItemValueType, CacheStore, CacheStoreNotifier, EvictionResult,
+    QueueAddAndRetrievePayload, QueueAddPayload, QueueItem,
 };
 use crate::metastore::{Column, ColumnType};
 
@@ -15,8 +16,10 @@ use crate::table::{Row, TableValue};
 use crate::util::metrics;
 use crate::{app_metrics, CubeError};
 use async_trait::async_trait;
+use chrono::Utc;
 use datafusion::sql::parser::Statement as DFStatement;
 use deepsize::DeepSizeOf;
+use futures::StreamExt;
 use sqlparser::ast::Statement;
 use std::path::Path;
 use std::sync::Arc;
@@ -24,6 +27,7 @@ use std::time::{Duration, SystemTime};
 
 pub struct CacheStoreSqlService {
     cachestore: Arc<dyn CacheStore>,
+    cache_notifier: Arc<CacheStoreNotifier>,
     query_planner: Arc<dyn QueryPlanner>,
     process_rate_limiter: Arc<dyn ProcessRateLimiter>,
 }
@@ -33,16 +37,22 @@ crate::di_service!(CacheStoreSqlService, [SqlService]);
 impl CacheStoreSqlService {
     pub fn new(
         cachestore: Arc<dyn CacheStore>,
+        cache_notifier: Arc<CacheStoreNotifier>,
         query_planner: Arc<dyn QueryPlanner>,
         process_rate_limiter: Arc<dyn ProcessRateLimiter>,
     ) -> Self {
         Self {
             cachestore,
+            cache_notifier,
             query_planner,
             process_rate_limiter,
         }
     }
 
+    pub fn cachestore(&self) -> Arc<dyn CacheStore> {
+        self.cachestore.clone()
+    }
+
     pub async fn exec_system_command_with_context(
         &self,
         _context: SqlQueryContext,
@@ -193,17 +203,24 @@ impl CacheStoreSqlService {
 
     pub async fn exec_cache_command_with_context(
         &self,
-        _context: SqlQueryContext,
+        context: SqlQueryContext,
         command: CacheCommand,
     ) -> Result<Arc<DataFrame>, CubeError> {
         let command_tag = command.as_tag_command();
         app_metrics::CACHE_QUERIES
             .add_with_tags(1, Some(&vec![metrics::format_tag("command", command_tag)]));
 
+        let trace_index = TraceIndex {
+            // Important, it is used to aggregate all stats for cache by id
+            table_id: Some(1),
+            trace_obj: None,
+            // Budgeted per authenticated user, anonymous connections share one budget.
+            tenants: context.user.iter().cloned().collect(),
+        };
         let timeout = Some(Duration::from_secs(90));
         let wait_ms = self
             .process_rate_limiter
-            .wait_for_allow(TaskType::Cache, timeout)
+            .wait_for_allow(TaskType::Cache, &trace_index, timeout)
             .await?;
 
         let execution_time = SystemTime::now();
@@ -214,12 +231,15 @@ impl CacheStoreSqlService {
                 value,
                 ttl,
                 nx,
+                if_version,
             } => {
                 let value_size = key.value.deep_size_of() + value.deep_size_of();
-                let success = self
-                    .cachestore
-                    .cache_set(CacheItem::new(key.value, ttl, value), nx)
-                    .await?;
+                let item = CacheItem::new(key.value, ttl, value);
+                let success = if let Some(version) = if_version {
+                    self.cachestore.cache_set_if_version(item, version).await?
+                } else {
+                    self.cachestore.cache_set(item, nx).await?
+                };
 
                 (
                     Arc::new(DataFrame::new(
@@ -230,19 +250,37 @@ impl CacheStoreSqlService {
                     true,
                 )
             }
-            CacheCommand::Get { key } => {
+            CacheCommand::Get { key, with_version } => {
                 let result = self.cachestore.cache_get(key.value).await?;
-                let value = if let Some(result) = result {
-                    TableValue::String(result.into_row().value)
+                let (value, version) = if let Some(result) = result {
+                    let row = result.into_row();
+                    row.ensure_value_type(CacheItemValueType::String)?;
+
+                    (
+                        TableValue::String(row.value),
+                        TableValue::Int(row.version as i64),
+                    )
                 } else {
-                    TableValue::Null
+                    (TableValue::Null, TableValue::Null)
                 };
 
-                (
-                    Arc::new(DataFrame::new(
+                let (columns, values) = if with_version {
+                    (
+                        vec![
+                            Column::new("value".to_string(), ColumnType::String, 0),
+                            Column::new("version".to_string(), ColumnType::Int, 1),
+                        ],
+                        vec![value, version],
+                    )
+                } else {
+                    (
                         vec![Column::new("value".to_string(), ColumnType::String, 0)],
-                        vec![Row::new(vec![value])],
-                    )),
+                        vec![value],
+                    )
+                };
+
+                (
+                    Arc::new(DataFrame::new(columns, vec![Row::new(values)])),
                     None,
                     true,
                 )
@@ -271,6 +309,153 @@ impl CacheStoreSqlService {
 
                 (Arc::new(DataFrame::new(vec![], vec![])), None, false)
             }
+            CacheCommand::Publish { channel, message } => {
+                let value_size = channel.value.deep_size_of() + message.deep_size_of();
+                self.cache_notifier.publish(channel.value, message);
+
+                (
+                    Arc::new(DataFrame::new(vec![], vec![])),
+                    Some(value_size),
+                    true,
+                )
+            }
+            CacheCommand::Subscribe { .. } => {
+                return Err(CubeError::internal(
+                    "CACHE SUBSCRIBE must be executed with exec_cache_subscribe".to_string(),
+                ))
+            }
+            CacheCommand::HSet {
+                key,
+                field,
+                value,
+                ttl,
+            } => {
+                let value_size =
+                    key.value.deep_size_of() + field.deep_size_of() + value.deep_size_of();
+                let created = self
+                    .cachestore
+                    .cache_hset(key.value, field, value, ttl)
+                    .await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![Column::new("created".to_string(), ColumnType::Boolean, 0)],
+                        vec![Row::new(vec![TableValue::Boolean(created)])],
+                    )),
+                    Some(value_size),
+                    true,
+                )
+            }
+            CacheCommand::HGet { key, field } => {
+                let value = self.cachestore.cache_hget(key.value, field).await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![Column::new("value".to_string(), ColumnType::String, 0)],
+                        vec![Row::new(vec![value
+                            .map(TableValue::String)
+                            .unwrap_or(TableValue::Null)])],
+                    )),
+                    None,
+                    true,
+                )
+            }
+            CacheCommand::HGetAll { key } => {
+                let fields = self.cachestore.cache_hgetall(key.value).await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![
+                            Column::new("field".to_string(), ColumnType::String, 0),
+                            Column::new("value".to_string(), ColumnType::String, 1),
+                        ],
+                        fields
+                            .into_iter()
+                            .map(|(field, value)| {
+                                Row::new(vec![TableValue::String(field), TableValue::String(value)])
+                            })
+                            .collect(),
+                    )),
+                    None,
+                    true,
+                )
+            }
+            CacheCommand::HDel { key, field } => {
+                let removed = self.cachestore.cache_hdel(key.value, field).await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![Column::new("removed".to_string(), ColumnType::Boolean, 0)],
+                        vec![Row::new(vec![TableValue::Boolean(removed)])],
+                    )),
+                    None,
+                    true,
+                )
+            }
+            CacheCommand::ZAdd {
+                key,
+                score,
+                member,
+                ttl,
+            } => {
+                let value_size = key.value.deep_size_of() + member.deep_size_of();
+                let created = self
+                    .cachestore
+                    .cache_zadd(key.value, member, score, ttl)
+                    .await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![Column::new("created".to_string(), ColumnType::Boolean, 0)],
+                        vec![Row::new(vec![TableValue::Boolean(created)])],
+                    )),
+                    Some(value_size),
+                    true,
+                )
+            }
+            CacheCommand::ZRange {
+                key,
+                start,
+                stop,
+                rev,
+            } => {
+                let members = self
+                    .cachestore
+                    .cache_zrange(key.value, start, stop, rev)
+                    .await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![
+                            Column::new("member".to_string(), ColumnType::String, 0),
+                            Column::new("score".to_string(), ColumnType::Float, 1),
+                        ],
+                        members
+                            .into_iter()
+                            .map(|(member, score)| {
+                                Row::new(vec![
+                                    TableValue::String(member),
+                                    TableValue::Float(score.into()),
+                                ])
+                            })
+                            .collect(),
+                    )),
+                    None,
+                    true,
+                )
+            }
+            CacheCommand::ZRem { key, member } => {
+                let removed = self.cachestore.cache_zrem(key.value, member).await?;
+
+                (
+                    Arc::new(DataFrame::new(
+                        vec![Column::new("removed".to_string(), ColumnType::Boolean, 0)],
+                        vec![Row::new(vec![TableValue::Boolean(removed)])],
+                    )),
+                    None,
+                    true,
+                )
+            }
             CacheCommand::Incr { path } => {
                 let row = self.cachestore.cache_incr(path.value).await?;
 
@@ -287,11 +472,6 @@ impl CacheStoreSqlService {
             }
         };
 
-        let trace_index = TraceIndex {
-            // Important, it is used to aggregate all stats for cache by id
-            table_id: Some(1),
-            trace_obj: None,
-        };
         self.process_rate_limiter
             .commit_task_usage(
                 TaskType::Cache,
@@ -316,6 +496,37 @@ impl CacheStoreSqlService {
         Ok(result)
     }
 
+    pub fn exec_cache_subscribe(&self, prefix: String) -> Result<QueryResult, CubeError> {
+        app_metrics::CACHE_QUERIES
+            .add_with_tags(1, Some(&vec![metrics::format_tag("command", "subscribe")]));
+
+        let rows = self
+            .cache_notifier
+            .subscribe(prefix)
+            .map(|event| {
+                let event = event?;
+
+                Ok(Row::new(vec![
+                    TableValue::String(event.kind.as_str().to_string()),
+                    TableValue::String(event.key),
+                    match event.value {
+                        Some(value) => TableValue::String(Arc::unwrap_or_clone(value)),
+                        None => TableValue::Null,
+                    },
+                ]))
+            })
+            .boxed();
+
+        Ok(QueryResult::Subscription {
+            columns: vec![
+                Column::new("event".to_string(), ColumnType::String, 0),
+                Column::new("key".to_string(), ColumnType::String, 1),
+                Column::new("value".to_string(), ColumnType::String, 2),
+            ],
+            rows,
+        })
+    }
+
     pub async fn exec_queue_command_with_context(
         &self,
         context: SqlQueryContext,
@@ -325,10 +536,17 @@ impl CacheStoreSqlService {
         app_metrics::QUEUE_QUERIES
             .add_with_tags(1, Some(&vec![metrics::format_tag("command", command_tag)]));
 
+        let trace_index = TraceIndex {
+            // Important, it is used to aggregate all stats for queue by id
+            table_id: Some(1),
+            trace_obj: None,
+            // Budgeted per authenticated user, anonymous connections share one budget.
+            tenants: context.user.iter().cloned().collect(),
+        };
         let timeout = Some(Duration::from_secs(90));
         let wait_ms = self
             .process_rate_limiter
-            .wait_for_allow(TaskType::Queue, timeout)
+            .wait_for_allow(TaskType::Queue, &trace_index, timeout)
             .await?;
 
         let execution_time = SystemTime::now();
@@ -341,6 +559,8 @@ impl CacheStoreSqlService {
                 orphaned,
                 value,
                 external_id,
+                max_attempts,
+                delay,
             } => {
                 if exclusive && context.process_id.is_none() {
                     return Err(CubeError::user(
@@ -359,6 +579,8 @@ impl CacheStoreSqlService {
                         process_id: context.process_id.clone(),
                         exclusive,
                         external_id,
+                        max_attempts,
+                        visible_at: delay.map(|delay| delay.visible_at(Utc::now())),
                     })
                     .await?;
 
@@ -386,6 +608,8 @@ impl CacheStoreSqlService {
                 orphaned,
                 value,
                 external_id,
+                max_attempts,
+                delay,
                 concurrency,
             } => {
                 if exclusive && context.process_id.is_none() {
@@ -405,6 +629,8 @@ impl CacheStoreSqlService {
                         process_id: context.process_id.clone(),
                         exclusive,
                         external_id,
+                        max_attempts,
+                        visible_at: delay.map(|delay| delay.visible_at(Utc::now())),
                         concurrency,
                     })
                     .await?;
@@ -633,11 +859,6 @@ impl CacheStoreSqlService {
             }
         };
 
-        let trace_index = TraceIndex {
-            // Important, it is used to aggregate all stats for queue by id
-            table_id: Some(1),
-            trace_obj: None,
-        };
         self.process_rate_limiter
             .commit_task_usage(
                 TaskType::Queue,
@@ -710,6 +931,9 @@ impl SqlService for CacheStoreSqlService {
             CubeStoreStatement::Queue(command) => Ok(QueryResult::Frame(
                 self.exec_queue_command_with_context(ctx, command).await?,
             )),
+            CubeStoreStatement::Cache(CacheCommand::Subscribe { prefix }) => {
+                self.exec_cache_subscribe(prefix.value)
+            }
             CubeStoreStatement::Cache(command) => Ok(QueryResult::Frame(
                 self.exec_cache_command_with_context(ctx, command).await?,
             )),
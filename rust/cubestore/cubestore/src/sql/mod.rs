@@ -10,7 +10,7 @@ use chrono::format::Item::{Fixed, Literal, Numeric, Space};
 use chrono::format::Numeric::{Day, Hour, Minute, Month, Second, Year};
 use chrono::format::Pad::Zero;
 use chrono::format::Parsed;
-use chrono::{ParseResult, TimeZone, Utc};
+use chrono::{NaiveDate, ParseResult, TimeZone, Utc};
 use datafusion::arrow::array::*;
 use datafusion::arrow::compute::kernels::cast_utils::string_to_timestamp_nanos;
 use datafusion::arrow::datatypes::SchemaRef;
@@ -33,20 +33,26 @@ use tempfile::TempDir;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::time::timeout;
-use tracing::instrument;
+use tracing::{instrument, Instrument};
 use tracing_futures::WithSubscriber;
 
+use cubedatasketches::ThetaDataSketch;
 use cubehll::HllSketch;
 use parser::Statement as CubeStoreStatement;
 
-use crate::cachestore::CacheStore;
+use crate::cachestore::{CacheStore, CacheStoreNotifier};
 use crate::cluster::{Cluster, WorkerPlanningParams};
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::import::limits::ConcurrencyLimits;
+use crate::import::ndjson::resolve_column_paths;
 use crate::import::{parse_space_separated_binstring, ImportService, Ingestion};
+use crate::metastore::backup;
 use crate::metastore::multi_index::MultiIndex;
-use crate::metastore::source::SourceCredentials;
+use crate::metastore::source::{
+    HttpStreamFormat, KafkaPayloadFormat, SchemaRegistryConfig, SourceCredentials,
+};
+use crate::metastore::table::{DeleteComparison, DeletePredicate, TableParquetSettings};
 use crate::metastore::{
     is_valid_plain_binary_hll, HllFlavour, IdRow, ImportFormat, Index, IndexDef, IndexType,
     MetaStoreTable, Schema,
@@ -58,12 +64,18 @@ use crate::queryplanner::query_executor::{
     batches_to_dataframe, find_topmost_cluster_send_exec, QueryExecutor,
 };
 use crate::queryplanner::serialized_plan::{PreSerializedPlan, RowFilter, SerializedPlan};
+use crate::queryplanner::tdigest::TDigest;
 use crate::queryplanner::{PlanningMeta, QueryPlan, QueryPlanner};
+use crate::remotefs::fsck::{self, FsckOptions};
 use crate::remotefs::RemoteFs;
+use crate::sql::access_control::{statement_access, UserGrants};
 use crate::sql::cache::SqlResultCache;
-use crate::sql::parser::{CubeStoreParser, DropCommand, MetaStoreCommand, SystemCommand};
+use crate::sql::parser::{
+    AlterTableCommand, CacheCommand, CubeStoreParser, DropCommand, MetaStoreCommand, SystemCommand,
+};
 use crate::store::ChunkDataStore;
-use crate::table::{data, Row, TableValue, TimestampValue};
+use crate::table::parquet::parse_compression;
+use crate::table::{data, DateValue, IntervalValue, Row, TableValue, TimestampValue};
 use crate::util::decimal::{Decimal, Decimal96};
 use crate::util::strings::path_to_string;
 use crate::CubeError;
@@ -76,6 +88,7 @@ use data::create_array_builder;
 use datafusion::cube_ext::catch_unwind::async_try_with_catch_unwind;
 use deepsize::DeepSizeOf;
 
+pub mod access_control;
 pub mod cache;
 pub mod cachestore;
 mod explain_detailed;
@@ -95,6 +108,12 @@ pub enum QueryResult {
         schema: SchemaRef,
         batches: BoxStream<'static, Result<RecordBatch, CubeError>>,
     },
+    /// Long-lived result of `CACHE SUBSCRIBE`, every row is delivered to the client as soon as
+    /// it arrives. The stream ends only when the subscription is closed.
+    Subscription {
+        columns: Vec<Column>,
+        rows: BoxStream<'static, Result<Row, CubeError>>,
+    },
 }
 
 impl QueryResult {
@@ -102,6 +121,9 @@ impl QueryResult {
         match self {
             QueryResult::Frame(df) => df.get_schema(),
             QueryResult::Stream { schema, .. } => schema.clone(),
+            QueryResult::Subscription { columns, .. } => {
+                DataFrame::new(columns.clone(), vec![]).get_schema()
+            }
         }
     }
 
@@ -118,6 +140,7 @@ impl QueryResult {
 
                 Ok(Arc::new(df))
             }
+            QueryResult::Subscription { .. } => Err(subscription_unsupported()),
         }
     }
 
@@ -137,6 +160,7 @@ impl QueryResult {
                 schema,
                 batches: batches.try_collect().await?,
             },
+            QueryResult::Subscription { .. } => return Err(subscription_unsupported()),
         };
 
         cube_ext::spawn_blocking(move || -> Result<Vec<u8>, CubeError> {
@@ -190,6 +214,10 @@ impl std::fmt::Debug for QueryResult {
                 .debug_struct("Stream")
                 .field("schema", schema)
                 .finish_non_exhaustive(),
+            QueryResult::Subscription { columns, .. } => f
+                .debug_struct("Subscription")
+                .field("columns", columns)
+                .finish_non_exhaustive(),
         }
     }
 }
@@ -198,12 +226,19 @@ impl PartialEq for QueryResult {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (QueryResult::Frame(a), QueryResult::Frame(b)) => a == b,
-            // Streaming results carry a live BoxStream and aren't comparable.
+            // Streaming results and subscriptions carry a live BoxStream and aren't comparable.
             _ => false,
         }
     }
 }
 
+fn subscription_unsupported() -> CubeError {
+    CubeError::user(
+        "Subscriptions can't be collected, they are supported only over the WebSocket protocol"
+            .to_string(),
+    )
+}
+
 impl From<DataFrame> for QueryResult {
     fn from(df: DataFrame) -> Self {
         QueryResult::Frame(Arc::new(df))
@@ -309,6 +344,8 @@ pub struct SqlQueryContext {
     pub trace_obj: Option<String>,
     pub process_id: Option<String>,
     pub parameters: Option<QueryParameters>,
+    /// Grants of the authenticated user, `None` allows all statements.
+    pub grants: Option<UserGrants>,
 }
 
 impl SqlQueryContext {
@@ -341,6 +378,12 @@ impl SqlQueryContext {
         res.parameters = parameters.clone();
         res
     }
+
+    pub fn with_grants(&self, grants: Option<UserGrants>) -> Self {
+        let mut res = self.clone();
+        res.grants = grants;
+        res
+    }
 }
 
 pub struct SqlServiceImpl {
@@ -366,6 +409,7 @@ impl SqlServiceImpl {
     pub fn new(
         db: Arc<dyn MetaStore>,
         cachestore: Arc<dyn CacheStore>,
+        cache_notifier: Arc<CacheStoreNotifier>,
         chunk_store: Arc<dyn ChunkDataStore>,
         limits: Arc<ConcurrencyLimits>,
         query_planner: Arc<dyn QueryPlanner>,
@@ -384,6 +428,7 @@ impl SqlServiceImpl {
         Arc::new(SqlServiceImpl {
             cachestore: CacheStoreSqlService::new(
                 cachestore,
+                cache_notifier,
                 query_planner.clone(),
                 process_rate_limiter,
             ),
@@ -492,6 +537,7 @@ impl SqlServiceImpl {
             self.chunk_store.clone(),
             self.limits.clone(),
             table.clone(),
+            self.config_obj.encryption_key_id().clone(),
         );
         for rows_chunk in data.chunks(self.rows_per_chunk) {
             let rows = parse_chunk(rows_chunk, &real_col)?;
@@ -501,6 +547,60 @@ impl SqlServiceImpl {
         Ok(data.len() as u64)
     }
 
+    async fn delete_data(
+        &self,
+        schema_name: String,
+        table_name: String,
+        selection: &Expr,
+    ) -> Result<(), CubeError> {
+        let table = self
+            .db
+            .get_table(schema_name.clone(), table_name.clone())
+            .await?;
+        let predicate = parse_delete_predicate(selection, table.get_row().get_columns())?;
+        let columns = predicate.columns();
+        // Rows are deleted before the unique key deduplication, so filtering by other columns
+        // could bring back older versions of deleted rows.
+        if let Some(unique_key) = table.get_row().unique_key_columns() {
+            if let Some(c) = columns
+                .iter()
+                .find(|c| !unique_key.iter().any(|k| k.get_name() == **c))
+            {
+                return Err(CubeError::user(format!(
+                    "Delete from table {}.{} with unique key can filter only by unique key columns, but '{}' is used",
+                    schema_name, table_name, c
+                )));
+            }
+        }
+        for index in self.db.get_table_indexes(table.get_id()).await?.iter() {
+            let index_name = index.get_row().get_name();
+            if index.get_row().multi_index_id().is_some() {
+                return Err(CubeError::user(format!(
+                    "Delete is not supported for table {}.{} with partitioned index {}",
+                    schema_name, table_name, index_name
+                )));
+            }
+            // Aggregate indexes hold rows grouped by key columns only.
+            let index_columns = match index.get_row().get_type() {
+                IndexType::Regular => &index.get_row().get_columns()[..],
+                IndexType::Aggregate => {
+                    &index.get_row().get_columns()[..index.get_row().sort_key_size() as usize]
+                }
+            };
+            if let Some(c) = columns
+                .iter()
+                .find(|c| !index_columns.iter().any(|ic| ic.get_name() == **c))
+            {
+                return Err(CubeError::user(format!(
+                    "Delete by column '{}' is not supported for table {}.{} as index {} doesn't have it as a dimension",
+                    c, schema_name, table_name, index_name
+                )));
+            }
+        }
+        self.db.add_delete_marker(table.get_id(), predicate).await?;
+        Ok(())
+    }
+
     async fn dump_select_inputs(
         &self,
         query: &str,
@@ -707,6 +807,24 @@ pub fn boolean_prop(credentials: &Vec<SqlOption>, prop_name: &str) -> Option<boo
     return None;
 }
 
+/// Parses the `json_paths` option of an NDJSON import, a JSON object mapping column names to JSON
+/// paths, e.g. `'{"country": "$.user.address.country"}'`. Paths are validated by
+/// `resolve_column_paths`.
+fn parse_json_paths_option(json_paths: &str) -> Result<Vec<(String, String)>, CubeError> {
+    let bad_paths = || CubeError::user(format!("Bad json_paths {}", json_paths));
+    let parsed = json::parse(json_paths).map_err(|_| bad_paths())?;
+    if !parsed.is_object() {
+        return Err(bad_paths());
+    }
+    parsed
+        .entries()
+        .map(|(column, path)| {
+            let path = path.as_str().ok_or_else(bad_paths)?;
+            Ok((column.to_string(), path.to_string()))
+        })
+        .collect()
+}
+
 /// Normalizes an ident used for a column name -- hypothetically, by calling `to_ascii_lowercase()`
 /// when it is unquoted.  But actually it does nothing -- unquoted column names are being treated
 /// case sensitively, repeating our behavior for the DF upgrade.  This function serves as a marker
@@ -723,6 +841,19 @@ pub fn normalize_for_column_name(ident: &Ident) -> String {
     // datafusion::sql::planner::IdentNormalizer::new(true).normalize(ident.clone())
 }
 
+fn schema_and_table_name(name: &ObjectName, query: &str) -> Result<(String, String), CubeError> {
+    match name.0.as_slice() {
+        [schema, table] => Ok((
+            normalize_for_schema_table_or_index_name(schema),
+            normalize_for_schema_table_or_index_name(table),
+        )),
+        _ => Err(CubeError::user(format!(
+            "Schema's name should be present in query (boo.table1). Your query was '{}'",
+            query
+        ))),
+    }
+}
+
 /// Normalizes an ident used for "source" names -- hypothetically, this might call
 /// `to_ascii_lowercase()`, but actually it does nothing.  See comment for
 /// `normalize_for_column_name`.
@@ -796,6 +927,9 @@ impl SqlService for SqlServiceImpl {
             let mut parser = CubeStoreParser::new(query, context.parameters.take())?;
             parser.parse_statement()?
         };
+        if let Some(grants) = &context.grants {
+            grants.check(context.user.as_deref(), &statement_access(&ast))?;
+        }
         // trace!("AST is: {:?}", ast);
         match ast {
             CubeStoreStatement::Statement(Statement::ShowVariable { variable }) => {
@@ -902,6 +1036,96 @@ impl SqlService for SqlServiceImpl {
                     .exec_system_command_with_context(context, command)
                     .await?
                     .into()),
+                SystemCommand::Backup { location } => {
+                    let manifest = backup::create_backup(
+                        self.db.clone(),
+                        self.cachestore.cachestore(),
+                        self.remote_fs.clone(),
+                        location,
+                    )
+                    .await?;
+
+                    Ok(DataFrame::new(
+                        vec![
+                            Column::new("metastore_snapshot_id".to_string(), ColumnType::String, 0),
+                            Column::new(
+                                "cachestore_snapshot_id".to_string(),
+                                ColumnType::String,
+                                1,
+                            ),
+                            Column::new("data_files".to_string(), ColumnType::Int, 2),
+                        ],
+                        vec![Row::new(vec![
+                            TableValue::String(manifest.metastore.snapshot_id.to_string()),
+                            TableValue::String(manifest.cachestore.snapshot_id.to_string()),
+                            TableValue::Int(manifest.data_files.len() as i64),
+                        ])],
+                    )
+                    .into())
+                }
+                SystemCommand::Restore { location } => {
+                    let (metastore_snapshot_id, cachestore_snapshot_id) = backup::restore_backup(
+                        self.db.clone(),
+                        self.cachestore.cachestore(),
+                        self.remote_fs.clone(),
+                        location,
+                    )
+                    .await?;
+
+                    Ok(DataFrame::new(
+                        vec![
+                            Column::new("metastore_snapshot_id".to_string(), ColumnType::String, 0),
+                            Column::new(
+                                "cachestore_snapshot_id".to_string(),
+                                ColumnType::String,
+                                1,
+                            ),
+                        ],
+                        vec![Row::new(vec![
+                            TableValue::String(metastore_snapshot_id.to_string()),
+                            TableValue::String(cachestore_snapshot_id.to_string()),
+                        ])],
+                    )
+                    .into())
+                }
+                SystemCommand::Fsck { verify, repair } => {
+                    let issues = fsck::fsck(
+                        self.db.clone(),
+                        self.remote_fs.clone(),
+                        self.cluster.clone(),
+                        FsckOptions { verify, repair },
+                    )
+                    .await?;
+
+                    let rows = issues
+                        .into_iter()
+                        .map(|issue| {
+                            Row::new(vec![
+                                TableValue::String(issue.kind.as_str().to_string()),
+                                TableValue::String(issue.object.to_string()),
+                                issue
+                                    .id
+                                    .map_or(TableValue::Null, |id| TableValue::Int(id as i64)),
+                                issue.file.map_or(TableValue::Null, TableValue::String),
+                                TableValue::String(issue.details),
+                                issue.action.map_or(TableValue::Null, TableValue::String),
+                            ])
+                        })
+                        .collect();
+
+                    Ok(DataFrame::new(
+                        vec![
+                            Column::new("kind".to_string(), ColumnType::String, 0),
+                            Column::new("object".to_string(), ColumnType::String, 1),
+                            Column::new("id".to_string(), ColumnType::Int, 2),
+                            Column::new("file".to_string(), ColumnType::String, 3),
+                            Column::new("details".to_string(), ColumnType::String, 4),
+                            Column::new("action".to_string(), ColumnType::String, 5),
+                        ],
+                        rows,
+                    )
+                    .into())
+                }
             },
             CubeStoreStatement::Statement(Statement::SetVariable { .. }) => {
                 Ok(DataFrame::empty().into())
@@ -965,6 +1189,11 @@ impl SqlService for SqlServiceImpl {
                             match input_format.as_str() {
                                 "csv" => Result::Ok(ImportFormat::CSV),
                                 "csv_no_header" => Result::Ok(ImportFormat::CSVNoHeader),
+                                "parquet" => Result::Ok(ImportFormat::Parquet),
+                                "arrow" => Result::Ok(ImportFormat::ArrowIPC),
+                                "ndjson" => Result::Ok(ImportFormat::NDJSON {
+                                    column_paths: Vec::new(),
+                                }),
                                 _ => Result::Err(CubeError::user(format!(
                                     "Bad input_format {}",
                                     value
@@ -1030,6 +1259,43 @@ impl SqlService for SqlServiceImpl {
                             escape,
                             quote,
                         },
+                        ImportFormat::Parquet
+                        | ImportFormat::ArrowIPC
+                        | ImportFormat::NDJSON { .. } => {
+                            return Err(CubeError::user(format!(
+                                "Delimiter can't be specified for {:?} input format",
+                                import_format
+                            )))
+                        }
+                    }
+                }
+                let json_paths = with_options
+                    .iter()
+                    .filter_map(filter_sql_option_key_value)
+                    .find(|&(name, _)| name.value == "json_paths")
+                    .map_or(Ok(None), |(_, value)| match value {
+                        Expr::Value(Value::SingleQuotedString(json_paths)) => {
+                            parse_json_paths_option(json_paths).map(Some)
+                        }
+                        _ => Err(CubeError::user(format!("Bad json_paths {}", value))),
+                    })?;
+                if let Some(json_paths) = json_paths {
+                    let column_names = columns
+                        .iter()
+                        .map(|c| normalize_for_column_name(&c.name))
+                        .collect::<Vec<_>>();
+                    resolve_column_paths(
+                        &json_paths,
+                        &column_names.iter().map(|c| c.as_str()).collect::<Vec<_>>(),
+                    )?;
+                    match &mut import_format {
+                        ImportFormat::NDJSON { column_paths } => *column_paths = json_paths,
+                        f => {
+                            return Err(CubeError::user(format!(
+                                "json_paths can't be specified for {:?} input format",
+                                f
+                            )))
+                        }
                     }
                 }
                 let build_range_end = with_options
@@ -1092,6 +1358,82 @@ impl SqlService for SqlServiceImpl {
                             value
                         ))),
                     })?;
+                let retention_column = with_options
+                    .iter()
+                    .filter_map(filter_sql_option_key_value)
+                    .find(|&(name, _)| name.value == "retention_column")
+                    .map_or(Result::Ok(None), |(_, value)| match value {
+                        Expr::Value(Value::SingleQuotedString(column)) => {
+                            Result::Ok(Some(column.clone()))
+                        }
+                        _ => {
+                            Result::Err(CubeError::user(format!("Bad retention_column {}", value)))
+                        }
+                    })?;
+                let retention_period = with_options
+                    .iter()
+                    .filter_map(filter_sql_option_key_value)
+                    .find(|&(name, _)| name.value == "retention_period")
+                    .map_or(Result::Ok(None), |(_, value)| match value {
+                        Expr::Value(Value::SingleQuotedString(period)) => {
+                            let micros = interval_from_string(period)?.get_micros();
+                            if micros < IntervalValue::MICROS_PER_SECOND {
+                                return Result::Err(CubeError::user(format!(
+                                    "Bad retention_period {}. It should be at least one second.",
+                                    value
+                                )));
+                            }
+                            Result::Ok(Some((micros / IntervalValue::MICROS_PER_SECOND) as u64))
+                        }
+                        _ => {
+                            Result::Err(CubeError::user(format!("Bad retention_period {}", value)))
+                        }
+                    })?;
+                let retention =
+                    match (retention_column, retention_period) {
+                        (Some(column), Some(period_secs)) => Some((column, period_secs)),
+                        (None, None) => None,
+                        _ => return Err(CubeError::user(
+                            "retention_column and retention_period should be specified together"
+                                .to_string(),
+                        )),
+                    };
+                let compression = with_options
+                    .iter()
+                    .filter_map(filter_sql_option_key_value)
+                    .find(|&(name, _)| name.value == "compression")
+                    .map_or(Result::Ok(None), |(_, value)| match value {
+                        Expr::Value(Value::SingleQuotedString(compression)) => {
+                            parse_compression(compression)?;
+                            Result::Ok(Some(compression.trim().to_lowercase()))
+                        }
+                        _ => Result::Err(CubeError::user(format!("Bad compression {}", value))),
+                    })?;
+                let bloom_filter_columns = with_options
+                    .iter()
+                    .filter_map(filter_sql_option_key_value)
+                    .find(|&(name, _)| name.value == "bloom_filter_columns")
+                    .map_or(Result::Ok(Vec::new()), |(_, value)| match value {
+                        Expr::Value(Value::SingleQuotedString(columns)) => Result::Ok(
+                            columns
+                                .split(',')
+                                .map(|c| c.trim())
+                                .filter(|c| !c.is_empty())
+                                .map(|c| c.to_string())
+                                .unique()
+                                .collect(),
+                        ),
+                        _ => Result::Err(CubeError::user(format!(
+                            "Bad bloom_filter_columns {}",
+                            value
+                        ))),
+                    })?;
+                let parquet_settings = if compression.is_some() || !bloom_filter_columns.is_empty()
+                {
+                    Some(TableParquetSettings::new(compression, bloom_filter_columns))
+                } else {
+                    None
+                };
 
                 let res = self
                     .table_creator
@@ -1113,6 +1455,8 @@ impl SqlService for SqlServiceImpl {
                         unique_key,
                         aggregates,
                         partitioned_index,
+                        retention,
+                        parquet_settings,
                         &context.trace_obj,
                     )
                     .await?;
@@ -1193,6 +1537,21 @@ impl SqlService for SqlServiceImpl {
                             let password = string_prop(&credentials, "password");
                             let host = string_prop(&credentials, "host");
                             let use_ssl = boolean_prop(&credentials, "use_ssl");
+                            let format = string_prop(&credentials, "format")
+                                .map(|f| f.parse::<KafkaPayloadFormat>())
+                                .transpose()?
+                                .unwrap_or_default();
+                            let schema_registry = string_prop(&credentials, "schema_registry_url")
+                                .map(|url| SchemaRegistryConfig {
+                                    url,
+                                    user: string_prop(&credentials, "schema_registry_user"),
+                                    password: string_prop(&credentials, "schema_registry_password"),
+                                });
+                            if format != KafkaPayloadFormat::Json && schema_registry.is_none() {
+                                return Err(CubeError::user(
+                                    "schema_registry_url is required as credential for kafka source with avro or protobuf format".to_string(),
+                                ));
+                            }
                             Ok(SourceCredentials::Kafka {
                                 user,
                                 password,
@@ -1200,6 +1559,45 @@ impl SqlService for SqlServiceImpl {
                                     "host is required as credential for kafka source".to_string(),
                                 ))?,
                                 use_ssl: use_ssl.unwrap_or(false),
+                                format,
+                                schema_registry,
+                            })
+                        }
+                        "http" => {
+                            let user = string_prop(&credentials, "user");
+                            let password = string_prop(&credentials, "password");
+                            let url = string_prop(&credentials, "url");
+                            let format = string_prop(&credentials, "format")
+                                .map(|f| f.parse::<HttpStreamFormat>())
+                                .transpose()?
+                                .unwrap_or(HttpStreamFormat::Sse);
+                            Ok(SourceCredentials::Http {
+                                user,
+                                password,
+                                url: url.ok_or(CubeError::user(
+                                    "url is required as credential for http source".to_string(),
+                                ))?,
+                                format,
+                            })
+                        }
+                        "files" => {
+                            let prefix = string_prop(&credentials, "prefix");
+                            let poll_interval_secs =
+                                string_prop(&credentials, "poll_interval_secs")
+                                    .map(|v| {
+                                        v.parse::<u64>().map_err(|e| {
+                                            CubeError::user(format!(
+                                                "Can't parse poll_interval_secs '{}': {}",
+                                                v, e
+                                            ))
+                                        })
+                                    })
+                                    .transpose()?;
+                            Ok(SourceCredentials::Files {
+                                prefix: prefix.ok_or(CubeError::user(
+                                    "prefix is required as credential for files source".to_string(),
+                                ))?,
+                                poll_interval_secs: poll_interval_secs.unwrap_or(5),
                             })
                         }
                         x => Err(CubeError::user(format!("Not supported stream type: {}", x))),
@@ -1276,6 +1674,68 @@ impl SqlService for SqlServiceImpl {
 
                 Ok(DataFrame::empty().into())
             }
+            CubeStoreStatement::AlterTable { name, command } => {
+                let (schema_name, table_name) = schema_and_table_name(&name, query)?;
+                let table = self.db.get_table(schema_name.clone(), table_name).await?;
+                match command {
+                    AlterTableCommand::AddColumn(column) => {
+                        let column = convert_columns_type(
+                            &vec![column],
+                            self.config_obj.allow_decimal128(),
+                        )?
+                        .remove(0);
+                        self.db
+                            .add_table_column(
+                                table.get_id(),
+                                column.get_name().clone(),
+                                column.get_column_type().clone(),
+                            )
+                            .await?;
+                        // Cached results of `SELECT *` don't have the new column.
+                        self.cache.clear().await;
+                    }
+                    AlterTableCommand::DropColumn(column) => {
+                        self.db
+                            .drop_table_column(table.get_id(), normalize_for_column_name(&column))
+                            .await?;
+                        self.cache.clear().await;
+                    }
+                    AlterTableCommand::RenameTo(new_name) => {
+                        let (new_schema_name, new_table_name) = if new_name.0.len() == 1 {
+                            (
+                                schema_name,
+                                normalize_for_schema_table_or_index_name(&new_name.0[0]),
+                            )
+                        } else {
+                            schema_and_table_name(&new_name, query)?
+                        };
+                        self.db
+                            .rename_table(table.get_id(), new_schema_name, new_table_name)
+                            .await?;
+                    }
+                }
+
+                app_metrics::DATA_QUERIES.add_with_tags(
+                    1,
+                    Some(&vec![metrics::format_tag("command", "alter_table")]),
+                );
+
+                Ok(DataFrame::empty().into())
+            }
+            CubeStoreStatement::SwapTables { left, right } => {
+                app_metrics::DATA_QUERIES.add_with_tags(
+                    1,
+                    Some(&vec![metrics::format_tag("command", "swap_tables")]),
+                );
+
+                let (schema_name, table_name) = schema_and_table_name(&left, query)?;
+                let left = self.db.get_table(schema_name, table_name).await?;
+                let (schema_name, table_name) = schema_and_table_name(&right, query)?;
+                let right = self.db.get_table(schema_name, table_name).await?;
+                self.db.swap_tables(left.get_id(), right.get_id()).await?;
+
+                Ok(DataFrame::empty().into())
+            }
             CubeStoreStatement::Statement(Statement::Insert(Insert {
                 table,
                 columns,
@@ -1316,11 +1776,58 @@ impl SqlService for SqlServiceImpl {
                     .await?;
                 Ok(DataFrame::empty().into())
             }
+            CubeStoreStatement::Statement(Statement::Delete(Delete {
+                from,
+                using,
+                selection,
+                ..
+            })) => {
+                app_metrics::DATA_QUERIES
+                    .add_with_tags(1, Some(&vec![metrics::format_tag("command", "delete")]));
+
+                let tables = match &from {
+                    FromTable::WithFromKeyword(tables) | FromTable::WithoutKeyword(tables) => {
+                        tables
+                    }
+                };
+                let table_name = match tables.as_slice() {
+                    [TableWithJoins {
+                        relation: TableFactor::Table { name, .. },
+                        joins,
+                    }] if joins.is_empty() && using.is_none() => name,
+                    _ => {
+                        return Err(CubeError::user(format!(
+                            "Delete from a single table is expected. Your query was '{}'",
+                            query
+                        )))
+                    }
+                };
+                let selection = selection.ok_or_else(|| {
+                    CubeError::user(format!(
+                        "WHERE clause is required for delete. Your query was '{}'",
+                        query
+                    ))
+                })?;
+
+                let nv = &table_name.0;
+                if nv.len() != 2 {
+                    return Err(CubeError::user(format!("Schema's name should be present in query (boo.table1). Your query was '{}'", query)));
+                }
+                let schema_name = &normalize_for_schema_table_or_index_name(&nv[0]);
+                let table_name = &normalize_for_schema_table_or_index_name(&nv[1]);
+
+                self.delete_data(schema_name.clone(), table_name.clone(), &selection)
+                    .await?;
+                Ok(DataFrame::empty().into())
+            }
             CubeStoreStatement::Queue(command) => Ok(self
                 .cachestore
                 .exec_queue_command_with_context(context, command)
                 .await?
                 .into()),
+            CubeStoreStatement::Cache(CacheCommand::Subscribe { prefix }) => {
+                self.cachestore.exec_cache_subscribe(prefix.value)
+            }
             CubeStoreStatement::Cache(command) => Ok(self
                 .cachestore
                 .exec_cache_command_with_context(context, command)
@@ -1335,6 +1842,7 @@ impl SqlService for SqlServiceImpl {
                         &context.inline_tables,
                         context.trace_obj.clone(),
                     )
+                    .instrument(tracing::info_span!("planning"))
                     .await?;
 
                 app_metrics::DATA_QUERY_LOGICAL_PLAN_TOTAL_CREATION_TIME_US
@@ -1560,6 +2068,166 @@ fn parse_chunk(chunk: &[Vec<Expr>], column: &Vec<&Column>) -> Result<Vec<ArrayRe
     Ok(arrays)
 }
 
+fn parse_delete_predicate(expr: &Expr, columns: &[Column]) -> Result<DeletePredicate, CubeError> {
+    Ok(match expr {
+        Expr::Nested(e) => parse_delete_predicate(e, columns)?,
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => DeletePredicate::Not(Box::new(parse_delete_predicate(expr, columns)?)),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => DeletePredicate::And(
+            Box::new(parse_delete_predicate(left, columns)?),
+            Box::new(parse_delete_predicate(right, columns)?),
+        ),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Or,
+            right,
+        } => DeletePredicate::Or(
+            Box::new(parse_delete_predicate(left, columns)?),
+            Box::new(parse_delete_predicate(right, columns)?),
+        ),
+        Expr::BinaryOp { left, op, right } => {
+            let op = match op {
+                BinaryOperator::Eq => DeleteComparison::Eq,
+                BinaryOperator::NotEq => DeleteComparison::NotEq,
+                BinaryOperator::Lt => DeleteComparison::Lt,
+                BinaryOperator::LtEq => DeleteComparison::LtEq,
+                BinaryOperator::Gt => DeleteComparison::Gt,
+                BinaryOperator::GtEq => DeleteComparison::GtEq,
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "Unsupported operator in delete predicate: {}",
+                        expr
+                    )))
+                }
+            };
+            match (
+                delete_predicate_column(left, columns)?,
+                delete_predicate_column(right, columns)?,
+            ) {
+                (Some(column), None) => DeletePredicate::Compare {
+                    column: column.get_name().clone(),
+                    op,
+                    value: parse_delete_value(right, column)?,
+                },
+                (None, Some(column)) => DeletePredicate::Compare {
+                    column: column.get_name().clone(),
+                    op: op.flip(),
+                    value: parse_delete_value(left, column)?,
+                },
+                _ => {
+                    return Err(CubeError::user(format!(
+                    "Comparison of a column with a constant is expected in delete predicate: {}",
+                    expr
+                )))
+                }
+            }
+        }
+        Expr::InList {
+            expr: e,
+            list,
+            negated,
+        } => {
+            let column = delete_predicate_column_required(e, expr, columns)?;
+            DeletePredicate::InList {
+                column: column.get_name().clone(),
+                values: list
+                    .iter()
+                    .map(|v| parse_delete_value(v, column))
+                    .collect::<Result<Vec<_>, _>>()?,
+                negated: *negated,
+            }
+        }
+        Expr::Between {
+            expr: e,
+            negated,
+            low,
+            high,
+        } => {
+            let column = delete_predicate_column_required(e, expr, columns)?;
+            let between = DeletePredicate::And(
+                Box::new(DeletePredicate::Compare {
+                    column: column.get_name().clone(),
+                    op: DeleteComparison::GtEq,
+                    value: parse_delete_value(low, column)?,
+                }),
+                Box::new(DeletePredicate::Compare {
+                    column: column.get_name().clone(),
+                    op: DeleteComparison::LtEq,
+                    value: parse_delete_value(high, column)?,
+                }),
+            );
+            if *negated {
+                DeletePredicate::Not(Box::new(between))
+            } else {
+                between
+            }
+        }
+        Expr::IsNull(e) => DeletePredicate::IsNull {
+            column: delete_predicate_column_required(e, expr, columns)?
+                .get_name()
+                .clone(),
+            negated: false,
+        },
+        Expr::IsNotNull(e) => DeletePredicate::IsNull {
+            column: delete_predicate_column_required(e, expr, columns)?
+                .get_name()
+                .clone(),
+            negated: true,
+        },
+        _ => {
+            return Err(CubeError::user(format!(
+                "Unsupported delete predicate: {}",
+                expr
+            )))
+        }
+    })
+}
+
+fn delete_predicate_column<'a>(
+    expr: &Expr,
+    columns: &'a [Column],
+) -> Result<Option<&'a Column>, CubeError> {
+    let ident = match expr {
+        Expr::Identifier(ident) => ident,
+        Expr::CompoundIdentifier(idents) if !idents.is_empty() => &idents[idents.len() - 1],
+        _ => return Ok(None),
+    };
+    let name = normalize_for_column_name(ident);
+    match columns.iter().find(|c| *c.get_name() == name) {
+        Some(c) => Ok(Some(c)),
+        None => Err(CubeError::user(format!(
+            "Column {} is not present in table",
+            name
+        ))),
+    }
+}
+
+fn delete_predicate_column_required<'a>(
+    expr: &Expr,
+    predicate: &Expr,
+    columns: &'a [Column],
+) -> Result<&'a Column, CubeError> {
+    delete_predicate_column(expr, columns)?.ok_or_else(|| {
+        CubeError::user(format!(
+            "Column is expected in delete predicate: {}",
+            predicate
+        ))
+    })
+}
+
+fn parse_delete_value(expr: &Expr, column: &Column) -> Result<TableValue, CubeError> {
+    let mut buffer = Vec::new();
+    let mut builder = create_array_builder(column.get_column_type());
+    extract_data(expr, column, &mut buffer, builder.as_mut())?;
+    Ok(TableValue::from_array(builder.finish().as_ref(), 0))
+}
+
 fn parse_hyper_log_log<'a>(
     buffer: &'a mut Vec<u8>,
     v: &'a Value,
@@ -1783,6 +2451,28 @@ fn extract_data<'a>(
                 .unwrap()
                 .append_value(val);
         }
+        t @ (ColumnType::TDigest | ColumnType::ThetaSketch) => {
+            let builder = builder
+                .as_any_mut()
+                .downcast_mut::<BinaryBuilder>()
+                .unwrap();
+            if is_null {
+                builder.append_null();
+                return Ok(());
+            }
+            let val;
+            if let Expr::Value(v) = cell {
+                val = parse_binary_string(buffer, v)?;
+                if let ColumnType::TDigest = t {
+                    TDigest::read(val)?;
+                } else {
+                    ThetaDataSketch::read(val)?;
+                }
+            } else {
+                return Err(CubeError::user("Corrupted data in query.".to_string()));
+            };
+            builder.append_value(val);
+        }
         ColumnType::Timestamp => {
             let builder = builder
                 .as_any_mut()
@@ -1837,6 +2527,57 @@ fn extract_data<'a>(
             let v = parse_float(cell)?;
             builder.append_value(v);
         }
+        ColumnType::Date => {
+            let builder = builder
+                .as_any_mut()
+                .downcast_mut::<Date32Builder>()
+                .unwrap();
+            if is_null {
+                builder.append_null();
+                return Ok(());
+            }
+            match cell {
+                Expr::Value(Value::SingleQuotedString(v)) => {
+                    builder.append_value(date_from_string(v)?.get_days());
+                }
+                x => return Err(CubeError::user(format!("Can't parse date from, {:?}", x))),
+            }
+        }
+        ColumnType::Interval => {
+            let builder = builder
+                .as_any_mut()
+                .downcast_mut::<DurationMicrosecondBuilder>()
+                .unwrap();
+            if is_null {
+                builder.append_null();
+                return Ok(());
+            }
+            match cell {
+                Expr::Value(Value::SingleQuotedString(v)) => {
+                    builder.append_value(interval_from_string(v)?.get_micros());
+                }
+                x => {
+                    return Err(CubeError::user(format!(
+                        "Can't parse interval from, {:?}",
+                        x
+                    )))
+                }
+            }
+        }
+        // Lists are written as JSON arrays.
+        t @ ColumnType::Json | t @ ColumnType::List(_) => {
+            if is_null {
+                data::append_value(builder, t, &TableValue::Null);
+                return Ok(());
+            }
+            match cell {
+                Expr::Value(Value::SingleQuotedString(v)) => {
+                    let v = ImportFormat::parse_column_value_str(column, v)?;
+                    data::append_value(builder, t, &v);
+                }
+                x => return Err(CubeError::user(format!("Can't parse {} from, {:?}", t, x))),
+            }
+        }
     }
     Ok(())
 }
@@ -1860,6 +2601,155 @@ pub fn timestamp_from_string(v: &str) -> Result<TimestampValue, CubeError> {
     Ok(TimestampValue::new(nanos))
 }
 
+/// Accepts `YYYY-MM-DD`. Timestamps are accepted as well and truncated to their UTC date, as
+/// some sources export dates with a midnight time part.
+pub fn date_from_string(v: &str) -> Result<DateValue, CubeError> {
+    let days = match NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d") {
+        Ok(date) => (date - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days(),
+        Err(_) => match timestamp_from_string(v) {
+            Ok(ts) => ts.get_time_stamp().div_euclid(86_400 * 1_000_000_000),
+            Err(_) => return Err(CubeError::user(format!("Can't parse date: {}", v))),
+        },
+    };
+    let days =
+        i32::try_from(days).map_err(|_| CubeError::user(format!("Date is out of range: {}", v)))?;
+    Ok(DateValue::new(days))
+}
+
+/// Accepts Postgres-style intervals (`1 day 02:03:04.5`, `-3 days -00:00:01`, `90 minutes`)
+/// and ISO 8601 durations (`P1DT2H30M`). Months and years are rejected as they have no fixed
+/// length.
+pub fn interval_from_string(v: &str) -> Result<IntervalValue, CubeError> {
+    let bad_interval = || CubeError::user(format!("Can't parse interval: {}", v));
+    let trimmed = v.trim();
+    let micros = if trimmed.starts_with(|c: char| c == 'P' || c == 'p') {
+        parse_iso_interval(&trimmed[1..], v)?
+    } else {
+        let mut micros = 0_i64;
+        let mut tokens = trimmed.split_whitespace();
+        let mut empty = true;
+        while let Some(token) = tokens.next() {
+            empty = false;
+            let part = if token.contains(':') {
+                parse_interval_time(token).ok_or_else(bad_interval)?
+            } else {
+                let unit = tokens.next().ok_or_else(bad_interval)?;
+                let unit = interval_unit_micros(unit, v)?;
+                interval_amount(token, unit).ok_or_else(bad_interval)?
+            };
+            micros = micros.checked_add(part).ok_or_else(bad_interval)?;
+        }
+        if empty {
+            return Err(bad_interval());
+        }
+        micros
+    };
+    Ok(IntervalValue::new(micros))
+}
+
+fn interval_unit_micros(unit: &str, interval: &str) -> Result<i64, CubeError> {
+    const SECOND: i64 = IntervalValue::MICROS_PER_SECOND;
+    Ok(match unit.to_lowercase().as_str() {
+        "microsecond" | "microseconds" | "us" => 1,
+        "millisecond" | "milliseconds" | "ms" => 1000,
+        "second" | "seconds" | "sec" | "secs" | "s" => SECOND,
+        "minute" | "minutes" | "min" | "mins" | "m" => 60 * SECOND,
+        "hour" | "hours" | "h" => 3600 * SECOND,
+        "day" | "days" | "d" => IntervalValue::MICROS_PER_DAY,
+        "week" | "weeks" | "w" => 7 * IntervalValue::MICROS_PER_DAY,
+        "month" | "months" | "mon" | "mons" | "year" | "years" | "y" => {
+            return Err(CubeError::user(format!(
+                "Can't parse interval: {}. Months and years are not supported as they don't have a fixed length",
+                interval
+            )))
+        }
+        _ => return Err(CubeError::user(format!("Can't parse interval: {}", interval))),
+    })
+}
+
+fn interval_amount(amount: &str, unit: i64) -> Option<i64> {
+    if let Ok(n) = amount.parse::<i64>() {
+        return n.checked_mul(unit);
+    }
+    let n = amount.parse::<f64>().ok()? * unit as f64;
+    if !n.is_finite() || n.abs() >= i64::MAX as f64 {
+        return None;
+    }
+    Some(n.round() as i64)
+}
+
+/// `[-]HH:MM[:SS[.ffffff]]`
+fn parse_interval_time(s: &str) -> Option<i64> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let parts = s.split(':').collect_vec();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let hours = parts[0].parse::<u32>().ok()? as i64;
+    let minutes = parts[1].parse::<u8>().ok()? as i64;
+    let (seconds, fraction) = match parts.get(2) {
+        Some(s) => s.split_once('.').unwrap_or((*s, "")),
+        None => ("0", ""),
+    };
+    let seconds = seconds.parse::<u8>().ok()? as i64;
+    if minutes >= 60
+        || seconds >= 60
+        || fraction.len() > 6
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let fraction = format!("{:0<6}", fraction).parse::<i64>().ok()?;
+    let micros =
+        ((hours * 60 + minutes) * 60 + seconds) * IntervalValue::MICROS_PER_SECOND + fraction;
+    Some(if negative { -micros } else { micros })
+}
+
+/// `[nW][nD][T[nH][nM][nS]]` after the leading `P`.
+fn parse_iso_interval(s: &str, interval: &str) -> Result<i64, CubeError> {
+    let bad_interval = || CubeError::user(format!("Can't parse interval: {}", interval));
+    if s.is_empty() {
+        return Err(bad_interval());
+    }
+    let mut micros = 0_i64;
+    let mut in_time = false;
+    let mut amount_start = 0;
+    for (i, c) in s.char_indices() {
+        if c.is_ascii_digit() || c == '.' || c == '-' {
+            continue;
+        }
+        let amount = &s[amount_start..i];
+        amount_start = i + c.len_utf8();
+        let c = c.to_ascii_uppercase();
+        if c == 'T' {
+            if in_time || !amount.is_empty() {
+                return Err(bad_interval());
+            }
+            in_time = true;
+            continue;
+        }
+        let unit = match (c, in_time) {
+            ('W', false) => "w",
+            ('D', false) => "d",
+            ('Y', false) | ('M', false) => "month",
+            ('H', true) => "h",
+            ('M', true) => "m",
+            ('S', true) => "s",
+            _ => return Err(bad_interval()),
+        };
+        let unit = interval_unit_micros(unit, interval)?;
+        let part = interval_amount(amount, unit).ok_or_else(bad_interval)?;
+        micros = micros.checked_add(part).ok_or_else(bad_interval)?;
+    }
+    if amount_start != s.len() {
+        return Err(bad_interval());
+    }
+    Ok(micros)
+}
+
 fn parse_time(s: &str, format: &[chrono::format::Item]) -> ParseResult<Parsed> {
     let mut p = Parsed::new();
     chrono::format::parse(&mut p, s, format.into_iter())?;
@@ -1966,6 +2856,7 @@ mod tests {
     use crate::queryplanner::pretty_printers::{pp_phys_plan, pp_phys_plan_ext, PPOptions};
     use crate::remotefs::queue::QueueRemoteFs;
     use crate::scheduler::SchedulerImpl;
+    use crate::sql::access_control::Permission;
     use crate::table::data::{cmp_min_rows, cmp_row_key_heap};
     use crate::table::TableValue;
     use regex::Regex;
@@ -2159,6 +3050,7 @@ mod tests {
             let service = SqlServiceImpl::new(
                 meta_store,
                 cache_store,
+                CacheStoreNotifier::new(tokio::sync::broadcast::channel(16).0),
                 store,
                 limits,
                 Arc::new(MockQueryPlanner::new()),
@@ -2244,6 +3136,7 @@ mod tests {
             let service = SqlServiceImpl::new(
                 meta_store.clone(),
                 cache_store,
+                CacheStoreNotifier::new(tokio::sync::broadcast::channel(16).0),
                 chunk_store,
                 limits,
                 Arc::new(MockQueryPlanner::new()),
@@ -2364,6 +3257,7 @@ mod tests {
             let service = SqlServiceImpl::new(
                 meta_store.clone(),
                 cache_store,
+                CacheStoreNotifier::new(tokio::sync::broadcast::channel(16).0),
                 chunk_store,
                 limits,
                 Arc::new(MockQueryPlanner::new()),
@@ -4238,6 +5132,78 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn parquet_bloom_filter_pruning() -> Result<(), CubeError> {
+        Config::test("parquet_bloom_filter_pruning")
+            .update_config(|mut c| {
+                // Keep the chunk the plan reads from being compacted away
+                c.compaction_chunks_count_threshold = 100;
+                c
+            })
+            .start_test(async move |services| {
+                let service = services.sql_service;
+
+                service.exec_query("CREATE SCHEMA s").await?;
+                service
+                    .exec_query(
+                        "CREATE TABLE s.events (id int, name text) \
+                         WITH (bloom_filter_columns = 'name')",
+                    )
+                    .await?;
+                // Three row groups, each with names from 'a' to 'z', so statistics can't prune
+                // them and only the bloom filter tells the second group has the name
+                let values = (0..3 * crate::store::ROW_GROUP_SIZE)
+                    .map(|i| {
+                        let name = match i % 1000 {
+                            0 => "a".to_string(),
+                            1 => "z".to_string(),
+                            _ => format!("n{}", i),
+                        };
+                        format!("({}, '{}')", i, name)
+                    })
+                    .join(", ");
+                service
+                    .exec_query(&format!(
+                        "INSERT INTO s.events (id, name) VALUES {}",
+                        values
+                    ))
+                    .await?;
+
+                let plans = service
+                    .plan_query("SELECT id FROM s.events WHERE name = 'n20000'")
+                    .await?;
+                let context = crate::queryplanner::QueryPlannerImpl::make_execution_context(
+                    datafusion::prelude::SessionConfig::new(),
+                );
+                let batches =
+                    datafusion::physical_plan::collect(plans.worker.clone(), context.task_ctx())
+                        .await?;
+                assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+                fn sum_metric(plan: &Arc<dyn ExecutionPlan>, name: &str) -> usize {
+                    plan.metrics()
+                        .and_then(|m| m.sum_by_name(name))
+                        .map_or(0, |v| v.as_usize())
+                        + plan
+                            .children()
+                            .into_iter()
+                            .map(|c| sum_metric(c, name))
+                            .sum::<usize>()
+                }
+                assert_eq!(
+                    sum_metric(&plans.worker, "row_groups_matched_bloom_filter"),
+                    1
+                );
+                assert_eq!(
+                    sum_metric(&plans.worker, "row_groups_pruned_bloom_filter"),
+                    2
+                );
+                Ok::<(), CubeError>(())
+            })
+            .await;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn check_memory_test() -> Result<(), CubeError> {
         Config::test("check_memory_test")
@@ -6137,6 +7103,67 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn statements_restricted_by_user_grants() -> Result<(), CubeError> {
+        Config::test("statements_restricted_by_user_grants")
+            .start_test(async move |services| {
+                let service = services.sql_service;
+                for schema in ["s1", "s2"] {
+                    service
+                        .exec_query(&format!("CREATE SCHEMA {}", schema))
+                        .await?
+                        .collect()
+                        .await?;
+                    service
+                        .exec_query(&format!("CREATE TABLE {}.t (id int)", schema))
+                        .await?
+                        .collect()
+                        .await?;
+                }
+
+                let context = SqlQueryContext::default()
+                    .with_user(Some("reader".to_string()))
+                    .with_grants(Some(UserGrants {
+                        schemas: Some(vec!["s1".to_string()]),
+                        permissions: vec![Permission::Read],
+                    }));
+                service
+                    .exec_query_with_context(context.clone(), "SELECT * FROM s1.t")
+                    .await?
+                    .collect()
+                    .await?;
+                for (query, error) in [
+                    (
+                        "SELECT * FROM s1.t JOIN s2.t AS t2 ON t.id = t2.id",
+                        "Access denied for user 'reader': schema 's2' isn't granted",
+                    ),
+                    (
+                        "INSERT INTO s1.t (id) VALUES (1)",
+                        "Access denied for user 'reader': 'write' permission is required",
+                    ),
+                    (
+                        "CACHE GET 'k'",
+                        "Access denied for user 'reader': 'cache' permission is required",
+                    ),
+                    (
+                        "SYS DROP CACHE",
+                        "Access denied for user 'reader': 'admin' permission is required",
+                    ),
+                ] {
+                    let err = service
+                        .exec_query_with_context(context.clone(), query)
+                        .await
+                        .err()
+                        .unwrap_or_else(|| panic!("{} should be denied", query));
+                    assert_eq!(err.message, error);
+                }
+
+                Ok::<(), CubeError>(())
+            })
+            .await;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn total_count_over_groupping() -> Result<(), CubeError> {
         Config::test("total_count_over_groupping")
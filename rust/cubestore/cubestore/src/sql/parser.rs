@@ -1,5 +1,6 @@
 use crate::cachestore::{QueueItemStatus, QueueKey, QUEUE_ITEM_EXTERNAL_ID_MAX_LEN};
 use crate::sql::{QueryParameter, QueryParameters};
+use chrono::{DateTime, Duration, Utc};
 use sqlparser::ast::{
     ColumnDef, CreateIndex, CreateTable, HiveDistributionStyle, Ident, ObjectName, Query,
     SqlOption, Statement as SQLStatement, Value,
@@ -62,6 +63,14 @@ pub enum Statement {
         credentials: Vec<SqlOption>,
         or_update: bool,
     },
+    AlterTable {
+        name: ObjectName,
+        command: AlterTableCommand,
+    },
+    SwapTables {
+        left: ObjectName,
+        right: ObjectName,
+    },
     Cache(CacheCommand),
     Queue(QueueCommand),
     System(SystemCommand),
@@ -69,6 +78,13 @@ pub enum Statement {
     ExplainAnalyzeDetailed(Box<Query>),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterTableCommand {
+    AddColumn(ColumnDef),
+    DropColumn(Ident),
+    RenameTo(ObjectName),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RocksStoreName {
     Meta,
@@ -82,9 +98,12 @@ pub enum CacheCommand {
         value: String,
         ttl: Option<u32>,
         nx: bool,
+        /// Compare-and-set, `0` expects the key to be missing
+        if_version: Option<u64>,
     },
     Get {
         key: Ident,
+        with_version: bool,
     },
     Keys {
         prefix: Ident,
@@ -96,6 +115,46 @@ pub enum CacheCommand {
     Incr {
         path: Ident,
     },
+    Subscribe {
+        prefix: Ident,
+    },
+    Publish {
+        channel: Ident,
+        message: String,
+    },
+    HSet {
+        key: Ident,
+        field: String,
+        value: String,
+        ttl: Option<u32>,
+    },
+    HGet {
+        key: Ident,
+        field: String,
+    },
+    HGetAll {
+        key: Ident,
+    },
+    HDel {
+        key: Ident,
+        field: String,
+    },
+    ZAdd {
+        key: Ident,
+        score: f64,
+        member: String,
+        ttl: Option<u32>,
+    },
+    ZRange {
+        key: Ident,
+        start: i64,
+        stop: i64,
+        rev: bool,
+    },
+    ZRem {
+        key: Ident,
+        member: String,
+    },
 }
 
 impl CacheCommand {
@@ -107,6 +166,15 @@ impl CacheCommand {
             CacheCommand::Remove { .. } => "remove",
             CacheCommand::Clear { .. } => "clear",
             CacheCommand::Incr { .. } => "incr",
+            CacheCommand::Subscribe { .. } => "subscribe",
+            CacheCommand::Publish { .. } => "publish",
+            CacheCommand::HSet { .. } => "hset",
+            CacheCommand::HGet { .. } => "hget",
+            CacheCommand::HGetAll { .. } => "hgetall",
+            CacheCommand::HDel { .. } => "hdel",
+            CacheCommand::ZAdd { .. } => "zadd",
+            CacheCommand::ZRange { .. } => "zrange",
+            CacheCommand::ZRem { .. } => "zrem",
         }
     }
 }
@@ -120,6 +188,9 @@ pub enum QueueCommand {
         key: Ident,
         value: String,
         external_id: Option<String>,
+        /// Enables re-queue of orphaned and stalled items, see `QueueItem::fail_attempt`
+        max_attempts: Option<u32>,
+        delay: Option<QueueDelay>,
     },
     /// `QUEUE ADD` which also claims the item (moves it to the active status) in the
     /// same atomic operation, when the `concurrency` budget of the prefix allows it.
@@ -130,11 +201,15 @@ pub enum QueueCommand {
         key: Ident,
         value: String,
         external_id: Option<String>,
+        max_attempts: Option<u32>,
+        delay: Option<QueueDelay>,
         concurrency: u32,
     },
     Get {
         key: QueueKey,
     },
+    /// Lists orphaned and stalled items to cancel, except items with a retry policy,
+    /// see `CacheStore::queue_to_cancel`
     ToCancel {
         prefix: Ident,
         heartbeat_timeout: Option<u32>,
@@ -176,6 +251,22 @@ pub enum QueueCommand {
     Clear {},
 }
 
+/// `DELAY <seconds>` or `AT '<timestamp>'` of `QUEUE ADD`, the item cannot be claimed before it
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueDelay {
+    Seconds(u32),
+    Until(DateTime<Utc>),
+}
+
+impl QueueDelay {
+    pub fn visible_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            QueueDelay::Seconds(seconds) => now + Duration::seconds(*seconds as i64),
+            QueueDelay::Until(at) => at.clone(),
+        }
+    }
+}
+
 impl QueueCommand {
     pub fn as_tag_command(&self) -> &'static str {
         match self {
@@ -186,6 +277,7 @@ impl QueueCommand {
             QueueCommand::List { status_filter, .. } => match status_filter {
                 Some(QueueItemStatus::Active) => "active",
                 Some(QueueItemStatus::Pending) => "pending",
+                Some(QueueItemStatus::Dead) => "dead",
                 _ => "list",
             },
             QueueCommand::Cancel { .. } => "cancel",
@@ -203,11 +295,25 @@ impl QueueCommand {
 #[derive(Debug, Clone, PartialEq)]
 pub enum SystemCommand {
     KillAllJobs,
-    Repartition { partition_id: u64 },
+    Repartition {
+        partition_id: u64,
+    },
     Drop(DropCommand),
     PanicWorker,
     MetaStore(MetaStoreCommand),
     CacheStore(CacheStoreCommand),
+    /// Location is a path on the remote fs. Metastore and cachestore snapshots are taken one
+    /// after another, see `backup::create_backup`
+    Backup {
+        location: String,
+    },
+    Restore {
+        location: String,
+    },
+    Fsck {
+        verify: bool,
+        repair: bool,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -304,6 +410,20 @@ impl<'a> CubeStoreParser<'a> {
                     self.parser.next_token();
                     self.parse_create()
                 }
+                Keyword::ALTER
+                    if matches!(
+                        self.parser.peek_nth_token(1).token,
+                        Token::Word(w) if w.keyword == Keyword::TABLE
+                    ) =>
+                {
+                    self.parser.next_token(); // ALTER
+                    self.parser.next_token(); // TABLE
+                    self.parse_alter_table()
+                }
+                _ if w.value.eq_ignore_ascii_case("swap") => {
+                    self.parser.next_token();
+                    self.parse_swap_tables()
+                }
                 _ if w.value.eq_ignore_ascii_case("dump") => {
                     self.parser.next_token();
                     let s = self.parser.parse_statement()?;
@@ -387,6 +507,33 @@ impl<'a> CubeStoreParser<'a> {
         }
     }
 
+    fn parse_alter_table(&mut self) -> Result<Statement, ParserError> {
+        let name = self.parser.parse_object_name(false)?;
+        let command = if self.parser.parse_keyword(Keyword::ADD) {
+            let _ = self.parser.parse_keyword(Keyword::COLUMN);
+            AlterTableCommand::AddColumn(self.parser.parse_column_def()?)
+        } else if self.parser.parse_keyword(Keyword::DROP) {
+            let _ = self.parser.parse_keyword(Keyword::COLUMN);
+            AlterTableCommand::DropColumn(self.parser.parse_identifier()?)
+        } else if self.parser.parse_keywords(&[Keyword::RENAME, Keyword::TO]) {
+            AlterTableCommand::RenameTo(self.parser.parse_object_name(false)?)
+        } else {
+            return self.parser.expected(
+                "ADD COLUMN, DROP COLUMN or RENAME TO after ALTER TABLE",
+                self.parser.peek_token(),
+            );
+        };
+        Ok(Statement::AlterTable { name, command })
+    }
+
+    fn parse_swap_tables(&mut self) -> Result<Statement, ParserError> {
+        self.parser.expect_keyword(Keyword::TABLES)?;
+        let left = self.parser.parse_object_name(false)?;
+        self.parser.expect_token(&Token::Comma)?;
+        let right = self.parser.parse_object_name(false)?;
+        Ok(Statement::SwapTables { left, right })
+    }
+
     pub fn parse_streaming_source_table(&mut self) -> Result<Vec<ColumnDef>, ParserError> {
         if self.parser.parse_keyword(Keyword::CREATE) && self.parser.parse_keyword(Keyword::TABLE) {
             let statement = self.parser.parse_create_table(false, false, None, false)?;
@@ -504,20 +651,40 @@ impl<'a> CubeStoreParser<'a> {
         let command = match method.as_str() {
             "set" => {
                 let nx = self.parse_custom_token(&"nx");
-                let ttl = if self.parse_custom_token(&"ttl") {
-                    Some(self.parse_integer("ttl", false)?)
+                let ttl = self.parse_cache_ttl()?;
+                let key = self.parse_identifier()?;
+                let value = self.parse_literal_string()?;
+
+                let if_version = if self.parse_custom_token(&"if") {
+                    if !self.parse_custom_token(&"version") {
+                        return Err(ParserError::ParserError(format!(
+                            "Expected VERSION after IF, actual: {}",
+                            self.parser.peek_token().token
+                        )));
+                    }
+                    self.parser.expect_token(&Token::Eq)?;
+
+                    Some(self.parse_integer("version", false)?)
                 } else {
                     None
                 };
 
+                if nx && if_version.is_some() {
+                    return Err(ParserError::ParserError(
+                        "NX and IF VERSION cannot be used together".to_string(),
+                    ));
+                }
+
                 CacheCommand::Set {
-                    key: self.parse_identifier()?,
-                    value: self.parse_literal_string()?,
+                    key,
+                    value,
                     ttl,
                     nx,
+                    if_version,
                 }
             }
             "get" => CacheCommand::Get {
+                with_version: self.parse_custom_token(&"with_version"),
                 key: self.parse_identifier()?,
             },
             "keys" => CacheCommand::Keys {
@@ -530,9 +697,49 @@ impl<'a> CubeStoreParser<'a> {
                 key: self.parse_identifier()?,
             },
             "clear" => CacheCommand::Clear {},
+            "subscribe" => CacheCommand::Subscribe {
+                prefix: self.parse_identifier()?,
+            },
+            "publish" => CacheCommand::Publish {
+                channel: self.parse_identifier()?,
+                message: self.parse_literal_string()?,
+            },
+            "hset" => CacheCommand::HSet {
+                ttl: self.parse_cache_ttl()?,
+                key: self.parse_identifier()?,
+                field: self.parse_literal_string()?,
+                value: self.parse_literal_string()?,
+            },
+            "hget" => CacheCommand::HGet {
+                key: self.parse_identifier()?,
+                field: self.parse_literal_string()?,
+            },
+            "hgetall" => CacheCommand::HGetAll {
+                key: self.parse_identifier()?,
+            },
+            "hdel" => CacheCommand::HDel {
+                key: self.parse_identifier()?,
+                field: self.parse_literal_string()?,
+            },
+            "zadd" => CacheCommand::ZAdd {
+                ttl: self.parse_cache_ttl()?,
+                key: self.parse_identifier()?,
+                score: self.parse_score()?,
+                member: self.parse_literal_string()?,
+            },
+            "zrange" => CacheCommand::ZRange {
+                rev: self.parse_custom_token(&"rev"),
+                key: self.parse_identifier()?,
+                start: self.parse_integer("start", true)?,
+                stop: self.parse_integer("stop", true)?,
+            },
+            "zrem" => CacheCommand::ZRem {
+                key: self.parse_identifier()?,
+                member: self.parse_literal_string()?,
+            },
             other => {
                 return Err(ParserError::ParserError(format!(
-                    "Unknown cache command: {}, available: SET|GET|KEYS|INCR|REMOVE|CLEAR",
+                    "Unknown cache command: {}, available: SET|GET|KEYS|INCR|REMOVE|CLEAR|SUBSCRIBE|PUBLISH|HSET|HGET|HGETALL|HDEL|ZADD|ZRANGE|ZREM",
                     other
                 )))
             }
@@ -665,6 +872,110 @@ impl<'a> CubeStoreParser<'a> {
         Ok(Statement::System(SystemCommand::MetaStore(command)))
     }
 
+    fn parse_cache_ttl(&mut self) -> Result<Option<u32>, ParserError> {
+        if self.parse_custom_token(&"ttl") {
+            Ok(Some(self.parse_integer("ttl", false)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_score(&mut self) -> Result<f64, ParserError> {
+        if let Token::Placeholder(placeholder) = self.parser.peek_token().token {
+            self.parser.next_token();
+
+            return match self.unwrap_placeholder(&placeholder)? {
+                QueryParameter::Int64Value(value) => Ok(value as f64),
+                QueryParameter::Float64Value(value) if !value.is_finite() => {
+                    Err(ParserError::ParserError(format!(
+                        "score must be a finite number, actual: {}",
+                        value
+                    )))
+                }
+                QueryParameter::Float64Value(value) => Ok(value),
+                other => Err(ParserError::ParserError(format!(
+                    "Wrong parameters type, actual: {}, expected: float64 parameter",
+                    other.get_type()
+                ))),
+            };
+        }
+
+        let is_negative = self.parser.consume_token(&Token::Minus);
+        match self.parser.parse_number_value()? {
+            Value::Number(var, false) => {
+                let score = var.parse::<f64>().map_err(|err| {
+                    ParserError::ParserError(format!(
+                        "score must be a valid number, error: {}",
+                        err
+                    ))
+                })?;
+                if !score.is_finite() {
+                    return Err(ParserError::ParserError(format!(
+                        "score must be a finite number, actual: {}",
+                        var
+                    )));
+                }
+
+                Ok(if is_negative { -score } else { score })
+            }
+            x => Err(ParserError::ParserError(format!(
+                "score must be a valid number, actual: {:?}",
+                x
+            ))),
+        }
+    }
+
+    fn parse_max_attempts(&mut self) -> Result<u32, ParserError> {
+        let max_attempts: u32 = self.parse_integer("max_attempts", false)?;
+        if max_attempts == 0 {
+            return Err(ParserError::ParserError(
+                "MAX_ATTEMPTS must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(max_attempts)
+    }
+
+    fn parse_queue_delay(
+        &mut self,
+        prev: &Option<QueueDelay>,
+        at: bool,
+    ) -> Result<QueueDelay, ParserError> {
+        if prev.is_some() {
+            return Err(ParserError::ParserError(
+                "DELAY and AT cannot be used together".to_string(),
+            ));
+        }
+
+        if at {
+            let value = self.parse_literal_string()?;
+            let at = DateTime::parse_from_rfc3339(&value).map_err(|err| {
+                ParserError::ParserError(format!(
+                    "AT must be a RFC 3339 timestamp, actual: {}, error: {}",
+                    value, err
+                ))
+            })?;
+
+            Ok(QueueDelay::Until(at.with_timezone(&Utc)))
+        } else {
+            Ok(QueueDelay::Seconds(self.parse_integer("delay", false)?))
+        }
+    }
+
+    fn parse_queue_item_status(&mut self) -> Result<QueueItemStatus, ParserError> {
+        let status = self.parser.parse_identifier()?;
+
+        match status.value.to_ascii_lowercase().as_str() {
+            "pending" => Ok(QueueItemStatus::Pending),
+            "active" => Ok(QueueItemStatus::Active),
+            "dead" => Ok(QueueItemStatus::Dead),
+            other => Err(ParserError::ParserError(format!(
+                "Unknown queue item status: {}, expected PENDING, ACTIVE or DEAD",
+                other
+            ))),
+        }
+    }
+
     fn parse_queue(&mut self) -> Result<Statement, ParserError> {
         let method = match self.parser.next_token().token {
             Token::Word(w) => w.value.to_ascii_lowercase(),
@@ -682,12 +993,17 @@ impl<'a> CubeStoreParser<'a> {
                 let mut priority = 0i64;
                 let mut orphaned: Option<u32> = None;
                 let mut external_id: Option<String> = None;
+                let mut max_attempts: Option<u32> = None;
+                let mut delay: Option<QueueDelay> = None;
 
                 parse_sql_options!(self, {
                     "exclusive" => { exclusive = true },
                     "priority" => { priority = self.parse_integer("priority", true)? },
                     "orphaned" => { orphaned = Some(self.parse_integer("orphaned", false)?) },
                     "external_id" => { external_id = Some(self.parse_external_id()?) },
+                    "max_attempts" => { max_attempts = Some(self.parse_max_attempts()?) },
+                    "delay" => { delay = Some(self.parse_queue_delay(&delay, false)?) },
+                    "at" => { delay = Some(self.parse_queue_delay(&delay, true)?) },
                 });
 
                 QueueCommand::Add {
@@ -697,6 +1013,8 @@ impl<'a> CubeStoreParser<'a> {
                     key: self.parse_identifier()?,
                     value: self.parse_literal_string()?,
                     external_id,
+                    max_attempts,
+                    delay,
                 }
             }
             "add_and_retrieve" => {
@@ -704,12 +1022,17 @@ impl<'a> CubeStoreParser<'a> {
                 let mut priority = 0i64;
                 let mut orphaned: Option<u32> = None;
                 let mut external_id: Option<String> = None;
+                let mut max_attempts: Option<u32> = None;
+                let mut delay: Option<QueueDelay> = None;
 
                 parse_sql_options!(self, {
                     "exclusive" => { exclusive = true },
                     "priority" => { priority = self.parse_integer("priority", true)? },
                     "orphaned" => { orphaned = Some(self.parse_integer("orphaned", false)?) },
                     "external_id" => { external_id = Some(self.parse_external_id()?) },
+                    "max_attempts" => { max_attempts = Some(self.parse_max_attempts()?) },
+                    "delay" => { delay = Some(self.parse_queue_delay(&delay, false)?) },
+                    "at" => { delay = Some(self.parse_queue_delay(&delay, true)?) },
                 });
 
                 QueueCommand::AddAndRetrieve {
@@ -719,6 +1042,8 @@ impl<'a> CubeStoreParser<'a> {
                     key: self.parse_identifier()?,
                     value: self.parse_literal_string()?,
                     external_id,
+                    max_attempts,
+                    delay,
                     concurrency: self.parse_integer("concurrency", false)?,
                 }
             }
@@ -795,11 +1120,17 @@ impl<'a> CubeStoreParser<'a> {
             }
             "list" => {
                 let with_payload = self.parse_custom_token(&"with_payload");
+                let prefix = self.parser.parse_identifier()?;
+                let status_filter = if self.parse_custom_token(&"status") {
+                    Some(self.parse_queue_item_status()?)
+                } else {
+                    None
+                };
 
                 QueueCommand::List {
-                    prefix: self.parser.parse_identifier()?,
+                    prefix,
                     with_payload,
-                    status_filter: None,
+                    status_filter,
                     sort_by_priority: true,
                 }
             }
@@ -868,6 +1199,20 @@ impl<'a> CubeStoreParser<'a> {
             self.parse_cachestore()
         } else if self.parse_custom_token("panic") && self.parse_custom_token("worker") {
             Ok(Statement::System(SystemCommand::PanicWorker))
+        } else if self.parse_custom_token("backup") {
+            self.parser.expect_keyword(Keyword::TO)?;
+            Ok(Statement::System(SystemCommand::Backup {
+                location: self.parse_literal_string()?,
+            }))
+        } else if self.parse_custom_token("restore") {
+            self.parser.expect_keyword(Keyword::FROM)?;
+            Ok(Statement::System(SystemCommand::Restore {
+                location: self.parse_literal_string()?,
+            }))
+        } else if self.parse_custom_token("fsck") {
+            let verify = self.parse_custom_token("verify");
+            let repair = self.parse_custom_token("repair");
+            Ok(Statement::System(SystemCommand::Fsck { verify, repair }))
         } else {
             Err(ParserError::ParserError(
                 "Unknown system command".to_string(),
@@ -1291,6 +1636,8 @@ mod tests {
                 key,
                 value,
                 external_id,
+                max_attempts,
+                delay,
                 concurrency,
             }) => {
                 assert!(!exclusive);
@@ -1299,6 +1646,8 @@ mod tests {
                 assert_eq!(key.value, "key");
                 assert_eq!(value, "value");
                 assert_eq!(external_id, None);
+                assert_eq!(max_attempts, None);
+                assert_eq!(delay, None);
                 assert_eq!(concurrency, 4);
             }
             _ => panic!("Expected QueueCommand::AddAndRetrieve"),
@@ -1331,6 +1680,174 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_queue_retry_policy() -> Result<(), CubeError> {
+        match parse_stmt("QUEUE ADD PRIORITY 1 MAX_ATTEMPTS 3 'key' 'value'")? {
+            Statement::Queue(QueueCommand::Add { max_attempts, .. }) => {
+                assert_eq!(max_attempts, Some(3));
+            }
+            other => panic!("Expected QueueCommand::Add, actual: {:?}", other),
+        }
+
+        let res = parse_stmt("QUEUE ADD MAX_ATTEMPTS 0 'key' 'value'");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        match parse_stmt("QUEUE LIST WITH_PAYLOAD prefix STATUS dead")? {
+            Statement::Queue(QueueCommand::List {
+                with_payload,
+                status_filter,
+                ..
+            }) => {
+                assert!(with_payload);
+                assert_eq!(status_filter, Some(QueueItemStatus::Dead));
+            }
+            other => panic!("Expected QueueCommand::List, actual: {:?}", other),
+        }
+
+        let res = parse_stmt("QUEUE LIST prefix STATUS finished");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_queue_add_delay() -> Result<(), CubeError> {
+        match parse_stmt("QUEUE ADD PRIORITY 1 DELAY 30 'key' 'value'")? {
+            Statement::Queue(QueueCommand::Add { delay, .. }) => {
+                assert_eq!(delay, Some(QueueDelay::Seconds(30)));
+            }
+            other => panic!("Expected QueueCommand::Add, actual: {:?}", other),
+        }
+
+        match parse_stmt("QUEUE ADD_AND_RETRIEVE AT '2030-01-02T03:04:05+01:00' 'key' 'value' 1")? {
+            Statement::Queue(QueueCommand::AddAndRetrieve { delay, .. }) => {
+                let at = DateTime::parse_from_rfc3339("2030-01-02T02:04:05Z")
+                    .unwrap()
+                    .with_timezone(&Utc);
+                assert_eq!(delay, Some(QueueDelay::Until(at)));
+            }
+            other => panic!("Expected QueueCommand::AddAndRetrieve, actual: {:?}", other),
+        }
+
+        let res = parse_stmt("QUEUE ADD DELAY 30 AT '2030-01-02T03:04:05Z' 'key' 'value'");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        let res = parse_stmt("QUEUE ADD AT 'tomorrow' 'key' 'value'");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_cache_data_structures() -> Result<(), CubeError> {
+        match parse_stmt("CACHE SET TTL 60 'lock:1' 'owner' IF VERSION = 3")? {
+            Statement::Cache(CacheCommand::Set {
+                ttl, if_version, ..
+            }) => {
+                assert_eq!(ttl, Some(60));
+                assert_eq!(if_version, Some(3));
+            }
+            other => panic!("Expected CacheCommand::Set, actual: {:?}", other),
+        }
+
+        let res = parse_stmt("CACHE SET NX 'lock:1' 'owner' IF VERSION = 0");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+        let res = parse_stmt("CACHE SET 'lock:1' 'owner' IF 3");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        match parse_stmt("CACHE GET WITH_VERSION 'lock:1'")? {
+            Statement::Cache(CacheCommand::Get { key, with_version }) => {
+                assert_eq!(key.value, "lock:1");
+                assert!(with_version);
+            }
+            other => panic!("Expected CacheCommand::Get, actual: {:?}", other),
+        }
+
+        match parse_stmt("CACHE HSET TTL 10 'query:1' 'status' 'done'")? {
+            Statement::Cache(CacheCommand::HSet {
+                key,
+                field,
+                value,
+                ttl,
+            }) => {
+                assert_eq!(key.value, "query:1");
+                assert_eq!(field, "status");
+                assert_eq!(value, "done");
+                assert_eq!(ttl, Some(10));
+            }
+            other => panic!("Expected CacheCommand::HSet, actual: {:?}", other),
+        }
+
+        match parse_stmt("CACHE ZADD 'slow' -1.5 'query:1'")? {
+            Statement::Cache(CacheCommand::ZAdd { score, member, .. }) => {
+                assert_eq!(score, -1.5);
+                assert_eq!(member, "query:1");
+            }
+            other => panic!("Expected CacheCommand::ZAdd, actual: {:?}", other),
+        }
+
+        match parse_stmt("CACHE ZRANGE REV 'slow' 0 -1")? {
+            Statement::Cache(CacheCommand::ZRange {
+                key,
+                start,
+                stop,
+                rev,
+            }) => {
+                assert_eq!(key.value, "slow");
+                assert_eq!((start, stop, rev), (0, -1, true));
+            }
+            other => panic!("Expected CacheCommand::ZRange, actual: {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_cache_zadd_score_placeholder() -> Result<(), CubeError> {
+        let mut parser = CubeStoreParser::new(
+            "CACHE ZADD 'slow' ? 'query:1'",
+            Some(vec![QueryParameter::Float64Value(2.5)]),
+        )?;
+        match parser.parse_statement()? {
+            Statement::Cache(CacheCommand::ZAdd { score, .. }) => assert_eq!(score, 2.5),
+            other => panic!("Expected CacheCommand::ZAdd, actual: {:?}", other),
+        }
+
+        for score in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut parser = CubeStoreParser::new(
+                "CACHE ZADD 'slow' ? 'query:1'",
+                Some(vec![QueryParameter::Float64Value(score)]),
+            )?;
+            let res = parser.parse_statement();
+            assert!(res.is_err(), "expected parse error, got: {:?}", res);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_cache_subscribe_publish() -> Result<(), CubeError> {
+        match parse_stmt("CACHE SUBSCRIBE 'refresh:*'")? {
+            Statement::Cache(CacheCommand::Subscribe { prefix }) => {
+                assert_eq!(prefix.value, "refresh:*");
+            }
+            other => panic!("Expected CacheCommand::Subscribe, actual: {:?}", other),
+        }
+
+        match parse_stmt("CACHE PUBLISH 'refresh:all' 'message'")? {
+            Statement::Cache(CacheCommand::Publish { channel, message }) => {
+                assert_eq!(channel.value, "refresh:all");
+                assert_eq!(message, "message");
+            }
+            other => panic!("Expected CacheCommand::Publish, actual: {:?}", other),
+        }
+
+        let res = parse_stmt("CACHE PUBLISH 'refresh:all'");
+        assert!(res.is_err(), "expected parse error, got: {:?}", res);
+
+        Ok(())
+    }
+
     #[test]
     fn parse_queue_add_and_retrieve_placeholders() -> Result<(), CubeError> {
         let mut parser = CubeStoreParser::new(
@@ -1496,6 +2013,44 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_alter_table() -> Result<(), CubeError> {
+        match parse_stmt("ALTER TABLE foo.orders ADD COLUMN amount decimal(10, 2)")? {
+            Statement::AlterTable {
+                name,
+                command: AlterTableCommand::AddColumn(column),
+            } => {
+                assert_eq!(name.to_string(), "foo.orders");
+                assert_eq!(column.name.value, "amount");
+            }
+            s => panic!("Expected ALTER TABLE ADD COLUMN, got {:?}", s),
+        }
+        match parse_stmt("ALTER TABLE foo.orders DROP amount")? {
+            Statement::AlterTable {
+                command: AlterTableCommand::DropColumn(column),
+                ..
+            } => assert_eq!(column.value, "amount"),
+            s => panic!("Expected ALTER TABLE DROP COLUMN, got {:?}", s),
+        }
+        match parse_stmt("ALTER TABLE foo.orders RENAME TO foo.orders_old")? {
+            Statement::AlterTable {
+                command: AlterTableCommand::RenameTo(name),
+                ..
+            } => assert_eq!(name.to_string(), "foo.orders_old"),
+            s => panic!("Expected ALTER TABLE RENAME TO, got {:?}", s),
+        }
+        match parse_stmt("SWAP TABLES foo.orders, foo.orders_new")? {
+            Statement::SwapTables { left, right } => {
+                assert_eq!(left.to_string(), "foo.orders");
+                assert_eq!(right.to_string(), "foo.orders_new");
+            }
+            s => panic!("Expected SWAP TABLES, got {:?}", s),
+        }
+        assert!(parse_stmt("ALTER TABLE foo.orders ALTER COLUMN amount").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn parse_metastore_set_current() -> Result<(), CubeError> {
         let res = parse_stmt("sys MeTasTore SEt_Current 1671235558783")?;
@@ -1508,4 +2063,46 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_backup_restore() -> Result<(), CubeError> {
+        match parse_stmt("SYS BACKUP TO 'backups/daily'")? {
+            Statement::System(SystemCommand::Backup { location }) => {
+                assert_eq!(location, "backups/daily");
+            }
+            _ => panic!("Expected Backup"),
+        }
+
+        match parse_stmt("sys restore from 'backups/daily'")? {
+            Statement::System(SystemCommand::Restore { location }) => {
+                assert_eq!(location, "backups/daily");
+            }
+            _ => panic!("Expected Restore"),
+        }
+
+        assert!(parse_stmt("SYS BACKUP 'backups/daily'").is_err());
+        assert!(parse_stmt("SYS RESTORE 'backups/daily'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_fsck() -> Result<(), CubeError> {
+        for (query, expected_verify, expected_repair) in [
+            ("SYS FSCK", false, false),
+            ("sys fsck verify", true, false),
+            ("SYS FSCK REPAIR", false, true),
+            ("SYS FSCK VERIFY REPAIR", true, true),
+        ] {
+            match parse_stmt(query)? {
+                Statement::System(SystemCommand::Fsck { verify, repair }) => {
+                    assert_eq!(verify, expected_verify, "{}", query);
+                    assert_eq!(repair, expected_repair, "{}", query);
+                }
+                _ => panic!("Expected Fsck for {}", query),
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -6,7 +6,7 @@ use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::import::ImportService;
 use crate::metastore::job::JobType;
-use crate::metastore::table::StreamOffset;
+use crate::metastore::table::{StreamOffset, TableParquetSettings};
 use crate::metastore::{
     table::Table, HllFlavour, IdRow, ImportFormat, IndexDef, IndexType, MetaStoreEvent, RowKey,
     TableId,
@@ -99,6 +99,8 @@ impl TableCreator {
         unique_key: Option<Vec<Ident>>,
         aggregates: Option<Vec<(Ident, Ident)>>,
         partitioned_index: Option<PartitionedIndexRef>,
+        retention: Option<(String, u64)>,
+        parquet_settings: Option<TableParquetSettings>,
         trace_obj: &Option<String>,
     ) -> Result<IdRow<Table>, CubeError> {
         let extension: Option<serde_json::Value> =
@@ -122,6 +124,8 @@ impl TableCreator {
                     unique_key,
                     aggregates,
                     partitioned_index,
+                    retention,
+                    parquet_settings,
                     &trace_obj,
                     &extension,
                 )
@@ -159,6 +163,8 @@ impl TableCreator {
                     unique_key,
                     aggregates,
                     partitioned_index,
+                    retention,
+                    parquet_settings,
                     &trace_obj,
                     &extension,
                 )
@@ -185,6 +191,8 @@ impl TableCreator {
         unique_key: Option<Vec<Ident>>,
         aggregates: Option<Vec<(Ident, Ident)>>,
         partitioned_index: Option<PartitionedIndexRef>,
+        retention: Option<(String, u64)>,
+        parquet_settings: Option<TableParquetSettings>,
         trace_obj: &Option<String>,
         extension: &Option<serde_json::Value>,
     ) -> Result<IdRow<Table>, CubeError> {
@@ -204,7 +212,7 @@ impl TableCreator {
                     external,
                     if_not_exists,
                     locations.clone(),
-                    import_format,
+                    import_format.clone(),
                     build_range_end,
                     seal_at,
                     select_statement.clone(),
@@ -214,6 +222,8 @@ impl TableCreator {
                     unique_key.clone(),
                     aggregates.clone(),
                     partitioned_index.clone(),
+                    retention.clone(),
+                    parquet_settings.clone(),
                     trace_obj,
                     extension,
                 )
@@ -287,6 +297,8 @@ impl TableCreator {
         unique_key: Option<Vec<Ident>>,
         aggregates: Option<Vec<(Ident, Ident)>>,
         partitioned_index: Option<PartitionedIndexRef>,
+        retention: Option<(String, u64)>,
+        parquet_settings: Option<TableParquetSettings>,
         trace_obj: &Option<String>,
         extension: &Option<serde_json::Value>,
     ) -> Result<IdRow<Table>, CubeError> {
@@ -418,6 +430,8 @@ impl TableCreator {
                     None,
                     false,
                     extension.as_ref().map(|json_value| json_value.to_string()),
+                    retention,
+                    parquet_settings,
                 )
                 .await;
         }
@@ -504,6 +518,8 @@ impl TableCreator {
                 trace_obj_to_save,
                 if_not_exists,
                 extension.as_ref().map(|json_value| json_value.to_string()),
+                retention,
+                parquet_settings,
             )
             .await?;
 
@@ -744,154 +760,167 @@ pub fn convert_columns_type(
     for (i, col) in columns.iter().enumerate() {
         let cube_col = Column::new(
             normalize_for_column_name(&col.name),
-            match &col.data_type {
-                DataType::Date
-                | DataType::Time(_, _)
-                | DataType::Char(_)
-                | DataType::Varchar(_)
-                | DataType::Clob(_)
-                | DataType::Text
-                | DataType::TinyText
-                | DataType::MediumText
-                | DataType::LongText
-                | DataType::String(_)
-                | DataType::Character(_)
-                | DataType::CharacterVarying(_)
-                | DataType::CharVarying(_)
-                | DataType::Nvarchar(_)
-                | DataType::CharacterLargeObject(_)
-                | DataType::CharLargeObject(_)
-                | DataType::FixedString(_) => ColumnType::String,
-                DataType::Uuid
-                | DataType::Binary(_)
-                | DataType::Varbinary(_)
-                | DataType::Blob(_)
-                | DataType::TinyBlob
-                | DataType::MediumBlob
-                | DataType::LongBlob
-                | DataType::Bytea
-                | DataType::Array(_)
-                | DataType::Bytes(_) => ColumnType::Bytes,
-                DataType::Decimal(number_info)
-                | DataType::Numeric(number_info)
-                | DataType::BigNumeric(number_info)
-                | DataType::BigDecimal(number_info)
-                | DataType::Dec(number_info) => {
-                    let (precision, scale) = match number_info {
-                        ExactNumberInfo::None => (None, None),
-                        ExactNumberInfo::Precision(p) => (Some(*p), None),
-                        ExactNumberInfo::PrecisionAndScale(p, s) => (Some(*p), Some(*s)),
-                    };
-                    let (precision, scale) =
-                        proper_decimal_args(&precision, &scale, allow_decimal128);
-                    if precision > 18 {
-                        ColumnType::Decimal96 {
-                            precision: precision as i32,
-                            scale: scale as i32,
-                        }
-                    } else {
-                        ColumnType::Decimal {
-                            precision: precision as i32,
-                            scale: scale as i32,
-                        }
-                    }
-                }
-                DataType::SmallInt(_)
-                | DataType::Int(_)
-                | DataType::BigInt(_)
-                | DataType::Interval
-                | DataType::TinyInt(_)
-                | DataType::UnsignedTinyInt(_)
-                | DataType::Int2(_)
-                | DataType::UnsignedInt2(_)
-                | DataType::UnsignedSmallInt(_)
-                | DataType::MediumInt(_)
-                | DataType::UnsignedMediumInt(_)
-                | DataType::Int4(_)
-                | DataType::Int8(_)
-                | DataType::Int16
-                | DataType::Int32
-                | DataType::Int64
-                | DataType::Int128
-                | DataType::Int256
-                | DataType::Integer(_)
-                | DataType::UnsignedInt(_)
-                | DataType::UnsignedInt4(_)
-                | DataType::UnsignedInteger(_)
-                | DataType::UInt8
-                | DataType::UInt16
-                | DataType::UInt32
-                | DataType::UInt64
-                | DataType::UInt128
-                | DataType::UInt256
-                | DataType::UnsignedBigInt(_)
-                | DataType::UnsignedInt8(_) => ColumnType::Int,
-                DataType::Boolean | DataType::Bool => ColumnType::Boolean,
-                DataType::Float(_)
-                | DataType::Real
-                | DataType::Double(_)
-                | DataType::Float4
-                | DataType::Float32
-                | DataType::Float64
-                | DataType::Float8
-                | DataType::DoublePrecision => ColumnType::Float,
-                DataType::Timestamp(_, _)
-                | DataType::Date32
-                | DataType::Datetime(_)
-                | DataType::Datetime64(_, _) => ColumnType::Timestamp,
-                DataType::Custom(custom, _) => {
-                    let custom_type_name = custom.to_string().to_lowercase();
-                    match custom_type_name.as_str() {
-                        "tinyint" | "mediumint" => ColumnType::Int,
-                        "decimal96" => ColumnType::Decimal96 {
-                            scale: 5,
-                            precision: 27,
-                        },
-                        "int96" => ColumnType::Int96,
-                        "bytes" => ColumnType::Bytes,
-                        "varbinary" => ColumnType::Bytes,
-                        "hyperloglog" => ColumnType::HyperLogLog(HllFlavour::Airlift),
-                        "hyperloglogpp" => ColumnType::HyperLogLog(HllFlavour::ZetaSketch),
-                        "hll_snowflake" => ColumnType::HyperLogLog(HllFlavour::Snowflake),
-                        "hll_postgres" => ColumnType::HyperLogLog(HllFlavour::Postgres),
-                        "hll_datasketches" => ColumnType::HyperLogLog(HllFlavour::DataSketches),
-                        _ => {
-                            return Err(CubeError::user(format!(
-                                "Custom type '{}' is not supported",
-                                custom
-                            )))
-                        }
-                    }
-                }
-                DataType::Regclass
-                | DataType::JSON
-                | DataType::JSONB
-                | DataType::Map(_, _)
-                | DataType::Tuple(_)
-                | DataType::Nested(_)
-                | DataType::Enum(_, _)
-                | DataType::Set(_)
-                | DataType::Struct(_, _)
-                | DataType::Union(_)
-                | DataType::Nullable(_)
-                | DataType::LowCardinality(_)
-                | DataType::Bit(_)
-                | DataType::BitVarying(_)
-                | DataType::AnyType
-                | DataType::Unspecified
-                | DataType::Trigger => {
-                    return Err(CubeError::user(format!(
-                        "Type '{}' is not supported.",
-                        col.data_type
-                    )));
-                }
-            },
+            convert_data_type(&col.data_type, allow_decimal128)?,
             i,
         );
         rolupdb_columns.push(cube_col);
     }
     Ok(rolupdb_columns)
 }
+
+fn convert_data_type(
+    data_type: &DataType,
+    allow_decimal128: bool,
+) -> Result<ColumnType, CubeError> {
+    Ok(match data_type {
+        DataType::Time(_, _)
+        | DataType::Char(_)
+        | DataType::Varchar(_)
+        | DataType::Clob(_)
+        | DataType::Text
+        | DataType::TinyText
+        | DataType::MediumText
+        | DataType::LongText
+        | DataType::String(_)
+        | DataType::Character(_)
+        | DataType::CharacterVarying(_)
+        | DataType::CharVarying(_)
+        | DataType::Nvarchar(_)
+        | DataType::CharacterLargeObject(_)
+        | DataType::CharLargeObject(_)
+        | DataType::FixedString(_) => ColumnType::String,
+        DataType::Uuid
+        | DataType::Binary(_)
+        | DataType::Varbinary(_)
+        | DataType::Blob(_)
+        | DataType::TinyBlob
+        | DataType::MediumBlob
+        | DataType::LongBlob
+        | DataType::Bytea
+        | DataType::Array(ArrayElemTypeDef::None)
+        | DataType::Bytes(_) => ColumnType::Bytes,
+        DataType::Array(ArrayElemTypeDef::AngleBracket(element))
+        | DataType::Array(ArrayElemTypeDef::SquareBracket(element, _))
+        | DataType::Array(ArrayElemTypeDef::Parenthesis(element)) => {
+            ColumnType::list_of(convert_data_type(element, allow_decimal128)?)?
+        }
+        DataType::JSON | DataType::JSONB => ColumnType::Json,
+        DataType::Decimal(number_info)
+        | DataType::Numeric(number_info)
+        | DataType::BigNumeric(number_info)
+        | DataType::BigDecimal(number_info)
+        | DataType::Dec(number_info) => {
+            let (precision, scale) = match number_info {
+                ExactNumberInfo::None => (None, None),
+                ExactNumberInfo::Precision(p) => (Some(*p), None),
+                ExactNumberInfo::PrecisionAndScale(p, s) => (Some(*p), Some(*s)),
+            };
+            let (precision, scale) = proper_decimal_args(&precision, &scale, allow_decimal128);
+            if precision > 18 {
+                ColumnType::Decimal96 {
+                    precision: precision as i32,
+                    scale: scale as i32,
+                }
+            } else {
+                ColumnType::Decimal {
+                    precision: precision as i32,
+                    scale: scale as i32,
+                }
+            }
+        }
+        DataType::SmallInt(_)
+        | DataType::Int(_)
+        | DataType::BigInt(_)
+        | DataType::TinyInt(_)
+        | DataType::UnsignedTinyInt(_)
+        | DataType::Int2(_)
+        | DataType::UnsignedInt2(_)
+        | DataType::UnsignedSmallInt(_)
+        | DataType::MediumInt(_)
+        | DataType::UnsignedMediumInt(_)
+        | DataType::Int4(_)
+        | DataType::Int8(_)
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Int128
+        | DataType::Int256
+        | DataType::Integer(_)
+        | DataType::UnsignedInt(_)
+        | DataType::UnsignedInt4(_)
+        | DataType::UnsignedInteger(_)
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::UInt128
+        | DataType::UInt256
+        | DataType::UnsignedBigInt(_)
+        | DataType::UnsignedInt8(_) => ColumnType::Int,
+        DataType::Boolean | DataType::Bool => ColumnType::Boolean,
+        DataType::Date => ColumnType::Date,
+        DataType::Interval => ColumnType::Interval,
+        DataType::Float(_)
+        | DataType::Real
+        | DataType::Double(_)
+        | DataType::Float4
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Float8
+        | DataType::DoublePrecision => ColumnType::Float,
+        DataType::Timestamp(_, _)
+        | DataType::Date32
+        | DataType::Datetime(_)
+        | DataType::Datetime64(_, _) => ColumnType::Timestamp,
+        DataType::Custom(custom, _) => {
+            let custom_type_name = custom.to_string().to_lowercase();
+            match custom_type_name.as_str() {
+                "tinyint" | "mediumint" => ColumnType::Int,
+                "decimal96" => ColumnType::Decimal96 {
+                    scale: 5,
+                    precision: 27,
+                },
+                "int96" => ColumnType::Int96,
+                "bytes" => ColumnType::Bytes,
+                "varbinary" => ColumnType::Bytes,
+                "hyperloglog" => ColumnType::HyperLogLog(HllFlavour::Airlift),
+                "hyperloglogpp" => ColumnType::HyperLogLog(HllFlavour::ZetaSketch),
+                "hll_snowflake" => ColumnType::HyperLogLog(HllFlavour::Snowflake),
+                "hll_postgres" => ColumnType::HyperLogLog(HllFlavour::Postgres),
+                "hll_datasketches" => ColumnType::HyperLogLog(HllFlavour::DataSketches),
+                "tdigest" => ColumnType::TDigest,
+                "theta_sketch" => ColumnType::ThetaSketch,
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "Custom type '{}' is not supported",
+                        custom
+                    )))
+                }
+            }
+        }
+        DataType::Regclass
+        | DataType::Map(_, _)
+        | DataType::Tuple(_)
+        | DataType::Nested(_)
+        | DataType::Enum(_, _)
+        | DataType::Set(_)
+        | DataType::Struct(_, _)
+        | DataType::Union(_)
+        | DataType::Nullable(_)
+        | DataType::LowCardinality(_)
+        | DataType::Bit(_)
+        | DataType::BitVarying(_)
+        | DataType::AnyType
+        | DataType::Unspecified
+        | DataType::Trigger => {
+            return Err(CubeError::user(format!(
+                "Type '{}' is not supported.",
+                data_type
+            )));
+        }
+    })
+}
+
 fn proper_decimal_args(
     precision: &Option<u64>,
     scale: &Option<u64>,
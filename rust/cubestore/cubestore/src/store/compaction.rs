@@ -9,6 +9,7 @@ use crate::metastore::{
     deactivate_table_on_corrupt_data, table::Table, Chunk, IdRow, Index, IndexType, MetaStore,
     Partition, PartitionData,
 };
+use crate::queryplanner::delete_markers::filter_deleted_rows;
 use crate::queryplanner::merge_sort::LastRowByUniqueKeyExec;
 use crate::queryplanner::metadata_cache::MetadataCacheFactory;
 use crate::queryplanner::trace_data_loaded::{DataLoadedSize, TraceDataLoadedExec};
@@ -197,18 +198,26 @@ impl CompactionServiceImpl {
         )
         .task_ctx();
 
-        let in_memory_res = self
-            .compact_chunks_to_memory(mem_chunks, &partition, &index, &table, task_context.clone())
-            .await;
-        let persistent_res = self
-            .compact_chunks_to_persistent(
-                persistent_chunks,
-                &partition,
-                &index,
-                &table,
-                task_context,
-            )
-            .await;
+        let mut in_memory_res = Ok(());
+        for chunks in group_chunks_by_deletes_applied(mem_chunks) {
+            let res = self
+                .compact_chunks_to_memory(chunks, &partition, &index, &table, task_context.clone())
+                .await;
+            in_memory_res = in_memory_res.and(res);
+        }
+        let mut persistent_res = Ok(());
+        for chunks in group_chunks_by_deletes_applied(persistent_chunks) {
+            let res = self
+                .compact_chunks_to_persistent(
+                    chunks,
+                    &partition,
+                    &index,
+                    &table,
+                    task_context.clone(),
+                )
+                .await;
+            persistent_res = persistent_res.and(res);
+        }
         deactivate_res?;
         in_memory_res?;
         persistent_res?;
@@ -230,12 +239,17 @@ impl CompactionServiceImpl {
         let compaction_in_memory_chunks_size_limit =
             self.config.compaction_in_memory_chunks_size_limit();
 
-        chunks.sort_by(|a, b| {
-            a.get_row()
-                .get_row_count()
-                .partial_cmp(&b.get_row().get_row_count())
-                .unwrap()
-        });
+        if table.get_row().aggregates_depend_on_insert_order() {
+            // Groups must not interleave in time, so that compacted chunks keep their place.
+            sort_chunks_by_insert_time(&mut chunks);
+        } else {
+            chunks.sort_by(|a, b| {
+                a.get_row()
+                    .get_row_count()
+                    .partial_cmp(&b.get_row().get_row_count())
+                    .unwrap()
+            });
+        }
 
         let mut compact_groups = Vec::new();
 
@@ -345,7 +359,12 @@ impl CompactionServiceImpl {
         let replay_handle_id =
             merge_replay_handles(self.meta_store.clone(), &chunks, table.get_id()).await?;
         self.meta_store
-            .swap_chunks_without_check(old_chunk_ids, new_chunk_ids, replay_handle_id)
+            .swap_chunks_without_check(
+                old_chunk_ids,
+                new_chunk_ids,
+                replay_handle_id,
+                self.config.encryption_key_id().clone(),
+            )
             .await?;
 
         Ok(())
@@ -427,7 +446,12 @@ impl CompactionServiceImpl {
             .await?;
 
         self.meta_store
-            .swap_chunks_without_check(old_chunk_ids, vec![(chunk.get_id(), file_size)], None)
+            .swap_chunks_without_check(
+                old_chunk_ids,
+                vec![(chunk.get_id(), file_size)],
+                None,
+                self.config.encryption_key_id().clone(),
+            )
             .await?;
 
         Ok(())
@@ -489,7 +513,12 @@ impl CompactionService for CompactionServiceImpl {
             .meta_store
             .get_chunks_by_partition(partition_id, false)
             .await?;
-        all_pending_chunks.sort_by_key(|c| c.get_row().get_row_count());
+        if table.get_row().aggregates_depend_on_insert_order() {
+            // Chunks left out must be newer than everything merged into the partition.
+            sort_chunks_by_insert_time(&mut all_pending_chunks);
+        } else {
+            all_pending_chunks.sort_by_key(|c| c.get_row().get_row_count());
+        }
         let mut size = 0;
         let chunks = all_pending_chunks
             .iter()
@@ -506,7 +535,12 @@ impl CompactionService for CompactionServiceImpl {
             .map(|c| c.clone())
             .collect::<Vec<_>>();
 
-        if chunks.is_empty() {
+        // Partition data is rewritten to purge rows deleted by pending `DELETE` markers even if
+        // there are no chunks to merge.
+        let purge_deletes = multi_part.is_none()
+            && partition.get_row().has_main_table_file()
+            && partition.get_row().deletes_applied() < table.get_row().last_delete_marker_id();
+        if chunks.is_empty() && !purge_deletes {
             return Ok(());
         }
 
@@ -543,6 +577,26 @@ impl CompactionService for CompactionServiceImpl {
 
         let chunks = chunks_to_use;
 
+        // Merged rows are purged of `DELETE` markers the table has by now, newer markers are left
+        // for the next compaction. Chunks activated after the table was read are newer than its
+        // markers, so the table is reloaded to get the markers they applied.
+        let table = if chunks
+            .iter()
+            .any(|c| c.get_row().deletes_applied() > table.get_row().last_delete_marker_id())
+        {
+            self.meta_store.get_table_by_id(table.get_id()).await?
+        } else {
+            table
+        };
+        let deletes_applied = table.get_row().last_delete_marker_id();
+        let chunk_inputs = chunk_inputs
+            .into_iter()
+            .zip(chunks.iter())
+            .map(|(input, c)| {
+                filter_deleted_rows(input, table.get_row(), c.get_row().deletes_applied())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let chunks_row_count = chunks
             .iter()
             .map(|c| c.get_row().get_row_count())
@@ -612,14 +666,19 @@ impl CompactionService for CompactionServiceImpl {
 
             if self.config.metastore_batch_rpc() {
                 let children = (0..new_partitions_count)
-                    .map(|_| Partition::new_child(&partition, None))
+                    .map(|_| {
+                        Partition::new_child(&partition, None).set_deletes_applied(deletes_applied)
+                    })
                     .collect::<Vec<_>>();
                 new_partitions = self.meta_store.create_partitions(children).await?;
             } else {
                 for _ in 0..new_partitions_count {
                     new_partitions.push(
                         self.meta_store
-                            .create_partition(Partition::new_child(&partition, None))
+                            .create_partition(
+                                Partition::new_child(&partition, None)
+                                    .set_deletes_applied(deletes_applied),
+                            )
                             .await?,
                     );
                 }
@@ -695,10 +754,14 @@ impl CompactionService for CompactionServiceImpl {
 
                 let data_source_exec = DataSourceExec::new(Arc::new(file_scan));
 
-                Arc::new(TraceDataLoadedExec::new(
-                    Arc::new(data_source_exec),
-                    data_loaded_size.clone(),
-                ))
+                filter_deleted_rows(
+                    Arc::new(TraceDataLoadedExec::new(
+                        Arc::new(data_source_exec),
+                        data_loaded_size.clone(),
+                    )),
+                    table.get_row(),
+                    partition.get_row().deletes_applied(),
+                )?
             }
             None => Arc::new(EmptyExec::new(schema.clone())),
         };
@@ -740,7 +803,13 @@ impl CompactionService for CompactionServiceImpl {
             // In memory chunks shouldn't ever get here. Otherwise replay handle should be defined.
             let swapped = self
                 .meta_store
-                .swap_compacted_chunks(partition_id, chunk_ids, c.get_id(), file_size)
+                .swap_compacted_chunks(
+                    partition_id,
+                    chunk_ids,
+                    c.get_id(),
+                    file_size,
+                    self.config.encryption_key_id().clone(),
+                )
                 .await?;
             if !swapped {
                 log::debug!(
@@ -752,6 +821,33 @@ impl CompactionService for CompactionServiceImpl {
             return Ok(());
         }
 
+        if count_and_min.is_empty() {
+            // All rows were deleted. An empty partition still covers the key range.
+            let mut new_partitions = new_partitions.into_iter();
+            let empty = new_partitions.next().ok_or_else(|| {
+                CubeError::internal(format!(
+                    "No new partitions for compaction of {}",
+                    partition_id
+                ))
+            })?;
+            for p in new_partitions {
+                self.meta_store.delete_partition(p.get_id()).await?;
+            }
+            let min_max = (
+                partition.get_row().get_min_val().clone(),
+                partition.get_row().get_max_val().clone(),
+            );
+            self.meta_store
+                .swap_active_partitions(
+                    vec![(partition, chunks)],
+                    vec![(empty, 0)],
+                    vec![(0, min_max, (None, None))],
+                    self.config.encryption_key_id().clone(),
+                )
+                .await?;
+            return Ok(());
+        }
+
         let mut filtered_partitions = Vec::new();
         for (i, p) in new_partitions
             .into_iter()
@@ -845,6 +941,7 @@ impl CompactionService for CompactionServiceImpl {
                         }
                     })
                     .collect::<Result<Vec<_>, CubeError>>()?,
+                self.config.encryption_key_id().clone(),
             )
             .await?;
 
@@ -968,6 +1065,7 @@ impl CompactionService for CompactionServiceImpl {
             self.remote_fs.clone(),
             self.metadata_cache_factory.clone(),
             self.config.metastore_batch_rpc(),
+            self.config.encryption_key_id().clone(),
             keys,
             key_len,
             multi_partition_id,
@@ -1012,6 +1110,7 @@ impl CompactionService for CompactionServiceImpl {
             self.remote_fs.clone(),
             self.metadata_cache_factory.clone(),
             self.config.metastore_batch_rpc(),
+            self.config.encryption_key_id().clone(),
             keys,
             key_len,
             multi_partition_id,
@@ -1617,6 +1716,27 @@ pub fn cast_plan_to_schema(
     Ok(Arc::new(ProjectionExec::try_new(exprs, plan)?))
 }
 
+/// Orders chunks by the time their oldest rows were inserted. `merge_chunks` keeps the input order
+/// for rows with equal keys, which is what `LAST_VALUE` aggregations rely on.
+pub fn sort_chunks_by_insert_time(chunks: &mut [IdRow<Chunk>]) {
+    chunks.sort_by_key(|c| {
+        let c_row = c.get_row();
+        (c_row.oldest_insert_at().or(*c_row.created_at()), c.get_id())
+    });
+}
+
+/// Splits chunks into groups which applied the same `DELETE` markers, keeping their order. Merged
+/// chunks inherit the markers applied by their sources, so only chunks of one group can be merged.
+pub fn group_chunks_by_deletes_applied(chunks: Vec<IdRow<Chunk>>) -> Vec<Vec<IdRow<Chunk>>> {
+    chunks
+        .into_iter()
+        .into_group_map_by(|c| c.get_row().deletes_applied())
+        .into_iter()
+        .sorted_by_key(|(deletes_applied, _)| *deletes_applied)
+        .map(|(_, chunks)| chunks)
+        .collect()
+}
+
 /// Builds a `SendableRecordBatchStream` merging the persistent partition data `l` with the
 /// already-sorted chunk inputs `r` (one sorted ExecutionPlan per chunk). Inputs are merged with a
 /// k-way `SortPreservingMergeExec` instead of being concatenated and re-sorted.
@@ -1729,7 +1849,8 @@ mod tests {
     use crate::config::Config;
     use crate::config::MockConfigObj;
     use crate::metastore::{
-        BaseRocksStoreFs, Column, ColumnType, IndexDef, IndexType, RocksMetaStore,
+        AggregateFunction, BaseRocksStoreFs, Column, ColumnType, IndexDef, IndexType,
+        RocksMetaStore,
     };
     use crate::queryplanner::metadata_cache::BasicMetadataCacheFactory;
     use crate::remotefs::LocalDirRemoteFs;
@@ -1775,6 +1896,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -2036,6 +2159,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -2207,6 +2332,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -2404,6 +2531,8 @@ mod tests {
                 None,
                 false,
                 None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -2572,6 +2701,95 @@ mod tests {
         let _ = fs::remove_dir_all(chunk_remote_store_path.clone());
     }
 
+    #[tokio::test]
+    async fn merge_chunks_count_and_picked_values() {
+        let cols = vec![
+            Column::new("key".to_string(), ColumnType::String, 0),
+            Column::new("cnt".to_string(), ColumnType::Int, 1),
+            Column::new("any".to_string(), ColumnType::Int, 2),
+            Column::new("last".to_string(), ColumnType::Int, 3),
+        ];
+        let schema = Arc::new(Schema::new(
+            cols.iter().map(|c| c.into()).collect::<Vec<Field>>(),
+        ));
+        let aggregates = vec![
+            AggregateColumn::new(cols[1].clone(), AggregateFunction::COUNT),
+            AggregateColumn::new(cols[2].clone(), AggregateFunction::ANY_VALUE),
+            AggregateColumn::new(cols[3].clone(), AggregateFunction::LAST_VALUE),
+        ];
+        let batch =
+            |keys: Vec<&str>, cnt: Vec<i64>, any: Vec<Option<i64>>, last: Vec<Option<i64>>| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(StringArray::from(keys)),
+                        Arc::new(Int64Array::from(cnt)),
+                        Arc::new(Int64Array::from(any)),
+                        Arc::new(Int64Array::from(last)),
+                    ],
+                )
+                .unwrap()
+            };
+        // Inputs are passed oldest first.
+        let main_table = try_make_memory_data_source(
+            &[vec![batch(
+                vec!["a", "b"],
+                vec![2, 1],
+                vec![Some(1), None],
+                vec![Some(10), Some(20)],
+            )]],
+            schema.clone(),
+            None,
+        )
+        .unwrap();
+        let chunk = try_make_memory_data_source(
+            &[vec![batch(
+                vec!["a", "a", "b"],
+                vec![1, 3, 1],
+                vec![Some(2), None, Some(5)],
+                vec![Some(11), None, Some(21)],
+            )]],
+            schema.clone(),
+            None,
+        )
+        .unwrap();
+
+        let stream = merge_chunks(
+            1,
+            main_table,
+            vec![chunk],
+            None,
+            Some(aggregates),
+            Arc::new(TaskContext::default()),
+        )
+        .await
+        .unwrap();
+        let batches = datafusion::physical_plan::common::collect(stream)
+            .await
+            .unwrap();
+        let batch = concat_batches(&schema, &batches).unwrap();
+        let rows = (0..batch.num_rows())
+            .map(|i| Row::new(TableValue::from_columns(batch.columns(), i)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![
+                    TableValue::String("a".to_string()),
+                    TableValue::Int(6),
+                    TableValue::Int(1),
+                    TableValue::Int(11),
+                ]),
+                Row::new(vec![
+                    TableValue::String("b".to_string()),
+                    TableValue::Int(2),
+                    TableValue::Int(5),
+                    TableValue::Int(21),
+                ]),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn partition_compaction_int96() {
         Config::test("partition_compaction_int96")
@@ -2984,6 +3202,8 @@ struct MultiSplit {
     fs: Arc<dyn RemoteFs>,
     metadata_cache_factory: Arc<dyn CubestoreMetadataCacheFactory>,
     metastore_batch_rpc: bool,
+    /// Key the remote fs encrypts uploaded files with.
+    encryption_key_id: Option<String>,
     keys: Vec<Row>,
     key_len: usize,
     multi_partition_id: u64,
@@ -3001,6 +3221,7 @@ impl MultiSplit {
         fs: Arc<dyn RemoteFs>,
         metadata_cache_factory: Arc<dyn CubestoreMetadataCacheFactory>,
         metastore_batch_rpc: bool,
+        encryption_key_id: Option<String>,
         keys: Vec<Row>,
         key_len: usize,
         multi_partition_id: u64,
@@ -3012,6 +3233,7 @@ impl MultiSplit {
             fs,
             metadata_cache_factory,
             metastore_batch_rpc,
+            encryption_key_id,
             keys,
             key_len,
             multi_partition_id,
@@ -3157,6 +3379,7 @@ impl MultiSplit {
                     .collect(),
                 self.new_partition_rows,
                 initial_split,
+                self.encryption_key_id,
             )
             .await
     }
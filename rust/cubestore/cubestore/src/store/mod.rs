@@ -21,8 +21,9 @@ extern crate bincode;
 use bincode::{deserialize_from, serialize_into};
 
 use crate::metastore::{
-    deactivate_table_due_to_corrupt_data, deactivate_table_on_corrupt_data, table::Table, Chunk,
-    Column, ColumnType, IdRow, Index, IndexType, MetaStore, Partition, WAL,
+    deactivate_table_due_to_corrupt_data, deactivate_table_on_corrupt_data, table::Table,
+    AggregateFunction, Chunk, Column, ColumnType, IdRow, Index, IndexType, MetaStore, Partition,
+    WAL,
 };
 use crate::queryplanner::{try_make_memory_data_source, QueryPlannerImpl};
 use crate::remotefs::{ensure_temp_file_is_dropped, RemoteFs};
@@ -43,11 +44,16 @@ use crate::config::ConfigObj;
 use crate::metastore::chunks::chunk_file_name;
 use crate::queryplanner::trace_data_loaded::{DataLoadedSize, TraceDataLoadedExec};
 use crate::table::data::{cmp_min_rows, cmp_partition_key};
-use crate::table::parquet::{arrow_schema, CubestoreMetadataCacheFactory, ParquetTableStore};
+use crate::table::parquet::{
+    arrow_schema, reconcile_batch_schema, CubestoreMetadataCacheFactory, ParquetTableStore,
+};
 use compaction::{
-    cast_plan_to_schema, merge_chunks, merge_replay_handles, write_chunks_split_into_children,
+    cast_plan_to_schema, group_chunks_by_deletes_applied, merge_chunks, merge_replay_handles,
+    sort_chunks_by_insert_time, write_chunks_split_into_children,
+};
+use datafusion::arrow::array::{
+    Array, ArrayRef, Int64Array, Int64Builder, StringBuilder, UInt64Array,
 };
-use datafusion::arrow::array::{Array, ArrayRef, Int64Builder, StringBuilder, UInt64Array};
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::arrow::row::{RowConverter, SortField};
@@ -459,9 +465,15 @@ impl ChunkDataStore for ChunkStore {
             .into_iter()
             .filter(|c| c.get_row().in_memory() && c.get_row().active())
             .collect::<Vec<_>>();
+        // Chunks which applied newer `DELETE` markers are left for the next repartition.
+        let mut chunks = group_chunks_by_deletes_applied(chunks)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
         if chunks.is_empty() {
             return Ok(());
         }
+        sort_chunks_by_insert_time(&mut chunks);
 
         //Merge all partition in memory chunk into one
         let key_size = index.get_row().sort_key_size() as usize;
@@ -535,7 +547,12 @@ impl ChunkDataStore for ChunkStore {
             merge_replay_handles(self.meta_store.clone(), &chunks, table.get_id()).await?;
 
         self.meta_store
-            .swap_chunks_without_check(old_chunk_ids, new_chunk_ids?, replay_handle_id)
+            .swap_chunks_without_check(
+                old_chunk_ids,
+                new_chunk_ids?,
+                replay_handle_id,
+                self.config.encryption_key_id().clone(),
+            )
             .await?;
 
         Ok(())
@@ -611,7 +628,12 @@ impl ChunkDataStore for ChunkStore {
             .await?;
 
         self.meta_store
-            .swap_chunks(old_chunks, new_chunk_ids, replay_handle_id.clone())
+            .swap_chunks(
+                old_chunks,
+                new_chunk_ids,
+                replay_handle_id.clone(),
+                self.config.encryption_key_id().clone(),
+            )
             .await?;
 
         Ok(())
@@ -690,19 +712,19 @@ impl ChunkDataStore for ChunkStore {
                     && c.get_id() <= end_chunk_id
             })
             .collect::<Vec<_>>();
-        if group.is_empty() {
-            return Ok(());
+        for group in group_chunks_by_deletes_applied(group) {
+            self.merge_chunk_group_into_children(
+                &partition,
+                &index,
+                &table,
+                &children,
+                &boundaries,
+                &group,
+                data_loaded_size.clone(),
+            )
+            .await?;
         }
-        self.merge_chunk_group_into_children(
-            &partition,
-            &index,
-            &table,
-            &children,
-            &boundaries,
-            &group,
-            data_loaded_size,
-        )
-        .await
+        Ok(())
     }
 
     async fn get_chunk_columns(&self, chunk: IdRow<Chunk>) -> Result<Vec<RecordBatch>, CubeError> {
@@ -731,12 +753,11 @@ impl ChunkDataStore for ChunkStore {
             }
             let memory_chunks = self.memory_chunks.read().await;
             let chunk_name = chunk_file_name(chunk.get_id(), chunk.get_row().suffix());
-            Ok(vec![memory_chunks
-                .get(&chunk_name)
-                .map(|b| b.clone())
-                .unwrap_or(RecordBatch::new_empty(Arc::new(
-                    arrow_schema(&index.get_row()),
-                )))])
+            let schema = Arc::new(arrow_schema(&index.get_row()));
+            Ok(vec![match memory_chunks.get(&chunk_name) {
+                Some(b) => reconcile_batch_schema(b.clone(), &schema)?,
+                None => RecordBatch::new_empty(schema),
+            }])
         } else {
             let (local_file, index) = self.download_chunk(chunk, partition, index).await?;
             let metadata_cache_factory: Arc<dyn CubestoreMetadataCacheFactory> =
@@ -960,16 +981,18 @@ impl ChunkStore {
                     break;
                 }
             }
-            self.merge_chunk_group_into_children(
-                &partition,
-                &index,
-                &table,
-                &children,
-                &boundaries,
-                &group,
-                data_loaded_size.clone(),
-            )
-            .await?;
+            for group in group_chunks_by_deletes_applied(group) {
+                self.merge_chunk_group_into_children(
+                    &partition,
+                    &index,
+                    &table,
+                    &children,
+                    &boundaries,
+                    &group,
+                    data_loaded_size.clone(),
+                )
+                .await?;
+            }
             if start.elapsed() >= time_budget {
                 break;
             }
@@ -1272,11 +1295,21 @@ impl ChunkStore {
         // the checked swap to preserve the row-count integrity guard.
         let swap_result = if merge_collapses_rows {
             self.meta_store
-                .swap_chunks_without_check(group_ids.clone(), new_chunk_ids, replay_handle_id)
+                .swap_chunks_without_check(
+                    group_ids.clone(),
+                    new_chunk_ids,
+                    replay_handle_id,
+                    self.config.encryption_key_id().clone(),
+                )
                 .await
         } else {
             self.meta_store
-                .swap_chunks(group_ids.clone(), new_chunk_ids, replay_handle_id)
+                .swap_chunks(
+                    group_ids.clone(),
+                    new_chunk_ids,
+                    replay_handle_id,
+                    self.config.encryption_key_id().clone(),
+                )
                 .await
         };
         if let Err(e) = swap_result {
@@ -1482,6 +1515,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -1580,6 +1615,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -1600,7 +1637,7 @@ mod tests {
                 .unwrap()
                 .unwrap();
             meta_store
-                .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None)
+                .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None, None)
                 .await
                 .unwrap();
             let chunk = meta_store.get_chunk(1).await.unwrap();
@@ -1698,6 +1735,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -1805,6 +1844,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -1917,6 +1958,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -1941,7 +1984,7 @@ mod tests {
                     .unwrap()
                     .unwrap();
                 meta_store
-                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None)
+                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None, None)
                     .await
                     .unwrap();
                 chunk_ids.push(chunk.get_id());
@@ -1964,6 +2007,7 @@ mod tests {
                         (0, (None, Some(mid.clone())), (None, Some(mid.clone()))),
                         (0, (Some(mid.clone()), None), (Some(mid.clone()), None)),
                     ],
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2114,6 +2158,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2138,7 +2184,7 @@ mod tests {
                     .unwrap()
                     .unwrap();
                 meta_store
-                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None)
+                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None, None)
                     .await
                     .unwrap();
                 chunk_ids.push(chunk.get_id());
@@ -2161,6 +2207,7 @@ mod tests {
                         (0, (None, Some(mid.clone())), (None, Some(mid.clone()))),
                         (0, (Some(mid.clone()), None), (Some(mid.clone()), None)),
                     ],
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2286,6 +2333,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2307,7 +2356,7 @@ mod tests {
                     .unwrap()
                     .unwrap();
                 meta_store
-                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None)
+                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None, None)
                     .await
                     .unwrap();
             }
@@ -2329,6 +2378,7 @@ mod tests {
                         (0, (None, Some(mid.clone())), (None, Some(mid.clone()))),
                         (0, (Some(mid.clone()), None), (Some(mid.clone()), None)),
                     ],
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2440,6 +2490,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2472,7 +2524,7 @@ mod tests {
                     .unwrap()
                     .unwrap();
                 meta_store
-                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None)
+                    .swap_chunks(Vec::new(), vec![(chunk.get_id(), file_size)], None, None)
                     .await
                     .unwrap();
                 chunk_ids.push(chunk.get_id());
@@ -2495,6 +2547,7 @@ mod tests {
                         (0, (None, Some(mid.clone())), (None, Some(mid.clone()))),
                         (0, (Some(mid.clone()), None), (Some(mid.clone()), None)),
                     ],
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2615,6 +2668,8 @@ mod tests {
                     None,
                     false,
                     None,
+                    None,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -2719,8 +2774,12 @@ impl ChunkStore {
                             .collect(),
                     )?;
                     let rows = converter.convert_columns(sort_key)?;
-                    remaining_rows
-                        .sort_unstable_by(|a, b| rows.row(*a as usize).cmp(&rows.row(*b as usize)));
+                    // Rows with equal keys keep their input order for `LAST_VALUE` aggregations.
+                    remaining_rows.sort_unstable_by(|a, b| {
+                        rows.row(*a as usize)
+                            .cmp(&rows.row(*b as usize))
+                            .then(a.cmp(b))
+                    });
                     Ok((columns, remaining_rows))
                 })
                 .await??;
@@ -2990,6 +3049,18 @@ impl ChunkStore {
         // The table is the same for every index/chunk produced by this call, so load it once
         // here instead of re-fetching it per chunk over the metastore RPC downstream.
         let table = self.meta_store.get_table_by_id(table_id).await?;
+        // Every ingested row is counted once by `COUNT` aggregations, merges sum the counts up.
+        for aggr_col in table.get_row().aggregate_columns() {
+            if aggr_col.function() != &AggregateFunction::COUNT {
+                continue;
+            }
+            if let Some(column) = columns
+                .iter()
+                .find(|c| c.get_name() == aggr_col.column().get_name())
+            {
+                rows[column.get_index()] = count_column(rows[column.get_index()].as_ref());
+            }
+        }
         // When batching is enabled, fetch the active partitions of all indexes in one RPC
         // instead of one per index inside partition_rows_for_index. The result is positionally
         // aligned with `indexes` (result[i] holds index indexes[i]'s active partitions).
@@ -3072,6 +3143,13 @@ fn min_max_values_from_data(data: &[ArrayRef], key_size: usize) -> (Option<Row>,
     }
 }
 
+/// 1 for every non-null value and 0 for nulls.
+fn count_column(column: &dyn Array) -> ArrayRef {
+    Arc::new(Int64Array::from_iter_values(
+        (0..column.len()).map(|i| column.is_valid(i) as i64),
+    ))
+}
+
 fn remap_columns(
     table_id: u64,
     old: &[ArrayRef],
@@ -0,0 +1,805 @@
+use crate::metastore::ColumnType;
+use crate::CubeError;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use json::number::Number;
+use json::object::Object;
+use json::JsonValue;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AvroType {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record {
+        name: String,
+        fields: Vec<AvroField>,
+    },
+    Enum {
+        name: String,
+        symbols: Vec<String>,
+    },
+    Array(Box<AvroType>),
+    Map(Box<AvroType>),
+    Union(Vec<AvroType>),
+    Fixed {
+        name: String,
+        size: usize,
+    },
+    /// Reference to a record, enum or fixed type by its full name.
+    Named(String),
+    /// Unscaled value is stored in `bytes` or in `fixed` of the given size.
+    Decimal {
+        precision: u16,
+        scale: u16,
+        fixed_size: Option<usize>,
+    },
+    Uuid,
+    Date,
+    TimeMillis,
+    TimeMicros,
+    TimestampMillis,
+    TimestampMicros,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvroField {
+    pub name: String,
+    pub field_type: AvroType,
+}
+
+/// Avro schema as returned by the schema registry. Values are decoded into json, so kafka rows in
+/// all formats share `parse_json_payload_and_key`.
+#[derive(Clone, Debug)]
+pub struct AvroSchema {
+    root: AvroType,
+    named: HashMap<String, AvroType>,
+}
+
+impl AvroSchema {
+    pub fn parse(schema: &str) -> Result<Self, CubeError> {
+        Self::parse_with_references(schema, &[])
+    }
+
+    /// `references` are schemas defining named types used by the schema, e.g. schema registry
+    /// references. Schemas go before the ones that use their types.
+    pub fn parse_with_references(schema: &str, references: &[String]) -> Result<Self, CubeError> {
+        let mut named = HashMap::new();
+        for reference in references {
+            parse_type(&parse_json(reference)?, None, &mut named)?;
+        }
+        let root = parse_type(&parse_json(schema)?, None, &mut named)?;
+        Ok(Self { root, named })
+    }
+
+    pub fn root(&self) -> &AvroType {
+        &self.root
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<JsonValue, CubeError> {
+        let mut reader = AvroReader { data };
+        let value = self.decode_type(&self.root, &mut reader)?;
+        if !reader.data.is_empty() {
+            return Err(CubeError::user(format!(
+                "Avro message has {} trailing bytes",
+                reader.data.len()
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Column type the values of the given avro type are loaded as, `None` if values of the type
+    /// can't be loaded into a single column type.
+    pub fn column_type(&self, avro_type: &AvroType) -> Option<ColumnType> {
+        match avro_type {
+            AvroType::Null => None,
+            AvroType::Boolean => Some(ColumnType::Boolean),
+            AvroType::Int | AvroType::Long => Some(ColumnType::Int),
+            AvroType::Float | AvroType::Double => Some(ColumnType::Float),
+            AvroType::String | AvroType::Enum { .. } | AvroType::Uuid => Some(ColumnType::String),
+            AvroType::Bytes | AvroType::Fixed { .. } => Some(ColumnType::Bytes),
+            AvroType::Record { .. } | AvroType::Map(_) => Some(ColumnType::Json),
+            AvroType::Array(items) => self
+                .column_type(items)
+                .filter(|t| t.is_list_element())
+                .map(|t| ColumnType::List(Box::new(t)))
+                .or(Some(ColumnType::Json)),
+            AvroType::Union(branches) => {
+                let mut non_null = branches.iter().filter(|b| **b != AvroType::Null);
+                match (non_null.next(), non_null.next()) {
+                    (Some(branch), None) => self.column_type(branch),
+                    _ => None,
+                }
+            }
+            AvroType::Named(name) => self.named.get(name).and_then(|t| self.column_type(t)),
+            AvroType::Decimal {
+                precision, scale, ..
+            } => Some(ColumnType::Decimal {
+                scale: *scale as i32,
+                precision: *precision as i32,
+            }),
+            AvroType::Date => Some(ColumnType::Date),
+            // Time of day, milliseconds or microseconds after midnight
+            AvroType::TimeMillis | AvroType::TimeMicros => Some(ColumnType::Int),
+            AvroType::TimestampMillis | AvroType::TimestampMicros => Some(ColumnType::Timestamp),
+        }
+    }
+
+    /// Type of the field in the top level record.
+    pub fn field_type(&self, name: &str) -> Option<&AvroType> {
+        match self.resolve(&self.root) {
+            AvroType::Record { fields, .. } => fields
+                .iter()
+                .find(|f| f.name == name)
+                .map(|f| &f.field_type),
+            _ => None,
+        }
+    }
+
+    fn resolve<'a>(&'a self, avro_type: &'a AvroType) -> &'a AvroType {
+        match avro_type {
+            AvroType::Named(name) => self.named.get(name).unwrap_or(avro_type),
+            t => t,
+        }
+    }
+
+    fn decode_type(
+        &self,
+        avro_type: &AvroType,
+        reader: &mut AvroReader,
+    ) -> Result<JsonValue, CubeError> {
+        Ok(match avro_type {
+            AvroType::Null => JsonValue::Null,
+            AvroType::Boolean => JsonValue::Boolean(reader.read_fixed(1)?[0] != 0),
+            AvroType::Int
+            | AvroType::Long
+            | AvroType::Date
+            | AvroType::TimeMillis
+            | AvroType::TimeMicros => JsonValue::from(reader.read_long()?),
+            AvroType::Float => {
+                let bytes = reader.read_fixed(4)?;
+                JsonValue::from(f32::from_le_bytes(bytes.try_into().unwrap()) as f64)
+            }
+            AvroType::Double => {
+                let bytes = reader.read_fixed(8)?;
+                JsonValue::from(f64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            AvroType::Bytes => JsonValue::String(base64::encode(reader.read_bytes()?)),
+            AvroType::Fixed { size, .. } => {
+                JsonValue::String(base64::encode(reader.read_fixed(*size)?))
+            }
+            AvroType::String | AvroType::Uuid => JsonValue::String(reader.read_string()?),
+            AvroType::Record { fields, .. } => {
+                let mut object = Object::with_capacity(fields.len());
+                for field in fields.iter() {
+                    object.insert(&field.name, self.decode_type(&field.field_type, reader)?);
+                }
+                JsonValue::Object(object)
+            }
+            AvroType::Enum { name, symbols } => {
+                let index = reader.read_long()?;
+                let symbol = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| symbols.get(i))
+                    .ok_or_else(|| {
+                        CubeError::user(format!("Invalid index {} of avro enum {}", index, name))
+                    })?;
+                JsonValue::String(symbol.to_string())
+            }
+            AvroType::Array(items) => {
+                let mut values = Vec::new();
+                while let Some(count) = reader.read_block_count()? {
+                    for _ in 0..count {
+                        values.push(self.decode_type(items, reader)?);
+                    }
+                }
+                JsonValue::Array(values)
+            }
+            AvroType::Map(values) => {
+                let mut object = Object::new();
+                while let Some(count) = reader.read_block_count()? {
+                    for _ in 0..count {
+                        let key = reader.read_string()?;
+                        object.insert(&key, self.decode_type(values, reader)?);
+                    }
+                }
+                JsonValue::Object(object)
+            }
+            AvroType::Union(branches) => {
+                let index = reader.read_long()?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| branches.get(i))
+                    .ok_or_else(|| {
+                        CubeError::user(format!("Invalid avro union branch index {}", index))
+                    })?;
+                self.decode_type(branch, reader)?
+            }
+            AvroType::Named(name) => {
+                let named = self.named.get(name).ok_or_else(|| {
+                    CubeError::internal(format!("Avro type {} is not defined", name))
+                })?;
+                self.decode_type(named, reader)?
+            }
+            AvroType::Decimal {
+                scale, fixed_size, ..
+            } => {
+                let bytes = match fixed_size {
+                    Some(size) => reader.read_fixed(*size)?,
+                    None => reader.read_bytes()?,
+                };
+                decimal_to_json(bytes, *scale)?
+            }
+            AvroType::TimestampMillis => timestamp_to_json(reader.read_long()?, 1_000_000)?,
+            AvroType::TimestampMicros => timestamp_to_json(reader.read_long()?, 1_000)?,
+        })
+    }
+}
+
+fn parse_json(schema: &str) -> Result<Value, CubeError> {
+    serde_json::from_str(schema)
+        .map_err(|e| CubeError::user(format!("Can't parse avro schema: {}", e)))
+}
+
+fn parse_type(
+    value: &Value,
+    namespace: Option<&str>,
+    named: &mut HashMap<String, AvroType>,
+) -> Result<AvroType, CubeError> {
+    match value {
+        Value::String(name) => parse_type_name(name, namespace, named),
+        Value::Array(branches) => Ok(AvroType::Union(
+            branches
+                .iter()
+                .map(|b| parse_type(b, namespace, named))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Value::Object(object) => {
+            let type_value = object
+                .get("type")
+                .ok_or_else(|| CubeError::user(format!("Avro type is missing in {}", value)))?;
+            let type_name = match type_value {
+                Value::String(type_name) => type_name.as_str(),
+                // Wrapped type, e.g. {"type": {"type": "array", ...}}
+                _ => return parse_type(type_value, namespace, named),
+            };
+            if let Some(logical_type) = parse_logical_type(object, type_name, namespace, named)? {
+                return Ok(logical_type);
+            }
+
+            match type_name {
+                "record" | "error" => {
+                    let (name, namespace) = full_name(object, namespace)?;
+                    // Registered before the fields, so recursive references can be resolved
+                    named.insert(name.clone(), AvroType::Null);
+                    let fields = object
+                        .get("fields")
+                        .and_then(|f| f.as_array())
+                        .ok_or_else(|| {
+                            CubeError::user(format!("Fields are missing in avro record {}", name))
+                        })?
+                        .iter()
+                        .map(|field| {
+                            Ok(AvroField {
+                                name: string_attr(field, "name")?.to_string(),
+                                field_type: parse_type(
+                                    field.get("type").ok_or_else(|| {
+                                        CubeError::user(format!(
+                                            "Type is missing for field in avro record {}",
+                                            name
+                                        ))
+                                    })?,
+                                    namespace.as_deref(),
+                                    named,
+                                )?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, CubeError>>()?;
+                    let record = AvroType::Record {
+                        name: name.clone(),
+                        fields,
+                    };
+                    named.insert(name.clone(), record);
+                    Ok(AvroType::Named(name))
+                }
+                "enum" => {
+                    let (name, _) = full_name(object, namespace)?;
+                    let symbols = object
+                        .get("symbols")
+                        .and_then(|s| s.as_array())
+                        .ok_or_else(|| {
+                            CubeError::user(format!("Symbols are missing in avro enum {}", name))
+                        })?
+                        .iter()
+                        .map(|s| {
+                            s.as_str().map(|s| s.to_string()).ok_or_else(|| {
+                                CubeError::user(format!("Invalid symbol in avro enum {}", name))
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    named.insert(
+                        name.clone(),
+                        AvroType::Enum {
+                            name: name.clone(),
+                            symbols,
+                        },
+                    );
+                    Ok(AvroType::Named(name))
+                }
+                "fixed" => {
+                    let (name, _) = full_name(object, namespace)?;
+                    let size = size_attr(object, &name)?;
+                    named.insert(
+                        name.clone(),
+                        AvroType::Fixed {
+                            name: name.clone(),
+                            size,
+                        },
+                    );
+                    Ok(AvroType::Named(name))
+                }
+                "array" => Ok(AvroType::Array(Box::new(parse_type(
+                    object.get("items").ok_or_else(|| {
+                        CubeError::user("Items are missing in avro array".to_string())
+                    })?,
+                    namespace,
+                    named,
+                )?))),
+                "map" => Ok(AvroType::Map(Box::new(parse_type(
+                    object.get("values").ok_or_else(|| {
+                        CubeError::user("Values are missing in avro map".to_string())
+                    })?,
+                    namespace,
+                    named,
+                )?))),
+                name => parse_type_name(name, namespace, named),
+            }
+        }
+        x => Err(CubeError::user(format!("Unexpected avro type: {}", x))),
+    }
+}
+
+/// Unknown logical types and logical types with a wrong underlying type are ignored, as the
+/// specification requires.
+fn parse_logical_type(
+    object: &Map<String, Value>,
+    type_name: &str,
+    namespace: Option<&str>,
+    named: &mut HashMap<String, AvroType>,
+) -> Result<Option<AvroType>, CubeError> {
+    let logical_type = match object.get("logicalType").and_then(|l| l.as_str()) {
+        Some(logical_type) => logical_type,
+        None => return Ok(None),
+    };
+
+    Ok(match (logical_type, type_name) {
+        ("decimal", "bytes") | ("decimal", "fixed") => {
+            let precision = object.get("precision").and_then(|p| p.as_u64());
+            let scale = object.get("scale").and_then(|p| p.as_u64()).unwrap_or(0);
+            let precision = match precision {
+                Some(precision) if scale <= precision && precision <= u16::MAX as u64 => {
+                    precision as u16
+                }
+                _ => return Ok(None),
+            };
+            let fixed_size = if type_name == "fixed" {
+                let (name, _) = full_name(object, namespace)?;
+                let size = size_attr(object, &name)?;
+                named.insert(name.clone(), AvroType::Fixed { name, size });
+                Some(size)
+            } else {
+                None
+            };
+            Some(AvroType::Decimal {
+                precision,
+                scale: scale as u16,
+                fixed_size,
+            })
+        }
+        ("uuid", "string") => Some(AvroType::Uuid),
+        ("date", "int") => Some(AvroType::Date),
+        ("time-millis", "int") => Some(AvroType::TimeMillis),
+        ("time-micros", "long") => Some(AvroType::TimeMicros),
+        // Local timestamps have no time zone, they are loaded as UTC ones
+        ("timestamp-millis", "long") | ("local-timestamp-millis", "long") => {
+            Some(AvroType::TimestampMillis)
+        }
+        ("timestamp-micros", "long") | ("local-timestamp-micros", "long") => {
+            Some(AvroType::TimestampMicros)
+        }
+        _ => None,
+    })
+}
+
+fn parse_type_name(
+    name: &str,
+    namespace: Option<&str>,
+    named: &HashMap<String, AvroType>,
+) -> Result<AvroType, CubeError> {
+    Ok(match name {
+        "null" => AvroType::Null,
+        "boolean" => AvroType::Boolean,
+        "int" => AvroType::Int,
+        "long" => AvroType::Long,
+        "float" => AvroType::Float,
+        "double" => AvroType::Double,
+        "bytes" => AvroType::Bytes,
+        "string" => AvroType::String,
+        name => {
+            let qualified = namespace
+                .filter(|_| !name.contains('.'))
+                .map(|ns| format!("{}.{}", ns, name));
+            match qualified {
+                Some(qualified) if named.contains_key(&qualified) => AvroType::Named(qualified),
+                _ if named.contains_key(name) => AvroType::Named(name.to_string()),
+                _ => return Err(CubeError::user(format!("Unknown avro type: {}", name))),
+            }
+        }
+    })
+}
+
+fn full_name(
+    object: &Map<String, Value>,
+    namespace: Option<&str>,
+) -> Result<(String, Option<String>), CubeError> {
+    let name = object
+        .get("name")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| CubeError::user("Name is missing in avro named type".to_string()))?;
+    if let Some(pos) = name.rfind('.') {
+        return Ok((name.to_string(), Some(name[0..pos].to_string())));
+    }
+    let namespace = object
+        .get("namespace")
+        .and_then(|n| n.as_str())
+        .or(namespace)
+        .filter(|n| !n.is_empty());
+    Ok(match namespace {
+        Some(namespace) => (
+            format!("{}.{}", namespace, name),
+            Some(namespace.to_string()),
+        ),
+        None => (name.to_string(), None),
+    })
+}
+
+fn string_attr<'a>(value: &'a Value, attr: &str) -> Result<&'a str, CubeError> {
+    value
+        .get(attr)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CubeError::user(format!("'{}' is missing in {}", attr, value)))
+}
+
+fn size_attr(object: &Map<String, Value>, name: &str) -> Result<usize, CubeError> {
+    object
+        .get("size")
+        .and_then(|s| s.as_u64())
+        .map(|s| s as usize)
+        .ok_or_else(|| CubeError::user(format!("Size is missing in avro fixed {}", name)))
+}
+
+fn decimal_to_json(bytes: &[u8], scale: u16) -> Result<JsonValue, CubeError> {
+    if bytes.len() > 16 {
+        return Err(CubeError::user(format!(
+            "Avro decimal of {} bytes is too large",
+            bytes.len()
+        )));
+    }
+    // Two's-complement big-endian unscaled value
+    let mut unscaled: i128 = if bytes.first().map_or(false, |b| b & 0x80 != 0) {
+        -1
+    } else {
+        0
+    };
+    for b in bytes {
+        unscaled = (unscaled << 8) | *b as i128;
+    }
+    match u64::try_from(unscaled.unsigned_abs()) {
+        Ok(mantissa) => Ok(JsonValue::Number(Number::from_parts(
+            unscaled >= 0,
+            mantissa,
+            -(scale as i16),
+        ))),
+        // JSON numbers have a 64-bit mantissa, larger values are passed as decimal strings.
+        Err(_) => {
+            let scale = scale as usize;
+            let digits = format!("{:0>width$}", unscaled.unsigned_abs(), width = scale + 1);
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            let sign = if unscaled < 0 { "-" } else { "" };
+            Ok(JsonValue::String(if scale == 0 {
+                format!("{}{}", sign, int)
+            } else {
+                format!("{}{}.{}", sign, int, frac)
+            }))
+        }
+    }
+}
+
+fn timestamp_to_json(value: i64, nanos_per_unit: i64) -> Result<JsonValue, CubeError> {
+    let units_per_second = 1_000_000_000 / nanos_per_unit;
+    let timestamp = Utc
+        .timestamp_opt(
+            value.div_euclid(units_per_second),
+            (value.rem_euclid(units_per_second) * nanos_per_unit) as u32,
+        )
+        .single()
+        .ok_or_else(|| CubeError::user(format!("Avro timestamp {} is out of range", value)))?;
+    Ok(JsonValue::String(
+        timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    ))
+}
+
+struct AvroReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> AvroReader<'a> {
+    fn read_fixed(&mut self, len: usize) -> Result<&'a [u8], CubeError> {
+        if self.data.len() < len {
+            return Err(CubeError::user(
+                "Unexpected end of avro message".to_string(),
+            ));
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    /// Variable-length zig-zag encoded long, ints are encoded the same way.
+    fn read_long(&mut self) -> Result<i64, CubeError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_fixed(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(CubeError::user("Invalid avro varint".to_string()))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], CubeError> {
+        let len = self.read_long()?;
+        let len = usize::try_from(len)
+            .map_err(|_| CubeError::user(format!("Invalid avro bytes length {}", len)))?;
+        self.read_fixed(len)
+    }
+
+    fn read_string(&mut self) -> Result<String, CubeError> {
+        let bytes = self.read_bytes()?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| CubeError::user(format!("Invalid avro string: {}", e)))
+    }
+
+    /// Arrays and maps are encoded as blocks, terminated by an empty one. A negative count is
+    /// followed by the size of the block in bytes. Counts are bounded by the bytes left, so a
+    /// corrupted message can't make the decoder loop over a huge block of empty items.
+    fn read_block_count(&mut self) -> Result<Option<u64>, CubeError> {
+        let count = self.read_long()?;
+        if count == 0 {
+            return Ok(None);
+        }
+        if count < 0 {
+            self.read_long()?;
+        }
+        let count = count.unsigned_abs();
+        if count > self.data.len() as u64 {
+            return Err(CubeError::user(format!(
+                "Invalid avro block count {}, only {} bytes left",
+                count,
+                self.data.len()
+            )));
+        }
+        Ok(Some(count))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn encode_long(value: i64, out: &mut Vec<u8>) {
+        let mut v = ((value << 1) ^ (value >> 63)) as u64;
+        loop {
+            if v < 0x80 {
+                out.push(v as u8);
+                return;
+            }
+            out.push((v as u8 & 0x7f) | 0x80);
+            v >>= 7;
+        }
+    }
+
+    pub fn encode_string(value: &str, out: &mut Vec<u8>) {
+        encode_long(value.len() as i64, out);
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    const EVENT_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Event",
+        "namespace": "com.example",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "name", "type": ["null", "string"]},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["CLICK", "VIEW"]}},
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}},
+            {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+            {"name": "ratio", "type": "double"},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "parent", "type": ["null", "Event"]}
+        ]
+    }"#;
+
+    fn encode_event(id: i64, name: Option<&str>, parent: Option<i64>, out: &mut Vec<u8>) {
+        encode_long(id, out);
+        match name {
+            Some(name) => {
+                encode_long(1, out);
+                encode_string(name, out);
+            }
+            None => encode_long(0, out),
+        }
+        encode_long(1, out);
+        // -12.34
+        encode_long(2, out);
+        out.extend_from_slice(&(-1234i16).to_be_bytes());
+        encode_long(19513, out);
+        encode_long(1_685_934_000_123_456, out);
+        out.extend_from_slice(&0.5f64.to_le_bytes());
+        encode_long(2, out);
+        encode_string("a", out);
+        encode_string("b", out);
+        encode_long(0, out);
+        match parent {
+            Some(parent) => {
+                encode_long(1, out);
+                encode_event(parent, None, None, out);
+            }
+            None => encode_long(0, out),
+        }
+    }
+
+    #[test]
+    fn decode_record() {
+        let schema = AvroSchema::parse(EVENT_SCHEMA).unwrap();
+        let mut data = Vec::new();
+        encode_event(42, Some("foo"), Some(7), &mut data);
+
+        let value = schema.decode(&data).unwrap();
+        assert_eq!(value["id"], 42);
+        assert_eq!(value["name"], "foo");
+        assert_eq!(value["kind"], "VIEW");
+        assert_eq!(value["amount"].to_string(), "-12.34");
+        assert_eq!(value["day"], 19513);
+        assert_eq!(value["at"], "2023-06-05T03:00:00.123456Z");
+        assert_eq!(value["ratio"], 0.5);
+        assert_eq!(value["tags"], json::array!["a", "b"]);
+        assert_eq!(value["parent"]["id"], 7);
+        assert!(value["parent"]["name"].is_null());
+        assert!(value["parent"]["parent"].is_null());
+
+        assert!(schema.decode(&data[0..data.len() - 1]).is_err());
+        data.push(0);
+        assert!(schema.decode(&data).is_err());
+    }
+
+    #[test]
+    fn decode_limits() {
+        let schema = AvroSchema::parse(
+            r#"{
+                "type": "record",
+                "name": "Limits",
+                "fields": [
+                    {"name": "amount", "type": {"type": "fixed", "name": "Amount", "size": 16, "logicalType": "decimal", "precision": 38, "scale": 2}},
+                    {"name": "values", "type": {"type": "array", "items": "long"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let encode = |amount: i128, count: i64| {
+            let mut data = amount.to_be_bytes().to_vec();
+            encode_long(count, &mut data);
+            if count != 0 {
+                for i in 0..count.min(2) {
+                    encode_long(i, &mut data);
+                }
+                encode_long(0, &mut data);
+            }
+            data
+        };
+
+        let value = schema
+            .decode(&encode(-1234567890123456789012345, 2))
+            .unwrap();
+        assert_eq!(value["amount"], "-12345678901234567890123.45");
+        assert_eq!(value["values"], json::array![0, 1]);
+        let value = schema.decode(&encode(5, 0)).unwrap();
+        assert_eq!(value["amount"].to_string(), "0.05");
+
+        let err = schema.decode(&encode(5, i64::MAX)).unwrap_err();
+        assert!(err.message.contains("Invalid avro block count"), "{}", err);
+    }
+
+    #[test]
+    fn column_types() {
+        let schema = AvroSchema::parse(EVENT_SCHEMA).unwrap();
+        let column_type = |name: &str| schema.column_type(schema.field_type(name).unwrap());
+
+        assert_eq!(column_type("id"), Some(ColumnType::Int));
+        assert_eq!(column_type("name"), Some(ColumnType::String));
+        assert_eq!(column_type("kind"), Some(ColumnType::String));
+        assert_eq!(
+            column_type("amount"),
+            Some(ColumnType::Decimal {
+                scale: 2,
+                precision: 10
+            })
+        );
+        assert_eq!(column_type("day"), Some(ColumnType::Date));
+        assert_eq!(column_type("at"), Some(ColumnType::Timestamp));
+        assert_eq!(column_type("ratio"), Some(ColumnType::Float));
+        assert_eq!(
+            column_type("tags"),
+            Some(ColumnType::List(Box::new(ColumnType::String)))
+        );
+        assert_eq!(column_type("parent"), Some(ColumnType::Json));
+        assert!(schema.field_type("missing").is_none());
+    }
+
+    #[test]
+    fn parse_with_references() {
+        let address = r#"{
+            "type": "record",
+            "name": "Address",
+            "namespace": "com.example",
+            "fields": [{"name": "city", "type": "string"}]
+        }"#;
+        let schema = r#"{
+            "type": "record",
+            "name": "User",
+            "namespace": "com.example",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "address", "type": "Address"}
+            ]
+        }"#;
+        assert!(AvroSchema::parse(schema).is_err());
+
+        let schema = AvroSchema::parse_with_references(schema, &[address.to_string()]).unwrap();
+        let mut data = Vec::new();
+        encode_long(1, &mut data);
+        encode_string("Berlin", &mut data);
+        let value = schema.decode(&data).unwrap();
+        assert_eq!(value["id"], 1);
+        assert_eq!(value["address"]["city"], "Berlin");
+        assert_eq!(
+            schema.column_type(schema.field_type("address").unwrap()),
+            Some(ColumnType::Json)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(AvroSchema::parse("not json").is_err());
+        assert!(AvroSchema::parse(r#"{"type": "record", "name": "A"}"#).is_err());
+        assert!(AvroSchema::parse(
+            r#"{"type": "record", "name": "A", "fields": [{"name": "b", "type": "B"}]}"#
+        )
+        .is_err());
+        // Unknown logical types fall back to the underlying type
+        assert_eq!(
+            AvroSchema::parse(r#"{"type": "string", "logicalType": "custom"}"#)
+                .unwrap()
+                .root(),
+            &AvroType::String
+        );
+    }
+}
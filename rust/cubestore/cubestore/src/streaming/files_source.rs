@@ -0,0 +1,534 @@
+use crate::metastore::table::StreamOffset;
+use crate::metastore::Column;
+use crate::remotefs::{RemoteFile, RemoteFs};
+use crate::streaming::traffic_sender::TrafficSender;
+use crate::streaming::{parse_json_payload_and_key, StreamingSource};
+use crate::table::{Row, TableValue};
+use crate::CubeError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::cube_ext;
+use futures::Stream;
+use futures_util::stream;
+use itertools::Itertools;
+use json::object::Object;
+use json::JsonValue;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Seq of a row is `file_base << LINE_BITS | line`. The base of a file is assigned when the file is
+/// consumed: its upload time in milliseconds since `BASE_EPOCH_MILLIS`, raised above the base of
+/// the previously consumed file, so seqs grow in the consumption order.
+const LINE_BITS: u32 = 22;
+/// 2020-01-01, keeps bases of the upload times within 41 bits for the next decades.
+const BASE_EPOCH_MILLIS: i64 = 1_577_836_800_000;
+
+/// Tails NDJSON files uploaded under the prefix of the remote fs. Files are consumed in the order
+/// their listed size and upload time stop changing, only newline-terminated lines are consumed.
+/// Bases of the consumed files are persisted under the state prefix: after a restart files past
+/// the last ingested position are read again with the same seqs. Files removed from the prefix are
+/// pruned from the state.
+#[derive(Clone)]
+pub struct FilesStreamingSource {
+    remote_fs: Arc<dyn RemoteFs>,
+    prefix: String,
+    state_prefix: String,
+    poll_interval: Duration,
+    offset: Option<StreamOffset>,
+    columns: Vec<Column>,
+    unique_key_columns: Vec<Column>,
+    seq_column_index: usize,
+    select_statement: Option<String>,
+    trace_obj: Option<String>,
+}
+
+/// Consumed files of the source. Every save writes a new file named by the version and removes the
+/// previous one, so a stale local copy of the state is never read.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct FilesState {
+    version: u64,
+    /// Base assigned last, kept when its file is pruned.
+    last_base: Option<i64>,
+    files: HashMap<String, ConsumedFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ConsumedFile {
+    /// `None` for files skipped by the `LATEST` offset.
+    base: Option<i64>,
+    size: u64,
+}
+
+impl FilesStreamingSource {
+    pub fn new(
+        remote_fs: Arc<dyn RemoteFs>,
+        prefix: String,
+        state_prefix: String,
+        poll_interval: Duration,
+        offset: Option<StreamOffset>,
+        columns: Vec<Column>,
+        unique_key_columns: Vec<Column>,
+        seq_column_index: usize,
+        select_statement: Option<String>,
+        trace_obj: Option<String>,
+    ) -> Self {
+        Self {
+            remote_fs,
+            prefix,
+            state_prefix,
+            poll_interval,
+            offset,
+            columns,
+            unique_key_columns,
+            seq_column_index,
+            select_statement,
+            trace_obj,
+        }
+    }
+
+    /// Files under the prefix in the order of their upload times.
+    async fn list_files(&self) -> Result<Vec<RemoteFile>, CubeError> {
+        let mut files = self
+            .remote_fs
+            .list_with_metadata(self.prefix.clone())
+            .await?;
+        files.sort_by(|a, b| (a.updated, &a.remote_path).cmp(&(b.updated, &b.remote_path)));
+        Ok(files)
+    }
+
+    fn state_path(&self, version: u64) -> String {
+        format!("{}{:020}.json", self.state_prefix, version)
+    }
+
+    async fn load_state(&self) -> Result<Option<FilesState>, CubeError> {
+        let path = match self
+            .remote_fs
+            .list(self.state_prefix.clone())
+            .await?
+            .into_iter()
+            .max()
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let local_path = self.remote_fs.download_file(path.clone(), None).await?;
+        let state = serde_json::from_slice(&tokio::fs::read(&local_path).await?).map_err(|e| {
+            CubeError::internal(format!(
+                "Unable to read files source state '{}': {}",
+                path, e
+            ))
+        })?;
+        Ok(Some(state))
+    }
+
+    async fn save_state(&self, state: &mut FilesState) -> Result<(), CubeError> {
+        let previous = self.state_path(state.version);
+        state.version += 1;
+        let path = self.state_path(state.version);
+        let temp_path = self.remote_fs.temp_upload_path(path.clone()).await?;
+        tokio::fs::write(&temp_path, serde_json::to_vec(state)?).await?;
+        self.remote_fs.upload_file(temp_path, path).await?;
+        if state.version > 1 {
+            if let Err(e) = self.remote_fs.delete_file(previous.clone()).await {
+                warn!(
+                    "Unable to remove previous files source state '{}': {}",
+                    previous, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads newline-terminated lines of the file, the unterminated tail is left out.
+    async fn read_file(
+        &self,
+        base: i64,
+        file: &RemoteFile,
+        initial_seq_value: Option<i64>,
+    ) -> Result<Vec<Row>, CubeError> {
+        let local_path = self
+            .remote_fs
+            .download_file(file.remote_path.clone(), Some(file.file_size))
+            .await?;
+        let remote_path = file.remote_path.clone();
+        let columns = self.columns.clone();
+        let unique_key_columns = self.unique_key_columns.clone();
+        let seq_column_index = self.seq_column_index;
+        cube_ext::spawn_blocking(move || -> Result<Vec<Row>, CubeError> {
+            let mut reader = BufReader::new(File::open(&local_path)?);
+            let mut rows = Vec::new();
+            let mut buf = Vec::new();
+            for line_number in 0usize.. {
+                buf.clear();
+                if reader.read_until(b'\n', &mut buf)? == 0 {
+                    break;
+                }
+                if buf.last() != Some(&b'\n') {
+                    warn!(
+                        "Last line of '{}' isn't terminated by a newline and is skipped",
+                        remote_path
+                    );
+                    break;
+                }
+                if line_number >= 1 << LINE_BITS {
+                    return Err(CubeError::user(format!(
+                        "File '{}' has more than {} lines",
+                        remote_path,
+                        1 << LINE_BITS
+                    )));
+                }
+                let parse_error = |e: String| {
+                    CubeError::user(format!(
+                        "Can't parse line {} of '{}': {}",
+                        line_number + 1,
+                        remote_path,
+                        e
+                    ))
+                };
+                let line = std::str::from_utf8(&buf)
+                    .map_err(|e| parse_error(e.to_string()))?
+                    .trim_end();
+                let seq = (base << LINE_BITS) | line_number as i64;
+                if line.trim().is_empty() || initial_seq_value.map_or(false, |s| seq < s) {
+                    continue;
+                }
+                let payload = json::parse(line).map_err(|e| parse_error(e.to_string()))?;
+                let mut values = parse_json_payload_and_key(
+                    &columns,
+                    &unique_key_columns,
+                    payload,
+                    &JsonValue::Object(Object::new()),
+                )
+                .map_err(|e| parse_error(e.to_string()))?;
+                values[seq_column_index] = TableValue::Int(seq);
+                rows.push(Row::new(values));
+            }
+            Ok(rows)
+        })
+        .await?
+    }
+}
+
+struct FilesTail {
+    source: FilesStreamingSource,
+    traffic_sender: Arc<TrafficSender>,
+    initial_seq_value: Option<i64>,
+    /// `None` until the first listing.
+    state: Option<FilesState>,
+    /// Files consumed before the restart and not ingested up to the end, in the order of bases.
+    replay: VecDeque<(String, i64)>,
+    /// Sizes and upload times of the files seen by the previous listing and not consumed yet.
+    /// Copies to the remote fs aren't atomic, so a file is read once it's listed unchanged twice.
+    pending: HashMap<String, (u64, DateTime<Utc>)>,
+    rows: VecDeque<Row>,
+}
+
+impl FilesTail {
+    /// Loads the persisted state, files past the initial position are queued to be read again.
+    async fn load_state(&mut self, files: &[RemoteFile]) -> Result<FilesState, CubeError> {
+        let initial_base = self.initial_seq_value.map(|initial| initial >> LINE_BITS);
+        if let Some(state) = self.source.load_state().await? {
+            self.replay = state
+                .files
+                .iter()
+                .filter_map(|(path, file)| Some((path.clone(), file.base?)))
+                .filter(|(_, base)| initial_base.map_or(true, |initial| *base >= initial))
+                .sorted_by_key(|(_, base)| *base)
+                .collect();
+            return Ok(state);
+        }
+
+        let mut state = FilesState {
+            last_base: initial_base,
+            ..FilesState::default()
+        };
+        // Without the state only the `EARLIEST` offset reads files uploaded before the start
+        if initial_base.is_none() && !matches!(self.source.offset, Some(StreamOffset::Earliest)) {
+            for file in files {
+                state.files.insert(
+                    file.remote_path.clone(),
+                    ConsumedFile {
+                        base: None,
+                        size: file.file_size,
+                    },
+                );
+            }
+            if !state.files.is_empty() {
+                self.source.save_state(&mut state).await?;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Reads the next file or waits for the poll interval if there are no new files.
+    async fn next_file(&mut self) -> Result<(), CubeError> {
+        let files = self.source.list_files().await?;
+        if self.state.is_none() {
+            let state = self.load_state(&files).await?;
+            self.state = Some(state);
+        }
+        let state = self.state.as_mut().unwrap();
+
+        let next = if let Some((path, base)) = self.replay.pop_front() {
+            match files.iter().find(|f| f.remote_path == path) {
+                Some(file) => Some((base, file.clone())),
+                None => {
+                    warn!("File '{}' was removed before it was ingested", path);
+                    return Ok(());
+                }
+            }
+        } else {
+            let mut pending = HashMap::new();
+            let mut next = None;
+            for file in files.iter() {
+                if let Some(consumed) = state.files.get_mut(&file.remote_path) {
+                    if consumed.size != file.file_size {
+                        warn!(
+                            "File '{}' has changed after it was consumed, changes are ignored",
+                            file.remote_path
+                        );
+                        consumed.size = file.file_size;
+                    }
+                    continue;
+                }
+                let listed = (file.file_size, file.updated);
+                if next.is_none() && self.pending.get(&file.remote_path) == Some(&listed) {
+                    next = Some(file.clone());
+                } else {
+                    pending.insert(file.remote_path.clone(), listed);
+                }
+            }
+            self.pending = pending;
+
+            match next {
+                Some(file) => {
+                    let updated = (file.updated.timestamp_millis() - BASE_EPOCH_MILLIS).max(0);
+                    let base = state
+                        .last_base
+                        .map_or(updated, |last| updated.max(last + 1));
+                    state.last_base = Some(base);
+                    state.files.insert(
+                        file.remote_path.clone(),
+                        ConsumedFile {
+                            base: Some(base),
+                            size: file.file_size,
+                        },
+                    );
+                    let listed = files
+                        .iter()
+                        .map(|f| f.remote_path.as_str())
+                        .collect::<HashSet<_>>();
+                    state.files.retain(|path, _| listed.contains(path.as_str()));
+                    // The base is persisted before the rows are ingested, so they keep their seqs
+                    self.source.save_state(state).await?;
+                    Some((base, file))
+                }
+                None => None,
+            }
+        };
+
+        match next {
+            Some((base, file)) => {
+                self.traffic_sender.process_event(file.file_size)?;
+                // Only the file at the initial position can be ingested partially
+                let initial_seq_value = self
+                    .initial_seq_value
+                    .filter(|initial| initial >> LINE_BITS == base);
+                let rows = self
+                    .source
+                    .read_file(base, &file, initial_seq_value)
+                    .await?;
+                self.rows.extend(rows);
+            }
+            None => {
+                tokio::time::sleep(self.source.poll_interval).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StreamingSource for FilesStreamingSource {
+    async fn row_stream(
+        &self,
+        _columns: Vec<Column>,
+        _seq_column: Column,
+        initial_seq_value: Option<i64>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
+        let tail = FilesTail {
+            source: self.clone(),
+            traffic_sender: TrafficSender::new(self.trace_obj.clone()),
+            initial_seq_value,
+            state: None,
+            replay: VecDeque::new(),
+            pending: HashMap::new(),
+            rows: VecDeque::new(),
+        };
+        Ok(Box::pin(stream::unfold(tail, |mut tail| async move {
+            loop {
+                if let Some(row) = tail.rows.pop_front() {
+                    return Some((Ok(row), tail));
+                }
+                if let Err(e) = tail.next_file().await {
+                    return Some((Err(e), tail));
+                }
+            }
+        })))
+    }
+
+    fn source_columns(&self) -> &Vec<Column> {
+        &self.columns
+    }
+
+    fn source_seq_column_index(&self) -> usize {
+        self.seq_column_index
+    }
+
+    async fn validate_table_location(&self) -> Result<(), CubeError> {
+        if self.select_statement.is_some() {
+            return Err(CubeError::user(
+                "select_statement isn't supported for files sources".to_string(),
+            ));
+        }
+        self.remote_fs
+            .list_with_metadata(self.prefix.clone())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metastore::ColumnType;
+    use crate::remotefs::LocalDirRemoteFs;
+    use std::io::Write;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    fn write_file(dir: &Path, name: &str, content: &str, modified_secs_ago: u64) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(name))
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(modified_secs_ago))
+            .unwrap();
+    }
+
+    fn ids(rows: &mut VecDeque<Row>) -> Vec<(i64, i64)> {
+        rows.drain(..)
+            .map(|r| match (&r.values()[0], &r.values()[1]) {
+                (TableValue::Int(id), TableValue::Int(seq)) => (*id, *seq),
+                x => panic!("Unexpected id and seq {:?}", x),
+            })
+            .collect()
+    }
+
+    fn tail(dir: &Path, initial_seq_value: Option<i64>) -> FilesTail {
+        let columns = vec![
+            Column::new("ID".to_string(), ColumnType::Int, 0),
+            Column::new("__seq".to_string(), ColumnType::Int, 1),
+        ];
+        let source = FilesStreamingSource::new(
+            LocalDirRemoteFs::new_noop(dir.to_path_buf()),
+            "incoming/".to_string(),
+            "state/".to_string(),
+            Duration::from_millis(1),
+            Some(StreamOffset::Earliest),
+            columns.clone(),
+            vec![columns[0].clone()],
+            1,
+            None,
+            None,
+        );
+        FilesTail {
+            source,
+            traffic_sender: TrafficSender::new(None),
+            initial_seq_value,
+            state: None,
+            replay: VecDeque::new(),
+            pending: HashMap::new(),
+            rows: VecDeque::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn tail_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let incoming = dir.path().join("incoming");
+        std::fs::create_dir_all(&incoming).unwrap();
+        let mut tail = tail(dir.path(), None);
+
+        // File being copied is read once its size stops changing
+        write_file(&incoming, "b.ndjson", "{\"ID\": 3}\n{\"ID\"", 10);
+        tail.next_file().await.unwrap();
+        write_file(&incoming, "b.ndjson", ": 4}\n", 10);
+        tail.next_file().await.unwrap();
+        assert_eq!(ids(&mut tail.rows), Vec::<(i64, i64)>::new());
+        tail.next_file().await.unwrap();
+        let b = ids(&mut tail.rows);
+        assert_eq!(b.iter().map(|(id, _)| *id).collect_vec(), vec![3, 4]);
+
+        // File uploaded before the consumed one is still picked up, its seqs go after
+        write_file(&incoming, "a.ndjson", "{\"ID\": 1}\n{\"ID\": 2}\n", 20);
+        tail.next_file().await.unwrap();
+        tail.next_file().await.unwrap();
+        let a = ids(&mut tail.rows);
+        assert_eq!(a.iter().map(|(id, _)| *id).collect_vec(), vec![1, 2]);
+        assert!(a[0].1 > b[1].1);
+
+        // Unterminated line is left out
+        write_file(&incoming, "c.ndjson", "{\"ID\": 5}\n{\"ID\": 6}", 0);
+        tail.next_file().await.unwrap();
+        tail.next_file().await.unwrap();
+        let c = ids(&mut tail.rows);
+        assert_eq!(c.iter().map(|(id, _)| *id).collect_vec(), vec![5]);
+        assert!(c[0].1 > a[1].1);
+
+        // Consumed files aren't read again
+        write_file(&incoming, "c.ndjson", "\n", 0);
+        for _ in 0..3 {
+            tail.next_file().await.unwrap();
+        }
+        assert_eq!(ids(&mut tail.rows), Vec::<(i64, i64)>::new());
+
+        // After a restart files past the ingested position are read again with the same seqs
+        let mut tail = self::tail(dir.path(), Some(a[1].1));
+        for _ in 0..3 {
+            tail.next_file().await.unwrap();
+        }
+        assert_eq!(ids(&mut tail.rows), vec![a[1], c[0], (6, c[0].1 + 1)]);
+
+        // Removed files are pruned from the state
+        std::fs::remove_file(incoming.join("b.ndjson")).unwrap();
+        write_file(&incoming, "d.ndjson", "{\"ID\": 7}\n", 0);
+        tail.next_file().await.unwrap();
+        tail.next_file().await.unwrap();
+        let d = ids(&mut tail.rows);
+        assert_eq!(d.iter().map(|(id, _)| *id).collect_vec(), vec![7]);
+        assert!(d[0].1 > c[0].1);
+        let state = tail.state.as_ref().unwrap();
+        assert_eq!(
+            state.files.keys().sorted().collect_vec(),
+            vec![
+                "incoming/a.ndjson",
+                "incoming/c.ndjson",
+                "incoming/d.ndjson"
+            ]
+        );
+        let state_files = std::fs::read_dir(dir.path().join("state"))
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().is_file())
+            .count();
+        assert_eq!(state_files, 1);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::metastore::source::HttpStreamFormat;
+use crate::metastore::Column;
+use crate::streaming::traffic_sender::TrafficSender;
+use crate::streaming::{parse_json_payload_and_key, StreamingSource};
+use crate::table::{Row, TableValue};
+use crate::CubeError;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use futures_util::stream;
+use json::object::Object;
+use json::JsonValue;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Streams rows from a long-lived HTTP response. Sequence of a row is the numeric id of the SSE
+/// event or the seq column field of the row itself. On replay the last consumed seq is sent in
+/// the `Last-Event-ID` header, so the server can resume the stream.
+#[derive(Clone)]
+pub struct HttpStreamingSource {
+    user: Option<String>,
+    password: Option<String>,
+    url: String,
+    format: HttpStreamFormat,
+    columns: Vec<Column>,
+    unique_key_columns: Vec<Column>,
+    seq_column_index: usize,
+    select_statement: Option<String>,
+    trace_obj: Option<String>,
+}
+
+impl HttpStreamingSource {
+    pub fn new(
+        user: Option<String>,
+        password: Option<String>,
+        url: String,
+        format: HttpStreamFormat,
+        columns: Vec<Column>,
+        unique_key_columns: Vec<Column>,
+        seq_column_index: usize,
+        select_statement: Option<String>,
+        trace_obj: Option<String>,
+    ) -> Self {
+        Self {
+            user,
+            password,
+            url,
+            format,
+            columns,
+            unique_key_columns,
+            seq_column_index,
+            select_statement,
+            trace_obj,
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingSource for HttpStreamingSource {
+    async fn row_stream(
+        &self,
+        _columns: Vec<Column>,
+        _seq_column: Column,
+        initial_seq_value: Option<i64>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
+        let accept = match self.format {
+            HttpStreamFormat::Sse => "text/event-stream",
+            HttpStreamFormat::Ndjson => "application/x-ndjson",
+        };
+        let mut builder = reqwest::Client::new()
+            .get(&self.url)
+            .header("Accept", accept);
+        if let Some(user) = &self.user {
+            builder = builder.basic_auth(user, self.password.as_ref());
+        }
+        if let Some(seq) = initial_seq_value {
+            // Initial seq is inclusive while the last event id is the one already consumed
+            builder = builder.header("Last-Event-ID", (seq - 1).to_string());
+        }
+        let res = builder.send().await?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(CubeError::user(format!(
+                "Http stream {} responded with {}: {}",
+                self.url, status, body
+            )));
+        }
+
+        let parser = HttpStreamParser::new(
+            self.format,
+            self.columns.clone(),
+            self.unique_key_columns.clone(),
+            self.seq_column_index,
+            initial_seq_value,
+        );
+        let traffic_sender = TrafficSender::new(self.trace_obj.clone());
+        Ok(Box::pin(
+            res.bytes_stream()
+                .scan(parser, move |parser, bytes| {
+                    let rows = bytes.map_err(|e| CubeError::from(e)).and_then(|b| {
+                        traffic_sender.process_event(b.len() as u64)?;
+                        parser.push(&b)
+                    });
+                    futures::future::ready(Some(stream::iter(match rows {
+                        Ok(rows) => rows.into_iter().map(|r| Ok(r)).collect::<Vec<_>>(),
+                        Err(e) => vec![Err(e)],
+                    })))
+                })
+                .flatten(),
+        ))
+    }
+
+    fn source_columns(&self) -> &Vec<Column> {
+        &self.columns
+    }
+
+    fn source_seq_column_index(&self) -> usize {
+        self.seq_column_index
+    }
+
+    async fn validate_table_location(&self) -> Result<(), CubeError> {
+        if self.select_statement.is_some() {
+            return Err(CubeError::user(
+                "select_statement isn't supported for http sources".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct HttpStreamParser {
+    format: HttpStreamFormat,
+    columns: Vec<Column>,
+    unique_key_columns: Vec<Column>,
+    seq_column_index: usize,
+    initial_seq_value: Option<i64>,
+    /// Bytes after the last line separator.
+    tail: Vec<u8>,
+    event_data: Vec<String>,
+    event_id: Option<String>,
+}
+
+impl HttpStreamParser {
+    pub fn new(
+        format: HttpStreamFormat,
+        columns: Vec<Column>,
+        unique_key_columns: Vec<Column>,
+        seq_column_index: usize,
+        initial_seq_value: Option<i64>,
+    ) -> Self {
+        Self {
+            format,
+            columns,
+            unique_key_columns,
+            seq_column_index,
+            initial_seq_value,
+            tail: Vec::new(),
+            event_data: Vec::new(),
+            event_id: None,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<Row>, CubeError> {
+        self.tail.extend_from_slice(bytes);
+        let mut rows = Vec::new();
+        while let Some(pos) = self.tail.iter().position(|b| *b == b'\n') {
+            let line = self.tail.drain(0..=pos).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(&['\n', '\r'][..]);
+            let row = match self.format {
+                HttpStreamFormat::Ndjson => self.ndjson_line(line)?,
+                HttpStreamFormat::Sse => self.sse_line(line)?,
+            };
+            rows.extend(row);
+        }
+        Ok(rows)
+    }
+
+    fn ndjson_line(&mut self, line: &str) -> Result<Option<Row>, CubeError> {
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        self.to_row(line, None)
+    }
+
+    fn sse_line(&mut self, line: &str) -> Result<Option<Row>, CubeError> {
+        if line.is_empty() {
+            let id = self.event_id.take();
+            if self.event_data.is_empty() {
+                return Ok(None);
+            }
+            let data = self.event_data.join("\n");
+            self.event_data.clear();
+            let seq = id
+                .map(|id| {
+                    id.parse::<i64>().map_err(|_| {
+                        CubeError::user(format!("SSE event id '{}' isn't a number", id))
+                    })
+                })
+                .transpose()?;
+            return self.to_row(&data, seq);
+        }
+        // Comments are used as keep-alive by SSE servers
+        if line.starts_with(':') {
+            return Ok(None);
+        }
+        let (field, value) = match line.find(':') {
+            Some(pos) => (
+                &line[0..pos],
+                line[pos + 1..]
+                    .strip_prefix(' ')
+                    .unwrap_or(&line[pos + 1..]),
+            ),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.event_data.push(value.to_string()),
+            "id" => self.event_id = Some(value.to_string()),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn to_row(&self, data: &str, seq: Option<i64>) -> Result<Option<Row>, CubeError> {
+        let payload = json::parse(data)
+            .map_err(|e| CubeError::user(format!("Can't parse '{}' payload: {}", data, e)))?;
+        let mut values = parse_json_payload_and_key(
+            &self.columns,
+            &self.unique_key_columns,
+            payload,
+            &JsonValue::Object(Object::new()),
+        )
+        .map_err(|e| CubeError::user(format!("Can't parse http stream row '{}': {}", data, e)))?;
+        let seq = match (seq, &values[self.seq_column_index]) {
+            (Some(seq), _) => seq,
+            (None, TableValue::Int(seq)) => *seq,
+            (None, _) => {
+                return Err(CubeError::user(format!(
+                    "Http stream row '{}' has neither event id nor '{}' field",
+                    data,
+                    self.columns[self.seq_column_index].get_name()
+                )))
+            }
+        };
+        if self
+            .initial_seq_value
+            .map_or(false, |initial| seq < initial)
+        {
+            return Ok(None);
+        }
+        values[self.seq_column_index] = TableValue::Int(seq);
+        Ok(Some(Row::new(values)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metastore::ColumnType;
+
+    fn parser(format: HttpStreamFormat, initial_seq_value: Option<i64>) -> HttpStreamParser {
+        let columns = vec![
+            Column::new("id".to_string(), ColumnType::Int, 0),
+            Column::new("name".to_string(), ColumnType::String, 1),
+            Column::new("__seq".to_string(), ColumnType::Int, 2),
+        ];
+        let unique_key_columns = vec![columns[0].clone()];
+        HttpStreamParser::new(format, columns, unique_key_columns, 2, initial_seq_value)
+    }
+
+    fn row(id: i64, name: &str, seq: i64) -> Row {
+        Row::new(vec![
+            TableValue::Int(id),
+            TableValue::String(name.to_string()),
+            TableValue::Int(seq),
+        ])
+    }
+
+    #[test]
+    fn parse_sse() {
+        let mut p = parser(HttpStreamFormat::Sse, None);
+        assert_eq!(
+            p.push(b": keep-alive\n\nid: 10\nevent: row\ndata: {\"id\": 1,")
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            p.push(b" \"name\": \"a\"}\n\nid:11\r\ndata:{\"id\": 2, \"name\": \"b\"}\r\n\r\n")
+                .unwrap(),
+            vec![row(1, "a", 10), row(2, "b", 11)]
+        );
+        // Multi-line data is joined with line feeds
+        assert_eq!(
+            p.push(b"id: 12\ndata: {\"id\": 3,\ndata: \"name\": \"c\"}\n\n")
+                .unwrap(),
+            vec![row(3, "c", 12)]
+        );
+        assert_eq!(
+            p.push(b"id: x\ndata: {\"id\": 4}\n\n")
+                .unwrap_err()
+                .to_string(),
+            "SSE event id 'x' isn't a number"
+        );
+    }
+
+    #[test]
+    fn parse_ndjson() {
+        let mut p = parser(HttpStreamFormat::Ndjson, Some(2));
+        assert_eq!(
+            p.push(b"{\"id\": 1, \"name\": \"a\", \"__seq\": 1}\n\n{\"id\": 2, \"name\": \"b\", \"__seq\": 2}\n{\"id\": 3")
+                .unwrap(),
+            vec![row(2, "b", 2)]
+        );
+        assert_eq!(
+            p.push(b", \"name\": \"c\", \"__seq\": 3}\n").unwrap(),
+            vec![row(3, "c", 3)]
+        );
+        assert_eq!(
+            p.push(b"{\"id\": 4}\n").unwrap_err().to_string(),
+            "Http stream row '{\"id\": 4}' has neither event id nor '__seq' field"
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
+use crate::metastore::source::{KafkaPayloadFormat, SchemaRegistryConfig};
 use crate::metastore::table::StreamOffset;
-use crate::metastore::Column;
+use crate::metastore::{Column, ColumnType};
 use crate::queryplanner::metadata_cache::MetadataCacheFactory;
+use crate::streaming::kafka_decoder::{kafka_payload_decoder, KafkaPayloadDecoder};
 use crate::streaming::kafka_post_processing::{KafkaPostProcessPlan, KafkaPostProcessPlanner};
+use crate::streaming::schema_registry::{RegistrySchema, SchemaRegistryClient};
 use crate::streaming::traffic_sender::TrafficSender;
 use crate::streaming::{parse_json_payload_and_key, StreamingSource};
 use crate::table::{Row, TableValue};
@@ -40,6 +43,9 @@ pub struct KafkaStreamingSource {
     use_ssl: bool,
     post_processing_plan: Option<KafkaPostProcessPlan>,
     trace_obj: Option<String>,
+    format: KafkaPayloadFormat,
+    schema_registry: Option<SchemaRegistryConfig>,
+    schema_registry_client: Arc<dyn SchemaRegistryClient>,
 }
 
 impl KafkaStreamingSource {
@@ -60,6 +66,9 @@ impl KafkaStreamingSource {
         use_ssl: bool,
         trace_obj: Option<String>,
         metadata_cache_factory: Arc<dyn MetadataCacheFactory>,
+        format: KafkaPayloadFormat,
+        schema_registry: Option<SchemaRegistryConfig>,
+        schema_registry_client: Arc<dyn SchemaRegistryClient>,
     ) -> Result<Self, CubeError> {
         let (post_processing_plan, columns, unique_key_columns, seq_column_index) =
             if let Some(select_statement) = select_statement {
@@ -98,10 +107,57 @@ impl KafkaStreamingSource {
             use_ssl,
             post_processing_plan,
             trace_obj,
+            format,
+            schema_registry,
+            schema_registry_client,
         })
     }
 }
 
+/// Converts kafka messages to rows of the streaming table, `None` for messages to skip.
+#[async_trait]
+pub trait KafkaMessageParser: Send + Sync {
+    async fn parse(&self, message: KafkaMessage<'_>) -> Result<Option<Row>, CubeError>;
+}
+
+struct KafkaRowParser {
+    columns: Vec<Column>,
+    unique_key_columns: Vec<Column>,
+    seq_column_index: usize,
+    traffic_sender: Arc<TrafficSender>,
+    decoder: Arc<dyn KafkaPayloadDecoder>,
+}
+
+#[async_trait]
+impl KafkaMessageParser for KafkaRowParser {
+    async fn parse(&self, m: KafkaMessage<'_>) -> Result<Option<Row>, CubeError> {
+        let payload_bytes = match m.payload() {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+        self.traffic_sender
+            .process_event(payload_bytes.len() as u64)?;
+        let payload = self.decoder.decode_payload(payload_bytes).await?;
+        let key = match m.key() {
+            Some(key) => self.decoder.decode_key(key).await?,
+            None => JsonValue::Object(Object::new()),
+        };
+
+        let mut values =
+            parse_json_payload_and_key(&self.columns, &self.unique_key_columns, payload, &key)
+                .map_err(|e| {
+                    CubeError::user(format!(
+                        "Can't parse kafka row with '{}' key and '{}' payload: {}",
+                        key,
+                        self.decoder.display(payload_bytes),
+                        e
+                    ))
+                })?;
+        values[self.seq_column_index] = TableValue::Int(m.offset());
+        Ok(Some(Row::new(values)))
+    }
+}
+
 #[async_trait]
 pub trait KafkaClientService: DIService + Send + Sync {
     async fn create_message_stream(
@@ -114,7 +170,7 @@ pub trait KafkaClientService: DIService + Send + Sync {
         user: &Option<String>,
         password: &Option<String>,
         use_ssl: bool,
-        to_row: Arc<dyn Fn(KafkaMessage) -> Result<Option<Row>, CubeError> + Send + Sync>,
+        to_row: Arc<dyn KafkaMessageParser>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError>;
 
     async fn calulate_lag(
@@ -135,8 +191,8 @@ pub struct KafkaClientServiceImpl {
 pub enum KafkaMessage<'a> {
     BorrowedMessage(BorrowedMessage<'a>),
     MockMessage {
-        key: Option<String>,
-        payload: Option<String>,
+        key: Option<Vec<u8>>,
+        payload: Option<Vec<u8>>,
         offset: i64,
     },
 }
@@ -145,14 +201,14 @@ impl<'a> KafkaMessage<'a> {
     pub fn key(&self) -> Option<&[u8]> {
         match self {
             KafkaMessage::BorrowedMessage(m) => m.key(),
-            KafkaMessage::MockMessage { key, .. } => key.as_ref().map(|k| k.as_bytes()),
+            KafkaMessage::MockMessage { key, .. } => key.as_deref(),
         }
     }
 
     pub fn payload(&self) -> Option<&[u8]> {
         match self {
             KafkaMessage::BorrowedMessage(m) => m.payload(),
-            KafkaMessage::MockMessage { payload, .. } => payload.as_ref().map(|k| k.as_bytes()),
+            KafkaMessage::MockMessage { payload, .. } => payload.as_deref(),
         }
     }
 
@@ -176,7 +232,7 @@ impl KafkaClientService for KafkaClientServiceImpl {
         user: &Option<String>,
         password: &Option<String>,
         use_ssl: bool,
-        to_row: Arc<dyn Fn(KafkaMessage) -> Result<Option<Row>, CubeError> + Send + Sync>,
+        to_row: Arc<dyn KafkaMessageParser>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
         let mut config = ClientConfig::new();
         config.set("bootstrap.servers", hosts.join(","));
@@ -233,23 +289,25 @@ impl KafkaClientService for KafkaClientServiceImpl {
             async move {
                 loop {
                     let message = stream_consumer.recv().await;
-                    let row = message
-                        .map_err(|e| {
-                            CubeError::user(format!("Error during fetching kafka message: {}", e))
-                        })
-                        .and_then(|m| {
-                            let res = to_row(KafkaMessage::BorrowedMessage(m));
-                            if config_obj.skip_kafka_parsing_errors() {
-                                if let Err(e) = res {
+                    let row = match message {
+                        Ok(m) => {
+                            let res = to_row.parse(KafkaMessage::BorrowedMessage(m)).await;
+                            match res {
+                                Err(e) if config_obj.skip_kafka_parsing_errors() => {
                                     log::error!(
                                         "Skipping parsing kafka message due to error: {}",
                                         e
                                     );
-                                    return Ok(None);
+                                    Ok(None)
                                 }
+                                res => res,
                             }
-                            res
-                        });
+                        }
+                        Err(e) => Err(CubeError::user(format!(
+                            "Error during fetching kafka message: {}",
+                            e
+                        ))),
+                    };
                     match row {
                         Ok(None) => continue,
                         Ok(Some(row)) => break Some((Ok(row), ())),
@@ -309,10 +367,17 @@ impl StreamingSource for KafkaStreamingSource {
         _seq_column: Column,
         initial_seq_value: Option<i64>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
-        let column_to_move = self.columns.clone();
-        let unique_key_columns = self.unique_key_columns.clone();
-        let seq_column_index_to_move = self.seq_column_index;
-        let traffic_sender = TrafficSender::new(self.trace_obj.clone());
+        let to_row = Arc::new(KafkaRowParser {
+            columns: self.columns.clone(),
+            unique_key_columns: self.unique_key_columns.clone(),
+            seq_column_index: self.seq_column_index,
+            traffic_sender: TrafficSender::new(self.trace_obj.clone()),
+            decoder: kafka_payload_decoder(
+                self.format,
+                self.schema_registry.clone(),
+                self.schema_registry_client.clone(),
+            )?,
+        });
         let hosts = self
             .host
             .clone()
@@ -339,47 +404,7 @@ impl StreamingSource for KafkaStreamingSource {
                 &self.user,
                 &self.password,
                 self.use_ssl,
-                Arc::new(move |m| -> Result<_, _> {
-                    if let Some(payload_str) = m.payload().map(|p| String::from_utf8_lossy(p)) {
-                        traffic_sender.process_event(payload_str.len() as u64)?;
-                        let payload = json::parse(payload_str.as_ref()).map_err(|e| {
-                            CubeError::user(format!("Can't parse '{}' payload: {}", payload_str, e))
-                        })?;
-                        // Kafka can store additional metadata in suffix that contains information about window size for example
-                        // Another use case is streams would usually don't have any keys
-                        let mut key = JsonValue::Object(Object::new());
-                        if let Some(key_str) = m.key().map(|p| String::from_utf8_lossy(p)) {
-                            if key_str.starts_with("{") {
-                                if let Some(last_brace) = key_str.find("}") {
-                                    key = json::parse(&key_str.as_ref()[0..last_brace + 1])
-                                        .map_err(|e| {
-                                            CubeError::user(format!(
-                                                "Can't parse '{}' key: {}",
-                                                key_str, e
-                                            ))
-                                        })?;
-                                }
-                            }
-                        }
-
-                        let mut values = parse_json_payload_and_key(
-                            &column_to_move,
-                            &unique_key_columns,
-                            payload,
-                            &key,
-                        )
-                        .map_err(|e| {
-                            CubeError::user(format!(
-                                "Can't parse kafka row with '{}' key and '{}' payload: {}",
-                                key, payload_str, e
-                            ))
-                        })?;
-                        values[seq_column_index_to_move] = TableValue::Int(m.offset());
-                        Ok(Some(Row::new(values)))
-                    } else {
-                        Ok(None)
-                    }
-                }),
+                to_row,
             )
             .await?;
 
@@ -407,13 +432,59 @@ impl StreamingSource for KafkaStreamingSource {
         self.seq_column_index
     }
 
-    fn validate_table_location(&self) -> Result<(), CubeError> {
-        // TODO
-        // self.query(None)?;
+    async fn validate_table_location(&self) -> Result<(), CubeError> {
+        let registry = match (&self.format, &self.schema_registry) {
+            (KafkaPayloadFormat::Avro, Some(registry)) => registry,
+            _ => return Ok(()),
+        };
+        let schema = self
+            .schema_registry_client
+            .latest_schema(registry, &format!("{}-value", self.topic))
+            .await?;
+        // Topics without a registered schema yet are validated by the first message
+        if let Some(RegistrySchema::Avro(schema)) = schema.as_deref() {
+            for (i, column) in self.columns.iter().enumerate() {
+                if i == self.seq_column_index {
+                    continue;
+                }
+                let field_type = match schema.field_type(column.get_name()) {
+                    Some(field_type) => field_type,
+                    None => continue,
+                };
+                if let Some(avro_column_type) = schema.column_type(field_type) {
+                    if !avro_column_type_compatible(column.get_column_type(), &avro_column_type) {
+                        return Err(CubeError::user(format!(
+                            "Column '{}' of type {} can't be loaded from avro field of type {} in topic '{}'",
+                            column.get_name(),
+                            column.get_column_type(),
+                            avro_column_type,
+                            self.topic
+                        )));
+                    }
+                }
+            }
+        }
         Ok(())
     }
 }
 
+/// Mirrors conversions of `parse_json_value` for values decoded from avro.
+fn avro_column_type_compatible(column_type: &ColumnType, avro_column_type: &ColumnType) -> bool {
+    match (column_type, avro_column_type) {
+        (ColumnType::String, ColumnType::Int | ColumnType::Float | ColumnType::Boolean) => true,
+        (ColumnType::Float, ColumnType::Int) => true,
+        (ColumnType::Decimal { .. }, ColumnType::Int | ColumnType::Float) => true,
+        (
+            ColumnType::Decimal { scale, .. },
+            ColumnType::Decimal {
+                scale: avro_scale, ..
+            },
+        ) => scale >= avro_scale,
+        (ColumnType::Json, _) => true,
+        (column_type, avro_column_type) => column_type == avro_column_type,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
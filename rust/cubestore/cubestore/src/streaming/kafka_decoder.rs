@@ -0,0 +1,109 @@
+use crate::metastore::source::{KafkaPayloadFormat, SchemaRegistryConfig};
+use crate::streaming::schema_registry::{RegistrySchema, SchemaRegistryClient};
+use crate::CubeError;
+use async_trait::async_trait;
+use json::object::Object;
+use json::JsonValue;
+use std::sync::Arc;
+
+/// Decodes keys and payloads of kafka messages into json values loaded by
+/// `parse_json_payload_and_key`.
+#[async_trait]
+pub trait KafkaPayloadDecoder: Send + Sync {
+    async fn decode_key(&self, key: &[u8]) -> Result<JsonValue, CubeError>;
+
+    async fn decode_payload(&self, payload: &[u8]) -> Result<JsonValue, CubeError>;
+
+    /// Representation of the raw message data in error messages.
+    fn display(&self, data: &[u8]) -> String {
+        String::from_utf8_lossy(data).to_string()
+    }
+}
+
+pub fn kafka_payload_decoder(
+    format: KafkaPayloadFormat,
+    schema_registry: Option<SchemaRegistryConfig>,
+    schema_registry_client: Arc<dyn SchemaRegistryClient>,
+) -> Result<Arc<dyn KafkaPayloadDecoder>, CubeError> {
+    match (format, schema_registry) {
+        (KafkaPayloadFormat::Json, _) => Ok(Arc::new(JsonPayloadDecoder)),
+        (_, Some(registry)) => Ok(Arc::new(SchemaRegistryPayloadDecoder {
+            registry,
+            client: schema_registry_client,
+        })),
+        (format, None) => Err(CubeError::user(format!(
+            "Schema registry is required to decode {:?} kafka messages",
+            format
+        ))),
+    }
+}
+
+pub struct JsonPayloadDecoder;
+
+#[async_trait]
+impl KafkaPayloadDecoder for JsonPayloadDecoder {
+    async fn decode_key(&self, key: &[u8]) -> Result<JsonValue, CubeError> {
+        // Kafka can store additional metadata in suffix that contains information about window size for example
+        // Another use case is streams would usually don't have any keys
+        let key_str = String::from_utf8_lossy(key);
+        if key_str.starts_with("{") {
+            if let Some(last_brace) = key_str.find("}") {
+                return json::parse(&key_str.as_ref()[0..last_brace + 1])
+                    .map_err(|e| CubeError::user(format!("Can't parse '{}' key: {}", key_str, e)));
+            }
+        }
+        Ok(JsonValue::Object(Object::new()))
+    }
+
+    async fn decode_payload(&self, payload: &[u8]) -> Result<JsonValue, CubeError> {
+        let payload_str = String::from_utf8_lossy(payload);
+        json::parse(payload_str.as_ref())
+            .map_err(|e| CubeError::user(format!("Can't parse '{}' payload: {}", payload_str, e)))
+    }
+}
+
+/// Decodes messages in the Confluent wire format: magic byte `0`, big-endian schema id and
+/// data encoded with the schema. Data is decoded with the type the schema is registered with.
+pub struct SchemaRegistryPayloadDecoder {
+    registry: SchemaRegistryConfig,
+    client: Arc<dyn SchemaRegistryClient>,
+}
+
+impl SchemaRegistryPayloadDecoder {
+    async fn decode_framed(&self, data: &[u8]) -> Result<Option<JsonValue>, CubeError> {
+        if data.len() < 5 || data[0] != 0 {
+            return Ok(None);
+        }
+        let id = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        let schema = self.client.schema_by_id(&self.registry, id).await?;
+        let value = match schema.as_ref() {
+            RegistrySchema::Avro(schema) => schema.decode(&data[5..])?,
+            RegistrySchema::Protobuf(schema) => schema.decode_with_message_indexes(&data[5..])?,
+            RegistrySchema::Json => JsonPayloadDecoder.decode_payload(&data[5..]).await?,
+        };
+        Ok(Some(value))
+    }
+}
+
+#[async_trait]
+impl KafkaPayloadDecoder for SchemaRegistryPayloadDecoder {
+    async fn decode_key(&self, key: &[u8]) -> Result<JsonValue, CubeError> {
+        // Keys are often produced by a string serializer even if values use the registry
+        match self.decode_framed(key).await? {
+            Some(key) => Ok(key),
+            None => JsonPayloadDecoder.decode_key(key).await,
+        }
+    }
+
+    async fn decode_payload(&self, payload: &[u8]) -> Result<JsonValue, CubeError> {
+        self.decode_framed(payload).await?.ok_or_else(|| {
+            CubeError::user(
+                "Kafka message payload isn't prefixed with a schema registry id".to_string(),
+            )
+        })
+    }
+
+    fn display(&self, data: &[u8]) -> String {
+        base64::encode(data)
+    }
+}
@@ -1,5 +1,11 @@
+mod avro;
+mod files_source;
+mod http_source;
 pub mod kafka;
+mod kafka_decoder;
 mod kafka_post_processing;
+mod protobuf;
+pub mod schema_registry;
 pub(crate) mod topic_table_provider;
 mod traffic_sender;
 
@@ -7,16 +13,22 @@ mod buffered_stream;
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::cube_ext::ordfloat::OrdF64;
+use crate::import::ndjson::parse_json_list;
+use crate::import::parse_decimal;
 use crate::metastore::replay_handle::{ReplayHandle, SeqPointer, SeqPointerForLocation};
 use crate::metastore::source::SourceCredentials;
 use crate::metastore::table::{StreamOffset, Table};
 use crate::metastore::{Column, ColumnType, IdRow, MetaStore};
 use crate::queryplanner::metadata_cache::MetadataCacheFactory;
-use crate::sql::timestamp_from_string;
+use crate::remotefs::RemoteFs;
+use crate::sql::{date_from_string, interval_from_string, timestamp_from_string};
 use crate::store::ChunkDataStore;
+use crate::streaming::files_source::FilesStreamingSource;
+use crate::streaming::http_source::HttpStreamingSource;
 use crate::streaming::kafka::{KafkaClientService, KafkaStreamingSource};
+use crate::streaming::schema_registry::SchemaRegistryClient;
 use crate::table::data::{append_row, create_array_builders};
-use crate::table::{Row, TableValue, TimestampValue};
+use crate::table::{DateValue, IntervalValue, Row, TableValue, TimestampValue};
 use crate::util::decimal::Decimal;
 use crate::{app_metrics, CubeError};
 use async_trait::async_trait;
@@ -59,6 +71,8 @@ pub struct StreamingServiceImpl {
     ksql_client: Arc<dyn KsqlClient>,
     kafka_client: Arc<dyn KafkaClientService>,
     metadata_cache_factory: Arc<dyn MetadataCacheFactory>,
+    schema_registry_client: Arc<dyn SchemaRegistryClient>,
+    remote_fs: Arc<dyn RemoteFs>,
 }
 
 crate::di_service!(StreamingServiceImpl, [StreamingService]);
@@ -71,6 +85,8 @@ impl StreamingServiceImpl {
         ksql_client: Arc<dyn KsqlClient>,
         kafka_client: Arc<dyn KafkaClientService>,
         metadata_cache_factory: Arc<dyn MetadataCacheFactory>,
+        schema_registry_client: Arc<dyn SchemaRegistryClient>,
+        remote_fs: Arc<dyn RemoteFs>,
     ) -> Arc<Self> {
         Arc::new(Self {
             config_obj,
@@ -79,6 +95,8 @@ impl StreamingServiceImpl {
             ksql_client,
             kafka_client,
             metadata_cache_factory,
+            schema_registry_client,
+            remote_fs,
         })
     }
 
@@ -149,11 +167,11 @@ impl StreamingServiceImpl {
                 password,
                 host,
                 use_ssl,
+                format,
+                schema_registry,
             } => Ok(Arc::new(KafkaStreamingSource::try_new(
                 table.get_id(),
-                table.get_row().unique_key_columns()
-                    .ok_or_else(|| CubeError::internal(format!("Streaming table without unique key columns: {:?}", table)))?
-                    .into_iter().cloned().collect(),
+                self.unique_key_columns(table)?,
                 seq_column,
                 table.get_row().get_columns().clone(),
                 user.clone(),
@@ -170,10 +188,74 @@ impl StreamingServiceImpl {
                 *use_ssl,
                 trace_obj,
                 self.metadata_cache_factory.clone(),
+                *format,
+                schema_registry.clone(),
+                self.schema_registry_client.clone(),
             ).await?)),
+            SourceCredentials::Http {
+                user,
+                password,
+                url,
+                format,
+            } => {
+                if partition.is_some() {
+                    return Err(CubeError::user(format!("Partitions aren't supported by http sources but found in location url '{}'", location_url)));
+                }
+                Ok(Arc::new(HttpStreamingSource::new(
+                    user.clone(),
+                    password.clone(),
+                    format!("{}/{}", url.trim_end_matches('/'), table_name),
+                    *format,
+                    table.get_row().get_columns().clone(),
+                    self.unique_key_columns(table)?,
+                    seq_column.get_index(),
+                    table.get_row().select_statement().clone(),
+                    trace_obj,
+                )))
+            }
+            SourceCredentials::Files {
+                prefix,
+                poll_interval_secs,
+            } => {
+                if partition.is_some() {
+                    return Err(CubeError::user(format!("Partitions aren't supported by files sources but found in location url '{}'", location_url)));
+                }
+                Ok(Arc::new(FilesStreamingSource::new(
+                    self.remote_fs.clone(),
+                    format!("{}/{}/", prefix.trim_end_matches('/'), table_name),
+                    format!(
+                        "{}/{}.{}.state/",
+                        prefix.trim_end_matches('/'),
+                        table_name,
+                        table.get_id()
+                    ),
+                    Duration::from_secs(*poll_interval_secs),
+                    table.get_row().stream_offset().clone(),
+                    table.get_row().get_columns().clone(),
+                    self.unique_key_columns(table)?,
+                    seq_column.get_index(),
+                    table.get_row().select_statement().clone(),
+                    trace_obj,
+                )))
+            }
         }
     }
 
+    fn unique_key_columns(&self, table: &IdRow<Table>) -> Result<Vec<Column>, CubeError> {
+        Ok(table
+            .get_row()
+            .unique_key_columns()
+            .ok_or_else(|| {
+                CubeError::internal(format!(
+                    "Streaming table without unique key columns: {:?}",
+                    table
+                ))
+            })?
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
     async fn try_seal_table(&self, table: &IdRow<Table>) -> Result<bool, CubeError> {
         if let Some(seal_at) = table.get_row().seal_at() {
             if seal_at < &Utc::now() {
@@ -398,7 +480,12 @@ impl StreamingService for StreamingServiceImpl {
                 }
             }
             self.meta_store
-                .activate_chunks(table.get_id(), new_chunk_ids, Some(replay_handle.get_id()))
+                .activate_chunks(
+                    table.get_id(),
+                    new_chunk_ids,
+                    Some(replay_handle.get_id()),
+                    self.config_obj.encryption_key_id().clone(),
+                )
                 .await?;
 
             if let Ok(process_time) = process_started.elapsed() {
@@ -418,7 +505,7 @@ impl StreamingService for StreamingServiceImpl {
         location: &str,
     ) -> Result<(), CubeError> {
         let source = self.source_by(&table, location, None).await?;
-        source.validate_table_location()?;
+        source.validate_table_location().await?;
         Ok(())
     }
 }
@@ -444,7 +531,7 @@ pub trait StreamingSource: Send + Sync {
         None
     }
 
-    fn validate_table_location(&self) -> Result<(), CubeError>;
+    async fn validate_table_location(&self) -> Result<(), CubeError>;
 }
 
 #[derive(Clone)]
@@ -567,9 +654,14 @@ pub fn parse_json_value(column: &Column, value: &JsonValue) -> Result<TableValue
         ColumnType::Int96 => Err(CubeError::user(
             "int96 unsupported for streaming data".to_string(),
         )),
+        // Binary values are base64 encoded, avro bytes and fixed are decoded this way
         ColumnType::Bytes => match value {
-            _ => Err(CubeError::internal(format!(
-                "ksql source bytes import isn't supported"
+            JsonValue::Short(v) => Ok(TableValue::Bytes(base64::decode(v.as_str())?)),
+            JsonValue::String(v) => Ok(TableValue::Bytes(base64::decode(v.as_str())?)),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "ksql source returned {:?} as row value but base64 string expected",
+                x
             ))),
         },
         ColumnType::HyperLogLog(_) => match value {
@@ -577,6 +669,12 @@ pub fn parse_json_value(column: &Column, value: &JsonValue) -> Result<TableValue
                 "ksql source HLL import isn't supported"
             ))),
         },
+        ColumnType::TDigest => Err(CubeError::internal(
+            "ksql source t-digest import isn't supported".to_string(),
+        )),
+        ColumnType::ThetaSketch => Err(CubeError::internal(
+            "ksql source theta sketch import isn't supported".to_string(),
+        )),
         ColumnType::Timestamp => match value {
             JsonValue::Short(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
             JsonValue::String(v) => Ok(TableValue::Timestamp(timestamp_from_string(v.as_str())?)),
@@ -598,6 +696,15 @@ pub fn parse_json_value(column: &Column, value: &JsonValue) -> Result<TableValue
                     .map(|v| v as i128)
                     .ok_or(CubeError::user(format!("Can't convert {:?} to decimal", v)))?,
             ))),
+            // Avro decimals that don't fit into a JSON number are decoded as strings.
+            JsonValue::Short(v) => Ok(TableValue::Decimal(parse_decimal(
+                v.as_str(),
+                *scale as u8,
+            )?)),
+            JsonValue::String(v) => Ok(TableValue::Decimal(parse_decimal(
+                v.as_str(),
+                *scale as u8,
+            )?)),
             JsonValue::Null => Ok(TableValue::Null),
             x => Err(CubeError::internal(format!(
                 "ksql source returned {:?} as row value but only number values are supported",
@@ -623,6 +730,45 @@ pub fn parse_json_value(column: &Column, value: &JsonValue) -> Result<TableValue
                 x
             ))),
         },
+        // Numbers are days since epoch, as ksqlDB serializes `DATE`.
+        ColumnType::Date => match value {
+            JsonValue::Short(v) => Ok(TableValue::Date(date_from_string(v.as_str())?)),
+            JsonValue::String(v) => Ok(TableValue::Date(date_from_string(v.as_str())?)),
+            JsonValue::Number(v) => Ok(TableValue::Date(DateValue::new(
+                v.as_fixed_point_i64(0)
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or(CubeError::user(format!("Can't convert {:?} to date", v)))?,
+            ))),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "ksql source returned {:?} as row value but only primitive values are supported",
+                x
+            ))),
+        },
+        // Numbers are milliseconds, same as for timestamps.
+        ColumnType::Interval => match value {
+            JsonValue::Short(v) => Ok(TableValue::Interval(interval_from_string(v.as_str())?)),
+            JsonValue::String(v) => Ok(TableValue::Interval(interval_from_string(v.as_str())?)),
+            JsonValue::Number(v) => Ok(TableValue::Interval(IntervalValue::new(
+                v.as_fixed_point_i64(3).ok_or(CubeError::user(format!(
+                    "Can't convert {:?} to interval",
+                    v
+                )))?,
+            ))),
+            JsonValue::Null => Ok(TableValue::Null),
+            x => Err(CubeError::internal(format!(
+                "ksql source returned {:?} as row value but only primitive values are supported",
+                x
+            ))),
+        },
+        ColumnType::Json => match value {
+            JsonValue::Null => Ok(TableValue::Null),
+            v => Ok(TableValue::String(v.dump())),
+        },
+        ColumnType::List(element) => match value {
+            JsonValue::Null => Ok(TableValue::Null),
+            v => parse_json_list(element, v),
+        },
     }
 }
 
@@ -922,7 +1068,7 @@ impl StreamingSource for KSqlStreamingSource {
         self.seq_column_index
     }
 
-    fn validate_table_location(&self) -> Result<(), CubeError> {
+    async fn validate_table_location(&self) -> Result<(), CubeError> {
         self.query(None)?;
         Ok(())
     }
@@ -945,7 +1091,7 @@ mod tests {
     use crate::metastore::chunks::chunk_file_name;
     use crate::scheduler::SchedulerImpl;
     use crate::sql::MySqlDialectWithBackTicks;
-    use crate::streaming::kafka::KafkaMessage;
+    use crate::streaming::kafka::{KafkaMessage, KafkaMessageParser};
     use crate::streaming::{KSqlQuery, KSqlQuerySchema, KsqlClient, KsqlResponse};
     use crate::TableId;
     use chrono::{SecondsFormat, TimeZone, Utc};
@@ -953,6 +1099,7 @@ mod tests {
     use sqlparser::parser::Parser;
     use sqlparser::tokenizer::Tokenizer;
     use tokio::time::timeout;
+    use warp::Filter;
 
     pub struct MockKsqlClient;
 
@@ -1087,7 +1234,7 @@ mod tests {
             _user: &Option<String>,
             _password: &Option<String>,
             _use_ssl: bool,
-            to_row: Arc<dyn Fn(KafkaMessage) -> Result<Option<Row>, CubeError> + Send + Sync>,
+            to_row: Arc<dyn KafkaMessageParser>,
         ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
             let max_offset = 5000;
             let offset = match offset {
@@ -1113,27 +1260,29 @@ mod tests {
                         .to_rfc3339_opts(SecondsFormat::Millis, true);
                     messages.push(KafkaMessage::MockMessage {
                         // Keys in kafka can have suffixes which contain arbitrary metadata like window size
-                        key: Some(format!(
-                            "{}foo",
-                            serde_json::json!({ "MESSAGEID": i.to_string() }).to_string()
-                        )),
+                        key: Some(
+                            format!(
+                                "{}foo",
+                                serde_json::json!({ "MESSAGEID": i.to_string() }).to_string()
+                            )
+                            .into_bytes(),
+                        ),
                         payload: Some(
                             serde_json::json!({ "ANONYMOUSID": j.to_string(), "FILTER_ID":i, "TIMESTAMP": ts_string })
-                                .to_string(),
+                                .to_string()
+                                .into_bytes(),
                         ),
                         offset: i,
                     });
                 }
             }
 
-            let rows = messages
-                .into_iter()
-                .map(|m| to_row(m))
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .flatten()
-                .map(|m| Ok(m))
-                .collect::<Vec<_>>();
+            let mut rows = Vec::new();
+            for m in messages {
+                if let Some(row) = to_row.parse(m).await? {
+                    rows.push(Ok(row));
+                }
+            }
 
             Ok(Box::pin(stream::iter(rows)))
         }
@@ -1855,4 +2004,229 @@ mod tests {
         })
             .await;
     }
+
+    pub struct MockAvroKafkaClient;
+
+    crate::di_service!(MockAvroKafkaClient, [KafkaClientService]);
+
+    const AVRO_EVENT_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Event",
+        "fields": [
+            {"name": "ID", "type": "long"},
+            {"name": "NAME", "type": ["null", "string"]},
+            {"name": "TS", "type": {"type": "long", "logicalType": "timestamp-millis"}}
+        ]
+    }"#;
+
+    #[async_trait::async_trait]
+    impl KafkaClientService for MockAvroKafkaClient {
+        async fn create_message_stream(
+            &self,
+            _table_id: u64,
+            _topic: String,
+            _partition: i32,
+            _offset: Offset,
+            _hosts: Vec<String>,
+            _user: &Option<String>,
+            _password: &Option<String>,
+            _use_ssl: bool,
+            to_row: Arc<dyn KafkaMessageParser>,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Row, CubeError>> + Send>>, CubeError> {
+            let mut rows = Vec::new();
+            for i in 0..100 {
+                // Confluent wire format: magic byte, schema id, avro data
+                let mut payload = vec![0, 0, 0, 0, 1];
+                avro::tests::encode_long(i, &mut payload);
+                avro::tests::encode_long(1, &mut payload);
+                avro::tests::encode_string(&format!("event_{}", i), &mut payload);
+                avro::tests::encode_long(1_600_000_000_000 + i * 1000, &mut payload);
+                let m = KafkaMessage::MockMessage {
+                    key: Some(format!("{}", i).into_bytes()),
+                    payload: Some(payload),
+                    offset: i,
+                };
+                if let Some(row) = to_row.parse(m).await? {
+                    rows.push(Ok(row));
+                }
+            }
+            Ok(Box::pin(stream::iter(rows)))
+        }
+    }
+
+    #[tokio::test]
+    async fn streaming_avro_kafka() {
+        let (registry_url, _) = schema_registry::tests::start_mock_registry(vec![
+            schema_registry::tests::MockRegistrySchema {
+                id: 1,
+                subject: "EVENTS-value",
+                schema_type: "AVRO",
+                schema: AVRO_EVENT_SCHEMA,
+                references: vec![],
+            },
+        ])
+        .await;
+
+        Config::test("streaming_avro_kafka").update_config(|mut c| {
+            c.stream_replay_check_interval_secs = 1;
+            c.stale_stream_timeout = 1;
+            c
+        }).start_with_injector_override(async move |injector| {
+            injector.register_typed::<dyn KafkaClientService, _, _, _>(async move |_| {
+                Arc::new(MockAvroKafkaClient)
+            })
+                .await
+        }, async move |services| {
+            let service = services.sql_service;
+
+            let _ = service.exec_query("CREATE SCHEMA test").await?.collect().await?;
+
+            service
+                .exec_query(&format!("CREATE SOURCE OR UPDATE kafka AS 'kafka' VALUES (host = 'localhost:9092', format = 'avro', schema_registry_url = '{}')", registry_url))
+                .await?.collect().await?;
+
+            let listener = services.cluster.job_result_listener();
+
+            let _ = service
+                .exec_query("CREATE TABLE test.events (`ID` int, `NAME` text, `TS` timestamp) WITH (stream_offset = 'earliest') unique key (`ID`) location 'stream://kafka/EVENTS/0'")
+                .await?.collect().await?;
+
+            let wait = listener.wait_for_job_results(vec![
+                (RowKey::Table(TableId::Tables, 1), JobType::TableImportCSV("stream://kafka/EVENTS/0".to_string())),
+            ]);
+            timeout(Duration::from_secs(15), wait).await?.unwrap();
+
+            let result = service
+                .exec_query("SELECT `ID`, `NAME`, `TS` FROM test.events WHERE `ID` = 42")
+                .await?.collect().await?;
+            assert_eq!(result.get_rows(), &vec![Row::new(vec![
+                TableValue::Int(42),
+                TableValue::String("event_42".to_string()),
+                TableValue::Timestamp(TimestampValue::new(1_600_000_042_000_000_000)),
+            ])]);
+
+            let err = service
+                .exec_query("CREATE TABLE test.events_invalid (`ID` int, `NAME` int) unique key (`ID`) location 'stream://kafka/EVENTS/0'")
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("Column 'NAME' of type int can't be loaded from avro field of type text"),
+                "{}",
+                err
+            );
+            Ok::<(), CubeError>(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn streaming_http_sse() {
+        let body = (1..=3)
+            .map(|i| {
+                format!(
+                    "id: {}\ndata: {{\"ID\": {}, \"NAME\": \"name_{}\"}}\n\n",
+                    i, i, i
+                )
+            })
+            .join(": keep-alive\n\n");
+        let route = warp::path!("events" / "orders").map(move || {
+            warp::http::Response::builder()
+                .header("Content-Type", "text/event-stream")
+                .body(body.clone())
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        Config::test("streaming_http_sse").start_test(async move |services| {
+            let service = services.sql_service;
+
+            let _ = service.exec_query("CREATE SCHEMA test").await?.collect().await?;
+
+            service
+                .exec_query(&format!("CREATE SOURCE OR UPDATE http AS 'events' VALUES (url = 'http://{}/events', format = 'sse')", addr))
+                .await?.collect().await?;
+
+            let listener = services.cluster.job_result_listener();
+
+            let _ = service
+                .exec_query("CREATE TABLE test.orders (`ID` int, `NAME` text) unique key (`ID`) location 'stream://events/orders'")
+                .await?.collect().await?;
+
+            let wait = listener.wait_for_job_results(vec![
+                (RowKey::Table(TableId::Tables, 1), JobType::TableImportCSV("stream://events/orders".to_string())),
+            ]);
+            timeout(Duration::from_secs(15), wait).await?.unwrap();
+
+            let result = service
+                .exec_query("SELECT `ID`, `NAME`, `__seq` FROM test.orders ORDER BY `ID`")
+                .await?.collect().await?;
+            assert_eq!(
+                result.get_rows(),
+                &(1..=3)
+                    .map(|i| Row::new(vec![
+                        TableValue::Int(i),
+                        TableValue::String(format!("name_{}", i)),
+                        TableValue::Int(i),
+                    ]))
+                    .collect::<Vec<_>>()
+            );
+            Ok::<(), CubeError>(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn streaming_files_tail() {
+        Config::test("streaming_files_tail").update_config(|mut c| {
+            c.stale_stream_timeout = 2;
+            c
+        }).start_test(async move |services| {
+            let service = services.sql_service;
+            let remote_fs = services.injector.get_service_typed::<dyn RemoteFs>().await;
+
+            for (file, ids) in [("events/1.ndjson", 0..3), ("events/2.ndjson", 3..5)] {
+                let remote_path = format!("incoming/{}", file);
+                let temp_path = remote_fs.temp_upload_path(remote_path.clone()).await?;
+                let lines = ids
+                    .map(|i| format!("{{\"ID\": {}, \"NAME\": \"name_{}\"}}\n", i, i))
+                    .join("");
+                std::fs::write(&temp_path, lines)?;
+                remote_fs.upload_file(temp_path, remote_path).await?;
+            }
+
+            let _ = service.exec_query("CREATE SCHEMA test").await?.collect().await?;
+
+            service
+                .exec_query("CREATE SOURCE OR UPDATE files AS 'drop' VALUES (prefix = 'incoming', poll_interval_secs = '1')")
+                .await?.collect().await?;
+
+            let listener = services.cluster.job_result_listener();
+
+            let _ = service
+                .exec_query("CREATE TABLE test.events (`ID` int, `NAME` text) WITH (stream_offset = 'earliest') unique key (`ID`) location 'stream://drop/events'")
+                .await?.collect().await?;
+
+            // Tailing never ends the stream, so the import is finished by the stale stream timeout
+            let wait = listener.wait_for_job_results(vec![
+                (RowKey::Table(TableId::Tables, 1), JobType::TableImportCSV("stream://drop/events".to_string())),
+            ]);
+            let _ = timeout(Duration::from_secs(15), wait).await;
+
+            let result = service
+                .exec_query("SELECT COUNT(*), MIN(`ID`), MAX(`ID`) FROM test.events")
+                .await?.collect().await?;
+            assert_eq!(result.get_rows(), &vec![Row::new(vec![TableValue::Int(5), TableValue::Int(0), TableValue::Int(4)])]);
+
+            // Rows of the second file follow all rows of the first one
+            let result = service
+                .exec_query("SELECT `ID` FROM test.events ORDER BY `__seq`")
+                .await?.collect().await?;
+            assert_eq!(
+                result.get_rows(),
+                &(0..5).map(|i| Row::new(vec![TableValue::Int(i)])).collect::<Vec<_>>()
+            );
+            Ok::<(), CubeError>(())
+        })
+            .await;
+    }
 }
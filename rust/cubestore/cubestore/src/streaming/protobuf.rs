@@ -0,0 +1,1118 @@
+use crate::CubeError;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use json::object::Object;
+use json::JsonValue;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtoFieldType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    Message(String),
+    Enum(String),
+    Map(Box<ProtoFieldType>, Box<ProtoFieldType>),
+    /// `google.protobuf.Timestamp`, loaded as a timestamp string.
+    Timestamp,
+    /// `google.protobuf.*Value` wrappers, loaded as the wrapped value.
+    Wrapper(Box<ProtoFieldType>),
+    /// Type name as written in the schema, resolved after parsing.
+    Unresolved(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProtoLabel {
+    Singular,
+    Optional,
+    Repeated,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtoField {
+    pub name: String,
+    pub number: u64,
+    pub label: ProtoLabel,
+    pub field_type: ProtoFieldType,
+    pub in_oneof: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtoMessage {
+    pub full_name: String,
+    pub fields: Vec<ProtoField>,
+    /// Full names of nested messages in the declaration order.
+    pub nested: Vec<String>,
+    /// Syntax of the file declaring the message, imports can use a different one.
+    pub proto3: bool,
+}
+
+/// Protobuf schema parsed from the `.proto` source returned by the schema registry. Types of other
+/// files are resolved from the imports passed to `parse_with_imports`, well-known timestamp and
+/// wrapper types are built in.
+#[derive(Clone, Debug)]
+pub struct ProtobufSchema {
+    /// Syntax of the file being parsed.
+    proto3: bool,
+    /// Top level messages in the declaration order.
+    messages: Vec<String>,
+    message_defs: HashMap<String, ProtoMessage>,
+    enums: HashMap<String, Vec<(i64, String)>>,
+}
+
+impl ProtobufSchema {
+    pub fn parse(schema: &str) -> Result<Self, CubeError> {
+        Self::parse_with_imports(schema, &[])
+    }
+
+    /// `imports` are sources of the files imported by the schema, e.g. schema registry
+    /// references. Their types can be used by the schema, but message indexes only select
+    /// messages of the schema itself.
+    pub fn parse_with_imports(schema: &str, imports: &[String]) -> Result<Self, CubeError> {
+        let mut parsed = ProtobufSchema {
+            proto3: false,
+            messages: Vec::new(),
+            message_defs: HashMap::new(),
+            enums: HashMap::new(),
+        };
+        for source in imports.iter().map(|s| s.as_str()).chain([schema]) {
+            parsed.proto3 = false;
+            parsed.messages.clear();
+            let mut parser = ProtoParser {
+                tokens: tokenize(source)?,
+                pos: 0,
+                schema: parsed,
+            };
+            parser.parse_file()?;
+            parsed = parser.schema;
+        }
+        parsed.resolve_types()?;
+        Ok(parsed)
+    }
+
+    /// Decodes a message prefixed with the Confluent message indexes, which select the message
+    /// type within the schema.
+    pub fn decode_with_message_indexes(&self, data: &[u8]) -> Result<JsonValue, CubeError> {
+        let mut reader = ProtoReader { data };
+        let count = zigzag(reader.read_varint()?);
+        // Every index takes at least a byte.
+        if count < 0 || count as u64 > reader.data.len() as u64 {
+            return Err(CubeError::user(format!(
+                "Invalid protobuf message index count {}",
+                count
+            )));
+        }
+        let indexes = if count == 0 {
+            vec![0]
+        } else {
+            (0..count)
+                .map(|_| reader.read_varint().map(zigzag))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let message = self.message_by_indexes(&indexes)?;
+        self.decode_message(message, reader.data)
+    }
+
+    pub fn message_by_indexes(&self, indexes: &[i64]) -> Result<&ProtoMessage, CubeError> {
+        let mut candidates = &self.messages;
+        let mut message = None;
+        for index in indexes {
+            let name = usize::try_from(*index)
+                .ok()
+                .and_then(|i| candidates.get(i))
+                .ok_or_else(|| {
+                    CubeError::user(format!(
+                        "Protobuf message with indexes {:?} is not found",
+                        indexes
+                    ))
+                })?;
+            let m = &self.message_defs[name];
+            candidates = &m.nested;
+            message = Some(m);
+        }
+        message.ok_or_else(|| CubeError::user("Protobuf schema has no messages".to_string()))
+    }
+
+    pub fn decode_message(
+        &self,
+        message: &ProtoMessage,
+        data: &[u8],
+    ) -> Result<JsonValue, CubeError> {
+        let mut reader = ProtoReader { data };
+        let mut object = Object::new();
+        while !reader.data.is_empty() {
+            let tag = reader.read_varint()?;
+            let (number, wire_type) = (tag >> 3, (tag & 7) as u8);
+            let field = match message.fields.iter().find(|f| f.number == number) {
+                Some(field) => field,
+                None => {
+                    reader.skip(wire_type)?;
+                    continue;
+                }
+            };
+
+            match (&field.field_type, field.label) {
+                (ProtoFieldType::Map(key_type, value_type), _) => {
+                    let entry = reader.read_len_delimited(wire_type)?;
+                    let (key, value) = self.decode_map_entry(key_type, value_type, entry)?;
+                    if !object.get(&field.name).map_or(false, |m| m.is_object()) {
+                        object.insert(&field.name, JsonValue::Object(Object::new()));
+                    }
+                    object[field.name.as_str()].insert(&key, value).unwrap();
+                }
+                (field_type, ProtoLabel::Repeated) => {
+                    let mut values = Vec::new();
+                    if wire_type == 2 && is_packable(field_type) {
+                        let mut packed = ProtoReader {
+                            data: reader.read_len_delimited(wire_type)?,
+                        };
+                        while !packed.data.is_empty() {
+                            values.push(self.decode_value(
+                                field_type,
+                                scalar_wire_type(field_type),
+                                &mut packed,
+                            )?);
+                        }
+                    } else {
+                        values.push(self.decode_value(field_type, wire_type, &mut reader)?);
+                    }
+                    if !object.get(&field.name).map_or(false, |v| v.is_array()) {
+                        object.insert(&field.name, JsonValue::Array(Vec::new()));
+                    }
+                    for value in values {
+                        object[field.name.as_str()].push(value).unwrap();
+                    }
+                }
+                (field_type, _) => {
+                    let value = self.decode_value(field_type, wire_type, &mut reader)?;
+                    object.insert(&field.name, value);
+                }
+            }
+        }
+
+        for field in message.fields.iter() {
+            if object.get(&field.name).is_none() {
+                if let Some(default) = self.default_value(message, field) {
+                    object.insert(&field.name, default);
+                }
+            }
+        }
+
+        Ok(JsonValue::Object(object))
+    }
+
+    fn decode_map_entry(
+        &self,
+        key_type: &ProtoFieldType,
+        value_type: &ProtoFieldType,
+        data: &[u8],
+    ) -> Result<(String, JsonValue), CubeError> {
+        let mut reader = ProtoReader { data };
+        let mut key = None;
+        let mut value = None;
+        while !reader.data.is_empty() {
+            let tag = reader.read_varint()?;
+            let wire_type = (tag & 7) as u8;
+            match tag >> 3 {
+                1 => key = Some(self.decode_value(key_type, wire_type, &mut reader)?),
+                2 => value = Some(self.decode_value(value_type, wire_type, &mut reader)?),
+                _ => reader.skip(wire_type)?,
+            }
+        }
+        let key = match key {
+            Some(JsonValue::Short(s)) => s.to_string(),
+            Some(JsonValue::String(s)) => s,
+            Some(k) => k.dump(),
+            None => self
+                .scalar_default(key_type)
+                .map_or(String::new(), |k| match k {
+                    JsonValue::Short(s) => s.to_string(),
+                    k => k.dump(),
+                }),
+        };
+        let value = match value {
+            Some(value) => value,
+            None => self.scalar_default(value_type).unwrap_or(JsonValue::Null),
+        };
+        Ok((key, value))
+    }
+
+    fn decode_value(
+        &self,
+        field_type: &ProtoFieldType,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<JsonValue, CubeError> {
+        let expected_wire_type = scalar_wire_type(field_type);
+        if wire_type != expected_wire_type {
+            return Err(CubeError::user(format!(
+                "Unexpected protobuf wire type {} for {:?}, expected: {}",
+                wire_type, field_type, expected_wire_type
+            )));
+        }
+
+        Ok(match field_type {
+            ProtoFieldType::Double => JsonValue::from(f64::from_le_bytes(
+                reader.read_fixed(8)?.try_into().unwrap(),
+            )),
+            ProtoFieldType::Float => JsonValue::from(f32::from_le_bytes(
+                reader.read_fixed(4)?.try_into().unwrap(),
+            ) as f64),
+            ProtoFieldType::Int32 => JsonValue::from(reader.read_varint()? as i32),
+            ProtoFieldType::Int64 => JsonValue::from(reader.read_varint()? as i64),
+            ProtoFieldType::Uint32 => JsonValue::from(reader.read_varint()? as u32),
+            ProtoFieldType::Uint64 => JsonValue::from(reader.read_varint()?),
+            ProtoFieldType::Sint32 | ProtoFieldType::Sint64 => {
+                JsonValue::from(zigzag(reader.read_varint()?))
+            }
+            ProtoFieldType::Fixed32 => JsonValue::from(u32::from_le_bytes(
+                reader.read_fixed(4)?.try_into().unwrap(),
+            )),
+            ProtoFieldType::Sfixed32 => JsonValue::from(i32::from_le_bytes(
+                reader.read_fixed(4)?.try_into().unwrap(),
+            )),
+            ProtoFieldType::Fixed64 => JsonValue::from(u64::from_le_bytes(
+                reader.read_fixed(8)?.try_into().unwrap(),
+            )),
+            ProtoFieldType::Sfixed64 => JsonValue::from(i64::from_le_bytes(
+                reader.read_fixed(8)?.try_into().unwrap(),
+            )),
+            ProtoFieldType::Bool => JsonValue::Boolean(reader.read_varint()? != 0),
+            ProtoFieldType::String => {
+                let bytes = reader.read_len_delimited(wire_type)?;
+                JsonValue::String(
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|e| CubeError::user(format!("Invalid protobuf string: {}", e)))?,
+                )
+            }
+            ProtoFieldType::Bytes => {
+                JsonValue::String(base64::encode(reader.read_len_delimited(wire_type)?))
+            }
+            ProtoFieldType::Enum(name) => {
+                let value = reader.read_varint()? as i32 as i64;
+                // Unknown values are kept as numbers
+                match self.enums[name].iter().find(|(number, _)| *number == value) {
+                    Some((_, symbol)) => JsonValue::String(symbol.to_string()),
+                    None => JsonValue::from(value),
+                }
+            }
+            ProtoFieldType::Message(name) => {
+                let data = reader.read_len_delimited(wire_type)?;
+                self.decode_message(&self.message_defs[name], data)?
+            }
+            ProtoFieldType::Timestamp => {
+                let mut reader = ProtoReader {
+                    data: reader.read_len_delimited(wire_type)?,
+                };
+                let (mut seconds, mut nanos) = (0i64, 0i64);
+                while !reader.data.is_empty() {
+                    let tag = reader.read_varint()?;
+                    match (tag >> 3, (tag & 7) as u8) {
+                        (1, 0) => seconds = reader.read_varint()? as i64,
+                        (2, 0) => nanos = reader.read_varint()? as i32 as i64,
+                        (_, wire_type) => reader.skip(wire_type)?,
+                    }
+                }
+                let timestamp = u32::try_from(nanos)
+                    .ok()
+                    .and_then(|nanos| Utc.timestamp_opt(seconds, nanos).single())
+                    .ok_or_else(|| {
+                        CubeError::user(format!(
+                            "Protobuf timestamp {}s {}ns is out of range",
+                            seconds, nanos
+                        ))
+                    })?;
+                JsonValue::String(timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            ProtoFieldType::Wrapper(wrapped) => {
+                let mut reader = ProtoReader {
+                    data: reader.read_len_delimited(wire_type)?,
+                };
+                let mut value = None;
+                while !reader.data.is_empty() {
+                    let tag = reader.read_varint()?;
+                    let wire_type = (tag & 7) as u8;
+                    if tag >> 3 == 1 {
+                        value = Some(self.decode_value(wrapped, wire_type, &mut reader)?);
+                    } else {
+                        reader.skip(wire_type)?;
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => self.scalar_default(wrapped).unwrap_or(JsonValue::Null),
+                }
+            }
+            ProtoFieldType::Map(..) | ProtoFieldType::Unresolved(_) => {
+                return Err(CubeError::internal(format!(
+                    "Unexpected protobuf field type {:?}",
+                    field_type
+                )))
+            }
+        })
+    }
+
+    /// Proto3 doesn't distinguish unset fields from fields with default values, so defaults are
+    /// filled in for them to not load zeros as nulls.
+    fn default_value(&self, message: &ProtoMessage, field: &ProtoField) -> Option<JsonValue> {
+        match (&field.field_type, field.label) {
+            (ProtoFieldType::Map(..), _) => Some(JsonValue::Object(Object::new())),
+            (_, ProtoLabel::Repeated) => Some(JsonValue::Array(Vec::new())),
+            (field_type, ProtoLabel::Singular) if message.proto3 && !field.in_oneof => {
+                self.scalar_default(field_type)
+            }
+            _ => None,
+        }
+    }
+
+    fn scalar_default(&self, field_type: &ProtoFieldType) -> Option<JsonValue> {
+        match field_type {
+            ProtoFieldType::Bool => Some(JsonValue::Boolean(false)),
+            ProtoFieldType::String | ProtoFieldType::Bytes => Some(JsonValue::from("")),
+            ProtoFieldType::Enum(name) => self.enums[name]
+                .first()
+                .map(|(_, symbol)| JsonValue::String(symbol.to_string())),
+            ProtoFieldType::Message(_)
+            | ProtoFieldType::Map(..)
+            | ProtoFieldType::Timestamp
+            | ProtoFieldType::Wrapper(_)
+            | ProtoFieldType::Unresolved(_) => None,
+            _ => Some(JsonValue::from(0)),
+        }
+    }
+
+    fn resolve_types(&mut self) -> Result<(), CubeError> {
+        let names = self.message_defs.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            let mut message = self.message_defs.remove(&name).unwrap();
+            for field in message.fields.iter_mut() {
+                field.field_type = self.resolve_type(&field.field_type, &name)?;
+            }
+            self.message_defs.insert(name, message);
+        }
+        Ok(())
+    }
+
+    fn resolve_type(
+        &self,
+        field_type: &ProtoFieldType,
+        scope: &str,
+    ) -> Result<ProtoFieldType, CubeError> {
+        let type_name = match field_type {
+            ProtoFieldType::Unresolved(type_name) => type_name,
+            ProtoFieldType::Map(key, value) => {
+                return Ok(ProtoFieldType::Map(
+                    Box::new(self.resolve_type(key, scope)?),
+                    Box::new(self.resolve_type(value, scope)?),
+                ))
+            }
+            t => return Ok(t.clone()),
+        };
+
+        let well_known = match type_name.trim_start_matches('.') {
+            "google.protobuf.Timestamp" => Some(ProtoFieldType::Timestamp),
+            "google.protobuf.DoubleValue" => Some(ProtoFieldType::Double),
+            "google.protobuf.FloatValue" => Some(ProtoFieldType::Float),
+            "google.protobuf.Int64Value" => Some(ProtoFieldType::Int64),
+            "google.protobuf.UInt64Value" => Some(ProtoFieldType::Uint64),
+            "google.protobuf.Int32Value" => Some(ProtoFieldType::Int32),
+            "google.protobuf.UInt32Value" => Some(ProtoFieldType::Uint32),
+            "google.protobuf.BoolValue" => Some(ProtoFieldType::Bool),
+            "google.protobuf.StringValue" => Some(ProtoFieldType::String),
+            "google.protobuf.BytesValue" => Some(ProtoFieldType::Bytes),
+            _ => None,
+        };
+        match well_known {
+            Some(ProtoFieldType::Timestamp) => return Ok(ProtoFieldType::Timestamp),
+            Some(wrapped) => return Ok(ProtoFieldType::Wrapper(Box::new(wrapped))),
+            None => {}
+        }
+
+        // Names are looked up in the scope of the message and then in the enclosing scopes
+        let candidates = if let Some(absolute) = type_name.strip_prefix('.') {
+            vec![absolute.to_string()]
+        } else {
+            let mut candidates = Vec::new();
+            let mut scope = Some(scope);
+            while let Some(s) = scope {
+                candidates.push(format!("{}.{}", s, type_name));
+                scope = s.rfind('.').map(|pos| &s[0..pos]);
+            }
+            candidates.push(type_name.to_string());
+            candidates
+        };
+        for candidate in candidates {
+            if self.message_defs.contains_key(&candidate) || candidate == scope {
+                return Ok(ProtoFieldType::Message(candidate));
+            }
+            if self.enums.contains_key(&candidate) {
+                return Ok(ProtoFieldType::Enum(candidate));
+            }
+        }
+        Err(CubeError::user(format!(
+            "Unknown protobuf type '{}' in message {}",
+            type_name, scope
+        )))
+    }
+}
+
+fn scalar_wire_type(field_type: &ProtoFieldType) -> u8 {
+    match field_type {
+        ProtoFieldType::Double | ProtoFieldType::Fixed64 | ProtoFieldType::Sfixed64 => 1,
+        ProtoFieldType::Float | ProtoFieldType::Fixed32 | ProtoFieldType::Sfixed32 => 5,
+        ProtoFieldType::String
+        | ProtoFieldType::Bytes
+        | ProtoFieldType::Message(_)
+        | ProtoFieldType::Map(..)
+        | ProtoFieldType::Timestamp
+        | ProtoFieldType::Wrapper(_)
+        | ProtoFieldType::Unresolved(_) => 2,
+        _ => 0,
+    }
+}
+
+fn is_packable(field_type: &ProtoFieldType) -> bool {
+    scalar_wire_type(field_type) != 2
+}
+
+fn zigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+struct ProtoReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn read_fixed(&mut self, len: usize) -> Result<&'a [u8], CubeError> {
+        if self.data.len() < len {
+            return Err(CubeError::user(
+                "Unexpected end of protobuf message".to_string(),
+            ));
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, CubeError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_fixed(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CubeError::user("Invalid protobuf varint".to_string()))
+    }
+
+    fn read_len_delimited(&mut self, wire_type: u8) -> Result<&'a [u8], CubeError> {
+        if wire_type != 2 {
+            return Err(CubeError::user(format!(
+                "Unexpected protobuf wire type {}, expected: 2",
+                wire_type
+            )));
+        }
+        let len = self.read_varint()?;
+        self.read_fixed(len as usize)
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<(), CubeError> {
+        match wire_type {
+            0 => {
+                self.read_varint()?;
+            }
+            1 => {
+                self.read_fixed(8)?;
+            }
+            2 => {
+                self.read_len_delimited(wire_type)?;
+            }
+            5 => {
+                self.read_fixed(4)?;
+            }
+            x => {
+                return Err(CubeError::user(format!(
+                    "Unsupported protobuf wire type {}",
+                    x
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, CubeError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_alphabetic()
+            || c == '_'
+            // Fully qualified type name, e.g. `.google.protobuf.Timestamp`
+            || (c == '.' && chars.get(i + 1).map_or(false, |c| c.is_alphabetic()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit() || c == '-' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(CubeError::user(
+                    "Unterminated string in protobuf schema".to_string(),
+                ));
+            }
+            i += 1;
+            tokens.push(Token::Str(value));
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct ProtoParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    schema: ProtobufSchema,
+}
+
+impl ProtoParser {
+    fn parse_file(&mut self) -> Result<(), CubeError> {
+        let mut package = None;
+        while let Some(token) = self.next() {
+            match token {
+                Token::Ident(i) if i == "syntax" || i == "edition" => {
+                    self.expect_symbol('=')?;
+                    if let Some(Token::Str(syntax)) = self.next() {
+                        self.schema.proto3 = syntax == "proto3";
+                    }
+                    self.expect_symbol(';')?;
+                }
+                Token::Ident(i) if i == "package" => {
+                    package = Some(self.expect_ident()?);
+                    self.expect_symbol(';')?;
+                }
+                Token::Ident(i) if i == "message" => {
+                    let name = self.parse_message(package.as_deref())?;
+                    self.schema.messages.push(name);
+                }
+                Token::Ident(i) if i == "enum" => self.parse_enum(package.as_deref())?,
+                Token::Ident(i) if i == "service" || i == "extend" => self.skip_block()?,
+                Token::Symbol(';') => {}
+                // import, option
+                _ => self.skip_statement()?,
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_message(&mut self, scope: Option<&str>) -> Result<String, CubeError> {
+        let full_name = qualify(scope, &self.expect_ident()?);
+        self.expect_symbol('{')?;
+        let mut message = ProtoMessage {
+            full_name: full_name.clone(),
+            fields: Vec::new(),
+            nested: Vec::new(),
+            proto3: self.schema.proto3,
+        };
+        self.parse_message_body(&mut message, false)?;
+        self.schema.message_defs.insert(full_name.clone(), message);
+        Ok(full_name)
+    }
+
+    fn parse_message_body(
+        &mut self,
+        message: &mut ProtoMessage,
+        in_oneof: bool,
+    ) -> Result<(), CubeError> {
+        loop {
+            let token = self.next().ok_or_else(|| {
+                CubeError::user(format!(
+                    "Unexpected end of protobuf message {}",
+                    message.full_name
+                ))
+            })?;
+            match token {
+                Token::Symbol('}') => return Ok(()),
+                Token::Symbol(';') => {}
+                Token::Ident(i) if i == "message" && !in_oneof => {
+                    let name = self.parse_message(Some(&message.full_name))?;
+                    message.nested.push(name);
+                }
+                Token::Ident(i) if i == "enum" && !in_oneof => {
+                    self.parse_enum(Some(&message.full_name))?
+                }
+                Token::Ident(i) if i == "oneof" && !in_oneof => {
+                    self.expect_ident()?;
+                    self.expect_symbol('{')?;
+                    self.parse_message_body(message, true)?;
+                }
+                Token::Ident(i) if i == "extend" => self.skip_block()?,
+                Token::Ident(i) if i == "option" || i == "reserved" || i == "extensions" => {
+                    self.skip_statement()?
+                }
+                Token::Ident(i) if i == "group" => {
+                    return Err(CubeError::user(format!(
+                        "Protobuf groups are not supported in message {}",
+                        message.full_name
+                    )))
+                }
+                Token::Ident(i) if i == "map" => {
+                    self.expect_symbol('<')?;
+                    let key_type = field_type(&self.expect_ident()?);
+                    self.expect_symbol(',')?;
+                    let value_type = field_type(&self.expect_ident()?);
+                    self.expect_symbol('>')?;
+                    let field = self.parse_field_tail(
+                        ProtoFieldType::Map(Box::new(key_type), Box::new(value_type)),
+                        ProtoLabel::Repeated,
+                        in_oneof,
+                    )?;
+                    message.fields.push(field);
+                }
+                Token::Ident(i) => {
+                    let (label, type_name) = match i.as_str() {
+                        "repeated" => (ProtoLabel::Repeated, self.expect_ident()?),
+                        "optional" => (ProtoLabel::Optional, self.expect_ident()?),
+                        "required" => (ProtoLabel::Singular, self.expect_ident()?),
+                        _ => (ProtoLabel::Singular, i),
+                    };
+                    let field = self.parse_field_tail(field_type(&type_name), label, in_oneof)?;
+                    message.fields.push(field);
+                }
+                t => {
+                    return Err(CubeError::user(format!(
+                        "Unexpected {:?} in protobuf message {}",
+                        t, message.full_name
+                    )))
+                }
+            }
+        }
+    }
+
+    fn parse_field_tail(
+        &mut self,
+        field_type: ProtoFieldType,
+        label: ProtoLabel,
+        in_oneof: bool,
+    ) -> Result<ProtoField, CubeError> {
+        let name = self.expect_ident()?;
+        self.expect_symbol('=')?;
+        let number = self.expect_number()?;
+        let number = number.parse::<u64>().map_err(|_| {
+            CubeError::user(format!(
+                "Invalid number {} of protobuf field {}",
+                number, name
+            ))
+        })?;
+        // Field options, e.g. [packed = false]
+        self.skip_statement()?;
+        Ok(ProtoField {
+            name,
+            number,
+            label,
+            field_type,
+            in_oneof,
+        })
+    }
+
+    fn parse_enum(&mut self, scope: Option<&str>) -> Result<(), CubeError> {
+        let full_name = qualify(scope, &self.expect_ident()?);
+        self.expect_symbol('{')?;
+        let mut values = Vec::new();
+        loop {
+            match self.next() {
+                Some(Token::Symbol('}')) => break,
+                Some(Token::Symbol(';')) => {}
+                Some(Token::Ident(i)) if i == "option" || i == "reserved" => {
+                    self.skip_statement()?
+                }
+                Some(Token::Ident(symbol)) => {
+                    self.expect_symbol('=')?;
+                    let number = self.expect_number()?;
+                    let number = parse_int(&number).ok_or_else(|| {
+                        CubeError::user(format!(
+                            "Invalid value {} of protobuf enum {}",
+                            number, full_name
+                        ))
+                    })?;
+                    self.skip_statement()?;
+                    values.push((number, symbol));
+                }
+                t => {
+                    return Err(CubeError::user(format!(
+                        "Unexpected {:?} in protobuf enum {}",
+                        t, full_name
+                    )))
+                }
+            }
+        }
+        self.schema.enums.insert(full_name, values);
+        Ok(())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect_ident(&mut self) -> Result<String, CubeError> {
+        match self.next() {
+            Some(Token::Ident(i)) => Ok(i),
+            t => Err(CubeError::user(format!(
+                "Expected identifier in protobuf schema but {:?} found",
+                t
+            ))),
+        }
+    }
+
+    fn expect_number(&mut self) -> Result<String, CubeError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            t => Err(CubeError::user(format!(
+                "Expected number in protobuf schema but {:?} found",
+                t
+            ))),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), CubeError> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            t => Err(CubeError::user(format!(
+                "Expected '{}' in protobuf schema but {:?} found",
+                symbol, t
+            ))),
+        }
+    }
+
+    /// Skips tokens up to `;` outside of brackets.
+    fn skip_statement(&mut self) -> Result<(), CubeError> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some(Token::Symbol('{')) | Some(Token::Symbol('[')) | Some(Token::Symbol('(')) => {
+                    depth += 1
+                }
+                Some(Token::Symbol('}')) | Some(Token::Symbol(']')) | Some(Token::Symbol(')')) => {
+                    depth -= 1
+                }
+                Some(Token::Symbol(';')) if depth == 0 => return Ok(()),
+                Some(_) => {}
+                None => {
+                    return Err(CubeError::user(
+                        "Unexpected end of protobuf schema".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn skip_block(&mut self) -> Result<(), CubeError> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some(Token::Symbol('{')) => depth += 1,
+                Some(Token::Symbol('}')) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    return Err(CubeError::user(
+                        "Unexpected end of protobuf schema".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+fn qualify(scope: Option<&str>, name: &str) -> String {
+    match scope {
+        Some(scope) => format!("{}.{}", scope, name),
+        None => name.to_string(),
+    }
+}
+
+fn parse_int(value: &str) -> Option<i64> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let value = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i64>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn field_type(name: &str) -> ProtoFieldType {
+    match name {
+        "double" => ProtoFieldType::Double,
+        "float" => ProtoFieldType::Float,
+        "int32" => ProtoFieldType::Int32,
+        "int64" => ProtoFieldType::Int64,
+        "uint32" => ProtoFieldType::Uint32,
+        "uint64" => ProtoFieldType::Uint64,
+        "sint32" => ProtoFieldType::Sint32,
+        "sint64" => ProtoFieldType::Sint64,
+        "fixed32" => ProtoFieldType::Fixed32,
+        "fixed64" => ProtoFieldType::Fixed64,
+        "sfixed32" => ProtoFieldType::Sfixed32,
+        "sfixed64" => ProtoFieldType::Sfixed64,
+        "bool" => ProtoFieldType::Bool,
+        "string" => ProtoFieldType::String,
+        "bytes" => ProtoFieldType::Bytes,
+        name => ProtoFieldType::Unresolved(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+        loop {
+            if value < 0x80 {
+                out.push(value as u8);
+                return;
+            }
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+    }
+
+    pub fn encode_len_delimited(number: u64, value: &[u8], out: &mut Vec<u8>) {
+        encode_varint(number << 3 | 2, out);
+        encode_varint(value.len() as u64, out);
+        out.extend_from_slice(value);
+    }
+
+    const SCHEMA: &str = r#"
+        syntax = "proto3";
+        package com.example;
+
+        import "google/protobuf/timestamp.proto";
+        import "google/protobuf/wrappers.proto";
+
+        option java_package = "com.example.proto";
+
+        /* Unrelated message goes first */
+        message Other {
+            string value = 1;
+        }
+
+        message Event {
+            enum Kind {
+                KIND_UNSPECIFIED = 0;
+                CLICK = 1;
+            }
+            message Item {
+                string sku = 1;
+                int64 count = 2;
+            }
+
+            int64 id = 1;
+            string name = 2 [deprecated = true];
+            Kind kind = 3;
+            repeated int32 scores = 4;
+            repeated Item items = 5;
+            map<string, int64> counters = 6;
+            google.protobuf.Timestamp at = 7;
+            google.protobuf.StringValue note = 8;
+            oneof source {
+                string url = 9;
+                sint64 delta = 10;
+            }
+            double ratio = 11;
+            bool flag = 12;
+            reserved 13, 14;
+        }
+    "#;
+
+    #[test]
+    fn decode_message() {
+        let schema = ProtobufSchema::parse(SCHEMA).unwrap();
+
+        let mut data = Vec::new();
+        // Message indexes: [1], i.e. the second top level message
+        encode_varint(2, &mut data);
+        encode_varint(2, &mut data);
+        // id = -5
+        encode_varint(1 << 3, &mut data);
+        encode_varint(-5i64 as u64, &mut data);
+        encode_len_delimited(2, b"foo", &mut data);
+        // kind = CLICK
+        encode_varint(3 << 3, &mut data);
+        encode_varint(1, &mut data);
+        // packed scores
+        encode_len_delimited(4, &[1, 2, 3], &mut data);
+        let mut item = Vec::new();
+        encode_len_delimited(1, b"sku-1", &mut item);
+        encode_len_delimited(5, &item, &mut data);
+        let mut entry = Vec::new();
+        encode_len_delimited(1, b"a", &mut entry);
+        encode_varint(2 << 3, &mut entry);
+        encode_varint(7, &mut entry);
+        encode_len_delimited(6, &entry, &mut data);
+        let mut at = Vec::new();
+        encode_varint(1 << 3, &mut at);
+        encode_varint(1_685_934_000, &mut at);
+        encode_varint(2 << 3, &mut at);
+        encode_varint(300_000_000, &mut at);
+        encode_len_delimited(7, &at, &mut data);
+        let mut note = Vec::new();
+        encode_len_delimited(1, b"bar", &mut note);
+        encode_len_delimited(8, &note, &mut data);
+        // delta = -3
+        encode_varint(10 << 3, &mut data);
+        encode_varint(5, &mut data);
+        // unknown field
+        encode_varint(99 << 3 | 5, &mut data);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+
+        let value = schema.decode_with_message_indexes(&data).unwrap();
+        assert_eq!(value["id"], -5);
+        assert_eq!(value["name"], "foo");
+        assert_eq!(value["kind"], "CLICK");
+        assert_eq!(value["scores"], json::array![1, 2, 3]);
+        assert_eq!(value["items"][0]["sku"], "sku-1");
+        // Default values of proto3 fields
+        assert_eq!(value["items"][0]["count"], 0);
+        assert_eq!(value["counters"]["a"], 7);
+        assert_eq!(value["at"], "2023-06-05T03:00:00.300Z");
+        assert_eq!(value["note"], "bar");
+        assert_eq!(value["delta"], -3);
+        assert!(value["url"].is_null());
+        assert_eq!(value["ratio"], 0);
+        assert_eq!(value["flag"], false);
+
+        // Single zero byte refers to the first message
+        let mut other = vec![0];
+        encode_len_delimited(1, b"baz", &mut other);
+        let value = schema.decode_with_message_indexes(&other).unwrap();
+        assert_eq!(value["value"], "baz");
+
+        assert!(schema.decode_with_message_indexes(&[2, 10, 0]).is_err());
+        // 1000 message indexes in a 3 byte message
+        let err = schema
+            .decode_with_message_indexes(&[0xd0, 0x0f, 0])
+            .unwrap_err();
+        assert!(err.message.contains("message index count"), "{}", err);
+    }
+
+    #[test]
+    fn nested_message_indexes() {
+        let schema = ProtobufSchema::parse(SCHEMA).unwrap();
+        assert_eq!(
+            schema.message_by_indexes(&[1, 0]).unwrap().full_name,
+            "com.example.Event.Item"
+        );
+        assert!(schema.message_by_indexes(&[2]).is_err());
+    }
+
+    #[test]
+    fn parse_with_imports() {
+        let common = r#"
+            syntax = "proto3";
+            package com.example.common;
+
+            message Money {
+                string currency = 1;
+                int64 amount = 2;
+            }
+        "#;
+        let schema = r#"
+            syntax = "proto2";
+            package com.example;
+
+            import "common.proto";
+
+            message Order {
+                optional string id = 1;
+                optional com.example.common.Money total = 2;
+            }
+        "#;
+        assert!(ProtobufSchema::parse(schema).is_err());
+
+        let schema = ProtobufSchema::parse_with_imports(schema, &[common.to_string()]).unwrap();
+        // Messages of imports are not addressed by message indexes
+        assert!(schema.message_by_indexes(&[1]).is_err());
+
+        let mut total = Vec::new();
+        encode_len_delimited(1, b"USD", &mut total);
+        let mut data = vec![0];
+        encode_len_delimited(2, &total, &mut data);
+        let value = schema.decode_with_message_indexes(&data).unwrap();
+        // Proto2 fields of the schema stay null, proto3 fields of the import get defaults
+        assert!(value["id"].is_null());
+        assert_eq!(value["total"]["currency"], "USD");
+        assert_eq!(value["total"]["amount"], 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(ProtobufSchema::parse("message A { Unknown b = 1; }").is_err());
+        assert!(ProtobufSchema::parse("message A { string b = 1;").is_err());
+        assert!(ProtobufSchema::parse("message A { string b = x; }").is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::config::injection::DIService;
+use crate::metastore::source::SchemaRegistryConfig;
+use crate::streaming::avro::AvroSchema;
+use crate::streaming::protobuf::ProtobufSchema;
+use crate::CubeError;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[derive(Debug)]
+pub enum RegistrySchema {
+    Avro(AvroSchema),
+    Protobuf(ProtobufSchema),
+    Json,
+}
+
+/// Client of a Confluent-compatible schema registry.
+#[async_trait]
+pub trait SchemaRegistryClient: DIService + Send + Sync {
+    async fn schema_by_id(
+        &self,
+        registry: &SchemaRegistryConfig,
+        id: u32,
+    ) -> Result<Arc<RegistrySchema>, CubeError>;
+
+    /// Latest version of the subject schema, `None` if the subject isn't registered.
+    async fn latest_schema(
+        &self,
+        registry: &SchemaRegistryConfig,
+        subject: &str,
+    ) -> Result<Option<Arc<RegistrySchema>>, CubeError>;
+}
+
+#[derive(Deserialize)]
+struct SchemaResponse {
+    id: Option<u32>,
+    schema: String,
+    #[serde(rename = "schemaType")]
+    schema_type: Option<String>,
+    #[serde(default)]
+    references: Vec<SchemaReference>,
+}
+
+/// Reference to a schema of another subject: a named avro type or an imported `.proto` file.
+#[derive(Deserialize, Clone)]
+struct SchemaReference {
+    subject: String,
+    version: i64,
+}
+
+pub struct SchemaRegistryClientImpl {
+    client: reqwest::Client,
+    /// Registered schemas never change, so they are cached by registry url and id.
+    schemas: RwLock<HashMap<(String, u32), Arc<RegistrySchema>>>,
+}
+
+crate::di_service!(SchemaRegistryClientImpl, [SchemaRegistryClient]);
+
+impl SchemaRegistryClientImpl {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            client: reqwest::Client::new(),
+            schemas: RwLock::new(HashMap::new()),
+        })
+    }
+
+    async fn get(
+        &self,
+        registry: &SchemaRegistryConfig,
+        path: &str,
+    ) -> Result<Option<SchemaResponse>, CubeError> {
+        let url = format!("{}{}", registry.url.trim_end_matches('/'), path);
+        let mut builder = self
+            .client
+            .get(&url)
+            .header("Accept", "application/vnd.schemaregistry.v1+json");
+        if let Some(user) = &registry.user {
+            builder = builder.basic_auth(user, registry.password.as_ref());
+        }
+        let res = builder.send().await?;
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(CubeError::user(format!(
+                "Schema registry request {} failed with {}: {}",
+                url, status, body
+            )));
+        }
+        Ok(Some(res.json::<SchemaResponse>().await?))
+    }
+
+    /// Sources of the schemas referenced by `response`, including transitive references, in the
+    /// order the dependencies go before their dependents.
+    async fn referenced_schemas(
+        &self,
+        registry: &SchemaRegistryConfig,
+        response: &SchemaResponse,
+    ) -> Result<Vec<String>, CubeError> {
+        let mut sources = Vec::new();
+        let mut visited = HashSet::new();
+        // Responses are pushed back after their references, so they are taken once the
+        // references are added to `sources`
+        let mut stack = response
+            .references
+            .iter()
+            .rev()
+            .map(|r| (r.clone(), None))
+            .collect::<Vec<(SchemaReference, Option<SchemaResponse>)>>();
+        while let Some((reference, fetched)) = stack.pop() {
+            if let Some(fetched) = fetched {
+                sources.push(fetched.schema);
+                continue;
+            }
+            if !visited.insert((reference.subject.clone(), reference.version)) {
+                continue;
+            }
+            let fetched = self
+                .get(
+                    registry,
+                    &format!(
+                        "/subjects/{}/versions/{}",
+                        reference.subject, reference.version
+                    ),
+                )
+                .await?
+                .ok_or_else(|| {
+                    CubeError::user(format!(
+                        "Referenced schema {} version {} is not found in the registry",
+                        reference.subject, reference.version
+                    ))
+                })?;
+            let references = fetched.references.clone();
+            stack.push((reference, Some(fetched)));
+            stack.extend(references.into_iter().rev().map(|r| (r, None)));
+        }
+        Ok(sources)
+    }
+
+    async fn cache_schema(
+        &self,
+        registry: &SchemaRegistryConfig,
+        id: u32,
+        response: SchemaResponse,
+    ) -> Result<Arc<RegistrySchema>, CubeError> {
+        let references = self.referenced_schemas(registry, &response).await?;
+        // Avro is the default type, registry omits it
+        let schema = Arc::new(match response.schema_type.as_deref().unwrap_or("AVRO") {
+            "AVRO" => RegistrySchema::Avro(AvroSchema::parse_with_references(
+                &response.schema,
+                &references,
+            )?),
+            "PROTOBUF" => RegistrySchema::Protobuf(ProtobufSchema::parse_with_imports(
+                &response.schema,
+                &references,
+            )?),
+            "JSON" => RegistrySchema::Json,
+            x => {
+                return Err(CubeError::user(format!(
+                    "Unsupported schema type {} of schema {}",
+                    x, id
+                )))
+            }
+        });
+        self.schemas
+            .write()
+            .await
+            .insert((registry.url.clone(), id), schema.clone());
+        Ok(schema)
+    }
+}
+
+#[async_trait]
+impl SchemaRegistryClient for SchemaRegistryClientImpl {
+    async fn schema_by_id(
+        &self,
+        registry: &SchemaRegistryConfig,
+        id: u32,
+    ) -> Result<Arc<RegistrySchema>, CubeError> {
+        if let Some(schema) = self.schemas.read().await.get(&(registry.url.clone(), id)) {
+            return Ok(schema.clone());
+        }
+
+        let response = self
+            .get(registry, &format!("/schemas/ids/{}", id))
+            .await?
+            .ok_or_else(|| {
+                CubeError::user(format!("Schema {} is not found in the registry", id))
+            })?;
+        self.cache_schema(registry, id, response).await
+    }
+
+    async fn latest_schema(
+        &self,
+        registry: &SchemaRegistryConfig,
+        subject: &str,
+    ) -> Result<Option<Arc<RegistrySchema>>, CubeError> {
+        let response = match self
+            .get(registry, &format!("/subjects/{}/versions/latest", subject))
+            .await?
+        {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        let id = response.id.ok_or_else(|| {
+            CubeError::user(format!(
+                "Schema registry returned no id for the latest version of {}",
+                subject
+            ))
+        })?;
+        Ok(Some(self.cache_schema(registry, id, response).await?))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use warp::Filter;
+
+    pub struct MockRegistrySchema {
+        pub id: u32,
+        pub subject: &'static str,
+        pub schema_type: &'static str,
+        pub schema: &'static str,
+        /// Subjects of the referenced schemas, the mock registers a single version of a subject.
+        pub references: Vec<&'static str>,
+    }
+
+    /// Serves schemas the same way as the Confluent registry. Returns the url of the registry
+    /// and the counter of requests.
+    pub async fn start_mock_registry(
+        schemas: Vec<MockRegistrySchema>,
+    ) -> (String, Arc<AtomicUsize>) {
+        let schemas = Arc::new(schemas);
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let to_response = |s: &MockRegistrySchema| {
+            warp::reply::json(&serde_json::json!({
+                "id": s.id,
+                "subject": s.subject,
+                "version": 1,
+                "schemaType": s.schema_type,
+                "schema": s.schema,
+                "references": s.references.iter().map(|r| serde_json::json!({
+                    "name": r,
+                    "subject": r,
+                    "version": 1,
+                })).collect::<Vec<_>>(),
+            }))
+        };
+        let not_found = || {
+            warp::reply::with_status(
+                warp::reply::json(
+                    &serde_json::json!({"error_code": 40403, "message": "Not found"}),
+                ),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+        };
+
+        let (by_id_schemas, by_id_requests) = (schemas.clone(), requests.clone());
+        let by_id = warp::path!("schemas" / "ids" / u32).map(move |id: u32| {
+            by_id_requests.fetch_add(1, Ordering::SeqCst);
+            match by_id_schemas.iter().find(|s| s.id == id) {
+                Some(s) => warp::reply::with_status(to_response(s), warp::http::StatusCode::OK),
+                None => not_found(),
+            }
+        });
+        let (latest_schemas, latest_requests) = (schemas.clone(), requests.clone());
+        let latest =
+            warp::path!("subjects" / String / "versions" / "latest").map(move |subject: String| {
+                latest_requests.fetch_add(1, Ordering::SeqCst);
+                match latest_schemas.iter().rev().find(|s| s.subject == subject) {
+                    Some(s) => warp::reply::with_status(to_response(s), warp::http::StatusCode::OK),
+                    None => not_found(),
+                }
+            });
+
+        let (version_schemas, version_requests) = (schemas.clone(), requests.clone());
+        let by_version = warp::path!("subjects" / String / "versions" / u32).map(
+            move |subject: String, version: u32| {
+                version_requests.fetch_add(1, Ordering::SeqCst);
+                match version_schemas
+                    .iter()
+                    .find(|s| s.subject == subject && version == 1)
+                {
+                    Some(s) => warp::reply::with_status(to_response(s), warp::http::StatusCode::OK),
+                    None => not_found(),
+                }
+            },
+        );
+
+        let (addr, server) = warp::serve(by_id.or(latest).unify().or(by_version).unify())
+            .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}", addr), requests)
+    }
+
+    #[tokio::test]
+    async fn schemas_are_cached() {
+        let (url, requests) = start_mock_registry(vec![
+            MockRegistrySchema {
+                id: 1,
+                subject: "events-value",
+                schema_type: "AVRO",
+                schema: r#"{"type": "record", "name": "E", "fields": [{"name": "a", "type": "long"}]}"#,
+                references: vec![],
+            },
+            MockRegistrySchema {
+                id: 2,
+                subject: "events-key",
+                schema_type: "PROTOBUF",
+                schema: r#"syntax = "proto3"; message K { string id = 1; }"#,
+                references: vec![],
+            },
+        ])
+        .await;
+        let registry = SchemaRegistryConfig {
+            url: format!("{}/", url),
+            user: None,
+            password: None,
+        };
+        let client = SchemaRegistryClientImpl::new();
+
+        for _ in 0..3 {
+            let schema = client.schema_by_id(&registry, 1).await.unwrap();
+            assert!(matches!(schema.as_ref(), RegistrySchema::Avro(_)));
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let schema = client.latest_schema(&registry, "events-key").await.unwrap();
+        assert!(matches!(
+            schema.as_deref(),
+            Some(RegistrySchema::Protobuf(_))
+        ));
+        client.schema_by_id(&registry, 2).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        assert!(client
+            .latest_schema(&registry, "unknown-value")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            client
+                .schema_by_id(&registry, 3)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Schema 3 is not found in the registry"
+        );
+    }
+
+    #[tokio::test]
+    async fn schema_references() {
+        let (url, requests) = start_mock_registry(vec![
+            MockRegistrySchema {
+                id: 1,
+                subject: "money",
+                schema_type: "AVRO",
+                schema: r#"{"type": "record", "name": "Money", "fields": [{"name": "amount", "type": "long"}]}"#,
+                references: vec![],
+            },
+            MockRegistrySchema {
+                id: 2,
+                subject: "item",
+                schema_type: "AVRO",
+                schema: r#"{"type": "record", "name": "Item", "fields": [{"name": "price", "type": "Money"}]}"#,
+                references: vec!["money"],
+            },
+            MockRegistrySchema {
+                id: 3,
+                subject: "orders-value",
+                schema_type: "AVRO",
+                schema: r#"{"type": "record", "name": "Order", "fields": [
+                    {"name": "item", "type": "Item"},
+                    {"name": "total", "type": "Money"}
+                ]}"#,
+                references: vec!["item", "money"],
+            },
+            MockRegistrySchema {
+                id: 4,
+                subject: "broken-value",
+                schema_type: "AVRO",
+                schema: r#"{"type": "record", "name": "B", "fields": [{"name": "a", "type": "A"}]}"#,
+                references: vec!["unknown"],
+            },
+        ])
+        .await;
+        let registry = SchemaRegistryConfig {
+            url,
+            user: None,
+            password: None,
+        };
+        let client = SchemaRegistryClientImpl::new();
+
+        let schema = client.schema_by_id(&registry, 3).await.unwrap();
+        assert!(matches!(schema.as_ref(), RegistrySchema::Avro(_)));
+        // Schema itself and each referenced subject once
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        assert_eq!(
+            client
+                .schema_by_id(&registry, 4)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Referenced schema unknown version 1 is not found in the registry"
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::metastore::{Column, ColumnType};
 use crate::queryplanner::try_make_memory_data_source;
-use crate::table::{Row, TableValue, TimestampValue};
+use crate::table::{DateValue, IntervalValue, Row, TableValue, TimestampValue};
 use crate::util::decimal::{Decimal, Decimal96};
 use crate::util::int96::Int96;
 use itertools::Itertools;
@@ -27,6 +27,9 @@ pub enum TableValueR<'a> {
     Bytes(&'a [u8]),
     Timestamp(TimestampValue),
     Boolean(bool),
+    Date(DateValue),
+    Interval(IntervalValue),
+    List(&'a [TableValue]),
 }
 
 impl TableValueR<'_> {
@@ -42,6 +45,9 @@ impl TableValueR<'_> {
             TableValue::Bytes(b) => TableValueR::Bytes(&b),
             TableValue::Timestamp(v) => TableValueR::Timestamp(v.clone()),
             TableValue::Boolean(v) => TableValueR::Boolean(*v),
+            TableValue::Date(v) => TableValueR::Date(*v),
+            TableValue::Interval(v) => TableValueR::Interval(*v),
+            TableValue::List(v) => TableValueR::List(&v),
         }
     }
 }
@@ -132,6 +138,12 @@ pub fn cmp_same_types(l: &TableValueR, r: &TableValueR) -> Ordering {
         (TableValueR::Bytes(a), TableValueR::Bytes(b)) => a.cmp(b),
         (TableValueR::Timestamp(a), TableValueR::Timestamp(b)) => a.cmp(b),
         (TableValueR::Boolean(a), TableValueR::Boolean(b)) => a.cmp(b),
+        (TableValueR::Date(a), TableValueR::Date(b)) => a.cmp(b),
+        (TableValueR::Interval(a), TableValueR::Interval(b)) => a.cmp(b),
+        (TableValueR::List(a), TableValueR::List(b)) => {
+            let key_size = a.len().min(b.len());
+            cmp_row_key_heap(key_size, a, b).then(a.len().cmp(&b.len()))
+        }
         (a, b) => panic!("Can't compare {:?} to {:?}", a, b),
     }
 }
@@ -147,6 +159,8 @@ macro_rules! match_column_type {
             ColumnType::Int96 => $matcher!(Int96, Decimal128Builder, Int96),
             ColumnType::Bytes => $matcher!(Bytes, BinaryBuilder, Bytes),
             ColumnType::HyperLogLog(_) => $matcher!(HyperLogLog, BinaryBuilder, Bytes),
+            ColumnType::TDigest => $matcher!(TDigest, BinaryBuilder, Bytes),
+            ColumnType::ThetaSketch => $matcher!(ThetaSketch, BinaryBuilder, Bytes),
             ColumnType::Timestamp => $matcher!(Timestamp, TimestampMicrosecondBuilder, Timestamp),
             ColumnType::Boolean => $matcher!(Boolean, BooleanBuilder, Boolean),
             // scale and precision are used when creating but not when appending, hence underscore here.
@@ -157,6 +171,10 @@ macro_rules! match_column_type {
                 $matcher!(Decimal96, Decimal128Builder, Decimal96, _scale, _precision)
             }
             ColumnType::Float => $matcher!(Float, Float64Builder, Float),
+            ColumnType::Date => $matcher!(Date, Date32Builder, Date),
+            ColumnType::Interval => $matcher!(Interval, DurationMicrosecondBuilder, Interval),
+            ColumnType::Json => $matcher!(Json, StringBuilder, String),
+            ColumnType::List(_element) => $matcher!(List, ListBuilder, List, _element),
         }
     }};
 }
@@ -185,6 +203,9 @@ pub fn create_array_builder(t: &ColumnType) -> Box<dyn ArrayBuilder> {
                     .with_data_type(datafusion::arrow::datatypes::DataType::Decimal128(38, 0)),
             )
         };
+        ($type: tt, ListBuilder, List, $element: expr) => {
+            Box::new(ListBuilder::new(create_array_builder($element)))
+        };
         ($type: tt, $builder: tt $(,$arg: tt)*) => {
             Box::new($builder::new())
         };
@@ -224,6 +245,12 @@ pub fn append_value(b: &mut dyn ArrayBuilder, c: &ColumnType, v: &TableValue) {
         (Timestamp, $v: expr) => {{
             $v.get_time_stamp() / 1000
         }}; // Nanoseconds to microseconds.
+        (Date, $v: expr) => {{
+            $v.get_days()
+        }};
+        (Interval, $v: expr) => {{
+            $v.get_micros()
+        }};
         (String, $v: expr) => {{
             $v.as_str()
         }};
@@ -235,6 +262,24 @@ pub fn append_value(b: &mut dyn ArrayBuilder, c: &ColumnType, v: &TableValue) {
         }};
     }
     macro_rules! append {
+        ($type: tt, ListBuilder, List, $element: expr) => {{
+            let b = b
+                .as_any_mut()
+                .downcast_mut::<ListBuilder<Box<dyn ArrayBuilder>>>()
+                .unwrap();
+            if is_null {
+                b.append_null();
+                return;
+            }
+            let v = match v {
+                TableValue::List(v) => v,
+                other => panic!("unexpected value {:?} for type {:?}", other, c),
+            };
+            for item in v {
+                append_value(b.values().as_mut(), $element, item);
+            }
+            b.append(true);
+        }};
         ($type: tt, $builder: tt, $tv_enum: tt $(, $arg:tt)*) => {{
             let b = b.as_any_mut().downcast_mut::<$builder>().unwrap();
             if is_null {
@@ -3,15 +3,17 @@ use crate::util::decimal::{Decimal, Decimal96};
 use crate::util::int96::Int96;
 
 use datafusion::arrow::array::{
-    Array, ArrayRef, BinaryArray, BooleanArray, Decimal128Array, Float64Array, Int64Array,
-    StringArray, TimestampMicrosecondArray,
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Decimal128Array,
+    DurationMicrosecondArray, Float64Array, Int64Array, ListArray, StringArray,
+    TimestampMicrosecondArray,
 };
 use datafusion::arrow::datatypes::{DataType, TimeUnit};
 
 use crate::cube_ext::ordfloat::OrdF64;
-use chrono::{SecondsFormat, TimeZone, Utc};
+use chrono::{Duration, NaiveDate, SecondsFormat, TimeZone, Utc};
 use deepsize::{Context, DeepSizeOf};
 use itertools::Itertools;
+use json::JsonValue;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt;
@@ -33,6 +35,9 @@ pub enum TableValue {
     Bytes(Vec<u8>),
     Timestamp(TimestampValue),
     Boolean(bool),
+    Date(DateValue),
+    Interval(IntervalValue),
+    List(Vec<TableValue>),
 }
 
 impl DeepSizeOf for TableValue {
@@ -48,6 +53,9 @@ impl DeepSizeOf for TableValue {
             TableValue::Bytes(v) => v.deep_size_of_children(context),
             TableValue::Timestamp(_) => 0,
             TableValue::Boolean(_) => 0,
+            TableValue::Date(_) => 0,
+            TableValue::Interval(_) => 0,
+            TableValue::List(v) => v.deep_size_of_children(context),
         }
     }
 }
@@ -112,6 +120,23 @@ impl TableValue {
                     .unwrap()
                     .value(row),
             ),
+            DataType::Date32 => TableValue::Date(DateValue::new(
+                a.as_any().downcast_ref::<Date32Array>().unwrap().value(row),
+            )),
+            DataType::Duration(TimeUnit::Microsecond) => TableValue::Interval(IntervalValue::new(
+                a.as_any()
+                    .downcast_ref::<DurationMicrosecondArray>()
+                    .unwrap()
+                    .value(row),
+            )),
+            DataType::List(_) => {
+                let list = a.as_any().downcast_ref::<ListArray>().unwrap().value(row);
+                TableValue::List(
+                    (0..list.len())
+                        .map(|i| TableValue::from_array(list.as_ref(), i))
+                        .collect(),
+                )
+            }
             other => panic!(
                 "unexpected array type when converting to TableValue: {:?}",
                 other
@@ -119,6 +144,20 @@ impl TableValue {
         }
     }
 
+    /// JSON representation, used to render lists. Values without a JSON counterpart are rendered
+    /// as strings.
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            TableValue::Null => JsonValue::Null,
+            TableValue::String(v) => JsonValue::String(v.clone()),
+            TableValue::Int(v) => JsonValue::from(*v),
+            TableValue::Float(v) => JsonValue::from(v.0),
+            TableValue::Boolean(v) => JsonValue::Boolean(*v),
+            TableValue::List(v) => JsonValue::Array(v.iter().map(|v| v.to_json()).collect()),
+            v => JsonValue::String(v.to_string()),
+        }
+    }
+
     /// Render the value as a string, using `column_type` for context-dependent
     /// variants (currently `Decimal` / `Decimal96`, where scale lives on the
     /// column rather than on the value). Falls back to `Display` otherwise.
@@ -155,6 +194,9 @@ impl fmt::Display for TableValue {
             }
             TableValue::Timestamp(v) => f.write_str(&v.to_string()),
             TableValue::Boolean(v) => write!(f, "{}", v),
+            TableValue::Date(v) => write!(f, "{}", v),
+            TableValue::Interval(v) => write!(f, "{}", v),
+            TableValue::List(_) => f.write_str(&self.to_json().dump()),
         }
     }
 }
@@ -193,6 +235,101 @@ impl ToString for TimestampValue {
     }
 }
 
+/// Calendar date stored as the number of days since the Unix epoch.
+#[derive(Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DateValue {
+    days: i32,
+}
+
+impl DateValue {
+    pub fn new(days: i32) -> DateValue {
+        DateValue { days }
+    }
+
+    pub fn get_days(&self) -> i32 {
+        self.days
+    }
+
+    pub fn to_naive_date(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(1970, 1, 1).unwrap() + Duration::days(self.days as i64)
+    }
+}
+
+impl Debug for DateValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DateValue")
+            .field("days", &self.days)
+            .field("str", &self.to_string())
+            .finish()
+    }
+}
+
+impl fmt::Display for DateValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_naive_date().format("%Y-%m-%d"))
+    }
+}
+
+/// Fixed-length interval stored in microseconds. Months and years have no fixed length and
+/// can't be represented.
+#[derive(Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct IntervalValue {
+    micros: i64,
+}
+
+impl IntervalValue {
+    pub const MICROS_PER_SECOND: i64 = 1_000_000;
+    pub const MICROS_PER_DAY: i64 = 86_400 * Self::MICROS_PER_SECOND;
+
+    pub fn new(micros: i64) -> IntervalValue {
+        IntervalValue { micros }
+    }
+
+    pub fn get_micros(&self) -> i64 {
+        self.micros
+    }
+}
+
+impl Debug for IntervalValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntervalValue")
+            .field("micros", &self.micros)
+            .field("str", &self.to_string())
+            .finish()
+    }
+}
+
+/// Postgres-style output, e.g. `1 day 02:03:04.5` or `-3 days -00:00:01`. Days and the time part
+/// carry their own signs so the text parses back to the same value.
+impl fmt::Display for IntervalValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let days = self.micros / Self::MICROS_PER_DAY;
+        let time = self.micros % Self::MICROS_PER_DAY;
+        if days != 0 {
+            write!(f, "{} {}", days, if days == 1 { "day" } else { "days" })?;
+            if time == 0 {
+                return Ok(());
+            }
+            f.write_str(" ")?;
+        }
+        let abs = time.unsigned_abs();
+        let secs = abs / Self::MICROS_PER_SECOND as u64;
+        let fraction = abs % Self::MICROS_PER_SECOND as u64;
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}",
+            if time < 0 { "-" } else { "" },
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        if fraction != 0 {
+            f.write_str(format!(".{:06}", fraction).trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash, DeepSizeOf, PartialOrd)]
 pub struct Row {
     values: Vec<TableValue>,
@@ -228,13 +365,24 @@ pub fn cmp_same_types(l: &TableValue, r: &TableValue) -> Ordering {
         (TableValue::Bytes(a), TableValue::Bytes(b)) => a.cmp(b),
         (TableValue::Timestamp(a), TableValue::Timestamp(b)) => a.cmp(b),
         (TableValue::Boolean(a), TableValue::Boolean(b)) => a.cmp(b),
+        (TableValue::Date(a), TableValue::Date(b)) => a.cmp(b),
+        (TableValue::Interval(a), TableValue::Interval(b)) => a.cmp(b),
+        (TableValue::List(a), TableValue::List(b)) => {
+            for (a, b) in a.iter().zip(b.iter()) {
+                let o = cmp_same_types(a, b);
+                if o != Ordering::Equal {
+                    return o;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
         (a, b) => panic!("Can't compare {:?} to {:?}", a, b),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::table::{TableValue, TimestampValue};
+    use crate::table::{DateValue, IntervalValue, TableValue, TimestampValue};
     use crate::util::decimal::Decimal;
     use deepsize::DeepSizeOf;
     use serde::{Deserialize, Serialize};
@@ -250,6 +398,9 @@ mod tests {
             TableValue::Bytes(vec![1, 2, 3]),
             TableValue::Timestamp(TimestampValue::new(123)),
             TableValue::Boolean(false),
+            TableValue::Date(DateValue::new(-123)),
+            TableValue::Interval(IntervalValue::new(123)),
+            TableValue::List(vec![TableValue::Int(1), TableValue::Null]),
         ] {
             let b = bincode::serialize(v).expect(&format!("could not serialize {:?}", v));
             let v2: TableValue =
@@ -4,15 +4,17 @@ use crate::metastore::{IdRow, Index};
 use crate::queryplanner::metadata_cache::MetadataCacheFactory;
 use crate::CubeError;
 use async_trait::async_trait;
-use datafusion::arrow::array::ArrayRef;
-use datafusion::arrow::datatypes::{Field, Schema};
-use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::array::{new_null_array, ArrayRef};
+use datafusion::arrow::datatypes::{Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
 use datafusion::datasource::physical_plan::ParquetFileReaderFactory;
 use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::parquet::basic::{BrotliLevel, Compression, GzipLevel, ZstdLevel};
 use datafusion::parquet::file::properties::{
     WriterProperties, WriterPropertiesBuilder, WriterVersion,
 };
+use datafusion::parquet::schema::types::ColumnPath;
 use std::fs::File;
 use std::sync::Arc;
 
@@ -92,9 +94,10 @@ impl ParquetTableStore {
     pub fn read_columns(&self, path: &str) -> Result<Vec<RecordBatch>, CubeError> {
         let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
         let r = builder.with_batch_size(self.row_group_size).build()?;
+        let schema = Arc::new(self.arrow_schema());
         let mut batches = Vec::new();
         for b in r {
-            batches.push(b?)
+            batches.push(reconcile_batch_schema(b?, &schema)?)
         }
         Ok(batches)
     }
@@ -132,13 +135,24 @@ impl ParquetTableStore {
     }
 
     pub async fn writer_props(&self, table: &IdRow<Table>) -> Result<WriterProperties, CubeError> {
+        let mut builder = WriterProperties::builder()
+            .set_max_row_group_size(self.row_group_size)
+            .set_writer_version(WriterVersion::PARQUET_2_0);
+        if let Some(settings) = table.get_row().parquet_settings() {
+            if let Some(compression) = settings.compression() {
+                builder = builder.set_compression(parse_compression(compression)?);
+            }
+            for column in settings.bloom_filter_columns() {
+                let path = ColumnPath::from(column.as_str());
+                // Row groups can't have more distinct values than rows, the default is much
+                // larger and makes filters of small row groups mostly empty.
+                builder = builder
+                    .set_column_bloom_filter_enabled(path.clone(), true)
+                    .set_column_bloom_filter_ndv(path, self.row_group_size as u64);
+            }
+        }
         self.metadata_cache_factory
-            .build_writer_props(
-                table,
-                WriterProperties::builder()
-                    .set_max_row_group_size(self.row_group_size)
-                    .set_writer_version(WriterVersion::PARQUET_2_0),
-            )
+            .build_writer_props(table, builder)
             .await
             .map_err(CubeError::from)
     }
@@ -169,22 +183,101 @@ impl ParquetTableStore {
     }
 }
 
+/// Parses the `compression` table option: a codec name with an optional level in parentheses,
+/// e.g. `snappy` or `zstd(3)`. Codecs without a level use the parquet default one.
+pub fn parse_compression(compression: &str) -> Result<Compression, CubeError> {
+    let compression = compression.trim().to_lowercase();
+    let bad_level = || {
+        CubeError::user(format!(
+            "Bad compression level in '{}'. Expected an integer supported by the codec.",
+            compression
+        ))
+    };
+    let (codec, level) = match compression.split_once('(') {
+        Some((codec, level)) => (
+            codec.trim(),
+            Some(level.strip_suffix(')').ok_or_else(bad_level)?.trim()),
+        ),
+        None => (compression.as_str(), None),
+    };
+    let level_or = |default: u32| -> Result<u32, CubeError> {
+        level.map_or(Ok(default), |l| l.parse::<u32>().map_err(|_| bad_level()))
+    };
+    let no_level = |compression: Compression| {
+        if level.is_some() {
+            Err(CubeError::user(format!(
+                "Compression '{}' doesn't support levels",
+                codec
+            )))
+        } else {
+            Ok(compression)
+        }
+    };
+    match codec {
+        "uncompressed" | "none" => no_level(Compression::UNCOMPRESSED),
+        "snappy" => no_level(Compression::SNAPPY),
+        "lz4" => no_level(Compression::LZ4_RAW),
+        "gzip" => Ok(Compression::GZIP(
+            GzipLevel::try_new(level_or(GzipLevel::default().compression_level())?)
+                .map_err(|_| bad_level())?,
+        )),
+        "brotli" => Ok(Compression::BROTLI(
+            BrotliLevel::try_new(level_or(BrotliLevel::default().compression_level())?)
+                .map_err(|_| bad_level())?,
+        )),
+        "zstd" => Ok(Compression::ZSTD(
+            ZstdLevel::try_new(
+                level_or(ZstdLevel::default().compression_level() as u32)? as i32,
+            )
+            .map_err(|_| bad_level())?,
+        )),
+        _ => Err(CubeError::user(format!(
+            "Unsupported compression '{}'. Expected one of: uncompressed, snappy, lz4, gzip, brotli, zstd",
+            codec
+        ))),
+    }
+}
+
 pub fn arrow_schema(i: &Index) -> Schema {
     Schema::new(i.columns().iter().map(|c| c.into()).collect::<Vec<Field>>())
 }
 
+/// Brings data written before ALTER TABLE to the current index `schema`: columns are matched
+/// by name, added columns are filled with nulls and dropped ones are skipped.
+pub fn reconcile_batch_schema(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+) -> Result<RecordBatch, CubeError> {
+    if batch.schema().fields() == schema.fields() {
+        return Ok(batch);
+    }
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|f| match batch.column_by_name(f.name()) {
+            Some(c) => c.clone(),
+            None => new_null_array(f.data_type(), batch.num_rows()),
+        })
+        .collect::<Vec<ArrayRef>>();
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::assert_eq_columns;
-    use crate::metastore::table::Table;
+    use crate::metastore::table::{Table, TableParquetSettings};
     use crate::metastore::{Column, ColumnType, IdRow, Index};
     use crate::queryplanner::metadata_cache::BasicMetadataCacheFactory;
     use crate::store::{compaction, ROW_GROUP_SIZE};
     use crate::table::data::{cmp_row_key_heap, concat_record_batches, rows_to_columns, to_stream};
     use crate::table::parquet::{
-        arrow_schema, CubestoreMetadataCacheFactoryImpl, ParquetTableStore,
+        arrow_schema, parse_compression, CubestoreMetadataCacheFactoryImpl, ParquetTableStore,
     };
-    use crate::table::{Row, TableValue};
+    use crate::table::{DateValue, IntervalValue, Row, TableValue};
     use crate::util::decimal::Decimal;
     use datafusion::arrow::array::{
         ArrayRef, BooleanArray, Decimal128Array, Float64Array, Int64Array, StringArray,
@@ -192,6 +285,7 @@ mod tests {
     };
     use datafusion::arrow::record_batch::RecordBatch;
     use datafusion::parquet;
+    use datafusion::parquet::basic::{Compression, GzipLevel, ZstdLevel};
     use datafusion::parquet::data_type::DataType;
     use datafusion::parquet::file::reader::FileReader;
     use datafusion::parquet::file::reader::SerializedFileReader;
@@ -297,6 +391,14 @@ mod tests {
     }
 
     fn dummy_table_row(table_id: u64, table_name: &str) -> IdRow<Table> {
+        table_row_with_settings(table_id, table_name, None)
+    }
+
+    fn table_row_with_settings(
+        table_id: u64,
+        table_name: &str,
+        parquet_settings: Option<TableParquetSettings>,
+    ) -> IdRow<Table> {
         IdRow::<Table>::new(
             table_id,
             Table::new(
@@ -316,10 +418,78 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                parquet_settings,
             ),
         )
     }
 
+    #[tokio::test]
+    async fn compression_and_bloom_filters() {
+        let index = Index::try_new(
+            "table".to_string(),
+            1,
+            vec![
+                Column::new("id".to_string(), ColumnType::Int, 0),
+                Column::new("name".to_string(), ColumnType::String, 1),
+            ],
+            1,
+            None,
+            None,
+            Index::index_type_default(),
+        )
+        .unwrap();
+        let table = table_row_with_settings(
+            index.table_id(),
+            index.get_name(),
+            Some(TableParquetSettings::new(
+                Some("zstd(5)".to_string()),
+                vec!["name".to_string()],
+            )),
+        );
+
+        let dest_file = NamedTempFile::new().unwrap();
+        let store = ParquetTableStore::new(
+            index,
+            ROW_GROUP_SIZE,
+            CubestoreMetadataCacheFactoryImpl::new(Arc::new(BasicMetadataCacheFactory::new())),
+        );
+        let data: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["a", "b", "c"])),
+        ];
+        store
+            .write_data(dest_file.path().to_str().unwrap(), data, &table)
+            .await
+            .unwrap();
+
+        let r = SerializedFileReader::new(dest_file.into_file()).unwrap();
+        let columns = r.metadata().row_group(0).columns();
+        assert_eq!(
+            columns[0].compression(),
+            Compression::ZSTD(ZstdLevel::try_new(5).unwrap())
+        );
+        assert_eq!(columns[0].bloom_filter_offset(), None);
+        assert!(columns[1].bloom_filter_offset().is_some());
+    }
+
+    #[test]
+    fn compression_option() {
+        assert_eq!(parse_compression("snappy").unwrap(), Compression::SNAPPY);
+        assert_eq!(parse_compression(" LZ4 ").unwrap(), Compression::LZ4_RAW);
+        assert_eq!(
+            parse_compression("zstd").unwrap(),
+            Compression::ZSTD(ZstdLevel::default())
+        );
+        assert_eq!(
+            parse_compression("gzip(9)").unwrap(),
+            Compression::GZIP(GzipLevel::try_new(9).unwrap())
+        );
+        for bad in ["zstd(100)", "zstd(3", "gzip(x)", "snappy(1)", "lzo", ""] {
+            assert!(parse_compression(bad).is_err(), "{}", bad);
+        }
+    }
+
     #[tokio::test]
     async fn gutter() {
         let store = ParquetTableStore {
@@ -464,6 +634,51 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn date_and_interval_round_trip() {
+        let index = Index::try_new(
+            "table".to_string(),
+            1,
+            vec![
+                Column::new("date".to_string(), ColumnType::Date, 0),
+                Column::new("interval".to_string(), ColumnType::Interval, 1),
+            ],
+            1,
+            None,
+            None,
+            Index::index_type_default(),
+        )
+        .unwrap();
+        let table = dummy_table_row(index.table_id(), index.get_name());
+        let store = ParquetTableStore::new(
+            index.clone(),
+            ROW_GROUP_SIZE,
+            CubestoreMetadataCacheFactoryImpl::new(Arc::new(BasicMetadataCacheFactory::new())),
+        );
+
+        let rows = vec![
+            Row::new(vec![TableValue::Null, TableValue::Null]),
+            Row::new(vec![
+                TableValue::Date(DateValue::new(-1)),
+                TableValue::Interval(IntervalValue::new(-1_500_000)),
+            ]),
+            Row::new(vec![
+                TableValue::Date(DateValue::new(19723)),
+                TableValue::Interval(IntervalValue::new(93_600_000_000)),
+            ]),
+        ];
+        let columns = rows_to_columns(index.columns(), &rows);
+        let file = NamedTempFile::new().unwrap();
+        let file_name = file.path().to_str().unwrap();
+        store
+            .write_data(file_name, columns.clone(), &table)
+            .await
+            .unwrap();
+
+        let read = concat_record_batches(&store.read_columns(file_name).unwrap());
+        assert_eq_columns!(read.columns(), &columns);
+    }
+
     #[tokio::test]
     async fn failed_rle_run_bools() {
         const NUM_ROWS: usize = ROW_GROUP_SIZE;
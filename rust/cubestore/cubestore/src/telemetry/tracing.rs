@@ -1,9 +1,18 @@
 use crate::config::injection::DIService;
 use crate::CubeError;
+use opentelemetry::trace::{
+    SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TracerProvider as _,
+};
+use opentelemetry::{Context as OtelContext, KeyValue};
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::Resource;
 use std::sync::Arc;
 use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
 
-pub type TraceIdAndSpanId = (u128, u64);
+/// Trace id, span id and W3C trace flags of a span.
+pub type TraceIdAndSpanId = (u128, u64, u8);
 
 pub trait TracingHelper: DIService + Send + Sync {
     fn trace_and_span_id(&self) -> Option<TraceIdAndSpanId>;
@@ -17,14 +26,25 @@ pub struct TracingHelperImpl;
 
 impl TracingHelper for TracingHelperImpl {
     fn trace_and_span_id(&self) -> Option<TraceIdAndSpanId> {
-        None
+        let context = Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return None;
+        }
+        Some((
+            u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            u64::from_be_bytes(span_context.span_id().to_bytes()),
+            span_context.trace_flags().to_u8(),
+        ))
     }
 
     fn span_from_existing_trace(
         &self,
-        _trace_id_and_span_id: Option<TraceIdAndSpanId>,
+        trace_id_and_span_id: Option<TraceIdAndSpanId>,
     ) -> Option<Span> {
-        None
+        trace_id_and_span_id
+            .map(|ids| with_remote_parent(tracing::info_span!("Process existing trace"), Some(ids)))
     }
 }
 
@@ -35,3 +55,217 @@ impl TracingHelperImpl {
 }
 
 crate::di_service!(TracingHelperImpl, [TracingHelper]);
+
+/// Makes `span` a child of the span with the ids received from another process. Spans of an
+/// unsampled parent aren't sampled either.
+pub fn with_remote_parent(span: Span, trace_id_and_span_id: Option<TraceIdAndSpanId>) -> Span {
+    if let Some((trace_id, span_id, flags)) = trace_id_and_span_id {
+        let span_context = SpanContext::new(
+            TraceId::from(trace_id),
+            SpanId::from(span_id),
+            TraceFlags::new(flags),
+            true,
+            Default::default(),
+        );
+        span.set_parent(OtelContext::new().with_remote_span_context(span_context));
+    }
+    span
+}
+
+/// Root span of a query received from a client, `trace_parent` is the client span if any.
+pub fn query_span(trace_parent: Option<TraceIdAndSpanId>) -> Span {
+    with_remote_parent(tracing::info_span!("query"), trace_parent)
+}
+
+/// Parses the W3C `traceparent` header: `00-<32 hex trace id>-<16 hex span id>-<2 hex flags>`.
+pub fn parse_traceparent(traceparent: &str) -> Option<TraceIdAndSpanId> {
+    let parts = traceparent.trim().split('-').collect::<Vec<_>>();
+    if parts.len() < 4 {
+        return None;
+    }
+    let (version, trace_id, span_id, flags) = (parts[0], parts[1], parts[2], parts[3]);
+    // Future versions may append fields, version 00 has exactly four
+    if version.len() != 2 || version == "ff" || (version == "00" && parts.len() != 4) {
+        return None;
+    }
+    if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+        return None;
+    }
+    let is_hex = |s: &str| s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+    if ![version, trace_id, span_id, flags]
+        .iter()
+        .all(|s| is_hex(s))
+    {
+        return None;
+    }
+    let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+    let span_id = u64::from_str_radix(span_id, 16).ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    if trace_id == 0 || span_id == 0 {
+        return None;
+    }
+    Some((trace_id, span_id, flags))
+}
+
+pub fn format_traceparent((trace_id, span_id, flags): TraceIdAndSpanId) -> String {
+    format!("00-{:032x}-{:016x}-{:02x}", trace_id, span_id, flags)
+}
+
+/// `traceparent` field of the trace object sent along with http queries.
+pub fn traceparent_from_trace_obj(trace_obj: &str) -> Option<TraceIdAndSpanId> {
+    let trace_obj = serde_json::from_str::<serde_json::Value>(trace_obj).ok()?;
+    parse_traceparent(trace_obj.get("traceparent")?.as_str()?)
+}
+
+/// `traceparent` of the sqlcommenter comment: `/*traceparent='00-...-01'*/ SELECT ...`.
+pub fn traceparent_from_sql_comment(query: &str) -> Option<TraceIdAndSpanId> {
+    let mut rest = query;
+    while let Some(start) = rest.find("/*") {
+        let comment = &rest[start + 2..];
+        let end = comment.find("*/")?;
+        for pair in comment[0..end].split(',') {
+            if let Some((key, value)) = pair.split_once('=') {
+                if key.trim() == "traceparent" {
+                    return parse_traceparent(value.trim().trim_matches('\''));
+                }
+            }
+        }
+        rest = &comment[end + 2..];
+    }
+    None
+}
+
+/// Exports spans to the OTLP (HTTP/protobuf) endpoint set by `CUBESTORE_OTLP_TRACES_ENDPOINT`,
+/// e.g. `http://collector:4318/v1/traces`. Does nothing if the endpoint isn't set. Should be
+/// called within the tokio runtime as spans are exported in batches by a background task.
+pub fn init_otlp_tracing() -> Result<(), CubeError> {
+    let endpoint = match std::env::var("CUBESTORE_OTLP_TRACES_ENDPOINT") {
+        Ok(endpoint) if !endpoint.is_empty() => endpoint,
+        _ => return Ok(()),
+    };
+    let service_name =
+        std::env::var("CUBESTORE_OTLP_SERVICE_NAME").unwrap_or_else(|_| "cubestore".to_string());
+
+    let tracer_provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint.clone())
+                .with_protocol(Protocol::HttpBinary),
+        )
+        .with_trace_config(opentelemetry_sdk::trace::Config::default().with_resource(
+            Resource::new(vec![KeyValue::new("service.name", service_name)]),
+        ))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .map_err(|e| CubeError::internal(format!("Can't initialize OTLP exporter: {}", e)))?;
+    let tracer = tracer_provider.tracer("cubestore");
+    opentelemetry::global::set_tracer_provider(tracer_provider);
+
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| CubeError::internal(format!("Can't set tracing subscriber: {}", e)))?;
+    log::info!("Exporting traces to {}", endpoint);
+    Ok(())
+}
+
+/// Flushes spans that are not exported yet.
+pub fn shutdown_otlp_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::trace::TracerProvider;
+
+    #[test]
+    fn traceparent() {
+        let ids = (0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, 1);
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert_eq!(parse_traceparent(traceparent), Some(ids));
+        assert_eq!(format_traceparent(ids), traceparent);
+        // Unsampled and future versions
+        let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+        assert_eq!(parse_traceparent(unsampled), Some((ids.0, ids.1, 0)));
+        assert_eq!(format_traceparent((ids.0, ids.1, 0)), unsampled);
+        assert_eq!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra"),
+            Some(ids)
+        );
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(parse_traceparent(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn traceparent_carriers() {
+        let ids = Some((0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, 1));
+        assert_eq!(
+            traceparent_from_trace_obj(
+                r#"{"traceparent": "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", "requestId": "r"}"#
+            ),
+            ids
+        );
+        assert_eq!(traceparent_from_trace_obj(r#"{"requestId": "r"}"#), None);
+        assert_eq!(traceparent_from_trace_obj("not json"), None);
+
+        assert_eq!(
+            traceparent_from_sql_comment(
+                "/* other */ SELECT 1 /*action='run',traceparent='00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01'*/"
+            ),
+            ids
+        );
+        assert_eq!(
+            traceparent_from_sql_comment(
+                "SELECT 'traceparent=00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01'"
+            ),
+            None
+        );
+        assert_eq!(traceparent_from_sql_comment("SELECT 1 /* unclosed"), None);
+    }
+
+    #[test]
+    fn spans_continue_remote_trace() {
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let helper = TracingHelperImpl::new();
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(helper.trace_and_span_id(), None);
+            assert!(helper.span_from_existing_trace(None).is_none());
+
+            let parent = (0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, 1);
+            let span = helper.span_from_existing_trace(Some(parent)).unwrap();
+            let (trace_id, span_id, flags) = span.in_scope(|| helper.trace_and_span_id()).unwrap();
+            assert_eq!(trace_id, parent.0);
+            assert_ne!(span_id, parent.1);
+            assert_eq!(flags, 1);
+
+            let child = span.in_scope(|| tracing::info_span!("child"));
+            let child_ids = child.in_scope(|| helper.trace_and_span_id()).unwrap();
+            assert_eq!(child_ids.0, parent.0);
+            assert_ne!(child_ids.1, span_id);
+            assert_eq!(child_ids.2, 1);
+
+            // Unsampled trace stays unsampled
+            let span = helper
+                .span_from_existing_trace(Some((parent.0, parent.1, 0)))
+                .unwrap();
+            let ids = span.in_scope(|| helper.trace_and_span_id()).unwrap();
+            assert_eq!((ids.0, ids.2), (parent.0, 0));
+        });
+    }
+}
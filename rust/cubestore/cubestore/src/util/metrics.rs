@@ -14,6 +14,11 @@
 //!
 //! Note that misconfiguration (invalid port, address, etc) can cause metric updates to be silently
 //! ignored. This is by design to avoid interrupting normal operation.
+//!
+//! In `prometheus` mode nothing is sent over UDP. Metrics are aggregated in-process by the
+//! [registry] instead and exposed for scraping on the status HTTP endpoint.
+pub mod registry;
+
 use crate::CubeError;
 use std::net::ToSocketAddrs;
 use std::net::UdpSocket;
@@ -24,6 +29,8 @@ pub enum Compatibility {
     /// StatsD does not support distribution and histogram metric types.
     /// We replace them with timer in this mode, which provides similar functionality.
     StatsD,
+    /// Aggregate metrics in-process for Prometheus scrapes instead of sending them.
+    Prometheus,
 }
 /// Call once on application startup to initialize the metrics client.
 /// Will if called twice or metrics were reported before calling this function.
@@ -33,7 +40,11 @@ pub fn init_metrics(
     mode: Compatibility,
     constant_tags: Vec<String>,
 ) {
-    global_sink::init(bind_addr, server_addr, mode, constant_tags).unwrap()
+    if mode == Compatibility::Prometheus {
+        registry::init(constant_tags)
+    } else {
+        global_sink::init(bind_addr, server_addr, mode, constant_tags).unwrap()
+    }
 }
 
 pub const fn counter(name: &'static str) -> Counter {
@@ -74,9 +85,7 @@ impl Counter {
     }
 
     pub fn add_with_tags(&self, v: i64, tags: Option<&Vec<String>>) {
-        if let Some(s) = sink() {
-            s.send(&self.metric, v, tags)
-        }
+        self.metric.send(v, tags)
     }
 
     pub fn increment(&self) {
@@ -94,9 +103,7 @@ impl IntMetric {
     }
 
     pub fn report_with_tags(&self, v: i64, tags: Option<&Vec<String>>) {
-        if let Some(s) = sink() {
-            s.send(&self.metric, v, tags)
-        }
+        self.metric.send(v, tags)
     }
 }
 
@@ -120,6 +127,14 @@ impl Metric {
     const fn new(name: &'static str, kind: MetricType) -> Metric {
         Metric { name, kind }
     }
+
+    fn send(&self, v: i64, tags: Option<&Vec<String>>) {
+        if let Some(s) = sink() {
+            s.send(self, v, tags)
+        } else if let Some(r) = registry::registry() {
+            r.record(self, v, tags)
+        }
+    }
 }
 
 struct Sink {
@@ -0,0 +1,266 @@
+//! In-process aggregation of metrics for Prometheus scrapes, used in `Compatibility::Prometheus`
+//! mode.
+//!
+//! Counters are summed, gauges keep the last reported value, histograms and distributions are
+//! aggregated into exponential buckets. Each distinct set of tags forms a separate series. Tags
+//! in `name:value` form become labels, tags without a value become labels with an empty value.
+use super::{Metric, MetricType};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Content type of [Registry::render] output.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of histogram buckets, powers of 4 from 1 to ~10^9.
+const BUCKET_BOUNDS: [i64; 16] = {
+    let mut bounds = [1; 16];
+    let mut i = 1;
+    while i < bounds.len() {
+        bounds[i] = bounds[i - 1] * 4;
+        i += 1;
+    }
+    bounds
+};
+
+type Labels = Vec<(String, String)>;
+
+pub struct Registry {
+    constant_labels: Labels,
+    families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+struct Family {
+    kind: &'static str,
+    series: BTreeMap<Labels, Series>,
+}
+
+enum Series {
+    Counter(i64),
+    Gauge(i64),
+    Histogram {
+        buckets: [u64; BUCKET_BOUNDS.len()],
+        count: u64,
+        sum: i64,
+    },
+}
+
+impl Registry {
+    pub fn new(constant_tags: Vec<String>) -> Registry {
+        Registry {
+            constant_labels: parse_tags(&constant_tags),
+            families: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(super) fn record(&self, m: &Metric, value: i64, tags: Option<&Vec<String>>) {
+        let labels = tags.map(|t| parse_tags(t)).unwrap_or_default();
+        let kind = match m.kind {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram | MetricType::Distribution => "histogram",
+        };
+        let mut families = self.families.lock().unwrap();
+        let family = families.entry(m.name).or_insert_with(|| Family {
+            kind,
+            series: BTreeMap::new(),
+        });
+        let series = family.series.entry(labels).or_insert_with(|| match m.kind {
+            MetricType::Counter => Series::Counter(0),
+            MetricType::Gauge => Series::Gauge(0),
+            MetricType::Histogram | MetricType::Distribution => Series::Histogram {
+                buckets: [0; BUCKET_BOUNDS.len()],
+                count: 0,
+                sum: 0,
+            },
+        });
+        match series {
+            Series::Counter(v) => *v += value,
+            Series::Gauge(v) => *v = value,
+            Series::Histogram {
+                buckets,
+                count,
+                sum,
+            } => {
+                // Buckets are stored non-cumulative and summed up on render.
+                if let Some(i) = BUCKET_BOUNDS.iter().position(|b| value <= *b) {
+                    buckets[i] += 1;
+                }
+                *count += 1;
+                *sum += value;
+            }
+        }
+    }
+
+    /// Renders all series in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let name = sanitize_name(name);
+            let name = if family.kind == "counter" {
+                format!("{}_total", name)
+            } else {
+                name
+            };
+            writeln!(out, "# TYPE {} {}", name, family.kind).unwrap();
+            for (labels, series) in family.series.iter() {
+                // Label names must be unique, tags override constant labels of the same name.
+                let labels = self
+                    .constant_labels
+                    .iter()
+                    .chain(labels.iter())
+                    .cloned()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .collect::<Labels>();
+                match series {
+                    Series::Counter(v) | Series::Gauge(v) => {
+                        writeln!(out, "{}{} {}", name, format_labels(&labels, None), v).unwrap()
+                    }
+                    Series::Histogram {
+                        buckets,
+                        count,
+                        sum,
+                    } => {
+                        let mut cumulative = 0;
+                        for (bound, n) in BUCKET_BOUNDS.iter().zip(buckets.iter()) {
+                            cumulative += n;
+                            let le = bound.to_string();
+                            let labels = format_labels(&labels, Some(&le));
+                            writeln!(out, "{}_bucket{} {}", name, labels, cumulative).unwrap();
+                        }
+                        let labels_inf = format_labels(&labels, Some("+Inf"));
+                        writeln!(out, "{}_bucket{} {}", name, labels_inf, count).unwrap();
+                        let labels = format_labels(&labels, None);
+                        writeln!(out, "{}_sum{} {}", name, labels, sum).unwrap();
+                        writeln!(out, "{}_count{} {}", name, labels, count).unwrap();
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn parse_tags(tags: &[String]) -> Labels {
+    let mut labels = tags
+        .iter()
+        .map(|t| match t.split_once(':') {
+            Some((name, value)) => (sanitize_name(name), value.to_string()),
+            None => (sanitize_name(t), String::new()),
+        })
+        .collect::<Labels>();
+    labels.sort();
+    labels
+}
+
+/// Replaces characters not allowed in Prometheus metric and label names, e.g. dots in `cs.*`.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if c.is_ascii_alphabetic() || c == '_' || (i > 0 && c.is_ascii_digit()) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .chain(le.map(|le| ("le", le)))
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+static GLOBAL_REGISTRY: OnceLock<Option<Registry>> = OnceLock::new();
+
+pub(super) fn init(constant_tags: Vec<String>) {
+    let mut called = false;
+    GLOBAL_REGISTRY.get_or_init(|| {
+        called = true;
+        Some(Registry::new(constant_tags))
+    });
+    if !called {
+        panic!("Metrics initialized twice or used before initialization");
+    }
+}
+
+/// The registry aggregating reported metrics. Only present in `Compatibility::Prometheus` mode.
+pub fn registry() -> &'static Option<Registry> {
+    GLOBAL_REGISTRY.get_or_init(|| None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::metrics::{counter, gauge, histogram};
+
+    #[test]
+    fn render() {
+        let r = Registry::new(vec!["node:router".to_string()]);
+        let c = counter("cs.test.count");
+        r.record(&c.metric, 2, None);
+        r.record(&c.metric, 3, None);
+        r.record(&c.metric, 1, Some(&vec!["command:select".to_string()]));
+        let g = gauge("cs.test.size");
+        r.record(&g.metric, 10, None);
+        r.record(&g.metric, 7, None);
+        let h = histogram("cs.test.ms");
+        r.record(&h.metric, 3, None);
+        r.record(&h.metric, 20, None);
+
+        let out = r.render();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"# TYPE cs_test_count_total counter"));
+        assert!(lines.contains(&"cs_test_count_total{node=\"router\"} 5"));
+        assert!(lines.contains(&"cs_test_count_total{command=\"select\",node=\"router\"} 1"));
+        assert!(lines.contains(&"# TYPE cs_test_size gauge"));
+        assert!(lines.contains(&"cs_test_size{node=\"router\"} 7"));
+        assert!(lines.contains(&"# TYPE cs_test_ms histogram"));
+        assert!(lines.contains(&"cs_test_ms_bucket{node=\"router\",le=\"1\"} 0"));
+        assert!(lines.contains(&"cs_test_ms_bucket{node=\"router\",le=\"4\"} 1"));
+        assert!(lines.contains(&"cs_test_ms_bucket{node=\"router\",le=\"64\"} 2"));
+        assert!(lines.contains(&"cs_test_ms_bucket{node=\"router\",le=\"+Inf\"} 2"));
+        assert!(lines.contains(&"cs_test_ms_sum{node=\"router\"} 23"));
+        assert!(lines.contains(&"cs_test_ms_count{node=\"router\"} 2"));
+    }
+
+    #[test]
+    fn tags_override_constant_labels() {
+        let r = Registry::new(vec!["node:router".to_string(), "env:prod".to_string()]);
+        let c = counter("cs.test.count");
+        r.record(
+            &c.metric,
+            1,
+            Some(&vec![
+                "node:worker-1".to_string(),
+                "node:worker-2".to_string(),
+            ]),
+        );
+
+        let out = r.render();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert!(
+            lines.contains(&"cs_test_count_total{env=\"prod\",node=\"worker-2\"} 1"),
+            "{}",
+            out
+        );
+    }
+}